@@ -0,0 +1,201 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Times the import, lot processing and export of generated test data (see `crptls::test_data`), comparing each
+//  median with that of a saved baseline, to catch performance regressions.
+//
+//      cargo bench -- [FILTER] [--iterations N] [--save-baseline] [--max-regression PCT]
+//
+//  Only the scenarios whose name contains FILTER (i.e., `100k`) are run.  A scenario's first run saves its medians as
+//  the baseline (in target/bench-baselines/), as does any run with --save-baseline.  With --max-regression, the run fails
+//  if any median is more than PCT percent slower than the baseline's.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crptls::core_functions::{self, ImportProcessParameters, Timings};
+use crptls::test_data::TestData;
+
+use cryptools::export::{export_all, export_csv};
+
+
+/// Each scenario's name, and the test data it processes.
+fn scenarios() -> Vec<(&'static str, TestData)> {
+    vec![
+        ("10k_rows_6_accounts", TestData { rows: 10_000, accounts: 6, ..Default::default() }),
+        ("100k_rows_6_accounts", TestData { rows: 100_000, accounts: 6, ..Default::default() }),
+        ("100k_rows_24_accounts", TestData { rows: 100_000, accounts: 24, ..Default::default() }),
+    ]
+}
+
+/// The phases timed: the `Timings` phases of the import, those of the rest of the processing, and the export of
+/// every report.
+const PHASES: [&str; 3] = ["import", "lot processing", "export"];
+
+struct Args {
+    filter: Option<String>,
+    iterations: usize,
+    save_baseline: bool,
+    max_regression: Option<f64>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    let args = parse_args()?;
+    let dir = std::env::temp_dir().join(format!("cryptools_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let result = bench(&args, &dir);
+    fs::remove_dir_all(&dir)?;
+
+    result
+}
+
+fn bench(args: &Args, dir: &std::path::Path) -> Result<(), Box<dyn Error>> {
+
+    let baseline_path = baseline_path();
+    let baseline = read_baseline(&baseline_path)?;
+    let mut medians: Vec<(String, Duration)> = Vec::new();
+    let mut regressions: Vec<String> = Vec::new();
+
+    for (name, test_data) in scenarios() {
+
+        if let Some(filter) = &args.filter { if !name.contains(filter.as_str()) { continue } }
+
+        let input = dir.join(format!("{}.csv", name));
+        test_data.write(BufWriter::new(File::create(&input)?))?;
+
+        let export_path = dir.join(name);
+        fs::create_dir_all(&export_path)?;
+        let settings = ImportProcessParameters { export_path, ..Default::default() };
+
+        let mut samples: Vec<[Duration; PHASES.len()]> = Vec::with_capacity(args.iterations);
+
+        for _ in 0..args.iterations {
+            samples.push(run_once(&input, &settings)?);
+        }
+
+        println!("{}", name);
+
+        for (idx, phase) in PHASES.iter().enumerate() {
+
+            let mut durations: Vec<Duration> = samples.iter().map(|sample| sample[idx]).collect();
+            durations.sort();
+            let median = durations[durations.len() / 2];
+            let key = format!("{}/{}", name, phase);
+
+            let change = baseline.get(&key).map(|base| {
+                let change = (median.as_secs_f64() / base.as_secs_f64() - 1.0) * 100.0;
+                if args.max_regression.is_some_and(|max| change > max) { regressions.push(format!("{} ({:+.1}%)", key, change)) }
+                format!("{:+.1}% against the baseline", change)
+            });
+
+            println!("  {:<16} median {:>10.3?}  (min {:.3?}, max {:.3?})  {}",
+                phase, median, durations[0], durations[durations.len() - 1], change.unwrap_or_default());
+
+            medians.push((key, median));
+        }
+    }
+
+    //  A scenario run for the first time has its medians saved, without changing those of the others
+    let to_save: Vec<(String, Duration)> = medians.into_iter()
+        .filter(|(key, _median)| args.save_baseline || !baseline.contains_key(key))
+        .collect();
+
+    if !to_save.is_empty() {
+        write_baseline(&baseline_path, &baseline, &to_save)?;
+        println!("Saved {} median(s) to the baseline, {}.", to_save.len(), baseline_path.display());
+    }
+
+    if !regressions.is_empty() {
+        return Err(format!("Slower than the baseline by more than {}%: {}.",
+            args.max_regression.unwrap(), regressions.join(", ")).into())
+    }
+
+    Ok(())
+}
+
+/// Imports, processes and exports `input`, returning the time each of the `PHASES` took.
+fn run_once(input: &std::path::Path, settings: &ImportProcessParameters) -> Result<[Duration; PHASES.len()], Box<dyn Error>> {
+
+    let mut timings = Timings::default();
+
+    let (raw_acct_map, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[input.to_path_buf()], settings, &mut timings
+    )?;
+
+    let start = Instant::now();
+    export_all::export(settings, &[], &raw_acct_map, &acct_map, &ars, &txns_map, &mut Timings::default())?;
+    export_csv::commit_written_reports()?;
+    let export = start.elapsed();
+
+    let sum = |test: &dyn Fn(&str) -> bool| -> Duration {
+        timings.phases.iter().filter(|(phase, _)| test(phase)).map(|(_, duration)| *duration).sum()
+    };
+
+    let durations = [sum(&|phase| phase.starts_with("Import")), sum(&|phase| !phase.starts_with("Import")), export];
+
+    Ok(durations)
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+
+    let mut args = Args { filter: None, iterations: 5, save_baseline: false, max_regression: None };
+    let mut argv = std::env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            //  Passed by `cargo bench`
+            "--bench" => {}
+            "--iterations" => args.iterations = argv.next().ok_or("--iterations needs a number")?.parse::<usize>()?.max(1),
+            "--save-baseline" => args.save_baseline = true,
+            "--max-regression" => args.max_regression = Some(argv.next().ok_or("--max-regression needs a percentage")?.parse()?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}.", arg).into()),
+            _ => args.filter = Some(arg),
+        }
+    }
+
+    Ok(args)
+}
+
+fn baseline_path() -> PathBuf {
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("target"));
+    target_dir.join("bench-baselines").join("processing.txt")
+}
+
+/// The saved medians, in nanoseconds, by `scenario/phase`, one `KEY NANOSECONDS` line each.
+fn read_baseline(path: &std::path::Path) -> Result<HashMap<String, Duration>, Box<dyn Error>> {
+
+    let Ok(contents) = fs::read_to_string(path) else { return Ok(HashMap::new()) };
+    let mut baseline = HashMap::new();
+
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, nanos) = line.rsplit_once(' ').ok_or_else(|| format!("{} has a malformed line: {}", path.display(), line))?;
+        baseline.insert(key.to_string(), Duration::from_nanos(nanos.parse()?));
+    }
+
+    Ok(baseline)
+}
+
+/// Saves `medians` over the `baseline`, keeping those of any scenario that wasn't run.
+fn write_baseline(
+    path: &std::path::Path,
+    baseline: &HashMap<String, Duration>,
+    medians: &[(String, Duration)],
+) -> Result<(), Box<dyn Error>> {
+
+    let mut saved = baseline.clone();
+    saved.extend(medians.iter().cloned());
+
+    let mut lines: Vec<String> = saved.iter().map(|(key, median)| format!("{} {}", key, median.as_nanos())).collect();
+    lines.sort();
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, lines.join("\n") + "\n")?;
+
+    Ok(())
+}
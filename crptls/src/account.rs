@@ -12,7 +12,7 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde_derive::{Serialize, Deserialize};
 
-use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind, TxType};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct RawAccount {
@@ -27,6 +27,16 @@ impl RawAccount {
 		self.ticker == compare
     }
 
+    /// The exchange (or other platform) this account belongs to.  With a `separator`, that is the part of the
+    /// account name before the first `separator` (i.e., "Kraken" for "Kraken: BTC" and a separator of ":").
+    /// Otherwise, it is the whole account name.
+    pub fn exchange_label(&self, separator: Option<&str>) -> String {
+        match separator {
+            Some(sep) => self.name.split(sep).next().unwrap_or(&self.name).trim().to_string(),
+            None => self.name.trim().to_string(),
+        }
+    }
+
     pub fn margin_string(&self) -> String {
         if self.is_margin {
             "Margin".to_string()
@@ -94,12 +104,16 @@ pub struct RawMarginPair (pub Weak<RawAccount>, pub Weak<RawAccount>);	    //  a
 
 #[derive(Clone, Debug)]
 pub struct Lot {
-	pub date_as_string: String,
+	pub date_as_string: Rc<str>,
 	pub date_of_first_mvmt_in_lot: NaiveDate,
 	pub date_for_basis_purposes: NaiveDate,
 	pub lot_number: u32,	//	Does NOT start at zero.  First lot is lot 1.
 	pub account_key: u16,
 	pub movements: RefCell<Vec<Rc<Movement>>>,
+	/// Whether the `Lot` was inherited (created by a `TxKind::Inherited` `Transaction`, or received from an inherited
+	/// `Lot` in a transfer or like-kind exchange).  It has a stepped-up basis and is always long-term, whatever its
+	/// `date_for_basis_purposes`.
+	pub inherited: bool,
 }
 
 impl Lot {
@@ -120,12 +134,30 @@ impl Lot {
 		self.movements.borrow().iter().for_each(|movement| amts += movement.cost_basis.get());
 		amts
 	}
+
+	/// The holding period of the `Lot` if disposed of on `disposal_date` (see `Term::from_dates()`), which is always
+	/// long-term if it was `inherited`.
+	pub fn term(&self, disposal_date: NaiveDate, long_term_days: i64) -> Term {
+		if self.inherited { Term::LT } else { Term::from_dates(self.date_for_basis_purposes, disposal_date, long_term_days) }
+	}
+
+	/// The `Lot`'s identifier in the reports (see `lot_id()`).
+	pub fn id(&self) -> String {
+		lot_id(self.account_key, self.lot_number)
+	}
+}
+
+/// The identifier of lot `lot_number` of account `account_key` in the reports, i.e., `A2-L3`.  It's the same from one
+/// run of the same input to the next, as accounts are numbered by their column and lots in the order they're created,
+/// so it ties the lot reports to the gains reports (and to the journals, whose lot labels it is).
+pub fn lot_id(account_key: u16, lot_number: u32) -> String {
+	format!("A{}-L{}", account_key, lot_number)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Movement {
 	pub amount: Decimal,
-	pub date_as_string: String,
+	pub date_as_string: Rc<str>,
 	pub date: NaiveDate,
 	pub transaction_key: u32,
 	pub action_record_key: u32,
@@ -136,6 +168,11 @@ pub struct Movement {
 	pub proceeds: Cell<Decimal>,	//	Initialized with 0. Set in add_proceeds_to_movements()
     pub proceeds_lk: Cell<Decimal>,
     pub cost_basis_lk: Cell<Decimal>,
+    /// Initialized with 0. Set in add_cost_basis_with_wash_sales(): the disallowed loss (positive) of a wash sale, or,
+    /// for the acquisition of its replacement `Lot`, that same loss (negative), which was added to the cost basis.
+    /// It is part of the gain or loss, so a wash sale's loss is reduced and the acquisition still nets to zero.  Also
+    /// set in apply_adjusted_cost_base() for a superficial loss, whose denied loss (positive) is added to the ACB instead.
+    pub wash_sale_adjustment: Cell<Decimal>,
 }
 
 impl Movement {
@@ -191,11 +228,11 @@ impl Movement {
 	}
 
 	pub fn get_lk_gain_or_loss(&self) -> Decimal {
-		self.proceeds_lk.get() + self.cost_basis_lk.get()
+		self.proceeds_lk.get() + self.cost_basis_lk.get() + self.wash_sale_adjustment.get()
 	}
 
     pub fn get_orig_gain_or_loss(&self) -> Decimal {
-		self.proceeds.get() + self.cost_basis.get()
+		self.proceeds.get() + self.cost_basis.get() + self.wash_sale_adjustment.get()
 	}
 
 	/// This function is only called during export operations.  In addition, this will
@@ -205,7 +242,8 @@ impl Movement {
 		&self,
 		acct_map: &HashMap<u16, Account>,
 		ar_map: &HashMap<u32, ActionRecord>,
-		txns_map: &HashMap<u32, Transaction>
+		txns_map: &HashMap<u32, Transaction>,
+		long_term_days: i64,
 	) -> Term {
 
 		let ar = ar_map.get(&self.action_record_key).unwrap();
@@ -219,32 +257,18 @@ impl Movement {
 				// if there was like-kind treatment, the basis date may be before the `transaction` date.
 				let txn = txns_map.get(&self.transaction_key).unwrap();
 				if txn.action_record_idx_vec.len() == 2 {
-					let lot_date_for_basis_purposes = lot.date_for_basis_purposes;
-					if self.date.signed_duration_since(lot_date_for_basis_purposes) > chrono::Duration::days(365) {
-						return Term::LT
-					}
-					return Term::ST
+					return lot.term(self.date, long_term_days)
 				}
 
 				// For a single-`action record` `transaction`, term is meaningless, but it is being shown
 				// in the context of the holding period, in the event it were sold "today".
 				let today: NaiveDate = chrono::Local::now().naive_utc().date();
-				if today.signed_duration_since(lot.date_for_basis_purposes) > chrono::Duration::days(365) {
-					Term::LT
-				}
-				else {
-					Term::ST
-				}
+				lot.term(today, long_term_days)
 			}
 
 			Polarity::Outgoing => {
 
-				let lot_date_for_basis_purposes = lot.date_for_basis_purposes;
-
-                if self.date.signed_duration_since(lot_date_for_basis_purposes) > chrono::Duration::days(365) {
-					return Term::LT
-				}
-				Term::ST
+				lot.term(self.date, long_term_days)
 			}
 		}
 	}
@@ -260,6 +284,8 @@ impl Movement {
 
 		let txn = txns_map.get(&self.transaction_key).expect("Couldn't get txn. Tx num invalid?");
 
+		if txn.kind == TxKind::DerivativePnl { return Ok(dec!(0)) }	//  A derivative gain (see `derivatives`)
+
 		match txn.transaction_type(ar_map, raw_accts, acct_map)? {
 
 			TxType::Flow => {
@@ -286,6 +312,8 @@ impl Movement {
 
 		let txn = txns_map.get(&self.transaction_key).expect("Couldn't get txn. Tx num invalid?");
 
+		if txn.kind == TxKind::DerivativePnl { return Ok(dec!(0)) }	//  A derivative loss (see `derivatives`)
+
 		match txn.transaction_type(ar_map, raw_accts, acct_map)? {
 
 			TxType::Flow => {
@@ -349,6 +377,16 @@ pub enum Term {
 
 impl Term {
 
+    /// The holding period of something acquired (for basis purposes) on `basis_date` and disposed of on
+    /// `disposal_date`. It is long-term if held for more than `long_term_days` (365, i.e., one year, by default).
+    pub fn from_dates(basis_date: NaiveDate, disposal_date: NaiveDate, long_term_days: i64) -> Term {
+        if disposal_date.signed_duration_since(basis_date) > chrono::Duration::days(long_term_days) {
+            Term::LT
+        } else {
+            Term::ST
+        }
+    }
+
     pub fn abbr_string(&self) -> String {
         match *self {
             Term::LT => "LT".to_string(),
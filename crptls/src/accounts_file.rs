@@ -0,0 +1,423 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::account::RawAccount;
+use crate::csv_import_accts_txns::OPTIONAL_COLUMNS;
+
+
+/// An account described in the accounts file, rather than (or as well as) in the header rows of the CSV Input File.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountMetadata {
+    pub account_num: u16,
+    pub name: String,
+    pub ticker: String,
+    pub is_margin: bool,
+    /// The wallet address (or any other identifier of the account, such as an exchange's account number), if any.
+    pub address: Option<String>,
+    /// The entity (i.e., `LLC`) the account belongs to, if any (see `entities`).
+    pub entity: Option<String>,
+}
+
+/// The accounts file: a CSV with a header row, then one row per account, with the columns `account` (its number),
+/// `name` and `ticker`, and optionally `margin` (yes or no, and no where blank), `address` and `entity`, i.e.:
+///
+/// ```text
+/// account,name,ticker,margin,address,entity
+/// 1,Bank,USD,no,,
+/// 2,Coinbase,BTC,no,,
+/// 3,Ledger,BTC,no,bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq,LLC
+/// ```
+///
+/// With the usual CSV Input File, each account must be the same as the file's account of that number (but for its
+/// name).  With a file in the row format or the long schema (see `InputSchema`), these are its accounts (numbered as
+/// they are here), and any other it names is created as it's encountered.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountsFile {
+    /// In order of their numbers: 1, 2, 3...
+    pub accounts: Vec<AccountMetadata>,
+}
+
+impl AccountsFile {
+
+    pub fn from_file(path: &Path) -> Result<AccountsFile, Box<dyn Error>> {
+        AccountsFile::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader(input: impl Read) -> Result<AccountsFile, Box<dyn Error>> {
+
+        let mut rdr = csv::ReaderBuilder::new().has_headers(true).flexible(true).from_reader(input);
+        let headers = rdr.headers()?.clone();
+
+        let find = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+        let require = |name: &str| find(name).ok_or_else(|| format!("The accounts file has no `{}` column.", name));
+
+        let account_col = require("account")?;
+        let name_col = require("name")?;
+        let ticker_col = require("ticker")?;
+        let margin_col = find("margin");
+        let address_col = find("address");
+        let entity_col = find("entity");
+
+        let mut accounts: Vec<AccountMetadata> = Vec::new();
+
+        for (idx, result) in rdr.records().enumerate() {
+
+            let record = result?;
+            let row = idx + 2;
+            let field = |col: Option<usize>| col.and_then(|col| record.get(col)).unwrap_or("").trim().to_string();
+
+            if record.iter().all(|field| field.trim().is_empty()) { continue }
+
+            let account_num = field(Some(account_col)).parse::<u16>()
+                .map_err(|_| format!("Row {} of the accounts file: the account ({}) must be a number.", row, field(Some(account_col))))?;
+            let name = field(Some(name_col));
+            let ticker = field(Some(ticker_col));
+
+            if name.is_empty() || ticker.is_empty() {
+                return Err(format!("Row {} of the accounts file: account {} must have a name and a ticker.", row, account_num).into())
+            }
+
+            let is_margin = match field(margin_col).to_lowercase().as_str() {
+                "" | "no" | "non" | "false" => false,
+                "yes" | "margin" | "true" => true,
+                other => return Err(format!("Row {} of the accounts file: the margin value ({}) must be yes or no.", row, other).into()),
+            };
+
+            let address = Some(field(address_col)).filter(|address| !address.is_empty());
+            let entity = Some(field(entity_col)).filter(|entity| !entity.is_empty());
+
+            accounts.push(AccountMetadata { account_num, name, ticker, is_margin, address, entity });
+        }
+
+        accounts.sort_by_key(|acct| acct.account_num);
+
+        for (idx, acct) in accounts.iter().enumerate() {
+            if acct.account_num as usize != idx + 1 {
+                return Err(format!("The accounts in the accounts file must be numbered 1, 2, 3..., each once, but \
+                    account {} is where account {} should be.", acct.account_num, idx + 1).into())
+            }
+        }
+
+        Ok(AccountsFile { accounts })
+    }
+
+    pub fn get(&self, account_num: u16) -> Option<&AccountMetadata> {
+        self.accounts.get((account_num as usize).checked_sub(1)?)
+    }
+
+    /// The address of account `account_num`, if it has one.
+    pub fn address(&self, account_num: u16) -> Option<&str> {
+        self.get(account_num).and_then(|acct| acct.address.as_deref())
+    }
+
+    /// An error naming each account of the accounts file that isn't the same (by its ticker and margin setting) as
+    /// the imported account of its number, or that wasn't imported at all.
+    pub(crate) fn check_accounts(&self, raw_acct_map: &HashMap<u16, RawAccount>) -> Result<(), Box<dyn Error>> {
+
+        let mut problems: Vec<String> = Vec::new();
+
+        for acct in self.accounts.iter() {
+            match raw_acct_map.get(&acct.account_num) {
+                None => problems.push(format!("account {} ({}) isn't in the CSV Input File", acct.account_num, acct.name)),
+                Some(raw_acct) if raw_acct.ticker != acct.ticker || raw_acct.is_margin != acct.is_margin => {
+                    problems.push(format!("account {} is {} {} ({}), but {} {} ({}) in the CSV Input File",
+                        acct.account_num, acct.name, acct.ticker, margin_string(acct.is_margin),
+                        raw_acct.name, raw_acct.ticker, margin_string(raw_acct.is_margin)))
+                }
+                Some(_raw_acct) => {}
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(format!("The accounts file doesn't match the CSV Input File: {}.", problems.join("; ")).into())
+        }
+
+        Ok(())
+    }
+
+    /// Account `field` of a row of the row format, with the `ticker` of that row (blank, if not given): the account
+    /// of that number, or else the only one of that name (and the `ticker`, if given).
+    fn find(&self, field: &str, ticker: &str) -> Result<Option<&AccountMetadata>, String> {
+
+        if let Ok(account_num) = field.parse::<u16>() {
+            return match self.get(account_num) {
+                Some(acct) if ticker.is_empty() || acct.ticker.eq_ignore_ascii_case(ticker) => Ok(Some(acct)),
+                Some(acct) => Err(format!("account {} is {}, not {}", account_num, acct.ticker, ticker)),
+                None => Err(format!("there's no account {} in the accounts file", account_num)),
+            }
+        }
+
+        let matching: Vec<&AccountMetadata> = self.accounts.iter()
+            .filter(|acct| acct.name.eq_ignore_ascii_case(field))
+            .filter(|acct| ticker.is_empty() || acct.ticker.eq_ignore_ascii_case(ticker))
+            .collect();
+
+        match matching.len() {
+            0 | 1 => Ok(matching.first().copied()),
+            _ => Err(format!("the accounts file has more than one account named {}, so its ticker must be given", field)),
+        }
+    }
+}
+
+/// A transaction of a CSV with a single header row (in the row format, or the long schema), to be written in the
+/// layout of a CSV Input File by `write_input_file()`.
+pub(crate) struct RowTxn {
+    /// The row of the file it's from (or its first row), for errors.
+    pub(crate) row: usize,
+    pub(crate) date: String,
+    pub(crate) proceeds: String,
+    pub(crate) memo: String,
+    /// The values of its optional columns, in the order of the `optional_cols` of `write_input_file()`.
+    pub(crate) optional: Vec<String>,
+    pub(crate) legs: Vec<Leg>,
+}
+
+/// A quantity of a `RowTxn`, and the account it moves: its number or name, and its ticker (blank, if not given).
+pub(crate) struct Leg {
+    pub(crate) account: String,
+    pub(crate) ticker: String,
+    pub(crate) quantity: Decimal,
+}
+
+/// Parses the quantity `field` of a `Leg` (i.e., `1,000.5`).
+pub(crate) fn parse_quantity(field: &str) -> Result<Decimal, String> {
+    let quantity = field.trim().replace(',', "");
+    Decimal::from_str(&quantity).or_else(|_| Decimal::from_scientific(&quantity))
+        .map_err(|_| format!("the quantity ({}) isn't a number", field.trim()))
+}
+
+fn margin_string(is_margin: bool) -> &'static str {
+    if is_margin { "margin" } else { "non-margin" }
+}
+
+/// Converts a CSV in the row format, with a single header row, into the layout of a CSV Input File.  Its columns (in
+/// any order, and identified by their header) are `txDate`, `proceeds` and `memo`, any of the optional columns of a CSV
+/// Input File (i.e., `txType` or `tags`), and, for each account the transaction moves, an `account` column (its
+/// number or name), an optional `ticker` column, and a `quantity` column, repeated for as many accounts as a
+/// transaction may move (i.e., twice, for a trade).  Accounts are found or created as in `write_input_file()`, which
+/// writes the CSV Input File to `output`.
+pub fn rows_to_input_file<W: Write + 'static>(
+    input: impl Read,
+    accounts_file: Option<&AccountsFile>,
+    output: W,
+) -> Result<W, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new().has_headers(true).flexible(true).from_reader(input);
+    let headers = Headers::new(rdr.headers()?, "the file in the row format");
+
+    let date_col = headers.column("txDate")?.ok_or("The file in the row format has no txDate column.")?;
+    let proceeds_col = headers.column("proceeds")?;
+    let memo_col = headers.column("memo")?;
+    let optional_cols = headers.optional_columns()?;
+
+    let account_cols = headers.columns_named("account");
+    let ticker_cols = headers.columns_named("ticker");
+    let quantity_cols = headers.columns_named("quantity");
+
+    if account_cols.is_empty() || account_cols.len() != quantity_cols.len() {
+        return Err(format!("The file in the row format must have an account and a quantity column for each account a \
+            transaction moves, but it has {} account column(s) and {} quantity column(s).", account_cols.len(), quantity_cols.len()).into())
+    }
+    if !ticker_cols.is_empty() && ticker_cols.len() != account_cols.len() {
+        return Err(format!("The file in the row format has {} ticker column(s), but {} account column(s). Give a ticker \
+            column for each account column, or none.", ticker_cols.len(), account_cols.len()).into())
+    }
+
+    let mut txns: Vec<RowTxn> = Vec::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;
+        let field = |col: Option<usize>| col.and_then(|col| record.get(col)).unwrap_or("").trim().to_string();
+
+        let mut legs: Vec<Leg> = Vec::new();
+
+        for (leg, (account_col, quantity_col)) in account_cols.iter().zip(quantity_cols.iter()).enumerate() {
+
+            let quantity = field(Some(*quantity_col));
+
+            if quantity.is_empty() { continue }
+
+            legs.push(Leg {
+                account: field(Some(*account_col)),
+                ticker: field(ticker_cols.get(leg).copied()),
+                quantity: parse_quantity(&quantity).map_err(|e| format!("Row {} of the file in the row format: {}.", row, e))?,
+            });
+        }
+
+        txns.push(RowTxn {
+            row,
+            date: field(Some(date_col)),
+            proceeds: field(proceeds_col),
+            memo: field(memo_col),
+            optional: optional_cols.iter().map(|(_name, col)| field(Some(*col))).collect(),
+            legs,
+        });
+    }
+
+    write_input_file(&txns, &optional_cols, accounts_file, "the file in the row format", output)
+}
+
+/// The header row of a CSV with a single header row, whose columns are identified by their header (ignoring case).
+pub(crate) struct Headers {
+    headers: Vec<String>,
+    /// What the file is, for errors (i.e., `the file in the row format`).
+    description: &'static str,
+}
+
+impl Headers {
+
+    pub(crate) fn new(headers: &csv::StringRecord, description: &'static str) -> Headers {
+        let headers = headers.iter().map(|header| header.trim().trim_start_matches('\u{feff}').to_string()).collect();
+        Headers { headers, description }
+    }
+
+    pub(crate) fn columns_named(&self, name: &str) -> Vec<usize> {
+        self.headers.iter().enumerate()
+            .filter(|(_idx, header)| header.eq_ignore_ascii_case(name))
+            .map(|(idx, _header)| idx)
+            .collect()
+    }
+
+    /// The column `name`, if there is one.
+    pub(crate) fn column(&self, name: &str) -> Result<Option<usize>, Box<dyn Error>> {
+        match self.columns_named(name).as_slice() {
+            [] => Ok(None),
+            [col] => Ok(Some(*col)),
+            _ => Err(format!("{} has more than one {} column.", capitalized(self.description), name).into()),
+        }
+    }
+
+    /// Each of the optional columns of a CSV Input File the file has, with its column.
+    pub(crate) fn optional_columns(&self) -> Result<Vec<(&'static str, usize)>, Box<dyn Error>> {
+
+        let mut optional_cols: Vec<(&str, usize)> = Vec::new();
+
+        for name in OPTIONAL_COLUMNS.iter() {
+            if let Some(col) = self.column(name)? { optional_cols.push((name, col)) }
+        }
+
+        Ok(optional_cols)
+    }
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// Writes the `txns` in the layout of a CSV Input File, with the `optional_cols` after `memo`.  Each `Leg`'s account
+/// is found in the `accounts_file` (by its number, or else by its name and, if given, ticker), or else is the account
+/// of that name and ticker created when first encountered (numbered after those in the file, with no margin), so the
+/// ticker must then be given.  Two `Leg`s of a transaction in the same account are added together.  It's written to
+/// `output`.
+pub(crate) fn write_input_file<W: Write + 'static>(
+    txns: &[RowTxn],
+    optional_cols: &[(&str, usize)],
+    accounts_file: Option<&AccountsFile>,
+    description: &str,
+    output: W,
+) -> Result<W, Box<dyn Error>> {
+
+    let empty = AccountsFile::default();
+    let accounts_file = accounts_file.unwrap_or(&empty);
+
+    let mut accounts: Vec<RawAccount> = accounts_file.accounts.iter().map(|acct| RawAccount {
+        account_num: acct.account_num,
+        name: acct.name.clone(),
+        ticker: acct.ticker.clone(),
+        is_margin: acct.is_margin,
+    }).collect();
+    let known_accounts = accounts.len();
+
+    //  Each transaction's quantities, by the index of their account
+    let mut quantities_by_txn: Vec<Vec<(usize, Decimal)>> = Vec::with_capacity(txns.len());
+
+    for txn in txns.iter() {
+
+        let mut quantities: Vec<(usize, Decimal)> = Vec::new();
+
+        for leg in txn.legs.iter() {
+
+            let (account, ticker) = (leg.account.as_str(), leg.ticker.as_str());
+
+            if account.is_empty() {
+                return Err(format!("Row {} of {} has a quantity ({}) without an account.", txn.row, description, leg.quantity).into())
+            }
+
+            let acct_idx = match accounts_file.find(account, ticker).map_err(|e| format!("Row {} of {}: {}.", txn.row, description, e))? {
+                Some(acct) => acct.account_num as usize - 1,
+                None => match accounts.iter().skip(known_accounts).position(|acct| acct.name == account && acct.ticker == ticker) {
+                    Some(position) => known_accounts + position,
+                    None if ticker.is_empty() => {
+                        return Err(format!("Row {} of {}: account {} isn't in the accounts file, so its ticker must be \
+                            given, for it to be created.", txn.row, description, account).into())
+                    }
+                    None => {
+                        accounts.push(RawAccount {
+                            account_num: accounts.len() as u16 + 1,
+                            name: account.to_string(),
+                            ticker: ticker.to_string(),
+                            is_margin: false,
+                        });
+                        accounts.len() - 1
+                    }
+                },
+            };
+
+            match quantities.iter_mut().find(|(idx, _quantity)| *idx == acct_idx) {
+                Some((_idx, total)) => *total += leg.quantity,
+                None => quantities.push((acct_idx, leg.quantity)),
+            }
+        }
+
+        quantities_by_txn.push(quantities);
+    }
+
+    if accounts.len() > known_accounts {
+        log::info!("  Created {} account(s) not in the accounts file: {}.", accounts.len() - known_accounts,
+            accounts[known_accounts..].iter().map(|acct| format!("{} {}", acct.name, acct.ticker)).collect::<Vec<String>>().join(", "));
+    }
+
+    let mut wtr = csv::Writer::from_writer(output);
+
+    let account_header = |field: &dyn Fn(&RawAccount) -> String| -> Vec<String> {
+        (0..3 + optional_cols.len()).map(|_| "".to_string()).chain(accounts.iter().map(field)).collect()
+    };
+
+    let mut first_header = vec!["txDate".to_string(), "proceeds".to_string(), "memo".to_string()];
+    first_header.extend(optional_cols.iter().map(|(name, _col)| name.to_string()));
+    first_header.extend(accounts.iter().map(|acct| acct.account_num.to_string()));
+
+    wtr.write_record(&first_header)?;
+    wtr.write_record(account_header(&|acct| acct.name.clone()))?;
+    wtr.write_record(account_header(&|acct| acct.ticker.clone()))?;
+    wtr.write_record(account_header(&|acct| if acct.is_margin { "yes".to_string() } else { "no".to_string() }))?;
+
+    for (txn, quantities) in txns.iter().zip(quantities_by_txn.iter()) {
+
+        let mut row = vec![
+            txn.date.clone(),
+            if txn.proceeds.is_empty() { "0".to_string() } else { txn.proceeds.clone() },
+            txn.memo.clone(),
+        ];
+        row.extend(txn.optional.iter().cloned());
+        row.extend((0..accounts.len()).map(|acct_idx| {
+            quantities.iter().find(|(idx, _quantity)| *idx == acct_idx).map(|(_idx, quantity)| quantity.to_string()).unwrap_or_default()
+        }));
+
+        wtr.write_record(&row)?;
+    }
+
+    Ok(wtr.into_inner()?)
+}
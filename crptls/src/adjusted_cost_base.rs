@@ -0,0 +1,191 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crate::share_pooling;
+use crate::non_sale_disposals;
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// The number of days before and after a loss within which an acquisition makes it a superficial loss.
+const SUPERFICIAL_LOSS_DAYS: i64 = 30;
+
+/// A disposal (an outgoing `ActionRecord`) costed at the adjusted cost base of its currency.
+#[derive(Clone, Debug)]
+pub struct AcbDisposal {
+    pub tx_num: u32,
+    pub account_key: u16,
+    /// Positive.
+    pub amount: Decimal,
+    pub proceeds: Decimal,
+    /// The ACB of the amount disposed of (positive).
+    pub adjusted_cost_base: Decimal,
+    /// The part of the loss denied as a superficial loss (positive), which was added to the ACB of the holdings.
+    pub superficial_loss: Decimal,
+}
+
+/// An acquisition or disposal of a currency.
+struct Event {
+    tx_num: u32,
+    ar_num: u32,
+    date: NaiveDate,
+    /// Positive for an acquisition, negative for a disposal.
+    amount: Decimal,
+}
+
+/// Replaces the cost basis of every disposal with its share of the adjusted cost base (ACB), i.e., the average cost of
+/// everything held in the currency (across every non-margin `Account`) at the time.  The cost basis of all `Movement`s
+/// must already have been added.  A loss is a superficial loss to the extent the currency was acquired in the 30 days
+/// before or after it and is still held 30 days after it: that part of the loss is denied (see
+/// `Movement::wash_sale_adjustment`) and added to the ACB.  Transfers between the user's own `Account`s are ignored.
+/// Only disposals' cost basis changes; each `Lot` keeps its own.
+pub(crate) fn apply_adjusted_cost_base(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<AcbDisposal>, Box<dyn Error>> {
+
+    let mut currencies: HashMap<String, Vec<Event>> = HashMap::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            currencies.entry(raw_acct.ticker.clone()).or_default().push(Event {
+                tx_num: txn.tx_number,
+                ar_num: *ar_num,
+                date: txn.date,
+                amount: ar.amount,
+            });
+        }
+    }
+
+    let mut acb_disposals: Vec<AcbDisposal> = Vec::new();
+
+    for (ticker, events) in currencies.iter() {
+
+        let mut units = dec!(0);
+        let mut acb = dec!(0);
+
+        for event in events.iter() {
+
+            let ar = ars.get(&event.ar_num).unwrap();
+
+            if ar.direction() == Polarity::Incoming {
+                units += event.amount;
+                acb += ar.cost_basis_in_ar();
+                continue
+            }
+
+            let amount = -event.amount;
+
+            if amount > units {
+                return Err(format!("Txn {} disposed of {} {}, but only {} was held.", event.tx_num, amount, ticker, units).into())
+            }
+
+            let cost = if amount == units { acb } else { round_d128_1e2(&(acb * amount / units)) };
+            let proceeds = proceeds_of_disposal(event.tx_num, ar, ars, raw_acct_map, acct_map, txns_map)?;
+            let gain_or_loss = proceeds - cost;
+
+            units -= amount;
+            acb -= cost;
+
+            let mut superficial_loss = dec!(0);
+
+            //  A gift, donation or loss isn't a sale, so it can't be a superficial loss
+            let is_sale = !non_sale_disposals::is_non_sale(txns_map.get(&event.tx_num).unwrap().kind);
+
+            if gain_or_loss < dec!(0) && is_sale {
+
+                let window_start = event.date - Duration::days(SUPERFICIAL_LOSS_DAYS);
+                let window_end = event.date + Duration::days(SUPERFICIAL_LOSS_DAYS);
+
+                let acquired_in_window: Decimal = events.iter()
+                    .filter(|other| other.amount > dec!(0) && other.date >= window_start && other.date <= window_end)
+                    .map(|other| other.amount)
+                    .sum();
+                let held_at_window_end: Decimal = events.iter()
+                    .filter(|other| other.date <= window_end)
+                    .map(|other| other.amount)
+                    .sum();
+
+                let substituted = amount.min(acquired_in_window).min(held_at_window_end);
+
+                if substituted > dec!(0) {
+                    superficial_loss = round_d128_1e2(&(-gain_or_loss * substituted / amount));
+                    acb += superficial_loss;
+                }
+            }
+
+            share_pooling::set_cost_basis_of_disposal(ar, -cost, acct_map, txns_map);
+
+            if superficial_loss > dec!(0) {
+
+                let movements = ar.get_mvmts_in_ar_in_lot_date_order();
+                let mut unallocated = superficial_loss;
+
+                for (idx, mvmt) in movements.iter().enumerate() {
+                    let denied = if idx + 1 == movements.len() { unallocated }
+                        else { round_d128_1e2(&(superficial_loss * mvmt.amount / ar.amount)) };
+                    mvmt.wash_sale_adjustment.set(denied);
+                    unallocated -= denied;
+                }
+            }
+
+            acb_disposals.push(AcbDisposal {
+                tx_num: event.tx_num,
+                account_key: ar.account_key,
+                amount,
+                proceeds,
+                adjusted_cost_base: cost,
+                superficial_loss,
+            });
+        }
+    }
+
+    acb_disposals.sort_by_key(|disposal| disposal.tx_num);
+
+    Ok(acb_disposals)
+}
+
+/// The proceeds `add_proceeds_to_movements()` will give the `Movement`s of a disposal, which are none for a margin loss.
+fn proceeds_of_disposal(
+    tx_num: u32,
+    ar: &ActionRecord,
+    ars: &HashMap<u32, ActionRecord>,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Decimal, Box<dyn Error>> {
+
+    let txn = txns_map.get(&tx_num).unwrap();
+
+    if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::Flow && txn.action_record_idx_vec.len() == 2 {
+        return Ok(dec!(0))
+    }
+
+    let txn_proceeds = txn.proceeds_net_of_fee();
+
+    Ok(ar.get_mvmts_in_ar_in_lot_date_order().iter()
+        .map(|mvmt| round_d128_1e2(&(txn_proceeds * mvmt.amount / ar.amount)))
+        .sum())
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use rust_decimal_macros::dec;
+
+use crate::transaction::{Transaction, IncomeType};
+
+
+/// The basis of a `Lot` received in an airdrop or a hard fork (income with an `IncomeType::Airdrop` or
+/// `IncomeType::Fork`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AirdropBasis {
+    /// The `proceeds` (the fair market value when received) are income, and the basis of the `Lot`.
+    FairMarketValue,
+    /// The `Lot` has a basis of zero, and nothing is income when it's received, so the whole of the proceeds of a
+    /// later disposal is a gain.
+    Zero,
+}
+
+/// Whether `txn` is the receipt of an airdrop or a hard fork.
+pub fn is_airdrop_or_fork(txn: &Transaction) -> bool {
+    matches!(txn.income_type, Some(IncomeType::Airdrop) | Some(IncomeType::Fork))
+}
+
+/// Zeroes the `proceeds` of every airdrop and hard fork, so that each creates a `Lot` with a basis of zero and no
+/// income.  Returns the number of `Transaction`s zeroed.
+pub(crate) fn zero_airdrop_basis(txns_map: &mut HashMap<u32, Transaction>) -> usize {
+
+    let mut zeroed = 0;
+
+    for txn in txns_map.values_mut() {
+
+        if !is_airdrop_or_fork(txn) || txn.proceeds.is_zero() { continue }
+
+        txn.proceeds = dec!(0);
+        zeroed += 1;
+    }
+
+    zeroed
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+use crate::core_functions::ImportProcessParameters;
+use crate::csv_import_accts_txns;
+
+
+/// Writes the CSV Input File(s) (as imported, so converted or merged, if so set) to `wtr` as a single CSV Input File
+/// that may be attached to a bug report without giving away one's finances:
+///
+/// * every memo is blank, and each `payer` is renamed (`Payer 1`, `Payer 2`...), the same payer the same way;
+/// * each ticker other than the `home_currency` is renamed (`COIN1`, `COIN2`...), in the order its first account
+///   appears;
+/// * the quantities of each ticker (and the home currency amounts: proceeds, fees and the home currency accounts'
+///   quantities) are multiplied by a random whole factor, from 2 to 9, of its own.
+///
+/// A whole factor adds no decimal places, so every balance, transfer and disposal comes out as exactly as before, and
+/// each gain or loss is that of the original times the home currency's factor.  The factors aren't written anywhere.
+/// The dates, account names and other columns are unchanged.
+pub fn anonymize_input_files(
+    input_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+    wtr: impl Write,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut inputs = csv_import_accts_txns::open_input_files(input_file_paths, settings)?;
+
+    let input: Box<dyn Read> = if inputs.len() == 1 {
+        inputs.pop().unwrap().1
+    } else {
+        csv_import_accts_txns::merge_input_files(inputs, settings)?
+    };
+
+    anonymize(input, settings, wtr, &mut random_factor)
+}
+
+/// A whole number from 2 to 9, from the randomly seeded hasher of a `HashMap`.
+fn random_factor() -> u32 {
+    2 + (RandomState::new().build_hasher().finish() % 8) as u32
+}
+
+/// Anonymizes the CSV Input File read from `input`, as `anonymize_input_files()` does, with `factor` choosing each
+/// ticker's factor.
+pub(crate) fn anonymize(
+    input: impl Read,
+    settings: &ImportProcessParameters,
+    wtr: impl Write,
+    factor: &mut dyn FnMut() -> u32,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_reader(input);
+    let mut raw_acct_map: HashMap<u16, RawAccount> = HashMap::new();
+    let mut acct_map: HashMap<u16, Account> = HashMap::new();
+
+    let columns = csv_import_accts_txns::import_accounts(&mut rdr, settings, &mut raw_acct_map, &mut acct_map)?;
+    let header = rdr.headers()?.clone();
+
+    let mut tickers: HashMap<String, String> = HashMap::new();
+    let mut factors: HashMap<String, Decimal> = HashMap::new();
+    let home_factor = Decimal::from(factor());
+    factors.insert(settings.home_currency.clone(), home_factor);
+
+    //  Each account's renamed ticker and factor, in the order of the account columns
+    let mut accts: Vec<(&RawAccount, String, Decimal)> = Vec::with_capacity(raw_acct_map.len());
+
+    for num in 1..=raw_acct_map.len() {
+        let raw_acct = raw_acct_map.get(&(num as u16)).unwrap();
+        let ticker = match raw_acct.ticker == settings.home_currency {
+            true => raw_acct.ticker.clone(),
+            false => {
+                let next = format!("COIN{}", tickers.len() + 1);
+                tickers.entry(raw_acct.ticker.clone()).or_insert(next).clone()
+            }
+        };
+        let acct_factor = *factors.entry(raw_acct.ticker.clone()).or_insert_with(|| Decimal::from(factor()));
+        accts.push((raw_acct, ticker, acct_factor));
+    }
+
+    let mut csv_wtr = csv::Writer::from_writer(wtr);
+
+    //  The first header row is unchanged, and the others are blank but for the accounts
+    let blank: Vec<String> = vec![String::new(); columns.first_acct_col];
+    csv_wtr.write_record(&header)?;
+    csv_wtr.write_record(blank.iter().cloned().chain(accts.iter().map(|(acct, _, _)| acct.name.clone())))?;
+    csv_wtr.write_record(blank.iter().cloned().chain(accts.iter().map(|(_, ticker, _)| ticker.clone())))?;
+    csv_wtr.write_record(blank.iter().cloned().chain(accts.iter().map(|(acct, _, _)| {
+        if acct.is_margin { "yes".to_string() } else { "no".to_string() }
+    })))?;
+
+    let mut payers: HashMap<String, String> = HashMap::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 5;
+
+        let scale = |field: &str, by: Decimal| -> Result<String, Box<dyn Error>> {
+            let field = field.trim().replace(',', "");
+            if field.is_empty() { return Ok(field) }
+            let amount = field.parse::<Decimal>().map_err(|e| format!("Row {}: {} isn't a number: {}", row, field, e))?;
+            Ok((amount * by).normalize().to_string())
+        };
+
+        let mut fields: Vec<String> = Vec::with_capacity(record.len());
+
+        for (col, field) in record.iter().enumerate() {
+            let field = match col {
+                1 => scale(field, home_factor)?,
+                2 => String::new(),
+                _ if columns.fee == Some(col) => scale(field, home_factor)?,
+                _ if columns.payer == Some(col) && !field.trim().is_empty() => {
+                    let next = format!("Payer {}", payers.len() + 1);
+                    payers.entry(field.trim().to_string()).or_insert(next).clone()
+                }
+                _ if col >= columns.first_acct_col => match accts.get(col - columns.first_acct_col) {
+                    Some((_, _, acct_factor)) => scale(field, *acct_factor)?,
+                    None => field.to_string(),
+                },
+                _ => field.to_string(),
+            };
+            fields.push(field);
+        }
+
+        csv_wtr.write_record(&fields)?;
+    }
+
+    csv_wtr.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,198 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::core_functions::ImportProcessParameters;
+
+
+/// How often the balances of a `BalanceHistory` are taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BalanceInterval {
+    /// At the end of every day, from the first movement through the last.
+    Daily,
+    /// At the end of every month, from the month of the first movement through that of the last.
+    Monthly,
+}
+
+impl FromStr for BalanceInterval {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "daily" => Ok(BalanceInterval::Daily),
+            "monthly" => Ok(BalanceInterval::Monthly),
+            _ => Err(format!("The balance interval ({}) must be daily or monthly.", s)),
+        }
+    }
+}
+
+/// The amount of each asset held (across the reported non-margin accounts) at the end of each day or month of the
+/// history, for charting.  Only the movements are needed, not prices.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BalanceHistory {
+    /// The tickers, in order.
+    pub tickers: Vec<String>,
+    /// Each date the balances are taken, with the amount of each of the `tickers` held at the end of it.
+    pub balances: Vec<(NaiveDate, Vec<Decimal>)>,
+}
+
+impl BalanceHistory {
+
+    pub fn from_maps(
+        settings: &ImportProcessParameters,
+        interval: BalanceInterval,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+    ) -> BalanceHistory {
+
+        let mut changes_by_ticker: BTreeMap<String, BTreeMap<NaiveDate, Decimal>> = BTreeMap::new();
+
+        for (raw_acct, changes) in reported_accounts(settings, raw_acct_map, acct_map) {
+            let by_date = changes_by_ticker.entry(raw_acct.ticker.clone()).or_default();
+            for (date, change) in changes.into_iter() {
+                *by_date.entry(date).or_insert(dec!(0)) += change;
+            }
+        }
+
+        let dates: Vec<NaiveDate> = changes_by_ticker.values().flat_map(|by_date| by_date.keys().copied()).collect();
+        let (Some(first), Some(last)) = (dates.iter().min().copied(), dates.iter().max().copied()) else {
+            return BalanceHistory::default()
+        };
+
+        let tickers: Vec<String> = changes_by_ticker.keys().cloned().collect();
+        let mut held = vec![dec!(0); tickers.len()];
+        let mut balances: Vec<(NaiveDate, Vec<Decimal>)> = Vec::new();
+
+        let mut date = first;
+
+        loop {
+
+            for (idx, by_date) in changes_by_ticker.values().enumerate() {
+                if let Some(change) = by_date.get(&date) { held[idx] += *change }
+            }
+
+            let is_taken = match interval {
+                BalanceInterval::Daily => true,
+                BalanceInterval::Monthly => date == month_end(date) || date == last,
+            };
+
+            if is_taken {
+                balances.push((if interval == BalanceInterval::Monthly { month_end(date) } else { date }, held.clone()));
+            }
+
+            if date == last { break }
+            date = date + Days::new(1);
+        }
+
+        BalanceHistory { tickers, balances }
+    }
+}
+
+/// The highest balance of an `Account` during a (fiscal) year, as for reporting the maximum value of a foreign
+/// account (i.e., on an FBAR, once valued at the year-end rate).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighestBalance {
+    pub year: i32,
+    pub account_num: u16,
+    pub account: String,
+    pub ticker: String,
+    /// The highest balance at the end of any day of the year (including the balance held from the prior year).
+    pub highest: Decimal,
+    /// The first day the `highest` balance was held.
+    pub on: NaiveDate,
+    pub year_end: Decimal,
+}
+
+/// The highest balance of each reported non-margin `Account` in each (fiscal) year from its first movement's
+/// through the last movement's (of any `Account`), for each year it held anything.  In order of the year, then the
+/// account number.
+pub fn highest_balances(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Vec<HighestBalance> {
+
+    let accounts = reported_accounts(settings, raw_acct_map, acct_map);
+
+    let Some(last) = accounts.iter().filter_map(|(_raw_acct, changes)| changes.keys().next_back().copied()).max() else {
+        return Vec::new()
+    };
+
+    let mut highest: Vec<HighestBalance> = Vec::new();
+
+    for (raw_acct, changes) in accounts.iter() {
+
+        let Some(first) = changes.keys().next().copied() else { continue };
+        let mut balance = dec!(0);
+        let mut changes = changes.iter().peekable();
+
+        for year in settings.fiscal_year(first)..=settings.fiscal_year(last) {
+
+            let (start, end) = settings.fiscal_year_dates(year);
+            let mut year_highest = (balance, start);
+
+            while let Some((date, change)) = changes.next_if(|(date, _change)| **date <= end) {
+                balance += *change;
+                if balance > year_highest.0 { year_highest = (balance, *date) }
+            }
+
+            if year_highest.0 > dec!(0) {
+                highest.push(HighestBalance {
+                    year,
+                    account_num: raw_acct.account_num,
+                    account: raw_acct.name.clone(),
+                    ticker: raw_acct.ticker.clone(),
+                    highest: year_highest.0,
+                    on: year_highest.1,
+                    year_end: balance,
+                });
+            }
+        }
+    }
+
+    highest.sort_by_key(|balance| (balance.year, balance.account_num));
+    highest
+}
+
+/// Each reported non-margin `Account`, in order, with the net change in its balance on each date it has a movement.
+fn reported_accounts<'a>(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &'a HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Vec<(&'a RawAccount, BTreeMap<NaiveDate, Decimal>)> {
+
+    let mut accounts = Vec::new();
+
+    for j in 1..=acct_map.len() {
+
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        if raw_acct.is_margin || !settings.is_reported_account(raw_acct) { continue }
+
+        let mut changes: BTreeMap<NaiveDate, Decimal> = BTreeMap::new();
+
+        for lot in acct.list_of_lots.borrow().iter() {
+            for mvmt in lot.movements.borrow().iter() {
+                *changes.entry(mvmt.date).or_insert(dec!(0)) += mvmt.amount;
+            }
+        }
+
+        accounts.push((raw_acct, changes));
+    }
+
+    accounts
+}
+
+fn month_end(date: NaiveDate) -> NaiveDate {
+    let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1).unwrap().pred_opt().unwrap()
+}
@@ -2,47 +2,634 @@
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
 use std::path::PathBuf;
+use std::io::Cursor;
 use std::error::Error;
+use std::time::{Duration, Instant};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use chrono::NaiveDate;
+use chrono::{FixedOffset, NaiveDate};
+use rust_decimal::Decimal;
 
-use crate::account::{Account, RawAccount, Lot};
-use crate::transaction::{Transaction, ActionRecord};
-use crate::csv_import_accts_txns;
+use crate::account::{Account, RawAccount, Lot, Term};
+use crate::transaction::{Transaction, ActionRecord, IncomeType};
+use crate::csv_import_accts_txns::{self, InputFiles, InputSchema};
 use crate::import_cost_proceeds_etc;
 use crate::create_lots_mvmts;
 use crate::costing_method::InventoryCostingMethod;
+use crate::disposal_rounding::{self, DisposalRounding};
+use crate::dust_sweeps::{self, DustQuantities};
+use crate::synthetic_acquisitions::{self, SyntheticBasis};
+use crate::same_date_ordering::{self, SameDateOrder};
+use crate::specific_id::LotPicker;
+use crate::import_map::ImportMap;
+use crate::accounts_file::AccountsFile;
+use crate::entities::{self, Entities, EntityTransferTreatment};
+use crate::exclusions;
+use crate::fiat_costs::FiatCost;
+use crate::importers::Source;
+use crate::liquidity_pools::{self, PoolTreatment};
+use crate::lot_pooling::{self, LotPooling};
+use crate::run_state::{self, RunState};
+use crate::wash_sales;
+use crate::share_pooling;
+use crate::adjusted_cost_base;
+use crate::airdrops::{self, AirdropBasis};
+use crate::rebases::{self, RebaseTreatment};
+use crate::transfer_fees;
+use crate::transfer_matching;
+use crate::nfts;
+use crate::non_sale_disposals;
+use crate::stablecoins;
+use crate::token_aliases::{self, TokenAliases};
+use crate::dedupe::{DedupeField, DedupeMode, DEFAULT_DEDUPE_KEY};
+use crate::price_lookup::{self, PriceLookup};
+use crate::decimal_utils::{OutputPrecision, ReportLocale};
+use crate::report_language::ReportLanguage;
+use crate::csv_dialect::CsvDelimiter;
+use crate::fx_translation::{self, FxTranslation};
+use crate::report_filter::ReportFilter;
+use crate::summary;
+use crate::like_kind::LikeKindScope;
 
 
 /// `ImportProcessParameters` are determined from command-line args, environment variables, and/or wizard input from the user.
 /// They are the settings that allow the software to carry out the importing-from-csv of
 /// `Account`s and `Transaction`s, creation of `Lot`s and `Movement`s, addition of cost basis and proceeds
 /// to `Movement`s, and application of like-kind treatment, in a specific and automated fashion.
+#[derive(Clone)]
 pub struct ImportProcessParameters {
     pub input_file_date_separator: String,
     pub input_file_uses_iso_date_style: bool,
+    /// A chrono strftime format string (i.e., `%b %d, %Y`) for the `txDate` (and `settleDate`) column.  When set, it is
+    /// used instead of `input_file_date_separator` and `input_file_uses_iso_date_style`.
+    pub input_file_date_format: Option<String>,
+    /// The delimiter of the CSV Input File's fields, or `Auto` (default), to detect it (see `csv_dialect::detect()`).
+    pub csv_delimiter: CsvDelimiter,
+    /// The locale of the CSV Input File's numbers.  With one that has a decimal comma (i.e., `de`), `1.234,56` is read
+    /// as 1234.56.  Otherwise (default), a comma is taken to separate thousands.
+    pub input_locale: ReportLocale,
     pub home_currency: String,
     pub costing_method: InventoryCostingMethod,
+    /// The order in which same-date `Transaction`s are processed (see `SameDateOrder`).
+    pub same_date_order: SameDateOrder,
+    /// The user's (tax) timezone, as a UTC offset.  If set, the txDate and `txTime` of each row of the CSV Input File
+    /// with a `txTime` are converted to it, so a late-evening trade may fall on the next day (or in the next year).
+    pub timezone: Option<FixedOffset>,
+    /// A disposal is long-term if the `Lot` was held for more than this many days (see `Term::from_dates()`).
+    /// Defaults to 365.
+    pub long_term_days: i64,
     pub lk_treatment_enabled: bool,
     /// NaiveDate either from "1-1-1" (default and not to be used) or the actual date chosen (or passed in via env var)
     pub lk_cutoff_date: NaiveDate,
+    /// Which exchanges like-kind treatment applies to (see `LikeKindScope`).  By default, every exchange of two
+    /// non-home currencies through `lk_cutoff_date`.
+    pub lk_scope: LikeKindScope,
     pub lk_basis_date_preserved: bool,
+    /// When `true`, a `settleDate` in the CSV Input File (if the column is present and the field isn't blank) is used as
+    /// the basis date of acquired lots instead of the trade date.  Defaults to `false`.
+    pub settle_date_is_basis_date: bool,
+    /// When `true`, a row of the CSV Input File without any non-zero quantity doesn't become a `Transaction` (nor
+    /// create any `Lot` or `Movement`).  Instead, its memo (and its `proceeds`, if any) are appended to the memo of
+    /// the `Transaction` on the row before it.  Zero quantities in other rows are ignored.  Defaults to `false`.
+    pub zero_quantity_rows_are_annotations: bool,
     pub should_export: bool,
     pub export_path: PathBuf,
     pub journal_entry_export: bool,
+    /// Also write the journal entries as a ledger-cli/hledger journal (J2_Ledger_Journal.ledger) wherever
+    /// J1_Journal_Entries.txt is written.
+    pub ledger_export: bool,
+    /// Also write the journal entries as a Beancount journal (J3_Beancount_Journal.beancount) wherever
+    /// J1_Journal_Entries.txt is written.
+    pub beancount_export: bool,
+    /// Also write the journal entries as a general journal CSV for QuickBooks Online or Xero (J4_General_Journal.csv)
+    /// wherever J1_Journal_Entries.txt is written.
+    pub journal_csv_export: bool,
+    /// The journal entries' account names (or prefixes of them) and the accounts of the user's chart of accounts to
+    /// write instead, from the `--chart-of-accounts` file.  Empty for the default names.
+    pub chart_of_accounts: HashMap<String, String>,
+    /// The accounts the journal entries post gains, losses and income to (see `JournalAccounts`).
+    pub journal_accounts: JournalAccounts,
+    /// The costs paid in the home currency, from the `--costs` file (see `fiat_costs::import_costs()`), which are
+    /// expensed in the expense reports and journal entries, and netted against mining income in the mining P&L.
+    pub fiat_costs: Vec<FiatCost>,
+    /// With `--stdout`, the beginning of the file name of the one report to write to standard output (i.e., `C7_`),
+    /// instead of writing any to files.
+    pub stdout_report: Option<String>,
+    /// Prepend a UTF-8 byte order mark to exported CSV files, so Excel detects the encoding.
+    pub excel_bom: bool,
+    /// The format the reports made of rows (the C*.csv reports) are written in (see `TableFormat`).
+    pub table_format: TableFormat,
+    /// Split the Form 8949 report into short-term and long-term files for each account (or exchange label).
+    pub form_8949_per_account: bool,
+    /// When `true` (default), the home currency spent in a trade (i.e., the USD leg of a USD->BTC buy) is used as
+    /// the cost basis of the acquired asset. When `false`, the acquired asset is instead valued using the `proceeds`
+    /// column, as is done for crypto-to-crypto trades. Either way, the home currency leg never generates a gain.
+    pub home_curr_leg_is_basis: bool,
+    /// When set, same-day exchanges of dust into a single account, each with proceeds at or below this threshold,
+    /// are aggregated into one disposal on the Form 8949 report. This is for reporting only; gains are unchanged.
+    pub dust_threshold: Option<Decimal>,
+    /// The quantity of each ticker at or below which what a disposal leaves in an account is swept out of it, with its
+    /// cost basis written off, rather than left as near-empty lots (see `dust_sweeps`).
+    pub dust_quantities: DustQuantities,
+    /// When set, an account's exchange label is the part of its name before this separator (otherwise, the whole name).
+    pub exchange_label_separator: Option<String>,
+    /// When set, outgoing amounts with more precision than allowed are rounded before lot processing, so that
+    /// disposals close out the lots they draw from. Each rounding residual is printed.
+    pub disposal_rounding: Option<DisposalRounding>,
+    /// When set, a synthetic acquisition (valued as chosen) is inserted ahead of any transaction that would otherwise take
+    /// an account negative. Every synthetic acquisition is listed in its own report.
+    pub synthetic_acquisitions: Option<SyntheticBasis>,
+    /// When set, the CSV Input File is instead a CSV with a single header row, whose columns are as mapped.
+    pub import_map: Option<ImportMap>,
+    /// When set, the CSV Input File is instead an export in this exchange's format.
+    pub import_source: Option<Source>,
+    /// The shape of the CSV Input File (unless it's an export of the `import_source` or has an `import_map`).
+    pub input_schema: InputSchema,
+    /// When set, the accounts of the CSV Input File (see `AccountsFile`).
+    pub accounts_file: Option<AccountsFile>,
+    /// When `true`, the CSV Input File is read in large chunks, and any intermediate CSV (a converted or merged CSV
+    /// Input File) is written to a temporary file as it's converted or merged, rather than kept in memory.  A
+    /// decrypted CSV Input File is kept in memory regardless (see `spill_files::may_spill()`), and the transactions,
+    /// lots and movements always are.
+    pub low_memory: bool,
+    /// When set, the open lots of a prior run (or of an opening balances CSV) are carried forward ahead of the imported
+    /// transactions, which must all be dated after the state's `through` date.
+    pub resume_from: Option<RunState>,
+    /// When set, a loss on a disposal is disallowed (and added to the basis of the replacement lot) to the extent the
+    /// same currency was purchased within this many days before or after it. Each wash sale is printed and reported.
+    pub wash_sale_window: Option<i64>,
+    /// When set, the gain/loss, income, expense and journal entry reports cover only the `Transaction`s dated in this
+    /// fiscal year (see `fiscal_year()`), and their file names end in the year (see `tax_year_file_name()`).
+    pub tax_year: Option<i32>,
+    /// When `true`, those reports are instead exported once for each fiscal year with a `Transaction` in it.
+    pub split_by_tax_year: bool,
+    /// When `true`, the fee split off of a transfer between the user's own accounts (see `transfer_fees`) is a disposal
+    /// of the units spent on it, realizing a gain or loss.  When `false` (default), it is an expense of their basis.
+    pub transfer_fee_is_disposal: bool,
+    /// The tickers treated as cash equivalents (see `stablecoins`), whose disposals realize no gain or loss.  Any
+    /// other currency, including a fiat currency other than the home currency, has its own gains and losses.
+    pub stablecoins: Vec<String>,
+    /// If set, withdrawals and deposits of the same currency are paired into transfers between the user's own
+    /// `Account`s (see `transfer_matching`), this being the fraction of the units sent that may be lost to a fee.
+    pub transfer_match_tolerance: Option<Decimal>,
+    /// How a liquidity pool deposit or withdrawal is treated (see `liquidity_pools`).
+    pub pool_treatment: PoolTreatment,
+    /// Whether a disposal's cost basis is drawn only from the `Lot`s of its own `Account` (default) or from those of
+    /// its currency in any `Account` (see `lot_pooling`).
+    pub lot_pooling: LotPooling,
+    /// Whether an airdrop or a hard fork is income at its fair market value, which is then the basis of its `Lot`
+    /// (default), or creates a `Lot` with a basis of zero and no income (see `airdrops`).
+    pub airdrop_basis: AirdropBasis,
+    /// Whether an increase from a rebase is income as it accrues (default), or spreads the basis already held over
+    /// more units (see `rebases`).
+    pub rebase_treatment: RebaseTreatment,
+    /// Whether a transaction row duplicating an earlier one (by the `dedupe_key`'s fields) is imported as usual (default),
+    /// imported with a warning, or dropped (see `dedupe`).
+    pub dedupe: DedupeMode,
+    /// The fields compared in finding duplicate rows (see `dedupe::DEFAULT_DEDUPE_KEY`).
+    pub dedupe_key: Vec<DedupeField>,
+    /// Other headers the CSV Input File's columns may have, each mapped to the column it stands for (i.e., `Date` to
+    /// `txDate`; see `csv_import_accts_txns::parse_header_aliases()`).
+    pub header_aliases: HashMap<String, String>,
+    /// When set, an exchange of one ticker for another of the same asset (see `token_aliases`) carries its basis
+    /// and basis dates over, rather than being a disposal.
+    pub token_aliases: Option<TokenAliases>,
+    /// When set, the accounts are divided among entities (see `entities`), each of which gets its own set of reports.
+    pub entities: Option<Entities>,
+    /// How a transfer between the accounts of two entities is treated: carrying its basis over (default), or as a sale.
+    pub entity_transfers: EntityTransferTreatment,
+    /// When set, the `proceeds` of any `Transaction` missing them are filled in from historical prices (see
+    /// `price_lookup`), and any that couldn't be priced are printed.
+    pub price_lookup: Option<PriceLookup>,
+    /// Without `synthetic_acquisitions`, any `Transaction` that would take an account negative is listed (see
+    /// `synthetic_acquisitions::find_negative_balances()`) and processing stops.  When `true`, each is instead a
+    /// warning, and its shortfall is covered by a zero-basis synthetic acquisition.
+    pub lenient: bool,
+    /// When set, the corrections in this file (see `corrections::import_corrections()`), if it exists, are applied to
+    /// the CSV Input File as it is imported.  Corrections made in the print menu are saved to it.
+    pub corrections: Option<PathBuf>,
+    /// The lot selections file (see `specific_id::FileLotPicker`) that the `Lot`s of each disposal are read from under
+    /// specific identification, rather than asking the user.
+    pub lot_selections: Option<PathBuf>,
+    /// The numbers of the `Transaction`s (as imported) left out of processing, as if their rows weren't in the CSV
+    /// Input File (see `exclusions`).  Every later `Transaction` is renumbered.
+    pub excluded_txns: Vec<u32>,
+    /// The rows of the CSV Input File (as merged, if there are several) parked for review, which are left out as it is
+    /// imported, as if they weren't in it (see `needs_review::find_rows_needing_review()`).
+    pub parked_rows: Vec<usize>,
+    /// The decimal places (and rounding) of the home currency values and quantities in the reports.
+    pub output_precision: OutputPrecision,
+    /// A chrono strftime format string for the dates in the reports (see `report_date()`).
+    pub output_date_format: Option<String>,
+    /// The language of the text reports, the journal entries and the print menu (see `report_language`).
+    pub report_language: ReportLanguage,
+    /// The secondary home currencies (see `fx_translation`), each of which gets its own set of reports.
+    pub secondary_currencies: Vec<FxTranslation>,
+    /// When set, the home currency values are translated into this secondary currency before processing (see
+    /// `translated_settings()`).
+    pub translation: Option<FxTranslation>,
+    /// When set, the file name of each report begins with it (see `report_file_name()`).
+    pub file_name_prefix: Option<String>,
+    /// When set, the file name of each report ends in this date (see `report_file_name()`).
+    pub file_name_date: Option<NaiveDate>,
+    /// When `true`, a report may replace a file of the same name left by an earlier run.  When `false` (default),
+    /// that's an error, so an earlier run's reports aren't lost.
+    pub overwrite: bool,
+    /// Limits the movements in the reports to those of an account or asset, or of a date range (see `ReportFilter`).
+    pub report_filter: ReportFilter,
+    /// The month (1 through 12) the fiscal year begins in, by which the reports group by year and by quarter (see
+    /// `summary::fiscal_year()`), and by which a `tax_year` is chosen.  1 (default) for a calendar year.
+    pub fiscal_year_start_month: u32,
 }
 
-pub fn import_and_process_final(
-    input_file_path: PathBuf,
-    settings: &ImportProcessParameters,
-) -> Result<(
+impl Default for ImportProcessParameters {
+
+    /// The settings of a run without any environment variables or flags (as with `cryptools -a`), except that nothing
+    /// is exported.
+    fn default() -> Self {
+        ImportProcessParameters {
+            input_file_date_separator: "-".to_string(),
+            input_file_uses_iso_date_style: false,
+            input_file_date_format: None,
+            csv_delimiter: CsvDelimiter::Auto,
+            input_locale: ReportLocale::Plain,
+            home_currency: "USD".to_string(),
+            costing_method: InventoryCostingMethod::LIFObyLotCreationDate,
+            same_date_order: SameDateOrder::FileOrder,
+            timezone: None,
+            long_term_days: 365,
+            lk_treatment_enabled: false,
+            lk_cutoff_date: NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap(),
+            lk_scope: LikeKindScope::default(),
+            lk_basis_date_preserved: true,
+            settle_date_is_basis_date: false,
+            zero_quantity_rows_are_annotations: false,
+            should_export: false,
+            export_path: PathBuf::from("."),
+            journal_entry_export: false,
+            ledger_export: false,
+            beancount_export: false,
+            journal_csv_export: false,
+            chart_of_accounts: HashMap::new(),
+            journal_accounts: JournalAccounts::default(),
+            fiat_costs: Vec::new(),
+            stdout_report: None,
+            excel_bom: false,
+            table_format: TableFormat::Csv,
+            form_8949_per_account: false,
+            home_curr_leg_is_basis: true,
+            dust_threshold: None,
+            dust_quantities: DustQuantities::new(),
+            exchange_label_separator: None,
+            disposal_rounding: None,
+            synthetic_acquisitions: None,
+            import_map: None,
+            import_source: None,
+            input_schema: InputSchema::Wide,
+            accounts_file: None,
+            low_memory: false,
+            resume_from: None,
+            wash_sale_window: None,
+            tax_year: None,
+            split_by_tax_year: false,
+            transfer_fee_is_disposal: false,
+            stablecoins: Vec::new(),
+            transfer_match_tolerance: None,
+            pool_treatment: PoolTreatment::Swap,
+            lot_pooling: LotPooling::PerWallet,
+            airdrop_basis: AirdropBasis::FairMarketValue,
+            rebase_treatment: RebaseTreatment::Income,
+            dedupe: DedupeMode::Off,
+            dedupe_key: DEFAULT_DEDUPE_KEY.to_vec(),
+            header_aliases: HashMap::new(),
+            token_aliases: None,
+            entities: None,
+            entity_transfers: EntityTransferTreatment::Carryover,
+            price_lookup: None,
+            lenient: false,
+            corrections: None,
+            lot_selections: None,
+            excluded_txns: Vec::new(),
+            parked_rows: Vec::new(),
+            output_precision: OutputPrecision::default(),
+            output_date_format: None,
+            report_language: ReportLanguage::English,
+            secondary_currencies: Vec::new(),
+            translation: None,
+            file_name_prefix: None,
+            file_name_date: None,
+            overwrite: false,
+            report_filter: ReportFilter::default(),
+            fiscal_year_start_month: 1,
+        }
+    }
+}
+
+impl ImportProcessParameters {
+
+    /// Whether a `Transaction` dated `date` belongs in the reports limited to the `tax_year` (always, if none is set).
+    pub fn is_in_tax_year(&self, date: NaiveDate) -> bool {
+        self.tax_year.is_none_or(|year| self.fiscal_year(date) == year)
+    }
+
+    /// The fiscal year of `date`, named for the calendar year it begins in (see `summary::fiscal_year()`).
+    pub fn fiscal_year(&self, date: NaiveDate) -> i32 {
+        summary::fiscal_year(date, self.fiscal_year_start_month)
+    }
+
+    /// The first and last days of the fiscal `year` (see `summary::fiscal_year_dates()`).
+    pub fn fiscal_year_dates(&self, year: i32) -> (NaiveDate, NaiveDate) {
+        summary::fiscal_year_dates(year, self.fiscal_year_start_month)
+    }
+
+    /// When the fiscal `year` is, as written in the reports: `in 2023` for a calendar year, or else its dates, i.e.,
+    /// `from 2023-07-01 through 2024-06-30`.
+    pub fn fiscal_year_span(&self, year: i32) -> String {
+        match self.fiscal_year_start_month {
+            1 => format!("in {}", year),
+            _ => {
+                let (first, last) = self.fiscal_year_dates(year);
+                format!("from {} through {}", self.report_date(first), self.report_date(last))
+            }
+        }
+    }
+
+    /// Whether a `Transaction` dated `date` belongs in the reports: it's in the `tax_year`, and in the date range of the
+    /// `report_filter`.
+    pub fn is_reported_date(&self, date: NaiveDate) -> bool {
+        self.is_in_tax_year(date) && self.report_filter.includes_date(date)
+    }
+
+    /// Whether `txn` belongs in the reports: its date does (see `is_reported_date()`), and it has the `report_filter`'s
+    /// tag, if any.
+    pub fn is_reported_txn(&self, txn: &Transaction) -> bool {
+        self.is_reported_date(txn.date) && self.report_filter.includes_tags(&txn.tags)
+    }
+
+    /// Whether the movements of `raw_acct` belong in the reports (see `ReportFilter::includes_account()`).
+    pub fn is_reported_account(&self, raw_acct: &RawAccount) -> bool {
+        self.report_filter.includes_account(raw_acct)
+    }
+
+    /// The settings a run with a `translation` is processed with (after the translation) and reported with: those of
+    /// the run, except that the `translation`'s currency is the home currency.
+    pub fn translated_settings(&self) -> ImportProcessParameters {
+        match &self.translation {
+            Some(translation) => ImportProcessParameters { home_currency: translation.currency.clone(), ..self.clone() },
+            None => self.clone(),
+        }
+    }
+
+    /// `date` as written in the reports: in the `output_date_format`, if set, or as YYYY-MM-DD.
+    pub fn report_date(&self, date: NaiveDate) -> String {
+        match &self.output_date_format {
+            Some(format) => date.format(format).to_string(),
+            None => date.to_string(),
+        }
+    }
+
+    /// `file_name` with `_<tax_year>` inserted before its extension (i.e., `C4_Txns_mvmts_detail_2023.csv`), or
+    /// unchanged if no `tax_year` is set.
+    pub fn tax_year_file_name(&self, file_name: &str) -> String {
+        match (self.tax_year, file_name.rsplit_once('.')) {
+            (Some(year), Some((stem, extension))) => format!("{}_{}.{}", stem, year, extension),
+            (Some(year), None) => format!("{}_{}", file_name, year),
+            (None, _) => file_name.to_string(),
+        }
+    }
+
+    /// The name the report `file_name` is saved as: after the `file_name_prefix` (if set), and with `_<file_name_date>`
+    /// (if set) inserted before its extension, i.e., `alice_C4_Txns_mvmts_detail_2023_2024-04-15.csv`.
+    pub fn report_file_name(&self, file_name: &str) -> String {
+
+        let file_name = match (self.file_name_date, file_name.rsplit_once('.')) {
+            (Some(date), Some((stem, extension))) => format!("{}_{}.{}", stem, date, extension),
+            (Some(date), None) => format!("{}_{}", file_name, date),
+            (None, _) => file_name.to_string(),
+        };
+
+        match &self.file_name_prefix {
+            Some(prefix) => format!("{}{}", prefix, file_name),
+            None => file_name,
+        }
+    }
+}
+
+/// Receives the progress of a run as it goes, so that a front end can show it (i.e., as a progress bar).
+pub trait Progress {
+
+    /// `done` of the `total` items of `phase` (rows imported, or transactions processed) are done so far.  A `total`
+    /// of zero means it isn't known.  Once the `phase` is complete, this is called with `done` equal to `total`.
+    fn update(&mut self, phase: &str, done: usize, total: usize);
+
+    /// `phase` was completed in `duration` (see `Timings::record()`).
+    fn finished(&mut self, _phase: &str, _duration: Duration) {}
+}
+
+/// The format of a report made of rows, such as C1_Acct_Sum_with_cost_basis.csv.  It's a CSV, unless another format
+/// is chosen for it in the print menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableFormat {
+    Csv,
+    /// A text table, with each column padded to its widest cell (i.e., C1_Acct_Sum_with_cost_basis.txt).
+    Txt,
+    /// An array of objects, one per row after the header row, keyed by the header (i.e., C1_Acct_Sum_with_cost_basis.json).
+    Json,
+}
+
+impl TableFormat {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TableFormat::Csv => "CSV",
+            TableFormat::Txt => "TXT",
+            TableFormat::Json => "JSON",
+        }
+    }
+
+    /// `file_name` (of a CSV) with the extension of this format.
+    pub fn file_name(&self, file_name: &str) -> String {
+        let stem = file_name.strip_suffix(".csv").unwrap_or(file_name);
+        match self {
+            TableFormat::Csv => format!("{}.csv", stem),
+            TableFormat::Txt => format!("{}.txt", stem),
+            TableFormat::Json => format!("{}.json", stem),
+        }
+    }
+}
+
+/// The accounts the journal entries post realized gains and losses (by term) and income to, and the holding period
+/// separating short-term from long-term, from the JE_ACCOUNTS and JE_LONG_TERM_DAYS environment variables.  The
+/// default names are `Income:CapitalGains:ShortTerm` and `Income:CapitalGains:LongTerm` (for gains and losses alike),
+/// and `Income:Crypto`, with an account under it for each `IncomeType` (i.e., `Income:Crypto:Staking`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalAccounts {
+    pub short_term_gain: String,
+    pub short_term_loss: String,
+    pub long_term_gain: String,
+    pub long_term_loss: String,
+    /// The account of income without an `IncomeType`, and the parent of the account of each type not in `income_by_type`.
+    pub income: String,
+    pub income_by_type: BTreeMap<IncomeType, String>,
+    /// The number of days a `Lot` must be held for more than, for its gain or loss to post to a long-term account.
+    /// `None` for the `long_term_days` of the other reports.
+    pub long_term_days: Option<i64>,
+}
+
+impl Default for JournalAccounts {
+
+    fn default() -> JournalAccounts {
+        JournalAccounts {
+            short_term_gain: "Income:CapitalGains:ShortTerm".to_string(),
+            short_term_loss: "Income:CapitalGains:ShortTerm".to_string(),
+            long_term_gain: "Income:CapitalGains:LongTerm".to_string(),
+            long_term_loss: "Income:CapitalGains:LongTerm".to_string(),
+            income: "Income:Crypto".to_string(),
+            income_by_type: BTreeMap::new(),
+            long_term_days: None,
+        }
+    }
+}
+
+impl JournalAccounts {
+
+    /// Parses the accounts of a JE_ACCOUNTS `value`, as `key=account` separated by commas, over the defaults.  The keys
+    /// are `st-gain`, `st-loss`, `lt-gain`, `lt-loss` and `income`, and `income-` followed by an `incomeType` (i.e.,
+    /// `st-gain=Income:Gains:ShortTerm,st-loss=Expenses:Losses:ShortTerm,income-mining=Income:SelfEmployment`).
+    pub fn parse(value: &str) -> Result<JournalAccounts, Box<dyn Error>> {
+
+        let mut accounts = JournalAccounts::default();
+
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+
+            let (key, account) = entry.split_once('=')
+                .map(|(key, account)| (key.trim().to_ascii_lowercase(), account.trim().to_string()))
+                .filter(|(_key, account)| !account.is_empty())
+                .ok_or_else(|| format!("Journal account `{}` must be a key, then `=`, then an account (i.e., st-gain=Income:Gains).", entry))?;
+
+            match key.as_str() {
+                "st-gain" => accounts.short_term_gain = account,
+                "st-loss" => accounts.short_term_loss = account,
+                "lt-gain" => accounts.long_term_gain = account,
+                "lt-loss" => accounts.long_term_loss = account,
+                "income" => accounts.income = account,
+                other => match other.strip_prefix("income-").map(IncomeType::from_field) {
+                    Some(Ok(Some(income_type))) => { accounts.income_by_type.insert(income_type, account); }
+                    _ => return Err(format!("Journal account `{}` is for {}, but a key must be one of: st-gain, st-loss, \
+                        lt-gain, lt-loss, income, or income- followed by an incomeType (i.e., income-staking).", entry, other).into()),
+                },
+            }
+        }
+
+        Ok(accounts)
+    }
+
+    /// The account of realized gains (or, if not `gain`, losses) of the `term`.
+    pub fn gain_loss_account(&self, term: Term, gain: bool) -> &str {
+        match (term, gain) {
+            (Term::ST, true) => &self.short_term_gain,
+            (Term::ST, false) => &self.short_term_loss,
+            (Term::LT, true) => &self.long_term_gain,
+            (Term::LT, false) => &self.long_term_loss,
+        }
+    }
+
+    /// The account of income of the `income_type`.
+    pub fn income_account(&self, income_type: Option<IncomeType>) -> String {
+        match income_type {
+            Some(income_type) => self.income_by_type.get(&income_type).cloned()
+                .unwrap_or_else(|| format!("{}:{}", self.income, income_type)),
+            None => self.income.clone(),
+        }
+    }
+}
+
+/// Wall-clock durations of the major phases of a run, in the order they were recorded.
+/// These are printed to stderr at the end of a run when `--timings` is passed.
+#[derive(Default)]
+pub struct Timings {
+    pub phases: Vec<(String, Duration)>,
+    /// When set, told of the progress through the longest phases.
+    pub progress: Option<Box<dyn Progress>>,
+}
+
+impl Timings {
+
+    pub fn with_progress(progress: Box<dyn Progress>) -> Timings {
+        Timings { phases: Vec::new(), progress: Some(progress) }
+    }
+
+    /// Records the time elapsed since `start` as the duration of `phase`.
+    pub fn record(&mut self, phase: &str, start: Instant) {
+        let duration = start.elapsed();
+        if let Some(progress) = self.progress.as_mut() { progress.finished(phase, duration) }
+        self.phases.push((phase.to_string(), duration));
+    }
+
+    /// Tells the `progress`, if any, that `done` of `total` items of `phase` are done (see `Progress::update()`).
+    pub fn report_progress(&mut self, phase: &str, done: usize, total: usize) {
+        if let Some(progress) = self.progress.as_mut() { progress.update(phase, done, total) }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_phase, duration)| *duration).sum()
+    }
+}
+
+/// The maps of a fully processed CSV Input File.
+pub type ProcessedMaps = (
     HashMap<u16, RawAccount>,
     HashMap<u16, Account>,
     HashMap<u32, ActionRecord>,
     HashMap<u32, Transaction>,
-), Box<dyn Error>> {
+);
+
+/// Imports and fully processes the CSV Input File(s).  Several files are merged into one, in date order.
+pub fn import_and_process_final(
+    input_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+    timings: &mut Timings,
+) -> Result<ProcessedMaps, Box<dyn Error>> {
+
+    let inputs = csv_import_accts_txns::open_input_files(input_file_paths, settings)?;
+
+    import_and_process(inputs, settings, None, timings)
+}
+
+/// The same as `import_and_process_final()`, except that the CSV Input File is `csv` (i.e., as received by a front
+/// end), rather than read from a file.
+pub fn import_and_process_final_from_csv(
+    csv: Vec<u8>,
+    settings: &ImportProcessParameters,
+    timings: &mut Timings,
+) -> Result<ProcessedMaps, Box<dyn Error>> {
+
+    let input = csv_import_accts_txns::input_from_reader(Cursor::new(csv), settings)?;
+
+    import_and_process(vec![("CSV Input File".to_string(), input)], settings, None, timings)
+}
+
+/// The same as `import_and_process_final()`, except that `lot_picker` chooses the `Lot`s for each disposal
+/// when the costing method is `InventoryCostingMethod::SpecificIdentification`.
+pub fn import_and_process_final_with_lot_picker(
+    input_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+    lot_picker: &mut dyn LotPicker,
+    timings: &mut Timings,
+) -> Result<ProcessedMaps, Box<dyn Error>> {
+
+    let inputs = csv_import_accts_txns::open_input_files(input_file_paths, settings)?;
+
+    import_and_process(inputs, settings, Some(lot_picker), timings)
+}
+
+pub(crate) fn import_and_process(
+    inputs: InputFiles,
+    settings: &ImportProcessParameters,
+    lot_picker: Option<&mut dyn LotPicker>,
+    timings: &mut Timings,
+) -> Result<ProcessedMaps, Box<dyn Error>> {
 
     let mut transactions_map: HashMap<u32, Transaction> = HashMap::new();
     let mut action_records_map: HashMap<u32, ActionRecord> = HashMap::new();
@@ -51,17 +638,268 @@ pub fn import_and_process_final(
     let mut _lot_map: HashMap<(RawAccount, u32), Lot> = HashMap::new();
 
     csv_import_accts_txns::import_from_csv(
-        input_file_path,
-        settings.input_file_uses_iso_date_style,
-        &settings.input_file_date_separator,
+        inputs,
+        settings,
         &mut raw_account_map,
         &mut account_map,
         &mut action_records_map,
         &mut transactions_map,
+        timings,
     )?;
 
-    println!("  Successfully imported CSV Input File.");
-    println!("Processing the data...");
+    log::info!("  Successfully imported CSV Input File.");
+
+    let excluded = exclusions::exclude_transactions(&settings.excluded_txns, &mut action_records_map, &mut transactions_map)?;
+
+    if excluded > 0 {
+        log::info!("  Left out {} excluded transaction(s) (later transactions were renumbered).", excluded);
+    }
+
+    nfts::check_nft_quantities(&raw_account_map, &account_map, &action_records_map, &transactions_map)?;
+
+    let split = liquidity_pools::split_pool_transactions(
+        settings.pool_treatment,
+        &mut action_records_map,
+        &mut transactions_map,
+    );
+
+    if split > 0 {
+        log::info!("  Split {} liquidity pool deposit(s) and withdrawal(s) into an exchange for each asset (later transactions were renumbered).", split);
+    }
+
+    if let Some(aliases) = &settings.token_aliases {
+
+        let marked = token_aliases::mark_token_migrations(
+            aliases,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &mut transactions_map,
+        );
+
+        if marked > 0 {
+            log::info!("  Carried the basis over in {} exchange(s) of a token for an alias of it.", marked);
+        }
+    }
+
+    if let (Some(entities), EntityTransferTreatment::Sale) = (&settings.entities, settings.entity_transfers) {
+
+        let marked = entities::mark_entity_sales(
+            entities,
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &mut transactions_map,
+        );
+
+        for txn_num in marked.iter().filter(|txn_num| transactions_map.get(txn_num).unwrap().proceeds.is_zero()) {
+            log::warn!("  Txn {} is a sale between entities without proceeds, so it's a sale for nothing.", txn_num);
+        }
+
+        if !marked.is_empty() {
+            log::info!("  Treated {} transfer(s) between entities as sales.", marked.len());
+        }
+    }
+
+    if let Some(state) = &settings.resume_from {
+
+        let added = run_state::add_carried_forward_lots(
+            state,
+            settings,
+            &mut raw_account_map,
+            &mut account_map,
+            &mut action_records_map,
+            &mut transactions_map,
+        )?;
+
+        log::info!("  Carried forward {} open lot(s) held through {}.", added, state.through);
+    }
+
+    if let Some(lookup) = &settings.price_lookup {
+
+        let fill = price_lookup::fill_missing_proceeds(
+            lookup,
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &mut transactions_map,
+        )?;
+
+        for (tx_num, ticker, date) in fill.unpriced.iter() {
+            log::warn!("  No price for {} on {} was found, so txn {} still has no proceeds.", ticker, date, tx_num);
+        }
+
+        log::info!("  Filled in the missing proceeds of {} transaction(s) from historical prices ({} fetched and cached in {}).",
+            fill.filled.len(), fill.fetched, lookup.path.display());
+    }
+
+    //  Any proceeds filled in from prices are zeroed as well
+    if settings.airdrop_basis == AirdropBasis::Zero {
+
+        let zeroed = airdrops::zero_airdrop_basis(&mut transactions_map);
+
+        if zeroed > 0 {
+            log::info!("  Gave the lots of {} airdrop(s) and hard fork(s) a basis of zero, with no income.", zeroed);
+        }
+    }
+
+    let rebases = rebases::apply_rebase_treatment(settings.rebase_treatment, &action_records_map, &mut transactions_map);
+
+    if rebases > 0 {
+        log::info!("  Found {} rebase(s), treated as {}.", rebases, match settings.rebase_treatment {
+            RebaseTreatment::Income => "income as they accrue (increases) or losses (decreases)",
+            RebaseTreatment::SpreadBasis => "spreading the basis held (increases) or losses (decreases)",
+        });
+    }
+
+    //  Prices and carried-forward lots are in the home currency, so this comes after them
+    let translated_settings;
+    let settings = match &settings.translation {
+        Some(translation) => {
+
+            let translated = fx_translation::translate_home_currency_values(
+                translation,
+                &settings.home_currency,
+                &mut raw_account_map,
+                &account_map,
+                &mut action_records_map,
+                &mut transactions_map,
+            )?;
+
+            log::info!("  Translated {} transaction(s) from {} into {} at the rates of their dates.",
+                translated, settings.home_currency, translation.currency);
+
+            translated_settings = settings.translated_settings();
+            &translated_settings
+        }
+        None => settings,
+    };
+
+    log::info!("Processing the data...");
+
+    if let Some(tolerance) = settings.transfer_match_tolerance {
+
+        let pairs = transfer_matching::match_transfers(
+            tolerance,
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &mut action_records_map,
+            &mut transactions_map,
+        );
+
+        for (out_num, in_num) in pairs.iter() {
+            log::info!("  Matched the withdrawal of txn {} with the deposit of txn {} as a transfer.", out_num, in_num);
+        }
+
+        if !pairs.is_empty() {
+            log::info!("  Matched {} transfer(s) (later transactions were renumbered).", pairs.len());
+        }
+    }
+
+    let moved = same_date_ordering::reorder_same_date_txns(
+        settings.same_date_order,
+        &settings.home_currency,
+        &raw_account_map,
+        &account_map,
+        &mut action_records_map,
+        &mut transactions_map,
+    );
+
+    if moved > 0 {
+        log::info!("  Reordered {} same-date transaction(s), which were renumbered accordingly.", moved);
+    }
+
+    let added = transfer_fees::split_transfer_fees(
+        &raw_account_map,
+        &account_map,
+        &mut action_records_map,
+        &mut transactions_map,
+    )?;
+
+    if added > 0 {
+        log::info!("  Split the fees off of {} transfer(s) into transactions of their own (later transactions were renumbered).", added);
+    }
+
+    if let Some(rounding) = settings.disposal_rounding {
+
+        let residuals = disposal_rounding::round_disposal_amounts(
+            rounding,
+            &raw_account_map,
+            &account_map,
+            &mut action_records_map,
+            &transactions_map,
+        );
+
+        for r in residuals.iter() {
+            let raw_acct = raw_account_map.get(&account_map.get(&r.account_key).unwrap().raw_key).unwrap();
+            log::info!("  Rounded txn {} outgoing {} from {} to {} (residual of {}).",
+                r.tx_num, raw_acct.ticker, r.original_amount, r.rounded_amount, r.residual());
+        }
+    }
+
+    let swept = dust_sweeps::sweep_dust(
+        &settings.dust_quantities,
+        &raw_account_map,
+        &account_map,
+        &mut action_records_map,
+        &mut transactions_map,
+    )?;
+
+    if swept > 0 {
+        log::info!("  Swept the dust left in accounts by {} disposal(s) (later transactions were renumbered).", swept);
+    }
+
+    let synthetic_basis = match settings.synthetic_acquisitions {
+        Some(basis) => Some(basis),
+        None => {
+
+            let shortfalls = synthetic_acquisitions::find_negative_balances(
+                &settings.home_currency,
+                &raw_account_map,
+                &account_map,
+                &action_records_map,
+                &transactions_map,
+            );
+
+            let (label, level) = if settings.lenient { ("WARNING", log::Level::Warn) } else { ("FATAL", log::Level::Error) };
+
+            for s in shortfalls.iter() {
+                let raw_acct = raw_account_map.get(&account_map.get(&s.account_key).unwrap().raw_key).unwrap();
+                let row = transactions_map.get(&s.tx_num).unwrap().source_row
+                    .map(|row| format!(" (row {})", row)).unwrap_or_default();
+                log::log!(level, "{}: Txn {}{} on {} ({}) spends {} {} from {}, which is {} more than its balance.",
+                    label, s.tx_num, row, s.date_as_string, s.memo, -s.amount, raw_acct.ticker, raw_acct.name, s.shortfall);
+            }
+
+            if !shortfalls.is_empty() && !settings.lenient {
+                return Err(format!("{} transaction(s) would take an account negative. Correct the CSV Input File, or pass \
+                    --lenient to cover each shortfall with a zero-basis synthetic acquisition.", shortfalls.len()).into())
+            }
+
+            if shortfalls.is_empty() { None } else { Some(SyntheticBasis::Zero) }
+        }
+    };
+
+    if let Some(basis) = synthetic_basis {
+
+        let added = synthetic_acquisitions::cover_negative_balances(
+            basis,
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &mut action_records_map,
+            &mut transactions_map,
+        );
+
+        if added > 0 {
+            log::info!("  Added {} synthetic acquisition(s) to cover negative balances. See C10_Synthetic_acquisitions.csv.", added);
+        }
+    }
+
+    let start = Instant::now();
 
     transactions_map = create_lots_mvmts::create_lots_and_movements(
         &settings,
@@ -69,44 +907,168 @@ pub fn import_and_process_final(
         &account_map,
         &action_records_map,
         transactions_map,
+        lot_picker,
+        timings,
         // &mut lot_map,
     )?;
 
-    println!("  Created lots and movements.");
+    timings.record("Lot processing", start);
 
-    import_cost_proceeds_etc::add_cost_basis_to_movements(
-        &settings.home_currency,
-        &raw_account_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map
-    )?;
+    log::info!("  Created lots and movements.");
+
+    let start = Instant::now();
+
+    if let Some(window_days) = settings.wash_sale_window {
+
+        let wash_sales = wash_sales::add_cost_basis_with_wash_sales(
+            window_days,
+            settings.transfer_fee_is_disposal,
+            &settings.stablecoins,
+            &settings.home_currency,
+            settings.home_curr_leg_is_basis,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        for ws in wash_sales.iter() {
+            let raw_acct = raw_account_map.get(&account_map.get(&ws.account_key).unwrap().raw_key).unwrap();
+            log::info!("  Wash sale: txn {} disposed of {} {} at a loss, of which {} is disallowed and added to the basis \
+                of the lot acquired in txn {}.", ws.tx_num, ws.amount, raw_acct.ticker, ws.disallowed_loss, ws.replacement_tx_num);
+        }
+    } else {
+
+        import_cost_proceeds_etc::add_cost_basis_to_movements(
+            &settings.home_currency,
+            settings.home_curr_leg_is_basis,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+    }
+
+    log::info!("  Added cost basis to movements.");
 
-    println!("  Added cost basis to movements.");
+    if settings.costing_method == InventoryCostingMethod::SharePoolingSection104 {
+
+        let pooled_disposals = share_pooling::apply_share_pooling(
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        for d in pooled_disposals.iter().filter(|d| !d.same_day.is_zero() || !d.bed_and_breakfast.is_zero()) {
+            let raw_acct = raw_account_map.get(&account_map.get(&d.account_key).unwrap().raw_key).unwrap();
+            log::info!("  Share pooling: txn {} disposed of {} {}: {} matched same-day, {} bed and breakfast, {} from the \
+                Section 104 pool.", d.tx_num, (d.same_day + d.bed_and_breakfast + d.section_104).normalize(), raw_acct.ticker,
+                d.same_day.normalize(), d.bed_and_breakfast.normalize(), d.section_104.normalize());
+        }
+
+        log::info!("  Matched disposals with acquisitions and the Section 104 pool.");
+    }
+
+    if settings.costing_method == InventoryCostingMethod::AdjustedCostBase {
+
+        let acb_disposals = adjusted_cost_base::apply_adjusted_cost_base(
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        for d in acb_disposals.iter().filter(|d| !d.superficial_loss.is_zero()) {
+            let raw_acct = raw_account_map.get(&account_map.get(&d.account_key).unwrap().raw_key).unwrap();
+            log::info!("  Superficial loss: txn {} disposed of {} {} at a loss, of which {} is denied and added to the ACB.",
+                d.tx_num, d.amount, raw_acct.ticker, d.superficial_loss);
+        }
+
+        log::info!("  Costed disposals at the adjusted cost base.");
+    }
+
+    //  UK share pooling and the ACB already draw from every `Account` of the currency
+    let is_pooled = matches!(settings.costing_method,
+        InventoryCostingMethod::SharePoolingSection104 | InventoryCostingMethod::AdjustedCostBase);
+
+    if settings.lot_pooling == LotPooling::Universal && !is_pooled {
+
+        let universal_disposals = lot_pooling::apply_universal_pooling(
+            &settings.costing_method,
+            settings.long_term_days,
+            &settings.home_currency,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        for d in universal_disposals.iter().filter(|d| d.cost_basis != d.wallet_cost_basis) {
+            let raw_acct = raw_account_map.get(&account_map.get(&d.account_key).unwrap().raw_key).unwrap();
+            log::debug!("  Universal pooling: txn {} disposed of {} {} at a cost basis of {} (rather than {} from its own \
+                account).", d.tx_num, d.amount, raw_acct.ticker, d.cost_basis, d.wallet_cost_basis);
+        }
+
+        log::info!("  Costed disposals from the lots of every account of their currency.");
+    }
 
     import_cost_proceeds_etc::add_proceeds_to_movements(
+        &settings.home_currency,
         &raw_account_map,
         &account_map,
         &action_records_map,
         &transactions_map
     )?;
 
-    println!("  Added proceeds to movements.");
+    timings.record("Cost basis and proceeds", start);
+
+    log::info!("  Added proceeds to movements.");
 
     if settings.lk_treatment_enabled {
 
-        println!(" Applying like-kind treatment through cut-off date: {}.", settings.lk_cutoff_date);
+        match settings.lk_scope.windows.is_empty() {
+            true => log::info!(" Applying like-kind treatment through cut-off date: {}.", settings.lk_cutoff_date),
+            false => log::info!(" Applying like-kind treatment within {} window(s), through {}.",
+                settings.lk_scope.windows.len(), settings.lk_cutoff_date),
+        }
+
+        let start = Instant::now();
 
         import_cost_proceeds_etc::apply_like_kind_treatment(
             &settings.home_currency,
             settings.lk_cutoff_date,
+            &settings.lk_scope,
             &raw_account_map,
             &account_map,
             &action_records_map,
             &transactions_map
         )?;
 
-        println!("  Successfully applied like-kind treatment.");
+        timings.record("Like-kind treatment", start);
+
+        log::info!("  Successfully applied like-kind treatment.");
+    }
+
+    if !settings.transfer_fee_is_disposal {
+        transfer_fees::expense_transfer_fees(&account_map, &action_records_map, &transactions_map);
+    }
+
+    dust_sweeps::write_off_dust_sweeps(&action_records_map, &transactions_map);
+
+    non_sale_disposals::apply_non_sale_treatments(&account_map, &action_records_map, &transactions_map);
+
+    if !settings.stablecoins.is_empty() {
+
+        stablecoins::apply_cash_equivalent_treatment(
+            &settings.stablecoins,
+            &raw_account_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        );
     }
 
     Ok((raw_account_map, account_map, action_records_map, transactions_map))
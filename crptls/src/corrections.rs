@@ -0,0 +1,227 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::csv_import_accts_txns::OPTIONAL_COLUMNS;
+
+
+/// A field of a `Transaction` that may be corrected without editing the CSV Input File.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CorrectedField {
+    TxDate,
+    Proceeds,
+    Memo,
+    /// One of the `OPTIONAL_COLUMNS` (i.e., fee or txType), which replaces the field of the row as it's read.
+    Optional(&'static str),
+    /// The quantity of the account numbered so, which replaces the field of the row as it's read (blank for none).
+    Quantity(u16),
+    /// The whole row, which isn't imported at all.
+    Skip,
+}
+
+impl CorrectedField {
+
+    fn from_field(field: &str) -> Option<CorrectedField> {
+        match field {
+            "txDate" => Some(CorrectedField::TxDate),
+            "proceeds" => Some(CorrectedField::Proceeds),
+            "memo" => Some(CorrectedField::Memo),
+            "skip" | "void" => Some(CorrectedField::Skip),
+            _ => match field.parse::<u16>() {
+                Ok(account_num) if account_num > 0 => Some(CorrectedField::Quantity(account_num)),
+                _ => OPTIONAL_COLUMNS.iter().find(|column| **column == field).map(|column| CorrectedField::Optional(column)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for CorrectedField {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CorrectedField::TxDate => write!(f, "txDate"),
+            CorrectedField::Proceeds => write!(f, "proceeds"),
+            CorrectedField::Memo => write!(f, "memo"),
+            CorrectedField::Optional(column) => write!(f, "{}", column),
+            CorrectedField::Quantity(account_num) => write!(f, "{}", account_num),
+            CorrectedField::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// What a correction corrects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CorrectionTarget {
+    /// The row of the file (as imported, so after any merging or converting), counting the four header rows (so the
+    /// first transaction row is row 5).  See `Transaction::source_row`.
+    Row(usize),
+    /// The `Transaction` numbered so as the file is imported without the corrections (so a correction leaving out
+    /// an earlier row doesn't change which transaction this is).  Rows parked for review or dropped as duplicates
+    /// aren't numbered, and neither are annotation rows.
+    Txn(u32),
+}
+
+impl fmt::Display for CorrectionTarget {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CorrectionTarget::Row(row) => write!(f, "{}", row),
+            CorrectionTarget::Txn(tx_number) => write!(f, "#{}", tx_number),
+        }
+    }
+}
+
+/// A correction of one field of the `Transaction` on a row of the CSV Input File, applied as it is imported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Correction {
+    pub target: CorrectionTarget,
+    pub field: CorrectedField,
+    /// A txDate is YYYY-MM-DD, whatever the date format of the file, proceeds are a number of zero or more, a quantity
+    /// is a number (or blank), and the value of a skip is ignored.
+    pub value: String,
+}
+
+impl Correction {
+
+    /// The row corrected, if the correction is of a row (rather than of a transaction).
+    pub fn row(&self) -> Option<usize> {
+        match self.target {
+            CorrectionTarget::Row(row) => Some(row),
+            CorrectionTarget::Txn(_) => None,
+        }
+    }
+
+    /// The corrected txDate, if this corrects the txDate.
+    pub fn tx_date(&self) -> Option<NaiveDate> {
+        match self.field {
+            CorrectedField::TxDate if self.value.len() == 10 => NaiveDate::parse_from_str(&self.value, "%Y-%m-%d").ok(),
+            _ => None,
+        }
+    }
+
+    /// Checks that the `value` suits the `field`.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.field {
+            CorrectedField::TxDate if self.tx_date().is_none() =>
+                Err(format!("The corrected txDate ({}) must be YYYY-MM-DD.", self.value)),
+            CorrectedField::Proceeds => match self.value.replace(',', "").parse::<Decimal>() {
+                Ok(p) if p >= dec!(0) => Ok(()),
+                _ => Err(format!("The corrected proceeds ({}) must be a number of zero or more.", self.value)),
+            },
+            CorrectedField::Quantity(account_num) => match self.value.trim().is_empty() {
+                true => Ok(()),
+                false => self.value.replace(',', "").trim().parse::<Decimal>().map(|_| ())
+                    .map_err(|_| format!("The corrected quantity ({}) for account {} must be a number.", self.value, account_num)),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reads a corrections file: a CSV with a header row, then one `row,field,value` row per correction (i.e.,
+/// `12,proceeds,1500.00`), where the field is one of txDate, proceeds or memo, one of the optional columns (i.e.,
+/// `12,fee,2.50`), an account number, for its quantity (i.e., `12,3,-0.5`), or `skip` (or `void`), to leave the row
+/// out.  In place of the row, `#` and a transaction number (i.e., `#8,void,`) corrects that transaction (see
+/// `CorrectionTarget::Txn`), wherever its row is.  A later correction of the same field of the same row (or
+/// transaction) replaces an earlier one.
+pub fn import_corrections(path: &Path) -> Result<Vec<Correction>, Box<dyn Error>> {
+
+    let file = File::open(path)?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(file);
+
+    let mut corrections: Vec<Correction> = Vec::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let line = idx + 2;  //  Account for the header and one-based line numbers
+
+        let first = record.get(0).unwrap_or("").trim();
+
+        let target = match first.strip_prefix('#') {
+            Some(txn) => match txn.parse::<u32>() {
+                Ok(tx_number) if tx_number > 0 => CorrectionTarget::Txn(tx_number),
+                _ => return Err(format!("Corrections file line {} has an invalid transaction number (the first is #1).",
+                    line).into()),
+            },
+            None => match first.parse::<usize>() {
+                Ok(row) if row >= 5 => CorrectionTarget::Row(row),
+                _ => return Err(format!("Corrections file line {} has an invalid row (the first transaction is row 5, \
+                    or #1 for transaction 1).", line).into()),
+            },
+        };
+
+        let field = match record.get(1).and_then(|f| CorrectedField::from_field(f.trim())) {
+            Some(field) => field,
+            None => return Err(format!("Corrections file line {} has an invalid field (it must be txDate, proceeds, memo, \
+                one of {}, an account number, skip or void).", line, OPTIONAL_COLUMNS.join(", ")).into()),
+        };
+
+        let correction = Correction { target, field, value: record.get(2).unwrap_or("").to_string() };
+
+        correction.validate().map_err(|e| format!("Corrections file line {}: {}", line, e))?;
+
+        corrections.retain(|c| c.target != target || c.field != field);
+        corrections.push(correction);
+    }
+
+    Ok(corrections)
+}
+
+/// The corrections of rows, by row, for applying as the CSV Input File is imported.
+pub(crate) fn corrections_by_row(corrections: &[Correction]) -> HashMap<usize, Vec<&Correction>> {
+
+    let mut by_row: HashMap<usize, Vec<&Correction>> = HashMap::new();
+
+    for correction in corrections.iter() {
+        if let CorrectionTarget::Row(row) = correction.target {
+            by_row.entry(row).or_default().push(correction);
+        }
+    }
+
+    by_row
+}
+
+/// The corrections of transactions, by transaction number, for applying as the CSV Input File is imported.
+pub(crate) fn corrections_by_txn(corrections: &[Correction]) -> HashMap<u32, Vec<&Correction>> {
+
+    let mut by_txn: HashMap<u32, Vec<&Correction>> = HashMap::new();
+
+    for correction in corrections.iter() {
+        if let CorrectionTarget::Txn(tx_number) = correction.target {
+            by_txn.entry(tx_number).or_default().push(correction);
+        }
+    }
+
+    by_txn
+}
+
+/// Appends a correction to the corrections file, which is created (with its header row) if it doesn't exist.
+pub fn append_correction(path: &Path, correction: &Correction) -> Result<(), Box<dyn Error>> {
+
+    correction.validate()?;
+
+    let is_new = !path.exists();
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+
+    let mut wtr = csv::Writer::from_writer(file);
+
+    if is_new { wtr.write_record(["row", "field", "value"])? }
+
+    wtr.write_record([correction.target.to_string(), correction.field.to_string(), correction.value.clone()])?;
+    wtr.flush()?;
+
+    Ok(())
+}
@@ -15,6 +15,58 @@ pub enum InventoryCostingMethod {
     FIFObyLotCreationDate,
     /// 4. FIFO according to the basis date of the lot.
     FIFObyLotBasisDate,
+    /// 5. Lots that would be long-term as of the disposal date first, then short-term lots. FIFO by basis date within each.
+    LTfirstFIFObyLotBasisDate,
+    /// 6. Lots that would be long-term as of the disposal date first, then short-term lots. LIFO by basis date within each.
+    LTfirstLIFObyLotBasisDate,
+    /// 7. Highest in, first out: lots with the highest cost basis per unit first. Ties are FIFO by lot creation order.
+    HIFObyLotUnitBasis,
+    /// 8. Weighted average cost: each disposal is drawn from every lot in proportion to the lot's remaining
+    ///    balance, so its cost basis is the average cost basis of the holdings in the account.
+    AverageCostAcrossLots,
+    /// 9. Specific identification: the lots for each disposal are chosen by a `LotPicker` (e.g., interactively).
+    SpecificIdentification,
+    /// 10. UK share pooling (HMRC): each disposal is matched with acquisitions of the same currency (in any account)
+    ///     on the same day, then with acquisitions in the following 30 days ("bed and breakfast"), and only then with
+    ///     the Section 104 pool of everything else, at its average cost.  Units are drawn from lots as for average cost.
+    SharePoolingSection104,
+    /// 11. Canadian adjusted cost base (ACB): each disposal's cost basis is its share of the average cost of everything
+    ///     held in the currency (in any account), and a superficial loss is denied and added to the ACB.  Units are
+    ///     drawn from lots as for average cost.
+    AdjustedCostBase,
+}
+
+impl InventoryCostingMethod {
+
+    /// The method numbered `choice` (i.e., `1` for `LIFObyLotCreationDate`), as for `INV_COSTING_METHOD`.
+    pub fn from_choice(choice: &str) -> Option<InventoryCostingMethod> {
+        match choice.trim() {
+            "1" => Some(InventoryCostingMethod::LIFObyLotCreationDate),
+            "2" => Some(InventoryCostingMethod::LIFObyLotBasisDate),
+            "3" => Some(InventoryCostingMethod::FIFObyLotCreationDate),
+            "4" => Some(InventoryCostingMethod::FIFObyLotBasisDate),
+            "5" => Some(InventoryCostingMethod::LTfirstFIFObyLotBasisDate),
+            "6" => Some(InventoryCostingMethod::LTfirstLIFObyLotBasisDate),
+            "7" => Some(InventoryCostingMethod::HIFObyLotUnitBasis),
+            "8" => Some(InventoryCostingMethod::AverageCostAcrossLots),
+            "9" => Some(InventoryCostingMethod::SpecificIdentification),
+            "10" => Some(InventoryCostingMethod::SharePoolingSection104),
+            "11" => Some(InventoryCostingMethod::AdjustedCostBase),
+            _ => None,
+        }
+    }
+
+    /// Whether `Lot`s are chosen based on their cost basis, which must then be known as each disposal is processed.
+    pub fn selects_lots_by_basis(&self) -> bool {
+        matches!(self, InventoryCostingMethod::HIFObyLotUnitBasis | InventoryCostingMethod::SpecificIdentification)
+    }
+
+    /// Whether each disposal is drawn from every `Lot` in proportion to its balance.
+    pub fn spreads_across_lots(&self) -> bool {
+        matches!(self, InventoryCostingMethod::AverageCostAcrossLots
+            | InventoryCostingMethod::SharePoolingSection104
+            | InventoryCostingMethod::AdjustedCostBase)
+    }
 }
 
 impl fmt::Display for InventoryCostingMethod {
@@ -25,6 +77,13 @@ impl fmt::Display for InventoryCostingMethod {
            InventoryCostingMethod::LIFObyLotBasisDate => write!(f, "LIFO by lot basis date"),
            InventoryCostingMethod::FIFObyLotCreationDate => write!(f, "FIFO by lot creation date"),
            InventoryCostingMethod::FIFObyLotBasisDate => write!(f, "FIFO by lot basis date"),
+           InventoryCostingMethod::LTfirstFIFObyLotBasisDate => write!(f, "Long-term lots first, then FIFO by lot basis date"),
+           InventoryCostingMethod::LTfirstLIFObyLotBasisDate => write!(f, "Long-term lots first, then LIFO by lot basis date"),
+           InventoryCostingMethod::HIFObyLotUnitBasis => write!(f, "HIFO by lot cost basis per unit"),
+           InventoryCostingMethod::AverageCostAcrossLots => write!(f, "Weighted average cost across lots"),
+           InventoryCostingMethod::SpecificIdentification => write!(f, "Specific identification"),
+           InventoryCostingMethod::SharePoolingSection104 => write!(f, "UK share pooling (same day, 30 days, Section 104 pool)"),
+           InventoryCostingMethod::AdjustedCostBase => write!(f, "Canadian adjusted cost base (with superficial losses)"),
        }
     }
 }
\ No newline at end of file
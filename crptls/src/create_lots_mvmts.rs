@@ -6,14 +6,17 @@ use std::cell::{RefCell, Ref, Cell};
 use std::collections::HashMap;
 use std::error::Error;
 
+use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crate::core_functions::ImportProcessParameters;
-use crate::transaction::{Transaction, ActionRecord, TxType, Polarity, TxHasMargin};
-use crate::account::{Account, RawAccount, Lot, Movement};
+use crate::core_functions::{ImportProcessParameters, Timings};
+use crate::transaction::{Transaction, ActionRecord, TxType, TxKind, Polarity, TxHasMargin};
+use crate::account::{Account, RawAccount, Lot, Movement, Term};
 use crate::costing_method::InventoryCostingMethod;
 use crate::decimal_utils::round_d128_1e8;
+use crate::import_cost_proceeds_etc;
+use crate::specific_id::{self, LotPicker};
 
 /// This is probably the most important function in the whole program.  Based on the data in the CSV Input File,
 /// the `account`s and `transaction`s will be created.  Once the `account`s and `transaction`s have been created, both
@@ -36,12 +39,12 @@ use crate::decimal_utils::round_d128_1e8;
 ///
 /// Third, `lot`s and `movement`s are created sequentially and kept in that order.  Accordingly, an outgoing `movement` will
 /// reduce the last `lot` first.  If there is not enough in the last `lot`, the remainder will then be applied to the `lot`
-/// before that, and the `lot` before that, and so on (recursively), until the amount in the `action record` has been
+/// before that, and the `lot` before that, and so on, until the amount in the `action record` has been
 /// fully recorded as `lot` `movement`s.  This is known as last in, first out (LIFO).  Please refer to the `InventoryCostingMethod`
 /// enum for the available choices if LIFO is not desirable. If a different `inventory costing method` is chosen,
-/// the vector of indices is re-ordered to accomodate the paradigm that the `action record` amount will always be applied
-/// to the last `lot` (i.e., if e.g. FIFO is chosen, then the index for the last `lot` will be 0 instead of length - 1.).
-/// See below `vec_of_ordered_index_values`.
+/// the indices are re-ordered to accomodate the paradigm that the `action record` amount will always be applied
+/// to the first `lot` index (i.e., if e.g. FIFO is chosen, then the first index will be 0 instead of length - 1.).
+/// See below `lot_indexes`.
 ///
 /// Fourth, this function does not contemplate any income/expense/gain/loss at all.  It is solely an exercise in determining
 /// and solidifying how to split (if needed) the amount in each `action record` into `movement`s that post to the appropriate
@@ -52,6 +55,8 @@ pub(crate) fn create_lots_and_movements(
     acct_map: &HashMap<u16, Account>,
     ar_map: &HashMap<u32, ActionRecord>,
     txns_map: HashMap<u32, Transaction>,
+    mut lot_picker: Option<&mut dyn LotPicker>,
+    timings: &mut Timings,
     // lot_map: &HashMap<(RawAccount, u32), Lot>,
 ) -> Result<HashMap<u32,Transaction>, Box<dyn Error>> {
 
@@ -59,20 +64,41 @@ pub(crate) fn create_lots_and_movements(
     let chosen_costing_method = &settings.costing_method;
     let enable_lk_treatment = settings.lk_treatment_enabled;
     let like_kind_cutoff_date = settings.lk_cutoff_date;
+    let like_kind_scope = &settings.lk_scope;
     let lk_basis_date_preserved = settings.lk_basis_date_preserved;
 
     // This is set automatically based on how like-kind `exchange` `transaction`s work, but it could be left to user choice, in theory.
     let multiple_incoming_mvmts_per_ar_due_to_lk = lk_basis_date_preserved;
 
+    // By `account` key, the number of its first `lot`s known to have a zero balance, which FIFO by creation date skips.  A (non-margin)
+    // `lot` only receives an incoming `movement` when it's created, so once its balance is zero, it stays zero.
+    let mut spent_lots: HashMap<u16, usize> = HashMap::new();
+
     let length = txns_map.len();
 
     // Transactions are stored in a HashMap, and they are ordered sequentially starting at 1, so we iterate through
     // that range and use the corresponding `num` to get each transaction.
     for num in 1..=length {
 
+        timings.report_progress("Lot processing", num - 1, length);
+
         let txn_num = num as u32;
         let txn = txns_map.get(&(txn_num)).expect("Couldn't get txn. Tx num invalid?");
 
+        // Some costing methods choose `lot`s by their cost basis, which is otherwise only added once every `lot` and
+        // `movement` exists.  So, for those, the prior `transaction`'s `movement`s are given their cost basis first.
+        if chosen_costing_method.selects_lots_by_basis() && txn_num > 1 {
+            import_cost_proceeds_etc::add_cost_basis_to_txn_movements(
+                txn_num - 1,
+                chosen_home_currency,
+                settings.home_curr_leg_is_basis,
+                raw_acct_map,
+                acct_map,
+                ar_map,
+                &txns_map,
+            )?;
+        }
+
         // The first type of transaction we consider are those where both `action record`s have an `account` that
         // is a margin `account`.  If so, it is an `exchange` `transaction`.  `Exchange` `transaction`s for margin
         // `account`s don't create a new lot for every increase.  Rather, it keeps one lot per "close," which is
@@ -80,7 +106,7 @@ pub(crate) fn create_lots_and_movements(
         // of zeroing out both the margin `account`s by both closing the margin position AND making a transfer
         // between the margin quote `account` and the corresponding spot `account` such that both margin `account`s
         // now have a zero balance.
-        if txn.marginness(&ar_map, &raw_acct_map, &acct_map) == TxHasMargin::TwoARs {
+        if txn.marginness(&ar_map, &raw_acct_map, &acct_map)? == TxHasMargin::TwoARs {
             assert_eq!(txn.transaction_type(&ar_map, &raw_acct_map, &acct_map)?, TxType::Exchange);
             assert_eq!(txn.action_record_idx_vec.len(), 2);
 
@@ -152,6 +178,7 @@ pub(crate) fn create_lots_and_movements(
                         lot_number: base_number_of_lots + 1,
                         account_key: the_raw_pair_keys.0,
                         movements: RefCell::new([].to_vec()),
+                        inherited: false,
                     }
                 );
                 quote_lot = Rc::new(
@@ -162,6 +189,7 @@ pub(crate) fn create_lots_and_movements(
                         lot_number: quote_number_of_lots + 1,
                         account_key: the_raw_pair_keys.1,
                         movements: RefCell::new([].to_vec()),
+                        inherited: false,
                     }
                 );
 
@@ -188,6 +216,7 @@ pub(crate) fn create_lots_and_movements(
                 proceeds: Cell::new(dec!(0.0)),
                 proceeds_lk: Cell::new(dec!(0.0)),
                 cost_basis_lk: Cell::new(dec!(0.0)),
+                wash_sale_adjustment: Cell::new(dec!(0.0)),
             };
             let raw_base_acct = raw_acct_map.get(&base_acct.raw_key).unwrap();
             wrap_mvmt_and_push(
@@ -211,6 +240,7 @@ pub(crate) fn create_lots_and_movements(
                 proceeds: Cell::new(dec!(0.0)),
                 proceeds_lk: Cell::new(dec!(0.0)),
                 cost_basis_lk: Cell::new(dec!(0.0)),
+                wash_sale_adjustment: Cell::new(dec!(0.0)),
             };
             let raw_quote_acct = raw_acct_map.get(&quote_acct.raw_key).unwrap();
             wrap_mvmt_and_push(
@@ -258,6 +288,7 @@ pub(crate) fn create_lots_and_movements(
                                 lot_number: 1,
                                 account_key: acct.raw_key,
                                 movements: RefCell::new([].to_vec()),
+                                inherited: false,
                             }
                         );
                         new_lot_created = true;
@@ -282,6 +313,7 @@ pub(crate) fn create_lots_and_movements(
                         proceeds: Cell::new(dec!(0.0)),
                         proceeds_lk: Cell::new(dec!(0.0)),
                         cost_basis_lk: Cell::new(dec!(0.0)),
+                        wash_sale_adjustment: Cell::new(dec!(0.0)),
                     };
                     wrap_mvmt_and_push(
                         whole_mvmt,
@@ -336,6 +368,7 @@ pub(crate) fn create_lots_and_movements(
                                 proceeds: Cell::new(dec!(0.0)),
                                 proceeds_lk: Cell::new(dec!(0.0)),
                                 cost_basis_lk: Cell::new(dec!(0.0)),
+                                wash_sale_adjustment: Cell::new(dec!(0.0)),
                             };
                             wrap_mvmt_and_push(
                                 whole_mvmt,
@@ -351,91 +384,86 @@ pub(crate) fn create_lots_and_movements(
                         } else {
 
                             if acct.list_of_lots.borrow().len() == 0 {
-                                println!("FATAL: There are zero lots to spend from in transaction:\n{:#?}",txn);
-                                std::process::exit(1);
+                                return Err(format!("Txn {} on {} spends {} {} from {}, which has no lots to spend from.",
+                                    txn_num, txn.date_as_string, -ar.amount, raw_acct.ticker, raw_acct.name).into())
                             }
 
-                            let list_of_lots_to_use = acct.list_of_lots.clone();
+                            let number_of_lots = acct.list_of_lots.borrow().len();
+                            let first_unspent_lot = *spent_lots.get(&ar.account_key).unwrap_or(&0);
 
-                            //  The following returns a Vec to be iterated from beginning to end. It provides the index for the desired `lot`.
-                            let vec_of_ordered_index_values = match chosen_costing_method {
+                            //  The following provides the indexes of the `lot`s in the order they are to be used.  Where the
+                            //  order is that of creation, the indexes aren't collected, since usually only the first few are used.
+                            let mut lot_indexes: Box<dyn Iterator<Item = usize>> = match chosen_costing_method {
                                 InventoryCostingMethod::LIFObyLotCreationDate => {
-                                    get_lifo_by_creation_date(&list_of_lots_to_use.borrow())}
+                                    Box::new((0..number_of_lots).rev())}
                                 InventoryCostingMethod::LIFObyLotBasisDate => {
-                                    get_lifo_by_lot_basis_date(&list_of_lots_to_use.borrow())}
+                                    Box::new(get_indexes_by_lot_basis_date(&acct.list_of_lots.borrow(), false).into_iter())}
                                 InventoryCostingMethod::FIFObyLotCreationDate => {
-                                    get_fifo_by_creation_date(&list_of_lots_to_use.borrow())}
+                                    Box::new(first_unspent_lot..number_of_lots)}
                                 InventoryCostingMethod::FIFObyLotBasisDate => {
-                                    get_fifo_by_lot_basis_date(&list_of_lots_to_use.borrow())}
+                                    Box::new(get_indexes_by_lot_basis_date(&acct.list_of_lots.borrow(), true).into_iter())}
+                                InventoryCostingMethod::LTfirstFIFObyLotBasisDate => {
+                                    Box::new(get_long_term_first_by_lot_basis_date(&acct.list_of_lots.borrow(), txn.date, settings.long_term_days, true).into_iter())}
+                                InventoryCostingMethod::LTfirstLIFObyLotBasisDate => {
+                                    Box::new(get_long_term_first_by_lot_basis_date(&acct.list_of_lots.borrow(), txn.date, settings.long_term_days, false).into_iter())}
+                                InventoryCostingMethod::HIFObyLotUnitBasis => {
+                                    Box::new(get_hifo_by_lot_unit_basis(&acct.list_of_lots.borrow()).into_iter())}
+                                // The order is irrelevant because the `lot`s are chosen some other way (see below).
+                                InventoryCostingMethod::AverageCostAcrossLots
+                                | InventoryCostingMethod::SpecificIdentification
+                                | InventoryCostingMethod::SharePoolingSection104
+                                | InventoryCostingMethod::AdjustedCostBase => {
+                                    Box::new(0..number_of_lots)}
                             };
 
-                            assert_eq!(vec_of_ordered_index_values.len(), list_of_lots_to_use.borrow().len());
-
-                            fn get_lifo_by_creation_date(list_of_lots: &Ref<Vec<Rc<Lot>>>) -> Vec<usize> {
-                                let mut vec_of_indexes = [].to_vec(); // TODO: Add with_capacity()
-                                for (idx, _lot) in list_of_lots.iter().enumerate() {
-                                    vec_of_indexes.insert(0, idx)
-                                }
+                            /// Lots ordered by basis date (oldest first if `fifo`).  A `lot` received in a transfer (or like-kind
+                            /// exchange) keeps its basis date, so this can differ from the order the `lot`s were created.  Lots
+                            /// with the same basis date keep the order they were created (last created first if not `fifo`).
+                            fn get_indexes_by_lot_basis_date(list_of_lots: &Ref<Vec<Rc<Lot>>>, fifo: bool) -> Vec<usize> {
+                                let mut vec_of_indexes: Vec<usize> = (0..list_of_lots.len()).collect();
+                                vec_of_indexes.sort_by_key(|idx| list_of_lots[*idx].date_for_basis_purposes);
+                                if !fifo { vec_of_indexes.reverse() }
                                 vec_of_indexes
                             }
 
-                            #[allow(suspicious_double_ref_op)]
-                            fn get_lifo_by_lot_basis_date(list_of_lots: &Ref<Vec<Rc<Lot>>>) -> Vec<usize> {
-                                let mut reordered_vec = list_of_lots.clone().to_vec();
-                                let length = reordered_vec.len();
-                                for _ in 0..length {
-                                    for j in 0..length-1 {
-                                        if reordered_vec[j].date_for_basis_purposes > reordered_vec[j+1].date_for_basis_purposes {
-                                            reordered_vec.swap(j, j+1)
-                                        }
-                                    }
-                                }
-                                let mut vec_of_indexes = [].to_vec();
-                                for (idx, _lot) in reordered_vec.iter().enumerate() {
-                                    vec_of_indexes.insert(0, idx)
-                                }
-                                vec_of_indexes
+                            /// Lots that would be long-term if disposed of on `disposal_date` come first, followed by the
+                            /// short-term lots. Within each group, lots are ordered by basis date (oldest first if `fifo`).
+                            fn get_long_term_first_by_lot_basis_date(
+                                list_of_lots: &Ref<Vec<Rc<Lot>>>,
+                                disposal_date: NaiveDate,
+                                long_term_days: i64,
+                                fifo: bool,
+                            ) -> Vec<usize> {
+                                let (mut long_term, short_term): (Vec<usize>, Vec<usize>) = get_indexes_by_lot_basis_date(list_of_lots, fifo)
+                                    .into_iter()
+                                    .partition(|idx| {
+                                        list_of_lots[*idx].term(disposal_date, long_term_days) == Term::LT
+                                    });
+                                long_term.extend(short_term);
+                                long_term
                             }
 
-                            fn get_fifo_by_creation_date(list_of_lots: &Ref<Vec<Rc<Lot>>>) -> Vec<usize> {
-                                let mut vec_of_indexes = [].to_vec();
-                                for (idx, _lot) in list_of_lots.iter().enumerate() {
-                                    vec_of_indexes.push(idx)
-                                }
+                            /// Lots with the highest cost basis per unit come first.  A `lot`'s basis per unit is that of its first
+                            /// `movement`, since every later (outgoing) `movement` takes a proportional share of that basis.
+                            /// Ties keep the order the lots were created.
+                            fn get_hifo_by_lot_unit_basis(list_of_lots: &Ref<Vec<Rc<Lot>>>) -> Vec<usize> {
+                                let unit_basis = |lot: &Lot| {
+                                    let mvmts = lot.movements.borrow();
+                                    let first_mvmt = mvmts.first().expect("Lot has no movements.");
+                                    first_mvmt.cost_basis.get() / first_mvmt.amount
+                                };
+                                let mut vec_of_indexes: Vec<usize> = (0..list_of_lots.len()).collect();
+                                vec_of_indexes.sort_by_key(|idx| std::cmp::Reverse(unit_basis(&list_of_lots[*idx])));
                                 vec_of_indexes
                             }
 
-                            #[allow(suspicious_double_ref_op)]
-                            fn get_fifo_by_lot_basis_date(list_of_lots: &Ref<Vec<Rc<Lot>>>) -> Vec<usize> {
-                                let mut reordered_vec = list_of_lots.clone().to_vec();
-                                let length = reordered_vec.len();
-                                for _ in 0..length {
-                                    for j in 0..length-1 {
-                                        if reordered_vec[j].date_for_basis_purposes > reordered_vec[j+1].date_for_basis_purposes {
-                                            reordered_vec.swap(j, j+1)
-                                        }
-                                    }
-                                }
-                                let mut vec_of_indexes = [].to_vec();
-                                for (idx, _lot) in reordered_vec.iter().enumerate() {
-                                    vec_of_indexes.push(idx)
-                                }
-                                vec_of_indexes
-                            }
-
-                            // TODO: Consider whether a for-loop can track the index more cleanly
-                            // Now that the index values of each `lot` are in the appropriate order, the starting point (index 0)
-                            // and the starting lot_index can be chosen in preparation for the recursive `fit_into_lots` function.
-                            // If the tentative `movement` must be reduced to fit into the tentative `lot`, a revised `movement` will be created
-                            // using an amount that will be reduced to the exact amount to fit into the `lot`.  After the revised `movement`
-                            // is pushed to the `lot`, the index position will be incremented to provide a new `lot_index`, a new tentative
-                            // `lot` will be chosen, and the remainder of the amount will be used in a new tentative `movement`, and so on
-                            // until the entire `action record` amount has been put into a `movement` and posted to a `lot`.
-                            let index_position: usize = 0;
-                            let lot_index = vec_of_ordered_index_values[index_position];
-
-                            // Now that the tentative `lot` can be chosen, it is, and a tentative `movement` is created.
-                            let lot_to_use = list_of_lots_to_use.borrow()[lot_index].clone();
+                            // Now that the index values of each `lot` are in the appropriate order, a tentative `movement` is
+                            // created for the `fit_into_lots` function.  If the tentative `movement` must be reduced to fit into
+                            // the tentative `lot`, a revised `movement` will be created using an amount that will be reduced to
+                            // the exact amount to fit into the `lot`.  After the revised `movement` is pushed to the `lot`, the next
+                            // `lot_index` will provide a new tentative `lot`, and the remainder of the amount will be used in a new
+                            // tentative `movement`, and so on until the entire `action record` amount has been put into a
+                            // `movement` and posted to a `lot`.
                             let whole_mvmt = Movement {
                                 amount: ar.amount,
                                 date_as_string: txn.date_as_string.clone(),
@@ -445,27 +473,66 @@ pub(crate) fn create_lots_and_movements(
                                 cost_basis: Cell::new(dec!(0.0)),
                                 ratio_of_amt_to_incoming_mvmts_in_a_r: dec!(1.0),
                                 ratio_of_amt_to_outgoing_mvmts_in_a_r: Cell::new(dec!(1.0)),
-                                lot_num: lot_to_use.lot_number,
+                                lot_num: 0,  //  Set for each `movement` as it's posted to a `lot`
                                 proceeds: Cell::new(dec!(0.0)),
                                 proceeds_lk: Cell::new(dec!(0.0)),
                                 cost_basis_lk: Cell::new(dec!(0.0)),
+                                wash_sale_adjustment: Cell::new(dec!(0.0)),
                             };
 
                             // Just a last minute check that a home currency `action record` isn't being handled here
                             assert_eq!(raw_acct.is_home_currency(&chosen_home_currency), false);
 
-                            // Beginning here, it will recursively attempt to fit the outgoing amount into `lot`s.
+                            log::debug!("Txn {} on {}: spending {} {} from {} by {}.",
+                                txn_num, txn.date_as_string, -ar.amount, raw_acct.ticker, raw_acct.name, chosen_costing_method);
+
+                            // For average cost, the outgoing amount is spread across every `lot` with a balance instead.
+                            // Share pooling and ACB do the same, though their cost basis is later taken from the pool.
+                            if chosen_costing_method.spreads_across_lots() {
+                                spread_across_lots(
+                                    whole_mvmt,
+                                    chosen_home_currency,
+                                    ar,
+                                    raw_acct,
+                                    acct,
+                                )?;
+                                continue
+                            }
+
+                            // For specific identification, the outgoing amount is posted to whichever `lot`s are picked.
+                            if *chosen_costing_method == InventoryCostingMethod::SpecificIdentification {
+                                let lot_picker = lot_picker.as_deref_mut()
+                                    .ok_or("Specific identification requires a lot picker to choose the lots for each disposal.")?;
+                                let picked_lots = specific_id::pick_lots(lot_picker, txn, ar, raw_acct, acct, settings.long_term_days)?;
+                                post_to_lots(
+                                    whole_mvmt,
+                                    picked_lots,
+                                    "as picked",
+                                    chosen_home_currency,
+                                    ar,
+                                    raw_acct,
+                                );
+                                continue
+                            }
+
+                            // Beginning here, it will attempt to fit the outgoing amount into `lot`s.
                             fit_into_lots(
                                 whole_mvmt,
                                 ar.amount,
-                                list_of_lots_to_use,
-                                vec_of_ordered_index_values,
-                                index_position,
+                                &mut lot_indexes,
                                 &chosen_home_currency,
                                 &ar,
                                 &raw_acct,
                                 &acct,
-                            );
+                            )?;
+
+                            if *chosen_costing_method == InventoryCostingMethod::FIFObyLotCreationDate {
+                                let lots = acct.list_of_lots.borrow();
+                                let spent = lots.iter().skip(first_unspent_lot)
+                                    .take_while(|lot| lot.get_sum_of_amts_in_lot() == dec!(0))
+                                    .count();
+                                spent_lots.insert(ar.account_key, first_unspent_lot + spent);
+                            }
 
                             // Once the `action record`'s outgoing amount has been "consumed", the recording of this
                             // `action record` is complete.
@@ -515,6 +582,7 @@ pub(crate) fn create_lots_and_movements(
                                         proceeds: Cell::new(dec!(0.0)),
                                         proceeds_lk: Cell::new(dec!(0.0)),
                                         cost_basis_lk: Cell::new(dec!(0.0)),
+                                        wash_sale_adjustment: Cell::new(dec!(0.0)),
                                     };
                                     wrap_mvmt_and_push(
                                         mvmt,
@@ -532,6 +600,18 @@ pub(crate) fn create_lots_and_movements(
                                 // Now the incoming `flow` `action record`s with a non-margin account are handled.
                                 } else {
 
+                                    // An increase from a rebase, treated as spreading the basis held, is split across the `lot`s
+                                    // with a balance (when there are any) rather than being posted to a single new `lot`.
+                                    if txn.kind == TxKind::Rebase && spread_rebase_across_lots(
+                                        txn,
+                                        ar,
+                                        acct,
+                                        chosen_home_currency,
+                                        raw_acct,
+                                    ) {
+                                        continue
+                                    }
+
                                     // The base case is a single-`action record` `flow` `transaction` where a `lot` is created (assigned),
                                     // a `movement` is created (assigned), and the `movement` is pushed to the `lot`.  Note that the `lot` variable
                                     // was allocated above, and this `if` section of code merely assigns this `lot` to that variable.
@@ -541,11 +621,12 @@ pub(crate) fn create_lots_and_movements(
                                             Lot {
                                                 date_as_string: txn.date_as_string.clone(),
                                                 date_of_first_mvmt_in_lot: txn.date,
-                                                date_for_basis_purposes: txn.date,
+                                                date_for_basis_purposes: txn.date_for_basis_purposes,
 
                                                 lot_number: length_of_list_of_lots as u32 + 1,
                                                 account_key: acct.raw_key,
                                                 movements: RefCell::new([].to_vec()),
+                                                inherited: txn.kind.creates_inherited_lot(),
                                             }
                                         );
                                         mvmt = Movement {
@@ -561,6 +642,7 @@ pub(crate) fn create_lots_and_movements(
                                             proceeds: Cell::new(dec!(0.0)),
                                             proceeds_lk: Cell::new(dec!(0.0)),
                                             cost_basis_lk: Cell::new(dec!(0.0)),
+                                            wash_sale_adjustment: Cell::new(dec!(0.0)),
                                         };
 
                                     // The more complicated case is the dual-`action record` `flow` `transaction`.
@@ -575,7 +657,7 @@ pub(crate) fn create_lots_and_movements(
                                         // margin buy in the `lot` in relation to all the margin buys in the the `lot`; and for each `movement` that it
                                         // creates, that new `movement` is given the basis date of the respective margin-buy's `movement`.
                                         // (For those savvy, you noted that since margin trades produce no gain/loss, there is no basis to inherit.)
-                                        if multiple_incoming_mvmts_per_ar_due_to_lk && txn.date <= like_kind_cutoff_date {
+                                        if multiple_incoming_mvmts_per_ar_due_to_lk && like_kind_scope.covers_date(like_kind_cutoff_date, txn.date) {
                                             
                                             // First, two variables are allocated to hold some intermediate results that will be used to determine the
                                             // size of `movement`(s) and how many `lot`s are needed.
@@ -628,6 +710,7 @@ pub(crate) fn create_lots_and_movements(
                                                         lot_number: acct.list_of_lots.borrow().len() as u32 + 1,
                                                         account_key: acct.raw_key,
                                                         movements: RefCell::new([].to_vec()),
+                                                        inherited: false,
                                                     }
                                                 );
                                                 let percentage_used = round_d128_1e8(&(pos_mvmt.amount/&total_positive_amounts));
@@ -645,6 +728,7 @@ pub(crate) fn create_lots_and_movements(
                                                     proceeds: Cell::new(dec!(0.0)),
                                                     proceeds_lk: Cell::new(dec!(0.0)),
                                                     cost_basis_lk: Cell::new(dec!(0.0)),
+                                                    wash_sale_adjustment: Cell::new(dec!(0.0)),
                                                 };
                                                 wrap_mvmt_and_push(
                                                     inner_mvmt,
@@ -670,6 +754,7 @@ pub(crate) fn create_lots_and_movements(
                                                     lot_number: acct.list_of_lots.borrow().len() as u32 + 1,
                                                     account_key: acct.raw_key,
                                                     movements: RefCell::new([].to_vec()),
+                                                    inherited: false,
                                                 }
                                             );
                                             mvmt = Movement {
@@ -685,6 +770,7 @@ pub(crate) fn create_lots_and_movements(
                                                 proceeds: Cell::new(dec!(0.0)),
                                                 proceeds_lk: Cell::new(dec!(0.0)),
                                                 cost_basis_lk: Cell::new(dec!(0.0)),
+                                                wash_sale_adjustment: Cell::new(dec!(0.0)),
                                             };
 
                                         // Back to "base case" style treatment, if this is an incoming dual-`action record` `flow` `transaction`, but either
@@ -695,10 +781,11 @@ pub(crate) fn create_lots_and_movements(
                                                 Lot {
                                                     date_as_string: txn.date_as_string.clone(),
                                                     date_of_first_mvmt_in_lot: txn.date,
-                                                    date_for_basis_purposes: txn.date,
+                                                    date_for_basis_purposes: txn.date_for_basis_purposes,
                                                     lot_number: length_of_list_of_lots as u32 + 1,
                                                     account_key: acct.raw_key,
                                                     movements: RefCell::new([].to_vec()),
+                                                    inherited: false,
                                                 }
                                             );
                                             mvmt = Movement {
@@ -714,6 +801,7 @@ pub(crate) fn create_lots_and_movements(
                                                 proceeds: Cell::new(dec!(0.0)),
                                                 proceeds_lk: Cell::new(dec!(0.0)),
                                                 cost_basis_lk: Cell::new(dec!(0.0)),
+                                                wash_sale_adjustment: Cell::new(dec!(0.0)),
                                             };
                                         }
                                     }
@@ -737,8 +825,24 @@ pub(crate) fn create_lots_and_movements(
                                 let whole_mvmt;
                                 let lot;
 
+                                // A token migration carries the basis date of each outgoing `movement` over, as does a
+                                // `ToSelf` transfer (or a like-kind exchange).
+                                if txn.kind == TxKind::TokenMigration {
+                                    process_multiple_incoming_lots_and_mvmts(
+                                        txn_num,
+                                        ar_map.get(txn.action_record_idx_vec.first().unwrap()).unwrap(), // outgoing
+                                        ar, // incoming
+                                        chosen_home_currency,
+                                        acct_map,
+                                        &txns_map,
+                                        ar_map,
+                                        raw_acct,
+                                    );
+                                    continue
+                                }
+
                                 // The first check is for like-kind exchange treatment is applicable to the `transaction`:
-                                if multiple_incoming_mvmts_per_ar_due_to_lk && (txn.date <= like_kind_cutoff_date) {
+                                if multiple_incoming_mvmts_per_ar_due_to_lk && like_kind_scope.covers_date(like_kind_cutoff_date, txn.date) {
 
                                     // If lk is applicable, determine whether to `process_multiple..`,
                                     // based on if each `action record` has a home currency `account`.
@@ -749,7 +853,11 @@ pub(crate) fn create_lots_and_movements(
                                     let ic_ar = ar;
                                     let ic_raw_acct = raw_acct;
                                     both_are_non_home_curr = !og_raw_acct.is_home_currency(&chosen_home_currency)
-                                    && !ic_raw_acct.is_home_currency(&chosen_home_currency);
+                                    && !ic_raw_acct.is_home_currency(&chosen_home_currency)
+                                    // (and the pair's asset classes are within the like-kind scope)
+                                    && like_kind_scope.covers_pair(&og_raw_acct.ticker, &ic_raw_acct.ticker)
+                                    // (and it isn't a sale between entities, of one asset for itself)
+                                    && txn.kind != TxKind::EntitySale;
 
                                     if both_are_non_home_curr {
                                         process_multiple_incoming_lots_and_mvmts(
@@ -770,10 +878,11 @@ pub(crate) fn create_lots_and_movements(
                                             Lot {
                                                 date_as_string: txn.date_as_string.clone(),
                                                 date_of_first_mvmt_in_lot: txn.date,
-                                                date_for_basis_purposes: txn.date,
+                                                date_for_basis_purposes: txn.date_for_basis_purposes,
                                                 lot_number: length_of_list_of_lots as u32 + 1,
                                                 account_key: acct.raw_key,
                                                 movements: RefCell::new([].to_vec()),
+                                                inherited: false,
                                             }
                                         );
                                         whole_mvmt = Movement {
@@ -789,6 +898,7 @@ pub(crate) fn create_lots_and_movements(
                                             proceeds: Cell::new(dec!(0.0)),
                                             proceeds_lk: Cell::new(dec!(0.0)),
                                             cost_basis_lk: Cell::new(dec!(0.0)),
+                                            wash_sale_adjustment: Cell::new(dec!(0.0)),
                                         };
                                     }
                                 }
@@ -800,10 +910,11 @@ pub(crate) fn create_lots_and_movements(
                                         Lot {
                                             date_as_string: txn.date_as_string.clone(),
                                             date_of_first_mvmt_in_lot: txn.date,
-                                            date_for_basis_purposes: txn.date,
+                                            date_for_basis_purposes: txn.date_for_basis_purposes,
                                             lot_number: length_of_list_of_lots as u32 + 1,
                                             account_key: acct.raw_key,
                                             movements: RefCell::new([].to_vec()),
+                                            inherited: false,
                                         }
                                     );
                                     whole_mvmt = Movement {
@@ -819,6 +930,7 @@ pub(crate) fn create_lots_and_movements(
                                         proceeds: Cell::new(dec!(0.0)),
                                         proceeds_lk: Cell::new(dec!(0.0)),
                                         cost_basis_lk: Cell::new(dec!(0.0)),
+                                        wash_sale_adjustment: Cell::new(dec!(0.0)),
                                     };
                                 }
                                 // The `lot` and `whole_mvmt` variables have been initialized/assigned
@@ -836,8 +948,9 @@ pub(crate) fn create_lots_and_movements(
 
                                 // Based on experience, and considering how `transaction`s are constructed, this should never happen.
                                 if raw_acct.is_margin {
-                                    println!("FATAL: Consult developer. Found margin actionrecord in ToSelf transaction:\n{:#?}", txn);
-                                    std::process::exit(1);
+                                    return Err(format!("Txn {} on {} is a transfer, but moves the margin account {}, which \
+                                        shouldn't be possible. Please consult the developer.", txn_num, txn.date_as_string,
+                                        raw_acct.name).into())
 
                                 // When transferring to oneself, the amounts should carry over proportionally (considering the incoming `movement`
                                 // is likely to be less than the outgoing `movement` due to transaction fees), as should the basis date of each of the
@@ -949,124 +1062,189 @@ fn wrap_mvmt_and_push(
     let amt2 = round_d128_1e8(&amt);
     assert_eq!(amt, amt2);
 
+    log::trace!("Txn {}: posted {} {} to lot {} (basis date {}).",
+        this_mvmt.transaction_key, this_mvmt.amount, raw_acct.ticker, lot.lot_number, lot.date_for_basis_purposes);
+
     let mvmt = Rc::from(this_mvmt);
     lot.movements.borrow_mut().push(mvmt.clone());
     ar.movements.borrow_mut().push(mvmt);
 }
 
-/// Recursively check the balance in a `lot`, and if not zero then create a `movement` that is the lesser of
-/// the `mvmt_to_fit` or the balance of the `lot`; and if the `lot` balance is smaller than the amount of
-/// the `mvmt_to_fit`, then create a `movement` that will fit into that `lot` and push it to that `lot`; and
-/// then select the next `lot` and replace the `mvmt_to_fit` with a new `mvmt_to_fit` (reduced by the one
-/// that was pushed to the previous `lot`), and recursively check...
+/// Logs (as a debug message) that `amt` of the `lot`, whose balance was `balance`, was drawn for the disposal of `txn_num`,
+/// and why (`reason`).
+fn log_lot_drawn(txn_num: u32, amt: Decimal, balance: Decimal, lot: &Lot, ticker: &str, reason: &str) {
+    log::debug!("  Txn {}: drew {} {} from lot {} (basis date {}; balance {}, leaving {}), {}.",
+        txn_num, -amt, ticker, lot.lot_number, lot.date_for_basis_purposes, balance, balance + amt, reason);
+}
+
+/// Check the balance in each `lot` in the order of `lot_indexes`, and if not zero then create a `movement` that
+/// is the lesser of the `mvmt_to_fit` or the balance of the `lot`; and if the `lot` balance is smaller than the
+/// amount of the `mvmt_to_fit`, then create a `movement` that will fit into that `lot` and push it to that `lot`;
+/// and then select the next `lot` and reduce the amount to fit by the one that was pushed to the previous `lot`,
+/// and check again...
 fn fit_into_lots(
     mvmt_to_fit: Movement,
     amt_to_fit: Decimal,
-    list_of_lots_to_use: RefCell<Vec<Rc<Lot>>>,
-    vec_of_ordered_index_values: Vec<usize>,
-    index_position: usize,
+    lot_indexes: &mut dyn Iterator<Item = usize>,
     chosen_home_currency: &str,
     ar: &ActionRecord,
     raw_acct: &RawAccount,
     acct: &Account,
-) {
+) -> Result<(), Box<dyn Error>> {
 
-    let mut current_index_position = index_position;
-
-    // Here is a check to make sure the `lot` will exist. If it won't, then there will be an index
-    // out of bounds error. The account balance should be zero in that case, but it is checked
-    // anyway before printing the error message for the user and exiting.
-    if vec_of_ordered_index_values.len() == current_index_position {
-        println!("FATAL: Txn {} on {} spending {} {} has run out of lots to spend from.",
-            mvmt_to_fit.transaction_key, mvmt_to_fit.date_as_string, ar.amount, raw_acct.ticker);
-        let bal = if acct.get_sum_of_amts_in_lots() == dec!(0) { "0.00000000".to_string() }
-            else { acct.get_sum_of_amts_in_lots().to_string() };
-        println!("Account balance is only: {}", bal);
-        std::process::exit(1);
-    }
+    let mut amt_to_fit = amt_to_fit;
 
-    // Get the `lot`, and then get its balance to see how much room there is
-    let lot_index = vec_of_ordered_index_values[current_index_position];
-    let lot = acct.list_of_lots.borrow()[lot_index].clone();
-    let mut sum_of_mvmts_in_lot: Decimal = dec!(0.0);
-    for movement in lot.movements.borrow().iter() {
-        sum_of_mvmts_in_lot += movement.amount;
-    }
+    loop {
 
-    assert!(sum_of_mvmts_in_lot >= dec!(0.0));
+        // Here is a check to make sure the `lot` will exist. The account balance should be zero in that case,
+        // but it is checked anyway, for the error message.
+        let lot_index = match lot_indexes.next() {
+            Some(lot_index) => lot_index,
+            None => {
+                let bal = if acct.get_sum_of_amts_in_lots() == dec!(0) { "0.00000000".to_string() }
+                    else { acct.get_sum_of_amts_in_lots().to_string() };
+                return Err(format!("Txn {} on {} spending {} {} has run out of lots to spend from. The account balance \
+                    is only {}.", mvmt_to_fit.transaction_key, mvmt_to_fit.date_as_string, ar.amount, raw_acct.ticker, bal).into())
+            }
+        };
 
-    //  If the `lot` is "full", try the next.
-    if sum_of_mvmts_in_lot == dec!(0.0) {
+        // Get the `lot`, and then get its balance to see how much room there is
+        let lot = acct.list_of_lots.borrow()[lot_index].clone();
+        let mut sum_of_mvmts_in_lot: Decimal = dec!(0.0);
+        for movement in lot.movements.borrow().iter() {
+            sum_of_mvmts_in_lot += movement.amount;
+        }
 
-        current_index_position += 1;
+        assert!(sum_of_mvmts_in_lot >= dec!(0.0));
 
-        fit_into_lots(
-            mvmt_to_fit,
-            amt_to_fit,
-            list_of_lots_to_use,
-            vec_of_ordered_index_values,
-            current_index_position,
-            chosen_home_currency,
-            ar,
-            raw_acct,
-            acct,
-        );
-        return;
-    }
+        //  If the `lot` is "full", try the next.
+        if sum_of_mvmts_in_lot == dec!(0.0) {
+            log::trace!("  Txn {}: lot {} is spent, so the next lot in order is tried.", mvmt_to_fit.transaction_key, lot.lot_number);
+            continue
+        }
 
-    assert!(sum_of_mvmts_in_lot > dec!(0.0));
+        // If `remainder_amt_to_recurse` is positive, it means the `lot` balance exceeded `amt_to_fit`,
+        // therefore, the amount completely fits in the `lot`.  If negative, it is the `amt_to_fit`
+        // for the next `lot`.
+        let remainder_amt_to_recurse = (amt_to_fit + sum_of_mvmts_in_lot).round_dp(8);
 
-    // If `remainder_amt_to_recurse` is positive, it means the `lot` balance exceeded `amt_to_fit`,
-    // therefore, the amount completely fits in the `lot`.  If negative, it is passed as the `amt_to_fit`
-    // for the next round of recursion.
-    let remainder_amt_to_recurse = (amt_to_fit + sum_of_mvmts_in_lot).round_dp(8);
+        // If the remainder fits, the `movement` is wrapped/pushed, and the fitting is complete.
+        if remainder_amt_to_recurse >= dec!(0.0) {
 
-    // If the remainder fits, the `movement` is wrapped/pushed, and the recursion is complete.
-    if remainder_amt_to_recurse >= dec!(0.0) {
+            let remainder_mvmt_that_fits: Movement = Movement {
+                amount: amt_to_fit,
+                lot_num: lot.lot_number,
+                ..mvmt_to_fit
+            };
+            log_lot_drawn(remainder_mvmt_that_fits.transaction_key, amt_to_fit, sum_of_mvmts_in_lot, &lot, &raw_acct.ticker,
+                "the next lot in order with a balance");
+            wrap_mvmt_and_push(
+                remainder_mvmt_that_fits,
+                ar,
+                &lot,
+                chosen_home_currency,
+                raw_acct
+            );
+            return Ok(())
+        }
 
-        let remainder_mvmt_that_fits: Movement = Movement {
-            amount: amt_to_fit,
+        // The amt_to_fit doesn't completely fit in the present `lot`, but some does. Create a `movement` that will fit.
+        let mvmt_that_fits_in_lot: Movement = Movement {
+            amount: (-sum_of_mvmts_in_lot).round_dp(8),
             lot_num: lot.lot_number,
-            ..mvmt_to_fit
+            ..mvmt_to_fit.clone()
         };
+        log_lot_drawn(mvmt_that_fits_in_lot.transaction_key, mvmt_that_fits_in_lot.amount, sum_of_mvmts_in_lot, &lot,
+            &raw_acct.ticker, "the next lot in order with a balance (all of it)");
         wrap_mvmt_and_push(
-            remainder_mvmt_that_fits,
-            &ar,
+            mvmt_that_fits_in_lot,
+            ar,
             &lot,
-            &chosen_home_currency,
-            &raw_acct
+            chosen_home_currency,
+            raw_acct
         );
-        return
+
+        // After applying some of the `amt_to_fit` to the `lot`, take the remainder to the next `lot`
+        amt_to_fit = remainder_amt_to_recurse.round_dp(8);
+    }
+}
+
+/// Splits the outgoing `mvmt_to_spread` across every `lot` with a balance, in proportion to each `lot`'s share of the
+/// `account` balance.  Since each resulting `movement` takes a proportional share of its `lot`'s basis, the disposal as
+/// a whole takes the weighted average cost basis of the `account`.  The largest `lot` absorbs any rounding.
+fn spread_across_lots(
+    mvmt_to_spread: Movement,
+    chosen_home_currency: &str,
+    ar: &ActionRecord,
+    raw_acct: &RawAccount,
+    acct: &Account,
+) -> Result<(), Box<dyn Error>> {
+
+    let open_lots: Vec<(Rc<Lot>, Decimal)> = acct.list_of_lots.borrow().iter()
+        .map(|lot| (lot.clone(), lot.get_sum_of_amts_in_lot()))
+        .filter(|(_lot, balance)| *balance > dec!(0))
+        .collect();
+
+    let acct_balance: Decimal = open_lots.iter().map(|(_lot, balance)| *balance).sum();
+
+    if acct_balance + ar.amount < dec!(0) {
+        return Err(format!("Txn {} on {} spending {} {} has run out of lots to spend from. The account balance is only \
+            {}.", mvmt_to_spread.transaction_key, mvmt_to_spread.date_as_string, ar.amount, raw_acct.ticker, acct_balance).into())
     }
 
-    // The amt_to_fit doesn't completely fit in the present `lot`, but some does. Create a `movement` that will fit.
-    let mvmt_that_fits_in_lot: Movement = Movement {
-        amount: (-sum_of_mvmts_in_lot).round_dp(8),
-        lot_num: lot.lot_number,
-        ..mvmt_to_fit.clone()
-    };
-    wrap_mvmt_and_push(
-        mvmt_that_fits_in_lot,
-        &ar,
-        &lot,
-        &chosen_home_currency,
-        &raw_acct
-    );
+    let largest_idx = open_lots.iter().enumerate()
+        .max_by_key(|(_idx, (_lot, balance))| *balance)
+        .map(|(idx, _)| idx)
+        .unwrap();
+
+    let mut amts: Vec<Decimal> = open_lots.iter()
+        .map(|(_lot, balance)| round_d128_1e8(&(ar.amount * balance / acct_balance)))
+        .collect();
+    let sum_of_others: Decimal = amts.iter().enumerate()
+        .filter(|(idx, _amt)| *idx != largest_idx)
+        .map(|(_idx, amt)| *amt)
+        .sum();
+    amts[largest_idx] = ar.amount - sum_of_others;
+
+    let lots_and_amts = open_lots.into_iter()
+        .map(|(lot, _balance)| lot)
+        .zip(amts)
+        .filter(|(_lot, amt)| *amt != dec!(0))  //  Due to rounding, this could be zero.
+        .collect();
+
+    post_to_lots(mvmt_to_spread, lots_and_amts, "in proportion to its share of the balance", chosen_home_currency, ar, raw_acct);
+
+    Ok(())
+}
 
-    current_index_position += 1;
-
-    // After applying some of the `amt_to_fit` to the `lot`, increment the index, take the remainder, and recurse
-    fit_into_lots(
-        mvmt_to_fit,
-        remainder_amt_to_recurse.round_dp(8),  //  This was updated before recursing
-        list_of_lots_to_use,
-        vec_of_ordered_index_values,
-        current_index_position,     //  This was updated before recursing
-        chosen_home_currency,
-        ar,
-        raw_acct,
-        acct,
-    );
+/// Posts a `movement` of each (negative) amount in `lots_and_amts` to its `lot`, based on `mvmt_to_post`.  The
+/// `reason` each `lot` was drawn from is logged.
+fn post_to_lots(
+    mvmt_to_post: Movement,
+    lots_and_amts: Vec<(Rc<Lot>, Decimal)>,
+    reason: &str,
+    chosen_home_currency: &str,
+    ar: &ActionRecord,
+    raw_acct: &RawAccount,
+) {
+
+    for (lot, amt) in lots_and_amts.iter() {
+
+        log_lot_drawn(mvmt_to_post.transaction_key, *amt, lot.get_sum_of_amts_in_lot(), lot, &raw_acct.ticker, reason);
+
+        let mvmt = Movement {
+            amount: *amt,
+            lot_num: lot.lot_number,
+            ..mvmt_to_post.clone()
+        };
+        wrap_mvmt_and_push(
+            mvmt,
+            ar,
+            lot,
+            chosen_home_currency,
+            raw_acct,
+        );
+    }
 }
 
 /// This is for the surprisingly common occasion (not surprising once you think about it) when an
@@ -1090,7 +1268,7 @@ fn process_multiple_incoming_lots_and_mvmts(
     let mut all_but_last_incoming_mvmt_amt = dec!(0.0);
     let mut all_but_last_incoming_mvmt_ratio = dec!(0.0);
     // println!("Txn date: {}. Outgoing mvmts: {}, Outgoing amount: {}", txn.date, outgoing_ar.movements.borrow().len(), outgoing_ar.amount);
-    let list_of_mvmts_of_outgoing_ar = outgoing_ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+    let list_of_mvmts_of_outgoing_ar = outgoing_ar.get_mvmts_in_ar_in_lot_date_order();
     let list_of_mvmts_of_outgoing_ar_len = list_of_mvmts_of_outgoing_ar.len();
     let final_og_mvmt = list_of_mvmts_of_outgoing_ar.last().unwrap();
     //  First iteration, for all but final movement
@@ -1115,6 +1293,7 @@ fn process_multiple_incoming_lots_and_mvmts(
                 lot_number: length_of_list_of_lots as u32 + 1,
                 account_key: this_acct.raw_key,
                 movements: RefCell::new([].to_vec()),
+                inherited: outgoing_mvmt.get_lot(acct_map, ar_map).inherited,
             }
         )
         ;
@@ -1131,6 +1310,7 @@ fn process_multiple_incoming_lots_and_mvmts(
             proceeds: Cell::new(dec!(0.0)),
             proceeds_lk: Cell::new(dec!(0.0)),
             cost_basis_lk: Cell::new(dec!(0.0)),
+            wash_sale_adjustment: Cell::new(dec!(0.0)),
         };
         // println!("From first set of incoming movements, amount: {} {} to account: {}",
         //     incoming_mvmt.amount, acct_incoming_ar.ticker, acct_incoming_ar.account_num);
@@ -1160,6 +1340,7 @@ fn process_multiple_incoming_lots_and_mvmts(
             lot_number: length_of_list_of_lots as u32 + 1,
             account_key: this_acct.raw_key,
             movements: RefCell::new([].to_vec()),
+            inherited: final_og_mvmt.get_lot(acct_map, ar_map).inherited,
         }
     )
     ;
@@ -1176,6 +1357,7 @@ fn process_multiple_incoming_lots_and_mvmts(
         proceeds: Cell::new(dec!(0.0)),
         proceeds_lk: Cell::new(dec!(0.0)),
         cost_basis_lk: Cell::new(dec!(0.0)),
+        wash_sale_adjustment: Cell::new(dec!(0.0)),
     };
     // println!("Final incoming mvmt for this actionrecord, amount: {} {} to account: {}",
     //     incoming_mvmt.amount, acct_incoming_ar.ticker, acct_incoming_ar.account_num);
@@ -1188,3 +1370,75 @@ fn process_multiple_incoming_lots_and_mvmts(
     );
     this_acct.list_of_lots.borrow_mut().push(lot);
 }
+
+/// Splits the increase of a rebase (one spreading the basis held, rather than income) into a new `lot` for each `lot`
+/// with a balance in `acct`, in proportion to that balance, and with that `lot`'s basis date.  Since the rebase has no
+/// `proceeds`, each new `lot` has no basis.  Returns false, having posted nothing, if no `lot` has a balance.
+fn spread_rebase_across_lots(
+    txn: &Transaction,
+    ar: &ActionRecord,
+    acct: &Account,
+    chosen_home_currency: &str,
+    raw_acct: &RawAccount,
+) -> bool {
+
+    let lots_with_balance: Vec<(Rc<Lot>, Decimal)> = acct.list_of_lots.borrow().iter()
+        .map(|lot| (lot.clone(), lot.get_sum_of_amts_in_lot()))
+        .filter(|(_lot, balance)| *balance > dec!(0))
+        .collect();
+
+    if lots_with_balance.is_empty() { return false }
+
+    let total_balance: Decimal = lots_with_balance.iter().map(|(_lot, balance)| *balance).sum();
+    let mut amounts_used = dec!(0);
+    let mut ratios_used = dec!(0);
+
+    for (idx, (lot_with_balance, balance)) in lots_with_balance.iter().enumerate() {
+
+        //  The final `lot` takes what remains, so that rounding doesn't leave any of the increase unposted
+        let (amount, ratio) = match idx == lots_with_balance.len() - 1 {
+            true => (ar.amount - amounts_used, dec!(1) - ratios_used),
+            false => (
+                round_d128_1e8(&(ar.amount * balance / total_balance)),
+                round_d128_1e8(&(balance / total_balance)),
+            ),
+        };
+
+        if amount == dec!(0) { continue }  //  Due to rounding, this could be zero.
+
+        amounts_used += amount;
+        ratios_used += ratio;
+
+        let lot = Rc::new(
+            Lot {
+                date_as_string: txn.date_as_string.clone(),
+                date_of_first_mvmt_in_lot: txn.date,
+                date_for_basis_purposes: lot_with_balance.date_for_basis_purposes,
+                lot_number: acct.list_of_lots.borrow().len() as u32 + 1,
+                account_key: acct.raw_key,
+                movements: RefCell::new([].to_vec()),
+                inherited: lot_with_balance.inherited,
+            }
+        );
+        let mvmt = Movement {
+            amount,
+            date_as_string: txn.date_as_string.clone(),
+            date: txn.date,
+            transaction_key: txn.tx_number,
+            action_record_key: ar.self_ar_key,
+            cost_basis: Cell::new(dec!(0.0)),
+            ratio_of_amt_to_incoming_mvmts_in_a_r: ratio,
+            ratio_of_amt_to_outgoing_mvmts_in_a_r: Cell::new(dec!(1.0)),
+            lot_num: lot.lot_number,
+            proceeds: Cell::new(dec!(0.0)),
+            proceeds_lk: Cell::new(dec!(0.0)),
+            cost_basis_lk: Cell::new(dec!(0.0)),
+            wash_sale_adjustment: Cell::new(dec!(0.0)),
+        };
+
+        wrap_mvmt_and_push(mvmt, ar, &lot, chosen_home_currency, raw_acct);
+        acct.list_of_lots.borrow_mut().push(lot);
+    }
+
+    true
+}
@@ -0,0 +1,194 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::decimal_utils::ReportLocale;
+
+
+/// The delimiters a CSV Input File is looked for with, by `CsvDelimiter::Auto`, in order of preference.
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// How much of the start of a CSV Input File its delimiter and quote are detected from, in bytes.
+pub(crate) const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// The delimiter of the fields of a CSV Input File.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsvDelimiter {
+    /// Whichever of a comma, semicolon, tab or vertical bar splits the first rows of the file into the same number of
+    /// fields (see `detect()`), and a comma if none does (default).
+    Auto,
+    Char(u8),
+}
+
+impl FromStr for CsvDelimiter {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            _ if s.eq_ignore_ascii_case("auto") => Ok(CsvDelimiter::Auto),
+            _ if s.eq_ignore_ascii_case("tab") || s == "\t" || s == "\\t" => Ok(CsvDelimiter::Char(b'\t')),
+            "," | ";" | "|" => Ok(CsvDelimiter::Char(s.as_bytes()[0])),
+            _ => Err(format!("The CSV delimiter ({}) must be auto, `,`, `;`, `|` or tab.", s)),
+        }
+    }
+}
+
+/// The delimiter and quote of a CSV Input File, and whether its numbers have a decimal comma.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub decimal_comma: bool,
+}
+
+impl CsvDialect {
+
+    /// Whether the file is already read as it is: delimited by commas and quoted by double quotes, with decimal points.
+    pub fn is_standard(&self) -> bool {
+        self.delimiter == b',' && self.quote == b'"' && !self.decimal_comma
+    }
+}
+
+impl fmt::Display for CsvDialect {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.delimiter {
+            b'\t' => write!(f, "tab-delimited")?,
+            delimiter => write!(f, "delimited by `{}`", delimiter as char)?,
+        }
+        write!(f, ", quoted by `{}`", self.quote as char)?;
+        if self.decimal_comma { write!(f, ", with decimal commas")? }
+        Ok(())
+    }
+}
+
+/// The dialect of the file that begins with the `sample`: its `delimiter` (or, if `Auto`, the one detected), the quote
+/// detected, and a decimal comma if the `locale` writes numbers so.
+pub fn detect(sample: &[u8], delimiter: CsvDelimiter, locale: ReportLocale) -> CsvDialect {
+
+    //  The last line of the sample may be cut short, so it's left out if there are others
+    let text = String::from_utf8_lossy(sample);
+    let mut lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() > 1 && !sample.ends_with(b"\n") { lines.pop(); }
+
+    let quote = detect_quote(&lines);
+
+    let delimiter = match delimiter {
+        CsvDelimiter::Char(delimiter) => delimiter,
+        CsvDelimiter::Auto => DELIMITERS.iter()
+            .filter_map(|&delimiter| {
+                let counts: Vec<usize> = lines.iter().map(|line| count_unquoted(line, delimiter, quote)).collect();
+                let consistent = counts.first().is_some_and(|&first| first > 0 && counts.iter().all(|&count| count == first));
+                consistent.then(|| (delimiter, counts[0]))
+            })
+            //  The delimiter splitting the rows into the most fields, and the first of those, on a tie
+            .fold(None, |best: Option<(u8, usize)>, (delimiter, count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((delimiter, count)),
+            })
+            .map_or(b',', |(delimiter, _)| delimiter),
+    };
+
+    CsvDialect { delimiter, quote, decimal_comma: locale == ReportLocale::CommaDecimal }
+}
+
+/// A single quote, if a field of the `lines` is quoted by them and none by double quotes, or else a double quote.
+fn detect_quote(lines: &[&str]) -> u8 {
+
+    let quoted_by = |quote: u8| lines.iter().any(|line| DELIMITERS.iter().any(|&delimiter| has_quoted_field(line, delimiter, quote)));
+
+    if quoted_by(b'\'') && !quoted_by(b'"') { b'\'' } else { b'"' }
+}
+
+/// Whether a field of the `line` (delimited by the `delimiter`) begins with a `quote` that's closed at the end of a
+/// field (which may hold the delimiter).
+fn has_quoted_field(line: &str, delimiter: u8, quote: u8) -> bool {
+
+    let bytes = line.as_bytes();
+    let opens = |idx: usize| bytes[idx] == quote && (idx == 0 || bytes[idx - 1] == delimiter);
+    let closes = |idx: usize| bytes[idx] == quote && (idx + 1 == bytes.len() || bytes[idx + 1] == delimiter);
+
+    (0..bytes.len()).filter(|&idx| opens(idx)).any(|open| (open + 1..bytes.len()).any(closes))
+}
+
+/// The number of `delimiter`s in the `line` outside of a field quoted by `quote`.
+fn count_unquoted(line: &str, delimiter: u8, quote: u8) -> usize {
+
+    let mut quoted = false;
+    let mut count = 0;
+
+    for byte in line.bytes() {
+        if byte == quote { quoted = !quoted }
+        else if byte == delimiter && !quoted { count += 1 }
+    }
+
+    count
+}
+
+/// Rewrites the CSV read from `input`, of the `dialect`, as a standard CSV, to `output`.  With a decimal comma, each
+/// field that's a number (i.e., `-1.234,56`) is written with a decimal point and without its thousands separators
+/// (`-1234.56`).
+pub fn to_standard<W: Write + 'static>(input: impl Read, dialect: &CsvDialect, output: W) -> Result<W, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .from_reader(input);
+
+    let mut wtr = csv::WriterBuilder::new().flexible(true).from_writer(output);
+
+    for result in rdr.records() {
+        let record = result?;
+        match dialect.decimal_comma {
+            true => wtr.write_record(record.iter().map(|field| decimal_point(field).unwrap_or_else(|| field.to_string())))?,
+            false => wtr.write_record(&record)?,
+        }
+    }
+
+    Ok(wtr.into_inner()?)
+}
+
+/// The `field` with a decimal point, if it's a number written with a decimal comma (and any periods grouping its
+/// thousands): `1,5`, `-1.234`, `(1.234,56)` or `1.234,56`, but not `1.5` or `01.02.2020`.
+fn decimal_point(field: &str) -> Option<String> {
+
+    let trimmed = field.trim();
+
+    //  A negative number in the 'Accounting' format
+    if let Some(inner) = trimmed.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        return decimal_point(inner).map(|number| format!("({})", number))
+    }
+    let (sign, number) = match trimmed.strip_prefix('-') {
+        Some(number) => ("-", number),
+        None => ("", trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (whole, fraction) = match number.split_once(',') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (number, None),
+    };
+
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let mut groups = whole.split('.');
+    let first = groups.next().unwrap_or("");
+    let grouped = groups.clone().count() > 0;
+
+    let whole_is_number = is_digits(first)
+        && (!grouped || (first.len() <= 3 && groups.all(|group| group.len() == 3 && is_digits(group))));
+
+    if !whole_is_number || fraction.is_some_and(|fraction| !is_digits(fraction)) { return None }
+    if !grouped && fraction.is_none() { return None }
+
+    let whole = whole.replace('.', "");
+
+    Some(match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, whole, fraction),
+        None => format!("{}{}", sign, whole),
+    })
+}
@@ -2,75 +2,461 @@
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
 use std::error::Error;
-use std::process;
 use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Instant;
 
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crate::transaction::{Transaction, ActionRecord};
+use crate::transaction::{Transaction, ActionRecord, IncomeType, Polarity, TxKind};
 use crate::account::{Account, RawAccount};
-use crate::decimal_utils::round_d128_1e8;
+use crate::decimal_utils::{round_d128_1e2, round_d128_1e8};
+use crate::core_functions::{ImportProcessParameters, Timings};
+use crate::importers;
+use crate::csv_dialect;
+use crate::encryption::{self, Encryption};
+use crate::accounts_file;
+use crate::long_schema;
+use crate::spill_files::{self, IntermediateCsv, SpillWriter};
+use crate::non_sale_disposals;
+use crate::tags;
+use crate::timestamps;
+use crate::corrections::{self, Correction, CorrectedField};
+use crate::dedupe::{DedupeMode, DuplicateFinder};
+use crate::input_error::InputError;
 
 
+/// The CSV Input Files, each opened for reading, along with the name it's referred to by.
+pub type InputFiles = Vec<(String, Box<dyn Read>)>;
+
+/// Opens each of the CSV Input Files (see `open_input_file()`).
+pub(crate) fn open_input_files(
+    import_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+) -> Result<InputFiles, Box<dyn Error>> {
+
+    let mut inputs: InputFiles = Vec::with_capacity(import_file_paths.len());
+
+    for import_file_path in import_file_paths.iter() {
+        inputs.push((import_file_path.display().to_string(), open_input_file(import_file_path, settings)?));
+    }
+
+    Ok(inputs)
+}
+
+/// Imports the CSV Input File(s), each read from its reader.  Several files are first merged into one (see
+/// `merge_input_files()`).
 pub fn import_from_csv(
-    import_file_path: PathBuf,
-    iso_date_style: bool,
-    separator: &String,
+    mut inputs: InputFiles,
+    settings: &ImportProcessParameters,
     raw_acct_map: &mut HashMap<u16, RawAccount>,
     acct_map: &mut HashMap<u16, Account>,
     action_records: &mut HashMap<u32, ActionRecord>,
     transactions_map: &mut HashMap<u32, Transaction>,
+    timings: &mut Timings,
 ) -> Result<(), Box<dyn Error>> {
 
-    let file = match File::open(import_file_path) {
-        Ok(x) => {
-            // println!("\nCSV ledger file opened successfully.\n");
-            x
-        },
-        Err(e) => {
-            println!("Invalid import_file_path");
-            eprintln!("System error: {}", e);
-            std::process::exit(1);
-        }
+    let input: Box<dyn Read> = if inputs.len() == 1 {
+        inputs.pop().unwrap().1
+    } else {
+        let start = Instant::now();
+        let merged = merge_input_files(inputs, settings)?;
+        timings.record("Import: merge input files", start);
+        merged
     };
 
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(true)
-        .from_reader(file);
+        .from_reader(input);
+
+    let start = Instant::now();
 
-    import_accounts(&mut rdr, raw_acct_map, acct_map)?;
+    let columns = import_accounts(&mut rdr, settings, raw_acct_map, acct_map)?;
+
+    if let Some(accounts_file) = &settings.accounts_file { accounts_file.check_accounts(raw_acct_map)? }
+
+    timings.record("Import: account setup", start);
+
+    let corrections = match &settings.corrections {
+        Some(path) if path.exists() => corrections::import_corrections(path)
+            .map_err(|e| format!("Couldn't read the corrections file ({}): {}", path.display(), e))?,
+        _ => Vec::new(),
+    };
+
+    let start = Instant::now();
 
     import_transactions(
         &mut rdr,
-        iso_date_style,
-        &separator,
+        settings,
+        &columns,
+        &corrections,
         action_records,
         transactions_map,
+        timings,
     )?;
 
+    if !corrections.is_empty() {
+        log::info!("  Applied {} correction(s) from {}.", corrections.len(), settings.corrections.as_ref().unwrap().display());
+    }
+
+    timings.record("Import: parse transactions", start);
+
     Ok(())
 }
 
-fn import_accounts(
-    rdr: &mut csv::Reader<File>,
+/// Standard input, read in full the first time it's opened as the CSV Input File, so that it may be read again (i.e.,
+/// to process it in a secondary home currency).
+static STDIN_INPUT: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Whether `import_file_path` is `-`, which is standard input rather than a file.
+pub fn is_stdin(import_file_path: &Path) -> bool {
+    import_file_path == Path::new("-")
+}
+
+/// The bytes read from standard input as the CSV Input File, if it has been.
+pub fn stdin_input() -> Option<&'static [u8]> {
+    STDIN_INPUT.get().map(|bytes| bytes.as_slice())
+}
+
+/// Opens a CSV Input File (or standard input, for `-`), decrypted, if it's encrypted (see `encryption`), and converted
+/// from an exchange export or remapped, if so set.
+pub(crate) fn open_input_file(import_file_path: &Path, settings: &ImportProcessParameters) -> Result<Box<dyn Read>, Box<dyn Error>> {
+
+    if is_stdin(import_file_path) {
+
+        let stdin_input = match STDIN_INPUT.get() {
+            Some(bytes) => bytes,
+            None => {
+                let mut bytes: Vec<u8> = Vec::new();
+                io::stdin().read_to_end(&mut bytes)?;
+                STDIN_INPUT.get_or_init(|| bytes)
+            }
+        };
+
+        if let Some(encryption) = Encryption::detect(stdin_input) {
+            spill_files::keep_decrypted_input_in_memory(settings);
+            let plaintext = encryption::decrypt(stdin_input, encryption, "standard input")?;
+            return input_from_reader(Cursor::new(plaintext), settings)
+        }
+
+        return input_from_reader(stdin_input.as_slice(), settings)
+    }
+
+    let file = File::open(import_file_path)
+        .map_err(|e| format!("Couldn't open the CSV Input File {}: {}", import_file_path.display(), e))?;
+
+    let mut file = match settings.low_memory {
+        true => BufReader::with_capacity(spill_files::CHUNK_SIZE, file),
+        false => BufReader::new(file),
+    };
+
+    if let Some(encryption) = Encryption::detect(file.fill_buf()?) {
+        spill_files::keep_decrypted_input_in_memory(settings);
+        let mut encrypted = Vec::new();
+        file.read_to_end(&mut encrypted)?;
+        let plaintext = encryption::decrypt(&encrypted, encryption, &import_file_path.display().to_string())?;
+        return input_from_reader(Cursor::new(plaintext), settings)
+    }
+
+    input_from_reader(file, settings)
+}
+
+/// The shape of the CSV Input File.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputSchema {
+    /// Four header rows describing the accounts, then a row per transaction with a column of quantities per account
+    /// (default).
+    Wide,
+    /// A single header row, then a row per transaction naming the accounts it moves (see
+    /// `accounts_file::rows_to_input_file()`).
+    Rows,
+    /// A single header row, then a row per leg of a transaction, grouped by their txid (see
+    /// `long_schema::long_to_input_file()`).
+    Long,
+}
+
+impl FromStr for InputSchema {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "wide" => Ok(InputSchema::Wide),
+            "rows" => Ok(InputSchema::Rows),
+            "long" => Ok(InputSchema::Long),
+            _ => Err(format!("The input schema ({}) must be wide, rows or long.", s)),
+        }
+    }
+}
+
+/// The CSV Input File read from `reader`, rewritten as a standard CSV if it's of another `CsvDialect` (i.e.,
+/// semicolon-delimited, or with decimal commas), then converted from an exchange export or another `InputSchema`, or
+/// remapped, if so set.
+pub(crate) fn input_from_reader(reader: impl Read + 'static, settings: &ImportProcessParameters) -> Result<Box<dyn Read>, Box<dyn Error>> {
+
+    let mut reader = BufReader::with_capacity(csv_dialect::SAMPLE_SIZE, reader);
+    let dialect = csv_dialect::detect(reader.fill_buf()?, settings.csv_delimiter, settings.input_locale);
+
+    if dialect.is_standard() { return converted_input(reader, settings) }
+
+    log::info!("  Reading the CSV Input File as {}.", dialect);
+
+    let standard = csv_dialect::to_standard(reader, &dialect, IntermediateCsv::create(settings, "standard")?)?;
+
+    converted_input(standard.into_reader()?, settings)
+}
+
+/// The standard CSV read from `reader`, converted from an exchange export or another `InputSchema`, or remapped, if so
+/// set.  With `low_memory`, the conversion is written straight to a temporary file (see `IntermediateCsv`).
+fn converted_input(reader: impl Read + 'static, settings: &ImportProcessParameters) -> Result<Box<dyn Read>, Box<dyn Error>> {
+
+    let output = || IntermediateCsv::create(settings, "converted");
+
+    let converted = match (settings.import_source, &settings.import_map) {
+        (Some(source), _) => importers::to_input_file(source, reader, settings, output()?)?,
+        (None, Some(import_map)) => import_map.remap(reader, output()?)?,
+        (None, None) => match settings.input_schema {
+            InputSchema::Wide => return Ok(Box::new(reader)),
+            InputSchema::Rows => accounts_file::rows_to_input_file(reader, settings.accounts_file.as_ref(), output()?)?,
+            InputSchema::Long => long_schema::long_to_input_file(reader, settings.accounts_file.as_ref(), output()?)?,
+        },
+    };
+
+    Ok(converted.into_reader()?)
+}
+
+/// Where the columns of the CSV Input File are.  The first three columns are always `txDate`, `proceeds` and `memo`.
+/// Optional columns, identified by their header, may follow `memo`.  The account columns come last.
+pub(crate) struct ColumnLayout {
+    pub(crate) settle_date: Option<usize>,
+    pub(crate) tx_type: Option<usize>,
+    pub(crate) fee: Option<usize>,
+    pub(crate) income_type: Option<usize>,
+    pub(crate) tx_time: Option<usize>,
+    pub(crate) tx_seq: Option<usize>,
+    pub(crate) tags: Option<usize>,
+    pub(crate) expense_category: Option<usize>,
+    pub(crate) payer: Option<usize>,
+    pub(crate) first_acct_col: usize,
+}
+
+impl ColumnLayout {
+
+    /// The column of the optional column (one of the `OPTIONAL_COLUMNS`), if the file has it.
+    fn optional_column(&self, column: &str) -> Option<usize> {
+        match column {
+            "settleDate" => self.settle_date,
+            "txType" => self.tx_type,
+            "fee" => self.fee,
+            "incomeType" => self.income_type,
+            "txTime" => self.tx_time,
+            "txSeq" => self.tx_seq,
+            "tags" => self.tags,
+            "expenseCategory" => self.expense_category,
+            "payer" => self.payer,
+            _ => None,
+        }
+    }
+
+    /// Whether the `record` is imported as a `Transaction` of its own, which, when zero-quantity rows are annotations
+    /// (`zero_is_blank`), it is only if it has a quantity (or one that isn't a number, which fails to import).
+    fn is_transaction_row(&self, record: &csv::StringRecord, zero_is_blank: bool) -> bool {
+        !zero_is_blank || record.iter().skip(self.first_acct_col)
+            .filter(|field| !field.is_empty())
+            .any(|field| field.replace(',', "").parse::<Decimal>().map_or(true, |amount| !amount.is_zero()))
+    }
+
+    /// The field a `Correction` of the column `col` would replace.
+    pub(crate) fn corrected_field(&self, col: usize) -> Option<CorrectedField> {
+        match col {
+            0 => Some(CorrectedField::TxDate),
+            1 => Some(CorrectedField::Proceeds),
+            2 => Some(CorrectedField::Memo),
+            _ if col >= self.first_acct_col => Some(CorrectedField::Quantity((col - self.first_acct_col + 1) as u16)),
+            _ => OPTIONAL_COLUMNS.iter().find(|column| self.optional_column(column) == Some(col))
+                .map(|column| CorrectedField::Optional(column)),
+        }
+    }
+
+    /// The `record` with the fields of any optional column or quantity replaced by their `row_corrections` (the
+    /// txDate, proceeds and memo are corrected as they're parsed).
+    fn corrected_record(&self, record: csv::StringRecord, row_corrections: &[&Correction]) -> csv::StringRecord {
+
+        let replaced: Vec<(usize, &str)> = row_corrections.iter()
+            .filter_map(|c| match c.field {
+                CorrectedField::Optional(column) => self.optional_column(column).map(|col| (col, c.value.as_str())),
+                CorrectedField::Quantity(account_num) => Some((self.first_acct_col + account_num as usize - 1, c.value.as_str())),
+                _ => None,
+            })
+            .filter(|(col, _value)| *col < record.len())
+            .collect();
+
+        if replaced.is_empty() { return record }
+
+        record.iter().enumerate()
+            .map(|(col, field)| replaced.iter().find(|(replaced_col, _)| *replaced_col == col).map_or(field, |(_, v)| v))
+            .collect()
+    }
+}
+
+/// The headers of the columns before the account columns: the three required ones, in order, then the optional ones.
+pub const REQUIRED_COLUMNS: [&str; 3] = ["txDate", "proceeds", "memo"];
+pub const OPTIONAL_COLUMNS: [&str; 9] = ["settleDate", "txType", "fee", "incomeType", "txTime", "txSeq", "tags", "expenseCategory",
+    "payer"];
+
+/// Parses the `HEADER_ALIASES` setting, such as `txDate=Date|timestamp,memo=Notes`, into a map of each alias to the
+/// column it stands for (one of `REQUIRED_COLUMNS` or `OPTIONAL_COLUMNS`).
+pub fn parse_header_aliases(value: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+
+    let mut aliases = HashMap::new();
+
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+
+        let (column, names) = entry.split_once('=')
+            .ok_or_else(|| format!("Header alias `{}` must be a column, then `=`, then its aliases (i.e., txDate=Date|timestamp).", entry))?;
+        let column = column.trim();
+
+        if !REQUIRED_COLUMNS.contains(&column) && !OPTIONAL_COLUMNS.contains(&column) {
+            return Err(format!("Header alias `{}` is for {}, but a column must be one of: {}, {}.",
+                entry, column, REQUIRED_COLUMNS.join(", "), OPTIONAL_COLUMNS.join(", ")).into())
+        }
+
+        for name in names.split('|').map(str::trim).filter(|name| !name.is_empty()) {
+            if aliases.insert(name.to_string(), column.to_string()).is_some_and(|prior| prior != column) {
+                return Err(format!("Header alias {} is given for more than one column.", name).into())
+            }
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// The spreadsheet name of the column at `idx` (i.e., `A` for 0, `AA` for 26), for pointing to a cell.
+pub(crate) fn column_name(idx: usize) -> String {
+
+    let mut name = String::new();
+    let mut n = idx + 1;
+
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        name.insert(0, (b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+
+    name
+}
+
+/// Where the columns are, from the header row (the first row): the `REQUIRED_COLUMNS` first, then any of the
+/// `OPTIONAL_COLUMNS`, then the account numbers (1, 2, 3...), each header given as itself or one of its `aliases`.
+/// Every problem with it is named in the one error: the columns missing, the headers unexpected (with their columns),
+/// and where the account numbers go wrong.
+fn column_layout(headers: &[String], aliases: &HashMap<String, String>) -> Result<ColumnLayout, Box<dyn Error>> {
+
+    let mut columns = ColumnLayout {
+        settle_date: None, tx_type: None, fee: None, income_type: None, tx_time: None, tx_seq: None,
+        tags: None, expense_category: None, payer: None, first_acct_col: 0,
+    };
+
+    let mut unexpected: Vec<String> = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+
+    let resolved = |header: &str| -> String {
+        //  A spreadsheet may begin the file with a byte order mark
+        let header = header.trim_start_matches('\u{feff}').trim();
+        aliases.get(header).cloned().unwrap_or_else(|| header.to_string())
+    };
+
+    while columns.first_acct_col < headers.len() && headers[columns.first_acct_col].trim().parse::<u16>().is_err() {
+
+        let idx = columns.first_acct_col;
+        let header = resolved(&headers[idx]);
+        let optional = OPTIONAL_COLUMNS.iter().find(|column| **column == header);
+
+        match optional {
+            _ if idx < REQUIRED_COLUMNS.len() && header == REQUIRED_COLUMNS[idx] => seen.push(REQUIRED_COLUMNS[idx]),
+            Some(column) if idx >= REQUIRED_COLUMNS.len() && !seen.contains(column) => {
+                seen.push(column);
+                let slot = match *column {
+                    "settleDate" => &mut columns.settle_date,
+                    "txType" => &mut columns.tx_type,
+                    "fee" => &mut columns.fee,
+                    "incomeType" => &mut columns.income_type,
+                    "txTime" => &mut columns.tx_time,
+                    "txSeq" => &mut columns.tx_seq,
+                    "tags" => &mut columns.tags,
+                    "expenseCategory" => &mut columns.expense_category,
+                    _ => &mut columns.payer,
+                };
+                *slot = Some(idx);
+            }
+            _ => unexpected.push(format!("{} (column {})", headers[idx].trim(), column_name(idx))),
+        }
+        columns.first_acct_col += 1;
+    }
+
+    let missing: Vec<&str> = REQUIRED_COLUMNS.iter().filter(|column| !seen.contains(column)).copied().collect();
+
+    let mut problems: Vec<String> = Vec::new();
+
+    if !missing.is_empty() {
+        problems.push(format!("It's missing the column(s): {}.", missing.join(", ")))
+    }
+    if !unexpected.is_empty() {
+        problems.push(format!("It has unexpected column(s): {}.", unexpected.join(", ")))
+    }
+
+    if columns.first_acct_col == headers.len() {
+        problems.push("It has no account columns (numbered 1, 2, 3...).".to_string())
+    }
+
+    for (idx, header) in headers.iter().enumerate().skip(columns.first_acct_col) {
+        let expected = idx - columns.first_acct_col + 1;
+        if header.trim().parse::<u16>().ok() != Some(expected as u16) {
+            problems.push(format!("Account column {} must be numbered {}, not {}, as the accounts must be numbered in \
+                order (beginning with 1).", column_name(idx), expected, header.trim()));
+            break
+        }
+    }
+
+    if problems.is_empty() { return Ok(columns) }
+
+    Err(format!("CSV Import: The header row (row 1) doesn't fit the CSV Input File. {} It must begin with {}, which may \
+        be followed by any of {}, then the account columns (numbered 1, 2, 3...).  Other headers may stand for these with \
+        HEADER_ALIASES (i.e., txDate=Date|timestamp).", problems.join(" "), REQUIRED_COLUMNS.join(", "),
+        OPTIONAL_COLUMNS.join(", ")).into())
+}
+
+pub(crate) fn import_accounts(
+    rdr: &mut csv::Reader<impl Read>,
+    settings: &ImportProcessParameters,
     raw_acct_map: &mut HashMap<u16, RawAccount>,
     acct_map: &mut HashMap<u16, Account>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<ColumnLayout, Box<dyn Error>> {
 
     let header1 = rdr.headers()?.clone();   //  account_num
+
+    // A StringRecord doesn't accept the same range indexing needed below, so a Vec of Strings will be used
+    let headerstrings: Vec<String> = header1.into_iter().map(|field| field.to_string()).collect();
+
+    // The header row is checked before anything else is read, so a problem with it is named as such
+    let columns = column_layout(&headerstrings, &settings.header_aliases)?;
+
     let mut header2: csv::StringRecord = csv::StringRecord::new();  //  name
     let mut header3: csv::StringRecord = csv::StringRecord::new();  //  ticker
     let header4: csv::StringRecord; //  is_margin
 
     // Account Creation loop.  With rdr.has_headers() set to true above, the first record here is the second row of the CSV
-    for result in rdr.records() {
+    for (idx, result) in rdr.records().enumerate() {
         //  This initial iteration through records will break after the 4th row, after accounts have been created
-        let record = result?;
+        let record = result.map_err(|e| InputError::at_row(idx + 2, e.to_string()))?;
         if header2.len() == 0 {
             header2 = record.clone();
             continue    //  After header2 is set, continue to next record
@@ -83,29 +469,16 @@ fn import_accounts(
             header4 = record.clone();
             // println!("Assigned last header, record: {:?}", record);
 
-            // A StringRecord doesn't accept the same range indexing needed below, so a Vec of Strings will be used
-            let headerstrings: Vec<String> = header1.into_iter().map(|field| field.to_string()).collect();
-
-            let acct_num_warn = "Transactions will not import correctly if account numbers in the CSV import file aren't
-ordered chronologically (i.e., beginning in column 4 - the 1st account column - the value should be 1.
-The next column's value should be 2, then 3, etc, until the final account).";
-
             // Header row variables have been set.  It's now time to set up the accounts.
-            println!("\nCreating accounts...");
+            log::info!("\nCreating accounts...");
 
             let length = &headerstrings.len();
 
-            for (idx, field) in headerstrings[3..*length].iter().enumerate() {
-
-                // Parse account numbers.
-                let account_num = field.trim().parse::<u16>().expect(&format!("Header row account number should parse into u16: {}", field));
-                // For now, their columns aren't remembered.  Instead, they must have a particular index. 0th idx is the 1st account, and so on.
-                if account_num != ((idx + 1) as u16) {
-                    println!("FATAL: CSV Import: {}", acct_num_warn);
-                    std::process::exit(1);
-                }
+            for (idx, field) in headerstrings[columns.first_acct_col..*length].iter().enumerate() {
 
-                let ind = idx+3; // Add three because the idx skips the first three 'key' columns
+                // Account numbers were checked to be in order (1, 2, 3...) by `column_layout()`
+                let account_num = field.trim().parse::<u16>()?;
+                let ind = idx + columns.first_acct_col; // The idx skips the first three 'key' columns and any optional columns
                 let name:String = header2[ind].trim().to_string();
                 let ticker:String = header3[ind].trim().to_string();   //  no .to_uppercase() b/c margin...
                 let margin_string = &header4.clone()[ind];
@@ -113,10 +486,8 @@ The next column's value should be 2, then 3, etc, until the final account).";
                 let is_margin:bool = match margin_string.to_lowercase().trim() {
                     "no" | "non" | "false" => false,
                     "yes" | "margin" | "true" => true,
-                    _ => {
-                        println!("\n FATAL: CSV Import: Couldn't parse margin value for account {} {} \n",account_num, name);
-                        process::exit(1)
-                    }
+                    _ => return Err(InputError::in_field(4, &header4, ind, format!("Couldn't parse the margin value ({}) \
+                        for account {} {}. It must be yes or no.", margin_string.trim(), account_num, name)).into()),
                 };
 
                 let just_account: RawAccount = RawAccount {
@@ -138,69 +509,385 @@ The next column's value should be 2, then 3, etc, until the final account).";
             break    //  This `break` exits this scope so `accounts` can be accessed in `import_transactions`. The rdr stays put.
         }
     };
-    Ok(())
+    Ok(columns)
+}
+
+/// Merges several CSV Input Files into one.  An account in more than one file (with the same name, ticker and margin
+/// setting) becomes a single account, and accounts are numbered in the order they first appear.  The rows of all
+/// the files are then sorted by `txDate`, keeping the order of the files (and of the rows within each file) for rows
+/// on the same date.  The optional columns of any of the files are kept.  With `low_memory` (see
+/// `spill_files::may_spill()`), each row is spilled to a temporary file as it's read, keeping only its date and where
+/// it was written, and the merged file is written to another.
+pub(crate) fn merge_input_files(
+    inputs: InputFiles,
+    settings: &ImportProcessParameters,
+) -> Result<Box<dyn Read>, Box<dyn Error>> {
+
+    let mut merged_accts: Vec<RawAccount> = Vec::new();
+    let mut has_settle_date = false;
+    let mut has_tx_type = false;
+    let mut has_fee = false;
+    let mut has_income_type = false;
+    let mut has_tx_time = false;
+    let mut has_tx_seq = false;
+    let mut has_tags = false;
+    let mut has_expense_category = false;
+    let mut has_payer = false;
+
+    //  Each file's layout, its accounts' merged numbers, and its reader, at its first transaction row
+    type FileToMerge = (String, ColumnLayout, Vec<usize>, csv::Reader<Box<dyn Read>>);
+    let mut files: Vec<FileToMerge> = Vec::with_capacity(inputs.len());
+
+    for (name, input) in inputs {
+
+        let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_reader(input);
+        let mut file_raw_accts: HashMap<u16, RawAccount> = HashMap::new();
+        let mut file_accts: HashMap<u16, Account> = HashMap::new();
+
+        let columns = import_accounts(&mut rdr, settings, &mut file_raw_accts, &mut file_accts)?;
+
+        has_settle_date |= columns.settle_date.is_some();
+        has_tx_type |= columns.tx_type.is_some();
+        has_fee |= columns.fee.is_some();
+        has_income_type |= columns.income_type.is_some();
+        has_tx_time |= columns.tx_time.is_some();
+        has_tx_seq |= columns.tx_seq.is_some();
+        has_tags |= columns.tags.is_some();
+        has_expense_category |= columns.expense_category.is_some();
+        has_payer |= columns.payer.is_some();
+
+        let mut merged_nums: Vec<usize> = Vec::with_capacity(file_raw_accts.len());
+
+        for num in 1..=file_raw_accts.len() {
+            let raw_acct = file_raw_accts.remove(&(num as u16)).unwrap();
+            let merged_idx = match merged_accts.iter().position(|acct| {
+                acct.name == raw_acct.name && acct.ticker == raw_acct.ticker && acct.is_margin == raw_acct.is_margin
+            }) {
+                Some(idx) => idx,
+                None => { merged_accts.push(raw_acct); merged_accts.len() - 1 }
+            };
+            merged_nums.push(merged_idx);
+        }
+
+        files.push((name, columns, merged_nums, rdr));
+    }
+
+    let first_acct_col = 3 + has_settle_date as usize + has_tx_type as usize + has_fee as usize
+        + has_income_type as usize + has_tx_time as usize
+        + has_tx_seq as usize + has_tags as usize + has_expense_category as usize + has_payer as usize;
+    let mut rows: Vec<(NaiveDate, Vec<String>)> = Vec::new();
+    //  With `low_memory`, the rows' dates and where in `spilled_rows` they are, instead
+    let mut spilled_rows = match spill_files::may_spill(settings) {
+        true => Some(csv::Writer::from_writer(SpillWriter::create("rows")?)),
+        false => None,
+    };
+    let mut row_places: Vec<(NaiveDate, u64, usize)> = Vec::new();
+
+    for (name, columns, merged_nums, rdr) in files.iter_mut() {
+
+        for (row_num, record) in rdr.records().enumerate() {
+
+            let record = record?;
+            let field = |idx: usize| record.get(idx).unwrap_or("").to_string();
+
+            let date = parse_input_file_date(record.get(0).unwrap_or("").trim(), settings).ok_or_else(|| {
+                format!("{}: row {} has a txDate ({}) that doesn't match the date format.",
+                    name, row_num + 5, record.get(0).unwrap_or(""))
+            })?;
+
+            let mut row = vec![field(0), field(1), field(2)];
+            if has_settle_date { row.push(columns.settle_date.map(field).unwrap_or_default()) }
+            if has_tx_type { row.push(columns.tx_type.map(field).unwrap_or_default()) }
+            if has_fee { row.push(columns.fee.map(field).unwrap_or_default()) }
+            if has_income_type { row.push(columns.income_type.map(field).unwrap_or_default()) }
+            if has_tx_time { row.push(columns.tx_time.map(field).unwrap_or_default()) }
+            if has_tx_seq { row.push(columns.tx_seq.map(field).unwrap_or_default()) }
+            if has_tags { row.push(columns.tags.map(field).unwrap_or_default()) }
+            if has_expense_category { row.push(columns.expense_category.map(field).unwrap_or_default()) }
+            if has_payer { row.push(columns.payer.map(field).unwrap_or_default()) }
+            row.resize(first_acct_col + merged_accts.len(), String::new());
+
+            for (file_idx, merged_idx) in merged_nums.iter().enumerate() {
+                row[first_acct_col + merged_idx] = field(columns.first_acct_col + file_idx);
+            }
+
+            match spilled_rows.as_mut() {
+                Some(spilled_rows) => {
+                    let start = spilled_rows.get_ref().len();
+                    spilled_rows.write_record(&row)?;
+                    spilled_rows.flush()?;
+                    row_places.push((date, start, (spilled_rows.get_ref().len() - start) as usize));
+                }
+                None => rows.push((date, row)),
+            }
+        }
+    }
+
+    //  Stable, so same-date rows stay in file order
+    rows.sort_by_key(|(date, _)| *date);
+    row_places.sort_by_key(|(date, _start, _len)| *date);
+
+    let transactions = rows.len() + row_places.len();
+
+    let header = |first: Vec<&str>, field: &dyn Fn(usize, &RawAccount) -> String| -> Vec<String> {
+        let mut header: Vec<String> = first.into_iter().map(|f| f.to_string()).collect();
+        if has_settle_date { header.push(if header[0].is_empty() { "".to_string() } else { "settleDate".to_string() }) }
+        if has_tx_type { header.push(if header[0].is_empty() { "".to_string() } else { "txType".to_string() }) }
+        if has_fee { header.push(if header[0].is_empty() { "".to_string() } else { "fee".to_string() }) }
+        if has_income_type { header.push(if header[0].is_empty() { "".to_string() } else { "incomeType".to_string() }) }
+        if has_tx_time { header.push(if header[0].is_empty() { "".to_string() } else { "txTime".to_string() }) }
+        if has_tx_seq { header.push(if header[0].is_empty() { "".to_string() } else { "txSeq".to_string() }) }
+        if has_tags { header.push(if header[0].is_empty() { "".to_string() } else { "tags".to_string() }) }
+        if has_expense_category { header.push(if header[0].is_empty() { "".to_string() } else { "expenseCategory".to_string() }) }
+        if has_payer { header.push(if header[0].is_empty() { "".to_string() } else { "payer".to_string() }) }
+        header.extend(merged_accts.iter().enumerate().map(|(idx, acct)| field(idx, acct)));
+        header
+    };
+
+    let headers = [
+        header(vec!["txDate", "proceeds", "memo"], &|idx, _| (idx + 1).to_string()),
+        header(vec!["", "", ""], &|_, acct| acct.name.clone()),
+        header(vec!["", "", ""], &|_, acct| acct.ticker.clone()),
+        header(vec!["", "", ""], &|_, acct| if acct.is_margin { "yes".to_string() } else { "no".to_string() }),
+    ];
+
+    log::info!("  Merged {} files into {} transactions across {} accounts.", files.len(), transactions, merged_accts.len());
+
+    let Some(spilled_rows) = spilled_rows else {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+        for record in headers.iter().chain(rows.iter().map(|(_date, row)| row)) {
+            wtr.write_record(record)?;
+        }
+        return Ok(Box::new(Cursor::new(wtr.into_inner()?)))
+    };
+
+    let mut wtr = csv::Writer::from_writer(SpillWriter::create("merged")?);
+    for record in headers.iter() {
+        wtr.write_record(record)?;
+    }
+
+    //  Each row is copied as it was written, in date order
+    let mut merged = wtr.into_inner()?;
+    let mut spilled_rows = spilled_rows.into_inner()?.into_reader()?;
+    let mut row_bytes: Vec<u8> = Vec::new();
+
+    for (_date, start, len) in row_places.iter() {
+        row_bytes.resize(*len, 0);
+        spilled_rows.read_at(*start, &mut row_bytes)?;
+        merged.write_all(&row_bytes)?;
+    }
+
+    Ok(Box::new(merged.into_reader()?))
+}
+
+/// The latest `txDate` of the CSV Input File at `path`, if it has any rows.
+pub fn last_txn_date(path: &Path, settings: &ImportProcessParameters) -> Result<Option<NaiveDate>, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new().has_headers(true).flexible(true).from_reader(open_input_file(path, settings)?);
+
+    //  The three rows beneath the header describe the accounts
+    let last_date = rdr.records()
+        .skip(3)
+        .filter_map(|result| result.ok().and_then(|record| parse_input_file_date(record.get(0)?.trim(), settings)))
+        .max();
+
+    Ok(last_date)
+}
+
+/// The CSV Input File at `path` with the rows of the `added` one (i.e., as synced from an exchange's API) merged into
+/// it, as `merge_input_files()` merges them.
+pub fn merge_into_input_file(
+    path: &Path,
+    added: Vec<u8>,
+    settings: &ImportProcessParameters,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+
+    let inputs: InputFiles = vec![
+        (path.display().to_string(), open_input_file(path, settings)?),
+        ("the added rows".to_string(), Box::new(Cursor::new(added))),
+    ];
+
+    let mut merged = Vec::new();
+    merge_input_files(inputs, settings)?.read_to_end(&mut merged)?;
+
+    Ok(merged)
+}
+
+/// Parses a `txDate` the way `import_transactions()` does.
+pub(crate) fn parse_input_file_date(field: &str, settings: &ImportProcessParameters) -> Option<NaiveDate> {
+
+    let separator = &settings.input_file_date_separator;
+
+    if let Some(format) = &settings.input_file_date_format {
+        return NaiveDate::parse_from_str(field, format).ok()
+    }
+
+    let (format_yy, format_yyyy) = if settings.input_file_uses_iso_date_style {
+        (format!("%y{}%m{}%d", separator, separator), format!("%Y{}%m{}%d", separator, separator))
+    } else {
+        (format!("%m{}%d{}%y", separator, separator), format!("%m{}%d{}%Y", separator, separator))
+    };
+
+    NaiveDate::parse_from_str(field, &format_yy)
+        .or_else(|_| NaiveDate::parse_from_str(field, &format_yyyy))
+        .ok()
 }
 
 fn import_transactions(
-    rdr: &mut csv::Reader<File>,
-    iso_date_style: bool,
-    separator: &String,
+    rdr: &mut csv::Reader<impl Read>,
+    settings: &ImportProcessParameters,
+    columns: &ColumnLayout,
+    corrections: &[Correction],
     action_records: &mut HashMap<u32, ActionRecord>,
     txns_map: &mut HashMap<u32, Transaction>,
+    timings: &mut Timings,
 ) -> Result<(), Box<dyn Error>> {
 
+    let separator = &settings.input_file_date_separator;
+    let date_format = settings.input_file_date_format.as_deref();
+    let settle_date_is_basis_date = settings.settle_date_is_basis_date;
+
     let mut this_tx_number = 0;
     let mut this_ar_number = 0;
     let mut changed_action_records = 0;
     let mut changed_txn_num = Vec::new();
+    let mut annotation_rows = 0;
+    let mut skipped_rows = 0;
+    let mut parked_rows = 0;
+    let mut duplicate_rows = 0;
+    let mut duplicates = DuplicateFinder::new(&settings.dedupe_key);
 
-    println!("Creating transactions...");
+    let txn_corrections = corrections::corrections_by_txn(corrections);
+    let corrections = corrections::corrections_by_row(corrections);
 
-    for result in rdr.records() {
+    //  The transactions numbered as they would be without the corrections, for the `txn_corrections`
+    let mut uncorrected_tx_number = 0;
+    let mut uncorrected_duplicates = DuplicateFinder::new(&settings.dedupe_key);
+
+    //  Rows are in date order, so a `Transaction` shares the txDate string of the one before it, if they're the same
+    let mut prior_date_as_string: Rc<str> = Rc::from("");
+
+    log::info!("Creating transactions...");
+
+    for (idx, result) in rdr.records().enumerate() {
 
         //  rdr's cursor is at row 5, which is the first transaction row
-        let record = result?;
+        let row = idx + 5;
+        let record = result.map_err(|e| InputError::at_row(row, e.to_string()))?;
+        timings.report_progress("Import: parse transactions", idx, 0);
+        let mut row_corrections: Vec<&Correction> = corrections.get(&row).cloned().unwrap_or_default();
+
+        //  A correction of the row comes before one of its transaction, so it's the one applied to a field they share
+        let is_uncorrected_txn = !txn_corrections.is_empty()
+            && !settings.parked_rows.contains(&row)
+            && columns.is_transaction_row(&record, settings.zero_quantity_rows_are_annotations);
+
+        if is_uncorrected_txn
+            && (settings.dedupe != DedupeMode::Drop || uncorrected_duplicates.check(row, &record, columns).is_none())
+        {
+            uncorrected_tx_number += 1;
+            row_corrections.extend(txn_corrections.get(&uncorrected_tx_number).into_iter().flatten());
+        }
+
+        if row_corrections.iter().any(|c| c.field == CorrectedField::Skip) {
+            skipped_rows += 1;
+            continue
+        }
+
+        if settings.parked_rows.contains(&row) {
+            parked_rows += 1;
+            continue
+        }
+
+        let record = columns.corrected_record(record, &row_corrections);
+        //  A problem with a field (naming the field a correction would replace), or with the row as a whole, for its error
+        let field_error = |col: usize, message: String| InputError {
+            field: columns.corrected_field(col),
+            ..InputError::in_field(row, &record, col, message)
+        };
+        let row_error = |message: String| InputError::in_row(row, &record, message);
+
+        if settings.dedupe != DedupeMode::Off {
+            if let Some(duplicate) = duplicates.check(row, &record, columns) {
+                duplicate_rows += 1;
+                if settings.dedupe == DedupeMode::Drop { continue }
+                log::warn!("WARNING: Row {} ({}, {}) duplicates row {}.", row, duplicate.date, duplicate.memo, duplicate.duplicate_of);
+            }
+        }
+
         this_tx_number += 1;
 
         //  First, initialize metadata fields.
         let mut this_tx_date: &str = "";
         let mut this_proceeds: &str;
         let mut this_memo: &str = "";
-        let mut proceeds_parsed = 0f32;
+        let mut this_settle_date: &str = "";
+        let mut this_tx_type: &str = "";
+        let mut this_fee: &str = "";
+        let mut this_income_type: &str = "";
+        let mut this_tx_time: &str = "";
+        let mut this_tx_seq: &str = "";
+        let mut this_tags: &str = "";
+        let mut this_expense_category: &str = "";
+        let mut this_payer: &str = "";
+        let mut proceeds_parsed = dec!(0);
+
+        //  A correction replaces a field before it is parsed, so it may fix a field that wouldn't parse
+        let corrected = |field: CorrectedField| row_corrections.iter().find(|c| c.field == field).map(|c| c.value.as_str());
 
         //  Next, create action_records.
         let mut action_records_map_keys_vec: Vec<u32> = Vec::with_capacity(2);
-        let mut outgoing_ar: Option<ActionRecord> = None;
-        let mut incoming_ar: Option<ActionRecord> = None;
-        let mut outgoing_ar_num: Option<u32> = None;
-        let mut incoming_ar_num: Option<u32> = None;
+        let mut row_action_records: Vec<ActionRecord> = Vec::with_capacity(2);
 
         for (idx, field) in record.iter().enumerate() {
 
             //  Set metadata fields on first three fields.
             if idx == 0 { this_tx_date = field; }
             else if idx == 1 {
-                let no_comma_string = field.replace(",", "");
-                proceeds_parsed = no_comma_string.parse::<f32>()?;
+                let no_comma_string = corrected(CorrectedField::Proceeds).unwrap_or(field).replace(",", "");
+                //  Blank proceeds are zero (and may be filled in by a `PriceLookup`)
+                proceeds_parsed = if no_comma_string.trim().is_empty() { dec!(0) } else {
+                    no_comma_string.trim().parse::<Decimal>()
+                        .map_err(|e| field_error(idx, format!("The proceeds ({}) aren't a number: {}.", field, e)))?
+                };
             }
 
-            else if idx == 2 { this_memo = field; }
+            else if idx == 2 { this_memo = corrected(CorrectedField::Memo).unwrap_or(field); }
+
+            else if columns.settle_date == Some(idx) { this_settle_date = field.trim(); }
+
+            else if columns.tx_type == Some(idx) { this_tx_type = field.trim(); }
+
+            else if columns.fee == Some(idx) { this_fee = field.trim(); }
+
+            else if columns.income_type == Some(idx) { this_income_type = field.trim(); }
+
+            else if columns.tx_time == Some(idx) { this_tx_time = field.trim(); }
+
+            else if columns.tx_seq == Some(idx) { this_tx_seq = field.trim(); }
+
+            else if columns.tags == Some(idx) { this_tags = field.trim(); }
+
+            else if columns.expense_category == Some(idx) { this_expense_category = field.trim(); }
+
+            else if columns.payer == Some(idx) { this_payer = field.trim(); }
+
+            else if idx < columns.first_acct_col { continue }
 
             //  Check for empty strings. If not empty, it's a value for an action_record.
             else if field != "" {
-                this_ar_number += 1;
-                let ind = idx;  //  starts at 3, which is the fourth field
-                let acct_idx = ind - 2; //  acct_num and acct_key would be idx + 1, so subtract 2 from ind to get 1
+                let ind = idx;  //  starts at first_acct_col, which is the fourth field if there are no optional columns
+                let acct_idx = ind - columns.first_acct_col + 1; //  acct_num and acct_key start at 1
                 let account_key = acct_idx as u16;
 
                 let amount_str = field.replace(",", "");
-                let amount = match amount_str.parse::<Decimal>() {
-                    Ok(x) => x,
-                    Err(e) => {
-                        println!("FATAL: Couldn't convert amount to d128 for transaction:\n{:#?}", record);
-                        println!("Error: {}", e);
-                        std::process::exit(1);}
-                };
+                let amount = amount_str.parse::<Decimal>().map_err(|e| {
+                    field_error(idx, format!("The quantity ({}) for account {} isn't a number: {}.", field, account_key, e))
+                })?;
+
+                if settings.zero_quantity_rows_are_annotations && amount.is_zero() { continue }
+
+                this_ar_number += 1;
 
                 // When parsing to a d128, it won't error; rather it'll return a NaN. It must now check for NaN,
                 // and, if found, attempt to sanitize.  These checks will convert accounting/comma format to the expected
@@ -231,25 +918,22 @@ fn import_transactions(
                 };
 
                 if amount > dec!(0.0) {
-                    incoming_ar = Some(action_record);
-                    incoming_ar_num = Some(this_ar_number);
-                    action_records_map_keys_vec.push(incoming_ar_num.unwrap())
+                    action_records_map_keys_vec.push(this_ar_number)
                 } else {
-                    outgoing_ar = Some(action_record);
-                    outgoing_ar_num = Some(this_ar_number);
-                    action_records_map_keys_vec.insert(0, outgoing_ar_num.unwrap())
+                    action_records_map_keys_vec.insert(0, this_ar_number)
                 };
+
+                row_action_records.push(action_record);
             }
         }
 
-        // Note: the rust Trait implementation of FromStr for f32 is capable of parsing:
+        // Note: the rust_decimal implementation of FromStr for Decimal (used for proceeds) is capable of parsing:
             // '3.14'
             // '-3.14'
             // '2.5E10', or equivalently, '2.5e10'
             // '2.5E-10'
             // '5.'
             // '.5', or, equivalently, '0.5'
-            // 'inf', '-inf', 'NaN'
         // Notable observations from the list:
             // (a) scientific notation is accepted
             // (b) accounting format (numbers in parens representing negative numbers) is not explicitly accepted
@@ -309,20 +993,39 @@ fn import_transactions(
         //     all_done
         // }
 
-        if let Some(incoming_ar) = incoming_ar {
-            let x = incoming_ar_num.unwrap();
-            action_records.insert(x, incoming_ar);
+        if settings.zero_quantity_rows_are_annotations && action_records_map_keys_vec.is_empty() {
+
+            //  A row without quantities only annotates the transaction on the row before it
+            this_tx_number -= 1;
+            annotation_rows += 1;
+
+            let prior_txn = txns_map.get_mut(&this_tx_number).ok_or_else(|| {
+                row_error("The first transaction row has no quantities, so there's no transaction for it to annotate.".to_string())
+            })?;
+
+            let mut annotation = this_memo.trim().to_string();
+            if proceeds_parsed != dec!(0) {
+                annotation = format!("{} ({:.2} {})", annotation, round_d128_1e2(&proceeds_parsed), settings.home_currency).trim().to_string();
+            }
+            for tag in tags::tags_in_memo(&annotation) {
+                if !prior_txn.tags.contains(&tag) { prior_txn.tags.push(tag) }
+            }
+            if !annotation.is_empty() {
+                prior_txn.user_memo = if prior_txn.user_memo.is_empty() { annotation }
+                    else { format!("{} | {}", prior_txn.user_memo, annotation) };
+            }
+
+            continue
         }
 
-        if let Some(outgoing_ar) = outgoing_ar {
-            let y = outgoing_ar_num.unwrap();
-            action_records.insert(y, outgoing_ar);
+        for action_record in row_action_records {
+            action_records.insert(action_record.self_ar_key, action_record);
         }
 
         let format_yy: String;
         let format_yyyy: String;
 
-        if iso_date_style {
+        if settings.input_file_uses_iso_date_style {
             format_yyyy = "%Y".to_owned() + separator + "%m" + separator + "%d";
             format_yy = "%y".to_owned() + separator + "%m" + separator + "%d";
         } else {
@@ -330,28 +1033,270 @@ fn import_transactions(
             format_yy = "%m".to_owned() + separator + "%d" + separator + "%y";
         }
 
-        let tx_date = NaiveDate::parse_from_str(this_tx_date, &format_yy)
-            .unwrap_or_else(|_| NaiveDate::parse_from_str(this_tx_date, &format_yyyy)
-            .expect("
-FATAL: Transaction date parsing failed. You must tell the program the format of the date in your CSV Input File. The date separator \
-is expected to be a hyphen. The dating format is expected to be \"American\" (%m-%d-%y), not ISO 8601 (%y-%m-%d). You may set different \
-date format options via command line flag, environment variable or .env file. Perhaps first run with `--help` or see `.env.example.`\n")
-        );
+        let corrected_tx_date = row_corrections.iter().find_map(|c| c.tx_date());
+        let corrected_tx_date_string = corrected_tx_date.map(|date| date.format(date_format.unwrap_or(&format_yyyy)).to_string());
+        if let Some(date_string) = &corrected_tx_date_string { this_tx_date = date_string }
+
+        let tx_date = match date_format {
+            _ if corrected_tx_date.is_some() => corrected_tx_date.unwrap(),
+            // A DATE_FORMAT takes precedence over the ISO_DATE and separator options
+            Some(format) => NaiveDate::parse_from_str(this_tx_date, format).map_err(|_| {
+                field_error(0, format!("The txDate ({}) doesn't match the DATE_FORMAT ({}).", this_tx_date, format))
+            })?,
+            None => NaiveDate::parse_from_str(this_tx_date, &format_yy)
+                .or_else(|_| NaiveDate::parse_from_str(this_tx_date, &format_yyyy))
+                .map_err(|_| field_error(0, format!("The txDate ({}) isn't a date of the expected format ({}). You must tell \
+                    the program the format of the date in your CSV Input File. The date separator is expected to be a hyphen. \
+                    The dating format is expected to be \"American\" (%m-%d-%y), not ISO 8601 (%y-%m-%d). You may set \
+                    different date format options via command line flag, environment variable or .env file. Perhaps first \
+                    run with `--help` or see `.env.example`.", this_tx_date, format_yy)))?,
+        };
+
+        // A blank txTime isn't converted to the timezone, as there's no time to convert
+        let (local_tx_date, tx_time) = if this_tx_time.is_empty() { (tx_date, None) } else {
+            match timestamps::parse_tx_time(this_tx_time) {
+                Some((time, offset)) => {
+                    let (date, time) = timestamps::local_date_time(tx_date, time, offset, settings.timezone);
+                    (date, Some(time))
+                }
+                None => return Err(field_error(columns.tx_time.unwrap(), format!("The txTime ({}) isn't HH:MM or HH:MM:SS \
+                    (optionally followed by a UTC offset).", this_tx_time)).into()),
+            }
+        };
+
+        // The txDate string is shared with the `Lot`s and `Movement`s, so it's rewritten if the timezone changed the date
+        let local_tx_date_string = (local_tx_date != tx_date)
+            .then(|| local_tx_date.format(date_format.unwrap_or(&format_yyyy)).to_string());
+        if let Some(date_string) = &local_tx_date_string { this_tx_date = date_string }
+        let tx_date = local_tx_date;
+
+        let sequence = if this_tx_seq.is_empty() { None } else {
+            let sequence = this_tx_seq.parse::<u32>().map_err(|_| {
+                field_error(columns.tx_seq.unwrap(), format!("The txSeq ({}) isn't a whole number.", this_tx_seq))
+            })?;
+            Some(sequence)
+        };
+
+        // A blank settleDate falls back to the trade date
+        let date_for_basis_purposes = if settle_date_is_basis_date && !this_settle_date.is_empty() {
+            let settle_date_col = columns.settle_date.unwrap();
+            match date_format {
+                Some(format) => NaiveDate::parse_from_str(this_settle_date, format).map_err(|_| {
+                    field_error(settle_date_col, format!("The settleDate ({}) doesn't match the DATE_FORMAT ({}).", this_settle_date, format))
+                })?,
+                None => NaiveDate::parse_from_str(this_settle_date, &format_yy)
+                    .or_else(|_| NaiveDate::parse_from_str(this_settle_date, &format_yyyy))
+                    .map_err(|_| field_error(settle_date_col, format!("The settleDate ({}) isn't a date. The settleDate column \
+                        must use the same date format as the txDate column.", this_settle_date)))?,
+            }
+        } else { tx_date };
+
+        let kind = match this_tx_type.to_ascii_lowercase().as_str() {
+            "" | "standard" => TxKind::Standard,
+            "inherited" => TxKind::Inherited,
+            "gift" => TxKind::Gift,
+            "donation" => TxKind::Donation,
+            "lost" | "stolen" => TxKind::Lost,
+            "derivative-pnl" => TxKind::DerivativePnl,
+            //  Split into exchanges (see `liquidity_pools`)
+            "lp-deposit" | "lp-withdrawal" => TxKind::Standard,
+            //  Income of its `IncomeType` (see `airdrops`)
+            "airdrop" | "fork" => TxKind::Standard,
+            "rebase" => TxKind::Rebase,
+            _ => return Err(field_error(columns.tx_type.unwrap(), format!("The txType ({}) isn't recognized. It must be blank, \
+                standard, inherited, gift, donation, lost, stolen, derivative-pnl, lp-deposit, lp-withdrawal, airdrop, fork or \
+                rebase.", this_tx_type)).into()),
+        };
+
+        if non_sale_disposals::is_non_sale(kind) {
+
+            let is_single_outgoing_ar = action_records_map_keys_vec.len() == 1
+                && action_records.get(&action_records_map_keys_vec[0]).unwrap().direction() == Polarity::Outgoing;
+
+            if !is_single_outgoing_ar {
+                return Err(row_error(format!("Transaction {} is a {}, so it must have exactly one (negative) quantity.",
+                    this_tx_number, this_tx_type.to_ascii_lowercase())).into())
+            }
+        }
+
+        let is_pool = matches!(this_tx_type.to_ascii_lowercase().as_str(), "lp-deposit" | "lp-withdrawal");
+
+        if is_pool {
+
+            let is_deposit = this_tx_type.eq_ignore_ascii_case("lp-deposit");
+            let outgoing = action_records_map_keys_vec.iter()
+                .filter(|ar_num| action_records.get(ar_num).unwrap().direction() == Polarity::Outgoing)
+                .count();
+
+            if action_records_map_keys_vec.len() != 3 || outgoing != if is_deposit { 2 } else { 1 } {
+                return Err(row_error(format!("Transaction {} is an {}, so it must have exactly three quantities: {}.", this_tx_number,
+                    this_tx_type.to_ascii_lowercase(), if is_deposit {
+                        "the two (negative) assets deposited and the (positive) LP tokens received"
+                    } else {
+                        "the (negative) LP tokens withdrawn and the two (positive) assets received"
+                    })).into())
+            }
+        } else if action_records_map_keys_vec.len() > 2 {
+            return Err(row_error(format!("Transaction {} has {} quantities, but only a liquidity pool deposit or withdrawal \
+                (an lp-deposit or lp-withdrawal txType) may have more than two.", this_tx_number, action_records_map_keys_vec.len())).into())
+        } else if action_records_map_keys_vec.len() == 2 {
+
+            let first = action_records.get(&action_records_map_keys_vec[0]).unwrap();
+            let second = action_records.get(&action_records_map_keys_vec[1]).unwrap();
+
+            if first.direction() == second.direction() {
+                return Err(row_error(format!("Transaction {} has two quantities of the same sign, but one must be negative \
+                    and the other positive.", this_tx_number)).into())
+            }
+        }
+
+        if kind == TxKind::DerivativePnl && action_records_map_keys_vec.len() != 1 {
+            return Err(row_error(format!("Transaction {} is a derivative-pnl, so it must have exactly one quantity (the profit \
+                received or the loss paid).", this_tx_number)).into())
+        }
+
+        if kind == TxKind::Rebase && action_records_map_keys_vec.len() != 1 {
+            return Err(row_error(format!("Transaction {} is a rebase, so it must have exactly one quantity (the increase or \
+                decrease in the balance).", this_tx_number)).into())
+        }
+
+        if kind == TxKind::Inherited {
+
+            let is_single_incoming_ar = action_records_map_keys_vec.len() == 1
+                && action_records.get(&action_records_map_keys_vec[0]).unwrap().direction() == Polarity::Incoming;
+
+            if !is_single_incoming_ar {
+                return Err(row_error(format!("Transaction {} is inherited, so it must have exactly one (positive) quantity.",
+                    this_tx_number)).into())
+            }
+        }
+
+        // A blank fee is no fee
+        let fee = if this_fee.is_empty() { dec!(0) } else {
+            match this_fee.replace(",", "").parse::<Decimal>() {
+                Ok(fee) if fee >= dec!(0) => fee,
+                _ => return Err(field_error(columns.fee.unwrap(), format!("The fee ({}) isn't a number of zero or more.",
+                    this_fee)).into()),
+            }
+        };
+
+        let is_income = action_records_map_keys_vec.len() == 1
+            && action_records.get(&action_records_map_keys_vec[0]).unwrap().direction() == Polarity::Incoming;
+        let is_spend = action_records_map_keys_vec.len() == 1 && !is_income;
+
+        if !fee.is_zero() && is_income {
+            return Err(field_error(columns.fee.unwrap(), format!("Transaction {} has a fee, but it is income. A fee must be on \
+                an exchange, a transfer or a spend.", this_tx_number)).into())
+        }
+
+        let income_type = IncomeType::from_field(this_income_type).map_err(|e| {
+            field_error(columns.income_type.unwrap(), e.to_string())
+        })?;
+
+        if income_type.is_some() && (!is_income || kind != TxKind::Standard) {
+            return Err(field_error(columns.income_type.unwrap(), format!("Transaction {} has an incomeType, but it isn't \
+                income. It must have exactly one (positive) quantity.", this_tx_number)).into())
+        }
+
+        //  An airdrop or fork txType is income of that type; an incomeType isn't needed, but mustn't be another
+        let income_type = match this_tx_type.to_ascii_lowercase().as_str() {
+            "airdrop" | "fork" => {
+
+                let tx_type_income = if this_tx_type.eq_ignore_ascii_case("airdrop") { IncomeType::Airdrop } else { IncomeType::Fork };
+
+                if !is_income {
+                    return Err(row_error(format!("Transaction {} is an {}, so it must have exactly one (positive) quantity.",
+                        this_tx_number, this_tx_type.to_ascii_lowercase())).into())
+                }
+                if income_type.is_some_and(|income_type| income_type != tx_type_income) {
+                    return Err(field_error(columns.income_type.unwrap(), format!("Transaction {} is an {}, but its incomeType \
+                        is {}.", this_tx_number, this_tx_type.to_ascii_lowercase(), income_type.unwrap())).into())
+                }
+
+                Some(tx_type_income)
+            }
+            _ => income_type,
+        };
+
+        //  The memo's tags, then any others of the tags column
+        let mut tags = tags::tags_in_memo(this_memo);
+        let column_tags = tags::tags_in_column(this_tags).map_err(|e| {
+            field_error(columns.tags.unwrap(), format!("The tags aren't tags: {}", e))
+        })?;
+        for tag in column_tags {
+            if !tags.contains(&tag) { tags.push(tag) }
+        }
+
+        //  The expenseCategory column, or else an `#expense:` tag
+        let expense_category = tags::column_or_tag(this_expense_category, &tags, "expense");
+
+        if expense_category.is_some() && !(is_spend && matches!(kind, TxKind::Standard | TxKind::Lost)) && fee.is_zero() {
+            return Err(row_error(format!("Transaction {} has an expenseCategory, but it isn't a spend. It must have exactly \
+                one (negative) quantity and a txType of standard or lost, or else a fee.", this_tx_number)).into())
+        }
+
+        //  The payer column, or else a `#payer:` tag
+        let payer = tags::column_or_tag(this_payer, &tags, "payer");
+
+        if payer.is_some() && !is_income {
+            return Err(row_error(format!("Transaction {} has a payer, but it isn't income. It must have exactly one (positive) \
+                quantity.", this_tx_number)).into())
+        }
+
+        if *prior_date_as_string != *this_tx_date {
+            prior_date_as_string = Rc::from(this_tx_date);
+        }
 
         let transaction = Transaction {
             tx_number: this_tx_number,
-            date_as_string: this_tx_date.to_string(),
+            source_row: Some(row),
+            date_as_string: prior_date_as_string.clone(),
             date: tx_date,
+            date_for_basis_purposes,
+            time: tx_time,
+            sequence,
             user_memo: this_memo.to_string(),
             proceeds: proceeds_parsed,
+            fee,
+            income_type,
+            tags,
+            expense_category,
+            payer,
             action_record_idx_vec: action_records_map_keys_vec,
+            kind,
         };
 
         txns_map.insert(this_tx_number, transaction);
     };
 
     if changed_action_records > 0 {
-        println!("  Changed actionrecord amounts due to rounding precision: {}. Changed txn numbers: {:?}.", changed_action_records, changed_txn_num);
+        log::info!("  Changed actionrecord amounts due to rounding precision: {}. Changed txn numbers: {:?}.", changed_action_records, changed_txn_num);
+    }
+
+    if skipped_rows > 0 {
+        log::info!("  Rows left out, as the corrections skip them: {}.", skipped_rows);
+    }
+
+    let mut missing_txns: Vec<&u32> = txn_corrections.keys().filter(|tx_number| **tx_number > uncorrected_tx_number).collect();
+    if !missing_txns.is_empty() {
+        missing_txns.sort();
+        log::warn!("WARNING: The corrections of transaction(s) {:?} weren't applied, as the file has only {} transactions.",
+            missing_txns, uncorrected_tx_number);
+    }
+
+    if parked_rows > 0 {
+        log::info!("  Rows left out, as they're parked for review: {}.", parked_rows);
+    }
+
+    if annotation_rows > 0 {
+        log::info!("  Zero-quantity rows attached as annotations to the transaction before them: {}.", annotation_rows);
+    }
+
+    if duplicate_rows > 0 {
+        match settings.dedupe {
+            DedupeMode::Drop => log::warn!("  Duplicate rows dropped: {}.", duplicate_rows),
+            _ => log::warn!("  Duplicate rows imported anyway: {}.", duplicate_rows),
+        }
     }
 
     Ok(())
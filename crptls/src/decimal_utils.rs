@@ -1,7 +1,136 @@
 // Copyright (c) 2017-2023, scoobybejesus
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+
+/// How a value exactly halfway between two roundings is rounded where it's written to a report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputRounding {
+    /// Away from zero (i.e., 0.125 to 0.13).
+    HalfUp,
+    /// To the even digit (i.e., 0.125 to 0.12, but 0.135 to 0.14).
+    Bankers,
+}
+
+/// The decimal and thousands separators of the numbers written to the reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportLocale {
+    /// No thousands separator, and a period for the decimal separator (i.e., `1234.56`).
+    Plain,
+    /// A comma for the thousands separator and a period for the decimal separator (i.e., `1,234.56`).
+    DotDecimal,
+    /// A period for the thousands separator and a comma for the decimal separator (i.e., `1.234,56`).
+    CommaDecimal,
+}
+
+impl ReportLocale {
+
+    /// The locale for a language code (i.e., `en`, or `de-DE`), if it's one whose numbers are known.  A language is
+    /// taken to write its numbers the same way in every region, so only its first two letters matter.
+    pub fn from_code(code: &str) -> Option<ReportLocale> {
+        match code.to_ascii_lowercase().as_str() {
+            "plain" => Some(ReportLocale::Plain),
+            code => match code.get(..2) {
+                Some("en" | "ja" | "ko" | "zh" | "he" | "th") => Some(ReportLocale::DotDecimal),
+                Some("de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "sl" | "hr") =>
+                    Some(ReportLocale::CommaDecimal),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The decimal places (and rounding) of the home currency values and the quantities written to the reports, and
+/// their separators.  Values are kept exact while processing, so this only affects how they're written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputPrecision {
+    pub fiat_places: u32,
+    /// `None` writes each quantity with all of its decimal places.
+    pub crypto_places: Option<u32>,
+    pub rounding: OutputRounding,
+    pub locale: ReportLocale,
+}
+
+impl Default for OutputPrecision {
+    fn default() -> Self {
+        OutputPrecision {
+            fiat_places: 2,
+            crypto_places: None,
+            rounding: OutputRounding::Bankers,
+            locale: ReportLocale::Plain,
+        }
+    }
+}
+
+impl OutputPrecision {
+
+    fn round(&self, to_round: Decimal, places: u32) -> Decimal {
+        let strategy = match self.rounding {
+            OutputRounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            OutputRounding::Bankers => RoundingStrategy::MidpointNearestEven,
+        };
+        to_round.round_dp_with_strategy(places, strategy)
+    }
+
+    /// A home currency value, rounded to `fiat_places`, for totalling values as they're written (so that a report's
+    /// rows add up to its totals).
+    pub fn round_fiat(&self, value: Decimal) -> Decimal {
+        self.round(value, self.fiat_places)
+    }
+
+    /// A home currency value, rounded and written with exactly `fiat_places` decimal places (i.e., `12.30`).
+    pub fn fiat(&self, value: Decimal) -> String {
+        //  `Decimal`'s own `{:.2}` truncates rather than rounds, so it's rounded first
+        self.localize(format!("{:.*}", self.fiat_places as usize, self.round_fiat(value)))
+    }
+
+    /// A quantity, rounded to `crypto_places` if it has more decimal places than that (and if set).
+    pub fn crypto(&self, amount: Decimal) -> String {
+        match self.crypto_places {
+            Some(places) => self.localize(self.round(amount, places).to_string()),
+            None => self.number(amount),
+        }
+    }
+
+    /// Any other number (i.e., a price), unrounded.
+    pub fn number(&self, value: Decimal) -> String {
+        self.localize(value.to_string())
+    }
+
+    /// Swaps the separators of a number written as `-1234.56` for those of the `locale`.
+    fn localize(&self, number: String) -> String {
+
+        let (thousands, decimal) = match self.locale {
+            ReportLocale::Plain => return number,
+            ReportLocale::DotDecimal => (',', '.'),
+            ReportLocale::CommaDecimal => ('.', ','),
+        };
+
+        let (sign, digits) = match number.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", number.as_str()),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits, None),
+        };
+
+        let mut localized = String::from(sign);
+
+        for (idx, digit) in integer.chars().enumerate() {
+            if idx > 0 && (integer.len() - idx) % 3 == 0 { localized.push(thousands) }
+            localized.push(digit);
+        }
+
+        if let Some(fraction) = fraction {
+            localized.push(decimal);
+            localized.push_str(fraction);
+        }
+
+        localized
+    }
+}
 
 pub fn round_d128_generalized(to_round: &Decimal, places_past_decimal: u32) -> Decimal {
     let rounded: Decimal = to_round.round_dp(places_past_decimal);
@@ -0,0 +1,190 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+use crate::core_functions::ImportProcessParameters;
+use crate::csv_import_accts_txns::{self, ColumnLayout};
+
+
+/// What's done with a transaction row that duplicates an earlier one (by its `DedupeField`s), as when exports of
+/// overlapping date ranges are merged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DedupeMode {
+    /// Duplicates are imported like any other row (default).
+    Off,
+    /// Duplicates are imported, but each is warned of and listed.
+    Warn,
+    /// Duplicates are left out of the import, and each is listed.
+    Drop,
+}
+
+impl FromStr for DedupeMode {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(DedupeMode::Off),
+            "warn" => Ok(DedupeMode::Warn),
+            "drop" => Ok(DedupeMode::Drop),
+            _ => Err(format!("The dedupe mode ({}) must be warn, drop or off.", s)),
+        }
+    }
+}
+
+/// A field of a transaction row compared in finding duplicates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DedupeField {
+    /// The txDate (and txTime, if there is one).
+    Date,
+    /// The accounts with quantities.
+    Accounts,
+    /// The quantities (of each account, if `Accounts` are also compared).
+    Amounts,
+    Proceeds,
+    Memo,
+}
+
+/// The fields compared by default: the date, the accounts and their quantities, and the memo.
+pub const DEFAULT_DEDUPE_KEY: [DedupeField; 4] = [DedupeField::Date, DedupeField::Accounts, DedupeField::Amounts, DedupeField::Memo];
+
+/// Parses the `DEDUPE_KEY` setting, a comma-separated list of the fields to compare (i.e., `date,amounts,memo`).
+pub fn parse_dedupe_key(value: &str) -> Result<Vec<DedupeField>, Box<dyn Error>> {
+
+    let mut key = Vec::new();
+
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let field = match name.to_ascii_lowercase().as_str() {
+            "date" => DedupeField::Date,
+            "accounts" => DedupeField::Accounts,
+            "amounts" => DedupeField::Amounts,
+            "proceeds" => DedupeField::Proceeds,
+            "memo" => DedupeField::Memo,
+            _ => return Err(format!("The dedupe key field {} must be date, accounts, amounts, proceeds or memo.", name).into()),
+        };
+        if !key.contains(&field) { key.push(field) }
+    }
+
+    if key.is_empty() { return Err("The dedupe key must have at least one field.".into()) }
+
+    Ok(key)
+}
+
+/// A transaction row found to duplicate an earlier one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateRow {
+    /// The row of the CSV Input File (counting the four header rows), as merged, if there are several.
+    pub row: usize,
+    /// The earlier row it duplicates.
+    pub duplicate_of: usize,
+    pub date: String,
+    pub memo: String,
+    /// Each account with a quantity, with the quantity (i.e., `2: -0.5`).
+    pub quantities: String,
+}
+
+/// Finds the transaction rows that duplicate an earlier row, comparing the fields of the `key`.
+pub(crate) struct DuplicateFinder<'a> {
+    key: &'a [DedupeField],
+    seen: HashMap<Vec<String>, usize>,
+}
+
+impl<'a> DuplicateFinder<'a> {
+
+    pub(crate) fn new(key: &'a [DedupeField]) -> Self {
+        DuplicateFinder { key, seen: HashMap::new() }
+    }
+
+    /// The duplicate, if the `record` at `row` duplicates an earlier one, and otherwise `None` (remembering the
+    /// `record`).  Rows with no quantities (annotations, with `ZERO_QUANTITY_ANNOTATIONS`) are never duplicates.
+    pub(crate) fn check(&mut self, row: usize, record: &csv::StringRecord, columns: &ColumnLayout) -> Option<DuplicateRow> {
+
+        let field = |idx: Option<usize>| idx.and_then(|idx| record.get(idx)).unwrap_or("").trim();
+        //  So that `1.50` and `1.5` (or `1,000` and `1000`) are the same
+        let number = |value: &str| {
+            let value = value.replace(',', "");
+            Decimal::from_str(&value).map(|d| d.normalize().to_string()).unwrap_or(value)
+        };
+
+        let quantities: Vec<(usize, String)> = record.iter().enumerate().skip(columns.first_acct_col)
+            .filter(|(_idx, value)| !value.trim().is_empty())
+            .map(|(idx, value)| (idx - columns.first_acct_col + 1, number(value.trim())))
+            .filter(|(_acct, amount)| amount != "0")
+            .collect();
+
+        if quantities.is_empty() { return None }
+
+        let mut values: Vec<String> = Vec::with_capacity(self.key.len());
+
+        for field_name in self.key.iter() {
+            match field_name {
+                DedupeField::Date => values.push(format!("{} {}", field(Some(0)), field(columns.tx_time))),
+                DedupeField::Proceeds => values.push(number(field(Some(1)))),
+                DedupeField::Memo => values.push(field(Some(2)).to_string()),
+                DedupeField::Accounts if self.key.contains(&DedupeField::Amounts) => {
+                    values.extend(quantities.iter().map(|(acct, amount)| format!("{}: {}", acct, amount)))
+                }
+                DedupeField::Accounts => values.extend(quantities.iter().map(|(acct, _amount)| acct.to_string())),
+                DedupeField::Amounts if self.key.contains(&DedupeField::Accounts) => {}
+                DedupeField::Amounts => {
+                    let mut amounts: Vec<String> = quantities.iter().map(|(_acct, amount)| amount.clone()).collect();
+                    amounts.sort();
+                    values.extend(amounts)
+                }
+            }
+        }
+
+        match self.seen.get(&values) {
+            Some(first) => Some(DuplicateRow {
+                row,
+                duplicate_of: *first,
+                date: field(Some(0)).to_string(),
+                memo: field(Some(2)).to_string(),
+                quantities: quantities.iter().map(|(acct, amount)| format!("{}: {}", acct, amount)).collect::<Vec<String>>().join(", "),
+            }),
+            None => { self.seen.insert(values, row); None }
+        }
+    }
+}
+
+/// The transaction rows of the CSV Input File(s) (merged, if there are several) that duplicate an earlier row, by the
+/// `settings`' `dedupe_key`, whether or not they're dropped (see `DedupeMode`).
+pub fn find_duplicate_rows(
+    import_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+) -> Result<Vec<DuplicateRow>, Box<dyn Error>> {
+
+    let mut inputs = csv_import_accts_txns::open_input_files(import_file_paths, settings)?;
+
+    let input: Box<dyn Read> = if inputs.len() == 1 {
+        inputs.pop().unwrap().1
+    } else {
+        csv_import_accts_txns::merge_input_files(inputs, settings)?
+    };
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(input);
+
+    let mut raw_acct_map: HashMap<u16, RawAccount> = HashMap::new();
+    let mut acct_map: HashMap<u16, Account> = HashMap::new();
+
+    let columns = csv_import_accts_txns::import_accounts(&mut rdr, settings, &mut raw_acct_map, &mut acct_map)?;
+
+    let mut finder = DuplicateFinder::new(&settings.dedupe_key);
+    let mut duplicates = Vec::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+        if let Some(duplicate) = finder.check(idx + 5, &result?, &columns) { duplicates.push(duplicate) }
+    }
+
+    Ok(duplicates)
+}
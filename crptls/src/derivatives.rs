@@ -0,0 +1,67 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, TxKind};
+
+
+/// The realized profit or loss of a cash-settled margin or futures position (a `TxKind::DerivativePnl`
+/// `Transaction`).  The quantity received is acquired at its value, and the quantity paid is disposed of at its
+/// value (realizing a gain or loss on that currency, as any spend would), but the profit or loss itself is neither
+/// income nor an expense.
+#[derive(Clone, Debug)]
+pub struct DerivativePnl {
+    pub tx_num: u32,
+    pub date: NaiveDate,
+    pub account_key: u16,
+    /// Positive if received, negative if paid.
+    pub amount: Decimal,
+    /// The home currency value of the `amount`: positive for a profit, negative for a loss.
+    pub pnl: Decimal,
+}
+
+/// The `DerivativePnl` of a `TxKind::DerivativePnl` `Transaction` (`None` for any other).  Received or paid in the
+/// home currency, its value is the `amount`; in any other currency, it's the `Transaction`'s `proceeds`.
+pub fn derivative_pnl(
+    txn: &Transaction,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+) -> Option<DerivativePnl> {
+
+    if txn.kind != TxKind::DerivativePnl { return None }
+
+    let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+    let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+    let value = if raw_acct.is_home_currency(home_currency) { ar.amount.abs() } else { txn.proceeds };
+
+    Some(DerivativePnl {
+        tx_num: txn.tx_number,
+        date: txn.date,
+        account_key: ar.account_key,
+        amount: ar.amount,
+        pnl: if ar.amount < dec!(0) { -value } else { value },
+    })
+}
+
+/// Every `DerivativePnl`, in `Transaction` order.
+pub fn derivative_pnls(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Vec<DerivativePnl> {
+
+    (1..=txns_map.len() as u32)
+        .filter_map(|num| derivative_pnl(txns_map.get(&num).unwrap(), home_currency, raw_acct_map, acct_map, ars))
+        .collect()
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity};
+
+
+/// How the amount of an outgoing `ActionRecord` is rounded before `Lot`s and `Movement`s are created.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisposalRounding {
+    /// Round to the greatest precision of any amount previously received by the `Account`.  A disposal can only
+    /// draw from those `Lot`s, so this is the finest precision that can close them out without leaving a residual.
+    LotPrecision,
+    /// Round to a fixed number of decimal places.
+    DecimalPlaces(u32),
+}
+
+/// The difference between an outgoing `ActionRecord`'s amount in the CSV Input File and the rounded amount
+/// that was actually disposed of.
+#[derive(Clone, Debug)]
+pub struct RoundingResidual {
+    pub tx_num: u32,
+    pub account_key: u16,
+    pub original_amount: Decimal,
+    pub rounded_amount: Decimal,
+}
+
+impl RoundingResidual {
+    pub fn residual(&self) -> Decimal {
+        self.original_amount - self.rounded_amount
+    }
+}
+
+/// Rounds the amount of every outgoing non-margin `ActionRecord` that has more precision than allowed by
+/// `rounding`, returning a `RoundingResidual` for each one changed.  This runs before `Lot` processing, so a
+/// slightly over-precise disposal simply consumes the `Lot`s it was drawn from rather than leaving micro-lots
+/// (or coming up a hair short of the `Account` balance).  Amounts that would round to zero are left alone.
+pub(crate) fn round_disposal_amounts(
+    rounding: DisposalRounding,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Vec<RoundingResidual> {
+
+    let mut residuals: Vec<RoundingResidual> = Vec::new();
+    let mut precision_received: HashMap<u16, u32> = HashMap::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get_mut(ar_num).unwrap();
+            let acct = acct_map.get(&ar.account_key).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            if raw_acct.is_margin { continue }
+
+            let scale = ar.amount.normalize().scale();
+
+            if ar.direction() == Polarity::Incoming {
+                let precision = precision_received.entry(ar.account_key).or_insert(0);
+                if scale > *precision { *precision = scale }
+                continue
+            }
+
+            let allowed = match rounding {
+                DisposalRounding::LotPrecision => match precision_received.get(&ar.account_key) {
+                    Some(precision) => *precision,
+                    None => continue,
+                },
+                DisposalRounding::DecimalPlaces(places) => places,
+            };
+
+            if scale <= allowed { continue }
+
+            let rounded = ar.amount.round_dp(allowed);
+            if rounded == dec!(0) { continue }
+
+            residuals.push(RoundingResidual {
+                tx_num: txn.tx_number,
+                account_key: ar.account_key,
+                original_amount: ar.amount,
+                rounded_amount: rounded,
+            });
+
+            ar.amount = rounded;
+        }
+    }
+
+    residuals
+}
@@ -0,0 +1,141 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Term};
+use crate::transaction::{Transaction, ActionRecord, TxType};
+
+
+/// A "convert small balances" event: several tiny `Exchange` `Transaction`s on the same date, all of which
+/// dispose of some non-home-currency dust in exchange for the same incoming `Account` (i.e., dust to BNB).
+/// Grouping these is purely for reporting.  Each underlying `Transaction` is processed (and its gain or loss
+/// computed) exactly as it would be otherwise, and the aggregated totals are simply the sums of those results.
+#[derive(Clone, Debug)]
+pub struct DustConversion {
+    pub date: NaiveDate,
+    pub incoming_acct_key: u16,
+    /// `Transaction` numbers in the conversion, in ascending order.
+    pub txn_nums: Vec<u32>,
+}
+
+/// Sums of the outgoing movements in a `DustConversion` for a single holding period.
+#[derive(Clone, Debug)]
+pub struct DustTermTotals {
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    /// The wash sale loss disallowed (see `Movement::wash_sale_adjustment`).
+    pub adjustment: Decimal,
+    /// The lot basis date, if all lots disposed of in this term share the same one.
+    pub date_acquired: Option<NaiveDate>,
+}
+
+impl DustTermTotals {
+    pub fn gain_or_loss(&self) -> Decimal {
+        self.proceeds + self.cost_basis + self.adjustment
+    }
+}
+
+impl DustConversion {
+
+    /// Returns the (short-term, long-term) totals of the disposals in the conversion, using like-kind values.
+    pub fn totals_by_term(
+        &self,
+        home_currency: &str,
+        long_term_days: i64,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<(Option<DustTermTotals>, Option<DustTermTotals>), Box<dyn Error>> {
+
+        let mut st: Option<DustTermTotals> = None;
+        let mut lt: Option<DustTermTotals> = None;
+
+        for txn_num in self.txn_nums.iter() {
+
+            let txn = txns_map.get(txn_num).unwrap();
+            let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+            for mvmt in mvmts.iter() {
+
+                let lot = mvmt.get_lot(acct_map, ars);
+                let totals = match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                    Term::ST => &mut st,
+                    Term::LT => &mut lt,
+                };
+
+                match totals {
+                    Some(t) => {
+                        t.proceeds += mvmt.proceeds_lk.get();
+                        t.cost_basis += mvmt.cost_basis_lk.get();
+                        t.adjustment += mvmt.wash_sale_adjustment.get();
+                        if t.date_acquired != Some(lot.date_for_basis_purposes) { t.date_acquired = None }
+                    }
+                    None => {
+                        *totals = Some(DustTermTotals {
+                            proceeds: mvmt.proceeds_lk.get(),
+                            cost_basis: mvmt.cost_basis_lk.get(),
+                            adjustment: mvmt.wash_sale_adjustment.get(),
+                            date_acquired: Some(lot.date_for_basis_purposes),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok((st, lt))
+    }
+}
+
+/// Finds every dust conversion, which is two or more `Exchange` `Transaction`s on the same date into the same
+/// incoming `Account`, where each outgoing leg is a non-margin, non-home currency `Account` and each
+/// `Transaction`'s `proceeds` are at or below the `threshold`.
+pub fn find_dust_conversions(
+    threshold: Decimal,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<DustConversion>, Box<dyn Error>> {
+
+    let mut conversions: Vec<DustConversion> = Vec::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn_num = txn_num as u32;
+        let txn = txns_map.get(&txn_num).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? != TxType::Exchange { continue }
+        if !txn.both_exch_ars_are_non_home_curr(ars, raw_acct_map, acct_map, home_currency)? { continue }
+
+        let proceeds = txn.proceeds;
+        if proceeds > threshold || proceeds < dec!(0) { continue }
+
+        let og_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let ic_ar = ars.get(&txn.action_record_idx_vec[1]).unwrap();
+        let og_raw_acct = raw_acct_map.get(&acct_map.get(&og_ar.account_key).unwrap().raw_key).unwrap();
+        let ic_raw_acct = raw_acct_map.get(&acct_map.get(&ic_ar.account_key).unwrap().raw_key).unwrap();
+
+        if og_raw_acct.is_margin || ic_raw_acct.is_margin { continue }
+
+        match conversions.iter_mut().find(|dc| dc.date == txn.date && dc.incoming_acct_key == ic_ar.account_key) {
+            Some(dc) => dc.txn_nums.push(txn_num),
+            None => conversions.push(DustConversion {
+                date: txn.date,
+                incoming_acct_key: ic_ar.account_key,
+                txn_nums: vec![txn_num],
+            }),
+        }
+    }
+
+    conversions.retain(|dc| dc.txn_nums.len() > 1);
+
+    Ok(conversions)
+}
@@ -0,0 +1,157 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{self, Transaction, ActionRecord, Polarity, TxKind};
+
+
+/// The quantity of each ticker (in upper case) at or below which a leftover balance is dust.
+pub type DustQuantities = HashMap<String, Decimal>;
+
+/// Parses the `DUST_THRESHOLD` setting, such as `5.00,BTC=0.00000001,ETH=0.000000001`: a number alone is the
+/// proceeds threshold of dust conversions (see `dust_conversions`), and each `TICKER=quantity` is the quantity of
+/// that ticker at or below which a leftover balance is swept (see `sweep_dust()`).
+pub fn parse_dust_threshold(value: &str) -> Result<(Option<Decimal>, DustQuantities), Box<dyn Error>> {
+
+    let mut proceeds_threshold = None;
+    let mut quantities = DustQuantities::new();
+
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+
+        match entry.split_once('=') {
+            Some((ticker, quantity)) => {
+                let quantity = quantity.trim().parse::<Decimal>().ok().filter(|quantity| *quantity > dec!(0))
+                    .ok_or_else(|| format!("The dust quantity of {} ({}) must be a number above zero.", ticker.trim(),
+                        quantity.trim()))?;
+                quantities.insert(ticker.trim().to_ascii_uppercase(), quantity);
+            }
+            None => {
+                let threshold = entry.parse::<Decimal>()
+                    .map_err(|_| format!("`{}` must be a number (the proceeds threshold of dust conversions) or a \
+                        ticker, then `=`, then a quantity (i.e., BTC=0.00000001).", entry))?;
+                if proceeds_threshold.replace(threshold).is_some() {
+                    return Err("Only one proceeds threshold of dust conversions may be given.".into())
+                }
+            }
+        }
+    }
+
+    Ok((proceeds_threshold, quantities))
+}
+
+/// Sweeps the dust left in an `Account` by a disposal: whenever the `Transaction`s leave a non-margin `Account`'s
+/// balance above zero but at or below the dust quantity of its ticker (from `quantities`), a `TxKind::DustSweep`
+/// spend of that balance from the `Account` is inserted right after the disposal that left it, so the balance is
+/// zero rather than a few near-empty `Lot`s.  Every later `Transaction` ends up one higher.  Returns the number of
+/// sweeps inserted.
+pub(crate) fn sweep_dust(
+    quantities: &DustQuantities,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<usize, Box<dyn Error>> {
+
+    if quantities.is_empty() { return Ok(0) }
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut balances: HashMap<u16, Decimal> = HashMap::new();
+    let mut added = 0;
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.remove(&(num as u32)).unwrap();
+        let mut swept_accts: Vec<u16> = Vec::new();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get(ar_num).unwrap();
+            *balances.entry(ar.account_key).or_insert(dec!(0)) += ar.amount;
+
+            if ar.direction() == Polarity::Outgoing && !swept_accts.contains(&ar.account_key) {
+                swept_accts.push(ar.account_key);
+            }
+        }
+
+        let mut sweeps: Vec<Transaction> = Vec::new();
+
+        for account_key in swept_accts {
+
+            let raw_acct = raw_acct_map.get(&acct_map.get(&account_key).unwrap().raw_key).unwrap();
+            if raw_acct.is_margin { continue }
+
+            let Some(quantity) = quantities.get(&raw_acct.ticker.to_ascii_uppercase()) else { continue };
+            let balance = balances.get(&account_key).copied().unwrap_or(dec!(0));
+            if balance <= dec!(0) || balance > *quantity { continue }
+
+            let sweep_ar_num = ar_map.len() as u32 + 1;
+
+            ar_map.insert(sweep_ar_num, ActionRecord {
+                account_key,
+                amount: -balance,
+                tx_key: 0,  //  Set when renumbering, below
+                self_ar_key: sweep_ar_num,
+                movements: RefCell::new([].to_vec()),
+            });
+
+            sweeps.push(Transaction {
+                tx_number: 0,   //  Set when renumbering, below
+                source_row: None,
+                date_as_string: txn.date_as_string.clone(),
+                date: txn.date,
+                date_for_basis_purposes: txn.date,
+                time: None,
+                sequence: None,
+                user_memo: format!("Dust of {} {} swept from {}: {}", balance, raw_acct.ticker, raw_acct.name, txn.user_memo),
+                proceeds: dec!(0),
+                fee: dec!(0),
+                income_type: None,
+                tags: txn.tags.clone(),
+                expense_category: None,
+                payer: None,
+                action_record_idx_vec: vec![sweep_ar_num],
+                kind: TxKind::DustSweep,
+            });
+
+            balances.insert(account_key, dec!(0));
+        }
+
+        ordered_txns.push(txn);
+        added += sweeps.len();
+        ordered_txns.extend(sweeps);
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    Ok(added)
+}
+
+/// Writes off the cost basis of the dust swept (see `sweep_dust()`), by netting the proceeds of each sweep's
+/// `Movement`s against their cost basis, so that a sweep realizes no gain or loss.  This must follow any like-kind
+/// treatment, which may change the `cost_basis_lk`.
+pub(crate) fn write_off_dust_sweeps(
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) {
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if txn.kind != TxKind::DustSweep { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+            for mvmt in ars.get(ar_num).unwrap().get_mvmts_in_ar_in_lot_date_order().iter() {
+                mvmt.proceeds.set(-mvmt.cost_basis.get());
+                mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+
+/// The environment variable a passphrase may be given in, rather than typed at the prompt (i.e., in a script).
+pub const PASSPHRASE_VAR: &str = "CRYPTOOLS_PASSPHRASE";
+
+/// The passphrase an encrypted CSV Input File was decrypted with, so that it's only asked for once in a run (the file
+/// is read more than once, as when it's validated and then imported), and may encrypt the reports.
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// How a file is encrypted, as told by its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encryption {
+    /// An OpenPGP message (i.e., from `gpg --symmetric`, or `gpg --encrypt` to a key), armored or not, decrypted with
+    /// `gpg`.
+    OpenPgp,
+    /// An age file (i.e., from `age --passphrase`), armored or not, decrypted with `age`, which asks for its own
+    /// passphrase (or uses its own identity files).
+    Age,
+}
+
+impl Encryption {
+
+    /// The encryption of the file beginning with `bytes`, if it's encrypted.
+    pub fn detect(bytes: &[u8]) -> Option<Encryption> {
+        match bytes {
+            _ if bytes.starts_with(b"age-encryption.org/") => Some(Encryption::Age),
+            _ if bytes.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----") => Some(Encryption::Age),
+            _ if bytes.starts_with(b"-----BEGIN PGP MESSAGE-----") => Some(Encryption::OpenPgp),
+            //  A public-key (versions 3 and 6) or symmetric-key (versions 4 to 6) encrypted session key packet, in the old
+            //  or the new packet format, whose version follows its tag and length
+            [0x84, _, 3, ..] | [0x85, _, _, 3, ..] | [0x8c, _, 4..=6, ..] => Some(Encryption::OpenPgp),
+            [0xc1 | 0xc3, length, 3..=6, ..] if *length < 192 => Some(Encryption::OpenPgp),
+            _ => None,
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            Encryption::OpenPgp => "gpg",
+            Encryption::Age => "age",
+        }
+    }
+}
+
+/// Decrypts the `encrypted` file (`name`, for its errors).  An OpenPGP message is decrypted with the passphrase of
+/// `passphrase()`, and an age file by `age` itself, which asks for its passphrase at the terminal.
+pub fn decrypt(encrypted: &[u8], encryption: Encryption, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+
+    let output = match encryption {
+        Encryption::OpenPgp => {
+            let passphrase = passphrase(&format!("Passphrase for {}: ", name), false)?;
+            let output = run(encryption, &gpg_args(&["--decrypt"]), &[passphrase.as_bytes(), b"\n", encrypted])?;
+            if output.status.success() { *PASSPHRASE.lock().unwrap() = Some(passphrase) }
+            output
+        }
+        Encryption::Age => run(encryption, &["--decrypt"], &[encrypted])?,
+    };
+
+    if !output.status.success() {
+        //  A wrong passphrase is asked for again the next time
+        *PASSPHRASE.lock().unwrap() = None;
+        return Err(format!("Couldn't decrypt {} with {}: {}", name, encryption.program(),
+            String::from_utf8_lossy(&output.stderr).trim()).into())
+    }
+
+    Ok(output.stdout)
+}
+
+/// Encrypts the file at `path` with `gpg --symmetric` (AES-256), using the `passphrase`, to the same path with `.gpg`
+/// added, and removes the unencrypted file.  Returns the path of the encrypted file.  An error is left to the caller
+/// to name the file by.
+pub fn encrypt_file(path: &Path, passphrase: &str) -> Result<PathBuf, Box<dyn Error>> {
+
+    let encrypted_path = encrypted_path(path);
+    let plaintext = fs::read(path)?;
+    let output = run(
+        Encryption::OpenPgp,
+        &gpg_args(&["--symmetric", "--cipher-algo", "AES256"]),
+        &[passphrase.as_bytes(), b"\n", &plaintext],
+    )?;
+
+    if !output.status.success() {
+        return Err(format!("gpg failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into())
+    }
+
+    fs::write(&encrypted_path, &output.stdout)
+        .map_err(|e| format!("couldn't write {}: {}", encrypted_path.display(), e))?;
+    fs::remove_file(path)?;
+
+    Ok(encrypted_path)
+}
+
+/// An error unless `gpg` runs, as `encrypt_file()` needs it to.
+pub fn check_gpg() -> Result<(), Box<dyn Error>> {
+
+    let output = run(Encryption::OpenPgp, &["--version"], &[])?;
+
+    if !output.status.success() {
+        return Err(format!("gpg didn't run: {}", String::from_utf8_lossy(&output.stderr).trim()).into())
+    }
+
+    Ok(())
+}
+
+/// Where `encrypt_file()` writes the file at `path`: the same path with `.gpg` added.
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut encrypted_path = path.as_os_str().to_os_string();
+    encrypted_path.push(".gpg");
+    PathBuf::from(encrypted_path)
+}
+
+/// The arguments of a `gpg` run that reads the passphrase from the first line of its standard input (the rest being
+/// the file), rather than asking for it itself.
+fn gpg_args<'a>(command: &[&'a str]) -> Vec<&'a str> {
+    let mut args = vec!["--batch", "--quiet", "--yes", "--pinentry-mode", "loopback", "--passphrase-fd", "0"];
+    args.extend_from_slice(command);
+    args
+}
+
+/// Runs the program of the `encryption` with `args`, writing the `input` to its standard input.
+fn run(encryption: Encryption, args: &[&str], input: &[&[u8]]) -> Result<std::process::Output, Box<dyn Error>> {
+
+    let mut child = Command::new(encryption.program())
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run {} (is it installed?): {}", encryption.program(), e))?;
+
+    //  Written from its own thread, so that a large file can't deadlock against the output being read
+    let mut stdin = child.stdin.take().unwrap();
+    let input: Vec<u8> = input.concat();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+
+    Ok(output)
+}
+
+/// The passphrase: that of `PASSPHRASE_VAR`, if set, or the one already entered in this run, or else one typed (with
+/// `prompt`) at the terminal, without echo (entered twice, to `confirm` it).
+pub fn passphrase(prompt: &str, confirm: bool) -> Result<String, Box<dyn Error>> {
+
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) { return Ok(passphrase) }
+
+    if let Some(passphrase) = PASSPHRASE.lock().unwrap().clone() { return Ok(passphrase) }
+
+    let passphrase = prompt_without_echo(prompt)?;
+
+    if passphrase.is_empty() { return Err("No passphrase was entered.".into()) }
+
+    if confirm && prompt_without_echo("Enter it again: ")? != passphrase {
+        return Err("The passphrases didn't match.".into())
+    }
+
+    Ok(passphrase)
+}
+
+/// A line typed at the terminal (or, without one, read from standard input), with its echo turned off by `stty`.
+fn prompt_without_echo(prompt: &str) -> io::Result<String> {
+
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty");
+
+    let mut line = String::new();
+
+    match tty {
+        Ok(mut tty) => {
+            write!(tty, "{}", prompt)?;
+            tty.flush()?;
+
+            let stty = |arg: &str| Command::new("stty").arg(arg).stdin(File::open("/dev/tty")?).status();
+            let echo_off = stty("-echo").is_ok_and(|status| status.success());
+
+            let read = BufReader::new(tty.try_clone()?).read_line(&mut line);
+
+            if echo_off {
+                let _ = stty("echo");
+                writeln!(tty)?;
+            }
+            read?;
+        }
+        Err(_) => {
+            eprint!("{}", prompt);
+            io::stdin().lock().read_line(&mut line)?;
+        }
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,150 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::account::{Account, RawAccount};
+use crate::accounts_file::AccountsFile;
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind};
+
+
+/// The entity of every account not assigned to one.
+pub const DEFAULT_ENTITY: &str = "personal";
+
+/// How a transfer between the accounts of two entities is treated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntityTransferTreatment {
+    /// As a transfer between the user's own accounts: the basis and basis dates carry over, realizing no gain or loss
+    /// (i.e., a contribution to, or a distribution from, a disregarded entity).
+    Carryover,
+    /// As a sale by the entity sending to the entity receiving, at the transfer's `proceeds`: the sender realizes a
+    /// gain or loss, and the receiver's basis is the `proceeds`, with the transfer's date as the basis date.
+    Sale,
+}
+
+/// The entities (i.e., a person, an LLC and a trust) whose accounts are reported separately, each in a report set of
+/// its own.  An account not assigned to an entity belongs to the `DEFAULT_ENTITY`.  The lots are processed as one,
+/// across every entity, as they are without entities; only the reports are divided.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entities {
+    /// The entity of each account assigned to one, by its number.
+    pub by_account: HashMap<u16, String>,
+}
+
+impl Entities {
+
+    /// Parses entities given as `entity=account|account` separated by commas (i.e., `LLC=3|4,Trust=5`).
+    pub fn parse(value: &str) -> Result<Entities, Box<dyn Error>> {
+
+        let mut by_account: HashMap<u16, String> = HashMap::new();
+
+        for entry in value.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+
+            let (entity, accounts) = entry.split_once('=')
+                .ok_or_else(|| format!("The entity ({}) must be given as entity=account|account.", entry))?;
+            let entity = entity.trim();
+
+            check_name(entity)?;
+
+            for account in accounts.split('|').map(|account| account.trim()) {
+
+                let account_num = account.parse::<u16>()
+                    .map_err(|_| format!("The account ({}) of entity {} must be a number.", account, entity))?;
+
+                if let Some(other) = by_account.insert(account_num, entity.to_string()) {
+                    return Err(format!("Account {} is assigned to both {} and {}.", account_num, other, entity).into())
+                }
+            }
+        }
+
+        if by_account.is_empty() {
+            return Err("No entities were given.".into())
+        }
+
+        Ok(Entities { by_account })
+    }
+
+    /// The entities of the `entity` column of the `accounts_file`, if any account has one.
+    pub fn from_accounts_file(accounts_file: &AccountsFile) -> Result<Option<Entities>, Box<dyn Error>> {
+
+        let mut by_account: HashMap<u16, String> = HashMap::new();
+
+        for acct in accounts_file.accounts.iter() {
+            if let Some(entity) = &acct.entity {
+                check_name(entity)?;
+                by_account.insert(acct.account_num, entity.clone());
+            }
+        }
+
+        Ok(if by_account.is_empty() { None } else { Some(Entities { by_account }) })
+    }
+
+    /// The entity of account `account_num`.
+    pub fn entity_of(&self, account_num: u16) -> &str {
+        self.by_account.get(&account_num).map(|entity| entity.as_str()).unwrap_or(DEFAULT_ENTITY)
+    }
+
+    /// Every entity with one or more of the accounts of the `raw_acct_map`, in order of their names.
+    pub fn names(&self, raw_acct_map: &HashMap<u16, RawAccount>) -> Vec<String> {
+        let names: BTreeSet<&str> = raw_acct_map.keys().map(|account_num| self.entity_of(*account_num)).collect();
+        names.into_iter().map(|name| name.to_string()).collect()
+    }
+
+    /// The numbers of the accounts of the `raw_acct_map` belonging to `entity`, in order.
+    pub fn accounts_of(&self, entity: &str, raw_acct_map: &HashMap<u16, RawAccount>) -> Vec<u16> {
+        let mut accounts: Vec<u16> = raw_acct_map.keys().copied().filter(|num| self.entity_of(*num) == entity).collect();
+        accounts.sort_unstable();
+        accounts
+    }
+}
+
+/// An error unless `entity` can name the directory of its reports: letters, digits, spaces, `-` and `_` only.
+fn check_name(entity: &str) -> Result<(), Box<dyn Error>> {
+
+    if entity.is_empty() || !entity.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_')) {
+        return Err(format!("The entity name ({}) may have only letters, digits, spaces, - and _.", entity).into())
+    }
+
+    Ok(())
+}
+
+/// Marks every transfer from an account of one entity to an account of another (of anything other than the home
+/// currency) as a `TxKind::EntitySale`, which is processed as an exchange: a disposal at the `proceeds` by the entity
+/// sending, and an acquisition at the `proceeds` by the entity receiving.  Only `Standard` transfers between
+/// non-margin `Account`s are marked.  Returns the numbers of the `Transaction`s marked, in order.
+pub(crate) fn mark_entity_sales(
+    entities: &Entities,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Vec<u32> {
+
+    let mut marked: Vec<u32> = Vec::new();
+
+    for (txn_num, txn) in txns_map.iter_mut() {
+
+        if txn.kind != TxKind::Standard || txn.action_record_idx_vec.len() != 2 { continue }
+
+        let og_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let ic_ar = ars.get(&txn.action_record_idx_vec[1]).unwrap();
+
+        if og_ar.direction() != Polarity::Outgoing || ic_ar.direction() != Polarity::Incoming { continue }
+
+        let og_raw_acct = raw_acct_map.get(&acct_map.get(&og_ar.account_key).unwrap().raw_key).unwrap();
+        let ic_raw_acct = raw_acct_map.get(&acct_map.get(&ic_ar.account_key).unwrap().raw_key).unwrap();
+
+        if og_raw_acct.is_margin || ic_raw_acct.is_margin || og_raw_acct.ticker != ic_raw_acct.ticker { continue }
+        if og_raw_acct.is_home_currency(home_currency) { continue }
+
+        if entities.entity_of(og_raw_acct.account_num) != entities.entity_of(ic_raw_acct.account_num) {
+            txn.kind = TxKind::EntitySale;
+            marked.push(*txn_num);
+        }
+    }
+
+    marked.sort_unstable();
+    marked
+}
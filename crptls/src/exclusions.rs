@@ -0,0 +1,53 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::transaction::{self, Transaction, ActionRecord};
+
+
+/// Leaves the `Transaction`s numbered in `excluded` (as imported) out of processing, along with their `ActionRecord`s,
+/// as if their rows weren't in the CSV Input File (i.e., to see what the gains would be without a sale).  The
+/// `ActionRecord`s and every later `Transaction` are renumbered.  Returns the number of `Transaction`s left out.
+pub(crate) fn exclude_transactions(
+    excluded: &[u32],
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<usize, Box<dyn Error>> {
+
+    if excluded.is_empty() { return Ok(0) }
+
+    if let Some(txn_num) = excluded.iter().find(|txn_num| !txns_map.contains_key(txn_num)) {
+        return Err(format!("Transaction {} can't be excluded, as there are only {} transactions.", txn_num,
+            txns_map.len()).into())
+    }
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut old_ar_map = std::mem::take(ar_map);
+    let mut left_out = 0;
+
+    for num in 1..=txns_map.len() as u32 {
+
+        let mut txn = txns_map.remove(&num).unwrap();
+
+        if excluded.contains(&num) {
+            left_out += 1;
+            continue
+        }
+
+        //  The `ActionRecord`s are numbered consecutively again, as they were imported, in the order of their `Transaction`s
+        for ar_num in txn.action_record_idx_vec.iter_mut() {
+            let mut ar = old_ar_map.remove(ar_num).unwrap();
+            *ar_num = ar_map.len() as u32 + 1;
+            ar.self_ar_key = *ar_num;
+            ar_map.insert(*ar_num, ar);
+        }
+
+        ordered_txns.push(txn);
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    Ok(left_out)
+}
@@ -0,0 +1,212 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fs::File;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::summary::{self, ExpenseCategoryTotal, IncomeTypeTotal};
+use crate::transaction::IncomeType;
+
+
+/// A cost paid in the home currency, rather than in any `Account` of the CSV Input File (i.e., electricity, or the
+/// purchase of mining equipment), from a costs file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FiatCost {
+    /// The line of the costs file (counting its header row), by which it's referred to.
+    pub line: usize,
+    pub date: NaiveDate,
+    pub category: String,
+    /// Positive.
+    pub amount: Decimal,
+    pub memo: String,
+    /// For equipment, the number of years it's depreciated over: straight-line, by month, from the month it was bought
+    /// (see `expensed_in_year()`).  `None` for a cost expensed when it's paid.
+    pub depreciation_years: Option<u32>,
+}
+
+impl FiatCost {
+
+    /// The category of what's expensed of the cost each year: its own, or for equipment, i.e., `Miners (depreciation)`.
+    pub fn expense_category(&self) -> String {
+        match self.depreciation_years {
+            Some(_) => format!("{} (depreciation)", self.category),
+            None => self.category.clone(),
+        }
+    }
+
+    /// The (fiscal) years any of the cost is expensed in: the year it's paid, or for equipment, each year of its
+    /// depreciation.
+    pub fn expense_years(&self, fiscal_year_start_month: u32) -> RangeInclusive<i32> {
+
+        let first = summary::fiscal_year(self.date, fiscal_year_start_month);
+
+        match self.depreciation_years {
+            Some(years) => {
+                let last_month = month_index(self.date) + 12 * years as i32 - 1;
+                let last_date = NaiveDate::from_ymd_opt(last_month.div_euclid(12), last_month.rem_euclid(12) as u32 + 1, 1).unwrap();
+                first..=summary::fiscal_year(last_date, fiscal_year_start_month)
+            }
+            None => first..=first,
+        }
+    }
+
+    /// The amount of the cost expensed in the (fiscal) `year`: all of it in the year it's paid, or for equipment, a
+    /// twelfth of its yearly depreciation for each month of its depreciation in the year.  The depreciation of the
+    /// years adds up to the amount exactly.
+    pub fn expensed_in_year(&self, year: i32, fiscal_year_start_month: u32) -> Decimal {
+
+        let Some(years) = self.depreciation_years else {
+            return if summary::fiscal_year(self.date, fiscal_year_start_month) == year { self.amount } else { dec!(0) }
+        };
+
+        let months = 12 * years as i32;
+        //  The months of depreciation through the end of the fiscal year
+        let depreciated_through = |year: i32| {
+            let end = (year + 1) * 12 + fiscal_year_start_month as i32 - 1;
+            Decimal::from((end - month_index(self.date)).clamp(0, months))
+        };
+
+        let months = Decimal::from(months);
+
+        self.amount * depreciated_through(year) / months - self.amount * depreciated_through(year - 1) / months
+    }
+}
+
+/// The months since the start of year 0, of the month of `date`.
+fn month_index(date: NaiveDate) -> i32 {
+    date.year() * 12 + date.month0() as i32
+}
+
+/// Reads a costs file: a CSV with a header row, then one `date,category,amount,memo,depreciationYears` row per cost,
+/// where the date is YYYY-MM-DD, the amount is in the home currency, and the depreciation years are blank (or left
+/// out) for a cost expensed when it's paid (i.e., `2023-02-01,Miners,12000.00,Two S19s,3` and
+/// `2023-02-28,Electricity,310.55,February power bill`).
+pub fn import_costs(path: &Path) -> Result<Vec<FiatCost>, Box<dyn Error>> {
+
+    let file = File::open(path)?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut costs: Vec<FiatCost> = Vec::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let line = idx + 2;  //  Account for the header and one-based line numbers
+
+        let date = NaiveDate::parse_from_str(record.get(0).unwrap_or(""), "%Y-%m-%d")
+            .map_err(|_| format!("Costs file line {} has an invalid date (it must be YYYY-MM-DD).", line))?;
+
+        let category = match record.get(1) {
+            Some(category) if !category.is_empty() => category.to_string(),
+            _ => return Err(format!("Costs file line {} has no category.", line).into()),
+        };
+
+        let amount = match record.get(2).map(|amount| amount.replace(',', "").parse::<Decimal>()) {
+            Some(Ok(amount)) if amount > dec!(0) => amount,
+            _ => return Err(format!("Costs file line {} has an invalid amount (it must be a number more than zero).", line).into()),
+        };
+
+        let depreciation_years = match record.get(4).unwrap_or("") {
+            "" => None,
+            years => match years.parse::<u32>() {
+                Ok(years) if years > 0 => Some(years),
+                _ => return Err(format!("Costs file line {} has invalid depreciation years (it must be blank or a whole \
+                    number of years).", line).into()),
+            },
+        };
+
+        costs.push(FiatCost {
+            line,
+            date,
+            category,
+            amount,
+            memo: record.get(3).unwrap_or("").to_string(),
+            depreciation_years,
+        });
+    }
+
+    Ok(costs)
+}
+
+/// Groups what's expensed of the `costs` each (fiscal) year by `FiatCost::expense_category()`, as
+/// `summary::expenses_by_category_and_year()` groups the expenses of the `Transaction`s.  Sorted by year, then by
+/// category.
+pub fn expenses_by_category_and_year(costs: &[FiatCost], fiscal_year_start_month: u32) -> Vec<ExpenseCategoryTotal> {
+
+    let mut totals: Vec<ExpenseCategoryTotal> = Vec::new();
+
+    for cost in costs.iter() {
+
+        let category = Some(cost.expense_category());
+
+        for year in cost.expense_years(fiscal_year_start_month) {
+
+            let expense = cost.expensed_in_year(year, fiscal_year_start_month);
+
+            if expense.is_zero() { continue }
+
+            match totals.iter_mut().find(|t| t.year == year && t.category == category) {
+                Some(total) => total.expense += expense,
+                None => totals.push(ExpenseCategoryTotal { year, category: category.clone(), expense }),
+            }
+        }
+    }
+
+    totals.sort_by(|a, b| (a.year, &a.category).cmp(&(b.year, &b.category)));
+
+    totals
+}
+
+/// The mining income of a single (fiscal) year, and the costs expensed against it.
+#[derive(Clone, Debug)]
+pub struct MiningYear {
+    pub year: i32,
+    /// Zero or positive.
+    pub income: Decimal,
+    /// By category (see `expenses_by_category_and_year()`).
+    pub expenses: Vec<ExpenseCategoryTotal>,
+}
+
+impl MiningYear {
+
+    pub fn net(&self) -> Decimal {
+        self.income - self.expenses.iter().map(|total| total.expense).sum::<Decimal>()
+    }
+}
+
+/// Nets the `costs` expensed each (fiscal) year against the year's mining income (from the `incomes` of
+/// `summary::income_by_type_and_year()`), for each year with either.  The costs are taken to all be the mining
+/// operation's.  Sorted by year.
+pub fn mining_pnl_by_year(costs: &[FiatCost], incomes: &[IncomeTypeTotal], fiscal_year_start_month: u32) -> Vec<MiningYear> {
+
+    let mut years: Vec<MiningYear> = Vec::new();
+
+    for total in incomes.iter().filter(|total| total.income_type == Some(IncomeType::Mining)) {
+        match years.iter_mut().find(|y| y.year == total.year) {
+            Some(year) => year.income += total.income,
+            None => years.push(MiningYear { year: total.year, income: total.income, expenses: Vec::new() }),
+        }
+    }
+
+    for total in expenses_by_category_and_year(costs, fiscal_year_start_month) {
+        match years.iter_mut().find(|y| y.year == total.year) {
+            Some(year) => year.expenses.push(total),
+            None => years.push(MiningYear { year: total.year, income: dec!(0), expenses: vec![total] }),
+        }
+    }
+
+    years.sort_by_key(|year| year.year);
+
+    years
+}
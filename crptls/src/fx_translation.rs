@@ -0,0 +1,95 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+use crate::decimal_utils::round_d128_1e8;
+use crate::price_lookup;
+use crate::transaction::{Transaction, ActionRecord};
+
+
+/// A secondary home currency, and its exchange rates with the (primary) home currency, for translating a run's
+/// home currency values into it.
+#[derive(Clone, Debug)]
+pub struct FxTranslation {
+    pub currency: String,
+    /// By date, the units of the `currency` that one unit of the home currency is worth.
+    pub rates: BTreeMap<NaiveDate, Decimal>,
+}
+
+impl FxTranslation {
+
+    /// The rate on `date`, or (for a weekend or holiday without one) the latest rate before it.
+    pub fn rate_on(&self, date: NaiveDate) -> Option<Decimal> {
+        self.rates.range(..=date).next_back().map(|(_, rate)| *rate)
+    }
+}
+
+/// Reads the rates of `currency` from an FX rates file: a CSV with a header row, then one `date,currency,rate` row
+/// per rate, dated YYYY-MM-DD, with the rate being the units of the currency that one unit of the home currency is
+/// worth (i.e., `2023-01-31,EUR,0.92` for a home currency of USD).  It's the format of a price file (see
+/// `price_lookup::PriceLookup::path`), so the rates of several currencies may share a file.
+pub fn import_fx_rates(path: &Path, currency: &str) -> Result<FxTranslation, Box<dyn Error>> {
+
+    let rates: BTreeMap<NaiveDate, Decimal> = price_lookup::import_prices(path)?
+        .into_iter()
+        .filter(|((_, ticker), _)| ticker == currency)
+        .map(|((date, _), rate)| (date, rate))
+        .collect();
+
+    if rates.is_empty() {
+        return Err(format!("The FX rates file has no rates for {}.", currency).into())
+    }
+
+    Ok(FxTranslation { currency: currency.to_string(), rates })
+}
+
+/// Translates every home currency value of the imported `Transaction`s into the `translation`'s currency, at the
+/// rate of each `Transaction`'s date: its `proceeds` and `fee`, and the quantities of the home currency `Account`s,
+/// which are then that currency's.  A carried-forward `Lot`'s basis is translated at the rate of its basis date.
+/// Being done before any `Lot` is created, each basis is then translated at the rate of its acquisition, and each
+/// proceeds (or income) at the rate of its disposal (or receipt).  Returns the number of `Transaction`s translated.
+pub(crate) fn translate_home_currency_values(
+    translation: &FxTranslation,
+    home_currency: &str,
+    raw_acct_map: &mut HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<usize, Box<dyn Error>> {
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get_mut(&(num as u32)).unwrap();
+
+        let date = if txn.kind.is_carried_forward() { txn.date_for_basis_purposes } else { txn.date };
+        let rate = translation.rate_on(date).ok_or_else(|| {
+            format!("The FX rates file has no {} rate on or before {} (for transaction {}).", translation.currency, date, num)
+        })?;
+
+        txn.proceeds *= rate;
+        txn.fee *= rate;
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get_mut(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_home_currency(home_currency) {
+                ar.amount = round_d128_1e8(&(ar.amount * rate)).normalize();
+            }
+        }
+    }
+
+    for raw_acct in raw_acct_map.values_mut() {
+        if raw_acct.is_home_currency(home_currency) { raw_acct.ticker = translation.currency.clone() }
+    }
+
+    Ok(txns_map.len())
+}
@@ -8,12 +8,14 @@ use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crate::transaction::{Transaction, TxType, ActionRecord, Polarity};
+use crate::transaction::{Transaction, TxType, TxKind, ActionRecord, Polarity};
 use crate::account::{Account, RawAccount};
 use crate::decimal_utils::round_d128_1e2;
+use crate::like_kind::LikeKindScope;
 
 pub(crate) fn add_cost_basis_to_movements(
     home_currency: &String,
+    home_curr_leg_is_basis: bool,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>,
     ars: &HashMap<u32, ActionRecord>,
@@ -24,136 +26,188 @@ pub(crate) fn add_cost_basis_to_movements(
 
     for txn_num in 1..=length {
 
-        let txn_num = txn_num as u32;
-        let txn = txns_map.get(&(txn_num)).unwrap();
+        add_cost_basis_to_txn_movements(
+            txn_num as u32,
+            home_currency,
+            home_curr_leg_is_basis,
+            raw_acct_map,
+            acct_map,
+            ars,
+            txns_map,
+        )?;
+    }
 
-        for ar_num in txn.action_record_idx_vec.iter() {
+    Ok(())
+}
 
-            let ar = ars.get(ar_num).unwrap();
-            let acct = acct_map.get(&ar.account_key).unwrap();
-            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-            let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+/// Adds cost basis to the `movement`s of a single `transaction`.  Outgoing `movement`s take their basis from their
+/// `lot`, so every earlier `transaction` must already have been given its basis.
+pub(crate) fn add_cost_basis_to_txn_movements(
+    txn_num: u32,
+    home_currency: &String,
+    home_curr_leg_is_basis: bool,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
 
-            for (idx, mvmt) in movements.iter().enumerate() {
+    let txn = txns_map.get(&(txn_num)).unwrap();
 
-                let polarity = ar.direction();
-                let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
-                let is_home_curr = raw_acct.is_home_currency(home_currency);
-                let mvmt_copy = mvmt.clone();
-                let borrowed_mvmt = mvmt_copy.clone();
-                // println!("Txn: {} on {} of type: {:?}",
-                //     txn.tx_number,txn.date, txn.transaction_type(ars, raw_acct_map, acct_map));
+    for ar_num in txn.action_record_idx_vec.iter() {
 
-                if !raw_acct.is_margin {
+        let ar = ars.get(ar_num).unwrap();
+        let acct = acct_map.get(&ar.account_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
-                    match polarity {
+        for (idx, mvmt) in movements.iter().enumerate() {
 
-                        Polarity::Outgoing => {
+            let polarity = ar.direction();
+            let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+            let is_home_curr = raw_acct.is_home_currency(home_currency);
+            let mvmt_copy = mvmt.clone();
+            let borrowed_mvmt = mvmt_copy.clone();
+            // println!("Txn: {} on {} of type: {:?}",
+            //     txn.tx_number,txn.date, txn.transaction_type(ars, raw_acct_map, acct_map));
 
-                            if is_home_curr {
+            if !raw_acct.is_margin {
 
-                                let mvmts_amt = mvmt_copy.amount;
+                match polarity {
 
-                                mvmt.cost_basis.set(mvmts_amt);
-                                mvmt.cost_basis_lk.set(mvmts_amt);
+                    Polarity::Outgoing => {
 
-                            } else {
+                        if is_home_curr {
 
-                                let cb_of_lots_first_mvmt = mvmt_copy.get_cost_basis_of_lots_first_mvmt(acct_map, ars);
-                                let ratio_of_amt_to_lots_first_mvmt = borrowed_mvmt.ratio_of_amt_to_lots_first_mvmt(acct_map, ars);
-                                let unrounded_basis = -(cb_of_lots_first_mvmt * ratio_of_amt_to_lots_first_mvmt);
-                                let rounded_basis = round_d128_1e2(&unrounded_basis);
+                            let mvmts_amt = mvmt_copy.amount;
 
-                                mvmt.cost_basis.set(rounded_basis);
-                                mvmt.cost_basis_lk.set(rounded_basis);
-                            }
-                            assert!(mvmt.cost_basis.get() <= dec!(0));
-                            // assert!(mvmt.cost_basis_lk.get() <= dec!(0));   //  Same as above assert.
-                            continue
-                        }
+                            mvmt.cost_basis.set(mvmts_amt);
+                            mvmt.cost_basis_lk.set(mvmts_amt);
 
-                        Polarity::Incoming => {
+                        } else {
 
-                            if is_home_curr {
+                            let cb_of_lots_first_mvmt = mvmt_copy.get_cost_basis_of_lots_first_mvmt(acct_map, ars);
+                            let ratio_of_amt_to_lots_first_mvmt = borrowed_mvmt.ratio_of_amt_to_lots_first_mvmt(acct_map, ars);
+                            let unrounded_basis = -(cb_of_lots_first_mvmt * ratio_of_amt_to_lots_first_mvmt);
+                            let rounded_basis = round_d128_1e2(&unrounded_basis);
 
-                                let mvmts_amt = mvmt_copy.amount;
+                            mvmt.cost_basis.set(rounded_basis);
+                            mvmt.cost_basis_lk.set(rounded_basis);
+                        }
+                        assert!(mvmt.cost_basis.get() <= dec!(0));
+                        // assert!(mvmt.cost_basis_lk.get() <= dec!(0));   //  Same as above assert.
+                        continue
+                    }
 
-                                mvmt.cost_basis.set(mvmts_amt);
-                                mvmt.cost_basis_lk.set(mvmts_amt);
+                    Polarity::Incoming => {
 
-                            } else {
+                        if is_home_curr {
 
-                                match tx_type {
+                            let mvmts_amt = mvmt_copy.amount;
 
-                                    TxType::Exchange => {
+                            mvmt.cost_basis.set(mvmts_amt);
+                            mvmt.cost_basis_lk.set(mvmts_amt);
 
-                                        let other_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
-                                        let other_acct = acct_map.get(&other_ar.account_key).unwrap();
-                                        let raw_other_acct = raw_acct_map.get(&other_acct.raw_key).unwrap();
-                                        assert_eq!(other_ar.direction(), Polarity::Outgoing);
-                                        let other_ar_is_home_curr = raw_other_acct.is_home_currency(home_currency);
+                        } else {
 
-                                        if other_ar_is_home_curr {
-                                            mvmt.cost_basis.set(-(other_ar.amount));
-                                            mvmt.cost_basis_lk.set(-(other_ar.amount));
+                            match tx_type {
 
-                                        } else {
+                                TxType::Exchange => {
 
-                                            let ratio_of_amt_to_incoming_mvmts_in_a_r =
-                                                borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r;
-                                            let txn_proceeds = txn.proceeds
-                                                .to_string()
-                                                .parse::<Decimal>()
-                                                .unwrap();
-                                            let unrounded_basis = txn_proceeds * ratio_of_amt_to_incoming_mvmts_in_a_r;
-                                            let rounded_basis = round_d128_1e2(&unrounded_basis);
+                                    let other_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+                                    let other_acct = acct_map.get(&other_ar.account_key).unwrap();
+                                    let raw_other_acct = raw_acct_map.get(&other_acct.raw_key).unwrap();
+                                    assert_eq!(other_ar.direction(), Polarity::Outgoing);
+                                    let other_ar_is_home_curr = raw_other_acct.is_home_currency(home_currency);
 
-                                            mvmt.cost_basis.set(rounded_basis);
-                                            mvmt.cost_basis_lk.set(rounded_basis);
-                                        }
-                                    }
+                                    //  The home currency spent is the basis of what was acquired (unless the user
+                                    //  has elected to value the acquisition with the `proceeds` column instead).
+                                    //  That amount already includes any fee, since it is what left the account.
+                                    if other_ar_is_home_curr && home_curr_leg_is_basis {
+                                        mvmt.cost_basis.set(-(other_ar.amount));
+                                        mvmt.cost_basis_lk.set(-(other_ar.amount));
 
-                                    TxType::ToSelf => {
+                                    } else if txn.kind == TxKind::TokenMigration {
 
+                                        //  Each `Movement` takes the basis of its outgoing `Movement`, as in a `ToSelf` transfer
                                         let cb_vec_outgoing_ar = retrieve_cb_vec_from_corresponding_outgoing_toself(
                                             txn_num,
-                                            &ars,
+                                            ars,
                                             txns_map,
                                             acct_map
                                         );
+                                        let rounded_basis = round_d128_1e2(&cb_vec_outgoing_ar[idx]);
+
+                                        mvmt.cost_basis.set(-rounded_basis);
+                                        mvmt.cost_basis_lk.set(-rounded_basis);
 
-                                        assert!(idx <= cb_vec_outgoing_ar.len(),
-                                            "ToSelf txn had different # of in- and out- mvmts (more outs than ins).");
+                                    } else if txn.kind == TxKind::PoolCarryover {
 
-                                        let unrounded_basis = cb_vec_outgoing_ar[idx];
+                                        //  The basis of what was given carries over, as in a `ToSelf` transfer
+                                        let unrounded_basis = other_ar.cost_basis_in_ar() *
+                                            borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r;
                                         let rounded_basis = round_d128_1e2(&unrounded_basis);
 
-                                        mvmt.cost_basis.set(-rounded_basis);
-                                        mvmt.cost_basis_lk.set(-rounded_basis);
-                                    }
+                                        mvmt.cost_basis.set(rounded_basis);
+                                        mvmt.cost_basis_lk.set(rounded_basis);
 
-                                    TxType::Flow => {
+                                    } else {
 
-                                        let txn_proceeds = txn.proceeds.to_string().parse::<Decimal>().unwrap();
-                                        let mvmt_proceeds = round_d128_1e2(
-                                            &(txn_proceeds *
-                                            borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r)
-                                        );  //  Ratio should always be 1.0, but we do the calc anyway, for future-proofing.
+                                        let ratio_of_amt_to_incoming_mvmts_in_a_r =
+                                            borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r;
+                                        let txn_proceeds = txn.proceeds;
+                                        //  The fee on a purchase is capitalized into the basis.  On a trade of one
+                                        //  non-home currency for another, the value received is net of the fee, as
+                                        //  are the disposal's proceeds.
+                                        let fee = if other_ar_is_home_curr { txn.fee } else { -txn.fee };
+                                        let unrounded_basis = (txn_proceeds + fee) * ratio_of_amt_to_incoming_mvmts_in_a_r;
+                                        let rounded_basis = round_d128_1e2(&unrounded_basis);
 
-                                        mvmt.cost_basis.set(mvmt_proceeds);
-                                        mvmt.cost_basis_lk.set(mvmt_proceeds);
+                                        mvmt.cost_basis.set(rounded_basis);
+                                        mvmt.cost_basis_lk.set(rounded_basis);
                                     }
                                 }
+
+                                TxType::ToSelf => {
+
+                                    let cb_vec_outgoing_ar = retrieve_cb_vec_from_corresponding_outgoing_toself(
+                                        txn_num,
+                                        &ars,
+                                        txns_map,
+                                        acct_map
+                                    );
+
+                                    assert!(idx <= cb_vec_outgoing_ar.len(),
+                                        "ToSelf txn had different # of in- and out- mvmts (more outs than ins).");
+
+                                    let unrounded_basis = cb_vec_outgoing_ar[idx];
+                                    let rounded_basis = round_d128_1e2(&unrounded_basis);
+
+                                    mvmt.cost_basis.set(-rounded_basis);
+                                    mvmt.cost_basis_lk.set(-rounded_basis);
+                                }
+
+                                TxType::Flow => {
+
+                                    let txn_proceeds = txn.proceeds;
+                                    let mvmt_proceeds = round_d128_1e2(
+                                        &(txn_proceeds *
+                                        borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r)
+                                    );  //  Ratio should always be 1.0, but we do the calc anyway, for future-proofing.
+
+                                    mvmt.cost_basis.set(mvmt_proceeds);
+                                    mvmt.cost_basis_lk.set(mvmt_proceeds);
+                                }
                             }
-                            assert!(mvmt.cost_basis.get() >= dec!(0));
-                            // assert!(mvmt.cost_basis_lk.get() >= dec!(0));   //  Same as above assert.
-                            continue
                         }
+                        assert!(mvmt.cost_basis.get() >= dec!(0));
+                        // assert!(mvmt.cost_basis_lk.get() >= dec!(0));   //  Same as above assert.
+                        continue
                     }
-                } else {
-                    // Do nothing. Future changes can add a code path where margin txns "settle"
-                    // as they happen, though, if desired. Just need to write the code.
                 }
+            } else {
+                // Do nothing. Future changes can add a code path where margin txns "settle"
+                // as they happen, though, if desired. Just need to write the code.
             }
         }
     }
@@ -170,7 +224,7 @@ pub(crate) fn add_cost_basis_to_movements(
 
         assert_eq!(other_ar_borrowed.direction(), Polarity::Outgoing);
 
-        let movements = other_ar_borrowed.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+        let movements = other_ar_borrowed.get_mvmts_in_ar_in_lot_date_order();
         let mut vec = Vec::new();
 
         for mvmt in movements.iter() {
@@ -184,6 +238,7 @@ pub(crate) fn add_cost_basis_to_movements(
 }
 
 pub(crate) fn add_proceeds_to_movements(
+    home_currency: &str,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>,
     ars: &HashMap<u32, ActionRecord>,
@@ -202,7 +257,7 @@ pub(crate) fn add_proceeds_to_movements(
             let ar = ars.get(ar_num).unwrap();
             let acct = acct_map.get(&ar.account_key).unwrap();
             let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-            let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+            let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
             if !raw_acct.is_margin {
 
@@ -227,8 +282,25 @@ pub(crate) fn add_proceeds_to_movements(
                                         continue
                                     }
 
+                                    if (tx_type == TxType::Exchange) && matches!(txn.kind, TxKind::PoolCarryover | TxKind::TokenMigration) {
+
+                                        // Its basis carries over to what was received, so no gain or loss is realized
+                                        mvmt.proceeds.set(-mvmt.cost_basis.get());
+                                        mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+                                        continue
+                                    }
+
+                                    if (tx_type == TxType::Exchange) && raw_acct.is_home_currency(home_currency) {
+
+                                        // The home currency leg of a trade is the basis source for what was acquired,
+                                        // not a disposal, so its proceeds simply net against its basis for a $0 gain.
+                                        mvmt.proceeds.set(-mvmt.cost_basis.get());
+                                        mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+                                        continue
+                                    }
+
                                     let ratio = borrowed_mvmt.amount / ar.amount;
-                                    let proceeds_unrounded = txn.proceeds.to_string().parse::<Decimal>().unwrap() * ratio;
+                                    let proceeds_unrounded = txn.proceeds_net_of_fee() * ratio;
                                     let proceeds_rounded = round_d128_1e2(&proceeds_unrounded);
 
                                     mvmt.proceeds.set(proceeds_rounded);
@@ -237,12 +309,23 @@ pub(crate) fn add_proceeds_to_movements(
                                 }
 
                                 Polarity::Incoming => {
+
+                                    if !matches!(txn.kind, TxKind::Standard | TxKind::PoolCarryover | TxKind::TokenMigration | TxKind::EntitySale) {
+
+                                        // A synthetic acquisition only stands in for missing history, an inheritance is
+                                        // received at a stepped-up basis, and a carried-forward lot was acquired in a prior
+                                        // run. None is income, so each has a basis but no (negative) proceeds.
+                                        continue
+                                    }
+
                                     // For a time, this was blank. As part of the commit(s) to add cost_basis_lk
                                     // and proceeds_lk, let's change this to reflect that incoming proceeds are now
                                     // negative, which net against the positive cost_basis to result in a gain of $0.
-                                    // Additionally, we apply the same treatment to Flow txns.
-                                    mvmt.proceeds.set(-mvmt.cost_basis.get());
-                                    mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+                                    // Additionally, we apply the same treatment to Flow txns. A wash sale replacement's
+                                    // basis includes a disallowed loss, which its (negative) adjustment offsets.
+                                    let adjustment = mvmt.wash_sale_adjustment.get();
+                                    mvmt.proceeds.set(-(mvmt.cost_basis.get() + adjustment));
+                                    mvmt.proceeds_lk.set(-(mvmt.cost_basis_lk.get() + adjustment));
                                 }
                             }
                         }
@@ -265,9 +348,12 @@ pub(crate) fn add_proceeds_to_movements(
     Ok(())
 }
 
+/// Applies like-kind treatment to each `Transaction` dated on or before the `cutoff_date` (or within a window of the
+/// `scope`), for an exchange of two non-home currencies only if they're of one of the `scope`'s pairs.
 pub(crate) fn apply_like_kind_treatment(
     home_currency: &String,
     cutoff_date: NaiveDate,
+    scope: &LikeKindScope,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>,
     ars: &HashMap<u32, ActionRecord>,
@@ -283,8 +369,8 @@ pub(crate) fn apply_like_kind_treatment(
 
         update_current_txn_for_prior_likekind_treatment(txn_num, home_currency, &raw_acct_map, &acct_map, &ars, &txns_map)?;
 
-        if txn.date <= cutoff_date {
-            perform_likekind_treatment_on_txn(txn_num, home_currency, &raw_acct_map, &acct_map, &ars, &txns_map)?;
+        if scope.covers_date(cutoff_date, txn.date) {
+            perform_likekind_treatment_on_txn(txn_num, home_currency, scope, &raw_acct_map, &acct_map, &ars, &txns_map)?;
         }
     }
 
@@ -308,7 +394,7 @@ fn update_current_txn_for_prior_likekind_treatment(
         let ar = ars.get(ar_num).unwrap();
         let acct = acct_map.get(&ar.account_key).unwrap();
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-        let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+        let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
         for mvmt in movements.iter() {
 
@@ -334,7 +420,7 @@ fn update_current_txn_for_prior_likekind_treatment(
 
                             mvmt.cost_basis_lk.set(rounded_lk_basis);
 
-                            if tx_type == TxType::ToSelf {
+                            if tx_type == TxType::ToSelf || txn.kind == TxKind::TokenMigration {
                                 mvmt.proceeds_lk.set(-rounded_lk_basis)
                             }
                         }
@@ -345,6 +431,18 @@ fn update_current_txn_for_prior_likekind_treatment(
 
                         match tx_type {
 
+                            TxType::Exchange if txn.kind == TxKind::TokenMigration => {
+
+                                // The lk basis carries over, as in a `ToSelf` transfer
+                                let ratio_of_amt_to_incoming_mvmts_in_a_r =
+                                    borrowed_mvmt.ratio_of_amt_to_incoming_mvmts_in_a_r;
+                                let unrounded_lk_basis = sum_of_outgoing_lk_cost_basis_in_ar *
+                                    ratio_of_amt_to_incoming_mvmts_in_a_r;
+                                let rounded_lk_basis = round_d128_1e2(&unrounded_lk_basis);
+
+                                mvmt.cost_basis_lk.set(-rounded_lk_basis);
+                                mvmt.proceeds_lk.set(rounded_lk_basis);
+                            }
                             TxType::Exchange => {
                                 // Do nothing.
                                 // If txn.date is after the LK treatment date, the incoming mvmt goes untreated.
@@ -384,6 +482,7 @@ fn update_current_txn_for_prior_likekind_treatment(
 fn perform_likekind_treatment_on_txn(
     txn_num: u32,
     home_currency: &String,
+    scope: &LikeKindScope,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>,
     ars: &HashMap<u32, ActionRecord>,
@@ -397,14 +496,19 @@ fn perform_likekind_treatment_on_txn(
 
         TxType::Exchange => {
 
-            if txn.both_exch_ars_are_non_home_curr(ars, raw_acct_map, acct_map, home_currency)? {
+            let ticker = |ar_num: &u32| &raw_acct_map.get(&acct_map.get(&ars.get(ar_num).unwrap().account_key).unwrap().raw_key).unwrap().ticker;
+            let (given, received) = (ticker(&txn.action_record_idx_vec[0]), ticker(&txn.action_record_idx_vec[1]));
+
+            if txn.both_exch_ars_are_non_home_curr(ars, raw_acct_map, acct_map, home_currency)? && scope.covers_pair(given, received)
+                // (a sale between entities is no exchange of one asset for another)
+                && txn.kind != TxKind::EntitySale {
 
                 let mut sum_of_outgoing_lk_cost_basis_in_ar = dec!(0);
 
                 for ar_num in txn.action_record_idx_vec.iter() {
 
                     let ar = ars.get(ar_num).unwrap();
-                    let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+                    let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
                     for mvmt in movements.iter() {
 
@@ -449,7 +553,7 @@ fn perform_likekind_treatment_on_txn(
                 for ar_num in txn.action_record_idx_vec.iter() {
 
                     let ar = ars.get(ar_num).unwrap();
-                    let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+                    let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
                     let polarity = ar.direction();
 
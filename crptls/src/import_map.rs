@@ -0,0 +1,144 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde_derive::Deserialize;
+
+
+/// Declares which columns of a CSV file (with a single header row, as exchanges typically export) hold the data of a
+/// CSV Input File, so such a file can be imported without first being rearranged by hand.  Columns are identified by
+/// their header.  It is read from a TOML file, i.e.:
+///
+/// ```toml
+/// date = "Timestamp"
+/// proceeds = "USD Total"
+/// memo = "Notes"
+///
+/// [[accounts]]
+/// column = "BTC Amount"
+/// name = "Coinbase"
+/// ticker = "BTC"
+///
+/// [[accounts]]
+/// column = "USD Amount"
+/// name = "Bank"
+/// ticker = "USD"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportMap {
+    /// The `txDate` column.
+    pub date: String,
+    /// The `proceeds` column.  Without one (or where blank), proceeds are 0.
+    pub proceeds: Option<String>,
+    /// The `memo` column.  Without one, memos are blank.
+    pub memo: Option<String>,
+    /// The optional `settleDate` column.
+    pub settle_date: Option<String>,
+    /// The optional `txType` column.
+    pub tx_type: Option<String>,
+    /// The optional `fee` column.
+    pub fee: Option<String>,
+    /// The optional `incomeType` column.
+    pub income_type: Option<String>,
+    /// The `Account`s, numbered in the order listed.
+    pub accounts: Vec<MappedAccount>,
+}
+
+/// A column of quantities and the `Account` it belongs to.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappedAccount {
+    pub column: String,
+    pub name: String,
+    pub ticker: String,
+    #[serde(default)]
+    pub margin: bool,
+}
+
+impl ImportMap {
+
+    pub fn from_file(path: &Path) -> Result<ImportMap, Box<dyn Error>> {
+
+        let import_map: ImportMap = toml::from_str(&fs::read_to_string(path)?)?;
+
+        if import_map.accounts.is_empty() {
+            return Err("The import map must list at least one account (as [[accounts]]).".into())
+        }
+
+        Ok(import_map)
+    }
+
+    /// Rearranges the `source` CSV into the layout of a CSV Input File (the `txDate`, `proceeds` and `memo` columns, any
+    /// optional columns, then one column per `Account`, beneath the four account header rows), written to `output`.
+    pub(crate) fn remap<W: Write + 'static>(&self, source: impl Read, output: W) -> Result<W, Box<dyn Error>> {
+
+        let mut rdr = csv::ReaderBuilder::new().has_headers(true).flexible(true).from_reader(source);
+        let headers = rdr.headers()?.clone();
+
+        let find = |name: &str| -> Result<usize, Box<dyn Error>> {
+            headers.iter().position(|header| header.trim() == name.trim())
+                .ok_or_else(|| format!("The import map names a column ({}) that isn't in the file's header row.", name).into())
+        };
+
+        let date_col = find(&self.date)?;
+        let proceeds_col = self.proceeds.as_deref().map(find).transpose()?;
+        let memo_col = self.memo.as_deref().map(find).transpose()?;
+        let settle_date_col = self.settle_date.as_deref().map(find).transpose()?;
+        let tx_type_col = self.tx_type.as_deref().map(find).transpose()?;
+        let fee_col = self.fee.as_deref().map(find).transpose()?;
+        let income_type_col = self.income_type.as_deref().map(find).transpose()?;
+        let acct_cols = self.accounts.iter().map(|acct| find(&acct.column)).collect::<Result<Vec<usize>, _>>()?;
+
+        let mut optional_headers: Vec<&str> = Vec::new();
+        if settle_date_col.is_some() { optional_headers.push("settleDate") }
+        if tx_type_col.is_some() { optional_headers.push("txType") }
+        if fee_col.is_some() { optional_headers.push("fee") }
+        if income_type_col.is_some() { optional_headers.push("incomeType") }
+
+        let mut wtr = csv::Writer::from_writer(output);
+
+        let account_header = |field: &dyn Fn(&MappedAccount) -> String| -> Vec<String> {
+            ["".to_string(), "".to_string(), "".to_string()].into_iter()
+                .chain(optional_headers.iter().map(|_| "".to_string()))
+                .chain(self.accounts.iter().map(field))
+                .collect()
+        };
+
+        let mut first_header = vec!["txDate".to_string(), "proceeds".to_string(), "memo".to_string()];
+        first_header.extend(optional_headers.iter().map(|header| header.to_string()));
+        first_header.extend((1..=self.accounts.len()).map(|num| num.to_string()));
+
+        wtr.write_record(&first_header)?;
+        wtr.write_record(account_header(&|acct| acct.name.clone()))?;
+        wtr.write_record(account_header(&|acct| acct.ticker.clone()))?;
+        wtr.write_record(account_header(&|acct| if acct.margin { "yes".to_string() } else { "no".to_string() }))?;
+
+        for result in rdr.records() {
+
+            let record = result?;
+            let field = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or("").trim().to_string();
+
+            let proceeds = field(proceeds_col);
+
+            let mut row = vec![
+                field(Some(date_col)),
+                if proceeds.is_empty() { "0".to_string() } else { proceeds },
+                field(memo_col),
+            ];
+            if settle_date_col.is_some() { row.push(field(settle_date_col)) }
+            if tx_type_col.is_some() { row.push(field(tx_type_col)) }
+            if fee_col.is_some() { row.push(field(fee_col)) }
+            if income_type_col.is_some() { row.push(field(income_type_col)) }
+            row.extend(acct_cols.iter().map(|col| field(Some(*col))));
+
+            wtr.write_record(&row)?;
+        }
+
+        Ok(wtr.into_inner()?)
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+
+use super::{ImportedTxn, amount, column, datetime, reader, split_market, units};
+
+
+/// Binance's quote currencies.
+const QUOTES: [&str; 14] = [
+    "USDT", "BUSD", "USDC", "TUSD", "FDUSD", "USD", "EUR", "GBP", "TRY", "BTC", "ETH", "BNB", "DAI", "AUD",
+];
+
+/// Reads Binance's trade history export, in either its older layout (`Date(UTC)`, `Market` (i.e., `BTCUSDT`),
+/// `Type`, `Amount`, `Total`, `Fee`, `Fee Coin`) or its newer one (`Date(UTC)`, `Pair`, `Side`, `Executed`,
+/// `Amount` and `Fee`, each amount with its units, i.e., `0.5BTC`).  A fee in the base or quote currency reduces
+/// what was received.  A fee in any other currency (i.e., BNB) is its own outgoing transaction.
+pub(super) fn parse(export: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut rdr = reader(export);
+    let headers = rdr.headers()?.clone();
+
+    let newer_layout = column(&headers, "Executed").is_ok();
+
+    let date_col = column(&headers, "Date(UTC)")?;
+    let market_col = if newer_layout { column(&headers, "Pair")? } else { column(&headers, "Market")? };
+    let side_col = if newer_layout { column(&headers, "Side")? } else { column(&headers, "Type")? };
+    let base_amount_col = if newer_layout { column(&headers, "Executed")? } else { column(&headers, "Amount")? };
+    let quote_amount_col = if newer_layout { column(&headers, "Amount")? } else { column(&headers, "Total")? };
+    let fee_col = column(&headers, "Fee")?;
+    let fee_coin_col = if newer_layout { None } else { Some(column(&headers, "Fee Coin")?) };
+
+    let mut txns = Vec::new();
+
+    for result in rdr.records() {
+
+        let record = result?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let datetime = datetime(field(date_col))?;
+        let (base, quote) = split_market(&field(market_col).replace(['/', '-', '_'], ""), &QUOTES)?;
+        let base_amount = amount(field(base_amount_col))?.abs();
+        let quote_amount = amount(field(quote_amount_col))?.abs();
+        let fee = amount(field(fee_col))?.abs();
+        let fee_coin = match fee_coin_col {
+            Some(col) => field(col).to_uppercase(),
+            None => units(field(fee_col)),
+        };
+        let side = field(side_col).to_uppercase();
+        let memo = format!("{} {}", side, field(market_col));
+
+        let (mut base_leg, mut quote_leg) = match side.as_str() {
+            "BUY" => ((base.clone(), base_amount), (quote.clone(), -quote_amount)),
+            "SELL" => ((base.clone(), -base_amount), (quote.clone(), quote_amount)),
+            other => return Err(format!("the side `{}` (on {}) isn't BUY or SELL", other, datetime).into()),
+        };
+
+        let mut fee_txn = None;
+
+        if fee_coin == base {
+            base_leg.1 -= fee;
+        } else if fee_coin == quote {
+            quote_leg.1 -= fee;
+        } else if !fee.is_zero() {
+            fee_txn = Some(ImportedTxn {
+                datetime,
+                memo: format!("Fee for {}", memo),
+                value: None,
+                legs: vec![(fee_coin, -fee)],
+            });
+        }
+
+        txns.push(ImportedTxn::trade(datetime, memo, base_leg, quote_leg));
+        txns.extend(fee_txn);
+    }
+
+    Ok(txns)
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::Value;
+
+use super::{ChainApi, ImportedTxn};
+
+
+/// Reads the `pages` of each `ChainApi`'s responses into a transaction per on-chain transaction, received (positive)
+/// or sent (negative, including its fee) by the wallet.  A transaction between two of the wallet's own addresses is
+/// just its fee.  The ETH `eth_addresses` are the wallet's, to tell a sent transaction from a received one.
+pub(super) fn parse(pages: &[(ChainApi, String)], eth_addresses: &[String]) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    //  A transaction may be on several pages (i.e., one for each of its addresses), so each is netted by its hash
+    let mut by_hash: HashMap<String, ImportedTxn> = HashMap::new();
+    let eth_addresses: Vec<String> = eth_addresses.iter().map(|address| address.to_lowercase()).collect();
+    //  A node lists each output sent (to another address) by a transaction separately, each with the whole fee
+    let mut fees_charged: HashSet<String> = HashSet::new();
+    let mut fee_totals: HashMap<String, Decimal> = HashMap::new();
+
+    let mut net = |hash: String, datetime: NaiveDateTime, ticker: &str, change: Decimal, fee: Decimal| {
+        let txn = by_hash.entry(hash.clone()).or_insert_with(|| ImportedTxn {
+            datetime,
+            memo: String::new(),
+            value: None,
+            legs: vec![(ticker.to_string(), dec!(0))],
+        });
+        txn.legs[0].1 += change - fee;
+        let fee = *fee_totals.entry(hash.clone()).and_modify(|total| *total += fee).or_insert(fee);
+        let direction = if txn.legs[0].1 > dec!(0) { "Received" } else { "Sent" };
+        txn.memo = match fee.is_zero() {
+            true => format!("{} {} (tx {})", direction, ticker, hash),
+            false => format!("{} {}, with a fee of {} (tx {})", direction, ticker, fee.normalize(), hash),
+        };
+    };
+
+    for (api, page) in pages.iter() {
+
+        let response: Value = serde_json::from_str(page)?;
+        let text = |value: &Value| value.as_str().map(|s| s.to_string()).unwrap_or_else(|| value.to_string());
+
+        match api {
+
+            ChainApi::Blockchain => {
+                for tx in response["txs"].as_array().ok_or("a blockchain.info response has no `txs`")?.iter() {
+                    //  The `result` is the net change for the wallet, fee included
+                    let change = satoshis(&tx["result"])?;
+                    net(text(&tx["hash"]), time(&tx["time"])?, "BTC", change, dec!(0));
+                }
+            }
+
+            ChainApi::BitcoinRpc => {
+                if !response["error"].is_null() {
+                    return Err(format!("the node responded with an error: {}", response["error"]).into())
+                }
+                for tx in response["result"].as_array().ok_or("a node's response has no `result` array")?.iter() {
+                    let txid = text(&tx["txid"]);
+                    let amount = number(&text(&tx["amount"]))?;
+                    //  A sent transaction's fee is negative
+                    let fee = match tx["fee"].is_null() || !fees_charged.insert(txid.clone()) {
+                        true => dec!(0),
+                        false => -number(&text(&tx["fee"]))?,
+                    };
+                    let time_field = if tx["blocktime"].is_null() { &tx["time"] } else { &tx["blocktime"] };
+                    net(txid, time(time_field)?, "BTC", amount, fee);
+                }
+            }
+
+            ChainApi::Etherscan => {
+                let result = match response["result"].as_array() {
+                    Some(result) => result,
+                    //  "No transactions found" is a status of 0, with an empty result
+                    None => return Err(format!("the API responded with an error: {}", text(&response["result"])).into()),
+                };
+                for tx in result.iter() {
+
+                    let is_from = eth_addresses.contains(&text(&tx["from"]).to_lowercase());
+                    let is_to = eth_addresses.contains(&text(&tx["to"]).to_lowercase());
+                    //  A failed transaction transfers nothing, but its gas is still spent
+                    let value = if text(&tx["isError"]) == "1" { dec!(0) } else { wei(number(&text(&tx["value"]))?) };
+                    let change = match (is_from, is_to) {
+                        (true, false) => -value,
+                        (false, true) => value,
+                        _ => dec!(0),
+                    };
+                    let fee = match is_from {
+                        true => wei(number(&text(&tx["gasUsed"]))? * number(&text(&tx["gasPrice"]))?),
+                        false => dec!(0),
+                    };
+
+                    net(text(&tx["hash"]), time(&tx["timeStamp"])?, "ETH", change, fee);
+                }
+            }
+        }
+    }
+
+    //  Nothing is left of a transaction between the wallet's own addresses without a fee
+    let mut txns: Vec<ImportedTxn> = by_hash.into_values().collect();
+    txns.retain(|txn| !txn.legs[0].1.is_zero());
+
+    Ok(txns)
+}
+
+fn number(field: &str) -> Result<Decimal, Box<dyn Error>> {
+    let field = field.trim().trim_matches('"');
+    Decimal::from_str(field).or_else(|_| Decimal::from_scientific(field)).map_err(|_| format!("`{}` isn't an amount", field).into())
+}
+
+/// An amount of satoshis, in BTC.
+fn satoshis(value: &Value) -> Result<Decimal, Box<dyn Error>> {
+    Ok(number(&value.to_string())? / dec!(100_000_000))
+}
+
+/// An amount of wei, in ETH.
+fn wei(amount: Decimal) -> Decimal {
+    amount / dec!(1_000_000_000_000_000_000)
+}
+
+/// A time in seconds since the epoch (a number, or a string of one), in UTC.
+fn time(value: &Value) -> Result<NaiveDateTime, Box<dyn Error>> {
+
+    let seconds = value.as_i64().or_else(|| value.as_str().and_then(|s| s.parse::<i64>().ok()))
+        .ok_or_else(|| format!("`{}` isn't a time", value))?;
+
+    DateTime::from_timestamp(seconds, 0).map(|dt| dt.naive_utc()).ok_or_else(|| format!("`{}` isn't a time", value).into())
+}
@@ -0,0 +1,177 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use super::{ImportedTxn, amount, column, datetime, reader};
+
+
+/// Reads Coinbase's transaction history report, whose columns are `Timestamp`, `Transaction Type`, `Asset`,
+/// `Quantity Transacted`, the spot price's currency and amount, `Subtotal`, `Total (inclusive of fees and/or
+/// spread)`, `Fees and/or Spread` and `Notes` (preceded, in newer reports, by `ID`).  The lines of notes above the
+/// header row are skipped.  A `Convert`'s received currency and amount are read from its `Notes`.
+pub(super) fn parse(export: &str, home_currency: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let header_line = export.lines()
+        .position(|line| line.contains("Timestamp") && line.contains("Transaction Type"))
+        .ok_or("it has no header row with `Timestamp` and `Transaction Type` columns")?;
+    let export = export.lines().skip(header_line).collect::<Vec<&str>>().join("\n");
+
+    let mut rdr = reader(&export);
+    let headers = rdr.headers()?.clone();
+
+    let price_currency_col = column(&headers, "Spot Price Currency").or_else(|_| column(&headers, "Price Currency"))?;
+    let timestamp_col = column(&headers, "Timestamp")?;
+    let type_col = column(&headers, "Transaction Type")?;
+    let asset_col = column(&headers, "Asset")?;
+    let quantity_col = column(&headers, "Quantity Transacted")?;
+    let subtotal_col = column(&headers, "Subtotal")?;
+    let total_col = column(&headers, "Total (inclusive of fees and/or spread)")
+        .or_else(|_| column(&headers, "Total (inclusive of fees)"))?;
+    let notes_col = column(&headers, "Notes")?;
+
+    let mut txns = Vec::new();
+
+    for result in rdr.records() {
+
+        let record = result?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let datetime = datetime(field(timestamp_col))?;
+        let tx_type = field(type_col);
+        let asset = field(asset_col).to_uppercase();
+        let quantity = amount(field(quantity_col))?.abs();
+        let subtotal = amount(field(subtotal_col))?.abs();
+        let total = amount(field(total_col))?.abs();
+        let notes = field(notes_col);
+        let price_currency = field(price_currency_col).to_uppercase();
+
+        if !price_currency.is_empty() && price_currency != home_currency {
+            return Err(format!("a transaction on {} is valued in {}, not the home currency ({})",
+                datetime, price_currency, home_currency).into())
+        }
+
+        let memo = if notes.is_empty() { tx_type.to_string() } else { format!("{}: {}", tx_type, notes) };
+        let fiat = home_currency.to_string();
+
+        let txn = match tx_type.to_lowercase().as_str() {
+            "buy" | "advanced trade buy" => {
+                ImportedTxn::trade(datetime, memo, (asset, quantity), (fiat, -total))
+            }
+            "sell" | "advanced trade sell" => {
+                ImportedTxn::trade(datetime, memo, (asset, -quantity), (fiat, total))
+            }
+            "convert" => {
+                let (received_asset, received_quantity) = converted_to(notes)
+                    .ok_or_else(|| format!("can't tell what was received in the Convert on {} ({})", datetime, notes))?;
+                let mut txn = ImportedTxn::trade(datetime, memo, (asset, -quantity), (received_asset, received_quantity));
+                txn.value = Some(subtotal);
+                txn
+            }
+            "send" | "withdrawal" => {
+                ImportedTxn { datetime, memo, value: Some(subtotal), legs: vec![(asset, -quantity)] }
+            }
+            "receive" | "deposit" | "rewards income" | "staking income" | "learning reward" | "coinbase earn"
+            | "inflation reward" | "interest" => {
+                ImportedTxn { datetime, memo, value: Some(subtotal), legs: vec![(asset, quantity)] }
+            }
+            other => {
+                return Err(format!("the transaction type `{}` (on {}) isn't supported", other, datetime).into())
+            }
+        };
+
+        txns.push(txn);
+    }
+
+    Ok(txns)
+}
+
+/// Reads the `pages` of Coinbase's API (v2) `transactions` of each account, each with a `data` array of transactions,
+/// which have a `type`, a `status`, an `amount` and `native_amount` (each with an `amount` and a `currency`) and a
+/// `created_at` time.  Only `completed` transactions are read.  A buy or sale is in the account of the currency
+/// bought or sold, and is valued at its `native_amount`, so any buy, sale or trade in the home currency's own account
+/// is left out.  The two sides of a `trade` (a Convert), in two accounts, are joined by the trade's `id`.
+pub(super) fn parse_api(pages: &[String], home_currency: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut txns = Vec::new();
+    //  The index in `txns` of each trade with only one of its sides read so far
+    let mut trades: HashMap<String, usize> = HashMap::new();
+
+    for page in pages.iter() {
+
+        let response: Value = serde_json::from_str(page)?;
+
+        if let Some(error) = response["errors"].as_array().and_then(|errors| errors.first()) {
+            return Err(format!("the API responded with an error: {}", error["message"]).into())
+        }
+
+        let data = response["data"].as_array().ok_or("a response has no `data` array of transactions")?;
+
+        for entry in data.iter() {
+
+            let text = |value: &Value| value.as_str().unwrap_or("").trim().to_string();
+
+            if text(&entry["status"]) != "completed" { continue }
+
+            let datetime = datetime(&text(&entry["created_at"]))?;
+            let tx_type = text(&entry["type"]);
+            let asset = text(&entry["amount"]["currency"]).to_uppercase();
+            let quantity = amount(&text(&entry["amount"]["amount"]))?;
+            let native = amount(&text(&entry["native_amount"]["amount"]))?;
+            let native_currency = text(&entry["native_amount"]["currency"]).to_uppercase();
+
+            if !native_currency.is_empty() && native_currency != home_currency {
+                return Err(format!("a transaction on {} is valued in {}, not the home currency ({})",
+                    datetime, native_currency, home_currency).into())
+            }
+
+            let is_trade = matches!(tx_type.as_str(), "buy" | "sell" | "trade" | "advanced_trade_fill");
+            if is_trade && asset == home_currency { continue }
+
+            let title = text(&entry["details"]["title"]);
+            let memo = if title.is_empty() { tx_type.clone() } else { format!("{}: {}", tx_type, title) };
+            let fiat = home_currency.to_string();
+
+            match tx_type.as_str() {
+                "buy" | "sell" | "advanced_trade_fill" => {
+                    txns.push(ImportedTxn::trade(datetime, memo, (asset, quantity), (fiat, -native)));
+                }
+                "trade" => {
+                    let trade_id = text(&entry["trade"]["id"]);
+                    match trades.remove(&trade_id) {
+                        Some(idx) => {
+                            let txn: &mut ImportedTxn = &mut txns[idx];
+                            txn.legs.push((asset, quantity));
+                            //  The outgoing side values the trade
+                            if quantity < Decimal::ZERO { txn.value = Some(native.abs()) }
+                        }
+                        None => {
+                            trades.insert(trade_id, txns.len());
+                            txns.push(ImportedTxn { datetime, memo, value: Some(native.abs()), legs: vec![(asset, quantity)] });
+                        }
+                    }
+                }
+                _ => {
+                    txns.push(ImportedTxn { datetime, memo, value: Some(native.abs()), legs: vec![(asset, quantity)] });
+                }
+            }
+        }
+    }
+
+    Ok(txns)
+}
+
+/// The received currency and amount in a Convert's notes, i.e., `Converted 0.5 ETH to 0.0123 BTC`.
+fn converted_to(notes: &str) -> Option<(String, Decimal)> {
+
+    let received = notes.rsplit(" to ").next()?;
+    let mut words = received.split_whitespace();
+    let quantity = amount(words.next()?).ok()?;
+    let asset = words.next()?.to_uppercase();
+
+    Some((asset, quantity.abs()))
+}
@@ -0,0 +1,50 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+
+use super::{ImportedTxn, amount, column, datetime, reader};
+
+
+/// Reads Coinbase Pro's fills report, whose columns include `trade id`, `product` (i.e., `BTC-USD`), `side`,
+/// `created at`, `size`, `size unit`, `total` (negative for a buy, and net of the fee either way) and
+/// `price/fee/total unit`.
+pub(super) fn parse(export: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut rdr = reader(export);
+    let headers = rdr.headers()?.clone();
+
+    let trade_id_col = column(&headers, "trade id")?;
+    let product_col = column(&headers, "product")?;
+    let side_col = column(&headers, "side")?;
+    let created_at_col = column(&headers, "created at")?;
+    let size_col = column(&headers, "size")?;
+    let size_unit_col = column(&headers, "size unit")?;
+    let total_col = column(&headers, "total")?;
+    let total_unit_col = column(&headers, "price/fee/total unit")?;
+
+    let mut txns = Vec::new();
+
+    for result in rdr.records() {
+
+        let record = result?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let datetime = datetime(field(created_at_col))?;
+        let size = amount(field(size_col))?.abs();
+        let total = amount(field(total_col))?.abs();
+        let base = field(size_unit_col).to_uppercase();
+        let quote = field(total_unit_col).to_uppercase();
+        let memo = format!("{} {} (trade {})", field(side_col), field(product_col), field(trade_id_col));
+
+        let txn = match field(side_col).to_uppercase().as_str() {
+            "BUY" => ImportedTxn::trade(datetime, memo, (base, size), (quote, -total)),
+            "SELL" => ImportedTxn::trade(datetime, memo, (base, -size), (quote, total)),
+            other => return Err(format!("the side `{}` (on {}) isn't BUY or SELL", other, datetime).into()),
+        };
+
+        txns.push(txn);
+    }
+
+    Ok(txns)
+}
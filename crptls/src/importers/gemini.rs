@@ -0,0 +1,75 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+
+use rust_decimal::Decimal;
+
+use super::{ImportedTxn, amount, column, datetime, reader};
+
+
+/// Reads Gemini's transaction history (saved from Gemini's spreadsheet as CSV), whose columns include `Date`,
+/// `Time (UTC)`, `Type`, `Symbol`, and, for each currency, `<CCY> Amount <CCY>` and `Fee (<CCY>) <CCY>` (i.e.,
+/// `BTC Amount BTC`), with outgoing amounts and fees in parentheses.  Each row's legs are its non-zero amounts, net
+/// of fees.
+pub(super) fn parse(export: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut rdr = reader(export);
+    let headers = rdr.headers()?.clone();
+
+    let date_col = column(&headers, "Date")?;
+    let time_col = column(&headers, "Time (UTC)").ok();
+    let type_col = column(&headers, "Type")?;
+    let symbol_col = column(&headers, "Symbol").ok();
+
+    //  (ticker, amount column, fee column)
+    let mut currency_cols: Vec<(String, usize, Option<usize>)> = Vec::new();
+
+    for (idx, header) in headers.iter().enumerate() {
+        let words: Vec<&str> = header.split_whitespace().collect();
+        if let [ticker, "Amount", units] = words.as_slice() {
+            if ticker == units {
+                let fee_col = column(&headers, &format!("Fee ({}) {}", ticker, ticker))
+                    .or_else(|_| column(&headers, &format!("Trading Fee ({}) {}", ticker, ticker)))
+                    .ok();
+                currency_cols.push((ticker.to_uppercase(), idx, fee_col));
+            }
+        }
+    }
+
+    if currency_cols.is_empty() {
+        return Err("it has no `<CCY> Amount <CCY>` columns".into())
+    }
+
+    let mut txns = Vec::new();
+
+    for result in rdr.records() {
+
+        let record = result?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        if field(date_col).is_empty() { continue }  //  The totals row at the bottom
+
+        let datetime = match time_col {
+            Some(col) if !field(col).is_empty() => datetime(&format!("{} {}", field(date_col), field(col)))?,
+            _ => datetime(field(date_col))?,
+        };
+
+        let mut legs: Vec<(String, Decimal)> = Vec::new();
+
+        for (ticker, amount_col, fee_col) in currency_cols.iter() {
+            let mut net = amount(field(*amount_col))?;
+            if let Some(fee_col) = fee_col {
+                net -= amount(field(*fee_col))?.abs();
+            }
+            if !net.is_zero() { legs.push((ticker.clone(), net)) }
+        }
+
+        let symbol = symbol_col.map(field).unwrap_or("");
+        let memo = format!("{} {}", field(type_col), symbol).trim().to_string();
+
+        txns.push(ImportedTxn { datetime, memo, value: None, legs });
+    }
+
+    Ok(txns)
+}
@@ -0,0 +1,150 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+
+use chrono::{DateTime, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use super::{ImportedTxn, amount, column, datetime, reader, split_market};
+
+
+/// The quote currencies of Kraken's pairs, in both its legacy (`ZUSD`, `XXBT`) and newer (`USD`, `XBT`) codes.
+const QUOTES: [&str; 21] = [
+    "ZUSD", "ZEUR", "ZGBP", "ZCAD", "ZJPY", "ZCHF", "ZAUD", "XXBT", "XETH",
+    "USD", "EUR", "GBP", "CAD", "JPY", "CHF", "AUD", "XBT", "ETH", "USDT", "USDC", "DAI",
+];
+
+/// Reads Kraken's trades export, whose columns include `txid`, `pair` (i.e., `XXBTZUSD`), `time`, `type` (`buy` or
+/// `sell`), `cost`, `fee` and `vol`.
+pub(super) fn parse(export: &str) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut rdr = reader(export);
+    let headers = rdr.headers()?.clone();
+
+    let txid_col = column(&headers, "txid")?;
+    let pair_col = column(&headers, "pair")?;
+    let time_col = column(&headers, "time")?;
+    let type_col = column(&headers, "type")?;
+    let cost_col = column(&headers, "cost")?;
+    let fee_col = column(&headers, "fee")?;
+    let vol_col = column(&headers, "vol")?;
+
+    let mut txns = Vec::new();
+
+    for result in rdr.records() {
+
+        let record = result?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let datetime = datetime(field(time_col))?;
+        let cost = amount(field(cost_col))?.abs();
+        let fee = amount(field(fee_col))?.abs();
+        let vol = amount(field(vol_col))?.abs();
+
+        txns.push(trade(datetime, field(txid_col), field(pair_col), field(type_col), cost, fee, vol)?);
+    }
+
+    Ok(txns)
+}
+
+/// Reads the `pages` of Kraken's `TradesHistory` (each trade by its txid in `result.trades`, with the columns of the
+/// trades export) and `Ledgers` (each entry in `result.ledger`, of which only the `deposit`s and `withdrawal`s are
+/// read, with an `asset`, an `amount` and a `fee`) API responses.  Times are in seconds since the epoch.
+pub(super) fn parse_api(pages: &[String]) -> Result<Vec<ImportedTxn>, Box<dyn Error>> {
+
+    let mut txns = Vec::new();
+
+    for page in pages.iter() {
+
+        let response: Value = serde_json::from_str(page)?;
+
+        if let Some(error) = response["error"].as_array().and_then(|errors| errors.first()) {
+            return Err(format!("the API responded with an error: {}", error).into())
+        }
+
+        let result = &response["result"];
+        let text = |entry: &Value, key: &str| entry[key].as_str().unwrap_or("").trim().to_string();
+        let number = |entry: &Value, key: &str| amount(&text(entry, key));
+
+        for (txid, trade_entry) in result["trades"].as_object().into_iter().flatten() {
+            txns.push(trade(
+                time(&trade_entry["time"])?,
+                txid,
+                &text(trade_entry, "pair"),
+                &text(trade_entry, "type"),
+                number(trade_entry, "cost")?.abs(),
+                number(trade_entry, "fee")?.abs(),
+                number(trade_entry, "vol")?.abs(),
+            )?);
+        }
+
+        for (id, entry) in result["ledger"].as_object().into_iter().flatten() {
+
+            let entry_type = text(entry, "type");
+            if entry_type != "deposit" && entry_type != "withdrawal" { continue }
+
+            //  The fee comes out of the amount deposited, or on top of the amount withdrawn
+            let quantity = number(entry, "amount")? - number(entry, "fee")?.abs();
+            let memo = format!("{} {} (ledger {})", entry_type, text(entry, "asset"), id);
+
+            txns.push(ImportedTxn {
+                datetime: time(&entry["time"])?,
+                memo,
+                value: None,
+                legs: vec![(asset(&text(entry, "asset")), quantity)],
+            });
+        }
+    }
+
+    Ok(txns)
+}
+
+/// A buy or sale of the `pair` (i.e., `XXBTZUSD`).  The fee is in the quote currency, so a buy spends `cost` plus `fee`
+/// and a sale receives `cost` less `fee`.
+fn trade(
+    datetime: NaiveDateTime,
+    txid: &str,
+    pair: &str,
+    trade_type: &str,
+    cost: Decimal,
+    fee: Decimal,
+    vol: Decimal,
+) -> Result<ImportedTxn, Box<dyn Error>> {
+
+    let (base, quote) = split_market(pair, &QUOTES)?;
+    let (base, quote) = (asset(&base), asset(&quote));
+    let memo = format!("{} {} (txid {})", trade_type, pair, txid);
+
+    match trade_type.to_lowercase().as_str() {
+        "buy" => Ok(ImportedTxn::trade(datetime, memo, (base, vol), (quote, -(cost + fee)))),
+        "sell" => Ok(ImportedTxn::trade(datetime, memo, (base, -vol), (quote, cost - fee))),
+        other => Err(format!("the type `{}` (on {}) isn't buy or sell", other, datetime).into()),
+    }
+}
+
+/// A time in seconds since the epoch (i.e., `1688667796.8802`), in UTC.
+fn time(value: &Value) -> Result<NaiveDateTime, Box<dyn Error>> {
+
+    let seconds = value.as_f64().ok_or_else(|| format!("`{}` isn't a time", value))?;
+
+    DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+        .map(|dt| dt.naive_utc())
+        .ok_or_else(|| format!("`{}` isn't a time", value).into())
+}
+
+/// The usual ticker for a Kraken asset code (i.e., `BTC` for `XXBT` or `XBT`, and `USD` for `ZUSD`).
+fn asset(code: &str) -> String {
+
+    let code = match code.len() {
+        4 if code.starts_with('X') || code.starts_with('Z') => &code[1..],
+        _ => code,
+    };
+
+    match code {
+        "XBT" => "BTC".to_string(),
+        "XDG" => "DOGE".to_string(),
+        other => other.to_string(),
+    }
+}
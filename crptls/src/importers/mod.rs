@@ -0,0 +1,361 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! Converters from the transaction-history CSVs that exchanges export to the layout of a CSV Input File.  Each
+//! converter reads an export into `ImportedTxn`s, which are then written out with one `Account` per currency.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::core_functions::ImportProcessParameters;
+
+mod binance;
+mod chain;
+mod coinbase;
+mod coinbase_pro;
+mod gemini;
+mod kraken;
+
+
+/// The exchange whose export format the file to import is in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// Coinbase's transaction history report.
+    Coinbase,
+    /// Coinbase Pro's fills report.
+    CoinbasePro,
+    /// Kraken's trades export.
+    Kraken,
+    /// Binance's trade history export.
+    Binance,
+    /// Gemini's transaction history, saved as CSV.
+    Gemini,
+}
+
+impl Source {
+    pub const ALL: [Source; 5] = [Source::Coinbase, Source::CoinbasePro, Source::Kraken, Source::Binance, Source::Gemini];
+
+    /// The name used on the command line (i.e., `coinbase-pro`).
+    pub fn arg_name(&self) -> &'static str {
+        match self {
+            Source::Coinbase => "coinbase",
+            Source::CoinbasePro => "coinbase-pro",
+            Source::Kraken => "kraken",
+            Source::Binance => "binance",
+            Source::Gemini => "gemini",
+        }
+    }
+}
+
+impl fmt::Display for Source {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+           Source::Coinbase => write!(f, "Coinbase"),
+           Source::CoinbasePro => write!(f, "Coinbase Pro"),
+           Source::Kraken => write!(f, "Kraken"),
+           Source::Binance => write!(f, "Binance"),
+           Source::Gemini => write!(f, "Gemini"),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Source::ALL.iter()
+            .find(|source| source.arg_name().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!(
+                "`{}` isn't a supported exchange. Must be one of: {}",
+                s,
+                Source::ALL.iter().map(|source| source.arg_name()).collect::<Vec<&str>>().join(", ")
+            ))
+    }
+}
+
+/// Where the history of an on-chain wallet is read from (see `chain_history_to_input_file()`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChainApi {
+    /// blockchain.info's `multiaddr`, for BTC addresses and xpubs, with each transaction's net `result` for them.
+    Blockchain,
+    /// A Bitcoin Core node's `listtransactions` JSON-RPC, for the addresses of one of its (watch-only) wallets.
+    BitcoinRpc,
+    /// An Etherscan-compatible API's `txlist`, for ETH addresses (native ETH only, not tokens).
+    Etherscan,
+}
+
+/// A row (or, for some exchanges, several rows) of an exchange's export, as a transaction of a CSV Input File.
+pub(crate) struct ImportedTxn {
+    /// In UTC.
+    pub datetime: NaiveDateTime,
+    pub memo: String,
+    /// The value in the home currency, if the export has it.  When `None`, a trade against the home currency is
+    /// valued at its home currency leg, and any other transaction at 0.
+    pub value: Option<Decimal>,
+    /// One or two (ticker, amount) pairs, with outgoing amounts negative.
+    pub legs: Vec<(String, Decimal)>,
+}
+
+impl ImportedTxn {
+
+    /// A trade of `base` for `quote` (or the reverse, depending on the signs).  A zero leg (i.e., a free airdrop
+    /// recorded as a trade) is left out.
+    pub fn trade(datetime: NaiveDateTime, memo: String, base: (String, Decimal), quote: (String, Decimal)) -> ImportedTxn {
+        let legs = [base, quote].into_iter().filter(|(_, amount)| !amount.is_zero()).collect();
+        ImportedTxn { datetime, memo, value: None, legs }
+    }
+}
+
+/// Reads the `source` export and writes it in the layout of a CSV Input File, with one non-margin `Account` per
+/// currency (named after the exchange), in date order, to `output`.  Dates are written in the format the `settings`
+/// expect.
+pub(crate) fn to_input_file<W: Write + 'static>(
+    source: Source,
+    mut input: impl Read,
+    settings: &ImportProcessParameters,
+    output: W,
+) -> Result<W, Box<dyn Error>> {
+
+    let mut contents = String::new();
+    input.read_to_string(&mut contents)?;
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    let home_currency = settings.home_currency.as_str();
+
+    let txns = match source {
+        Source::Coinbase => coinbase::parse(contents, home_currency),
+        Source::CoinbasePro => coinbase_pro::parse(contents),
+        Source::Kraken => kraken::parse(contents),
+        Source::Binance => binance::parse(contents),
+        Source::Gemini => gemini::parse(contents),
+    }.map_err(|e| format!("Couldn't read the file as a {} export: {}", source, e))?;
+
+    write_input_file(&source.to_string(), txns, settings, output)
+}
+
+/// Reads the `pages` of `source`'s transaction history, each the JSON body of a response of its API (see
+/// `cryptools sync`), and writes them in the layout of a CSV Input File, as `to_input_file()` does.  Only Coinbase's
+/// and Kraken's APIs are read.  With `after`, only the transactions (in UTC) after that date are written.
+pub fn api_history_to_input_file(
+    source: Source,
+    pages: &[String],
+    after: Option<NaiveDate>,
+    settings: &ImportProcessParameters,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+
+    let mut txns = match source {
+        Source::Coinbase => coinbase::parse_api(pages, &settings.home_currency),
+        Source::Kraken => kraken::parse_api(pages),
+        _ => return Err(format!("{}'s API can't be read. Only Coinbase's and Kraken's can.", source).into()),
+    }.map_err(|e| format!("Couldn't read {}'s API responses: {}", source, e))?;
+
+    if let Some(after) = after { txns.retain(|txn| txn.datetime.date() > after) }
+
+    write_input_file(&source.to_string(), txns, settings, Vec::new())
+}
+
+/// Reads the `pages` of the on-chain history of a wallet, each the JSON body of a response of its `ChainApi` (see
+/// `cryptools chain-sync`), and writes them in the layout of a CSV Input File, with an `Account` (named `On-chain
+/// wallet`) for each of BTC and ETH.  Each transaction is received, or sent (including its fee), with proceeds of 0,
+/// to be reviewed (i.e., to combine a transfer with its other side) and priced before processing.  The
+/// `eth_addresses` are the wallet's own.
+pub fn chain_history_to_input_file(
+    pages: &[(ChainApi, String)],
+    eth_addresses: &[String],
+    settings: &ImportProcessParameters,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+
+    let txns = chain::parse(pages, eth_addresses).map_err(|e| format!("Couldn't read the on-chain history: {}", e))?;
+
+    write_input_file("On-chain wallet", txns, settings, Vec::new())
+}
+
+/// Writes the `txns` in the layout of a CSV Input File (see `to_input_file()`), with each `Account` named `source`, to
+/// `output`.
+fn write_input_file<W: Write + 'static>(
+    source: &str,
+    mut txns: Vec<ImportedTxn>,
+    settings: &ImportProcessParameters,
+    output: W,
+) -> Result<W, Box<dyn Error>> {
+
+    let home_currency = settings.home_currency.as_str();
+
+    txns.retain(|txn| !txn.legs.is_empty());
+    txns.sort_by_key(|txn| txn.datetime);   //  Stable, so same-time rows keep their order
+
+    let mut tickers: Vec<String> = Vec::new();
+    if txns.iter().any(|txn| txn.legs.iter().any(|(ticker, _)| ticker == home_currency)) {
+        tickers.push(home_currency.to_string());
+    }
+    for txn in txns.iter() {
+        for (ticker, _) in txn.legs.iter() {
+            if !tickers.contains(ticker) { tickers.push(ticker.clone()) }
+        }
+    }
+
+    let date_format = input_file_date_format(settings);
+
+    let mut wtr = csv::Writer::from_writer(output);
+
+    //  The exports' times are UTC, so a `TIMEZONE` puts each transaction on the date it was where the user is
+    let mut first_header = vec!["txDate".to_string(), "proceeds".to_string(), "memo".to_string(), "txTime".to_string()];
+    first_header.extend((1..=tickers.len()).map(|num| num.to_string()));
+    wtr.write_record(&first_header)?;
+
+    let account_header = |field: &dyn Fn(&String) -> String| -> Vec<String> {
+        ["".to_string(), "".to_string(), "".to_string(), "".to_string()].into_iter().chain(tickers.iter().map(field)).collect()
+    };
+
+    wtr.write_record(account_header(&|_| source.to_string()))?;
+    wtr.write_record(account_header(&|ticker| ticker.clone()))?;
+    wtr.write_record(account_header(&|_| "no".to_string()))?;
+
+    let mut unvalued = 0;
+    let mut withdrawals = 0;
+
+    for txn in txns.iter() {
+
+        if txn.legs.len() > 2 || (txn.legs.len() == 2 && txn.legs[0].0 == txn.legs[1].0) {
+            return Err(format!("{} export: a transaction on {} ({}) doesn't have one or two currencies.",
+                source, txn.datetime, txn.memo).into())
+        }
+
+        let home_leg = txn.legs.iter().find(|(ticker, _)| ticker == home_currency);
+        let value = match (txn.value, home_leg) {
+            (Some(value), _) => value.abs(),
+            (None, Some((_, amount))) => amount.abs(),
+            (None, None) => {
+                if txn.legs.len() == 2 { unvalued += 1 }
+                dec!(0)
+            }
+        };
+
+        if txn.legs.len() == 1 && home_leg.is_none() && txn.legs[0].1 < dec!(0) { withdrawals += 1 }
+
+        let mut row = vec![
+            txn.datetime.date().format(&date_format).to_string(),
+            value.round_dp(2).to_string(),
+            txn.memo.clone(),
+            txn.datetime.format("%H:%M:%SZ").to_string(),
+        ];
+        for ticker in tickers.iter() {
+            let amount = txn.legs.iter().find(|(t, _)| t == ticker).map(|(_, amount)| amount.normalize().to_string());
+            row.push(amount.unwrap_or_default());
+        }
+        wtr.write_record(&row)?;
+    }
+
+    log::info!("Converted {} {} transactions, across {} currencies.", txns.len(), source, tickers.len());
+
+    if unvalued > 0 {
+        log::info!("  Note: {} trades without a {} leg have proceeds of 0, because the export doesn't value them in {}. \
+            Their proceeds must be filled in for their gains to be correct.", unvalued, home_currency, home_currency);
+    }
+
+    if withdrawals > 0 {
+        log::info!("  Note: {} withdrawals of crypto (including fees paid in a third currency) are treated as spending. \
+            A withdrawal to another of your accounts should instead be combined with the matching deposit.", withdrawals);
+    }
+
+    Ok(wtr.into_inner()?)
+}
+
+/// The format `csv_import_accts_txns` parses a `txDate` with.
+pub(crate) fn input_file_date_format(settings: &ImportProcessParameters) -> String {
+
+    let separator = &settings.input_file_date_separator;
+
+    match &settings.input_file_date_format {
+        Some(format) => format.clone(),
+        None if settings.input_file_uses_iso_date_style => format!("%Y{}%m{}%d", separator, separator),
+        None => format!("%m{}%d{}%Y", separator, separator),
+    }
+}
+
+/// Reads a CSV `export`, beginning with its header row.
+fn reader(export: &str) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(export.as_bytes())
+}
+
+/// The index of the column with the header `name` (ignoring case and surrounding whitespace).
+fn column(headers: &csv::StringRecord, name: &str) -> Result<usize, Box<dyn Error>> {
+    headers.iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("it has no `{}` column", name).into())
+}
+
+/// Parses an amount such as `1,234.50`, `$1,234.50`, `(1.5 BTC)` or `-0.25BTC`.  An amount wrapped in parentheses
+/// is negative.  A blank field is 0.
+fn amount(field: &str) -> Result<Decimal, Box<dyn Error>> {
+
+    let field = field.trim();
+    let negative = field.starts_with('(') && field.ends_with(')');
+
+    let number: String = field.chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-' || *c == 'E' || *c == 'e')
+        .collect::<String>()
+        .trim_end_matches(['E', 'e'])
+        .to_string();
+
+    if number.is_empty() || number == "-" { return Ok(dec!(0)) }
+
+    let amount = Decimal::from_str(&number)
+        .or_else(|_| Decimal::from_scientific(&number))
+        .map_err(|_| format!("`{}` isn't an amount", field))?;
+
+    Ok(if negative { -amount.abs() } else { amount })
+}
+
+/// The ticker in an amount with units (i.e., `BTC` in `0.25BTC` or `(0.25 BTC)`).
+fn units(field: &str) -> String {
+    field.chars().filter(|c| c.is_ascii_alphabetic()).collect::<String>().to_uppercase()
+}
+
+/// Parses a UTC timestamp as any of the exchanges export it.  A timestamp with an offset is converted to UTC.
+fn datetime(field: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+
+    let field = field.trim().trim_end_matches(" UTC");
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(field) {
+        return Ok(dt.naive_utc())
+    }
+
+    for format in ["%Y-%m-%dT%H:%M:%S%.fZ", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(field, format) {
+            return Ok(dt)
+        }
+    }
+
+    for format in ["%Y-%m-%d", "%m/%d/%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(field, format) {
+            return Ok(date.and_hms_opt(0, 0, 0).unwrap())
+        }
+    }
+
+    Err(format!("`{}` isn't a date and time", field).into())
+}
+
+/// Splits a market symbol without a separator (i.e., `BTCUSDT`) into its base and quote currencies, by the longest
+/// of the `quotes` it ends with.
+fn split_market(market: &str, quotes: &[&str]) -> Result<(String, String), Box<dyn Error>> {
+
+    let market = market.trim().to_uppercase();
+
+    quotes.iter()
+        .filter(|quote| market.len() > quote.len() && market.ends_with(*quote))
+        .max_by_key(|quote| quote.len())
+        .map(|quote| (market[..market.len() - quote.len()].to_string(), quote.to_string()))
+        .ok_or_else(|| format!("can't tell the currencies of the market `{}`", market).into())
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fmt;
+
+use crate::corrections::CorrectedField;
+use crate::csv_import_accts_txns::column_name;
+
+
+/// The longest part of a row shown beneath an `InputError`, in characters, before it's cut short around the field.
+const SNIPPET_WIDTH: usize = 100;
+
+/// A row of the CSV Input File (as imported, so converted or merged, if so set) that failed to import or process,
+/// with where in it the problem is.  It's shown as a diagnostic naming the row and column, followed by the row itself
+/// with the offending field underlined:
+///
+/// ```text
+/// CSV Import: at row 7, column F: The quantity (1.2.3) for account 3 isn't a number: Invalid decimal: two decimal points.
+///    |
+///  7 | 03-01-18,100.00,Buy BTC,,,1.2.3
+///    |                           ^^^^^
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputError {
+    /// The row of the file, counting the four header rows (so the first transaction row is row 5).
+    pub row: usize,
+    /// The column (from 0, for column A) of the field at fault, if it's a single field.
+    pub column: Option<usize>,
+    /// The field at fault, as it is in the file.
+    pub value: Option<String>,
+    /// What's wrong, as a sentence.
+    pub message: String,
+    /// The fields of the row, if it was read.
+    pub fields: Vec<String>,
+    /// The field a correction of the row (see `corrections`) would replace to fix it, if any.  Any row may instead be
+    /// skipped with a `CorrectedField::Skip`.
+    pub field: Option<CorrectedField>,
+}
+
+impl InputError {
+
+    /// A problem with the field in `column` of the `record`, on `row`.
+    pub fn in_field(row: usize, record: &csv::StringRecord, column: usize, message: impl Into<String>) -> InputError {
+        InputError {
+            row,
+            column: Some(column),
+            value: record.get(column).map(|field| field.to_string()),
+            message: message.into(),
+            fields: record.iter().map(|field| field.to_string()).collect(),
+            field: None,
+        }
+    }
+
+    /// A problem with the `record` on `row` as a whole (i.e., the number of its quantities).
+    pub fn in_row(row: usize, record: &csv::StringRecord, message: impl Into<String>) -> InputError {
+        InputError {
+            row,
+            column: None,
+            value: None,
+            message: message.into(),
+            fields: record.iter().map(|field| field.to_string()).collect(),
+            field: None,
+        }
+    }
+
+    /// A problem with a row that couldn't be read (i.e., a CSV error), or that's no longer at hand.
+    pub fn at_row(row: usize, message: impl Into<String>) -> InputError {
+        InputError { row, column: None, value: None, message: message.into(), fields: Vec::new(), field: None }
+    }
+
+    /// The row as a line of the file, with where the field at fault starts and ends in it (in characters), if any.
+    fn line(&self) -> (String, Option<(usize, usize)>) {
+
+        let mut line = String::new();
+        let mut span = None;
+
+        for (idx, field) in self.fields.iter().enumerate() {
+
+            if idx > 0 { line.push(',') }
+
+            let quoted = match field.contains([',', '"', '\n', '\r']) {
+                true => format!("\"{}\"", field.replace('"', "\"\"")),
+                false => field.clone(),
+            };
+            let start = line.chars().count();
+            line.push_str(&quoted.replace(['\n', '\r'], " "));
+
+            if self.column == Some(idx) { span = Some((start, line.chars().count())) }
+        }
+
+        (line, span)
+    }
+
+    /// The row, cut down to `SNIPPET_WIDTH` characters around the field at fault, if it's longer, with that field's
+    /// span.
+    fn snippet(&self) -> (String, Option<(usize, usize)>) {
+
+        let (line, span) = self.line();
+        let chars: Vec<char> = line.chars().collect();
+
+        if chars.len() <= SNIPPET_WIDTH { return (line, span) }
+
+        let (field_start, field_end) = span.unwrap_or((0, 0));
+        let start = field_start.saturating_sub(SNIPPET_WIDTH / 3).min(chars.len() - SNIPPET_WIDTH);
+        let end = (start + SNIPPET_WIDTH).max(field_end).min(chars.len());
+
+        let lead = if start > 0 { "..." } else { "" };
+        let trail = if end < chars.len() { "..." } else { "" };
+        let snippet = format!("{}{}{}", lead, chars[start..end].iter().collect::<String>(), trail);
+
+        (snippet, span.map(|(field_start, field_end)| (field_start - start + lead.len(), field_end - start + lead.len())))
+    }
+}
+
+impl fmt::Display for InputError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match self.column {
+            Some(column) => write!(f, "CSV Import: at row {}, column {}: {}", self.row, column_name(column), self.message)?,
+            None => write!(f, "CSV Import: at row {}: {}", self.row, self.message)?,
+        }
+
+        if self.fields.is_empty() { return Ok(()) }
+
+        let (snippet, span) = self.snippet();
+        let gutter = " ".repeat(self.row.to_string().len() + 2);
+
+        write!(f, "\n{}|\n {} | {}", gutter, self.row, snippet)?;
+
+        if let Some((start, end)) = span {
+            write!(f, "\n{}| {}{}", gutter, " ".repeat(start), "^".repeat((end - start).max(1)))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for InputError {}
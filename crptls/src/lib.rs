@@ -6,12 +6,65 @@
 #![allow(unused_assignments)]
 
 pub mod account;
+pub mod adjusted_cost_base;
+pub mod airdrops;
+pub mod anonymize;
+pub mod balance_history;
 pub mod transaction;
+pub mod checksums;
 pub mod core_functions;
+pub mod corrections;
 pub mod costing_method;
+pub mod csv_dialect;
 pub mod csv_import_accts_txns;
 pub mod create_lots_mvmts;
+pub mod decimal_utils;
+pub mod dedupe;
+pub mod derivatives;
+pub mod disposal_rounding;
+pub mod dust_conversions;
+pub mod dust_sweeps;
+pub mod encryption;
+pub mod entities;
+pub mod exclusions;
+pub mod fiat_costs;
+pub mod fx_translation;
+pub mod import_map;
+pub mod input_error;
+pub mod accounts_file;
+pub mod long_schema;
+pub mod needs_review;
+pub mod spill_files;
+pub mod importers;
+pub mod like_kind;
+pub mod liquidity_pools;
+pub mod lot_pooling;
+pub mod nfts;
+pub mod non_sale_disposals;
+pub mod price_lookup;
+pub mod rebases;
+pub mod reconciliation;
+pub mod report_filter;
+pub mod report_language;
+pub mod run_state;
+pub mod same_date_ordering;
+pub mod share_pooling;
+pub mod specific_id;
+pub mod spot_prices;
+pub mod stablecoins;
+pub mod summary;
+pub mod synthetic_acquisitions;
+pub mod tags;
+pub mod template;
+pub mod test_data;
+pub mod timestamps;
+pub mod token_aliases;
+pub mod transfer_fees;
+pub mod transfer_matching;
+pub mod unresolved;
+pub mod validation;
+pub mod wash_sales;
 
-mod decimal_utils;
 mod import_cost_proceeds_etc;
+#[cfg(test)]
 mod tests;
\ No newline at end of file
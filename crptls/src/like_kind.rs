@@ -0,0 +1,208 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind, TxType};
+
+
+/// The asset class of a ticker that isn't listed in a `LikeKindScope`'s `classes`.
+pub const DEFAULT_CLASS: &str = "crypto";
+
+/// Which exchanges of two non-home currencies get like-kind treatment.  By default, every one dated on or before the
+/// like-kind cut-off date.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LikeKindScope {
+    /// The date ranges (first and last dates, inclusive) in which an exchange is like-kind, instead of those through
+    /// the cut-off date.  Empty for the latter.
+    pub windows: Vec<(NaiveDate, NaiveDate)>,
+    /// The asset class (lowercase) of each ticker listed, i.e., `fiat` for `EUR`.  Any other is in `DEFAULT_CLASS`.
+    pub classes: HashMap<String, String>,
+    /// The pairs of asset classes (or of tickers) that may be exchanged like-kind, in either order.  Empty for any two.
+    pub pairs: Vec<(String, String)>,
+}
+
+impl LikeKindScope {
+
+    /// Whether an exchange dated `date` is like-kind: within one of the `windows`, or, without any, on or before the
+    /// `cutoff_date`.
+    pub fn covers_date(&self, cutoff_date: NaiveDate, date: NaiveDate) -> bool {
+        match self.windows.is_empty() {
+            true => date <= cutoff_date,
+            false => self.windows.iter().any(|(first, last)| *first <= date && date <= *last),
+        }
+    }
+
+    /// Whether an exchange of `given` for `received` (tickers) is of one of the `pairs`.
+    pub fn covers_pair(&self, given: &str, received: &str) -> bool {
+
+        if self.pairs.is_empty() { return true }
+
+        let is = |side: &str, ticker: &str| side == self.class_of(ticker) || side.eq_ignore_ascii_case(ticker);
+
+        self.pairs.iter().any(|(first, second)| (is(first, given) && is(second, received))
+            || (is(first, received) && is(second, given)))
+    }
+
+    /// The asset class of the `ticker`.
+    pub fn class_of(&self, ticker: &str) -> &str {
+        self.classes.get(&ticker.to_ascii_uppercase()).map_or(DEFAULT_CLASS, |class| class.as_str())
+    }
+
+    /// The last date of the last of the `windows` (if any).
+    pub fn last_date(&self) -> Option<NaiveDate> {
+        self.windows.iter().map(|(_first, last)| *last).max()
+    }
+}
+
+/// Parses the `LK_WINDOWS` setting, such as `2016-01-01..2017-12-31,2019-01-01..2019-06-30`, into date ranges.
+pub fn parse_windows(value: &str) -> Result<Vec<(NaiveDate, NaiveDate)>, Box<dyn Error>> {
+
+    let mut windows = Vec::new();
+
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+
+        let (first, last) = entry.split_once("..")
+            .ok_or_else(|| format!("Like-kind window `{}` must be a first date, then `..`, then a last date (i.e., 2016-01-01..2017-12-31).", entry))?;
+
+        let date = |date: &str| NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("Like-kind window `{}` has a date that isn't YYYY-MM-DD.", entry));
+        let (first, last) = (date(first)?, date(last)?);
+
+        if first > last {
+            return Err(format!("Like-kind window `{}` ends before it begins.", entry).into())
+        }
+
+        windows.push((first, last));
+    }
+
+    Ok(windows)
+}
+
+/// Parses the `LK_ASSET_CLASSES` setting, such as `fiat=EUR|GBP,stable=USDC|DAI`, into a map of each ticker to its
+/// asset class.
+pub fn parse_asset_classes(value: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+
+    let mut classes = HashMap::new();
+
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+
+        let (class, tickers) = entry.split_once('=')
+            .ok_or_else(|| format!("Asset class `{}` must be a class, then `=`, then its tickers (i.e., fiat=EUR|GBP).", entry))?;
+        let class = class.trim().to_ascii_lowercase();
+
+        for ticker in tickers.split('|').map(str::trim).filter(|ticker| !ticker.is_empty()) {
+            let ticker = ticker.to_ascii_uppercase();
+            if classes.insert(ticker.clone(), class.clone()).is_some_and(|prior| prior != class) {
+                return Err(format!("Ticker {} is given more than one asset class.", ticker).into())
+            }
+        }
+    }
+
+    Ok(classes)
+}
+
+/// Parses the `LK_PAIRS` setting, such as `crypto/crypto,BTC/fiat`, into pairs of asset classes (or tickers).
+pub fn parse_pairs(value: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(|entry| {
+        match entry.split_once('/') {
+            Some((first, second)) if !first.trim().is_empty() && !second.trim().is_empty() =>
+                Ok((first.trim().to_ascii_lowercase(), second.trim().to_ascii_lowercase())),
+            _ => Err(format!("Like-kind pair `{}` must be two asset classes (or tickers) separated by `/` (i.e., crypto/crypto).", entry).into()),
+        }
+    }).collect()
+}
+
+
+/// An exchange of one non-home currency for another given like-kind treatment (on or before the like-kind cut-off
+/// date, and within its `LikeKindScope`), whose gain or loss is deferred into the basis of what was received.
+#[derive(Clone, Debug)]
+pub struct LikeKindExchange {
+    pub tx_num: u32,
+    pub date: NaiveDate,
+    pub given_acct_key: u16,
+    /// The quantity given up (positive).
+    pub given_amount: Decimal,
+    pub received_acct_key: u16,
+    pub received_amount: Decimal,
+    /// The earliest basis date of the `Lot`s given up.
+    pub given_acquired: NaiveDate,
+    /// The value of what was received, being the `proceeds` of what was given up.
+    pub fair_market_value: Decimal,
+    /// The (like-kind) basis of what was given up (positive).
+    pub given_basis: Decimal,
+    /// The gain realized, but not recognized (negative for a loss).
+    pub deferred_gain: Decimal,
+    /// The basis of what was received (positive), which is that of what was given up, carried over.
+    pub carryover_basis: Decimal,
+}
+
+/// Every `LikeKindExchange`, in `Transaction` order: each `Standard` exchange between non-margin `Account`s of two
+/// non-home currencies, dated on or before the `cutoff_date`, within the `scope`.  Only meaningful when like-kind
+/// treatment was applied.
+pub fn like_kind_exchanges(
+    home_currency: &str,
+    cutoff_date: NaiveDate,
+    scope: &LikeKindScope,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<LikeKindExchange>, Box<dyn Error>> {
+
+    let mut exchanges: Vec<LikeKindExchange> = Vec::new();
+
+    for num in 1..=txns_map.len() as u32 {
+
+        let txn = txns_map.get(&num).unwrap();
+
+        if txn.kind != TxKind::Standard || !scope.covers_date(cutoff_date, txn.date) { continue }
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? != TxType::Exchange { continue }
+        if !txn.both_exch_ars_are_non_home_curr(ars, raw_acct_map, acct_map, home_currency)? { continue }
+
+        let given_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let received_ar = ars.get(&txn.action_record_idx_vec[1]).unwrap();
+        assert_eq!(given_ar.direction(), Polarity::Outgoing);
+
+        let is_margin = |ar: &ActionRecord| raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap().is_margin;
+        if is_margin(given_ar) || is_margin(received_ar) { continue }
+
+        let ticker = |ar: &ActionRecord| &raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap().ticker;
+        if !scope.covers_pair(ticker(given_ar), ticker(received_ar)) { continue }
+
+        let given_mvmts = given_ar.get_mvmts_in_ar_in_lot_date_order();
+
+        let given_acquired = given_mvmts.iter()
+            .map(|mvmt| mvmt.get_lot(acct_map, ars).date_for_basis_purposes)
+            .min()
+            .unwrap_or(txn.date);
+
+        let fair_market_value: Decimal = given_mvmts.iter().map(|mvmt| mvmt.proceeds.get()).sum();
+        let given_basis: Decimal = -given_mvmts.iter().map(|mvmt| mvmt.cost_basis_lk.get()).sum::<Decimal>();
+        let carryover_basis: Decimal = received_ar.get_mvmts_in_ar_in_lot_date_order().iter()
+            .map(|mvmt| mvmt.cost_basis_lk.get())
+            .sum();
+
+        exchanges.push(LikeKindExchange {
+            tx_num: num,
+            date: txn.date,
+            given_acct_key: given_ar.account_key,
+            given_amount: -given_ar.amount,
+            received_acct_key: received_ar.account_key,
+            received_amount: received_ar.amount,
+            given_acquired,
+            fair_market_value,
+            given_basis,
+            deferred_gain: fair_market_value - given_basis,
+            carryover_basis,
+        });
+    }
+
+    Ok(exchanges)
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use rust_decimal_macros::dec;
+
+use crate::decimal_utils::{round_d128_1e2, round_d128_1e8};
+use crate::transaction::{self, Transaction, ActionRecord, Polarity, TxKind};
+
+
+/// How a deposit into (or a withdrawal from) a liquidity pool is treated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolTreatment {
+    /// Each asset deposited is exchanged for its share of the LP tokens (and each LP token withdrawn for its share
+    /// of the assets), realizing a gain or loss at its share of the `proceeds`.
+    Swap,
+    /// The basis of the assets deposited carries over into the LP tokens received (and that of the LP tokens withdrawn
+    /// into the assets received), realizing no gain or loss.  The basis date is that of the deposit (or withdrawal).
+    Carryover,
+}
+
+/// Splits every liquidity pool deposit (a `Transaction` with two outgoing `ActionRecord`s for the assets deposited,
+/// and an incoming one for the LP tokens) and withdrawal (the reverse) into two exchanges, one for each asset, in its
+/// place.  Each exchange gets half of the LP tokens, `proceeds` and `fee`, as for a pool of equal values (i.e., with
+/// a constant product), and a `TxKind::PoolCarryover` if the basis carries over; otherwise, it is `Standard`.  Every
+/// later `Transaction` is renumbered.  Returns the number of `Transaction`s split.
+pub(crate) fn split_pool_transactions(
+    treatment: PoolTreatment,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> usize {
+
+    if txns_map.values().all(|txn| txn.action_record_idx_vec.len() <= 2) { return 0 }
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut split = 0;
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.remove(&(num as u32)).unwrap();
+
+        if txn.action_record_idx_vec.len() <= 2 {
+            ordered_txns.push(txn);
+            continue
+        }
+
+        //  The LP tokens (for a deposit) or the assets (for a withdrawal) are on the side with two `ActionRecord`s
+        let (single, pair): (Vec<u32>, Vec<u32>) = {
+            let (outgoing, incoming): (Vec<u32>, Vec<u32>) = txn.action_record_idx_vec.iter()
+                .partition(|ar_num| ar_map.get(ar_num).unwrap().direction() == Polarity::Outgoing);
+            if outgoing.len() == 1 { (outgoing, incoming) } else { (incoming, outgoing) }
+        };
+
+        let single_ar = ar_map.get(&single[0]).unwrap();
+        let first_half = round_d128_1e8(&(single_ar.amount / dec!(2)));
+        let second_half = single_ar.amount - first_half;
+        let account_key = single_ar.account_key;
+
+        let second_ar_num = ar_map.len() as u32 + 1;
+        ar_map.get_mut(&single[0]).unwrap().amount = first_half;
+
+        ar_map.insert(second_ar_num, ActionRecord {
+            account_key,
+            amount: second_half,
+            tx_key: 0,  //  Set when renumbering, below
+            self_ar_key: second_ar_num,
+            movements: RefCell::new([].to_vec()),
+        });
+
+        let proceeds = round_d128_1e2(&(txn.proceeds / dec!(2)));
+        let fee = round_d128_1e2(&(txn.fee / dec!(2)));
+        let kind = if treatment == PoolTreatment::Carryover { TxKind::PoolCarryover } else { TxKind::Standard };
+
+        for (leg, (single_ar_num, pair_ar_num)) in [(single[0], pair[0]), (second_ar_num, pair[1])].into_iter().enumerate() {
+
+            //  The outgoing `ActionRecord` comes first, as in an imported exchange
+            let action_record_idx_vec = if ar_map.get(&single_ar_num).unwrap().direction() == Polarity::Outgoing {
+                vec![single_ar_num, pair_ar_num]
+            } else {
+                vec![pair_ar_num, single_ar_num]
+            };
+
+            ordered_txns.push(Transaction {
+                tx_number: 0,   //  Set when renumbering, below
+                user_memo: format!("{} (leg {} of 2)", txn.user_memo, leg + 1),
+                proceeds: if leg == 0 { proceeds } else { txn.proceeds - proceeds },
+                fee: if leg == 0 { fee } else { txn.fee - fee },
+                action_record_idx_vec,
+                kind,
+                ..txn.clone()
+            });
+        }
+
+        split += 1;
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    split
+}
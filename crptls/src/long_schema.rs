@@ -0,0 +1,124 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{Read, Write};
+
+use crate::accounts_file::{self, AccountsFile, Headers, Leg, RowTxn};
+
+
+const DESCRIPTION: &str = "the file in the long schema";
+
+/// Converts a CSV in the long schema, with a single header row and one row per leg of a transaction, into the
+/// layout of a CSV Input File.  Its columns (in any order, and identified by their header) are `date` (or `txDate`),
+/// `account` (an account's number or name), `asset` (its ticker, or `ticker`), `amount` (or `quantity`), and
+/// optionally `proceeds`, `txid`, `memo` and any of the optional columns of a CSV Input File (i.e., `txType`).  The
+/// rows with the same `txid` are the legs of one transaction (in the order its first leg appears), and a row without
+/// a `txid` is a transaction of its own.  A transaction's `proceeds` and optional columns are those given on any of
+/// its legs (which mustn't differ), and its memo is each different memo of its legs, in order.  Accounts are found or
+/// created as in `accounts_file::write_input_file()`, which writes the CSV Input File to `output`.
+pub fn long_to_input_file<W: Write + 'static>(
+    input: impl Read,
+    accounts_file: Option<&AccountsFile>,
+    output: W,
+) -> Result<W, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new().has_headers(true).flexible(true).from_reader(input);
+    let headers = Headers::new(rdr.headers()?, DESCRIPTION);
+
+    let either = |name: &str, alias: &str| -> Result<Option<usize>, Box<dyn Error>> {
+        match (headers.column(name)?, headers.column(alias)?) {
+            (Some(_), Some(_)) => Err(format!("The file in the long schema has both a {} and a {} column.", name, alias).into()),
+            (col, alias_col) => Ok(col.or(alias_col)),
+        }
+    };
+    let require = |col: Option<usize>, name: &str| col.ok_or_else(|| format!("The file in the long schema has no {} column.", name));
+
+    let date_col = require(either("date", "txDate")?, "date")?;
+    let account_col = require(headers.column("account")?, "account")?;
+    let amount_col = require(either("amount", "quantity")?, "amount")?;
+    let asset_col = either("asset", "ticker")?;
+    let proceeds_col = headers.column("proceeds")?;
+    let txid_col = headers.column("txid")?;
+    let memo_col = headers.column("memo")?;
+    let optional_cols = headers.optional_columns()?;
+
+    let mut txns: Vec<RowTxn> = Vec::new();
+    let mut txns_by_txid: HashMap<String, usize> = HashMap::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;
+        let field = |col: Option<usize>| col.and_then(|col| record.get(col)).unwrap_or("").trim().to_string();
+
+        if record.iter().all(|field| field.trim().is_empty()) { continue }
+
+        let txid = field(txid_col);
+        let amount = field(Some(amount_col));
+
+        let leg = match amount.is_empty() {
+            true => None,
+            false => Some(Leg {
+                account: field(Some(account_col)),
+                ticker: field(asset_col),
+                quantity: accounts_file::parse_quantity(&amount).map_err(|e| format!("Row {} of {}: {}.", row, DESCRIPTION, e))?,
+            }),
+        };
+
+        let existing = match txid.is_empty() {
+            true => None,
+            false => txns_by_txid.get(&txid).copied(),
+        };
+
+        let Some(txn_idx) = existing else {
+            if !txid.is_empty() { txns_by_txid.insert(txid, txns.len()); }
+            txns.push(RowTxn {
+                row,
+                date: field(Some(date_col)),
+                proceeds: field(proceeds_col),
+                memo: field(memo_col),
+                optional: optional_cols.iter().map(|(_name, col)| field(Some(*col))).collect(),
+                legs: leg.into_iter().collect(),
+            });
+            continue
+        };
+
+        let txn = &mut txns[txn_idx];
+        let first_row = txn.row;
+        let conflict = |name: &str, first: &str, value: &str| format!("Row {} of {}: the {} ({}) of transaction {} differs \
+            from that of its leg in row {} ({}).", row, DESCRIPTION, name, value, txid, first_row, first);
+
+        let date = field(Some(date_col));
+        if date != txn.date { return Err(conflict("date", &txn.date, &date).into()) }
+
+        let proceeds = field(proceeds_col);
+        match (txn.proceeds.is_empty(), proceeds.is_empty()) {
+            (true, _) => txn.proceeds = proceeds,
+            (false, false) if proceeds != txn.proceeds => return Err(conflict("proceeds", &txn.proceeds, &proceeds).into()),
+            _ => {}
+        }
+
+        for ((name, col), value) in optional_cols.iter().zip(txn.optional.iter_mut()) {
+            let this_value = field(Some(*col));
+            match (value.is_empty(), this_value.is_empty()) {
+                (true, _) => *value = this_value,
+                (false, false) if this_value != *value => return Err(conflict(name, value, &this_value).into()),
+                _ => {}
+            }
+        }
+
+        let memo = field(memo_col);
+        if !memo.is_empty() && !txn.memo.split("; ").any(|written| written == memo) {
+            txn.memo = match txn.memo.is_empty() {
+                true => memo,
+                false => format!("{}; {}", txn.memo, memo),
+            };
+        }
+
+        txn.legs.extend(leg);
+    }
+
+    accounts_file::write_input_file(&txns, &optional_cols, accounts_file, DESCRIPTION, output)
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Term};
+use crate::costing_method::InventoryCostingMethod;
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crate::share_pooling;
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// Where a disposal's cost basis is drawn from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LotPooling {
+    /// Each disposal is drawn only from the `Lot`s held in the `Account` it came out of, and a transfer between the
+    /// user's own `Account`s carries its `Lot`s (and their basis) along with it.
+    PerWallet,
+    /// Each disposal is drawn from the `Lot`s of its currency held in any (non-margin) `Account`, in the order of the
+    /// costing method, as though every `Account` of the currency were a single one.
+    Universal,
+}
+
+/// A disposal (an outgoing `ActionRecord`) costed from the universal pool of its currency.
+#[derive(Clone, Debug)]
+pub struct UniversalDisposal {
+    pub tx_num: u32,
+    pub account_key: u16,
+    /// Positive.
+    pub amount: Decimal,
+    /// The cost basis drawn from the universal pool (positive).
+    pub cost_basis: Decimal,
+    /// The cost basis it was drawn at from the `Lot`s of its own `Account` (positive).
+    pub wallet_cost_basis: Decimal,
+}
+
+/// The remainder of an acquisition of a currency, in the universal pool.
+struct PoolLot {
+    /// The order the acquisitions were made.
+    created: usize,
+    date_for_basis_purposes: NaiveDate,
+    inherited: bool,
+    units: Decimal,
+    /// Positive.
+    cost: Decimal,
+}
+
+/// Replaces the cost basis of every disposal with its cost drawn from the universal pool of its currency, which holds
+/// what remains of every acquisition of it in any non-margin `Account`.  The cost basis of all `Movement`s must already
+/// have been added.  Acquisitions are drawn from in the order that `costing_method` draws from an `Account`'s `Lot`s
+/// (or, for average cost, in proportion to what remains of each).  Transfers between the user's own `Account`s are
+/// ignored.  Only disposals' cost basis changes; each `Lot` keeps its own, and each disposal's term is still that of
+/// the `Lot`s it was drawn from in its own `Account`.
+pub(crate) fn apply_universal_pooling(
+    costing_method: &InventoryCostingMethod,
+    long_term_days: i64,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<UniversalDisposal>, Box<dyn Error>> {
+
+    let mut currencies: HashMap<String, Vec<PoolLot>> = HashMap::new();
+    let mut universal_disposals: Vec<UniversalDisposal> = Vec::new();
+    let mut created = 0;
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            let pool = currencies.entry(raw_acct.ticker.clone()).or_default();
+
+            if ar.direction() == Polarity::Incoming {
+
+                //  Each `Movement` is kept apart, since a carried-over basis date may differ between them
+                for mvmt in ar.movements.borrow().iter() {
+                    created += 1;
+                    let lot = mvmt.get_lot(acct_map, ars);
+                    pool.push(PoolLot {
+                        created,
+                        date_for_basis_purposes: lot.date_for_basis_purposes,
+                        inherited: lot.inherited,
+                        units: mvmt.amount,
+                        cost: mvmt.cost_basis.get().abs(),
+                    });
+                }
+                continue
+            }
+
+            let amount = -ar.amount;
+            let held: Decimal = pool.iter().map(|lot| lot.units).sum();
+
+            if amount > held {
+                return Err(format!("On {}, {} {} was disposed of, but only {} was held across every account.",
+                    txn.date, amount, raw_acct.ticker, held).into())
+            }
+
+            let cost = match costing_method.spreads_across_lots() {
+                true => draw_in_proportion(pool, amount, held),
+                false => {
+                    let order = pool_order(costing_method, pool, txn.date, long_term_days);
+                    draw_in_order(pool, amount, order)
+                }
+            };
+            let cost_basis = round_d128_1e2(&cost);
+
+            pool.retain(|lot| lot.units > dec!(0));
+
+            universal_disposals.push(UniversalDisposal {
+                tx_num: txn.tx_number,
+                account_key: ar.account_key,
+                amount,
+                cost_basis,
+                wallet_cost_basis: ar.cost_basis_in_ar(),
+            });
+
+            share_pooling::set_cost_basis_of_disposal(ar, -cost_basis, acct_map, txns_map);
+        }
+    }
+
+    Ok(universal_disposals)
+}
+
+/// The indexes of the `pool`'s acquisitions in the order `costing_method` would draw from them for a disposal on
+/// `disposal_date`, as for the `Lot`s of an `Account`.
+fn pool_order(
+    costing_method: &InventoryCostingMethod,
+    pool: &[PoolLot],
+    disposal_date: NaiveDate,
+    long_term_days: i64,
+) -> Vec<usize> {
+
+    let mut indexes: Vec<usize> = (0..pool.len()).collect();
+
+    match costing_method {
+        InventoryCostingMethod::LIFObyLotCreationDate => {
+            indexes.sort_by_key(|idx| std::cmp::Reverse(pool[*idx].created))}
+        InventoryCostingMethod::LIFObyLotBasisDate => {
+            indexes.sort_by_key(|idx| std::cmp::Reverse((pool[*idx].date_for_basis_purposes, pool[*idx].created)))}
+        InventoryCostingMethod::FIFObyLotBasisDate => {
+            indexes.sort_by_key(|idx| (pool[*idx].date_for_basis_purposes, pool[*idx].created))}
+        InventoryCostingMethod::LTfirstFIFObyLotBasisDate
+        | InventoryCostingMethod::LTfirstLIFObyLotBasisDate => {
+            indexes.sort_by_key(|idx| (pool[*idx].date_for_basis_purposes, pool[*idx].created));
+            if *costing_method == InventoryCostingMethod::LTfirstLIFObyLotBasisDate { indexes.reverse() }
+            let (mut long_term, short_term): (Vec<usize>, Vec<usize>) = indexes.into_iter().partition(|idx| {
+                pool[*idx].inherited
+                    || Term::from_dates(pool[*idx].date_for_basis_purposes, disposal_date, long_term_days) == Term::LT
+            });
+            long_term.extend(short_term);
+            indexes = long_term;
+        }
+        InventoryCostingMethod::HIFObyLotUnitBasis => {
+            indexes.sort_by_key(|idx| (std::cmp::Reverse(pool[*idx].cost / pool[*idx].units), pool[*idx].created))}
+        _ => {
+            indexes.sort_by_key(|idx| pool[*idx].created)}
+    }
+
+    indexes
+}
+
+/// Draws `amount` from the acquisitions of the `pool` in the `order` given, returning the cost drawn (positive).
+fn draw_in_order(pool: &mut [PoolLot], amount: Decimal, order: Vec<usize>) -> Decimal {
+
+    let mut remaining = amount;
+    let mut cost = dec!(0);
+
+    for idx in order {
+
+        if remaining <= dec!(0) { break }
+
+        let lot = &mut pool[idx];
+        let units = remaining.min(lot.units);
+        let lot_cost = if units == lot.units { lot.cost } else { lot.cost * units / lot.units };
+
+        lot.units -= units;
+        lot.cost -= lot_cost;
+        remaining -= units;
+        cost += lot_cost;
+    }
+
+    cost
+}
+
+/// Draws `amount` from every acquisition of the `pool` in proportion to what remains of it (`held`, in all),
+/// returning the cost drawn (positive).
+fn draw_in_proportion(pool: &mut [PoolLot], amount: Decimal, held: Decimal) -> Decimal {
+
+    let mut cost = dec!(0);
+
+    for lot in pool.iter_mut() {
+
+        let (units, lot_cost) = match amount == held {
+            true => (lot.units, lot.cost),
+            false => (lot.units * amount / held, lot.cost * amount / held),
+        };
+
+        lot.units -= units;
+        lot.cost -= lot_cost;
+        cost += lot_cost;
+    }
+
+    cost
+}
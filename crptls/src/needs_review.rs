@@ -0,0 +1,119 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+
+use crate::account::{Account, RawAccount};
+use crate::core_functions::{self, ImportProcessParameters, Timings};
+use crate::costing_method::InventoryCostingMethod;
+use crate::csv_import_accts_txns;
+use crate::unresolved::{self, Unresolved};
+use crate::validation;
+
+
+/// A transaction row that couldn't be fully classified, so is parked for review rather than stopping the run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewRow {
+    /// The row of the CSV Input File (counting the four header rows), as merged, if there are several.
+    pub row: usize,
+    pub date: String,
+    pub memo: String,
+    /// Each account with a quantity, with the quantity (i.e., `2: -0.5`).
+    pub quantities: String,
+    /// Every problem found with it.
+    pub reason: String,
+}
+
+/// The transaction rows of the CSV Input File(s) (merged, if there are several) that can't be fully classified: those
+/// that wouldn't import (every problem `validation::validate_input_file()` finds, such as two quantities of the same
+/// sign, so no direction), and, with transfer matching, the withdrawals and deposits without proceeds that no other
+/// side was matched with.  Set as the `parked_rows`, they're left out of processing, so that the rest of the run
+/// may complete.  Finding the unmatched transfers processes the other rows once more.
+pub fn find_rows_needing_review(
+    import_file_paths: &[PathBuf],
+    settings: &ImportProcessParameters,
+) -> Result<Vec<ReviewRow>, Box<dyn Error>> {
+
+    let mut inputs = csv_import_accts_txns::open_input_files(import_file_paths, settings)?;
+
+    let mut input: Box<dyn Read> = if inputs.len() == 1 {
+        inputs.pop().unwrap().1
+    } else {
+        csv_import_accts_txns::merge_input_files(inputs, settings)?
+    };
+
+    //  Read once, as converted and merged, for each pass
+    let mut csv = Vec::new();
+    input.read_to_end(&mut csv)?;
+
+    let mut reasons: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+
+    //  A problem with the header rows can't be parked, so it's left for the import to report
+    for issue in validation::validate_input(Box::new(Cursor::new(csv.clone())), settings)?.into_iter().filter(|issue| issue.row >= 5) {
+        reasons.entry(issue.row).or_default().push(issue.message);
+    }
+
+    if settings.transfer_match_tolerance.is_some() {
+
+        let mut pass_settings = settings.clone();
+        pass_settings.parked_rows = reasons.keys().copied().collect();
+        //  Which lots are disposed of doesn't matter here, so none are picked
+        if pass_settings.costing_method == InventoryCostingMethod::SpecificIdentification {
+            pass_settings.costing_method = InventoryCostingMethod::FIFObyLotCreationDate;
+        }
+
+        let (raw_acct_map, acct_map, ars, txns_map) = core_functions::import_and_process(
+            vec![("the CSV Input File".to_string(), Box::new(Cursor::new(csv.clone())))],
+            &pass_settings,
+            None,
+            &mut Timings::default(),
+        )?;
+
+        for unresolved_row in unresolved::find_unresolved_rows(&pass_settings, &raw_acct_map, &acct_map, &ars, &txns_map)? {
+            let reason = match unresolved_row.reason {
+                Unresolved::UnmatchedWithdrawal => "It's a withdrawal without proceeds, and no deposit was matched with it.",
+                Unresolved::UnmatchedDeposit => "It's a deposit without proceeds, and no withdrawal was matched with it.",
+                _ => continue,
+            };
+            reasons.entry(unresolved_row.row).or_default().push(reason.to_string());
+        }
+    }
+
+    if reasons.is_empty() { return Ok(Vec::new()) }
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(Cursor::new(csv));
+
+    let mut raw_acct_map: HashMap<u16, RawAccount> = HashMap::new();
+    let mut acct_map: HashMap<u16, Account> = HashMap::new();
+
+    let columns = csv_import_accts_txns::import_accounts(&mut rdr, settings, &mut raw_acct_map, &mut acct_map)?;
+
+    let mut review_rows = Vec::with_capacity(reasons.len());
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let Some(row_reasons) = reasons.get(&(idx + 5)) else { continue };
+        let record = result?;
+
+        let quantities: Vec<String> = record.iter().enumerate()
+            .skip(columns.first_acct_col)
+            .filter(|(_idx, value)| !value.trim().is_empty())
+            .map(|(idx, value)| format!("{}: {}", idx - columns.first_acct_col + 1, value.trim()))
+            .collect();
+
+        review_rows.push(ReviewRow {
+            row: idx + 5,
+            date: record.get(0).unwrap_or("").trim().to_string(),
+            memo: record.get(2).unwrap_or("").trim().to_string(),
+            quantities: quantities.join(", "),
+            reason: row_reasons.join(" "),
+        });
+    }
+
+    Ok(review_rows)
+}
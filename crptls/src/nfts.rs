@@ -0,0 +1,180 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount, Term};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+
+
+/// The collection and token ID of an NFT `Account`, whose ticker is the collection's ticker, a `#`, and the token ID
+/// (i.e., `BAYC#1234`), or `None` for a fungible one.  An NFT's quantity is always 1, so each acquisition of it is a
+/// `Lot` of its own.
+pub fn collection_and_token_id(ticker: &str) -> Option<(&str, &str)> {
+    match ticker.split_once('#') {
+        Some((collection, token_id)) if !collection.trim().is_empty() && !token_id.trim().is_empty() => {
+            Some((collection.trim(), token_id.trim()))
+        }
+        _ => None,
+    }
+}
+
+/// One acquisition of an NFT (a `Lot` of an NFT `Account`), and its sale, if it's been sold.
+#[derive(Clone, Debug)]
+pub struct NftItem {
+    pub account_key: u16,
+    pub collection: String,
+    pub token_id: String,
+    /// The `Transaction` that acquired it, before any transfers between the user's own `Account`s.
+    pub acquired_tx_num: u32,
+    /// The basis date, which a transfer between the user's own `Account`s carries over.
+    pub acquired: NaiveDate,
+    pub cost_basis: Decimal,
+    pub sale: Option<NftSale>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NftSale {
+    pub tx_num: u32,
+    pub date: NaiveDate,
+    pub proceeds: Decimal,
+    pub gain_or_loss: Decimal,
+    pub term: Term,
+}
+
+/// Every `NftItem`, by `Account` and then `Lot`, using like-kind values (which are the same as the original values
+/// unless like-kind treatment was applied).  An item transferred to another of the user's own `Account`s is listed
+/// once, in the `Account` it was last transferred to.
+pub fn nft_items(
+    long_term_days: i64,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<NftItem>, Box<dyn Error>> {
+
+    let mut items: Vec<NftItem> = Vec::new();
+
+    for acct_key in 1..=acct_map.len() as u16 {
+
+        let acct = acct_map.get(&acct_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        let (collection, token_id) = match collection_and_token_id(&raw_acct.ticker) {
+            Some(ids) if !raw_acct.is_margin => ids,
+            _ => continue,
+        };
+
+        for lot in acct.list_of_lots.borrow().iter() {
+
+            let mvmts = lot.movements.borrow();
+            let acquisition = match mvmts.iter().find(|mvmt| ars.get(&mvmt.action_record_key).unwrap().direction() == Polarity::Incoming) {
+                Some(mvmt) => mvmt,
+                None => continue,
+            };
+
+            let mut sale = None;
+
+            if let Some(disposal) = mvmts.iter().find(|mvmt| ars.get(&mvmt.action_record_key).unwrap().direction() == Polarity::Outgoing) {
+
+                let txn = txns_map.get(&disposal.transaction_key).unwrap();
+
+                //  Transferred to another of the user's own `Account`s, where it's listed instead
+                if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+                sale = Some(NftSale {
+                    tx_num: disposal.transaction_key,
+                    date: disposal.date,
+                    proceeds: disposal.proceeds_lk.get(),
+                    gain_or_loss: disposal.get_lk_gain_or_loss(),
+                    term: lot.term(disposal.date, long_term_days),
+                });
+            }
+
+            items.push(NftItem {
+                account_key: acct_key,
+                collection: collection.to_string(),
+                token_id: token_id.to_string(),
+                acquired_tx_num: original_acquisition(acquisition.transaction_key, raw_acct_map, acct_map, ars, txns_map)?,
+                acquired: lot.date_for_basis_purposes,
+                cost_basis: acquisition.cost_basis_lk.get(),
+                sale,
+            });
+        }
+    }
+
+    Ok(items)
+}
+
+/// The `Transaction` that first acquired an NFT received in `tx_num`, following it back through any transfers between
+/// the user's own `Account`s.
+fn original_acquisition(
+    tx_num: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<u32, Box<dyn Error>> {
+
+    let mut tx_num = tx_num;
+
+    loop {
+        let txn = txns_map.get(&tx_num).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? != TxType::ToSelf { return Ok(tx_num) }
+
+        let sent = ars.get(&txn.action_record_idx_vec[0]).unwrap().get_mvmts_in_ar_in_lot_date_order();
+        let lot = sent[0].get_lot(acct_map, ars);
+        let received = lot.movements.borrow().iter()
+            .find(|mvmt| ars.get(&mvmt.action_record_key).unwrap().direction() == Polarity::Incoming)
+            .map(|mvmt| mvmt.transaction_key);
+
+        match received {
+            Some(num) => tx_num = num,
+            None => return Ok(tx_num),
+        }
+    }
+}
+
+/// Checks that every quantity of an NFT is 1 (or -1), and that no NFT `Account` ever holds more than one.
+pub(crate) fn check_nft_quantities(
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut balances: HashMap<u16, Decimal> = HashMap::new();
+
+    for num in 1..=txns_map.len() as u32 {
+
+        let txn = txns_map.get(&num).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || collection_and_token_id(&raw_acct.ticker).is_none() { continue }
+
+            if ar.amount.abs() != Decimal::ONE {
+                return Err(format!("Transaction {} has a quantity of {} of the NFT {}, but an NFT's quantity is always \
+                    1 (or -1).", num, ar.amount, raw_acct.ticker).into())
+            }
+
+            let balance = balances.entry(ar.account_key).or_default();
+            *balance += ar.amount;
+
+            if *balance > Decimal::ONE {
+                return Err(format!("Transaction {} acquires the NFT {} in account {} ({}), which already holds it.",
+                    num, raw_acct.ticker, ar.account_key, raw_acct.name).into())
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, Term};
+use crate::transaction::{Transaction, ActionRecord, TxKind};
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// The part of a gift, donation or loss (the `Movement` of an outgoing `TxKind::Gift`, `TxKind::Donation` or
+/// `TxKind::Lost` `Transaction`) that came out of a single `Lot`.
+#[derive(Clone, Debug)]
+pub struct NonSaleDisposal {
+    pub tx_num: u32,
+    pub kind: TxKind,
+    pub date: NaiveDate,
+    pub account_key: u16,
+    pub lot_num: u32,
+    /// Negative.
+    pub amount: Decimal,
+    /// The `Lot`'s basis date, which carries over to the recipient of a gift.
+    pub basis_date: NaiveDate,
+    pub term: Term,
+    /// Positive.  This carries over to the recipient of a gift.
+    pub cost_basis: Decimal,
+    /// The `Movement`'s share of the `Transaction`'s `proceeds`.
+    pub fair_market_value: Decimal,
+    /// The charitable deduction for a donation: the fair market value if long-term, or the lesser of the fair market
+    /// value and the cost basis if short-term.  Zero for a gift or loss.
+    pub deduction: Decimal,
+    /// Zero for a gift or donation, which realize no gain, and the (negative) cost basis for a loss.
+    pub gain_or_loss: Decimal,
+}
+
+/// Whether a `Transaction` of `kind` is an outflow that isn't a sale: a gift or donation, which realizes no gain or
+/// loss, or a lost or stolen asset, which is written off without proceeds.
+pub fn is_non_sale(kind: TxKind) -> bool {
+    matches!(kind, TxKind::Gift | TxKind::Donation | TxKind::Lost)
+}
+
+/// Replaces the proceeds of every gift, donation and loss.  A gift's or donation's `Movement`s net their proceeds
+/// against their cost basis, so no gain or loss is realized (and the basis of what was given is the expense), while a
+/// loss's `Movement`s have none, so its cost basis is the loss.  This must follow any like-kind treatment, which may
+/// change the `cost_basis_lk`.
+pub(crate) fn apply_non_sale_treatments(
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) {
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if !is_non_sale(txn.kind) { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+            for mvmt in ars.get(ar_num).unwrap().get_mvmts_in_ar_in_lot_date_order().iter() {
+                if txn.kind == TxKind::Lost {
+                    mvmt.proceeds.set(dec!(0));
+                    mvmt.proceeds_lk.set(dec!(0));
+                } else {
+                    mvmt.proceeds.set(-mvmt.cost_basis.get());
+                    mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+                }
+            }
+        }
+    }
+}
+
+/// Lists every gift, donation and loss, by `Lot`, in `Transaction` order.  Values are like-kind values.
+pub fn non_sale_disposals(
+    long_term_days: i64,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Vec<NonSaleDisposal> {
+
+    let mut disposals: Vec<NonSaleDisposal> = Vec::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if !is_non_sale(txn.kind) { continue }
+
+        let txn_proceeds = txn.proceeds;
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+                let term = mvmt.get_term(acct_map, ars, txns_map, long_term_days);
+                let cost_basis = -mvmt.cost_basis_lk.get();
+                let fair_market_value = round_d128_1e2(&(txn_proceeds * mvmt.amount / ar.amount));
+
+                let deduction = match (txn.kind, &term) {
+                    (TxKind::Donation, Term::LT) => fair_market_value,
+                    (TxKind::Donation, Term::ST) => fair_market_value.min(cost_basis),
+                    _ => dec!(0),
+                };
+
+                disposals.push(NonSaleDisposal {
+                    tx_num: txn.tx_number,
+                    kind: txn.kind,
+                    date: txn.date,
+                    account_key: ar.account_key,
+                    lot_num: mvmt.lot_num,
+                    amount: mvmt.amount,
+                    basis_date: mvmt.get_lot(acct_map, ars).date_for_basis_purposes,
+                    term,
+                    cost_basis,
+                    fair_market_value,
+                    deduction,
+                    gain_or_loss: mvmt.get_lk_gain_or_loss(),
+                });
+            }
+        }
+    }
+
+    disposals
+}
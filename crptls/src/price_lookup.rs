@@ -0,0 +1,211 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, TxType};
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// Where the historical prices come from that fill in a `Transaction`'s missing `proceeds`.
+#[derive(Clone, Debug)]
+pub struct PriceLookup {
+    /// A CSV with a header row, then one `date,ticker,price` row per price, dated YYYY-MM-DD and priced in the home
+    /// currency (i.e., `2023-01-31,BTC,23125.00`).  Unless `offline`, prices fetched are appended to it, so it is
+    /// also the cache, and it is created if it doesn't exist.
+    pub path: PathBuf,
+    /// When `true`, prices are only ever read from the file, never fetched.
+    pub offline: bool,
+}
+
+/// A `Transaction` whose missing `proceeds` were filled in.
+#[derive(Clone, Debug)]
+pub struct FilledProceeds {
+    pub tx_num: u32,
+    /// The currency valued, which is the home currency if the `Transaction` has a home currency leg.
+    pub ticker: String,
+    pub price: Decimal,
+    pub proceeds: Decimal,
+}
+
+/// What `fill_missing_proceeds()` did.
+#[derive(Clone, Debug, Default)]
+pub struct PriceFill {
+    pub filled: Vec<FilledProceeds>,
+    /// The `Transaction`s (with the ticker and date) for which no price could be found, which keep zero `proceeds`.
+    pub unpriced: Vec<(u32, String, NaiveDate)>,
+    /// The number of prices fetched (and cached).
+    pub fetched: usize,
+}
+
+/// Fills in the `proceeds` of every `Transaction` with none (other than transfers between the user's own
+/// `Account`s, carried-forward lots and margin trades).  A `Transaction` with a home currency leg is valued at the
+/// home currency amount.  Otherwise, the amount of its outgoing (or only) currency is valued at that currency's price
+/// on the `Transaction`'s date (or, for an exchange, the incoming currency's, if the outgoing currency's is missing).
+/// Prices come from the `lookup` file and, unless offline, are fetched from CoinGecko (with `curl`) if not there.
+pub(crate) fn fill_missing_proceeds(
+    lookup: &PriceLookup,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<PriceFill, Box<dyn Error>> {
+
+    let mut prices = if lookup.offline || lookup.path.exists() { import_prices(&lookup.path)? } else { HashMap::new() };
+    let mut fetched: Vec<(NaiveDate, String, Decimal)> = Vec::new();
+    let mut fill = PriceFill::default();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        if !txn.proceeds.is_zero() || txn.kind.is_carried_forward() { continue }
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+        let legs: Vec<(&RawAccount, Decimal)> = txn.action_record_idx_vec.iter().map(|ar_num| {
+            let ar = ars.get(ar_num).unwrap();
+            (raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap(), ar.amount)
+        }).collect();
+
+        if legs.iter().any(|(raw_acct, _amount)| raw_acct.is_margin) { continue }
+
+        let valued = match legs.iter().find(|(raw_acct, _amount)| raw_acct.is_home_currency(home_currency)) {
+            Some((raw_acct, amount)) => Some((raw_acct.ticker.clone(), dec!(1), amount.abs())),
+            None => legs.iter().find_map(|(raw_acct, amount)| {
+                let price = match prices.get(&(txn.date, raw_acct.ticker.clone())) {
+                    Some(price) => Some(*price),
+                    None if !lookup.offline => {
+                        let price = fetch_price(&raw_acct.ticker, txn.date, home_currency);
+                        if let Some(price) = price {
+                            prices.insert((txn.date, raw_acct.ticker.clone()), price);
+                            fetched.push((txn.date, raw_acct.ticker.clone(), price));
+                        }
+                        price
+                    }
+                    None => None,
+                };
+                price.map(|price| (raw_acct.ticker.clone(), price, amount.abs()))
+            }),
+        };
+
+        match valued {
+            Some((ticker, price, amount)) => {
+                let proceeds = round_d128_1e2(&(price * amount));
+                txns_map.get_mut(&(num as u32)).unwrap().proceeds = proceeds;
+                fill.filled.push(FilledProceeds { tx_num: num as u32, ticker, price, proceeds });
+            }
+            None => fill.unpriced.push((num as u32, legs[0].0.ticker.clone(), txn.date)),
+        }
+    }
+
+    if !fetched.is_empty() {
+
+        let is_new = !lookup.path.exists();
+        let mut file = OpenOptions::new().append(true).create(true).open(&lookup.path)?;
+
+        if is_new { writeln!(file, "date,ticker,price")? }
+
+        for (date, ticker, price) in fetched.iter() {
+            writeln!(file, "{},{},{}", date, ticker, price)?;
+        }
+
+        fill.fetched = fetched.len();
+    }
+
+    Ok(fill)
+}
+
+/// Reads a price file (see `PriceLookup::path`) into a map of (date, ticker) to home currency price.
+pub fn import_prices(path: &Path) -> Result<HashMap<(NaiveDate, String), Decimal>, Box<dyn Error>> {
+
+    let file = File::open(path)?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut prices: HashMap<(NaiveDate, String), Decimal> = HashMap::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;  //  Account for the header and one-based row numbers
+
+        let date = match record.get(0).map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d")) {
+            Some(Ok(date)) => date,
+            _ => return Err(format!("Price file row {} has an invalid date (it must be YYYY-MM-DD).", row).into()),
+        };
+
+        let ticker = match record.get(1) {
+            Some(t) if !t.is_empty() => t.to_string(),
+            _ => return Err(format!("Price file row {} is missing a ticker.", row).into()),
+        };
+
+        let price = match record.get(2).map(|p| p.parse::<Decimal>()) {
+            Some(Ok(p)) if p >= dec!(0) => p,
+            _ => return Err(format!("Price file row {} has an invalid price for {}.", row, ticker).into()),
+        };
+
+        prices.insert((date, ticker), price);
+    }
+
+    Ok(prices)
+}
+
+/// Fetches the price of `ticker` in the home currency on `date` from CoinGecko's daily history, or `None` if it
+/// can't be had (i.e., without a network connection, or for a ticker CoinGecko doesn't know by that id).
+fn fetch_price(ticker: &str, date: NaiveDate, home_currency: &str) -> Option<Decimal> {
+
+    let url = format!("https://api.coingecko.com/api/v3/coins/{}/history?date={}&localization=false",
+        coingecko_id(ticker), date.format("%d-%m-%Y"));
+
+    let output = Command::new("curl").args(["-sf", "--max-time", "20", &url]).output().ok()?;
+
+    if !output.status.success() { return None }
+
+    price_from_history_json(&String::from_utf8_lossy(&output.stdout), home_currency)
+}
+
+/// The `market_data.current_price` of `home_currency` in a CoinGecko history response.
+fn price_from_history_json(json: &str, home_currency: &str) -> Option<Decimal> {
+
+    let current_price = &json[json.find("\"current_price\"")?..];
+    let current_price = &current_price[..current_price.find('}')?];
+    let key = format!("\"{}\":", home_currency.to_lowercase());
+    let value = &current_price[current_price.find(&key)? + key.len()..];
+    let end = value.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == 'e' || c == 'E' || c == '+'))
+        .unwrap_or(value.len());
+
+    Decimal::from_scientific(&value[..end]).or_else(|_| value[..end].parse::<Decimal>()).ok()
+}
+
+/// CoinGecko's id for `ticker`, for the most common tickers, or else the lowercase ticker.
+fn coingecko_id(ticker: &str) -> String {
+    match ticker.to_uppercase().as_str() {
+        "BTC" => "bitcoin",
+        "ETH" => "ethereum",
+        "LTC" => "litecoin",
+        "BCH" => "bitcoin-cash",
+        "XRP" => "ripple",
+        "XMR" => "monero",
+        "ADA" => "cardano",
+        "SOL" => "solana",
+        "DOT" => "polkadot",
+        "DOGE" => "dogecoin",
+        "USDT" => "tether",
+        "USDC" => "usd-coin",
+        _ => return ticker.to_lowercase(),
+    }.to_string()
+}
@@ -0,0 +1,53 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use rust_decimal_macros::dec;
+
+use crate::transaction::{Transaction, ActionRecord, IncomeType, Polarity, TxKind};
+
+
+/// How a rebase (a `TxKind::Rebase`: a change in the quantity of a rebasing or interest-bearing token held, i.e., an
+/// aToken or stETH, without a trade) is treated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RebaseTreatment {
+    /// An increase is income (of `IncomeType::Rebase`) as it accrues, at its `proceeds`, which are the basis of
+    /// the new `Lot`.
+    Income,
+    /// An increase isn't income: the new units have no basis of their own, so the basis already held is spread over
+    /// more units.  They are split into a `Lot` for each `Lot` with a balance in the `Account`, in proportion to its
+    /// balance, each with that `Lot`'s basis date.
+    SpreadBasis,
+}
+
+/// Readies every rebase for processing under `treatment`.  An increase treated as income becomes ordinary income of
+/// `IncomeType::Rebase`.  Any other rebase keeps its `TxKind::Rebase`, and its `proceeds` are zeroed: an increase as
+/// it adds no basis, and a decrease (under either treatment) as the units are lost without proceeds, so their basis
+/// is the loss.  Returns the number of rebases.
+pub(crate) fn apply_rebase_treatment(
+    treatment: RebaseTreatment,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> usize {
+
+    let mut rebases = 0;
+
+    for txn in txns_map.values_mut() {
+
+        if txn.kind != TxKind::Rebase { continue }
+
+        rebases += 1;
+
+        let is_increase = ars.get(&txn.action_record_idx_vec[0]).unwrap().direction() == Polarity::Incoming;
+
+        if is_increase && treatment == RebaseTreatment::Income {
+            txn.kind = TxKind::Standard;
+            txn.income_type = Some(IncomeType::Rebase);
+        } else {
+            txn.proceeds = dec!(0);
+        }
+    }
+
+    rebases
+}
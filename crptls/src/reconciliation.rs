@@ -0,0 +1,122 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+
+
+/// An `Account`'s balance at the end of its history, compared against the balance the user expected (as of the last
+/// `Transaction`, per the exchange or wallet).
+#[derive(Clone, Debug)]
+pub struct BalanceCheck {
+    pub account: String,
+    pub ticker: String,
+    /// `None` if the expected balances name an `Account` that isn't in the file_to_import.
+    pub computed: Option<Decimal>,
+    /// `None` if the expected balances don't list the `Account`.
+    pub expected: Option<Decimal>,
+}
+
+impl BalanceCheck {
+
+    /// The computed balance less the expected balance, if both are known.
+    pub fn difference(&self) -> Option<Decimal> {
+        Some(self.computed? - self.expected?)
+    }
+
+    pub fn status(&self) -> &'static str {
+        match (self.computed, self.expected) {
+            (None, _) => "Unknown account",
+            (_, None) => "Not verified",
+            _ if self.difference() == Some(Decimal::ZERO) => "OK",
+            _ => "MISMATCH",
+        }
+    }
+}
+
+/// A balance the user expects an `Account` to have at the end of its history.
+#[derive(Clone, Debug)]
+pub struct ExpectedBalance {
+    pub account: String,
+    pub ticker: String,
+    pub balance: Decimal,
+}
+
+/// Reads an expected balances file: a CSV with a header row, then one `account,ticker,balance` row per `Account`
+/// (i.e., `Exchange,BTC,0.5`), named and ticked as in the file_to_import.
+pub fn import_expected_balances(path: &Path) -> Result<Vec<ExpectedBalance>, Box<dyn Error>> {
+
+    let file = File::open(path)?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut balances: Vec<ExpectedBalance> = Vec::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;  //  Account for the header and one-based row numbers
+
+        let (account, ticker) = match (record.get(0), record.get(1)) {
+            (Some(a), Some(t)) if !a.is_empty() && !t.is_empty() => (a.to_string(), t.to_string()),
+            _ => return Err(format!("Expected balances file row {} is missing an account or ticker.", row).into()),
+        };
+
+        let balance = match record.get(2).map(|b| b.replace(',', "").parse::<Decimal>()) {
+            Some(Ok(b)) => b,
+            _ => return Err(format!("Expected balances file row {} has an invalid balance for {} ({}).",
+                row, account, ticker).into()),
+        };
+
+        if balances.iter().any(|b| b.account == account && b.ticker == ticker) {
+            return Err(format!("Expected balances file lists {} ({}) more than once.", account, ticker).into())
+        }
+
+        balances.push(ExpectedBalance { account, ticker, balance });
+    }
+
+    Ok(balances)
+}
+
+/// Checks every `Account`'s end-of-history balance against the `expected` ones (see `import_expected_balances()`),
+/// in `Account` order, followed by any expected balance of an `Account` that doesn't exist.
+pub fn reconcile_balances(
+    expected: &[ExpectedBalance],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Vec<BalanceCheck> {
+
+    let mut checks: Vec<BalanceCheck> = Vec::with_capacity(acct_map.len());
+
+    for j in 1..=acct_map.len() {
+
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        checks.push(BalanceCheck {
+            account: raw_acct.name.clone(),
+            ticker: raw_acct.ticker.clone(),
+            computed: Some(acct.get_sum_of_amts_in_lots()),
+            expected: expected.iter()
+                .find(|e| e.account == raw_acct.name && e.ticker == raw_acct.ticker)
+                .map(|e| e.balance),
+        });
+    }
+
+    for e in expected.iter() {
+        if !checks.iter().any(|check| check.account == e.account && check.ticker == e.ticker) {
+            checks.push(BalanceCheck { account: e.account.clone(), ticker: e.ticker.clone(), computed: None, expected: Some(e.balance) });
+        }
+    }
+
+    checks
+}
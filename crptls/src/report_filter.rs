@@ -0,0 +1,53 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use chrono::NaiveDate;
+
+use crate::account::RawAccount;
+use crate::tags::Tag;
+
+
+/// Limits the reports to the movements of a single account or asset, or of a date range or tag, without changing how the
+/// lots are processed (so a disposal's cost basis and term are the same as in an unfiltered run).  Each limit that
+/// isn't set lets everything through.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportFilter {
+    /// The ticker of the asset reported (i.e., `BTC`), matched without regard to case.
+    pub asset: Option<String>,
+    /// The number of the account reported, as in the header rows of the CSV Input File.
+    pub account: Option<u16>,
+    /// The numbers of the accounts reported (i.e., those of an entity; see `entities`).
+    pub accounts: Option<Vec<u16>>,
+    /// The first date reported.
+    pub from: Option<NaiveDate>,
+    /// The last date reported.
+    pub to: Option<NaiveDate>,
+    /// The tag (or, without a value, any tag of its name) of the transactions reported.
+    pub tag: Option<Tag>,
+}
+
+impl ReportFilter {
+
+    /// Whether nothing is filtered out.
+    pub fn is_empty(&self) -> bool {
+        *self == ReportFilter::default()
+    }
+
+    /// Whether `date` is within the `from` and `to` dates (inclusive).
+    pub fn includes_date(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+
+    /// Whether a transaction with the `tags` is reported: one of them is the `tag`.
+    pub fn includes_tags(&self, tags: &[Tag]) -> bool {
+        self.tag.as_ref().is_none_or(|filter| tags.iter().any(|tag| tag.matches(filter)))
+    }
+
+    /// Whether the movements of `raw_acct` are reported: it's the `account` (and one of the `accounts`), and of the
+    /// `asset`.
+    pub fn includes_account(&self, raw_acct: &RawAccount) -> bool {
+        self.account.is_none_or(|account_num| raw_acct.account_num == account_num)
+            && self.accounts.as_ref().is_none_or(|accounts| accounts.contains(&raw_acct.account_num))
+            && self.asset.as_ref().is_none_or(|asset| raw_acct.ticker.eq_ignore_ascii_case(asset))
+    }
+}
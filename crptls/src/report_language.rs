@@ -0,0 +1,192 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+
+/// The catalogs of the translated text, in gettext's PO format: each `msgid` is the English text as written in the
+/// code, and its `msgstr` is the translation.  Text missing from a catalog is left in English.
+const SPANISH_CATALOG: &str = include_str!("../locales/es.po");
+const GERMAN_CATALOG: &str = include_str!("../locales/de.po");
+
+/// The language of the headers, labels and sentences of the text reports, the journal entries and the print menu.
+/// The CSV reports' column headers, the account names of the ledger and beancount journals and the names of the
+/// reports' files stay in English, so that other programs can still read them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReportLanguage {
+    #[default]
+    English,
+    Spanish,
+    German,
+}
+
+impl ReportLanguage {
+
+    /// The language of a language code or locale (i.e., `es`, `de-DE`, or `de_AT.UTF-8`), if it's one with a catalog.
+    /// `C` and `POSIX` are English.
+    pub fn from_code(code: &str) -> Option<ReportLanguage> {
+        match code.to_ascii_lowercase().as_str() {
+            "c" | "posix" => Some(ReportLanguage::English),
+            code => match code.get(..2) {
+                Some("en") => Some(ReportLanguage::English),
+                Some("es") => Some(ReportLanguage::Spanish),
+                Some("de") => Some(ReportLanguage::German),
+                _ => None,
+            },
+        }
+    }
+
+    /// The language of the locale of the environment, as gettext finds it: the first set of LC_ALL, LC_MESSAGES and
+    /// LANG.  A language without a catalog is English.
+    pub fn from_env() -> ReportLanguage {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|val| !val.is_empty())
+            .and_then(|val| ReportLanguage::from_code(&val))
+            .unwrap_or_default()
+    }
+
+    /// The `msgid` (English text) in this language.
+    pub fn text<'a>(&self, msgid: &'a str) -> &'a str {
+        match self.catalog() {
+            Some(catalog) => catalog.get(msgid).map_or(msgid, |msgstr| msgstr.as_str()),
+            None => msgid,
+        }
+    }
+
+    /// The `msgid` in this language, with its placeholders filled by the `args`.  A placeholder is `{}` (the next
+    /// arg), or `{N}` (arg N, from 0), so that a translation may put them in another order, and either may be given
+    /// a width, aligned left (the default) or right, as with `format!()`: `{:8}`, `{:>10}` or `{1:<12}`.  `{{` and
+    /// `}}` are literal braces.
+    pub fn fill(&self, msgid: &str, args: &[&dyn Display]) -> String {
+        fill_placeholders(self.text(msgid), args)
+    }
+
+    fn catalog(&self) -> Option<&'static HashMap<String, String>> {
+
+        static SPANISH: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static GERMAN: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+        match self {
+            ReportLanguage::English => None,
+            ReportLanguage::Spanish => Some(SPANISH.get_or_init(|| parse_catalog(SPANISH_CATALOG))),
+            ReportLanguage::German => Some(GERMAN.get_or_init(|| parse_catalog(GERMAN_CATALOG))),
+        }
+    }
+
+    /// Each msgid of the catalog, with its msgstr (for checking the catalogs against each other).
+    pub fn catalog_entries(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = self.catalog().into_iter().flatten()
+            .map(|(msgid, msgstr)| (msgid.clone(), msgstr.clone()))
+            .collect();
+        entries.sort();
+        entries
+    }
+}
+
+/// The msgids and msgstrs of a PO `catalog`, each of which may continue on the lines after it as further quoted
+/// strings.  Comments and entries without a translation are skipped.
+fn parse_catalog(catalog: &str) -> HashMap<String, String> {
+
+    let mut entries = HashMap::new();
+    let mut msgid: Option<String> = None;
+    let mut msgstr: Option<String> = None;
+
+    let mut finish = |msgid: &mut Option<String>, msgstr: &mut Option<String>| {
+        if let (Some(id), Some(text)) = (msgid.take(), msgstr.take()) {
+            if !id.is_empty() && !text.is_empty() {
+                entries.insert(id, text);
+            }
+        }
+    };
+
+    for line in catalog.lines().map(|line| line.trim()) {
+
+        if let Some(quoted) = line.strip_prefix("msgid ") {
+            finish(&mut msgid, &mut msgstr);
+            msgid = Some(unquote(quoted));
+        } else if let Some(quoted) = line.strip_prefix("msgstr ") {
+            msgstr = Some(unquote(quoted));
+        } else if line.starts_with('"') {
+            if let Some(text) = msgstr.as_mut().or(msgid.as_mut()) {
+                text.push_str(&unquote(line));
+            }
+        }
+    }
+
+    finish(&mut msgid, &mut msgstr);
+
+    entries
+}
+
+/// The text of a PO quoted string, with its escapes (`\"`, `\\`, `\n` and `\t`) undone.
+fn unquote(quoted: &str) -> String {
+
+    let inner = quoted.trim().trim_start_matches('"').strip_suffix('"').unwrap_or("");
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            },
+            c => text.push(c),
+        }
+    }
+
+    text
+}
+
+/// The `template` with its placeholders (see `ReportLanguage::fill()`) filled by the `args`.  A placeholder without
+/// an arg is left empty.
+fn fill_placeholders(template: &str, args: &[&dyn Display]) -> String {
+
+    let mut filled = String::with_capacity(template.len() + 16 * args.len());
+    let mut next_arg = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+
+        match c {
+            '{' if chars.peek() == Some(&'{') => { chars.next(); filled.push('{') }
+            '}' if chars.peek() == Some(&'}') => { chars.next(); filled.push('}') }
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let (index, format) = spec.split_once(':').unwrap_or((&spec, ""));
+
+                let arg_idx = match index.parse::<usize>() {
+                    Ok(idx) => idx,
+                    Err(_) => { next_arg += 1; next_arg - 1 }
+                };
+                let value = args.get(arg_idx).map_or(String::new(), |arg| arg.to_string());
+
+                let (right, width) = match format.strip_prefix('>') {
+                    Some(width) => (true, width),
+                    None => (false, format.strip_prefix('<').unwrap_or(format)),
+                };
+                let padding = width.parse::<usize>().unwrap_or(0).saturating_sub(value.chars().count());
+
+                if right { filled.push_str(&" ".repeat(padding)) }
+                filled.push_str(&value);
+                if !right { filled.push_str(&" ".repeat(padding)) }
+            }
+            c => filled.push(c),
+        }
+    }
+
+    filled
+}
+
+/// The placeholders of a `text` (see `ReportLanguage::fill()`), as written, in order (for checking a translation
+/// against its msgid).
+pub fn placeholders(text: &str) -> Vec<String> {
+    let unescaped = text.replace("{{", "").replace("}}", "");
+    unescaped.split('{').skip(1).filter_map(|part| part.split_once('}').map(|(spec, _)| spec.to_string())).collect()
+}
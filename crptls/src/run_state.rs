@@ -0,0 +1,363 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::account::{Account, RawAccount, Term};
+use crate::core_functions::ImportProcessParameters;
+use crate::importers;
+use crate::transaction::{self, Transaction, ActionRecord, TxKind};
+
+
+/// The open `Lot`s at the end of a run, saved so that a later run can pick up where it left off with only the
+/// `Transaction`s since.  It is written to (and read from) a TOML file, i.e.:
+///
+/// ```toml
+/// through = "2020-12-31"
+/// home_currency = "USD"
+///
+/// [[lots]]
+/// account = "Coinbase"
+/// ticker = "BTC"
+/// acquired = "2016-02-01"
+/// basis_date = "2016-02-01"
+/// amount = "0.5"
+/// cost_basis = "220.00"
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunState {
+    /// The date of the last `Transaction` processed.  A resumed run may only hold `Transaction`s dated after it.
+    pub through: NaiveDate,
+    pub home_currency: String,
+    /// The non-zero `Lot`s of every non-margin, non-home currency `Account`, in `Account` and then `Lot` order.
+    #[serde(default)]
+    pub lots: Vec<OpenLot>,
+}
+
+/// The header row of an opening balances CSV (see `RunState::from_opening_balances()`).
+pub const OPENING_BALANCE_COLUMNS: [&str; 8] = [
+    "As of", "Account", "Ticker", "Amount", "Date acquired", "Basis date", "Cost basis", "Inherited",
+];
+
+/// What remains of a `Lot`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenLot {
+    /// The name of the `Account` holding the `Lot`.
+    pub account: String,
+    pub ticker: String,
+    /// The date of the `Lot`'s first `Movement`.
+    pub acquired: NaiveDate,
+    /// The date the `Lot`'s holding period runs from.
+    pub basis_date: NaiveDate,
+    /// The remaining amount.
+    pub amount: Decimal,
+    /// The remaining cost basis (after like-kind treatment, if it was applied).
+    pub cost_basis: Decimal,
+    /// Whether the `Lot` is `inherited`, and so long-term whatever its `basis_date`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inherited: bool,
+}
+
+impl OpenLot {
+
+    /// The holding period of the `Lot` if disposed of on `disposal_date` (see `Lot::term()`).
+    pub fn term(&self, disposal_date: NaiveDate, long_term_days: i64) -> Term {
+        if self.inherited { Term::LT } else { Term::from_dates(self.basis_date, disposal_date, long_term_days) }
+    }
+}
+
+impl RunState {
+
+    /// Takes a snapshot of the open `Lot`s of a fully processed run, as of its last `Transaction`.
+    pub fn from_processed(
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<RunState, Box<dyn Error>> {
+
+        let through = txns_map.values().map(|txn| txn.date).max()
+            .ok_or("There are no transactions, so there is no state to save.")?;
+
+        RunState::as_of(settings, raw_acct_map, acct_map, through)
+    }
+
+    /// Takes a snapshot of the `Lot`s of a fully processed run that were open at the end of `through`, counting only
+    /// the `Movement`s dated on or before it.  Home currency balances have no basis to carry forward, so they are left
+    /// out.  An open margin position can't be carried forward, so any non-zero margin `Account` is an error.
+    pub fn as_of(
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        through: NaiveDate,
+    ) -> Result<RunState, Box<dyn Error>> {
+
+        let mut lots = Vec::new();
+
+        for num in 1..=acct_map.len() {
+
+            let acct = acct_map.get(&(num as u16)).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            if raw_acct.is_margin {
+
+                let position: Decimal = acct.list_of_lots.borrow().iter()
+                    .flat_map(|lot| lot.movements.borrow().iter().map(|mvmt| (mvmt.date, mvmt.amount)).collect::<Vec<_>>())
+                    .filter(|(date, _amount)| *date <= through)
+                    .map(|(_date, amount)| amount)
+                    .sum();
+
+                if position != dec!(0) {
+                    return Err(format!("The margin account {} ({}) has an open position on {}, which can't be carried forward.",
+                        raw_acct.name, raw_acct.ticker, through).into())
+                }
+                continue
+            }
+
+            if raw_acct.is_home_currency(&settings.home_currency) { continue }
+
+            for lot in acct.list_of_lots.borrow().iter() {
+
+                let mut amount = dec!(0);
+                let mut cost_basis = dec!(0);
+
+                for mvmt in lot.movements.borrow().iter().filter(|mvmt| mvmt.date <= through) {
+                    amount += mvmt.amount;
+                    cost_basis += mvmt.cost_basis_lk.get();
+                }
+
+                if amount == dec!(0) { continue }
+
+                lots.push(OpenLot {
+                    account: raw_acct.name.clone(),
+                    ticker: raw_acct.ticker.clone(),
+                    acquired: lot.date_of_first_mvmt_in_lot,
+                    basis_date: lot.date_for_basis_purposes,
+                    amount,
+                    cost_basis,
+                    inherited: lot.inherited,
+                });
+            }
+        }
+
+        Ok(RunState { through, home_currency: settings.home_currency.clone(), lots })
+    }
+
+    pub fn from_file(path: &Path) -> Result<RunState, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The `Lot`s as rows of an opening balances CSV, beneath a header row of `OPENING_BALANCE_COLUMNS`.
+    pub fn opening_balance_rows(&self) -> Vec<Vec<String>> {
+
+        let mut rows = vec![OPENING_BALANCE_COLUMNS.iter().map(|column| column.to_string()).collect()];
+
+        for lot in self.lots.iter() {
+            rows.push(vec![
+                self.through.to_string(),
+                lot.account.clone(),
+                lot.ticker.clone(),
+                lot.amount.to_string(),
+                lot.acquired.to_string(),
+                lot.basis_date.to_string(),
+                lot.cost_basis.to_string(),
+                if lot.inherited { "yes" } else { "no" }.to_string(),
+            ]);
+        }
+
+        rows
+    }
+
+    /// Reads an opening balances CSV (as exported at a year end, and perhaps since edited, or written by hand for the
+    /// lots held before the tracked history begins) with a header row of `OPENING_BALANCE_COLUMNS`, in any order, and
+    /// dates as YYYY-MM-DD.  Every row must be as of the same date.  The "As of", "Basis date" and "Inherited" columns
+    /// may be left out: the lots are then as of the latest date acquired, each one's basis date is its date acquired,
+    /// and none is inherited.
+    pub fn from_opening_balances(path: &Path, home_currency: &str) -> Result<RunState, Box<dyn Error>> {
+
+        let contents = fs::read_to_string(path)?;
+        let contents = contents.trim_start_matches('\u{feff}');
+
+        let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_reader(contents.as_bytes());
+        let headers = rdr.headers()?.clone();
+
+        const AS_OF: usize = 0;
+        const BASIS_DATE: usize = 5;
+        const INHERITED: usize = 7;
+
+        let mut cols = [None; 8];
+        for (idx, name) in OPENING_BALANCE_COLUMNS.iter().enumerate() {
+            cols[idx] = headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+            if cols[idx].is_none() && idx != AS_OF && idx != BASIS_DATE && idx != INHERITED {
+                return Err(format!("It has no `{}` column.", name).into())
+            }
+        }
+
+        let mut as_of = None;
+        let mut lots = Vec::new();
+
+        for (idx, result) in rdr.records().enumerate() {
+
+            let record = result?;
+            let field = |col: usize| cols[col].and_then(|pos| record.get(pos)).unwrap_or("").trim();
+            let row = idx + 2;
+
+            let date = |col: usize| NaiveDate::parse_from_str(field(col), "%Y-%m-%d")
+                .map_err(|_| format!("Row {} has a `{}` ({}) that isn't a YYYY-MM-DD date.", row, OPENING_BALANCE_COLUMNS[col], field(col)));
+            let number = |col: usize| field(col).parse::<Decimal>()
+                .map_err(|_| format!("Row {} has a `{}` ({}) that isn't a number.", row, OPENING_BALANCE_COLUMNS[col], field(col)));
+
+            if cols[AS_OF].is_some() {
+                let row_as_of = date(AS_OF)?;
+                match as_of {
+                    None => as_of = Some(row_as_of),
+                    Some(as_of) if as_of != row_as_of => {
+                        return Err(format!("Row {} is as of {}, but the rows before it are as of {}.", row, row_as_of, as_of).into())
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            let acquired = date(4)?;
+            let basis_date = if cols[BASIS_DATE].is_some() { date(BASIS_DATE)? } else { acquired };
+            let inherited = match field(INHERITED).to_ascii_lowercase().as_str() {
+                "yes" | "true" => true,
+                "" | "no" | "false" => false,
+                other => return Err(format!("Row {} has an `Inherited` ({}) that isn't yes or no.", row, other).into()),
+            };
+
+            lots.push(OpenLot {
+                account: field(1).to_string(),
+                ticker: field(2).to_uppercase(),
+                amount: number(3)?,
+                acquired,
+                basis_date,
+                cost_basis: number(6)?,
+                inherited,
+            });
+        }
+
+        let through = as_of
+            .or_else(|| lots.iter().map(|lot| lot.acquired).max())
+            .ok_or("It has no lots.")?;
+
+        if let Some(lot) = lots.iter().find(|lot| lot.acquired > through) {
+            return Err(format!("A lot of {} {} was acquired on {}, after the \"As of\" date ({}).",
+                lot.amount, lot.ticker, lot.acquired, through).into())
+        }
+
+        Ok(RunState { through, home_currency: home_currency.to_string(), lots })
+    }
+}
+
+/// Inserts a `TxKind::CarriedForward` `Transaction` for each of the `state`'s open `Lot`s ahead of the imported
+/// `Transaction`s, which are renumbered to follow them.  Each is a single incoming `ActionRecord` dated the `state`'s
+/// `through` date, with the `Lot`'s basis date and its cost basis as `proceeds` (and, for an inherited `Lot`, of
+/// `TxKind::CarriedForwardInherited`), so the `Lot` it creates is disposed of just as the original would have been.  An `Account` not in the CSV Input File is added.  Returns the number of
+/// `Transaction`s added.
+pub(crate) fn add_carried_forward_lots(
+    state: &RunState,
+    settings: &ImportProcessParameters,
+    raw_acct_map: &mut HashMap<u16, RawAccount>,
+    acct_map: &mut HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<usize, Box<dyn Error>> {
+
+    if state.home_currency != settings.home_currency {
+        return Err(format!("The saved state's home currency ({}) isn't the home currency of this run ({}).",
+            state.home_currency, settings.home_currency).into())
+    }
+
+    for num in 1..=txns_map.len() {
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        if txn.date <= state.through {
+            return Err(format!("Transaction {} ({}) isn't after the saved state's date ({}). Only transactions since then \
+                may be imported when resuming.", txn.tx_number, txn.date, state.through).into())
+        }
+    }
+
+    let date_as_string: Rc<str> = Rc::from(state.through.format(&importers::input_file_date_format(settings)).to_string());
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(state.lots.len() + txns_map.len());
+
+    for lot in state.lots.iter() {
+
+        let existing_key = raw_acct_map.values()
+            .find(|raw_acct| raw_acct.name == lot.account && raw_acct.ticker == lot.ticker && !raw_acct.is_margin)
+            .map(|raw_acct| raw_acct.account_num);
+
+        let account_key = match existing_key {
+            Some(key) => key,
+            None => {
+                let account_num = raw_acct_map.len() as u16 + 1;
+                raw_acct_map.insert(account_num, RawAccount {
+                    account_num,
+                    name: lot.account.clone(),
+                    ticker: lot.ticker.clone(),
+                    is_margin: false,
+                });
+                acct_map.insert(account_num, Account {
+                    raw_key: account_num,
+                    list_of_lots: RefCell::new([].to_vec()),
+                });
+                account_num
+            }
+        };
+
+        let ar_num = ar_map.len() as u32 + 1;
+
+        ar_map.insert(ar_num, ActionRecord {
+            account_key,
+            amount: lot.amount,
+            tx_key: 0,  //  Set when renumbering, below
+            self_ar_key: ar_num,
+            movements: RefCell::new([].to_vec()),
+        });
+
+        ordered_txns.push(Transaction {
+            tx_number: 0,   //  Set when renumbering, below
+            source_row: None,
+            date_as_string: date_as_string.clone(),
+            date: state.through,
+            date_for_basis_purposes: lot.basis_date,
+            time: None,
+            sequence: None,
+            user_memo: format!("Carried forward {} {} acquired {}", lot.amount, lot.ticker, lot.acquired),
+            proceeds: lot.cost_basis,
+            fee: dec!(0),
+            income_type: None,
+            tags: Vec::new(),
+            expense_category: None,
+            payer: None,
+            action_record_idx_vec: vec![ar_num],
+            kind: if lot.inherited { TxKind::CarriedForwardInherited } else { TxKind::CarriedForward },
+        });
+    }
+
+    let added = ordered_txns.len();
+
+    for num in 1..=txns_map.len() {
+        ordered_txns.push(txns_map.remove(&(num as u32)).unwrap());
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    Ok(added)
+}
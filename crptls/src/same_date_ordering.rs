@@ -0,0 +1,110 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use chrono::NaiveTime;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{self, Transaction, ActionRecord, Polarity};
+
+
+/// The order in which `Transaction`s sharing a date (and `time` and `sequence`, if any) are processed.  Without a
+/// `txTime` or `txSeq` in the CSV Input File to order them by, a same-day acquisition and disposal in an `Account`
+/// may be processed in either order, and the order determines which `Lot` the disposal is drawn from under LIFO (or
+/// HIFO-like) costing methods.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameDateOrder {
+    /// The order of the rows in the CSV Input File.
+    FileOrder,
+    /// Acquisitions, then exchanges (which both acquire and dispose), then disposals.  Under LIFO, a same-day
+    /// disposal is then drawn from that day's acquisitions first (a short-term lot with a basis near the proceeds).
+    AcquisitionsFirst,
+    /// Disposals, then exchanges, then acquisitions.  A same-day disposal is then drawn only from lots held
+    /// before that day (possibly long-term, and with an older basis).
+    DisposalsFirst,
+}
+
+/// Stably reorders each run of consecutive same-date `Transaction`s by their `time`, then their `sequence` (either
+/// of which, if `None`, sorts first), then according to `order`, then renumbers every `Transaction` to match, so a
+/// `Transaction`'s number may differ from its row's position in the CSV Input File.  Ties keep the order of the
+/// rows.  A `Transaction` acquires if it has an incoming non-margin, non-home currency `ActionRecord`, and disposes
+/// if it has an outgoing one.  Returns the number of `Transaction`s that changed position.
+pub(crate) fn reorder_same_date_txns(
+    order: SameDateOrder,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> usize {
+
+    let is_sequenced = txns_map.values().any(|txn| txn.time.is_some() || txn.sequence.is_some());
+
+    if order == SameDateOrder::FileOrder && !is_sequenced { return 0 }
+
+    let rank = |txn: &Transaction| -> (Option<NaiveTime>, Option<u32>, u8) {
+
+        let mut acquires = false;
+        let mut disposes = false;
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            match ar.direction() {
+                Polarity::Incoming => acquires = true,
+                Polarity::Outgoing => disposes = true,
+            }
+        }
+
+        let acquisitions_first_rank = match (acquires, disposes) {
+            (true, false) => 0,
+            (false, true) => 2,
+            _ => 1,
+        };
+
+        let rank = match order {
+            SameDateOrder::FileOrder => 0,
+            SameDateOrder::DisposalsFirst => 2 - acquisitions_first_rank,
+            SameDateOrder::AcquisitionsFirst => acquisitions_first_rank,
+        };
+
+        (txn.time, txn.sequence, rank)
+    };
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut moved = 0;
+    let mut group_start = 0;
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.remove(&(num as u32)).unwrap();
+
+        if ordered_txns.last().is_some_and(|last| last.date != txn.date) {
+            moved += sort_group(&mut ordered_txns[group_start..], &rank);
+            group_start = ordered_txns.len();
+        }
+
+        ordered_txns.push(txn);
+    }
+
+    moved += sort_group(&mut ordered_txns[group_start..], &rank);
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    moved
+}
+
+/// Stably sorts a same-date group by `rank`, returning how many of its `Transaction`s changed position.
+fn sort_group<K: Ord>(group: &mut [Transaction], rank: &impl Fn(&Transaction) -> K) -> usize {
+
+    let original: Vec<u32> = group.iter().map(|txn| txn.tx_number).collect();
+
+    group.sort_by_key(|txn| rank(txn));
+
+    group.iter().zip(original.iter()).filter(|(txn, num)| txn.tx_number != **num).count()
+}
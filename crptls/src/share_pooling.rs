@@ -0,0 +1,210 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// The number of days after a disposal within which an acquisition is matched with it ("bed and breakfast").
+const BED_AND_BREAKFAST_DAYS: i64 = 30;
+
+/// How a disposal (an outgoing `ActionRecord`) was matched under the HMRC share pooling rules, in units, and the cost
+/// basis it was given as a result (negative, as on its `Movement`s).
+#[derive(Clone, Debug)]
+pub struct PooledDisposal {
+    pub tx_num: u32,
+    pub account_key: u16,
+    pub same_day: Decimal,
+    pub bed_and_breakfast: Decimal,
+    pub section_104: Decimal,
+    pub cost_basis: Decimal,
+}
+
+/// The acquisitions and disposals of a currency on a single date, which HMRC treats as a single acquisition and a
+/// single disposal.
+#[derive(Default)]
+struct Day {
+    acquired: Decimal,
+    cost: Decimal,
+    disposed: Decimal,
+    /// (`Transaction`, `ActionRecord`, units) of each disposal.
+    disposals: Vec<(u32, u32, Decimal)>,
+    //  Set while matching
+    same_day: Decimal,
+    bed_and_breakfast: Decimal,
+    bed_and_breakfast_cost: Decimal,
+    acquired_unmatched: Decimal,
+}
+
+/// Replaces the cost basis of every disposal with its cost under the HMRC share pooling rules.  The cost basis of all
+/// `Movement`s must already have been added.  For each currency (across every non-margin `Account`), disposals on a date
+/// are matched first with acquisitions on the same date, then with acquisitions in the following 30 days (earliest
+/// first), and the rest with the Section 104 pool, which holds every other acquisition at its average cost.  Transfers
+/// between the user's own `Account`s are neither.  Only disposals' cost basis changes; each `Lot` keeps its own.
+pub(crate) fn apply_share_pooling(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<PooledDisposal>, Box<dyn Error>> {
+
+    let mut currencies: HashMap<String, BTreeMap<NaiveDate, Day>> = HashMap::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            let day = currencies.entry(raw_acct.ticker.clone()).or_default().entry(txn.date).or_default();
+
+            match ar.direction() {
+                Polarity::Incoming => {
+                    day.acquired += ar.amount;
+                    day.cost += ar.cost_basis_in_ar();
+                }
+                Polarity::Outgoing => {
+                    day.disposed -= ar.amount;
+                    day.disposals.push((txn.tx_number, *ar_num, -ar.amount));
+                }
+            }
+        }
+    }
+
+    let mut pooled_disposals: Vec<PooledDisposal> = Vec::new();
+
+    for (ticker, days) in currencies.iter_mut() {
+
+        //  Same-day matching
+        for day in days.values_mut() {
+            day.same_day = day.acquired.min(day.disposed);
+            day.acquired_unmatched = day.acquired - day.same_day;
+        }
+
+        //  Bed and breakfast matching, in date order of both the disposals and the acquisitions
+        let dates: Vec<NaiveDate> = days.keys().copied().collect();
+
+        for (idx, date) in dates.iter().enumerate() {
+
+            let mut unmatched = days[date].disposed - days[date].same_day;
+
+            for later_date in dates.iter().skip(idx + 1) {
+
+                if unmatched <= dec!(0) || *later_date > *date + Duration::days(BED_AND_BREAKFAST_DAYS) { break }
+
+                let later_day = days.get_mut(later_date).unwrap();
+                let units = unmatched.min(later_day.acquired_unmatched);
+
+                if units <= dec!(0) { continue }
+
+                let cost = later_day.cost * units / later_day.acquired;
+                later_day.acquired_unmatched -= units;
+                unmatched -= units;
+
+                let day = days.get_mut(date).unwrap();
+                day.bed_and_breakfast += units;
+                day.bed_and_breakfast_cost += cost;
+            }
+        }
+
+        //  The Section 104 pool, for everything else
+        let mut pool_units = dec!(0);
+        let mut pool_cost = dec!(0);
+
+        for (date, day) in days.iter() {
+
+            if day.acquired_unmatched > dec!(0) {
+                pool_units += day.acquired_unmatched;
+                pool_cost += day.cost * day.acquired_unmatched / day.acquired;
+            }
+
+            let from_pool = day.disposed - day.same_day - day.bed_and_breakfast;
+            let mut cost_from_pool = dec!(0);
+
+            if from_pool > dec!(0) {
+
+                if from_pool > pool_units {
+                    return Err(format!("On {}, {} {} was disposed of, but the Section 104 pool held only {}.",
+                        date, from_pool, ticker, pool_units).into())
+                }
+
+                cost_from_pool = pool_cost * from_pool / pool_units;
+                pool_units -= from_pool;
+                pool_cost -= cost_from_pool;
+            }
+
+            if day.disposed == dec!(0) { continue }
+
+            let same_day_cost = if day.same_day > dec!(0) { day.cost * day.same_day / day.acquired } else { dec!(0) };
+            let total_cost = same_day_cost + day.bed_and_breakfast_cost + cost_from_pool;
+
+            for (tx_num, ar_num, units) in day.disposals.iter() {
+
+                let share = *units / day.disposed;
+                let ar = ars.get(ar_num).unwrap();
+                let cost_basis = -round_d128_1e2(&(total_cost * share));
+
+                set_cost_basis_of_disposal(ar, cost_basis, acct_map, txns_map);
+
+                pooled_disposals.push(PooledDisposal {
+                    tx_num: *tx_num,
+                    account_key: ar.account_key,
+                    same_day: day.same_day * share,
+                    bed_and_breakfast: day.bed_and_breakfast * share,
+                    section_104: from_pool * share,
+                    cost_basis,
+                });
+            }
+        }
+    }
+
+    pooled_disposals.sort_by_key(|disposal| disposal.tx_num);
+
+    Ok(pooled_disposals)
+}
+
+/// Spreads `cost_basis` over the `Movement`s of a disposal in proportion to their amounts, with any rounding difference
+/// in the largest.
+pub(crate) fn set_cost_basis_of_disposal(
+    ar: &ActionRecord,
+    cost_basis: Decimal,
+    acct_map: &HashMap<u16, Account>,
+    txns_map: &HashMap<u32, Transaction>,
+) {
+
+    let movements = ar.get_mvmts_in_ar_in_lot_date_order();
+
+    let largest_idx = movements.iter().enumerate()
+        .min_by_key(|(_idx, mvmt)| mvmt.amount)     //  Outgoing amounts are negative
+        .map(|(idx, _mvmt)| idx)
+        .unwrap();
+
+    let mut sum_of_others = dec!(0);
+
+    for (idx, mvmt) in movements.iter().enumerate() {
+        if idx == largest_idx { continue }
+        let mvmt_basis = round_d128_1e2(&(cost_basis * mvmt.amount / ar.amount));
+        mvmt.cost_basis.set(mvmt_basis);
+        mvmt.cost_basis_lk.set(mvmt_basis);
+        sum_of_others += mvmt_basis;
+    }
+
+    movements[largest_idx].cost_basis.set(cost_basis - sum_of_others);
+    movements[largest_idx].cost_basis_lk.set(cost_basis - sum_of_others);
+}
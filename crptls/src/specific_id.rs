@@ -0,0 +1,194 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::rc::Rc;
+use std::error::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Lot, Term};
+use crate::transaction::{Transaction, ActionRecord};
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// A `Lot` that a `Disposal` may be drawn from, as of the date of the `Disposal`.
+#[derive(Clone, Debug)]
+pub struct OpenLot {
+    pub lot_number: u32,
+    pub basis_date: NaiveDate,
+    pub term: Term,
+    pub balance: Decimal,
+    /// The cost basis of the `balance` (not of the whole `Lot`).
+    pub cost_basis: Decimal,
+    /// The gain or loss if the entire `balance` were disposed of at the `Disposal`'s price per unit.
+    pub unrealized_gain_or_loss: Decimal,
+}
+
+/// An outgoing `ActionRecord` awaiting a choice of `Lot`s under `InventoryCostingMethod::SpecificIdentification`.
+#[derive(Clone, Debug)]
+pub struct Disposal {
+    pub tx_number: u32,
+    pub date: NaiveDate,
+    pub account_name: String,
+    pub ticker: String,
+    /// Positive, unlike the `ActionRecord` amount.
+    pub amount: Decimal,
+    pub proceeds: Decimal,
+    pub memo: String,
+    /// In the order the `Lot`s were created.
+    pub open_lots: Vec<OpenLot>,
+}
+
+/// The (positive) amount to draw from each chosen `Lot`, by `lot_number`.
+pub type LotAllocations = Vec<(u32, Decimal)>;
+
+/// Chooses which `Lot`s each `Disposal` is drawn from.  The amounts returned by `allocate()` must add up to the
+/// `Disposal`'s `amount`, and none may exceed its `Lot`'s `balance`.  Returning an error (e.g., because the user
+/// cancelled) stops processing.
+pub trait LotPicker {
+    fn allocate(&mut self, disposal: &Disposal) -> Result<LotAllocations, Box<dyn Error>>;
+}
+
+impl<F> LotPicker for F where F: FnMut(&Disposal) -> Result<LotAllocations, Box<dyn Error>> {
+    fn allocate(&mut self, disposal: &Disposal) -> Result<LotAllocations, Box<dyn Error>> {
+        self(disposal)
+    }
+}
+
+/// A `LotPicker` that reads the `Lot`s of each `Disposal` from a lot selections file, for processing without an
+/// interactive terminal.
+pub struct FileLotPicker {
+    allocations: HashMap<u32, LotAllocations>,
+}
+
+impl FileLotPicker {
+
+    /// Reads a lot selections file: a CSV of txn,lot,amount rows, after a header, where the txn is the number of the
+    /// disposal's `Transaction`, the lot is a lot number (or a lot ID, such as A2-L3) and the amount is the (positive)
+    /// amount drawn from it.  A disposal drawn from several lots has a row for each.
+    pub fn from_file(path: &Path) -> Result<FileLotPicker, Box<dyn Error>> {
+
+        let file = File::open(path)
+            .map_err(|e| format!("Lot selections file {} couldn't be opened: {}", path.display(), e))?;
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(file);
+
+        let mut allocations: HashMap<u32, LotAllocations> = HashMap::new();
+
+        for (idx, result) in rdr.records().enumerate() {
+
+            let record = result?;
+            let line = idx + 2;  //  Account for the header and one-based line numbers
+
+            let tx_number = record.get(0).unwrap_or("").trim().trim_start_matches('#').parse::<u32>().ok()
+                .filter(|tx_number| *tx_number > 0)
+                .ok_or_else(|| format!("Lot selections file line {} has an invalid transaction number.", line))?;
+
+            let lot = record.get(1).unwrap_or("").trim();
+            let lot_number = lot.rsplit_once("-L").map_or(lot, |(_acct, lot_number)| lot_number).parse::<u32>()
+                .map_err(|_| format!("Lot selections file line {} has an invalid lot (a lot number, or a lot ID \
+                    such as A2-L3).", line))?;
+
+            let amount = record.get(2).unwrap_or("").trim().parse::<Decimal>().ok().filter(|amt| *amt > dec!(0))
+                .ok_or_else(|| format!("Lot selections file line {} has an invalid amount (it must be above zero).", line))?;
+
+            allocations.entry(tx_number).or_default().push((lot_number, amount));
+        }
+
+        Ok(FileLotPicker { allocations })
+    }
+}
+
+impl LotPicker for FileLotPicker {
+    fn allocate(&mut self, disposal: &Disposal) -> Result<LotAllocations, Box<dyn Error>> {
+        self.allocations.remove(&disposal.tx_number).ok_or_else(|| format!(
+            "Txn {}: the lot selections file doesn't say which lots the {} {} disposed of from {} are drawn from.",
+            disposal.tx_number, disposal.amount, disposal.ticker, disposal.account_name).into())
+    }
+}
+
+/// Each chosen `Lot`, with the (negative) amount to post to it.
+pub(crate) type PickedLots = Vec<(Rc<Lot>, Decimal)>;
+
+/// Asks `lot_picker` which of `acct`'s `Lot`s to draw the outgoing `ar` from, and returns the chosen `Lot`s.
+/// Every `Lot` with a balance must already have its cost basis.
+pub(crate) fn pick_lots(
+    lot_picker: &mut dyn LotPicker,
+    txn: &Transaction,
+    ar: &ActionRecord,
+    raw_acct: &RawAccount,
+    acct: &Account,
+    long_term_days: i64,
+) -> Result<PickedLots, Box<dyn Error>> {
+
+    let amount = -ar.amount;
+    let proceeds = txn.proceeds_net_of_fee();
+    let price_per_unit = proceeds / amount;
+
+    let lots: Vec<Rc<Lot>> = acct.list_of_lots.borrow().iter()
+        .filter(|lot| lot.get_sum_of_amts_in_lot() > dec!(0))
+        .cloned()
+        .collect();
+
+    let open_lots = lots.iter().map(|lot| {
+        let balance = lot.get_sum_of_amts_in_lot();
+        let mvmts = lot.movements.borrow();
+        let first_mvmt = mvmts.first().expect("Lot has no movements.");
+        let cost_basis = round_d128_1e2(&(first_mvmt.cost_basis.get() * balance / first_mvmt.amount));
+        OpenLot {
+            lot_number: lot.lot_number,
+            basis_date: lot.date_for_basis_purposes,
+            term: lot.term(txn.date, long_term_days),
+            balance,
+            cost_basis,
+            unrealized_gain_or_loss: round_d128_1e2(&(balance * price_per_unit)) - cost_basis,
+        }
+    }).collect();
+
+    let disposal = Disposal {
+        tx_number: txn.tx_number,
+        date: txn.date,
+        account_name: raw_acct.name.clone(),
+        ticker: raw_acct.ticker.clone(),
+        amount,
+        proceeds,
+        memo: txn.user_memo.clone(),
+        open_lots,
+    };
+
+    let allocations = lot_picker.allocate(&disposal)?;
+
+    let mut total = dec!(0);
+    let mut picked: PickedLots = Vec::with_capacity(allocations.len());
+
+    for (lot_number, amt) in allocations {
+
+        let lot = match lots.iter().find(|lot| lot.lot_number == lot_number) {
+            Some(lot) => lot,
+            None => return Err(format!("Txn {}: lot {} of {} has no balance to dispose of.",
+                txn.tx_number, lot_number, raw_acct.name).into()),
+        };
+
+        if amt <= dec!(0) || amt > lot.get_sum_of_amts_in_lot() || picked.iter().any(|(l, _)| l.lot_number == lot_number) {
+            return Err(format!("Txn {}: cannot draw {} {} from lot {} of {}.",
+                txn.tx_number, amt, raw_acct.ticker, lot_number, raw_acct.name).into())
+        }
+
+        total += amt;
+        picked.push((lot.clone(), -amt));
+    }
+
+    if total != amount {
+        return Err(format!("Txn {}: the chosen lots add up to {} {}, but {} {} was disposed of.",
+            txn.tx_number, total, raw_acct.ticker, amount, raw_acct.ticker).into())
+    }
+
+    Ok(picked)
+}
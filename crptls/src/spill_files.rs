@@ -0,0 +1,180 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::core_functions::ImportProcessParameters;
+
+
+/// The size of the chunks the CSV Input File (and any spill file) is read in, with `low_memory`.
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// For a name no other spill file of this run has.
+static SPILL_FILES_CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether a CSV Input File has been decrypted (see `keep_decrypted_input_in_memory()`).
+static DECRYPTED_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Notes that a CSV Input File was decrypted, so that nothing is spilled from then on (see `may_spill()`): a spill
+/// file is plaintext, which would defeat encrypting the file.
+pub(crate) fn keep_decrypted_input_in_memory(settings: &ImportProcessParameters) {
+    if settings.low_memory && !DECRYPTED_INPUT.swap(true, Ordering::Relaxed) {
+        log::info!("  The CSV Input File was decrypted, so it's kept in memory rather than spilled to temporary files.");
+    }
+}
+
+/// Whether an intermediate CSV is to be spilled to a temporary file: with `low_memory`, unless a CSV Input File
+/// was decrypted.
+pub(crate) fn may_spill(settings: &ImportProcessParameters) -> bool {
+    settings.low_memory && !DECRYPTED_INPUT.load(Ordering::Relaxed)
+}
+
+/// A temporary file, removed once dropped.  Held by the `SpillWriter` writing it, then by the `SpillReader` reading
+/// it back.
+struct SpillFile {
+    path: PathBuf,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Writes an intermediate CSV (i.e., a merged CSV Input File) to a temporary file rather than to memory.  It keeps
+/// count of the bytes written, so that a row may later be read back from where it was written (see
+/// `SpillReader::read_at()`).  Flushing it leaves its last chunk buffered (it's written once full, or by
+/// `into_reader()`), so that a `csv::Writer` may be flushed after each row to count its bytes.
+pub(crate) struct SpillWriter {
+    spill: SpillFile,
+    file: BufWriter<File>,
+    len: u64,
+}
+
+impl SpillWriter {
+
+    /// A new, empty spill file in the temporary directory, its name beginning with `cryptools_spill_PURPOSE`.  On
+    /// Unix, only the user may read it.
+    pub(crate) fn create(purpose: &str) -> io::Result<SpillWriter> {
+
+        let path = std::env::temp_dir().join(format!(
+            "cryptools_spill_{}_{}_{}.csv",
+            purpose,
+            std::process::id(),
+            SPILL_FILES_CREATED.fetch_add(1, Ordering::Relaxed),
+        ));
+
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&path)?;
+
+        Ok(SpillWriter { spill: SpillFile { path }, file: BufWriter::with_capacity(CHUNK_SIZE, file), len: 0 })
+    }
+
+    /// How many bytes have been written.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Finishes writing, to read the file back from its start.
+    pub(crate) fn into_reader(self) -> io::Result<SpillReader> {
+
+        let SpillWriter { spill, file, len: _ } = self;
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(SpillReader { _spill: spill, file: BufReader::with_capacity(CHUNK_SIZE, file), pos: 0 })
+    }
+}
+
+impl Write for SpillWriter {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads back a spill file (in `CHUNK_SIZE` chunks), which is removed once this is dropped.
+pub(crate) struct SpillReader {
+    _spill: SpillFile,
+    file: BufReader<File>,
+    pos: u64,
+}
+
+impl SpillReader {
+
+    /// Fills `buf` from `offset` in the file.  Reading rows in the order they were written doesn't seek, so it's
+    /// read in chunks just as it would be from the start.
+    pub(crate) fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+
+        if offset != self.pos {
+            self.file.seek_relative(offset as i64 - self.pos as i64)?;
+        }
+
+        self.file.read_exact(buf)?;
+        self.pos = offset + buf.len() as u64;
+
+        Ok(())
+    }
+}
+
+impl Read for SpillReader {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+/// Where an intermediate CSV (i.e., a converted CSV Input File) is written as it's converted: to a temporary file
+/// with `low_memory` (unless a CSV Input File was decrypted, see `may_spill()`), so that it's never held in memory
+/// whole, or else to memory.
+pub(crate) enum IntermediateCsv {
+    Memory(Vec<u8>),
+    Spilled(SpillWriter),
+}
+
+impl IntermediateCsv {
+
+    /// A new, empty intermediate CSV, named `purpose` if it's spilled (see `SpillWriter::create()`).
+    pub(crate) fn create(settings: &ImportProcessParameters, purpose: &str) -> io::Result<IntermediateCsv> {
+        match may_spill(settings) {
+            true => Ok(IntermediateCsv::Spilled(SpillWriter::create(purpose)?)),
+            false => Ok(IntermediateCsv::Memory(Vec::new())),
+        }
+    }
+
+    /// Finishes writing, to read the CSV back from its start.
+    pub(crate) fn into_reader(self) -> io::Result<Box<dyn Read>> {
+        match self {
+            IntermediateCsv::Memory(bytes) => Ok(Box::new(Cursor::new(bytes))),
+            IntermediateCsv::Spilled(wtr) => Ok(Box::new(wtr.into_reader()?)),
+        }
+    }
+}
+
+impl Write for IntermediateCsv {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            IntermediateCsv::Memory(bytes) => bytes.write(buf),
+            IntermediateCsv::Spilled(wtr) => wtr.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Term};
+
+
+/// Reads a spot-price file into a map of ticker to home-currency price.  The file is a CSV with a header
+/// row followed by one `ticker,price` row per currency (i.e., `BTC,27000.00`).  Tickers are matched exactly
+/// against the tickers in the file_to_import.
+pub fn import_spot_prices(path: &Path) -> Result<HashMap<String, Decimal>, Box<dyn Error>> {
+
+    let file = File::open(path)?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut prices: HashMap<String, Decimal> = HashMap::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;  //  Account for the header and one-based row numbers
+
+        let ticker = match record.get(0) {
+            Some(t) if !t.is_empty() => t.to_string(),
+            _ => return Err(format!("Spot-price file row {} is missing a ticker.", row).into()),
+        };
+
+        let price = match record.get(1).map(|p| p.parse::<Decimal>()) {
+            Some(Ok(p)) if p >= dec!(0) => p,
+            _ => return Err(format!("Spot-price file row {} has an invalid price for {}.", row, ticker).into()),
+        };
+
+        if prices.insert(ticker.clone(), price).is_some() {
+            return Err(format!("Spot-price file lists {} more than once.", ticker).into())
+        }
+    }
+
+    Ok(prices)
+}
+
+/// Each ticker's latest price, with its date.
+pub type LatestPrices = HashMap<String, (NaiveDate, Decimal)>;
+
+/// The latest price of each ticker in `dated_prices` (as read by `price_lookup::import_prices()`), with its date, and
+/// the latest date of any price, as of which holdings are valued.  `None` if there are no prices.
+pub fn latest_prices(
+    dated_prices: &HashMap<(NaiveDate, String), Decimal>,
+) -> Option<(NaiveDate, LatestPrices)> {
+
+    let mut latest: LatestPrices = HashMap::new();
+
+    for ((date, ticker), price) in dated_prices.iter() {
+        match latest.get(ticker) {
+            Some((latest_date, _price)) if latest_date >= date => {}
+            _ => { latest.insert(ticker.clone(), (*date, *price)); }
+        }
+    }
+
+    let as_of_date = latest.values().map(|(date, _price)| *date).max()?;
+
+    Some((as_of_date, latest))
+}
+
+/// The prospective tax impact of disposing of the entire remaining balance of a single open `Lot` at spot.
+#[derive(Clone, Debug)]
+pub struct OpenLotImpact {
+    pub acct_key: u16,
+    pub lot_number: u32,
+    pub date_for_basis_purposes: NaiveDate,
+    pub amount: Decimal,
+    pub cost_basis: Decimal,
+    pub spot_price: Decimal,
+    pub market_value: Decimal,
+    pub term: Term,
+    /// Days remaining until the lot goes long-term (zero if it already is).
+    pub days_until_lt: i64,
+}
+
+impl OpenLotImpact {
+    pub fn unrealized_gain_or_loss(&self) -> Decimal {
+        self.market_value - self.cost_basis
+    }
+}
+
+/// Values every open lot of every non-margin, non-home currency `Account` as though it were sold on `as_of_date`
+/// at its spot price, using like-kind basis.  The results are sorted with the largest unrealized loss first (the
+/// best harvesting candidates) and the largest gain last.  Tickers without a spot price are returned separately
+/// so they can be reported rather than silently ignored.
+pub fn open_lot_impacts(
+    spot_prices: &HashMap<String, Decimal>,
+    as_of_date: NaiveDate,
+    home_currency: &str,
+    long_term_days: i64,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> (Vec<OpenLotImpact>, Vec<String>) {
+
+    let mut impacts: Vec<OpenLotImpact> = Vec::new();
+    let mut unpriced: Vec<String> = Vec::new();
+
+    for j in 1..=acct_map.len() {
+
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+        for lot in acct.list_of_lots.borrow().iter() {
+
+            let amount = lot.get_sum_of_amts_in_lot();
+            if amount <= dec!(0) { continue }
+
+            let spot_price = match spot_prices.get(&raw_acct.ticker) {
+                Some(price) => *price,
+                None => {
+                    if !unpriced.contains(&raw_acct.ticker) { unpriced.push(raw_acct.ticker.clone()) }
+                    continue
+                }
+            };
+
+            let term = lot.term(as_of_date, long_term_days);
+            let days_held = as_of_date.signed_duration_since(lot.date_for_basis_purposes).num_days();
+            let days_until_lt = if lot.inherited { 0 } else { (long_term_days + 1 - days_held).max(0) };
+
+            impacts.push(OpenLotImpact {
+                acct_key: j as u16,
+                lot_number: lot.lot_number,
+                date_for_basis_purposes: lot.date_for_basis_purposes,
+                amount,
+                cost_basis: lot.get_sum_of_lk_basis_in_lot(),
+                spot_price,
+                market_value: (amount * spot_price).round_dp(2),
+                term,
+                days_until_lt,
+            });
+        }
+    }
+
+    impacts.sort_by_key(|impact| impact.unrealized_gain_or_loss());
+
+    (impacts, unpriced)
+}
+
+/// Totals of the `OpenLotImpact`s of one prospective term.
+#[derive(Clone, Debug, Default)]
+pub struct TermBucket {
+    pub lots: u32,
+    pub amount: Decimal,
+    pub cost_basis: Decimal,
+    pub market_value: Decimal,
+}
+
+impl TermBucket {
+
+    pub fn unrealized_gain_or_loss(&self) -> Decimal {
+        self.market_value - self.cost_basis
+    }
+
+    fn add(&mut self, impact: &OpenLotImpact) {
+        self.lots += 1;
+        self.amount += impact.amount;
+        self.cost_basis += impact.cost_basis;
+        self.market_value += impact.market_value;
+    }
+}
+
+/// A currency's unrealized gains and losses, split by whether they'd currently be short- or long-term if sold.
+#[derive(Clone, Debug)]
+pub struct UnrealizedByTerm {
+    pub ticker: String,
+    pub st: TermBucket,
+    pub lt: TermBucket,
+}
+
+impl UnrealizedByTerm {
+    pub fn unrealized_gain_or_loss(&self) -> Decimal {
+        self.st.unrealized_gain_or_loss() + self.lt.unrealized_gain_or_loss()
+    }
+}
+
+/// Totals the `impacts` (see `open_lot_impacts()`) of each currency by prospective term, across all of that
+/// currency's `Account`s.  The results are sorted by ticker.
+pub fn unrealized_by_term(
+    impacts: &[OpenLotImpact],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Vec<UnrealizedByTerm> {
+
+    let mut currencies: Vec<UnrealizedByTerm> = Vec::new();
+
+    for impact in impacts.iter() {
+
+        let raw_acct = raw_acct_map.get(&acct_map.get(&impact.acct_key).unwrap().raw_key).unwrap();
+
+        let idx = match currencies.iter().position(|c| c.ticker == raw_acct.ticker) {
+            Some(idx) => idx,
+            None => {
+                currencies.push(UnrealizedByTerm {
+                    ticker: raw_acct.ticker.clone(),
+                    st: TermBucket::default(),
+                    lt: TermBucket::default(),
+                });
+                currencies.len() - 1
+            }
+        };
+
+        match impact.term {
+            Term::ST => currencies[idx].st.add(impact),
+            Term::LT => currencies[idx].lt.add(impact),
+        }
+    }
+
+    currencies.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+
+    currencies
+}
+
+/// An open lot at an unrealized loss (see `open_lot_impacts()`), and how much of the year's realized gains selling it
+/// today would offset.
+#[derive(Clone, Debug)]
+pub struct HarvestCandidate {
+    pub impact: OpenLotImpact,
+    /// The unrealized loss, as a positive amount.
+    pub harvestable_loss: Decimal,
+    /// The short-term realized gain the loss would offset.
+    pub offsets_st: Decimal,
+    /// The long-term realized gain the loss would offset.
+    pub offsets_lt: Decimal,
+}
+
+impl HarvestCandidate {
+
+    /// The part of the loss left once the realized gains are offset (which would be deducted from other income, up to a
+    /// limit, or carried forward).
+    pub fn loss_left_over(&self) -> Decimal {
+        self.harvestable_loss - self.offsets_st - self.offsets_lt
+    }
+}
+
+/// The `impacts` at an unrealized loss (see `open_lot_impacts()`), largest loss first, each with the part of the
+/// year's net realized gains (`realized_st` and `realized_lt`) its loss would offset, were the lots sold in that order.
+/// As when netting gains and losses for a tax return, a short-term loss offsets short-term gain first, then long-term
+/// gain, and a long-term loss offsets long-term gain first.
+pub fn harvest_candidates(impacts: &[OpenLotImpact], realized_st: Decimal, realized_lt: Decimal) -> Vec<HarvestCandidate> {
+
+    let mut gains_left_st = realized_st.max(dec!(0));
+    let mut gains_left_lt = realized_lt.max(dec!(0));
+
+    let mut losses: Vec<&OpenLotImpact> = impacts.iter().filter(|impact| impact.unrealized_gain_or_loss() < dec!(0)).collect();
+    losses.sort_by_key(|impact| impact.unrealized_gain_or_loss());
+
+    let mut candidates: Vec<HarvestCandidate> = Vec::new();
+
+    for impact in losses.into_iter() {
+
+        let harvestable_loss = -impact.unrealized_gain_or_loss();
+
+        let (same_term_gains, other_term_gains) = match impact.term {
+            Term::ST => (&mut gains_left_st, &mut gains_left_lt),
+            Term::LT => (&mut gains_left_lt, &mut gains_left_st),
+        };
+
+        let same_term_offset = harvestable_loss.min(*same_term_gains);
+        *same_term_gains -= same_term_offset;
+        let other_term_offset = (harvestable_loss - same_term_offset).min(*other_term_gains);
+        *other_term_gains -= other_term_offset;
+
+        let (offsets_st, offsets_lt) = match impact.term {
+            Term::ST => (same_term_offset, other_term_offset),
+            Term::LT => (other_term_offset, same_term_offset),
+        };
+
+        candidates.push(HarvestCandidate { impact: impact.clone(), harvestable_loss, offsets_st, offsets_lt });
+    }
+
+    candidates
+}
@@ -0,0 +1,50 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity};
+
+
+/// Whether `ticker` is one of the `stablecoins` treated as cash equivalents (see `apply_cash_equivalent_treatment()`).
+pub fn is_cash_equivalent(stablecoins: &[String], ticker: &str) -> bool {
+    stablecoins.iter().any(|coin| coin == ticker)
+}
+
+/// Replaces the proceeds of every disposal of a cash-equivalent stablecoin (from a non-margin `Account` whose ticker
+/// is one of the `stablecoins`), netting them against its cost basis, so no gain or loss is realized.  A stablecoin
+/// still has `Lot`s, so its basis carries through, and income received in one is still income.  This must follow
+/// any like-kind treatment, which may change the `cost_basis_lk`.  Returns the number of `Movement`s changed.
+pub(crate) fn apply_cash_equivalent_treatment(
+    stablecoins: &[String],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> usize {
+
+    let mut changed = 0;
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if ar.direction() != Polarity::Outgoing { continue }
+            if raw_acct.is_margin || !is_cash_equivalent(stablecoins, &raw_acct.ticker) { continue }
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+                mvmt.proceeds.set(-mvmt.cost_basis.get());
+                mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+                changed += 1;
+            }
+        }
+    }
+
+    changed
+}
@@ -0,0 +1,987 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Term};
+use crate::decimal_utils::round_d128_1e8;
+use crate::derivatives;
+use crate::transaction::{Transaction, ActionRecord, IncomeType, Polarity, TxKind, TxType};
+
+
+/// Headline figures for a fully processed set of `Transaction`s, using like-kind values throughout
+/// (which are the same as the original values unless like-kind treatment was applied).
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub txn_count: u32,
+    pub account_count: u32,
+    /// Every `Lot` created, including those since emptied.
+    pub lots_created: u32,
+    /// The dates of the first and last `Transaction`s (`None` if there are none).
+    pub date_range: Option<(NaiveDate, NaiveDate)>,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+    /// Realized gain or loss by the (fiscal) year of the disposal (see `fiscal_year()`), in order.
+    pub realized_by_year: Vec<YearGains>,
+    /// The proceeds of every disposal (positive).
+    pub proceeds: Decimal,
+    /// Zero or positive.
+    pub income: Decimal,
+    /// Zero or negative, consistent with `Movement::get_expense()`.
+    pub expense: Decimal,
+    /// The net realized profit or loss of cash-settled derivatives (see `derivatives`), which is neither income nor
+    /// an expense.
+    pub derivative_pnl: Decimal,
+    pub open_lots: u32,
+    /// Non-margin, non-home currency holdings by ticker, sorted by cost basis (largest first).
+    pub holdings: Vec<CurrencyHolding>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CurrencyHolding {
+    pub ticker: String,
+    pub amount: Decimal,
+    pub cost_basis: Decimal,
+    pub open_lots: u32,
+}
+
+impl RunSummary {
+
+    pub fn realized_total(&self) -> Decimal {
+        self.realized_st + self.realized_lt
+    }
+
+    /// Tallies gains, income and expenses the same way the movement summary report (C5) does, and current
+    /// holdings the same way the account sum reports (C1/C2) do.
+    pub fn from_maps(
+        home_currency: &str,
+        long_term_days: i64,
+        fiscal_year_start_month: u32,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<RunSummary, Box<dyn Error>> {
+
+        let mut summary = RunSummary {
+            txn_count: txns_map.len() as u32,
+            account_count: acct_map.len() as u32,
+            lots_created: 0,
+            date_range: None,
+            realized_st: dec!(0),
+            realized_lt: dec!(0),
+            realized_by_year: Vec::new(),
+            proceeds: dec!(0),
+            income: dec!(0),
+            expense: dec!(0),
+            derivative_pnl: dec!(0),
+            open_lots: 0,
+            holdings: Vec::new(),
+        };
+
+        for num in 1..=txns_map.len() {
+
+            let txn = txns_map.get(&(num as u32)).unwrap();
+            let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+            let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+            let pnl = derivatives::derivative_pnl(txn, home_currency, raw_acct_map, acct_map, ars);
+
+            if let Some(pnl) = &pnl { summary.derivative_pnl += pnl.pnl }
+
+            summary.date_range = match summary.date_range {
+                None => Some((txn.date, txn.date)),
+                Some((first, last)) => Some((first.min(txn.date), last.max(txn.date))),
+            };
+
+            for mvmt in mvmts.iter() {
+
+                let polarity = if mvmt.amount > dec!(0) { Polarity::Incoming } else { Polarity::Outgoing };
+
+                if tx_type == TxType::Flow && polarity == Polarity::Incoming {
+                    if pnl.is_none() { summary.income -= mvmt.proceeds_lk.get() }   //  Proceeds are negative for incoming txns
+                    continue
+                }
+
+                if tx_type == TxType::Flow && pnl.is_none() {
+                    summary.expense -= mvmt.proceeds_lk.get();
+                }
+
+                let year = match summary.realized_by_year.iter_mut().position(|year| year.year == fiscal_year(txn.date, fiscal_year_start_month)) {
+                    Some(idx) => &mut summary.realized_by_year[idx],
+                    None => {
+                        summary.realized_by_year.push(YearGains { year: fiscal_year(txn.date, fiscal_year_start_month), realized_st: dec!(0), realized_lt: dec!(0) });
+                        summary.realized_by_year.last_mut().unwrap()
+                    }
+                };
+
+                match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                    Term::ST => {
+                        summary.realized_st += mvmt.get_lk_gain_or_loss();
+                        year.realized_st += mvmt.get_lk_gain_or_loss();
+                    }
+                    Term::LT => {
+                        summary.realized_lt += mvmt.get_lk_gain_or_loss();
+                        year.realized_lt += mvmt.get_lk_gain_or_loss();
+                    }
+                }
+
+                summary.proceeds += mvmt.proceeds_lk.get();
+            }
+        }
+
+        summary.realized_by_year.sort_by_key(|year| year.year);
+
+        for j in 1..=acct_map.len() {
+
+            let acct = acct_map.get(&(j as u16)).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            summary.lots_created += acct.list_of_lots.borrow().len() as u32;
+
+            if raw_acct.is_margin { continue }
+
+            let open_lots = acct.get_num_of_nonzero_lots();
+            summary.open_lots += open_lots;
+
+            if raw_acct.is_home_currency(home_currency) || open_lots == 0 { continue }
+
+            let amount = acct.get_sum_of_amts_in_lots();
+            let cost_basis = acct.get_sum_of_lk_basis_in_lots();
+
+            match summary.holdings.iter_mut().find(|h| h.ticker == raw_acct.ticker) {
+                Some(holding) => {
+                    holding.amount += amount;
+                    holding.cost_basis += cost_basis;
+                    holding.open_lots += open_lots;
+                }
+                None => summary.holdings.push(CurrencyHolding {
+                    ticker: raw_acct.ticker.clone(),
+                    amount,
+                    cost_basis,
+                    open_lots,
+                }),
+            }
+        }
+
+        summary.holdings.sort_by_key(|holding| std::cmp::Reverse(holding.cost_basis));
+
+        Ok(summary)
+    }
+}
+
+/// Realized gains and losses on disposals from `Account`s sharing an exchange label (see `RawAccount::exchange_label()`).
+#[derive(Clone, Debug)]
+pub struct ExchangeGains {
+    pub label: String,
+    pub account_names: Vec<String>,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+}
+
+impl ExchangeGains {
+    pub fn realized_total(&self) -> Decimal {
+        self.realized_st + self.realized_lt
+    }
+}
+
+/// Groups realized gains and losses by the exchange label of the `Account` each disposal came out of.  Gains are
+/// tallied as in `RunSummary::from_maps()`, so the totals across all exchanges match the run's headline figures.
+/// Exchanges are sorted by total gain (largest first).
+pub fn gains_by_exchange(
+    home_currency: &str,
+    long_term_days: i64,
+    label_separator: Option<&str>,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<ExchangeGains>, Box<dyn Error>> {
+
+    let mut exchanges: Vec<ExchangeGains> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        for mvmt in mvmts.iter() {
+
+            if tx_type == TxType::Flow && mvmt.amount > dec!(0) { continue }   //  Income, not a disposal
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let raw_acct = raw_acct_map.get(&acct_map.get(&lot.account_key).unwrap().raw_key).unwrap();
+            let label = raw_acct.exchange_label(label_separator);
+
+            let idx = match exchanges.iter().position(|e| e.label == label) {
+                Some(idx) => idx,
+                None => {
+                    exchanges.push(ExchangeGains {
+                        label,
+                        account_names: Vec::new(),
+                        realized_st: dec!(0),
+                        realized_lt: dec!(0),
+                    });
+                    exchanges.len() - 1
+                }
+            };
+            let exchange = &mut exchanges[idx];
+
+            if !exchange.account_names.contains(&raw_acct.name) { exchange.account_names.push(raw_acct.name.clone()) }
+
+            match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                Term::ST => exchange.realized_st += mvmt.get_lk_gain_or_loss(),
+                Term::LT => exchange.realized_lt += mvmt.get_lk_gain_or_loss(),
+            }
+        }
+    }
+
+    exchanges.sort_by_key(|exchange| std::cmp::Reverse(exchange.realized_total()));
+
+    Ok(exchanges)
+}
+
+/// The realized gains and losses, income and fees of the `Account`s sharing an exchange label (see
+/// `RawAccount::exchange_label()`) in a single (fiscal) year, for reconciling against the exchange's own reporting
+/// (i.e., a Form 1099).
+#[derive(Clone, Debug)]
+pub struct ExchangeYearTotals {
+    pub year: i32,
+    pub label: String,
+    pub account_names: Vec<String>,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+    /// Received into its `Account`s.  Zero or positive.
+    pub income: Decimal,
+    /// Paid by the `Transaction`s out of its `Account`s (and split off of transfers out of them).  Zero or positive.
+    pub fees: Decimal,
+}
+
+impl ExchangeYearTotals {
+    pub fn realized_total(&self) -> Decimal {
+        self.realized_st + self.realized_lt
+    }
+}
+
+/// Groups realized gains and losses by the (fiscal) year of the disposal and the exchange label of the `Account` it
+/// came out of (as in `gains_by_exchange()`), income by the year and the label of the `Account` it was received into
+/// (tallied as in `RunSummary::from_maps()`), and fees by the year and the label of the `Account` of the
+/// `Transaction`'s outgoing quantity.  Sorted by year, then label.
+#[allow(clippy::too_many_arguments)]
+pub fn totals_by_exchange_and_year(
+    home_currency: &str,
+    long_term_days: i64,
+    fiscal_year_start_month: u32,
+    label_separator: Option<&str>,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<ExchangeYearTotals>, Box<dyn Error>> {
+
+    let mut totals: Vec<ExchangeYearTotals> = Vec::new();
+
+    let raw_acct_of = |account_key: u16| raw_acct_map.get(&acct_map.get(&account_key).unwrap().raw_key).unwrap();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let year = fiscal_year(txn.date, fiscal_year_start_month);
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        //  Income is received into the `Account` of its (only) `ActionRecord`
+        if let Some(received) = income_received(txn, home_currency, raw_acct_map, acct_map, ars, txns_map)? {
+            let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+            exchange_year_totals(&mut totals, year, raw_acct_of(ar.account_key), label_separator).income += received;
+        }
+
+        for mvmt in mvmts.iter() {
+
+            if tx_type == TxType::Flow && mvmt.amount > dec!(0) { continue }   //  Income, not a disposal
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let exchange = exchange_year_totals(&mut totals, year, raw_acct_of(lot.account_key), label_separator);
+
+            match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                Term::ST => exchange.realized_st += mvmt.get_lk_gain_or_loss(),
+                Term::LT => exchange.realized_lt += mvmt.get_lk_gain_or_loss(),
+            }
+        }
+
+        //  The outgoing `ActionRecord` is first; a transfer's fee is a `Transaction` of its own, whose proceeds are the fee
+        let fee = if txn.kind == TxKind::TransferFee { txn.proceeds } else { txn.fee };
+        if !fee.is_zero() {
+            let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+            exchange_year_totals(&mut totals, year, raw_acct_of(ar.account_key), label_separator).fees += fee;
+        }
+    }
+
+    totals.sort_by(|a, b| (a.year, &a.label).cmp(&(b.year, &b.label)));
+
+    Ok(totals)
+}
+
+/// The `totals` of the exchange label of `raw_acct` in `year`, added if there aren't any yet.
+fn exchange_year_totals<'a>(
+    totals: &'a mut Vec<ExchangeYearTotals>,
+    year: i32,
+    raw_acct: &RawAccount,
+    label_separator: Option<&str>,
+) -> &'a mut ExchangeYearTotals {
+
+    let label = raw_acct.exchange_label(label_separator);
+
+    let idx = match totals.iter().position(|t| t.year == year && t.label == label) {
+        Some(idx) => idx,
+        None => {
+            totals.push(ExchangeYearTotals {
+                year,
+                label,
+                account_names: Vec::new(),
+                realized_st: dec!(0),
+                realized_lt: dec!(0),
+                income: dec!(0),
+                fees: dec!(0),
+            });
+            totals.len() - 1
+        }
+    };
+
+    let exchange = &mut totals[idx];
+    if !exchange.account_names.contains(&raw_acct.name) { exchange.account_names.push(raw_acct.name.clone()) }
+
+    exchange
+}
+
+/// Realized gains and losses on disposals of a single currency in a single (fiscal) year.
+#[derive(Clone, Debug)]
+pub struct AssetYearGains {
+    pub year: i32,
+    pub ticker: String,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+}
+
+impl AssetYearGains {
+    pub fn realized_total(&self) -> Decimal {
+        self.realized_st + self.realized_lt
+    }
+}
+
+/// Groups realized gains and losses by the (fiscal) year of the disposal (see `fiscal_year()`) and the ticker disposed
+/// of.  Gains are tallied as in `RunSummary::from_maps()`, each as short-term or long-term by `long_term_days`.  Sorted
+/// by year, then ticker.
+pub fn gains_by_asset_and_year(
+    home_currency: &str,
+    long_term_days: i64,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<AssetYearGains>, Box<dyn Error>> {
+
+    let mut assets: Vec<AssetYearGains> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        for mvmt in mvmts.iter() {
+
+            if tx_type == TxType::Flow && mvmt.amount > dec!(0) { continue }   //  Income, not a disposal
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let raw_acct = raw_acct_map.get(&acct_map.get(&lot.account_key).unwrap().raw_key).unwrap();
+            let year = fiscal_year(txn.date, fiscal_year_start_month);
+
+            let idx = match assets.iter().position(|a| a.year == year && a.ticker == raw_acct.ticker) {
+                Some(idx) => idx,
+                None => {
+                    assets.push(AssetYearGains {
+                        year,
+                        ticker: raw_acct.ticker.clone(),
+                        realized_st: dec!(0),
+                        realized_lt: dec!(0),
+                    });
+                    assets.len() - 1
+                }
+            };
+            let asset = &mut assets[idx];
+
+            match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                Term::ST => asset.realized_st += mvmt.get_lk_gain_or_loss(),
+                Term::LT => asset.realized_lt += mvmt.get_lk_gain_or_loss(),
+            }
+        }
+    }
+
+    assets.sort_by(|a, b| (a.year, &a.ticker).cmp(&(b.year, &b.ticker)));
+
+    Ok(assets)
+}
+
+/// The acquisitions, disposals and holdings of a single currency over the whole history (across its non-margin
+/// `Account`s), for answering what its average cost basis is.  Values are like-kind values, as in `RunSummary`.
+/// Transfers between the user's own `Account`s are neither acquisitions nor disposals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetStatistics {
+    pub ticker: String,
+    /// The quantity received other than by transfer (bought, exchanged for or received as income).
+    pub acquired: Decimal,
+    /// The cost basis of what was `acquired`.
+    pub acquisition_cost: Decimal,
+    /// The quantity disposed of other than by transfer (positive).
+    pub disposed: Decimal,
+    /// The proceeds of what was `disposed` of.
+    pub proceeds: Decimal,
+    pub realized: Decimal,
+    pub holding: Decimal,
+    /// The remaining cost basis of the `holding`.
+    pub holding_basis: Decimal,
+}
+
+impl AssetStatistics {
+
+    /// The average cost of each unit acquired (`None` if none was).
+    pub fn average_acquisition_cost(&self) -> Option<Decimal> {
+        average(self.acquisition_cost, self.acquired)
+    }
+
+    /// The average proceeds of each unit disposed of (`None` if none was).
+    pub fn average_sale_price(&self) -> Option<Decimal> {
+        average(self.proceeds, self.disposed)
+    }
+
+    /// The average remaining basis of each unit held (`None` if none is).
+    pub fn average_holding_basis(&self) -> Option<Decimal> {
+        average(self.holding_basis, self.holding)
+    }
+}
+
+/// `total` divided by `quantity`, rounded to 8 decimal places (`None` if `quantity` is zero).
+fn average(total: Decimal, quantity: Decimal) -> Option<Decimal> {
+    if quantity.is_zero() { None } else { Some(round_d128_1e8(&(total / quantity))) }
+}
+
+/// The `AssetStatistics` of each currency other than the home currency, in ticker order.  Disposals and their gains
+/// are tallied as in `RunSummary::from_maps()`.
+pub fn asset_statistics(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<AssetStatistics>, Box<dyn Error>> {
+
+    let mut assets: Vec<AssetStatistics> = Vec::new();
+
+    fn asset<'a>(assets: &'a mut Vec<AssetStatistics>, ticker: &str) -> &'a mut AssetStatistics {
+        match assets.iter().position(|a| a.ticker == ticker) {
+            Some(idx) => &mut assets[idx],
+            None => {
+                assets.push(AssetStatistics {
+                    ticker: ticker.to_string(),
+                    acquired: dec!(0),
+                    acquisition_cost: dec!(0),
+                    disposed: dec!(0),
+                    proceeds: dec!(0),
+                    realized: dec!(0),
+                    holding: dec!(0),
+                    holding_basis: dec!(0),
+                });
+                assets.last_mut().unwrap()
+            }
+        }
+    }
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        for mvmt in mvmts.iter() {
+
+            if tx_type == TxType::Flow && mvmt.amount > dec!(0) { continue }   //  Income, not a disposal
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let raw_acct = raw_acct_map.get(&acct_map.get(&lot.account_key).unwrap().raw_key).unwrap();
+            let asset = asset(&mut assets, &raw_acct.ticker);
+
+            asset.disposed -= mvmt.amount;
+            asset.proceeds += mvmt.proceeds_lk.get();
+            asset.realized += mvmt.get_lk_gain_or_loss();
+        }
+    }
+
+    for j in 1..=acct_map.len() {
+
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+        let asset = asset(&mut assets, &raw_acct.ticker);
+
+        for lot in acct.list_of_lots.borrow().iter() {
+            for mvmt in lot.movements.borrow().iter().filter(|mvmt| mvmt.amount > dec!(0)) {
+
+                let txn = txns_map.get(&mvmt.transaction_key).unwrap();
+
+                if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+                asset.acquired += mvmt.amount;
+                asset.acquisition_cost += mvmt.cost_basis_lk.get();
+            }
+        }
+
+        asset.holding += acct.get_sum_of_amts_in_lots();
+        asset.holding_basis += acct.get_sum_of_lk_basis_in_lots();
+    }
+
+    assets.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+
+    Ok(assets)
+}
+
+/// Realized gains and losses on disposals of every currency in a single (fiscal) year.
+#[derive(Clone, Debug, PartialEq)]
+pub struct YearGains {
+    pub year: i32,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+}
+
+impl YearGains {
+    pub fn realized_total(&self) -> Decimal {
+        self.realized_st + self.realized_lt
+    }
+}
+
+/// Groups realized gains and losses by the year of the disposal, as in `gains_by_asset_and_year()`.  Sorted by year.
+pub fn gains_by_year(
+    home_currency: &str,
+    long_term_days: i64,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<YearGains>, Box<dyn Error>> {
+
+    let mut years: Vec<YearGains> = Vec::new();
+
+    for asset in gains_by_asset_and_year(home_currency, long_term_days, fiscal_year_start_month, raw_acct_map, acct_map, ars, txns_map)? {
+
+        //  Already sorted by year
+        match years.last_mut() {
+            Some(year) if year.year == asset.year => {
+                year.realized_st += asset.realized_st;
+                year.realized_lt += asset.realized_lt;
+            }
+            _ => years.push(YearGains { year: asset.year, realized_st: asset.realized_st, realized_lt: asset.realized_lt }),
+        }
+    }
+
+    Ok(years)
+}
+
+/// The income of a single kind (`None` for income without an `incomeType`) received in a single (fiscal) year.
+#[derive(Clone, Debug)]
+pub struct IncomeTypeTotal {
+    pub year: i32,
+    pub income_type: Option<IncomeType>,
+    /// Zero or positive.
+    pub income: Decimal,
+}
+
+/// Groups income by the year it was received and its `IncomeType`.  Income is tallied as in `RunSummary::from_maps()`.
+/// Sorted by year, then in the order of `IncomeType`, with untyped income last.
+pub fn income_by_type_and_year(
+    home_currency: &str,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<IncomeTypeTotal>, Box<dyn Error>> {
+
+    let mut totals: Vec<IncomeTypeTotal> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        let Some(income) = income_received(txn, home_currency, raw_acct_map, acct_map, ars, txns_map)? else { continue };
+
+        let year = fiscal_year(txn.date, fiscal_year_start_month);
+
+        match totals.iter_mut().find(|t| t.year == year && t.income_type == txn.income_type) {
+            Some(total) => total.income += income,
+            None => totals.push(IncomeTypeTotal { year, income_type: txn.income_type, income }),
+        }
+    }
+
+    totals.sort_by_key(|t| (t.year, t.income_type.is_none(), t.income_type));
+
+    Ok(totals)
+}
+
+/// The income received by an income `Transaction` (`None` for any other), tallied as in `RunSummary::from_maps()`.
+fn income_received(
+    txn: &Transaction,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Option<Decimal>, Box<dyn Error>> {
+
+    if txn.transaction_type(ars, raw_acct_map, acct_map)? != TxType::Flow { return Ok(None) }
+    if txn.kind == TxKind::DerivativePnl { return Ok(None) }
+
+    let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+    if mvmts.iter().all(|mvmt| mvmt.amount <= dec!(0)) { return Ok(None) }
+
+    Ok(Some(mvmts.iter()
+        .filter(|mvmt| mvmt.amount > dec!(0))
+        .map(|mvmt| -mvmt.proceeds_lk.get())  //  Proceeds are negative for incoming txns
+        .sum()))
+}
+
+/// The income paid by a single payer (`None` for income without one) in a single (fiscal) year, for reconciling
+/// against the payer's own reporting (i.e., a Form 1099).
+#[derive(Clone, Debug)]
+pub struct PayerIncomeTotal {
+    pub year: i32,
+    pub payer: Option<String>,
+    /// The number of income `Transaction`s.
+    pub payments: u32,
+    /// The fair market value when received.
+    pub income: Decimal,
+}
+
+/// Groups income by the year it was received and its `Transaction::payer`.  Income is tallied as in
+/// `income_by_type_and_year()`.  Sorted by year, then by payer, with income without a payer last.
+pub fn income_by_payer_and_year(
+    home_currency: &str,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<PayerIncomeTotal>, Box<dyn Error>> {
+
+    let mut totals: Vec<PayerIncomeTotal> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        let Some(income) = income_received(txn, home_currency, raw_acct_map, acct_map, ars, txns_map)? else { continue };
+
+        let year = fiscal_year(txn.date, fiscal_year_start_month);
+
+        match totals.iter_mut().find(|t| t.year == year && t.payer == txn.payer) {
+            Some(total) => { total.payments += 1; total.income += income }
+            None => totals.push(PayerIncomeTotal { year, payer: txn.payer.clone(), payments: 1, income }),
+        }
+    }
+
+    totals.sort_by(|a, b| (a.year, a.payer.is_none(), &a.payer).cmp(&(b.year, b.payer.is_none(), &b.payer)));
+
+    Ok(totals)
+}
+
+/// The expenses of a single category (`None` for uncategorized expenses) paid in a single (fiscal) year.
+#[derive(Clone, Debug)]
+pub struct ExpenseCategoryTotal {
+    pub year: i32,
+    pub category: Option<String>,
+    /// Zero or positive.
+    pub expense: Decimal,
+}
+
+/// Groups expenses by the year they were paid and their `Transaction::expense_category`.  Expenses are tallied as in
+/// the journal entries (see `Movement::get_expense()`), so they include gifts, donations and any transfer fees
+/// treated as expenses.  Sorted by year, then by category, with uncategorized expenses last.
+pub fn expenses_by_category_and_year(
+    home_currency: &str,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<ExpenseCategoryTotal>, Box<dyn Error>> {
+
+    let mut totals: Vec<ExpenseCategoryTotal> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? != TxType::Flow { continue }
+
+        let mut expense = dec!(0);
+
+        for mvmt in txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)? {
+            expense -= mvmt.get_expense(ars, raw_acct_map, acct_map, txns_map)?;  //  Expenses are negative
+        }
+
+        if expense.is_zero() { continue }
+
+        let year = fiscal_year(txn.date, fiscal_year_start_month);
+
+        match totals.iter_mut().find(|t| t.year == year && t.category == txn.expense_category) {
+            Some(total) => total.expense += expense,
+            None => totals.push(ExpenseCategoryTotal { year, category: txn.expense_category.clone(), expense }),
+        }
+    }
+
+    totals.sort_by(|a, b| (a.year, a.category.is_none(), &a.category).cmp(&(b.year, b.category.is_none(), &b.category)));
+
+    Ok(totals)
+}
+
+/// Realized gains and losses, and income, in a single quarter of a fiscal year, for estimating quarterly tax payments.
+#[derive(Clone, Debug)]
+pub struct QuarterTotals {
+    /// The fiscal year, named for the calendar year it begins in.
+    pub fiscal_year: i32,
+    /// 1 through 4.
+    pub quarter: u32,
+    /// The first day of the quarter.
+    pub start: NaiveDate,
+    /// The last day of the quarter.
+    pub end: NaiveDate,
+    pub realized_st: Decimal,
+    pub realized_lt: Decimal,
+    /// Zero or positive.
+    pub income: Decimal,
+}
+
+impl QuarterTotals {
+    pub fn total(&self) -> Decimal {
+        self.realized_st + self.realized_lt + self.income
+    }
+}
+
+/// The fiscal year of `date`, named for the calendar year it begins in, for a fiscal year beginning on the first day
+/// of `fiscal_year_start_month` (1 for a calendar year, in which case it's the calendar year).
+pub fn fiscal_year(date: NaiveDate, fiscal_year_start_month: u32) -> i32 {
+    if date.month() >= fiscal_year_start_month { date.year() } else { date.year() - 1 }
+}
+
+/// The first and last days of `fiscal_year` (see `fiscal_year()`), i.e., 2023-07-01 and 2024-06-30 for 2023, with a
+/// fiscal year beginning in July.
+pub fn fiscal_year_dates(fiscal_year: i32, fiscal_year_start_month: u32) -> (NaiveDate, NaiveDate) {
+    (quarter_dates(fiscal_year, 1, fiscal_year_start_month).0, quarter_dates(fiscal_year, 4, fiscal_year_start_month).1)
+}
+
+/// Parses the `FISCAL_YEAR_END` setting, the month and day (`MM-DD`, i.e., `06-30`) the fiscal year ends on, into the
+/// month the fiscal year begins in.  The fiscal year must end on the last day of a month (or on 02-28).
+pub fn parse_fiscal_year_end(value: &str) -> Result<u32, Box<dyn Error>> {
+
+    let invalid = || format!("The fiscal year end `{}` must be the last day of a month, as MM-DD (i.e., 06-30).", value);
+
+    let (month, day) = value.trim().split_once('-').ok_or_else(invalid)?;
+    let (month, day) = (month.parse::<u32>().map_err(|_| invalid())?, day.parse::<u32>().map_err(|_| invalid())?);
+
+    //  A non-leap year, so that February ends on the 28th
+    let date = NaiveDate::from_ymd_opt(2023, month, day).ok_or_else(invalid)?;
+    if date.succ_opt().is_none_or(|next| next.month() == month) { return Err(invalid().into()) }
+
+    Ok(month % 12 + 1)
+}
+
+/// The fiscal year (named for the calendar year it begins in) and quarter (1 through 4) of `date`, for a fiscal year
+/// beginning on the first day of `fiscal_year_start_month` (1 for a calendar year).
+pub fn fiscal_quarter(date: NaiveDate, fiscal_year_start_month: u32) -> (i32, u32) {
+
+    let months_in = (date.month() + 12 - fiscal_year_start_month) % 12;
+
+    (fiscal_year(date, fiscal_year_start_month), months_in / 3 + 1)
+}
+
+/// The first and last days of `quarter` of `fiscal_year` (see `fiscal_quarter()`).
+fn quarter_dates(fiscal_year: i32, quarter: u32, fiscal_year_start_month: u32) -> (NaiveDate, NaiveDate) {
+
+    let first_day = |months_in: u32| {
+        let month0 = fiscal_year_start_month - 1 + months_in;
+        NaiveDate::from_ymd_opt(fiscal_year + (month0 / 12) as i32, month0 % 12 + 1, 1).unwrap()
+    };
+
+    let start = first_day((quarter - 1) * 3);
+    let end = first_day(quarter * 3).pred_opt().unwrap();
+
+    (start, end)
+}
+
+/// Groups realized gains and losses (tallied as in `gains_by_asset_and_year()`) and income (as in
+/// `income_by_type_and_year()`) by the fiscal quarter they were realized or received in (see `fiscal_quarter()`).
+/// Every quarter of each fiscal year with any is listed, in order, so a quarter without any is there with zeros.
+pub fn gains_and_income_by_quarter(
+    home_currency: &str,
+    long_term_days: i64,
+    fiscal_year_start_month: u32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<QuarterTotals>, Box<dyn Error>> {
+
+    let mut quarters: Vec<QuarterTotals> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        if mvmts.is_empty() { continue }
+
+        let (fiscal_year, quarter) = fiscal_quarter(txn.date, fiscal_year_start_month);
+
+        //  The whole fiscal year is listed, once it has anything in it
+        if !quarters.iter().any(|q| q.fiscal_year == fiscal_year) {
+            for quarter in 1..=4 {
+                let (start, end) = quarter_dates(fiscal_year, quarter, fiscal_year_start_month);
+                quarters.push(QuarterTotals {
+                    fiscal_year,
+                    quarter,
+                    start,
+                    end,
+                    realized_st: dec!(0),
+                    realized_lt: dec!(0),
+                    income: dec!(0),
+                });
+            }
+        }
+
+        let totals = quarters.iter_mut().find(|q| q.fiscal_year == fiscal_year && q.quarter == quarter).unwrap();
+
+        for mvmt in mvmts.iter() {
+
+            if tx_type == TxType::Flow && mvmt.amount > dec!(0) {
+                if txn.kind != TxKind::DerivativePnl {
+                    totals.income -= mvmt.proceeds_lk.get();  //  Proceeds are negative for incoming txns
+                }
+                continue
+            }
+
+            match mvmt.get_term(acct_map, ars, txns_map, long_term_days) {
+                Term::ST => totals.realized_st += mvmt.get_lk_gain_or_loss(),
+                Term::LT => totals.realized_lt += mvmt.get_lk_gain_or_loss(),
+            }
+        }
+    }
+
+    quarters.sort_by_key(|q| q.start);
+
+    Ok(quarters)
+}
+
+/// Monthly series from the first `Transaction`'s month through the last's, as charted by the print menu and the HTML
+/// dashboard.  Values are like-kind values, as in `RunSummary`.
+#[derive(Clone, Debug)]
+pub struct MonthlySeries {
+    /// Each month, as (year, month).
+    pub months: Vec<(i32, u32)>,
+    /// By ticker (in ticker order), the amount held (across non-margin accounts) at the end of each month.
+    pub holdings: Vec<(String, Vec<Decimal>)>,
+    /// The realized gain/loss through the end of each month.
+    pub cumulative_realized: Vec<Decimal>,
+    /// The income of each month.
+    pub income: Vec<Decimal>,
+}
+
+impl MonthlySeries {
+
+    pub fn from_maps(
+        home_currency: &str,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<MonthlySeries, Box<dyn Error>> {
+
+        let mut charts = MonthlySeries { months: Vec::new(), holdings: Vec::new(), cumulative_realized: Vec::new(), income: Vec::new() };
+
+        let (first, last) = match (txns_map.get(&1), txns_map.get(&(txns_map.len() as u32))) {
+            (Some(first), Some(last)) => (first.date, last.date),
+            _ => return Ok(charts),
+        };
+
+        let (mut year, mut month) = (first.year(), first.month());
+
+        while (year, month) <= (last.year(), last.month()) {
+            charts.months.push((year, month));
+            (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+        }
+
+        let month_idx = |date: NaiveDate| charts.months.iter().position(|m| *m == (date.year(), date.month())).unwrap();
+
+        let mut changes: HashMap<String, Vec<Decimal>> = HashMap::new();
+
+        for j in 1..=acct_map.len() {
+
+            let acct = acct_map.get(&(j as u16)).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            if raw_acct.is_margin { continue }
+
+            let by_month = changes.entry(raw_acct.ticker.clone()).or_insert_with(|| vec![dec!(0); charts.months.len()]);
+
+            for lot in acct.list_of_lots.borrow().iter() {
+                for mvmt in lot.movements.borrow().iter() {
+                    // A carried-forward lot may be dated before the first transaction
+                    let idx = if mvmt.date < first { 0 } else { month_idx(mvmt.date) };
+                    by_month[idx] += mvmt.amount;
+                }
+            }
+        }
+
+        for (ticker, by_month) in changes.into_iter() {
+            let mut held = dec!(0);
+            charts.holdings.push((ticker, by_month.iter().map(|change| { held += *change; held }).collect()));
+        }
+
+        charts.holdings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut realized = vec![dec!(0); charts.months.len()];
+        charts.income = vec![dec!(0); charts.months.len()];
+
+        // As in `RunSummary::from_maps()`
+        for num in 1..=txns_map.len() {
+
+            let txn = txns_map.get(&(num as u32)).unwrap();
+            let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+            let idx = month_idx(txn.date);
+
+            for mvmt in txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?.iter() {
+
+                if tx_type == TxType::Flow && mvmt.amount > dec!(0) {
+                    charts.income[idx] -= mvmt.proceeds_lk.get();
+                    continue
+                }
+
+                realized[idx] += mvmt.get_lk_gain_or_loss();
+            }
+        }
+
+        let mut total = dec!(0);
+        charts.cumulative_realized = realized.iter().map(|gain| { total += *gain; total }).collect();
+
+        Ok(charts)
+    }
+}
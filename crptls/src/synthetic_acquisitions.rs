@@ -0,0 +1,169 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{self, Transaction, ActionRecord, Polarity, TxKind};
+
+
+/// How a synthetic acquisition is valued.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyntheticBasis {
+    /// No basis at all, which is the most conservative assumption (the entire proceeds of the disposal are gain).
+    Zero,
+    /// The value implied by the `proceeds` of the `Transaction` that would otherwise have caused the negative balance.
+    Market,
+}
+
+/// An outgoing `ActionRecord` that would take a non-margin, non-home currency `Account` negative.
+#[derive(Clone, Debug)]
+pub struct Shortfall {
+    pub tx_num: u32,
+    pub date_as_string: String,
+    pub memo: String,
+    pub account_key: u16,
+    /// Negative.
+    pub amount: Decimal,
+    /// Positive.  How much more was spent than the `Account` held.
+    pub shortfall: Decimal,
+}
+
+/// Lists every `Shortfall`, in `Transaction` order, with each `Account`'s running balance back at zero after each
+/// one (as though it had been covered), so that a single bad row isn't reported again on every later disposal.
+pub(crate) fn find_negative_balances(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Vec<Shortfall> {
+
+    let mut balances: HashMap<u16, Decimal> = HashMap::new();
+    let mut shortfalls: Vec<Shortfall> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            let balance = balances.entry(ar.account_key).or_insert(dec!(0));
+
+            if ar.direction() == Polarity::Outgoing && *balance + ar.amount < dec!(0) {
+
+                shortfalls.push(Shortfall {
+                    tx_num: num as u32,
+                    date_as_string: txn.date_as_string.to_string(),
+                    memo: txn.user_memo.clone(),
+                    account_key: ar.account_key,
+                    amount: ar.amount,
+                    shortfall: -(*balance + ar.amount),
+                });
+
+                *balance = -ar.amount;
+            }
+
+            *balance += ar.amount;
+        }
+    }
+
+    shortfalls
+}
+
+/// Inserts a `TxKind::Synthetic` acquisition `Transaction` immediately before any `Transaction` that would otherwise
+/// take a non-margin, non-home currency `Account` negative, covering exactly the shortfall.  This lets a run with a
+/// knowingly incomplete history complete.  Each synthetic `Transaction` is a single incoming `ActionRecord` dated the
+/// same day as the `Transaction` it covers, and it is renumbered into place, so that `Transaction` ends up one higher.
+/// Returns the number of synthetic `Transaction`s added.
+pub(crate) fn cover_negative_balances(
+    basis: SyntheticBasis,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> usize {
+
+    let mut balances: HashMap<u16, Decimal> = HashMap::new();
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut added = 0;
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.remove(&(num as u32)).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ar_map.get(ar_num).unwrap();
+            let (account_key, amount, polarity) = (ar.account_key, ar.amount, ar.direction());
+            let raw_acct = raw_acct_map.get(&acct_map.get(&account_key).unwrap().raw_key).unwrap();
+
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+            let balance = balances.entry(account_key).or_insert(dec!(0));
+
+            if polarity == Polarity::Outgoing && *balance + amount < dec!(0) {
+
+                let shortfall = -(*balance + amount);
+
+                let synthetic_basis = match basis {
+                    SyntheticBasis::Zero => dec!(0),
+                    SyntheticBasis::Market => {
+                        let txn_proceeds = txn.proceeds;
+                        (txn_proceeds * shortfall / -amount).round_dp(2)
+                    }
+                };
+
+                let synthetic_ar_num = ar_map.len() as u32 + 1;
+
+                ar_map.insert(synthetic_ar_num, ActionRecord {
+                    account_key,
+                    amount: shortfall,
+                    tx_key: 0,  //  Set when renumbering, below
+                    self_ar_key: synthetic_ar_num,
+                    movements: RefCell::new([].to_vec()),
+                });
+
+                ordered_txns.push(Transaction {
+                    tx_number: 0,   //  Set when renumbering, below
+                    source_row: None,
+                    date_as_string: txn.date_as_string.clone(),
+                    date: txn.date,
+                    date_for_basis_purposes: txn.date,
+                    time: None,
+                    sequence: None,
+                    user_memo: format!("Synthetic acquisition covering {} {} shortfall", shortfall, raw_acct.ticker),
+                    proceeds: synthetic_basis,
+                    fee: dec!(0),
+                    income_type: None,
+                    tags: Vec::new(),
+                    expense_category: None,
+                    payer: None,
+                    action_record_idx_vec: vec![synthetic_ar_num],
+                    kind: TxKind::Synthetic,
+                });
+
+                *balance += shortfall;
+                added += 1;
+            }
+
+            *balance += amount;
+        }
+
+        ordered_txns.push(txn);
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    added
+}
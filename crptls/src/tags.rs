@@ -0,0 +1,170 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, TxKind, TxType};
+
+
+/// A label on a `Transaction`, from a `#name` or `#name:value` word of its memo (i.e., `#staking` or
+/// `#payment:invoice42`) or from the optional `tags` column of the CSV Input File, by which the reports may be
+/// filtered (see `ReportFilter::tag`) and grouped (C32).
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Tag {
+    /// In lowercase, so that `#Staking` and `#staking` are the same tag.
+    pub name: String,
+    /// What follows the `:`, if anything (i.e., an invoice number), as written.
+    pub value: Option<String>,
+}
+
+impl Tag {
+
+    /// Whether `self` is the `filter`, or, for a `filter` without a value, any tag of its name.
+    pub fn matches(&self, filter: &Tag) -> bool {
+        self.name == filter.name && filter.value.as_ref().is_none_or(|value| self.value.as_ref() == Some(value))
+    }
+}
+
+impl fmt::Display for Tag {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "#{}:{}", self.name, value),
+            None => write!(f, "#{}", self.name),
+        }
+    }
+}
+
+impl FromStr for Tag {
+
+    type Err = String;
+
+    /// Parses a tag, with or without its `#` (i.e., `payment:invoice42`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_tag(s.trim().trim_start_matches('#'))
+            .ok_or_else(|| format!("The tag ({}) must be a name (of letters, numbers, `-` and `_`), optionally followed by \
+                `:` and a value (i.e., payment:invoice42).", s))
+    }
+}
+
+/// The tag of `word` (without its `#`), with any punctuation ending a sentence trimmed from it, or `None` if it
+/// isn't one.
+fn parse_tag(word: &str) -> Option<Tag> {
+
+    let word = word.trim_end_matches(['.', ',', ';', '!', '?', ')']);
+    let (name, value) = match word.split_once(':') {
+        Some((name, value)) => (name, Some(value).filter(|value| !value.is_empty())),
+        None => (word, None),
+    };
+
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') { return None }
+
+    Some(Tag { name: name.to_lowercase(), value: value.map(str::to_string) })
+}
+
+/// The tags of a memo: each word beginning with `#`, in order, each once.
+pub fn tags_in_memo(memo: &str) -> Vec<Tag> {
+
+    let mut tags: Vec<Tag> = Vec::new();
+
+    for tag in memo.split_whitespace().filter_map(|word| word.strip_prefix('#')).filter_map(parse_tag) {
+        if !tags.contains(&tag) { tags.push(tag) }
+    }
+
+    tags
+}
+
+/// The tags of a field of the `tags` column: separated by spaces or commas, each with or without its `#`.
+pub fn tags_in_column(field: &str) -> Result<Vec<Tag>, Box<dyn Error>> {
+
+    let mut tags: Vec<Tag> = Vec::new();
+
+    for word in field.split([' ', ',']).filter(|word| !word.trim().is_empty()) {
+        let tag = word.parse::<Tag>()?;
+        if !tags.contains(&tag) { tags.push(tag) }
+    }
+
+    Ok(tags)
+}
+
+/// A label of a `Transaction` that may be given by a column or a tag, such as the expense category of a spend: its
+/// `field` of that column (see the CSV Input File), unless that's blank, and otherwise the value of its first tag of
+/// that `name` (i.e., `meals`, for `#expense:meals`), if any.
+pub fn column_or_tag(field: &str, tags: &[Tag], name: &str) -> Option<String> {
+
+    if !field.trim().is_empty() { return Some(field.trim().to_string()) }
+
+    tags.iter().find(|tag| tag.name == name).and_then(|tag| tag.value.clone())
+}
+
+/// A tagged `Transaction`, for the report of each tag's transactions (C32).
+#[derive(Clone, Debug)]
+pub struct TaggedTransaction {
+    pub tag: Tag,
+    pub tx_num: u32,
+    pub date: NaiveDate,
+    pub memo: String,
+    pub proceeds: Decimal,
+    /// The value of what an income `Transaction` received (zero for any other).
+    pub income: Decimal,
+    /// The gain or loss realized on what it disposed of, if anything.
+    pub realized: Decimal,
+}
+
+/// Each tagged `Transaction`, once for each of its tags, sorted by tag and then in `Transaction` order.  Income and
+/// gains are tallied as in `summary::income_by_type_and_year()` and `summary::gains_by_asset_and_year()`.
+pub fn tagged_transactions(
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<TaggedTransaction>, Box<dyn Error>> {
+
+    let mut tagged: Vec<TaggedTransaction> = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        if txn.tags.is_empty() { continue }
+
+        //  A derivative's profit isn't income (see `derivatives`)
+        let is_income = txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::Flow && txn.kind != TxKind::DerivativePnl;
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+        let (mut income, mut realized) = (dec!(0), dec!(0));
+
+        for mvmt in mvmts.iter() {
+            match is_income && mvmt.amount > dec!(0) {
+                true => income += -mvmt.proceeds_lk.get(),  //  Proceeds are negative for incoming txns
+                false => realized += mvmt.get_lk_gain_or_loss(),
+            }
+        }
+
+        for tag in txn.tags.iter() {
+            tagged.push(TaggedTransaction {
+                tag: tag.clone(),
+                tx_num: txn.tx_number,
+                date: txn.date,
+                memo: txn.user_memo.clone(),
+                proceeds: txn.proceeds,
+                income,
+                realized,
+            });
+        }
+    }
+
+    tagged.sort_by(|a, b| (&a.tag, a.tx_num).cmp(&(&b.tag, b.tx_num)));
+
+    Ok(tagged)
+}
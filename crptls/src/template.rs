@@ -0,0 +1,91 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::io::Write;
+
+use chrono::NaiveDate;
+
+
+/// An account column of a template: a currency held at an exchange or in a wallet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateAccount {
+    /// The exchange or wallet (i.e., `Kraken` or `Ledger`).
+    pub name: String,
+    pub ticker: String,
+    pub is_margin: bool,
+}
+
+/// A CSV Input File to start from, for a new user without one: the four header rows, with a `Bank` account in the
+/// home currency and then an account column of each of the `accounts`, and a few sample rows showing how a buy, a
+/// transfer, a trade and a sale are entered, to be replaced with one's own transactions.
+#[derive(Clone, Debug)]
+pub struct Template {
+    pub home_currency: String,
+    /// A chrono strftime format string for the `txDate` (i.e., `%Y-%m-%d`), which should match the settings the file
+    /// will be imported with.
+    pub date_format: String,
+    /// At least one.
+    pub accounts: Vec<TemplateAccount>,
+}
+
+impl Template {
+
+    /// Writes the template to `wtr`.  The sample rows buy 2 of the first account's currency with the home currency,
+    /// move 1 of it to the next account of the same currency (if there is one), trade 0.5 of it for another currency
+    /// (if there is one) and sell 0.5 of it, so that no account but the `Bank` goes negative.  Each memo starts with
+    /// `Sample`.
+    pub fn write(&self, wtr: impl Write) -> Result<(), Box<dyn Error>> {
+
+        let Some(first) = self.accounts.first() else { return Err("The template needs at least one account.".into()) };
+
+        let bank = TemplateAccount { name: "Bank".to_string(), ticker: self.home_currency.clone(), is_margin: false };
+        let accts: Vec<&TemplateAccount> = std::iter::once(&bank).chain(self.accounts.iter()).collect();
+
+        let mut csv_wtr = csv::Writer::from_writer(wtr);
+
+        let header = |first: [&str; 3], field: &dyn Fn(usize, &TemplateAccount) -> String| -> Vec<String> {
+            let mut header: Vec<String> = first.iter().map(|f| f.to_string()).collect();
+            header.extend(accts.iter().enumerate().map(|(idx, acct)| field(idx, acct)));
+            header
+        };
+        csv_wtr.write_record(header(["txDate", "proceeds", "memo"], &|idx, _| (idx + 1).to_string()))?;
+        csv_wtr.write_record(header(["", "", ""], &|_, acct| acct.name.clone()))?;
+        csv_wtr.write_record(header(["", "", ""], &|_, acct| acct.ticker.clone()))?;
+        csv_wtr.write_record(header(["", "", ""], &|_, acct| if acct.is_margin { "yes".to_string() } else { "no".to_string() }))?;
+
+        //  The account columns after the Bank's (1) of another account of the first currency, and of another currency
+        let transfer_to = self.accounts.iter().skip(1).position(|acct| acct.ticker == first.ticker).map(|idx| idx + 2);
+        let traded_for = self.accounts.iter().position(|acct| acct.ticker != first.ticker).map(|idx| idx + 1);
+
+        let mut samples = vec![
+            (1, "0", format!("Sample: buy {} (replace these rows with your own)", first.ticker), vec![(0, "-1000.00"), (1, "2")]),
+        ];
+        if let Some(to) = transfer_to {
+            samples.push((2, "0", format!("Sample: move {} to {}", first.ticker, accts[to].name), vec![(1, "-1"), (to, "1")]));
+        }
+        if let Some(traded_for) = traded_for {
+            let acct = accts[traded_for];
+            samples.push((3, "300.00", format!("Sample: trade {} for {} (proceeds are the value received)", first.ticker,
+                acct.ticker), vec![(1, "-0.5"), (traded_for, "10")]));
+        }
+        samples.push((4, "400.00", format!("Sample: sell {}", first.ticker), vec![(1, "-0.5"), (0, "400.00")]));
+
+        for (month, proceeds, memo, legs) in samples {
+
+            let mut row = vec![String::new(); 3 + accts.len()];
+            row[0] = NaiveDate::from_ymd_opt(2023, month, 15).unwrap().format(&self.date_format).to_string();
+            row[1] = proceeds.to_string();
+            row[2] = memo;
+            for (acct_idx, amount) in legs {
+                row[3 + acct_idx] = amount.to_string();
+            }
+
+            csv_wtr.write_record(&row)?;
+        }
+
+        csv_wtr.flush()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::io::Write;
+
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+
+
+/// The tickers of the generated accounts after the home currency's, in order.
+const TICKERS: [&str; 8] = ["BTC", "ETH", "XMR", "LTC", "ADA", "DOT", "SOL", "XRP"];
+
+/// A CSV Input File of made-up transactions, for measuring performance or for a repro case that shares nothing of
+/// one's own history.  The same `TestData` always generates the same file.
+#[derive(Clone)]
+pub struct TestData {
+    /// The number of transaction rows.
+    pub rows: usize,
+    /// The number of accounts (at least 2): a `Bank` account in the `home_currency`, then an `Exchange` and a
+    /// `Wallet` account of each of BTC, ETH, XMR and so on, in turn.
+    pub accounts: usize,
+    /// Picks the transactions, quantities and prices.
+    pub seed: u64,
+    pub home_currency: String,
+    /// A chrono strftime format string for the `txDate` (i.e., `%m-%d-%y`), which should match the settings the file
+    /// will be imported with.
+    pub date_format: String,
+}
+
+impl Default for TestData {
+    fn default() -> Self {
+        TestData {
+            rows: 1000,
+            accounts: 4,
+            seed: 1,
+            home_currency: "USD".to_string(),
+            date_format: "%m-%d-%y".to_string(),
+        }
+    }
+}
+
+/// An account of the generated file.
+struct TestAccount {
+    name: String,
+    ticker: &'static str,
+    /// In units of 1e-8.
+    balance: u64,
+}
+
+impl TestData {
+
+    /// Writes the file to `wtr`: buys with and sales for the home currency, trades, and transfers between a
+    /// currency's exchange and wallet accounts, spread over ten years from 2017, with none of the accounts going
+    /// negative (except the `Bank`, which pays for the buys).  The quantities have at most eight decimal places, and
+    /// the home currency amounts two.
+    pub fn write(&self, wtr: impl Write) -> Result<(), Box<dyn Error>> {
+
+        if self.accounts < 2 { return Err("The test data needs at least 2 accounts.".into()) }
+
+        let mut accts: Vec<TestAccount> = vec![TestAccount { name: "Bank".to_string(), ticker: "", balance: 0 }];
+        for idx in 0..self.accounts - 1 {
+            let round = idx / (TICKERS.len() * 2);
+            let name = match (idx % 2, round) {
+                (0, 0) => "Exchange".to_string(),
+                (_, 0) => "Wallet".to_string(),
+                (0, _) => format!("Exchange {}", round + 1),
+                (_, _) => format!("Wallet {}", round + 1),
+            };
+            accts.push(TestAccount { name, ticker: TICKERS[(idx / 2) % TICKERS.len()], balance: 0 });
+        }
+
+        //  Cents per whole unit of each ticker, which wander (between a tenth and ten times where they start) as the
+        //  transactions are generated
+        let first_prices: Vec<u64> = (0..TICKERS.len()).map(|idx| 1_000_000 >> idx).collect();
+        let mut prices = first_prices.clone();
+        let mut rng = Rng::new(self.seed);
+        let first_date = NaiveDate::from_ymd_opt(2017, 1, 1).unwrap();
+
+        let mut csv_wtr = csv::Writer::from_writer(wtr);
+
+        let header = |first: &str, field: &dyn Fn(usize, &TestAccount) -> String| -> Vec<String> {
+            let mut header = vec![first.to_string(), "".to_string(), "".to_string()];
+            if !first.is_empty() { header[1] = "proceeds".to_string(); header[2] = "memo".to_string(); }
+            header.extend(accts.iter().enumerate().map(|(idx, acct)| field(idx, acct)));
+            header
+        };
+        csv_wtr.write_record(header("txDate", &|idx, _| (idx + 1).to_string()))?;
+        csv_wtr.write_record(header("", &|_, acct| acct.name.clone()))?;
+        csv_wtr.write_record(header("", &|_, acct| if acct.ticker.is_empty() { self.home_currency.clone() } else { acct.ticker.to_string() }))?;
+        csv_wtr.write_record(header("", &|_, _| "no".to_string()))?;
+
+        let mut row: Vec<String> = vec![String::new(); 3 + accts.len()];
+
+        for txn in 0..self.rows {
+
+            let date = first_date + Duration::days((txn as u64 * 3650 / self.rows as u64) as i64);
+            let moved = rng.below(TICKERS.len() as u64) as usize;
+            prices[moved] = (prices[moved] * (980 + rng.below(41)) / 1000)
+                .clamp(first_prices[moved] / 10, first_prices[moved] * 10);
+
+            //  Something to dispose of, if any (i.e., not on the first row)
+            let holding: Vec<usize> = (1..accts.len()).filter(|idx| accts[*idx].balance > 0).collect();
+            let kind = if holding.is_empty() { 0 } else { rng.below(20) };
+            let from = if holding.is_empty() { 0 } else { holding[rng.below(holding.len() as u64) as usize] };
+            let exchange = 1 + 2 * rng.below(accts.len() as u64 / 2) as usize;
+
+            let (memo, proceeds, legs) = match kind {
+                //  A sale of part of a holding, for the home currency
+                8..=12 => {
+                    let qty = part_of(accts[from].balance, &mut rng);
+                    let value = value_of(qty, &accts[from], &prices);
+                    Some(("Sell", value, vec![(from, -(qty as i128)), (0, value as i128)]))
+                }
+                //  A transfer of part of a holding to the other account of its ticker
+                13..=15 => {
+                    let to = if from % 2 == 1 { from + 1 } else { from - 1 };
+                    let qty = part_of(accts[from].balance, &mut rng);
+                    match to < accts.len() {
+                        true => Some(("Transfer", 0, vec![(from, -(qty as i128)), (to, qty as i128)])),
+                        false => None,
+                    }
+                }
+                //  A trade of part of a holding for another ticker, on an exchange
+                16.. => {
+                    let qty = part_of(accts[from].balance, &mut rng);
+                    let value = value_of(qty, &accts[from], &prices);
+                    let received = value as u128 * 100_000_000 / price_of(&accts[exchange], &prices) as u128;
+                    match accts[exchange].ticker != accts[from].ticker {
+                        true => Some(("Trade", value, vec![(from, -(qty as i128)), (exchange, received as i128)])),
+                        false => None,
+                    }
+                }
+                _ => None,
+            }
+            //  A buy, for the home currency, on an exchange (also instead of a trade too small to receive anything)
+            .filter(|(_memo, _proceeds, legs)| legs.iter().all(|(_acct, amount)| *amount != 0))
+            .unwrap_or_else(|| {
+                let qty = 1_000_000 + rng.below(100_000_000);
+                let cost = value_of(qty, &accts[exchange], &prices);
+                ("Buy", 0, vec![(0, -(cost as i128)), (exchange, qty as i128)])
+            });
+
+            row.iter_mut().for_each(|field| field.clear());
+            row[0] = date.format(&self.date_format).to_string();
+            row[1] = Decimal::new(proceeds as i64, 2).to_string();
+            row[2] = format!("{} {}", memo, txn + 1);
+
+            for (acct_idx, amount) in legs {
+                let acct = &mut accts[acct_idx];
+                row[3 + acct_idx] = match acct_idx {
+                    0 => Decimal::new(amount as i64, 2).to_string(),
+                    _ => {
+                        acct.balance = (acct.balance as i128 + amount) as u64;
+                        Decimal::new(amount as i64, 8).normalize().to_string()
+                    }
+                };
+            }
+
+            csv_wtr.write_record(&row)?;
+        }
+
+        csv_wtr.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Cents per whole unit of the `acct`'s ticker.
+fn price_of(acct: &TestAccount, prices: &[u64]) -> u64 {
+    prices[TICKERS.iter().position(|ticker| *ticker == acct.ticker).unwrap()]
+}
+
+/// The home currency value (in cents) of `qty` (in units of 1e-8) of the `acct`'s ticker, at least a cent.
+fn value_of(qty: u64, acct: &TestAccount, prices: &[u64]) -> u64 {
+    ((qty as u128 * price_of(acct, prices) as u128 / 100_000_000) as u64).max(1)
+}
+
+/// Between a tenth and all of `balance`, and at least 1.
+fn part_of(balance: u64, rng: &mut Rng) -> u64 {
+    (balance / 10 + rng.below(balance - balance / 10 + 1)).clamp(1, balance)
+}
+
+/// A small, seeded generator (xorshift64*), so the test data doesn't differ from one platform or build to the next.
+struct Rng(u64);
+
+impl Rng {
+
+    fn new(seed: u64) -> Rng {
+        //  xorshift never leaves zero, so no seed may start it there
+        Rng((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    /// A number from 0 up to (but not including) `n`, which must be at least 1.
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) % n
+    }
+}
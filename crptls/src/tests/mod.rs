@@ -2,3 +2,51 @@
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
 pub mod test;
+pub mod properties;
+
+//  Runs the tests of `test::_run_tests()` with the first sample input, and checks the invariants of
+//  `properties::check_invariants()` against both (see also tests/golden.rs, which compares the exported reports).
+//  Every other test of `test` and `properties` is a `#[test]` of its own.
+mod suite {
+
+    use std::path::PathBuf;
+
+    use crate::core_functions::{self, ImportProcessParameters, ProcessedMaps, Timings};
+
+    use super::{properties, test};
+
+    fn process_sample(file_name: &str, settings: &ImportProcessParameters) -> ProcessedMaps {
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/resources").join(file_name);
+
+        core_functions::import_and_process_final(&[path], settings, &mut Timings::default())
+            .unwrap_or_else(|e| panic!("{} failed to process: {}", file_name, e))
+    }
+
+    #[test]
+    fn run_tests() {
+
+        let (_raw_acct_map, acct_map, ars, txns_map) = process_sample(
+            "faker1__sample_input.csv", &ImportProcessParameters::default()
+        );
+
+        test::_run_tests(&txns_map, &ars, &acct_map);
+    }
+
+    #[test]
+    fn invariants_hold_for_sample_inputs() {
+
+        let slash_dates = ImportProcessParameters { input_file_date_separator: "/".to_string(), ..Default::default() };
+
+        for (file_name, settings) in [
+            ("faker1__sample_input.csv", ImportProcessParameters::default()),
+            ("faker2__sample_input.csv", slash_dates),
+        ] {
+            let (raw_acct_map, acct_map, ars, txns_map) = process_sample(file_name, &settings);
+
+            if let Err(failure) = properties::check_invariants(&settings, &raw_acct_map, &acct_map, &ars, &txns_map) {
+                panic!("Invariant failed for {}: {}", file_name, failure)
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crate::core_functions::{self, ImportProcessParameters};
+use crate::costing_method::InventoryCostingMethod;
+use crate::test_data::TestData;
+
+
+/// The costing methods the generated test data is processed with.
+const COSTING_METHODS: [InventoryCostingMethod; 5] = [
+    InventoryCostingMethod::LIFObyLotCreationDate,
+    InventoryCostingMethod::FIFObyLotBasisDate,
+    InventoryCostingMethod::LTfirstLIFObyLotBasisDate,
+    InventoryCostingMethod::HIFObyLotUnitBasis,
+    InventoryCostingMethod::AverageCostAcrossLots,
+];
+
+/// How many files of test data are generated (each with its own seed, number of accounts and of rows, and costing
+/// method).
+const CASES: u64 = 60;
+
+/// Checks the invariants of `check_invariants()` against many files of generated test data (see `TestData`), in the
+/// manner of a property-based test.  For a case that fails, the file is generated with fewer and fewer rows for as
+/// long as it still fails, so that the failure is reported with the smallest file found.
+#[test]
+fn _test_invariants_hold_for_generated_test_data() {
+
+    for case in 1..=CASES {
+
+        let test_data = TestData {
+            rows: 50 + (case as usize * 37) % 400,
+            accounts: 2 + case as usize % 11,
+            seed: case,
+            ..Default::default()
+        };
+        let costing_method = COSTING_METHODS[case as usize % COSTING_METHODS.len()].clone();
+
+        let Err(mut failure) = check_test_data(&test_data, &costing_method) else { continue };
+
+        //  Shrinks the case
+        let mut rows = test_data.rows;
+        while rows > 1 {
+            let fewer = TestData { rows: rows / 2, ..test_data.clone() };
+            match check_test_data(&fewer, &costing_method) {
+                Ok(()) => break,
+                Err(fewer_failure) => { rows = fewer.rows; failure = fewer_failure; }
+            }
+        }
+
+        panic!("Invariant failed for TestData {{ rows: {}, accounts: {}, seed: {} }} with {:?}: {}",
+            rows, test_data.accounts, test_data.seed, costing_method, failure);
+    }
+}
+
+/// Generates and processes the `test_data`, and checks the invariants of the result.
+fn check_test_data(test_data: &TestData, costing_method: &InventoryCostingMethod) -> Result<(), String> {
+
+    let mut csv = Vec::new();
+    test_data.write(&mut csv).map_err(|e| format!("The test data failed to generate: {}", e))?;
+
+    let settings = ImportProcessParameters { costing_method: costing_method.clone(), ..Default::default() };
+
+    let (raw_acct_map, acct_map, ars, txns_map) = core_functions::import_and_process_final_from_csv(
+        csv, &settings, &mut core_functions::Timings::default()
+    ).map_err(|e| format!("The test data failed to process: {}", e))?;
+
+    check_invariants(&settings, &raw_acct_map, &acct_map, &ars, &txns_map)
+}
+
+/// Checks that, in the processed maps:
+///
+/// * no `Lot` of an account other than a home currency or margin account is ever negative, summing its movements in
+///   the order they were posted;
+/// * the cost basis of what a transfer (`TxType::ToSelf`) moves out of one account is the cost basis it moves into
+///   the other;
+/// * each movement's gain or loss is its proceeds less its cost basis (and any wash sale adjustment), and the proceeds
+///   of the movements out of an account other than a home currency account, for a trade or sale (`TxType::Exchange`),
+///   are those of the transaction (net of any fee), to within the rounding of each to the cent.
+pub fn check_invariants(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), String> {
+
+    let is_tracked = |acct: &Account| -> bool {
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        !raw_acct.is_margin && !raw_acct.is_home_currency(&settings.home_currency)
+    };
+
+    for acct in acct_map.values().filter(|acct| is_tracked(acct)) {
+        for lot in acct.list_of_lots.borrow().iter() {
+            let mut sum = dec!(0);
+            for mvmt in lot.movements.borrow().iter() {
+                sum += mvmt.amount;
+                if sum < dec!(0) {
+                    return Err(format!("Lot {} of account {} is {} after the movement of txn {}.",
+                        lot.lot_number, acct.raw_key, sum, mvmt.transaction_key))
+                }
+            }
+        }
+    }
+
+    for tx_num in 1..=txns_map.len() as u32 {
+
+        let txn = txns_map.get(&tx_num).unwrap();
+        let txn_type = txn.transaction_type(ars, raw_acct_map, acct_map).map_err(|e| e.to_string())?;
+
+        let mut outgoing_basis = dec!(0);
+        let mut incoming_basis = dec!(0);
+        let mut outgoing_proceeds = dec!(0);
+        let mut outgoing_mvmts = 0;
+        let mut outgoing_is_tracked = false;
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+                let gain = mvmt.proceeds.get() + mvmt.cost_basis.get() + mvmt.wash_sale_adjustment.get();
+                if mvmt.get_orig_gain_or_loss() != gain {
+                    return Err(format!("The gain or loss of a movement of txn {} is {}, not its proceeds less its \
+                        cost basis ({}).", tx_num, mvmt.get_orig_gain_or_loss(), gain))
+                }
+
+                match ar.direction() {
+                    Polarity::Outgoing => {
+                        outgoing_basis += mvmt.cost_basis.get();
+                        outgoing_proceeds += mvmt.proceeds.get();
+                        outgoing_mvmts += 1;
+                    }
+                    Polarity::Incoming => incoming_basis += mvmt.cost_basis.get(),
+                }
+            }
+
+            if ar.direction() == Polarity::Outgoing { outgoing_is_tracked = is_tracked(acct_map.get(&ar.account_key).unwrap()) }
+        }
+
+        match txn_type {
+            TxType::ToSelf if txn.fee == dec!(0) && incoming_basis != -outgoing_basis => {
+                return Err(format!("Transfer txn {} moves {} of cost basis out, but {} in.",
+                    tx_num, -outgoing_basis, incoming_basis))
+            }
+            //  Each movement's share of the proceeds is rounded to the cent, so they may be a cent or so off in sum
+            TxType::Exchange if outgoing_is_tracked
+                && (outgoing_proceeds - txn.proceeds_net_of_fee()).abs() > dec!(0.005) * Decimal::from(outgoing_mvmts) => {
+                return Err(format!("The movements of txn {} have {} of proceeds, but the transaction has {}.",
+                    tx_num, outgoing_proceeds, txn.proceeds_net_of_fee()))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -3,13 +3,63 @@
 
 use std::fs;
 use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
 
+use chrono::{FixedOffset, NaiveDate, NaiveTime};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crate::account::Account;
-use crate::transaction::{Transaction, ActionRecord};
+use crate::account::{Account, Movement, RawAccount, Term};
+use crate::transaction::{Transaction, ActionRecord, IncomeType, TxKind, TxType};
+use crate::core_functions::{self, ImportProcessParameters, JournalAccounts, TableFormat};
+use crate::corrections::{self, Correction, CorrectedField, CorrectionTarget};
+use crate::unresolved::{self, Unresolved};
+use crate::needs_review;
+use crate::fiat_costs;
+use crate::costing_method::InventoryCostingMethod;
+use crate::disposal_rounding::DisposalRounding;
+use crate::derivatives;
+use crate::dust_conversions;
+use crate::fx_translation::FxTranslation;
+use crate::nfts;
+use crate::non_sale_disposals;
+use crate::price_lookup::PriceLookup;
+use crate::reconciliation;
+use crate::report_filter::ReportFilter;
+use crate::validation;
+use crate::csv_dialect::{self, CsvDelimiter, CsvDialect};
+use crate::csv_import_accts_txns::{self, InputSchema};
+use crate::dedupe::{self, DedupeMode, DEFAULT_DEDUPE_KEY};
+use crate::dust_sweeps::{self, DustQuantities};
+use crate::tags::{self, Tag};
+use crate::spot_prices;
+use crate::summary::{self, RunSummary, YearGains};
+use crate::template::{Template, TemplateAccount};
+use crate::test_data::TestData;
+use crate::synthetic_acquisitions::SyntheticBasis;
+use crate::timestamps;
+use crate::same_date_ordering::SameDateOrder;
+use crate::specific_id::{Disposal, FileLotPicker, LotAllocations};
+use crate::import_map::ImportMap;
+use crate::input_error::InputError;
+use crate::accounts_file::{self, AccountsFile};
+use crate::anonymize;
+use crate::long_schema;
+use crate::importers::{self, ChainApi, Source};
+use crate::like_kind;
+use crate::liquidity_pools::PoolTreatment;
+use crate::lot_pooling::LotPooling;
+use crate::airdrops::AirdropBasis;
+use crate::rebases::RebaseTreatment;
+use crate::run_state::RunState;
+use crate::token_aliases::TokenAliases;
+use crate::entities::{Entities, EntityTransferTreatment};
+use crate::balance_history::{self, BalanceHistory, BalanceInterval, HighestBalance};
 use crate::decimal_utils::*;
+use crate::report_language::{self, ReportLanguage};
+use crate::checksums;
 
 pub fn _run_tests(
     transactions_map: &HashMap<u32, Transaction>,
@@ -68,7 +118,7 @@ fn _compare_movements_across_implementations(
                 + " with amount: "
                 + &ar.amount.to_string() + &"\n".to_string()
             );
-            let mvmts = ar.get_mvmts_in_ar_in_lot_date_order(&account_map, &transactions_map);
+            let mvmts = ar.get_mvmts_in_ar_in_lot_date_order();
             let mut amts = dec!(0);
             for mvmt in mvmts {
                 amts += mvmt.amount;
@@ -157,7 +207,7 @@ pub fn _test_action_records_amts_vs_mvmt_amts(
         for ar_num in &txn.action_record_idx_vec {
 
             let ar = action_records_map.get(&(ar_num)).unwrap();
-            let mvmts = ar.get_mvmts_in_ar_in_lot_date_order(&account_map, &transactions_map);
+            let mvmts = ar.get_mvmts_in_ar_in_lot_date_order();
                 for mvmt in mvmts {
                     mvmt_amt_ar += mvmt.amount
                 }
@@ -195,6 +245,805 @@ pub fn _test_action_records_amts_vs_mvmt_amts(
     );
 }
 
+/// Default settings (as if run with `-a` and no env vars), for tests that import a small CSV of their own.
+fn _test_settings() -> ImportProcessParameters {
+    ImportProcessParameters {
+        input_file_date_separator: "-".to_string(),
+        input_file_uses_iso_date_style: false,
+        home_currency: "USD".to_string(),
+        costing_method: InventoryCostingMethod::LIFObyLotCreationDate,
+        lk_treatment_enabled: false,
+        lk_cutoff_date: NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap(),
+        lk_scope: like_kind::LikeKindScope::default(),
+        lk_basis_date_preserved: true,
+        settle_date_is_basis_date: false,
+        zero_quantity_rows_are_annotations: false,
+        should_export: false,
+        export_path: PathBuf::from("."),
+        journal_entry_export: false,
+        ledger_export: false,
+        beancount_export: false,
+        journal_csv_export: false,
+        chart_of_accounts: HashMap::new(),
+        journal_accounts: JournalAccounts::default(),
+        fiat_costs: Vec::new(),
+        stdout_report: None,
+        excel_bom: false,
+        table_format: TableFormat::Csv,
+        same_date_order: SameDateOrder::FileOrder,
+        timezone: None,
+        long_term_days: 365,
+        input_file_date_format: None,
+        csv_delimiter: CsvDelimiter::Auto,
+        input_locale: ReportLocale::Plain,
+        form_8949_per_account: false,
+        home_curr_leg_is_basis: true,
+        dust_threshold: None,
+        dust_quantities: DustQuantities::new(),
+        disposal_rounding: None,
+        exchange_label_separator: None,
+        synthetic_acquisitions: None,
+        import_map: None,
+        import_source: None,
+        input_schema: InputSchema::Wide,
+        accounts_file: None,
+        low_memory: false,
+        resume_from: None,
+        wash_sale_window: None,
+        tax_year: None,
+        split_by_tax_year: false,
+        transfer_fee_is_disposal: false,
+        price_lookup: None,
+        lenient: false,
+        corrections: None,
+        lot_selections: None,
+        excluded_txns: Vec::new(),
+        parked_rows: Vec::new(),
+        output_precision: OutputPrecision::default(),
+        output_date_format: None,
+        report_language: ReportLanguage::English,
+        secondary_currencies: Vec::new(),
+        stablecoins: Vec::new(),
+        transfer_match_tolerance: None,
+        pool_treatment: PoolTreatment::Swap,
+        lot_pooling: LotPooling::PerWallet,
+        airdrop_basis: AirdropBasis::FairMarketValue,
+        rebase_treatment: RebaseTreatment::Income,
+        dedupe: DedupeMode::Off,
+        dedupe_key: DEFAULT_DEDUPE_KEY.to_vec(),
+        header_aliases: HashMap::new(),
+        token_aliases: None,
+        entities: None,
+        entity_transfers: EntityTransferTreatment::Carryover,
+        translation: None,
+        file_name_prefix: None,
+        file_name_date: None,
+        overwrite: false,
+        report_filter: ReportFilter::default(),
+        fiscal_year_start_month: 1,
+    }
+}
+
+/// A directory of one test's own files (named for the test and the process, so that the tests, run at once, never
+/// share a file), removed with them once dropped.
+struct _TestDir(PathBuf);
+
+impl _TestDir {
+
+    fn new(test_name: &str) -> _TestDir {
+        let dir = std::env::temp_dir().join(format!("cryptools_test_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Unable to create test directory");
+        _TestDir(dir)
+    }
+
+    /// The path of `file_name` in the directory.
+    fn path(&self, file_name: &str) -> PathBuf {
+        self.0.join(file_name)
+    }
+
+    /// Writes `contents` to `file_name` in the directory (to be passed to `import_and_process_final()`) and returns
+    /// its path.
+    fn input_file(&self, file_name: &str, contents: &str) -> PathBuf {
+        let path = self.path(file_name);
+        fs::write(&path, contents).expect("Unable to write test input file");
+        path
+    }
+}
+
+impl Drop for _TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A CSV Input File of a USD Bank account and a BTC Exchange account, the accounts of most of these tests, with the
+/// transaction `rows` after its header rows.
+fn _bank_and_exchange_input(rows: &str) -> String {
+    format!("txDate,proceeds,memo,1,2\n,,,Bank,Exchange\n,,,USD,BTC\n,,,no,no\n{}", rows)
+}
+
+/// A USD->BTC buy should not be a disposal of USD. The USD leg nets to a $0 gain, and the BTC lot takes
+/// the USD spent as its cost basis (even though the `proceeds` column was left at 0).
+#[test]
+fn _test_home_currency_leg_of_buy() {
+
+    let dir = _TestDir::new("home_currency_leg_of_buy");
+
+    let input = _bank_and_exchange_input("\
+1-1-20,0,Buy BTC with USD,-7000,1
+");
+    let settings = _test_settings();
+    let path = dir.input_file("home_curr_leg.csv", &input);
+    let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let txn = txns_map.get(&1).unwrap();
+    let usd_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+    let btc_ar = ars.get(&txn.action_record_idx_vec[1]).unwrap();
+
+    for mvmt in usd_ar.get_mvmts_in_ar_in_lot_date_order() {
+        assert_eq!(mvmt.proceeds.get() + mvmt.cost_basis.get(), dec!(0), "USD leg of a buy produced a gain/loss.");
+        assert_eq!(mvmt.proceeds_lk.get() + mvmt.cost_basis_lk.get(), dec!(0), "USD leg of a buy produced a gain/loss.");
+    }
+
+    let btc_mvmts = btc_ar.get_mvmts_in_ar_in_lot_date_order();
+    assert_eq!(btc_mvmts.len(), 1);
+    assert_eq!(btc_mvmts[0].cost_basis.get(), dec!(7000), "BTC lot did not take the USD spent as its basis.");
+}
+
+/// Aggregating a dust conversion for reporting must not change the total gain of its underlying disposals.
+#[test]
+fn _test_dust_conversion_aggregation_preserves_gain() {
+
+    let dir = _TestDir::new("dust_conversion_aggregation_preserves_gain");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Exchange,Exchange,Exchange
+,,,USD,ETH,LTC,BNB
+,,,no,no,no,no
+1-1-20,0,Buy ETH,-3.00,0.01,,
+1-1-20,0,Buy LTC,-1.50,,0.02,
+6-1-20,2.35,Dust to BNB,,-0.01,,0.1
+6-1-20,0.4,Dust to BNB,,,-0.02,0.02
+";
+    let mut settings = _test_settings();
+    settings.dust_threshold = Some(dec!(5));
+    let path = dir.input_file("dust_conversion.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let conversions = dust_conversions::find_dust_conversions(
+        dec!(5), &settings.home_currency, &raw_accts, &acct_map, &ars, &txns_map
+    ).unwrap();
+
+    assert_eq!(conversions.len(), 1);
+    assert_eq!(conversions[0].txn_nums, vec![3, 4]);
+
+    let mut unaggregated_gain = dec!(0);
+    for txn_num in conversions[0].txn_nums.iter() {
+        let txn = txns_map.get(txn_num).unwrap();
+        for mvmt in txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap() {
+            unaggregated_gain += mvmt.get_lk_gain_or_loss();
+        }
+    }
+
+    let (st, lt) = conversions[0].totals_by_term(&settings.home_currency, settings.long_term_days, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let aggregated_gain = st.map_or(dec!(0), |t| t.gain_or_loss()) + lt.map_or(dec!(0), |t| t.gain_or_loss());
+
+    assert_eq!(aggregated_gain, unaggregated_gain, "Aggregating the dust conversion changed the total gain.");
+    assert_eq!(aggregated_gain, dec!(-1.75));
+}
+
+/// With long-term-first lot selection (LIFO within each group), a lot held exactly 365 days is still short-term,
+/// so the newer lot is chosen. One day later, the older lot has crossed into long-term and is chosen first.
+#[test]
+fn _test_long_term_lots_first_across_boundary() {
+
+    let dir = _TestDir::new("long_term_lots_first_across_boundary");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy lot 1,-3000,1
+6-1-19,0,Buy lot 2,-8000,1
+1-1-20,2000,Sell at 365 days,2000,-0.25
+1-2-20,2000,Sell at 366 days,2000,-0.25
+");
+    let mut settings = _test_settings();
+    settings.costing_method = InventoryCostingMethod::LTfirstLIFObyLotBasisDate;
+    let path = dir.input_file("lt_first.csv", &input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    for (txn_num, expected_lot, expected_term) in [(3, 2, Term::ST), (4, 1, Term::LT)] {
+
+        let txn = txns_map.get(&txn_num).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+
+        assert_eq!(mvmts.len(), 1);
+        assert_eq!(mvmts[0].lot_num, expected_lot, "Txn {} disposed of the wrong lot.", txn_num);
+        assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), expected_term);
+    }
+}
+
+/// A lot transferred in keeps its basis date, so the Exchange's second lot (created in July) has an older basis date
+/// than its first (created in June).  The basis date methods order the lots by basis date, not by creation.
+#[test]
+fn _test_basis_date_methods_order_lots_by_basis_date() {
+
+    let dir = _TestDir::new("basis_date_methods_order_lots_by_basis_date");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,Exchange
+,,,USD,BTC,BTC
+,,,no,no,no
+1-1-18,0,Buy in wallet,-1000,1,
+6-1-19,0,Buy on exchange,-8000,,1
+7-1-19,0,Transfer to exchange,,-1,1
+8-1-19,9000,Sell,9000,,-1
+";
+    let path = dir.input_file("basis_date_order.csv", input);
+
+    for (costing_method, expected_lot) in [
+        (InventoryCostingMethod::LIFObyLotCreationDate, 2),
+        (InventoryCostingMethod::LIFObyLotBasisDate, 1),
+        (InventoryCostingMethod::FIFObyLotCreationDate, 1),
+        (InventoryCostingMethod::FIFObyLotBasisDate, 2),
+    ] {
+        let mut settings = _test_settings();
+        settings.costing_method = costing_method.clone();
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let txn = txns_map.get(&4).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+
+        assert_eq!(mvmts.len(), 1);
+        assert_eq!(mvmts[0].lot_num, expected_lot, "{} disposed of the wrong lot.", costing_method);
+    }
+}
+
+#[test]
+fn _test_open_lot_impacts_sorted_losses_first() {
+
+    let dir = _TestDir::new("open_lot_impacts_sorted_losses_first");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Exchange
+,,,USD,BTC,ETH
+,,,no,no,no
+1-1-19,0,Buy BTC lot 1,-3000,1,
+6-1-19,0,Buy BTC lot 2,-12000,1,
+6-1-19,0,Buy ETH lot 1,-500,,2
+";
+    let settings = _test_settings();
+    let path = dir.input_file("open_lot_impacts.csv", input);
+    let (raw_accts, acct_map, _ars, _txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let mut prices: HashMap<String, Decimal> = HashMap::new();
+    prices.insert("BTC".to_string(), dec!(10000));
+    let as_of_date = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+
+    let (impacts, unpriced) = spot_prices::open_lot_impacts(&prices, as_of_date, &settings.home_currency, settings.long_term_days, &raw_accts, &acct_map);
+
+    assert_eq!(unpriced, vec!["ETH".to_string()]);
+    assert_eq!(impacts.len(), 2);
+
+    assert_eq!(impacts[0].lot_number, 2);
+    assert_eq!(impacts[0].unrealized_gain_or_loss(), dec!(-2000));
+    assert_eq!(impacts[0].term, Term::ST);
+    assert_eq!(impacts[0].days_until_lt, 151);
+
+    assert_eq!(impacts[1].lot_number, 1);
+    assert_eq!(impacts[1].unrealized_gain_or_loss(), dec!(7000));
+    assert_eq!(impacts[1].term, Term::LT);
+    assert_eq!(impacts[1].days_until_lt, 0);
+}
+
+#[test]
+fn _test_over_precise_disposal_rounds_to_close_lot() {
+
+    let dir = _TestDir::new("over_precise_disposal_rounds_to_close_lot");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy,-6000,1.5
+2-1-19,9000,Sell slightly more than held,9000,-1.50000001
+");
+    let mut settings = _test_settings();
+    settings.disposal_rounding = Some(DisposalRounding::LotPrecision);
+    let path = dir.input_file("disposal_rounding.csv", &input);
+    let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let btc_acct = acct_map.get(&2).unwrap();
+    let sell_og_ar = ars.get(&txns_map.get(&2).unwrap().action_record_idx_vec[0]).unwrap();
+
+    assert_eq!(sell_og_ar.amount, dec!(-1.5));
+    assert_eq!(btc_acct.list_of_lots.borrow().len(), 1);
+    assert_eq!(btc_acct.get_sum_of_amts_in_lots(), dec!(0));
+    assert_eq!(sell_og_ar.movements.borrow().len(), 1);
+}
+
+/// The dust a disposal leaves should be swept out of the account, with its basis written off at no gain or loss, but
+/// only at or below its ticker's dust quantity.
+#[test]
+fn _test_dust_left_by_disposal_is_swept() {
+
+    let dir = _TestDir::new("dust_left_by_disposal_is_swept");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Exchange
+,,,USD,BTC,ETH
+,,,no,no,no
+1-1-19,0,Buy BTC,-5000,1,
+1-1-19,0,Buy ETH,-1000,,1
+2-1-19,6000,Sell nearly all BTC,6000,-0.99999999,
+3-1-19,1200,Sell most ETH,1200,,-0.99
+";
+    let (proceeds_threshold, quantities) = dust_sweeps::parse_dust_threshold("5.00, btc=0.00000001, ETH=0.001").unwrap();
+    assert_eq!(proceeds_threshold, Some(dec!(5)));
+    assert!(dust_sweeps::parse_dust_threshold("BTC=0").is_err() && dust_sweeps::parse_dust_threshold("1,2").is_err());
+
+    let mut settings = _test_settings();
+    settings.dust_quantities = quantities;
+    let path = dir.input_file("dust_sweeps.csv", input);
+    let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let sweep = txns_map.get(&4).unwrap();
+    let sweep_mvmts = ars.get(&sweep.action_record_idx_vec[0]).unwrap().movements.borrow().clone();
+
+    assert_eq!(txns_map.len(), 5);
+    assert_eq!(sweep.kind, TxKind::DustSweep);
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0));
+    assert_eq!(sweep_mvmts.iter().map(|mvmt| mvmt.amount).sum::<Decimal>(), dec!(-0.00000001));
+    assert_eq!(sweep_mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum::<Decimal>(), dec!(0));
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(0.01));
+}
+
+#[test]
+fn _test_run_summary_headline_figures() {
+
+    let dir = _TestDir::new("run_summary_headline_figures");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy,-3000,1
+3-1-19,500,Staking reward,,0.1
+6-1-19,2000,Sell,2000,-0.5
+");
+    let settings = _test_settings();
+    let path = dir.input_file("run_summary.csv", &input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+
+    // LIFO: the 0.1 reward lot (basis 500) is sold first, then 0.4 of the purchased lot (basis 1200)
+    assert_eq!(summary.realized_st, dec!(300));
+    assert_eq!(summary.realized_lt, dec!(0));
+    assert_eq!(summary.income, dec!(500));
+    assert_eq!(summary.expense, dec!(0));
+    assert_eq!(summary.holdings.len(), 1);
+    assert_eq!(summary.holdings[0].amount, dec!(0.6));
+    assert_eq!(summary.holdings[0].cost_basis, dec!(1800));
+    assert_eq!(summary.account_count, 2);
+    assert_eq!(summary.proceeds, dec!(2000));
+    assert_eq!(summary.realized_by_year, vec![YearGains { year: 2019, realized_st: dec!(300), realized_lt: dec!(0) }]);
+}
+
+#[test]
+fn _test_settle_date_basis_flips_term() {
+
+    let dir = _TestDir::new("settle_date_basis_flips_term");
+
+    // The BTC was received on the settle date, days before the trade was booked
+    let input = "\
+txDate,proceeds,memo,settleDate,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+1-5-19,0,OTC buy,1-1-19,-3000,1
+1-3-20,5000,Sell,,5000,-1
+";
+    for (settle_date_is_basis_date, expected_term) in [(false, Term::ST), (true, Term::LT)] {
+
+        let mut settings = _test_settings();
+        settings.settle_date_is_basis_date = settle_date_is_basis_date;
+        let path = dir.input_file("settle_date.csv", input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let txn = txns_map.get(&2).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+
+        assert_eq!(mvmts.len(), 1);
+        assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), expected_term);
+    }
+}
+
+#[test]
+fn _test_gains_grouped_by_exchange_label() {
+
+    let dir = _TestDir::new("gains_grouped_by_exchange_label");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Kraken: BTC,Kraken: ETH,Gemini: BTC
+,,,USD,BTC,ETH,BTC
+,,,no,no,no,no
+1-1-19,0,Buy,-1000,1,,
+1-1-19,0,Buy,-500,,5,
+1-1-19,0,Buy,-1000,,,1
+3-1-19,1500,Sell BTC,1500,-1,,
+3-1-19,400,Sell ETH,400,,-5,
+3-1-20,800,Sell BTC,800,,,-1
+";
+    let mut settings = _test_settings();
+    settings.exchange_label_separator = Some(":".to_string());
+    let path = dir.input_file("gains_by_exchange.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let exchanges = summary::gains_by_exchange(&settings.home_currency, settings.long_term_days, settings.exchange_label_separator.as_deref(), &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+
+    assert_eq!(exchanges.len(), 2);
+
+    assert_eq!(exchanges[0].label, "Kraken");
+    assert_eq!(exchanges[0].realized_st, dec!(400));
+    assert_eq!(exchanges[0].realized_lt, dec!(0));
+    assert_eq!(exchanges[0].account_names.len(), 2);
+
+    assert_eq!(exchanges[1].label, "Gemini");
+    assert_eq!(exchanges[1].realized_st, dec!(0));
+    assert_eq!(exchanges[1].realized_lt, dec!(-200));
+}
+
+#[test]
+fn _test_exchange_totals_by_year_attribute_income_and_fees() {
+
+    let dir = _TestDir::new("exchange_totals_by_year_attribute_income_and_fees");
+
+    let input = "\
+txDate,proceeds,memo,fee,1,2,3
+,,,,Kraken: USD,Kraken: BTC,Wallet: BTC
+,,,,USD,BTC,BTC
+,,,,no,no,no
+1-1-20,1000,Buy,10,-1010,1,
+2-1-20,500,Staking,,,,0.1
+3-1-20,600,Transfer,12,,-0.5,0.49
+6-1-20,3000,Sell,5,2995,,-0.59
+2-1-21,1000,Sell,,1000,-0.5,
+";
+    let mut settings = _test_settings();
+    settings.exchange_label_separator = Some(":".to_string());
+    let path = dir.input_file("exchange_totals_by_year.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let totals = summary::totals_by_exchange_and_year(&settings.home_currency, settings.long_term_days,
+        settings.fiscal_year_start_month, settings.exchange_label_separator.as_deref(), &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+
+    let keys: Vec<(i32, &str)> = totals.iter().map(|t| (t.year, t.label.as_str())).collect();
+    assert_eq!(keys, vec![(2020, "Kraken"), (2020, "Wallet"), (2021, "Kraken")]);
+
+    //  The buy's fee is paid out of Kraken's USD account, and the transfer's out of its BTC account
+    assert_eq!((totals[0].income, totals[0].fees), (dec!(0), dec!(22)));
+    assert_eq!((totals[1].income, totals[1].fees), (dec!(500), dec!(5)));
+    assert_eq!(totals[0].account_names.len(), 2);
+
+    //  Every disposal is attributed to one exchange
+    let by_year = summary::gains_by_year(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month,
+        &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    for year in by_year.iter() {
+        let attributed: Decimal = totals.iter().filter(|t| t.year == year.year).map(|t| t.realized_total()).sum();
+        assert_eq!(attributed, year.realized_total());
+    }
+    assert!(!totals[2].realized_total().is_zero());
+}
+
+#[test]
+fn _test_synthetic_acquisition_covers_shortfall() {
+
+    let dir = _TestDir::new("synthetic_acquisition_covers_shortfall");
+
+    // Only 1 of the 1.5 BTC sold was ever recorded as acquired
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy,-3000,1
+6-1-19,6000,Sell,6000,-1.5
+");
+    let mut settings = _test_settings();
+    settings.synthetic_acquisitions = Some(SyntheticBasis::Market);
+    let path = dir.input_file("synthetic_acquisitions.csv", &input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    assert_eq!(txns_map.len(), 3);
+
+    let real: Vec<&Transaction> = (1..=3).map(|n| txns_map.get(&n).unwrap()).filter(|t| t.kind == TxKind::Standard).collect();
+    let synthetic: Vec<&Transaction> = (1..=3).map(|n| txns_map.get(&n).unwrap()).filter(|t| t.kind == TxKind::Synthetic).collect();
+
+    assert_eq!(real.len(), 2);
+    assert_eq!(synthetic.len(), 1);
+    assert_eq!(synthetic[0].tx_number, 2, "The synthetic acquisition should immediately precede the disposal it covers.");
+
+    let synthetic_ar = ars.get(&synthetic[0].action_record_idx_vec[0]).unwrap();
+    assert_eq!(synthetic_ar.amount, dec!(0.5));
+    assert_eq!(synthetic_ar.cost_basis_in_ar(), dec!(2000));
+
+    let btc_acct = acct_map.get(&2).unwrap();
+    assert_eq!(btc_acct.get_sum_of_amts_in_lots(), dec!(0));
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.income, dec!(0), "A synthetic acquisition must not be income.");
+    assert_eq!(summary.realized_st, dec!(1000));
+}
+
+#[test]
+fn _test_date_format_overrides_date_options() {
+
+    let dir = _TestDir::new("date_format_overrides_date_options");
+
+    let input = _bank_and_exchange_input("\
+\"Jan 5, 2021\",0,Buy,-3000,1
+\"Feb 5, 2021\",4000,Sell,4000,-1
+");
+    let mut settings = _test_settings();
+    settings.input_file_uses_iso_date_style = true;     //  Ignored when a DATE_FORMAT is set
+    settings.input_file_date_format = Some("%b %d, %Y".to_string());
+
+    let path = dir.input_file("date_format.csv", &input);
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    assert_eq!(txns_map.get(&1).unwrap().date, NaiveDate::from_ymd_opt(2021, 1, 5).unwrap());
+    assert_eq!(txns_map.get(&2).unwrap().date, NaiveDate::from_ymd_opt(2021, 2, 5).unwrap());
+}
+
+#[test]
+fn _test_zero_quantity_row_annotates_prior_txn() {
+
+    let dir = _TestDir::new("zero_quantity_row_annotates_prior_txn");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy,-3000,1
+1-1-19,5,Fee rebate,0,
+6-1-19,4000,Sell,4000,-1
+");
+    let mut settings = _test_settings();
+    settings.zero_quantity_rows_are_annotations = true;
+    let path = dir.input_file("zero_quantity_annotation.csv", &input);
+    let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    assert_eq!(txns_map.len(), 2, "The annotation row must not become a transaction.");
+    assert_eq!(ars.len(), 4);
+    assert_eq!(txns_map.get(&1).unwrap().user_memo, "Buy | Fee rebate (5.00 USD)");
+
+    let btc_acct = acct_map.get(&2).unwrap();
+    assert_eq!(btc_acct.list_of_lots.borrow().len(), 1, "The annotation row must not create a lot.");
+}
+
+#[test]
+fn _test_inherited_lot_is_long_term_next_day() {
+
+    let dir = _TestDir::new("inherited_lot_is_long_term_next_day");
+
+    // Inherited at a fair market value of 9000 on the date of death, then sold the next day
+    let input = "\
+txDate,proceeds,memo,txType,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+6-1-20,9000,Inheritance,inherited,,1
+6-2-20,9500,Sell,,9500,-1
+";
+    let settings = _test_settings();
+    let path = dir.input_file("inherited.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let btc_acct = acct_map.get(&2).unwrap();
+    let lot = &btc_acct.list_of_lots.borrow()[0];
+    assert!(lot.inherited);
+    assert_eq!(lot.get_sum_of_orig_basis_in_lot(), dec!(0), "The lot should be fully disposed of.");
+
+    let sale = txns_map.get(&2).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    assert_eq!(mvmts.len(), 1);
+    assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), Term::LT, "An inherited lot is always long-term.");
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.income, dec!(0), "An inheritance must not be income.");
+    assert_eq!(summary.realized_lt, dec!(500), "The basis should be stepped up to the fair market value.");
+    assert_eq!(summary.realized_st, dec!(0));
+}
+
+#[test]
+fn _test_inherited_lot_keeps_its_dates_through_transfers() {
+
+    let dir = _TestDir::new("inherited_lot_keeps_its_dates_through_transfers");
+
+    // Inherited into a wallet, half moved to the exchange the next day and sold there the day after
+    let input = "\
+txDate,proceeds,memo,txType,1,2,3
+,,,,Bank,Wallet,Exchange
+,,,,USD,BTC,BTC
+,,,,no,no,no
+6-1-20,9000,Inheritance,inherited,,1,
+6-2-20,0,Transfer,,,-0.5,0.5
+6-3-20,4800,Sell,,4800,,-0.5
+";
+    let settings = _test_settings();
+    let path = dir.input_file("inherited_transfer.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let date_of_death = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+
+    let wallet_lot = acct_map.get(&2).unwrap().list_of_lots.borrow()[0].clone();
+    assert!(wallet_lot.inherited);
+    assert_eq!(wallet_lot.date_for_basis_purposes, date_of_death, "An inherited lot's basis date must be the date of death.");
+
+    let exchange_lot = acct_map.get(&3).unwrap().list_of_lots.borrow()[0].clone();
+    assert!(exchange_lot.inherited, "A lot transferred from an inherited lot must be inherited.");
+    assert_eq!(exchange_lot.date_for_basis_purposes, date_of_death);
+
+    // Long-term by any holding period, such as a journal entry's own JE_LONG_TERM_DAYS
+    let sale = txns_map.get(&3).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    assert_eq!(mvmts.len(), 1);
+    for long_term_days in [settings.long_term_days, 3650] {
+        assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, long_term_days), Term::LT);
+    }
+
+    // Still inherited when carried forward through year-end holdings
+    let holdings = RunState::as_of(&settings, &raw_accts, &acct_map, NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()).unwrap();
+    let rows: Vec<String> = holdings.opening_balance_rows().iter().map(|row| row.join(",")).collect();
+    let holdings_path = dir.input_file("inherited_holdings.csv", &rows.join("\n"));
+    let opening = RunState::from_opening_balances(&holdings_path, &settings.home_currency).unwrap();
+
+    assert_eq!(opening.lots.len(), 1);
+    assert!(opening.lots[0].inherited);
+    assert_eq!(opening.lots[0].basis_date, date_of_death);
+    assert_eq!(opening.lots[0].term(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(), settings.long_term_days), Term::LT);
+
+    let next_year = "\
+txDate,proceeds,memo,1,2
+,,,Bank,Wallet
+,,,USD,BTC
+,,,no,no
+1-2-21,5000,Sell,5000,-0.5
+";
+    let mut settings = settings;
+    settings.resume_from = Some(opening);
+    let path = dir.input_file("inherited_next_year.csv", next_year);
+    let (_raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process from opening balances");
+
+    assert_eq!(txns_map.get(&1).unwrap().kind, TxKind::CarriedForwardInherited);
+    assert!(acct_map.get(&2).unwrap().list_of_lots.borrow()[0].inherited);
+}
+
+#[test]
+fn _test_same_date_order_changes_selected_lot() {
+
+    let dir = _TestDir::new("same_date_order_changes_selected_lot");
+
+    // Each day's pair is in the opposite order in the file
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy Jan,-2000,2
+6-1-19,5000,Sell Jun,5000,-1
+6-1-19,0,Buy Jun,-4800,1
+9-1-19,0,Buy Sep,-6000,1
+9-1-19,6100,Sell Sep,6100,-1
+");
+    // Under LIFO, the gain on each sale depends on whether that day's acquisition was processed first
+    for (order, jun_gain, sep_gain) in [
+        (SameDateOrder::FileOrder, dec!(4000), dec!(100)),
+        (SameDateOrder::AcquisitionsFirst, dec!(200), dec!(100)),
+        (SameDateOrder::DisposalsFirst, dec!(4000), dec!(1300)),
+    ] {
+        let mut settings = _test_settings();
+        settings.same_date_order = order;
+        let path = dir.input_file("same_date_order.csv", &input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let gain_on = |memo: &str| -> Decimal {
+            let txn = txns_map.values().find(|txn| txn.user_memo == memo).unwrap();
+            let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+            assert_eq!(ar.tx_key, txn.tx_number, "ActionRecords must be renumbered along with their Transaction.");
+            txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap()
+                .iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum()
+        };
+
+        assert_eq!(gain_on("Sell Jun"), jun_gain, "{:?}", order);
+        assert_eq!(gain_on("Sell Sep"), sep_gain, "{:?}", order);
+    }
+}
+
+#[test]
+fn _test_unrealized_gains_bucketed_by_term() {
+
+    let dir = _TestDir::new("unrealized_gains_bucketed_by_term");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Exchange,Wallet,Exchange
+,,,USD,BTC,BTC,ETH
+,,,no,no,no,no
+1-1-19,0,Buy BTC lot 1,-3000,1,,
+6-1-19,0,Buy BTC lot 2,-12000,,1,
+6-1-19,0,Buy ETH lot 1,-500,,,2
+";
+    let settings = _test_settings();
+    let path = dir.input_file("unrealized_by_term.csv", input);
+    let (raw_accts, acct_map, _ars, _txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let mut prices: HashMap<String, Decimal> = HashMap::new();
+    prices.insert("BTC".to_string(), dec!(10000));
+    prices.insert("ETH".to_string(), dec!(300));
+    let as_of_date = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+
+    let (impacts, _unpriced) = spot_prices::open_lot_impacts(&prices, as_of_date, &settings.home_currency, settings.long_term_days, &raw_accts, &acct_map);
+    let currencies = spot_prices::unrealized_by_term(&impacts, &raw_accts, &acct_map);
+
+    assert_eq!(currencies.len(), 2);
+
+    // The two BTC lots are in different accounts, but are subtotaled together
+    let btc = &currencies[0];
+    assert_eq!(btc.ticker, "BTC");
+    assert_eq!((btc.lt.lots, btc.lt.unrealized_gain_or_loss()), (1, dec!(7000)));
+    assert_eq!((btc.st.lots, btc.st.unrealized_gain_or_loss()), (1, dec!(-2000)));
+    assert_eq!(btc.unrealized_gain_or_loss(), dec!(5000));
+
+    let eth = &currencies[1];
+    assert_eq!(eth.ticker, "ETH");
+    assert_eq!((eth.st.lots, eth.st.amount, eth.st.unrealized_gain_or_loss()), (1, dec!(2), dec!(100)));
+    assert_eq!(eth.lt.lots, 0);
+}
+
+#[test]
+fn _test_harvest_candidates_offset_gains_by_term() {
+
+    let impact = |lot_number: u32, cost_basis: Decimal, market_value: Decimal, term: Term| spot_prices::OpenLotImpact {
+        acct_key: 1,
+        lot_number,
+        date_for_basis_purposes: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        amount: dec!(1),
+        cost_basis,
+        spot_price: market_value,
+        market_value,
+        term,
+        days_until_lt: 0,
+    };
+
+    let impacts = vec![
+        impact(1, dec!(1000), dec!(400), Term::LT),
+        impact(2, dec!(500), dec!(200), Term::ST),
+        impact(3, dec!(100), dec!(150), Term::ST),
+        impact(4, dec!(900), dec!(100), Term::ST),
+    ];
+
+    let candidates = spot_prices::harvest_candidates(&impacts, dec!(1000), dec!(200));
+    let offsets: Vec<(u32, Decimal, Decimal, Decimal, Decimal)> = candidates.iter()
+        .map(|c| (c.impact.lot_number, c.harvestable_loss, c.offsets_st, c.offsets_lt, c.loss_left_over()))
+        .collect();
+
+    // Largest loss first, and the lot at a gain is left out.  Lot 4's ST loss takes ST gain, lot 1's LT loss takes the
+    // LT gain and then the rest of the ST gain, and nothing is left for lot 2
+    assert_eq!(offsets, vec![
+        (4, dec!(800), dec!(800), dec!(0), dec!(0)),
+        (1, dec!(600), dec!(200), dec!(200), dec!(200)),
+        (2, dec!(300), dec!(0), dec!(0), dec!(300)),
+    ]);
+}
+
 fn _test_quantize_from_incoming_multiple_lots_fn (
     outgoing_mvmt_amt: Decimal,
     outgoing_ar_amt: Decimal,
@@ -242,3 +1091,3030 @@ fn _test_dec_rounded_1e2(random_float_string: &str) {
     println!("String into dec: {:?}; dec rounded to 2 places: {:?}", amt, amt2);
     //  Results of this test suggest that quantize() is off by one.  round_dec_1e8() was adjusted accordingly.
 }
+
+#[test]
+fn _test_hifo_and_average_cost_basis_of_disposal() {
+
+    let dir = _TestDir::new("hifo_and_average_cost_basis_of_disposal");
+
+    // The lots are transferred to the Wallet before the sale, so HIFO must rely on the basis carried over by the transfer
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-1-19,0,Buy Jan,-1000,1,
+2-1-19,0,Buy Feb,-5000,1,
+3-1-19,0,Buy Mar,-3000,1,
+4-1-19,0,Transfer,,-3,3
+5-1-19,6000,Sell May,6000,,-1.5
+";
+    for (method, basis) in [
+        (InventoryCostingMethod::HIFObyLotUnitBasis, dec!(-6500)),
+        (InventoryCostingMethod::AverageCostAcrossLots, dec!(-4500)),
+    ] {
+        let mut settings = _test_settings();
+        settings.costing_method = method.clone();
+        let path = dir.input_file("hifo_and_average_cost.csv", input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let txn = txns_map.values().find(|txn| txn.user_memo == "Sell May").unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+        let cost_basis: Decimal = mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum();
+
+        assert_eq!(cost_basis, basis, "{}", method);
+    }
+}
+
+#[test]
+fn _test_specific_id_posts_to_picked_lots() {
+
+    let dir = _TestDir::new("specific_id_posts_to_picked_lots");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy Jan,-1000,1
+2-1-19,0,Buy Feb,-5000,1
+3-1-19,0,Buy Mar,-3000,1
+5-1-19,6000,Sell May,6000,-1.5
+");
+    let mut settings = _test_settings();
+    settings.costing_method = InventoryCostingMethod::SpecificIdentification;
+    let path = dir.input_file("specific_id.csv", &input);
+
+    assert!(core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()).is_err(),
+        "Specific identification without a lot picker must fail.");
+
+    // All of March's lot, then half of January's
+    let mut picker = |disposal: &Disposal| -> Result<LotAllocations, Box<dyn std::error::Error>> {
+        assert_eq!(disposal.open_lots.len(), 3);
+        assert_eq!(disposal.open_lots[1].unrealized_gain_or_loss, dec!(-1000));
+        Ok(vec![(3, dec!(1)), (1, dec!(0.5))])
+    };
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final_with_lot_picker(
+        &[path], &settings, &mut picker, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let txn = txns_map.values().find(|txn| txn.user_memo == "Sell May").unwrap();
+    let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let cost_basis: Decimal = mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum();
+
+    assert_eq!(mvmts.len(), 2);
+    assert_eq!(cost_basis, dec!(-3500));
+
+    // The picked lots, by the IDs the reports show
+    let lot_ids: Vec<String> = mvmts.iter().map(|mvmt| mvmt.get_lot(&acct_map, &ars).id()).collect();
+    assert_eq!(lot_ids, ["A2-L1", "A2-L3"]);
+}
+
+#[test]
+fn _test_specific_id_reads_lot_selections_file() {
+
+    let dir = _TestDir::new("specific_id_reads_lot_selections_file");
+
+    let input = _bank_and_exchange_input("\
+1-1-19,0,Buy Jan,-1000,1
+2-1-19,0,Buy Feb,-5000,1
+3-1-19,0,Buy Mar,-3000,1
+5-1-19,6000,Sell May,6000,-1.5
+");
+    let mut settings = _test_settings();
+    settings.costing_method = InventoryCostingMethod::SpecificIdentification;
+    let path = dir.input_file("lot_selections.csv", &input);
+
+    // All of March's lot (by its lot ID), then half of January's (by its lot number)
+    let selections = dir.input_file("lot_selections_file.csv", "txn,lot,amount\n4,A2-L3,1\n4,1,0.5\n");
+    let mut picker = FileLotPicker::from_file(&selections).expect("Lot selections file failed to parse");
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final_with_lot_picker(
+        std::slice::from_ref(&path), &settings, &mut picker, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let txn = txns_map.get(&4).unwrap();
+    let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let cost_basis: Decimal = mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum();
+
+    assert_eq!(cost_basis, dec!(-3500));
+
+    // A disposal the file says nothing about
+    let selections = dir.input_file("lot_selections_file.csv", "txn,lot,amount\n3,1,1\n");
+    let mut picker = FileLotPicker::from_file(&selections).expect("Lot selections file failed to parse");
+    let error = match core_functions::import_and_process_final_with_lot_picker(
+        &[path], &settings, &mut picker, &mut core_functions::Timings::default()
+    ) {
+        Ok(_) => panic!("A disposal missing from the lot selections file must fail."),
+        Err(error) => error,
+    };
+
+    assert!(error.to_string().contains("Txn 4"), "{}", error);
+
+    let selections = dir.input_file("lot_selections_file.csv", "txn,lot,amount\n4,L,1\n");
+    assert!(FileLotPicker::from_file(&selections).is_err(), "An invalid lot must fail to parse.");
+}
+
+#[test]
+fn _test_import_map_remaps_exchange_export() {
+
+    let dir = _TestDir::new("import_map_remaps_exchange_export");
+
+    let export = "\
+Notes,Quantity BTC,Date,Quantity USD,Value
+Buy,0.5,1-1-19,-2000,
+Sell,-0.25,6-1-19,1500,1500
+";
+    let import_map: ImportMap = toml::from_str(r#"
+date = "Date"
+proceeds = "Value"
+memo = "Notes"
+
+[[accounts]]
+column = "Quantity USD"
+name = "Exchange"
+ticker = "USD"
+
+[[accounts]]
+column = "Quantity BTC"
+name = "Exchange"
+ticker = "BTC"
+"#).expect("Test import map failed to parse");
+
+    let mut settings = _test_settings();
+    settings.import_map = Some(import_map);
+    let path = dir.input_file("import_map.csv", export);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    assert_eq!(raw_accts.get(&2).unwrap().ticker, "BTC");
+
+    let txn = txns_map.get(&2).unwrap();
+    assert_eq!(txn.user_memo, "Sell");
+    assert_eq!(txn.proceeds, dec!(1500));
+
+    let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let cost_basis: Decimal = mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum();
+
+    assert_eq!(cost_basis, dec!(-1000));
+}
+
+#[test]
+fn _test_coinbase_export_converts_to_input_file() {
+
+    let dir = _TestDir::new("coinbase_export_converts_to_input_file");
+
+    let export = "\
+Transactions
+User,someone@example.com,abc123
+Timestamp,Transaction Type,Asset,Quantity Transacted,Spot Price Currency,Spot Price at Transaction,Subtotal,Total (inclusive of fees and/or spread),Fees and/or Spread,Notes
+2021-06-01T10:00:00Z,Sell,BTC,0.5,USD,40000,20000,19800,200,Sold 0.5 BTC for $19800
+2021-01-01T10:00:00Z,Buy,BTC,1,USD,30000,30000,30300,300,Bought 1 BTC for $30300
+2021-03-01T10:00:00Z,Convert,BTC,0.1,USD,50000,5000,5000,0,Converted 0.1 BTC to 2.5 ETH
+";
+    let mut settings = _test_settings();
+    settings.import_source = Some(Source::Coinbase);
+    let path = dir.input_file("coinbase_export.csv", export);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let tickers: Vec<&str> = (1..=raw_accts.len()).map(|num| raw_accts.get(&(num as u16)).unwrap().ticker.as_str()).collect();
+    assert_eq!(tickers, vec!["USD", "BTC", "ETH"]);
+
+    // Sorted by date, so the buy comes first
+    assert_eq!(txns_map.get(&1).unwrap().user_memo, "Buy: Bought 1 BTC for $30300");
+
+    let convert = txns_map.get(&2).unwrap();
+    assert_eq!(convert.proceeds, dec!(5000));
+
+    let mvmts = convert.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    assert_eq!(gain, dec!(1970));
+}
+
+#[test]
+fn _test_exchange_api_history_converts_to_input_file() {
+
+    // The USD account's side of the buy is left out, as is the pending send, and the Convert's sides are joined
+    let coinbase_pages = [
+        r#"{"pagination":{"next_uri":null},"data":[
+            {"id":"a1","type":"buy","status":"completed","amount":{"amount":"1.0","currency":"BTC"},"native_amount":{"amount":"30000.00","currency":"USD"},"created_at":"2021-01-01T10:00:00Z","details":{"title":"Bought Bitcoin"}},
+            {"id":"a2","type":"trade","status":"completed","amount":{"amount":"-0.1","currency":"BTC"},"native_amount":{"amount":"-5000.00","currency":"USD"},"created_at":"2021-03-01T10:00:00Z","trade":{"id":"t1"}},
+            {"id":"a3","type":"send","status":"pending","amount":{"amount":"-0.2","currency":"BTC"},"native_amount":{"amount":"-9000.00","currency":"USD"},"created_at":"2021-04-01T10:00:00Z"}
+        ]}"#.to_string(),
+        r#"{"data":[
+            {"id":"b1","type":"trade","status":"completed","amount":{"amount":"2.5","currency":"ETH"},"native_amount":{"amount":"5000.00","currency":"USD"},"created_at":"2021-03-01T10:00:00Z","trade":{"id":"t1"}},
+            {"id":"c1","type":"buy","status":"completed","amount":{"amount":"-30000.00","currency":"USD"},"native_amount":{"amount":"-30000.00","currency":"USD"},"created_at":"2021-01-01T10:00:00Z"},
+            {"id":"c2","type":"fiat_deposit","status":"completed","amount":{"amount":"30000.00","currency":"USD"},"native_amount":{"amount":"30000.00","currency":"USD"},"created_at":"2020-12-31T10:00:00Z"}
+        ]}"#.to_string(),
+    ];
+    let settings = _test_settings();
+
+    let csv = importers::api_history_to_input_file(Source::Coinbase, &coinbase_pages, None, &settings).unwrap();
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final_from_csv(
+        csv, &settings, &mut core_functions::Timings::default()
+    ).expect("The synced Coinbase history failed to process");
+
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(txns_map.get(&2).unwrap().user_memo, "buy: Bought Bitcoin");
+    let convert = txns_map.get(&3).unwrap();
+    assert_eq!(convert.proceeds, dec!(5000));
+    let mvmts = convert.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+    assert_eq!(gain, dec!(2000), "0.1 BTC, of a basis of 3000, converted for 5000 of ETH.");
+
+    // A deposit of BTC, then a buy and a sale of BTC for USD (with the fee in USD), timed in seconds since the epoch
+    let kraken_pages = [
+        r#"{"error":[],"result":{"count":2,"trades":{
+            "T1":{"pair":"XXBTZUSD","time":1609495200.5,"type":"buy","cost":"29000.0","fee":"50.0","vol":"1.0"},
+            "T2":{"pair":"XXBTZUSD","time":1622541600.0,"type":"sell","cost":"20000.0","fee":"40.0","vol":"0.5"}
+        }}}"#.to_string(),
+        r#"{"error":[],"result":{"count":1,"ledger":{
+            "L1":{"refid":"R1","time":1577872800.0,"type":"deposit","asset":"XXBT","amount":"0.2000","fee":"0.0000"}
+        }}}"#.to_string(),
+    ];
+
+    let after = NaiveDate::from_ymd_opt(2020, 1, 1);
+    let csv = importers::api_history_to_input_file(Source::Kraken, &kraken_pages, after, &settings).unwrap();
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final_from_csv(
+        csv, &settings, &mut core_functions::Timings::default()
+    ).expect("The synced Kraken history failed to process");
+
+    assert_eq!(txns_map.len(), 2, "The deposit of 2020-01-01 isn't after the date synced from.");
+    assert_eq!(txns_map.get(&1).unwrap().proceeds, dec!(29050));
+    assert_eq!(txns_map.get(&2).unwrap().proceeds, dec!(19960));
+
+    assert!(importers::api_history_to_input_file(Source::Kraken, &[r#"{"error":["EAPI:Invalid key"]}"#.to_string()], None, &settings).is_err());
+}
+
+#[test]
+fn _test_chain_history_converts_to_input_file() {
+
+    // A receipt of 0.5 BTC, then a send of 0.2 BTC whose net `result` includes its fee, on the pages of two addresses
+    let blockchain_pages = [
+        (ChainApi::Blockchain, r#"{"wallet":{"n_tx":2},"txs":[
+            {"hash":"h1","time":1609495200,"result":50000000}
+        ]}"#.to_string()),
+        (ChainApi::Blockchain, r#"{"wallet":{"n_tx":2},"txs":[
+            {"hash":"h2","time":1622541600,"result":-20010000}
+        ]}"#.to_string()),
+    ];
+    // A node lists each output of a send with the whole fee, and a failed ETH send still spends its gas
+    let node_pages = [(ChainApi::BitcoinRpc, r#"{"error":null,"result":[
+        {"txid":"n1","category":"receive","amount":1.0,"blocktime":1609495200},
+        {"txid":"n2","category":"send","amount":-0.3,"fee":-0.0001,"blocktime":1622541600},
+        {"txid":"n2","category":"send","amount":-0.1,"fee":-0.0001,"blocktime":1622541600}
+    ]}"#.to_string())];
+    let etherscan_pages = [(ChainApi::Etherscan, r#"{"status":"1","result":[
+        {"hash":"e1","timeStamp":"1609495200","from":"0xother","to":"0xABC","value":"2000000000000000000","gasUsed":"21000","gasPrice":"1000000000","isError":"0"},
+        {"hash":"e2","timeStamp":"1622541600","from":"0xabc","to":"0xother","value":"500000000000000000","gasUsed":"21000","gasPrice":"1000000000","isError":"1"},
+        {"hash":"e3","timeStamp":"1625133600","from":"0xabc","to":"0xother","value":"500000000000000000","gasUsed":"21000","gasPrice":"1000000000","isError":"0"}
+    ]}"#.to_string())];
+    let eth_addresses = ["0xabc".to_string()];
+    let settings = _test_settings();
+
+    let quantities = |pages: &[(ChainApi, String)]| -> Vec<Decimal> {
+        let csv = importers::chain_history_to_input_file(pages, &eth_addresses, &settings).unwrap();
+        let (_raw_accts, _acct_map, ars, txns_map) = core_functions::import_and_process_final_from_csv(
+            csv, &settings, &mut core_functions::Timings::default()
+        ).expect("The on-chain history failed to process");
+        (1..=txns_map.len() as u32)
+            .map(|num| txns_map[&num].action_record_idx_vec.iter().map(|ar_num| ars[ar_num].amount).sum())
+            .collect()
+    };
+
+    assert_eq!(quantities(&blockchain_pages), vec![dec!(0.5), dec!(-0.2001)]);
+    assert_eq!(quantities(&node_pages), vec![dec!(1.0), dec!(-0.4001)], "The fee is charged once per transaction.");
+    assert_eq!(quantities(&etherscan_pages), vec![dec!(2), dec!(-0.000021), dec!(-0.500021)]);
+
+    let error = [(ChainApi::Etherscan, r#"{"status":"0","message":"NOTOK","result":"Invalid API Key"}"#.to_string())];
+    assert!(importers::chain_history_to_input_file(&error, &eth_addresses, &settings).is_err());
+}
+
+#[test]
+fn _test_row_format_creates_accounts_not_in_accounts_file() {
+
+    let dir = _TestDir::new("row_format_creates_accounts_not_in_accounts_file");
+
+    let accounts = "\
+account,name,ticker,margin,address
+1,Bank,USD,no,
+2,Wallet,BTC,no,bc1qexample
+";
+    let rows = "\
+txDate,proceeds,memo,account,ticker,quantity,account,ticker,quantity
+1-1-19,0,Buy,1,,-2000,Exchange,BTC,0.5
+2-1-19,0,Withdrawal,Exchange,BTC,-0.5,Wallet,,0.5
+6-1-19,3000,Sell,wallet,BTC,-0.25,Bank,USD,3000
+";
+    let mut settings = _test_settings();
+    settings.input_schema = InputSchema::Rows;
+    settings.accounts_file = Some(AccountsFile::from_reader(accounts.as_bytes()).expect("Test accounts file failed to parse"));
+    let path = dir.input_file("row_format.csv", rows);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    // The accounts file's accounts keep their numbers, and the exchange's is created after them
+    assert_eq!(raw_accts.len(), 3);
+    assert_eq!(raw_accts.get(&2).unwrap().name, "Wallet");
+    assert_eq!(raw_accts.get(&3).unwrap().name, "Exchange");
+    assert_eq!(raw_accts.get(&3).unwrap().ticker, "BTC");
+    assert_eq!(settings.accounts_file.as_ref().unwrap().address(2), Some("bc1qexample"));
+
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0.25));
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(0));
+
+    let sell = txns_map.get(&3).unwrap();
+    let mvmts = sell.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    assert_eq!(gain, dec!(2000));
+
+    // An account the accounts file doesn't have, without a ticker, can't be created
+    let rows = "\
+txDate,proceeds,memo,account,quantity
+1-1-19,0,Deposit,Exchange,0.5
+";
+    let err = accounts_file::rows_to_input_file(rows.as_bytes(), settings.accounts_file.as_ref(), Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("its ticker must be given"));
+}
+
+#[test]
+fn _test_long_schema_groups_legs_by_txid() {
+
+    let dir = _TestDir::new("long_schema_groups_legs_by_txid");
+
+    let accounts = "\
+account,name,ticker,margin,address
+1,Bank,USD,no,
+";
+    let legs = "\
+date,txid,account,asset,amount,proceeds,memo
+1-1-19,a,Bank,,-2000,,Buy
+2-1-19,b,Exchange,BTC,-0.25,,Withdrawal
+1-1-19,a,Exchange,BTC,0.5,0,Buy
+2-1-19,b,Wallet,BTC,0.25,,To cold storage
+6-1-19,,Wallet,BTC,0.1,0,Gift
+";
+    let mut settings = _test_settings();
+    settings.input_schema = InputSchema::Long;
+    settings.accounts_file = Some(AccountsFile::from_reader(accounts.as_bytes()).expect("Test accounts file failed to parse"));
+    let path = dir.input_file("long_schema.csv", legs);
+
+    let (raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    // Legs sharing a txid are one transaction, wherever they appear, and a leg without one is its own
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(txns_map.get(&2).unwrap().user_memo, "Withdrawal; To cold storage");
+    assert_eq!(raw_accts.get(&2).unwrap().name, "Exchange");
+    assert_eq!(raw_accts.get(&3).unwrap().name, "Wallet");
+
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0.25));
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(0.35));
+
+    // A transaction's legs can't give it different dates
+    let legs = "\
+date,txid,account,asset,amount
+1-1-19,a,Bank,,-2000
+1-2-19,a,Exchange,BTC,0.5
+";
+    let err = long_schema::long_to_input_file(legs.as_bytes(), settings.accounts_file.as_ref(), Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("the date (1-2-19) of transaction a differs"));
+}
+
+#[test]
+fn _test_input_files_merged_by_date() {
+
+    let dir = _TestDir::new("input_files_merged_by_date");
+
+    let exchange = _bank_and_exchange_input("\
+1-1-19,0,Buy,-1000,1
+6-1-19,900,Sell,900,-0.5
+");
+    let wallet = "\
+txDate,proceeds,memo,1,2
+,,,Exchange,Wallet
+,,,BTC,BTC
+,,,no,no
+3-1-19,0,Withdraw,-0.25,0.25
+";
+    let settings = _test_settings();
+    let exchange_path = dir.input_file("merge_exchange.csv", &exchange);
+    let wallet_path = dir.input_file("merge_wallet.csv", wallet);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[exchange_path, wallet_path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input files failed to process");
+
+    // The Exchange BTC account is in both files
+    assert_eq!(raw_accts.len(), 3);
+    assert_eq!(raw_accts.get(&3).unwrap().name, "Wallet");
+
+    let memos: Vec<&str> = (1..=txns_map.len()).map(|num| txns_map.get(&(num as u32)).unwrap().user_memo.as_str()).collect();
+    assert_eq!(memos, vec!["Buy", "Withdraw", "Sell"]);
+
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0.25));
+
+    let sale = txns_map.get(&3).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    assert_eq!(gain, dec!(400));
+}
+
+#[test]
+fn _test_low_memory_merge_spills_to_disk() {
+
+    let dir = _TestDir::new("low_memory_merge_spills_to_disk");
+
+    let exchange = "\
+txDate,proceeds,memo,txType,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+6-1-19,900,\"Sell, partly\",,900,-0.5
+1-1-19,0,Buy,,-1000,1
+";
+    let wallet = "\
+txDate,proceeds,memo,1,2
+,,,Exchange,Wallet
+,,,BTC,BTC
+,,,no,no
+3-1-19,0,Withdraw,-0.25,0.25
+1-1-19,0,Same day,-0.1,0.1
+";
+    let exchange_path = dir.input_file("low_memory_exchange.csv", exchange);
+    let wallet_path = dir.input_file("low_memory_wallet.csv", wallet);
+    let paths = [exchange_path, wallet_path];
+
+    let merged = |settings: &ImportProcessParameters| -> String {
+        let inputs = csv_import_accts_txns::open_input_files(&paths, settings).expect("Test input files failed to open");
+        let mut merged = String::new();
+        csv_import_accts_txns::merge_input_files(inputs, settings).expect("Test input files failed to merge")
+            .read_to_string(&mut merged).unwrap();
+        merged
+    };
+
+    let mut settings = _test_settings();
+    let in_memory = merged(&settings);
+    settings.low_memory = true;
+    let spilled = merged(&settings);
+
+    // The rows read back from the spill file are those merged in memory, in the same (date, then file) order
+    assert_eq!(spilled, in_memory);
+    assert!(spilled.lines().nth(4).unwrap().starts_with("1-1-19,0,Buy,"));
+    assert!(spilled.lines().nth(5).unwrap().starts_with("1-1-19,0,Same day,"));
+    assert!(spilled.contains("\"Sell, partly\""));
+
+    let (_raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &paths, &settings, &mut core_functions::Timings::default()
+    ).expect("Test input files failed to process");
+
+    assert_eq!(txns_map.len(), 4);
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(0.35));
+}
+
+#[test]
+fn _test_generated_test_data_imports() {
+
+    let generate = |test_data: TestData| -> Vec<u8> {
+        let mut csv = Vec::new();
+        test_data.write(&mut csv).expect("Test data failed to generate");
+        csv
+    };
+
+    let csv = generate(TestData { rows: 2000, accounts: 7, ..Default::default() });
+
+    // The same settings always write the same file, and another seed another
+    assert_eq!(csv, generate(TestData { rows: 2000, accounts: 7, ..Default::default() }));
+    assert_ne!(csv, generate(TestData { rows: 2000, accounts: 7, seed: 2, ..Default::default() }));
+    assert!(TestData { accounts: 1, ..Default::default() }.write(Vec::new()).is_err());
+
+    let settings = _test_settings();
+    let (raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final_from_csv(
+        csv, &settings, &mut core_functions::Timings::default()
+    ).expect("Test data failed to process");
+
+    assert_eq!(txns_map.len(), 2000);
+    assert_eq!(raw_accts.len(), 7);
+    assert_eq!(raw_accts.get(&6).unwrap().name, "Exchange");
+    assert_eq!(raw_accts.get(&6).unwrap().ticker, "XMR");
+
+    // Each kind of transaction is generated, and no account but the bank's is ever negative
+    for kind in ["Buy", "Sell", "Transfer", "Trade"] {
+        assert!(txns_map.values().any(|txn| txn.user_memo.starts_with(kind)), "No {} was generated", kind);
+    }
+    for num in 2..=7 {
+        assert!(acct_map.get(&num).unwrap().get_sum_of_amts_in_lots() >= dec!(0));
+    }
+}
+
+#[test]
+fn _test_template_imports_with_sample_rows() {
+
+    let acct = |name: &str, ticker: &str| TemplateAccount { name: name.to_string(), ticker: ticker.to_string(), is_margin: false };
+
+    let template = Template {
+        home_currency: "USD".to_string(),
+        date_format: "%m-%d-%y".to_string(),
+        accounts: vec![acct("Kraken", "BTC"), acct("Kraken", "ETH"), acct("Ledger", "BTC")],
+    };
+
+    let mut csv = Vec::new();
+    template.write(&mut csv).expect("Template failed to write");
+    assert!(Template { accounts: Vec::new(), ..template.clone() }.write(Vec::new()).is_err());
+
+    let settings = _test_settings();
+    let (raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final_from_csv(
+        csv, &settings, &mut core_functions::Timings::default()
+    ).expect("Template failed to process");
+
+    // The Bank, then the accounts in order, and a buy, a transfer, a trade and a sale that leave the first account empty
+    assert_eq!(raw_accts.len(), 4);
+    assert_eq!(raw_accts.get(&1).unwrap().name, "Bank");
+    assert_eq!(raw_accts.get(&4).unwrap().name, "Ledger");
+    assert_eq!(txns_map.len(), 4);
+    assert!(txns_map.values().all(|txn| txn.user_memo.starts_with("Sample")));
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0));
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(10));
+    assert_eq!(acct_map.get(&4).unwrap().get_sum_of_amts_in_lots(), dec!(1));
+}
+
+#[test]
+fn _test_anonymized_input_scales_gains() {
+
+    let input = "\
+txDate,proceeds,memo,payer,1,2,3
+,,,,Bank,Coinbase,Coinbase
+,,,,USD,BTC,ETH
+,,,,no,no,no
+1-1-19,0,Bought at my bank,,\"-1,000\",0.33333333,
+2-1-19,400,Swap,,,-0.1,2.5
+3-1-19,,Salary,Acme Corp,,0.01,
+6-1-19,900,Sold for rent,,900,-0.2,
+";
+    let settings = _test_settings();
+
+    // The home currency (USD) is multiplied by 2, BTC by 3, and ETH by 5
+    let mut factors = [2, 3, 5].into_iter();
+    let mut anonymized = Vec::new();
+    anonymize::anonymize(input.as_bytes(), &settings, &mut anonymized, &mut || factors.next().unwrap())
+        .expect("Test input file failed to anonymize");
+    let anonymized = String::from_utf8(anonymized).unwrap();
+
+    let lines: Vec<&str> = anonymized.lines().collect();
+    assert_eq!(lines[2], ",,,,USD,COIN1,COIN2");
+    assert_eq!(lines[4], "1-1-19,0,,,-2000,0.99999999,");
+    assert_eq!(lines[6], "3-1-19,,,Payer 1,,0.03,");
+    assert!(!anonymized.contains("rent") && !anonymized.contains("Acme"));
+
+    let gains = |csv: &str| -> Decimal {
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final_from_csv(
+            csv.as_bytes().to_vec(), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+        txns_map.values()
+            .filter_map(|txn| txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).ok())
+            .flatten()
+            .map(|mvmt| mvmt.get_orig_gain_or_loss())
+            .sum()
+    };
+
+    // Every lot comes out as before, so each gain is just twice the original's
+    assert!(!gains(input).is_zero());
+    assert_eq!(gains(&anonymized), gains(input) * dec!(2));
+}
+
+#[test]
+fn _test_resumed_run_carries_forward_open_lots() {
+
+    let dir = _TestDir::new("resumed_run_carries_forward_open_lots");
+
+    let first_year = _bank_and_exchange_input("\
+1-1-19,0,Buy,-1000,1
+6-1-19,900,Sell,900,-0.5
+");
+    let second_year = _bank_and_exchange_input("\
+3-1-20,2000,Sell,2000,-0.5
+");
+    let mut settings = _test_settings();
+    let first_path = dir.input_file("resume_first.csv", &first_year);
+    let second_path = dir.input_file("resume_second.csv", &second_year);
+
+    let (raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&first_path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let state = RunState::from_processed(&settings, &raw_accts, &acct_map, &txns_map).unwrap();
+
+    let state_path = dir.path("resume_state.toml");
+    state.write_to_file(&state_path).unwrap();
+
+    let state = RunState::from_file(&state_path).unwrap();
+
+    // The USD account has no basis to carry forward
+    assert_eq!(state.through, NaiveDate::from_ymd_opt(2019, 6, 1).unwrap());
+    assert_eq!(state.lots.len(), 1);
+    assert_eq!(state.lots[0].amount, dec!(0.5));
+    assert_eq!(state.lots[0].cost_basis, dec!(500));
+
+    settings.resume_from = Some(state.clone());
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&second_path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process when resuming");
+
+    assert_eq!(txns_map.get(&1).unwrap().kind, TxKind::CarriedForward);
+
+    let sale = txns_map.get(&2).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    // Held since 1-1-19 at a basis of 500, so the gain is long-term
+    assert_eq!(gain, dec!(1500));
+    assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), Term::LT);
+
+    // A transaction from before the saved state's date can't be appended
+    assert!(core_functions::import_and_process_final(
+        std::slice::from_ref(&first_path), &settings, &mut core_functions::Timings::default()
+    ).is_err());
+}
+
+#[test]
+fn _test_year_end_holdings_as_opening_balances() {
+
+    let dir = _TestDir::new("year_end_holdings_as_opening_balances");
+
+    let both_years = _bank_and_exchange_input("\
+7-1-19,0,Buy,-1000,1
+1-15-20,800,Sell,800,-0.4
+");
+    let second_year = _bank_and_exchange_input("\
+1-15-20,800,Sell,800,-0.4
+");
+    let mut settings = _test_settings();
+    let both_path = dir.input_file("holdings_both.csv", &both_years);
+    let second_path = dir.input_file("holdings_second.csv", &second_year);
+
+    let (raw_accts, acct_map, _ars, _txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&both_path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    // Only the 2019 movements count toward the year-end holdings
+    let year_end = NaiveDate::from_ymd_opt(2019, 12, 31).unwrap();
+    let holdings = RunState::as_of(&settings, &raw_accts, &acct_map, year_end).unwrap();
+
+    assert_eq!(holdings.lots.len(), 1);
+    assert_eq!(holdings.lots[0].amount, dec!(1));
+    assert_eq!(holdings.lots[0].cost_basis, dec!(1000));
+
+    let rows: Vec<String> = holdings.opening_balance_rows().iter().map(|row| row.join(",")).collect();
+    let holdings_path = dir.input_file("holdings.csv", &rows.join("\n"));
+
+    let opening = RunState::from_opening_balances(&holdings_path, &settings.home_currency).unwrap();
+    assert_eq!(opening.through, year_end);
+
+    settings.resume_from = Some(opening);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&second_path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process from opening balances");
+
+    let sale = txns_map.get(&2).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    assert_eq!(gain, dec!(400));
+    assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), Term::ST);
+}
+
+#[test]
+fn _test_opening_lots_acquired_before_tracked_history() {
+
+    let dir = _TestDir::new("opening_lots_acquired_before_tracked_history");
+
+    //  Written by hand, without the "As of" and "Basis date" columns
+    let opening_lots = "\
+Account,Ticker,Amount,Date acquired,Cost basis
+Wallet,BTC,1,2016-03-01,400
+Exchange,ETH,10,2017-06-01,3000
+";
+    let recent = "\
+txDate,proceeds,memo,1,2
+,,,Bank,Wallet
+,,,USD,BTC
+,,,no,no
+2-1-21,20000,Sell,20000,-0.5
+";
+    let mut settings = _test_settings();
+    let opening_path = dir.input_file("opening_lots.csv", opening_lots);
+    let recent_path = dir.input_file("opening_lots_recent.csv", recent);
+
+    let opening = RunState::from_opening_balances(&opening_path, &settings.home_currency).unwrap();
+
+    assert_eq!(opening.through, NaiveDate::from_ymd_opt(2017, 6, 1).unwrap());
+    assert_eq!(opening.lots[0].basis_date, NaiveDate::from_ymd_opt(2016, 3, 1).unwrap());
+
+    settings.resume_from = Some(opening);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&recent_path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process from opening lots");
+
+    //  The ETH account isn't in the CSV Input File, so it's added
+    assert_eq!(raw_accts.len(), 3);
+
+    let sale = txns_map.get(&3).unwrap();
+    let mvmts = sale.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_orig_gain_or_loss()).sum();
+
+    assert_eq!(gain, dec!(19800));
+    assert_eq!(mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days), Term::LT);
+}
+
+#[test]
+fn _test_wash_sale_defers_disallowed_loss() {
+
+    let dir = _TestDir::new("wash_sale_defers_disallowed_loss");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,0,Buy,-1000,1
+2-1-20,600,Sell at a loss,600,-1
+2-15-20,0,Buy back,-700,1
+6-1-20,1500,Sell,1500,-1
+");
+    let mut settings = _test_settings();
+    settings.wash_sale_window = Some(30);
+    let path = dir.input_file("wash_sale.csv", &contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let gain = |txn_num: u32| -> Decimal {
+        let txn = txns_map.get(&txn_num).unwrap();
+        txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap()
+            .iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum()
+    };
+
+    // The 400 loss is disallowed, since BTC was bought back 14 days later...
+    let mvmts_of = |txn_num: u32, ticker: &str| -> Vec<Rc<Movement>> {
+        let txn = txns_map.get(&txn_num).unwrap();
+        txn.action_record_idx_vec.iter().map(|ar_num| ars.get(ar_num).unwrap())
+            .filter(|ar| raw_accts.get(&ar.account_key).unwrap().ticker == ticker)
+            .flat_map(|ar| ar.get_mvmts_in_ar_in_lot_date_order())
+            .collect()
+    };
+    assert_eq!(mvmts_of(2, "BTC")[0].wash_sale_adjustment.get(), dec!(400));
+    assert_eq!(gain(2), dec!(0));
+
+    // ...and added to the basis of the replacement lot, whose own acquisition has no gain
+    let replacement_mvmts = mvmts_of(3, "BTC");
+    assert_eq!(replacement_mvmts[0].cost_basis.get(), dec!(1100));
+    assert_eq!(replacement_mvmts[0].get_lk_gain_or_loss(), dec!(0));
+
+    // The loss is recognized when the replacement lot is sold, so the total gain is unchanged
+    assert_eq!(gain(4), dec!(400));
+
+    // Without a replacement purchase inside the window, the loss is allowed
+    settings.wash_sale_window = Some(10);
+    let path = dir.input_file("wash_sale.csv", &contents);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let txn = txns_map.get(&2).unwrap();
+    let loss: Decimal = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map)
+        .unwrap().iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum();
+    assert_eq!(loss, dec!(-400));
+}
+
+#[test]
+fn _test_share_pooling_matches_same_day_then_30_days_then_pool() {
+
+    let dir = _TestDir::new("share_pooling_matches_same_day_then_30_days_then_pool");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Kraken
+,,,GBP,BTC,BTC
+,,,no,no,no
+1-1-20,0,Buy,-1000,1,
+2-1-20,0,Buy,-2000,,1
+3-1-20,1000,Sell,1000,-0.5,
+3-1-20,0,Buy back the same day,-380,0.2,
+3-20-20,0,Buy back within 30 days,-160,,0.1
+6-1-20,1400,Sell,1400,-0.7,
+";
+    let mut settings = _test_settings();
+    settings.home_currency = "GBP".to_string();
+    settings.costing_method = InventoryCostingMethod::SharePoolingSection104;
+    let path = dir.input_file("share_pooling.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let cost_basis_and_gain = |txn_num: u32| -> (Decimal, Decimal) {
+        let txn = txns_map.get(&txn_num).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+        (mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum(), mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum())
+    };
+
+    // 0.2 BTC is matched with the same day's 380 purchase, 0.1 with the 160 purchase 19 days later, and the remaining 0.2
+    // with the pool of both accounts' earlier purchases (2 BTC costing 3000), at 1500 per BTC
+    assert_eq!(cost_basis_and_gain(3), (dec!(-840), dec!(160)));
+
+    // Neither later purchase joined the pool, which still averages 1500 per BTC, although the Exchange account's own
+    // lots average less
+    assert_eq!(cost_basis_and_gain(6), (dec!(-1050), dec!(350)));
+}
+
+#[test]
+fn _test_adjusted_cost_base_denies_superficial_loss() {
+
+    let dir = _TestDir::new("adjusted_cost_base_denies_superficial_loss");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Kraken
+,,,CAD,BTC,BTC
+,,,no,no,no
+1-1-20,0,Buy,-10000,1,
+2-1-20,0,Buy,-14000,,1
+3-1-20,9000,Sell at a loss,9000,-1,
+6-1-20,8000,Sell,8000,,-0.5
+";
+    let mut settings = _test_settings();
+    settings.home_currency = "CAD".to_string();
+    settings.costing_method = InventoryCostingMethod::AdjustedCostBase;
+    let path = dir.input_file("adjusted_cost_base.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let cost_basis_denied_and_gain = |txn_num: u32| -> (Decimal, Decimal, Decimal) {
+        let txn = txns_map.get(&txn_num).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+        (
+            mvmts.iter().map(|mvmt| mvmt.cost_basis.get()).sum(),
+            mvmts.iter().map(|mvmt| mvmt.wash_sale_adjustment.get()).sum(),
+            mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum(),
+        )
+    };
+
+    // Both accounts' BTC average 12000, and the Kraken BTC bought 29 days earlier is still held 30 days later, so the
+    // whole 3000 loss is superficial
+    assert_eq!(cost_basis_denied_and_gain(3), (dec!(-12000), dec!(3000), dec!(0)));
+
+    // The denied loss was added to the ACB of the remaining 1 BTC, although the Kraken lot itself cost 14000
+    assert_eq!(cost_basis_denied_and_gain(4), (dec!(-7500), dec!(0), dec!(500)));
+}
+
+#[test]
+fn _test_long_term_days_sets_holding_period_and_year_totals() {
+
+    let dir = _TestDir::new("long_term_days_sets_holding_period_and_year_totals");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Exchange
+,,,USD,BTC,ETH
+,,,no,no,no
+1-1-20,0,Buy,-1000,1,
+1-1-20,0,Buy,-500,,10
+3-1-20,600,Sell,600,-0.5,
+12-1-20,300,Sell,300,,-5
+2-1-21,700,Sell,700,-0.5,
+";
+    let mut settings = _test_settings();
+    settings.long_term_days = 90;
+    let path = dir.input_file("long_term_days.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let term = |txn_num: u32| -> Term {
+        let txn = txns_map.get(&txn_num).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+        mvmts[0].get_term(&acct_map, &ars, &txns_map, settings.long_term_days)
+    };
+
+    // 60 days is short-term and 335 days long-term when the threshold is 90 days (both are short-term by default)
+    assert_eq!(term(3), Term::ST);
+    assert_eq!(term(4), Term::LT);
+    assert_eq!(term(5), Term::LT);
+
+    let assets = summary::gains_by_asset_and_year(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let totals: Vec<(i32, &str, Decimal, Decimal)> = assets.iter()
+        .map(|a| (a.year, a.ticker.as_str(), a.realized_st, a.realized_lt))
+        .collect();
+
+    assert_eq!(totals, vec![
+        (2020, "BTC", dec!(100), dec!(0)),
+        (2020, "ETH", dec!(0), dec!(50)),
+        (2021, "BTC", dec!(0), dec!(200)),
+    ]);
+}
+
+#[test]
+fn _test_tax_year_limits_dates_and_names_files() {
+
+    let mut settings = _test_settings();
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    assert!(settings.is_in_tax_year(date("2020-06-01")));
+    assert_eq!(settings.tax_year_file_name("C4_Txns_mvmts_detail.csv"), "C4_Txns_mvmts_detail.csv");
+
+    settings.tax_year = Some(2021);
+
+    assert!(!settings.is_in_tax_year(date("2020-12-31")));
+    assert!(settings.is_in_tax_year(date("2021-01-01")));
+    assert!(!settings.is_in_tax_year(date("2022-01-01")));
+    assert_eq!(settings.tax_year_file_name("C4_Txns_mvmts_detail.csv"), "C4_Txns_mvmts_detail_2021.csv");
+    assert_eq!(settings.tax_year_file_name("J1_Journal_Entries.txt"), "J1_Journal_Entries_2021.txt");
+}
+
+#[test]
+fn _test_report_file_names_take_prefix_and_date() {
+
+    let mut settings = _test_settings();
+
+    assert_eq!(settings.report_file_name("C1_Account_sums.csv"), "C1_Account_sums.csv");
+
+    settings.file_name_prefix = Some("alice_".to_string());
+    settings.file_name_date = Some(NaiveDate::from_ymd_opt(2024, 4, 15).unwrap());
+    settings.tax_year = Some(2023);
+
+    assert_eq!(settings.report_file_name("C1_Account_sums.csv"), "alice_C1_Account_sums_2024-04-15.csv");
+    assert_eq!(
+        settings.report_file_name(&settings.tax_year_file_name("C4_Txns_mvmts_detail.csv")),
+        "alice_C4_Txns_mvmts_detail_2023_2024-04-15.csv"
+    );
+    assert_eq!(TableFormat::Json.file_name("C1_Account_sums.csv"), "C1_Account_sums.json");
+    assert_eq!(TableFormat::Txt.file_name("C1_Account_sums.csv"), "C1_Account_sums.txt");
+}
+
+#[test]
+fn _test_report_filter_limits_accounts_and_dates() {
+
+    let mut settings = _test_settings();
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    let btc = RawAccount { account_num: 3, name: "Wallet".to_string(), ticker: "BTC".to_string(), is_margin: false };
+    let eth = RawAccount { account_num: 4, name: "Wallet".to_string(), ticker: "ETH".to_string(), is_margin: false };
+
+    assert!(settings.report_filter.is_empty());
+    assert!(settings.is_reported_account(&eth));
+
+    settings.report_filter = ReportFilter {
+        asset: Some("btc".to_string()),
+        account: None,
+        accounts: None,
+        from: Some(date("2020-01-01")),
+        to: Some(date("2020-12-31")),
+        tag: None,
+    };
+
+    assert!(settings.is_reported_account(&btc));
+    assert!(!settings.is_reported_account(&eth));
+    assert!(!settings.is_reported_date(date("2019-12-31")));
+    assert!(settings.is_reported_date(date("2020-01-01")));
+    assert!(settings.is_reported_date(date("2020-12-31")));
+    assert!(!settings.is_reported_date(date("2021-01-01")));
+
+    settings.report_filter.account = Some(4);
+    assert!(!settings.is_reported_account(&btc));
+
+    settings.tax_year = Some(2021);
+    assert!(!settings.is_reported_date(date("2020-06-01")));
+}
+
+#[test]
+fn _test_fees_adjust_basis_and_proceeds() {
+
+    let dir = _TestDir::new("fees_adjust_basis_and_proceeds");
+
+    let contents = "\
+txDate,proceeds,memo,fee,1,2,3
+,,,,Bank,Exchange,Wallet
+,,,,USD,BTC,BTC
+,,,,no,no,no
+1-1-20,1000,Buy,10,-1000,1,
+2-1-20,600,Transfer,12,,-0.5,0.49
+3-1-20,600,Sell,5,595,-0.5,
+";
+    let mut settings = _test_settings();
+    settings.home_curr_leg_is_basis = false;
+    let path = dir.input_file("fees.csv", contents);
+
+    // The (cost basis, proceeds) of each action record, by txn number, in order
+    let process = |settings: &ImportProcessParameters| -> Vec<Vec<(Decimal, Decimal)>> {
+        let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+        (1..=txns_map.len() as u32).map(|txn_num| {
+            txns_map.get(&txn_num).unwrap().action_record_idx_vec.iter().map(|ar_num| {
+                let mvmts = ars.get(ar_num).unwrap().get_mvmts_in_ar_in_lot_date_order();
+                (mvmts.iter().map(|m| m.cost_basis.get()).sum(), mvmts.iter().map(|m| m.proceeds.get()).sum())
+            }).collect()
+        }).collect()
+    };
+
+    let txns = process(&settings);
+
+    // The buy's fee is capitalized into its basis, and the sale's fee reduces its proceeds
+    assert_eq!(txns[0][1].0, dec!(1010));
+    assert_eq!(txns[3][0], (dec!(-505), dec!(595)));
+
+    // The transfer's fee (0.01 BTC) is split off into its own txn, as an expense by default...
+    assert_eq!(txns.len(), 4);
+    assert_eq!(txns[1][0].0, dec!(-494.90));
+    assert_eq!(txns[2], vec![(dec!(-10.10), dec!(10.10))]);
+
+    // ...and as a disposal if chosen
+    settings.transfer_fee_is_disposal = true;
+    assert_eq!(process(&settings)[2], vec![(dec!(-10.10), dec!(12))]);
+}
+
+#[test]
+fn _test_income_type_groups_income_by_kind_and_year() {
+
+    let dir = _TestDir::new("income_type_groups_income_by_kind_and_year");
+
+    let contents = "\
+txDate,proceeds,memo,incomeType,1,2
+,,,,Pool,Exchange
+,,,,BTC,ETH
+,,,,no,no
+1-1-20,100,Block reward,mining,0.01,
+2-1-20,50,Reward,staking,,1
+3-1-20,20,Reward,Staking,,0.5
+4-1-20,10,Misc,,,0.1
+1-1-21,200,Block reward,mining,0.02,
+";
+    let settings = _test_settings();
+    let path = dir.input_file("income_type.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    assert_eq!(txns_map.get(&3).unwrap().income_type, Some(IncomeType::Staking));
+    assert_eq!(txns_map.get(&4).unwrap().income_type, None);
+
+    let totals = summary::income_by_type_and_year(&settings.home_currency, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let totals: Vec<(i32, Option<IncomeType>, Decimal)> = totals.iter().map(|t| (t.year, t.income_type, t.income)).collect();
+
+    // Untyped income sorts after the typed income of its year
+    assert_eq!(totals, vec![
+        (2020, Some(IncomeType::Mining), dec!(100)),
+        (2020, Some(IncomeType::Staking), dec!(70)),
+        (2020, None, dec!(10)),
+        (2021, Some(IncomeType::Mining), dec!(200)),
+    ]);
+
+    assert!(IncomeType::from_field("gift").is_err());
+}
+
+#[test]
+fn _test_quarterly_summary_buckets_by_fiscal_quarter() {
+
+    let dir = _TestDir::new("quarterly_summary_buckets_by_fiscal_quarter");
+
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    assert_eq!(summary::fiscal_quarter(date("2020-03-31"), 1), (2020, 1));
+    assert_eq!(summary::fiscal_quarter(date("2020-04-01"), 1), (2020, 2));
+    assert_eq!(summary::fiscal_quarter(date("2020-03-31"), 4), (2019, 4));
+    assert_eq!(summary::fiscal_quarter(date("2020-12-31"), 10), (2020, 1));
+
+    let contents = "\
+txDate,proceeds,memo,incomeType,1,2,3
+,,,,Bank,Exchange,Pool
+,,,,USD,BTC,BTC
+,,,,no,no,no
+1-15-20,0,Buy,,-1000,1,
+2-1-20,100,Block reward,mining,,,0.01
+5-1-20,600,Sell,,600,-0.5,
+7-1-21,300,Sell,,300,-0.25,
+";
+    let mut settings = _test_settings();
+    settings.fiscal_year_start_month = 4;
+    let path = dir.input_file("quarterly_summary.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let quarters = summary::gains_and_income_by_quarter(
+        &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+    ).unwrap();
+    let totals: Vec<(i32, u32, NaiveDate, Decimal, Decimal, Decimal)> = quarters.iter()
+        .map(|q| (q.fiscal_year, q.quarter, q.start, q.realized_st, q.realized_lt, q.income))
+        .collect();
+
+    // The fiscal years begin in April, and each with anything in it is listed in full
+    assert_eq!(totals.len(), 12);
+    assert_eq!(totals[3], (2019, 4, date("2020-01-01"), dec!(0), dec!(0), dec!(100)));
+    assert_eq!(totals[4], (2020, 1, date("2020-04-01"), dec!(100), dec!(0), dec!(0)));
+    assert_eq!(totals[5], (2020, 2, date("2020-07-01"), dec!(0), dec!(0), dec!(0)));
+    assert_eq!(totals[9], (2021, 2, date("2021-07-01"), dec!(0), dec!(50), dec!(0)));
+    assert_eq!(quarters[11].end, date("2022-03-31"));
+}
+
+/// With a `FISCAL_YEAR_END` of 06-30, gains, income and expenses should be grouped by fiscal years from July through
+/// June, each named for the year it begins in, and a `tax_year` should choose the transactions of one.
+#[test]
+fn _test_fiscal_year_end_groups_years_and_tax_year() {
+
+    let dir = _TestDir::new("fiscal_year_end_groups_years_and_tax_year");
+
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    assert_eq!(summary::parse_fiscal_year_end("06-30").unwrap(), 7);
+    assert_eq!(summary::parse_fiscal_year_end("12-31").unwrap(), 1);
+    assert_eq!(summary::parse_fiscal_year_end("02-28").unwrap(), 3);
+    assert!(summary::parse_fiscal_year_end("06-15").is_err());
+    assert!(summary::parse_fiscal_year_end("13-31").is_err());
+    assert!(summary::parse_fiscal_year_end("0630").is_err());
+
+    assert_eq!(summary::fiscal_year_dates(2023, 7), (date("2023-07-01"), date("2024-06-30")));
+    assert_eq!(summary::fiscal_year_dates(2023, 1), (date("2023-01-01"), date("2023-12-31")));
+
+    let contents = "\
+txDate,proceeds,memo,incomeType,1,2,3
+,,,,Bank,Exchange,Pool
+,,,,USD,BTC,BTC
+,,,,no,no,no
+1-15-20,0,Buy,,-1000,1,
+6-30-20,100,Block reward,mining,,,0.01
+7-1-20,600,Sell,,600,-0.5,
+5-1-21,300,Sell,,300,-0.25,
+";
+    let mut settings = _test_settings();
+    settings.fiscal_year_start_month = summary::parse_fiscal_year_end("06-30").unwrap();
+    let path = dir.input_file("fiscal_year_end.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let years = summary::gains_by_year(
+        &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+    ).unwrap();
+    let years: Vec<(i32, Decimal)> = years.iter().map(|y| (y.year, y.realized_total())).collect();
+    assert_eq!(years, vec![(2020, dec!(150))], "Both sales are in the fiscal year beginning 2020-07-01.");
+
+    let incomes = summary::income_by_type_and_year(
+        &settings.home_currency, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+    ).unwrap();
+    assert_eq!(incomes.iter().map(|t| t.year).collect::<Vec<i32>>(), vec![2019], "June 2020 ends the 2019 fiscal year.");
+
+    settings.tax_year = Some(2020);
+    assert!(!settings.is_in_tax_year(date("2020-06-30")));
+    assert!(settings.is_in_tax_year(date("2020-07-01")) && settings.is_in_tax_year(date("2021-06-30")));
+    assert_eq!(settings.fiscal_year_span(2020), "from 2020-07-01 through 2021-06-30");
+}
+
+#[test]
+fn _test_gains_by_year_differ_by_costing_method() {
+
+    let dir = _TestDir::new("gains_by_year_differ_by_costing_method");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,100,Buy,-100,0.1
+6-1-20,300,Buy,-300,0.1
+7-1-20,100,Sell,100,-0.05
+3-1-21,125,Sell,125,-0.05
+");
+    let path = dir.input_file("gains_by_year.csv", &contents);
+
+    for (method, expected) in [
+        (InventoryCostingMethod::FIFObyLotCreationDate, [(2020, dec!(50), dec!(0)), (2021, dec!(0), dec!(75))]),
+        (InventoryCostingMethod::LIFObyLotCreationDate, [(2020, dec!(-50), dec!(0)), (2021, dec!(-25), dec!(0))]),
+    ] {
+        let mut settings = _test_settings();
+        settings.costing_method = method.clone();
+
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+
+        let years = summary::gains_by_year(
+            &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+        ).unwrap();
+        let years: Vec<(i32, Decimal, Decimal)> = years.iter().map(|y| (y.year, y.realized_st, y.realized_lt)).collect();
+
+        assert_eq!(years, expected, "{:?}", method);
+    }
+}
+
+#[test]
+fn _test_excluded_txns_are_left_out_and_renumbered() {
+
+    let dir = _TestDir::new("excluded_txns_are_left_out_and_renumbered");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,100,Buy,-100,0.1
+6-1-20,300,Buy,-300,0.1
+7-1-20,100,Sell,100,-0.05
+3-1-21,125,Sell,125,-0.05
+");
+    let path = dir.input_file("excluded_txns.csv", &contents);
+    let mut settings = _test_settings();
+    settings.costing_method = InventoryCostingMethod::FIFObyLotCreationDate;
+    settings.excluded_txns = vec![3];
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(ars.len(), 6);
+    let last = txns_map.get(&3).unwrap();
+    assert_eq!(last.user_memo, "Sell");
+    assert_eq!(last.proceeds, dec!(125), "The 2021 sale is renumbered as the third.");
+    assert!(last.action_record_idx_vec.iter().all(|ar_num| ars.get(ar_num).unwrap().tx_key == 3));
+
+    let years = summary::gains_by_year(
+        &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+    ).unwrap();
+    let years: Vec<(i32, Decimal, Decimal)> = years.iter().map(|y| (y.year, y.realized_st, y.realized_lt)).collect();
+    assert_eq!(years, [(2021, dec!(0), dec!(75))], "Without the 2020 sale, the first lot is sold in 2021.");
+
+    settings.excluded_txns = vec![5];
+    assert!(core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default()).is_err(),
+        "There is no transaction 5 to exclude.");
+}
+
+#[test]
+fn _test_universal_lot_pooling_draws_across_accounts() {
+
+    let dir = _TestDir::new("universal_lot_pooling_draws_across_accounts");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-1-20,100,Buy,-100,1,
+6-1-20,300,Buy,-300,,1
+7-1-20,200,Sell,200,-0.5,
+7-15-20,0,To wallet,,-0.25,0.25
+8-1-20,400,Sell,400,,-1
+";
+    let path = dir.input_file("lot_pooling.csv", contents);
+
+    for (method, pooling, expected) in [
+        (InventoryCostingMethod::LIFObyLotCreationDate, LotPooling::PerWallet, [dec!(-50), dec!(-250)]),
+        (InventoryCostingMethod::LIFObyLotCreationDate, LotPooling::Universal, [dec!(-150), dec!(-200)]),
+        (InventoryCostingMethod::FIFObyLotCreationDate, LotPooling::Universal, [dec!(-50), dec!(-200)]),
+        (InventoryCostingMethod::AverageCostAcrossLots, LotPooling::Universal, [dec!(-100), dec!(-200)]),
+    ] {
+        let mut settings = _test_settings();
+        settings.costing_method = method.clone();
+        settings.lot_pooling = pooling;
+
+        let (_raw_accts, _acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+
+        let cost_basis: Vec<Decimal> = [3u32, 5].iter().map(|txn_num| {
+            txns_map.get(txn_num).unwrap().action_record_idx_vec.iter()
+                .map(|ar_num| ars.get(ar_num).unwrap())
+                .find(|ar| ar.amount < dec!(0))
+                .map(|ar| -ar.cost_basis_in_ar())
+                .unwrap()
+        }).collect();
+
+        assert_eq!(cost_basis, expected, "{:?}, {:?}", method, pooling);
+    }
+}
+
+#[test]
+fn _test_airdrops_and_forks_at_fmv_or_zero_basis() {
+
+    let dir = _TestDir::new("airdrops_and_forks_at_fmv_or_zero_basis");
+
+    let contents = "\
+txDate,proceeds,memo,txType,incomeType,1,2,3
+,,,,,Bank,Exchange,Wallet
+,,,,,USD,BCH,UNI
+,,,,,no,no,no
+8-1-17,300,BCH fork,fork,,,1,
+9-1-20,1200,UNI airdrop,,airdrop,,,400
+1-1-21,500,Sell,,,500,-1,
+";
+    let path = dir.input_file("airdrops.csv", contents);
+
+    for (basis, expected_income, expected_gain) in [
+        (AirdropBasis::FairMarketValue, dec!(1500), dec!(200)),
+        (AirdropBasis::Zero, dec!(0), dec!(500)),
+    ] {
+        let mut settings = _test_settings();
+        settings.airdrop_basis = basis;
+
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+
+        assert_eq!(txns_map.get(&1).unwrap().income_type, Some(IncomeType::Fork));
+
+        let summary = summary::RunSummary::from_maps(
+            &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+        ).unwrap();
+
+        assert_eq!(summary.income, expected_income, "{:?}", basis);
+        assert_eq!(summary.realized_total(), expected_gain, "{:?}", basis);
+    }
+}
+
+#[test]
+fn _test_rebases_as_income_or_spread_basis() {
+
+    let dir = _TestDir::new("rebases_as_income_or_spread_basis");
+
+    let contents = "\
+txDate,proceeds,memo,txType,1,2
+,,,,Bank,Wallet
+,,,,USD,STETH
+,,,,no,no
+1-1-20,10000,Buy,,-10000,10
+6-1-20,20000,Buy,,-20000,10
+1-1-21,3000,Rebase,rebase,,2
+2-1-21,1500,Rebase,rebase,,-1
+3-1-21,42000,Sell,,42000,-21
+";
+    let path = dir.input_file("rebases.csv", contents);
+    let basis_date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+
+    for (treatment, expected_income, expected_gain, expected_basis_dates) in [
+        (RebaseTreatment::Income, dec!(3000), dec!(9000), vec![basis_date("2021-01-01")]),
+        (RebaseTreatment::SpreadBasis, dec!(0), dec!(12000), vec![basis_date("2020-01-01"), basis_date("2020-06-01")]),
+    ] {
+        let mut settings = _test_settings();
+        settings.rebase_treatment = treatment;
+
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+
+        //  The lots of the increase, and their basis dates
+        let lots = acct_map.get(&2).unwrap().list_of_lots.borrow();
+        let rebase_lots: Vec<NaiveDate> = lots.iter().skip(2).map(|lot| lot.date_for_basis_purposes).collect();
+        assert_eq!(rebase_lots, expected_basis_dates, "{:?}", treatment);
+        let rebase_units: Decimal = lots.iter().skip(2).map(|lot| lot.movements.borrow()[0].amount).sum();
+        assert_eq!(rebase_units, dec!(2), "{:?}", treatment);
+
+        let summary = summary::RunSummary::from_maps(
+            &settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map
+        ).unwrap();
+
+        //  Either way, the decrease is a loss of the basis of the unit lost
+        assert_eq!(summary.income, expected_income, "{:?}", treatment);
+        assert_eq!(summary.realized_total(), expected_gain, "{:?}", treatment);
+    }
+}
+
+#[test]
+fn _test_gifts_donations_and_losses_realize_no_sale() {
+
+    let dir = _TestDir::new("gifts_donations_and_losses_realize_no_sale");
+
+    let contents = "\
+txDate,proceeds,memo,txType,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Buy,,-1000,1
+6-1-20,300,To niece,gift,,-0.2
+3-1-21,800,To charity,donation,,-0.3
+4-1-21,100,To charity,donation,,-0.1
+5-1-21,500,Hacked,stolen,,-0.2
+";
+    let settings = _test_settings();
+    let path = dir.input_file("gifts.csv", contents);
+
+    let (_raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let disposals = non_sale_disposals::non_sale_disposals(settings.long_term_days, &acct_map, &ars, &txns_map);
+    let values: Vec<(TxKind, Decimal, Decimal, Decimal)> = disposals.iter()
+        .map(|d| (d.kind, d.cost_basis, d.deduction, d.gain_or_loss))
+        .collect();
+
+    // A gift and donations realize nothing (a donation held long-term is deductible at its value), and a theft is a loss
+    assert_eq!(values, vec![
+        (TxKind::Gift, dec!(200), dec!(0), dec!(0)),
+        (TxKind::Donation, dec!(300), dec!(800), dec!(0)),
+        (TxKind::Donation, dec!(100), dec!(100), dec!(0)),
+        (TxKind::Lost, dec!(200), dec!(0), dec!(-200)),
+    ]);
+    assert_eq!(disposals[0].basis_date, NaiveDate::from_ymd_opt(2020, 1, 1).unwrap());
+}
+
+/// With a `PriceLookup`, a `Transaction` missing its `proceeds` should be valued at its home currency leg, if any, or
+/// else at the day's price of its outgoing currency (falling back to the incoming one).  Offline, a price missing from
+/// the file leaves the `proceeds` at zero, and is reported.
+#[test]
+fn _test_price_lookup_fills_missing_proceeds() {
+
+    let dir = _TestDir::new("price_lookup_fills_missing_proceeds");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Exchange,Exchange,Wallet
+,,,USD,BTC,ETH,BTC
+,,,no,no,no,no
+1-1-20,,Buy,-1000,0.1,,
+6-1-20,,Trade,,-0.05,2,
+7-1-20,0,Trade back,,0.01,-1,
+8-1-20,,Withdrawal,,-0.02,,0.02
+9-1-20,,Unpriced spend,,,-0.5,
+";
+    let prices = "\
+date,ticker,price
+2020-06-01,BTC,9500.00
+2020-07-01,BTC,9100.00
+";
+    let mut settings = _test_settings();
+    settings.price_lookup = Some(PriceLookup {
+        path: dir.input_file("price_lookup_prices.csv", prices),
+        offline: true,
+    });
+    let path = dir.input_file("price_lookup.csv", contents);
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let proceeds: Vec<Decimal> = (1..=txns_map.len()).map(|num| txns_map.get(&(num as u32)).unwrap().proceeds).collect();
+
+    // The buy's USD leg, 0.05 BTC at 9,500, 0.01 BTC at 9,100 (with no ETH price that day), a transfer and no ETH price
+    assert_eq!(proceeds, vec![dec!(1000), dec!(475), dec!(91), dec!(0), dec!(0)]);
+}
+
+/// Holdings should be valued as of the latest date in a prices file, each currency at its own latest price.
+#[test]
+fn _test_latest_prices_value_the_portfolio() {
+
+    let mut dated_prices: HashMap<(NaiveDate, String), Decimal> = HashMap::new();
+    dated_prices.insert((NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(), "BTC".to_string()), dec!(7000));
+    dated_prices.insert((NaiveDate::from_ymd_opt(2020, 3, 1).unwrap(), "BTC".to_string()), dec!(8500));
+    dated_prices.insert((NaiveDate::from_ymd_opt(2020, 2, 1).unwrap(), "ETH".to_string()), dec!(180));
+
+    let (as_of_date, latest) = spot_prices::latest_prices(&dated_prices).unwrap();
+
+    assert_eq!(as_of_date, NaiveDate::from_ymd_opt(2020, 3, 1).unwrap());
+    assert_eq!(latest.get("BTC"), Some(&(NaiveDate::from_ymd_opt(2020, 3, 1).unwrap(), dec!(8500))));
+    assert_eq!(latest.get("ETH"), Some(&(NaiveDate::from_ymd_opt(2020, 2, 1).unwrap(), dec!(180))));
+    assert!(spot_prices::latest_prices(&HashMap::new()).is_none());
+}
+
+/// Each account's computed balance should be checked against the expected balance, if any, and an expected balance
+/// of an account that doesn't exist should be flagged as well.
+#[test]
+fn _test_reconcile_balances_flags_mismatches() {
+
+    let dir = _TestDir::new("reconcile_balances_flags_mismatches");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-1-20,0,Buy,-7000,1,
+2-1-20,0,Withdrawal,,-0.4,0.4
+";
+    let settings = _test_settings();
+    let path = dir.input_file("reconcile_balances.csv", contents);
+
+    let (raw_accts, acct_map, _ars, _txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let expected: Vec<reconciliation::ExpectedBalance> = [("Exchange", "BTC", dec!(0.6)), ("Wallet", "BTC", dec!(0.5)), ("Ledger", "ETH", dec!(2))]
+        .iter()
+        .map(|(account, ticker, balance)| reconciliation::ExpectedBalance {
+            account: account.to_string(), ticker: ticker.to_string(), balance: *balance
+        })
+        .collect();
+
+    let checks = reconciliation::reconcile_balances(&expected, &raw_accts, &acct_map);
+    let statuses: Vec<(&str, &str, Option<Decimal>)> = checks.iter()
+        .map(|check| (check.account.as_str(), check.status(), check.difference()))
+        .collect();
+
+    assert_eq!(statuses, vec![
+        ("Bank", "Not verified", None),
+        ("Exchange", "OK", Some(dec!(0))),
+        ("Wallet", "MISMATCH", Some(dec!(-0.1))),
+        ("Ledger", "Unknown account", None),
+    ]);
+}
+
+/// A disposal of more than an account holds should stop processing (after every shortfall is listed), unless
+/// lenient, in which case each shortfall is covered by a zero-basis synthetic acquisition.
+#[test]
+fn _test_negative_balances_are_fatal_unless_lenient() {
+
+    let dir = _TestDir::new("negative_balances_are_fatal_unless_lenient");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,0,Buy,-1000,0.1
+2-1-20,500,Sell,500,-0.2
+3-1-20,500,Sell again,500,-0.1
+");
+    let mut settings = _test_settings();
+    let path = dir.input_file("negative_balances.csv", &contents);
+
+    let result = core_functions::import_and_process_final(
+        std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+    );
+    assert!(result.is_err());
+
+    settings.lenient = true;
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    // Each sale is preceded by a synthetic acquisition of the 0.1 BTC it was short
+    let kinds: Vec<TxKind> = (1..=txns_map.len()).map(|num| txns_map.get(&(num as u32)).unwrap().kind).collect();
+    assert_eq!(kinds, vec![TxKind::Standard, TxKind::Synthetic, TxKind::Standard, TxKind::Synthetic, TxKind::Standard]);
+    assert_eq!(txns_map.get(&2).unwrap().proceeds, dec!(0));
+}
+
+/// Validation should report every problem in the CSV Input File, by row, rather than stopping at the first.
+#[test]
+fn _test_validation_lists_every_problem_by_row() {
+
+    let dir = _TestDir::new("validation_lists_every_problem_by_row");
+
+    let contents = "\
+txDate,proceeds,memo,fee,1,2,3
+,,,,Bank,Exchange,Wallet
+,,,,USD,BTC,BTC
+,,,,no,no,no
+1-1-20,0,Buy,,-1000,0.1,
+1-2-20,abc,Bad proceeds,,-1000,0.1,
+12-1-19,0,Out of order,,,-0.05,0.05
+2-1-20,0,Lossy transfer,,,-0.05,0.04
+2-2-20,0,Transfer with a fee,1.50,,-0.02,0.01
+2-3-20,0,Same sign,,1,1,
+";
+    let settings = _test_settings();
+    let path = dir.input_file("validation.csv", contents);
+
+    let issues = validation::validate_input_file(&path, &settings).expect("Test input file failed to validate");
+    let rows: Vec<usize> = issues.iter().map(|issue| issue.row).collect();
+
+    assert_eq!(rows, vec![6, 7, 8, 10]);
+    assert!(issues[2].message.contains("no fee"));
+}
+
+/// The header row should be checked before anything else is imported, naming every column missing or unexpected (and
+/// where), and columns with other headers should import once those are given as aliases.
+#[test]
+fn _test_header_row_checked_against_aliases() {
+
+    let dir = _TestDir::new("header_row_checked_against_aliases");
+
+    let contents = "\
+Date,Proceeds,Notes,Fee,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Buy,,-1000,0.1
+";
+    let mut settings = _test_settings();
+    let path = dir.input_file("header_aliases.csv", contents);
+
+    let error = validation::validate_input_file(&path, &settings).unwrap_err().to_string();
+    assert!(error.contains("missing the column(s): txDate, proceeds, memo."), "{}", error);
+    assert!(error.contains("Date (column A), Proceeds (column B), Notes (column C), Fee (column D)"), "{}", error);
+
+    assert!(csv_import_accts_txns::parse_header_aliases("txDate=Date,amount=Amount").is_err());
+    settings.header_aliases = csv_import_accts_txns::parse_header_aliases("txDate=Date|timestamp,proceeds=Proceeds,memo=Notes")
+        .expect("Header aliases failed to parse");
+
+    let error = validation::validate_input_file(&path, &settings).unwrap_err().to_string();
+    assert!(error.contains("unexpected column(s): Fee (column D).") && !error.contains("missing"), "{}", error);
+
+    settings.header_aliases.insert("Fee".to_string(), "fee".to_string());
+    let (_, _, _, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+    assert_eq!(txns_map.get(&1).unwrap().proceeds, dec!(1000));
+
+    let misnumbered = dir.input_file("header_misnumbered.csv", &contents.replacen("Fee,1,2", "fee,1,3", 1)
+        .replacen("Date,Proceeds,Notes", "txDate,proceeds,memo", 1));
+    let error = validation::validate_input_file(&misnumbered, &settings).unwrap_err().to_string();
+    assert!(error.contains("Account column F must be numbered 2, not 3"), "{}", error);
+
+    let malformed = dir.input_file("malformed_proceeds.csv", &contents.replacen("1000,Buy", "1000x,Buy", 1));
+    let error = core_functions::import_and_process_final(&[malformed], &settings, &mut core_functions::Timings::default())
+        .unwrap_err().to_string();
+    assert!(error.contains("at row 5, column B"), "{}", error);
+}
+
+/// A cost of the costs file should be expensed when paid, or for equipment, depreciated by month over its years (in
+/// full, whatever the fiscal year), and the mining P&L should net each year's costs against its mining income.
+#[test]
+fn _test_fiat_costs_depreciated_and_netted_against_mining_income() {
+
+    let dir = _TestDir::new("fiat_costs_depreciated_and_netted_against_mining_income");
+
+    let costs_path = dir.input_file("costs.csv", "\
+date,category,amount,memo,depreciationYears
+2020-07-01,Miners,3600,Two miners,2
+2020-01-31,Electricity,50,January power
+2021-01-31,Electricity,60
+");
+    let costs = fiat_costs::import_costs(&costs_path).expect("Costs file failed to import");
+
+    assert_eq!(costs[0].expense_years(1), 2020..=2022);
+    let by_year: Vec<Decimal> = (2020..=2022).map(|year| costs[0].expensed_in_year(year, 1)).collect();
+    assert_eq!(by_year, vec![dec!(900), dec!(1800), dec!(900)]);
+    //  A fiscal year beginning in July depreciates the same 24 months over two years
+    assert_eq!(costs[0].expense_years(7), 2020..=2021);
+    assert_eq!(costs[0].expensed_in_year(2020, 7), dec!(1800));
+    assert_eq!(costs[2].expense_category(), "Electricity");
+    assert_eq!(costs[2].expensed_in_year(2020, 1), dec!(0));
+
+    let contents = "\
+txDate,proceeds,memo,incomeType,1
+,,,,Wallet
+,,,,BTC
+,,,,no
+1-1-20,100,Block reward,mining,0.01
+1-1-21,200,Block reward,mining,0.02
+";
+    let settings = _test_settings();
+    let path = dir.input_file("mining_pnl.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let incomes = summary::income_by_type_and_year(&settings.home_currency, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let years = fiat_costs::mining_pnl_by_year(&costs, &incomes, settings.fiscal_year_start_month);
+
+    let years: Vec<(i32, Decimal, Vec<String>, Decimal)> = years.iter()
+        .map(|y| (y.year, y.income, y.expenses.iter().map(|e| e.category.clone().unwrap()).collect(), y.net()))
+        .collect();
+    assert_eq!(years, vec![
+        (2020, dec!(100), vec!["Electricity".to_string(), "Miners (depreciation)".to_string()], dec!(-850)),
+        (2021, dec!(200), vec!["Electricity".to_string(), "Miners (depreciation)".to_string()], dec!(-1660)),
+        (2022, dec!(0), vec!["Miners (depreciation)".to_string()], dec!(-900)),
+    ]);
+
+    let bad_path = dir.input_file("costs_bad.csv", "date,category,amount\n2020-01-31,Electricity,-5\n");
+    let error = fiat_costs::import_costs(&bad_path).unwrap_err().to_string();
+    assert!(error.contains("line 2 has an invalid amount"), "{}", error);
+}
+
+/// JE_ACCOUNTS should rename only the accounts it names, with an income type's own account taking the place of the
+/// default one under `income`.
+#[test]
+fn _test_journal_accounts_parse_over_defaults() {
+
+    let defaults = JournalAccounts::default();
+    assert_eq!(defaults.gain_loss_account(Term::LT, false), "Income:CapitalGains:LongTerm");
+    assert_eq!(defaults.income_account(Some(IncomeType::Staking)), "Income:Crypto:Staking");
+    assert_eq!(defaults.income_account(None), "Income:Crypto");
+
+    let accounts = JournalAccounts::parse("st-loss=Expenses:Losses:ShortTerm, income=Revenue, income-Mining=Revenue:SE")
+        .expect("Journal accounts failed to parse");
+    assert_eq!(accounts.gain_loss_account(Term::ST, true), "Income:CapitalGains:ShortTerm");
+    assert_eq!(accounts.gain_loss_account(Term::ST, false), "Expenses:Losses:ShortTerm");
+    assert_eq!(accounts.income_account(Some(IncomeType::Mining)), "Revenue:SE");
+    assert_eq!(accounts.income_account(Some(IncomeType::Interest)), "Revenue:Interest");
+
+    assert!(JournalAccounts::parse("gain=Income:Gains").is_err());
+    assert!(JournalAccounts::parse("income-bonus=Income:Bonus").is_err());
+    assert!(JournalAccounts::parse("lt-gain=").is_err());
+}
+
+/// A row that fails to import should be reported as an `InputError` with its row, column and value, shown with the
+/// row beneath and the field underlined, rather than ending the program.
+#[test]
+fn _test_import_errors_name_row_column_and_value() {
+
+    let input = |sell_row: &str| -> Vec<u8> {
+        format!("txDate,proceeds,memo,1,2\n,,,Bank,Exchange\n,,,USD,BTC\n,,,no,no\n1-1-20,1000,Buy,-1000,0.1\n{}\n", sell_row)
+            .into_bytes()
+    };
+    let import_error = |sell_row: &str| -> InputError {
+        let err = core_functions::import_and_process_final_from_csv(
+            input(sell_row), &_test_settings(), &mut core_functions::Timings::default()
+        ).expect_err("A malformed row imported");
+        *err.downcast::<InputError>().expect("The error isn't an InputError")
+    };
+
+    let err = import_error("1-2-20,500,Sell,500,-1.2.3");
+    assert_eq!((err.row, err.column, err.value.as_deref()), (6, Some(4), Some("-1.2.3")));
+    assert_eq!(err.to_string(), "\
+CSV Import: at row 6, column E: The quantity (-1.2.3) for account 2 isn't a number: Invalid decimal: two decimal points.
+   |
+ 6 | 1-2-20,500,Sell,500,-1.2.3
+   |                     ^^^^^^");
+
+    let err = import_error("13-2-20,500,Sell,500,-0.05");
+    assert_eq!((err.row, err.column, err.value.as_deref()), (6, Some(0), Some("13-2-20")));
+
+    //  A long row is cut down around the field, and a quoted field is shown as it is in the file
+    let memo = "x".repeat(150);
+    let err = import_error(&format!("1-2-20,500,\"Sell, {}\",500,-0.0.5", memo));
+    let snippet = err.to_string().lines().nth(2).unwrap().to_string();
+    assert!(snippet.starts_with(" 6 | ...") && snippet.ends_with(",500,-0.0.5"), "{}", snippet);
+
+    //  A problem with the row as a whole has no column
+    let err = import_error("1-2-20,500,Sell,500,0.05");
+    assert_eq!((err.row, err.column), (6, None));
+    assert!(err.message.contains("two quantities of the same sign"), "{}", err.message);
+    assert!(!err.to_string().contains('^'));
+}
+
+/// A file delimited by semicolons, tabs or vertical bars, or quoted by single quotes, should have its dialect detected,
+/// and one with decimal commas (by its locale) should have its numbers read with decimal points, so that it imports
+/// just as the standard CSV would.
+#[test]
+fn _test_csv_dialects_read_as_standard() {
+
+    let standard = "\
+txDate,proceeds,memo,1,2
+,,,Bank,Exchange
+,,,EUR,BTC
+,,,no,no
+1-1-20,1234.5,\"Buy, at last\",-1234.5,0.5
+1-2-20,600,Sell,600,-0.25
+";
+    let semicolons = "\
+txDate;proceeds;memo;1;2
+;;;Bank;Exchange
+;;;EUR;BTC
+;;;no;no
+1-1-20;1.234,5;Buy, at last;-1.234,5;0,5
+1-2-20;600;Sell;600;-0,25
+";
+
+    let detected = |sample: &str| csv_dialect::detect(sample.as_bytes(), CsvDelimiter::Auto, ReportLocale::Plain);
+
+    assert!(detected(standard).is_standard());
+    assert_eq!(detected(semicolons).delimiter, b';');
+    assert_eq!(detected(&standard.replace(',', "\t")).delimiter, b'\t');
+    assert_eq!(detected("a|b|c\n'x|y'|2|3\n").delimiter, b'|');
+    assert_eq!(detected("a,b,c\n'x,y',2,3\n").quote, b'\'');
+    assert_eq!(csv_dialect::detect(semicolons.as_bytes(), CsvDelimiter::Char(b','), ReportLocale::Plain).delimiter, b',');
+
+    //  Only numbers are rewritten: not the dates, memos and the like
+    let dialect = CsvDialect { delimiter: b';', quote: b'"', decimal_comma: true };
+    let rewritten = String::from_utf8(csv_dialect::to_standard("1,5;-1.234;(0,5);01.02.2020;1.5;x,y;1.234.567,891\n".as_bytes(),
+        &dialect, Vec::new()).unwrap()).unwrap();
+    assert_eq!(rewritten, "1.5,-1234,(0.5),01.02.2020,1.5,\"x,y\",1234567.891\n");
+
+    let import = |contents: &str, settings: &ImportProcessParameters| {
+        let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final_from_csv(
+            contents.as_bytes().to_vec(), settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+        (1..=txns_map.len() as u32).map(|num| {
+            let txn = txns_map.get(&num).unwrap();
+            (txn.proceeds, txn.user_memo.clone())
+        }).collect::<Vec<_>>()
+    };
+
+    let settings = ImportProcessParameters { home_currency: "EUR".to_string(), .._test_settings() };
+    let european = ImportProcessParameters { input_locale: ReportLocale::CommaDecimal, ..settings.clone() };
+
+    let expected = import(standard, &settings);
+    assert_eq!(expected[0], (dec!(1234.5), "Buy, at last".to_string()));
+    assert_eq!(import(semicolons, &european), expected);
+}
+
+/// An OpenPGP message or an age file should be detected by its first bytes, while a CSV (even one beginning with an
+/// accented letter) shouldn't, and a file encrypted by `encrypt_file()` should import as the file itself would (if
+/// `gpg` is installed), without being spilled to a temporary file as plaintext, even with `low_memory`.
+#[test]
+fn _test_encrypted_input_detected_and_decrypted() {
+
+    use crate::encryption::{self, Encryption};
+
+    let dir = _TestDir::new("encrypted_input_detected_and_decrypted");
+
+    assert_eq!(Encryption::detect(b"\x8c\x0d\x04\x09\x03\x02"), Some(Encryption::OpenPgp));
+    assert_eq!(Encryption::detect(b"\xc3\x0d\x04\x09\x03\x02"), Some(Encryption::OpenPgp));
+    assert_eq!(Encryption::detect(b"-----BEGIN PGP MESSAGE-----\n"), Some(Encryption::OpenPgp));
+    assert_eq!(Encryption::detect(b"age-encryption.org/v1\n-> scrypt"), Some(Encryption::Age));
+    assert_eq!(Encryption::detect(b"txDate,proceeds,memo"), None);
+    assert_eq!(Encryption::detect("\u{c9}poque,proceeds".as_bytes()), None);
+
+    if std::process::Command::new("gpg").arg("--version").output().is_err() {
+        eprintln!("Skipped decrypting: gpg isn't installed.");
+        return
+    }
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,1000,Buy,-1000,0.1
+");
+    let path = dir.input_file("encrypted.csv", &contents);
+    let encrypted_path = encryption::encrypt_file(&path, "correct horse").expect("gpg failed to encrypt");
+
+    assert!(!path.exists());
+    assert!(Encryption::detect(&fs::read(&encrypted_path).unwrap()).is_some());
+
+    let mut settings = _test_settings();
+    settings.low_memory = true;
+
+    std::env::set_var(encryption::PASSPHRASE_VAR, "correct horse");
+    let result = core_functions::import_and_process_final(&[encrypted_path], &settings, &mut core_functions::Timings::default());
+    std::env::remove_var(encryption::PASSPHRASE_VAR);
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = result.expect("The encrypted input file failed to process");
+    assert_eq!(txns_map.get(&1).unwrap().proceeds, dec!(1000));
+    assert!(!crate::spill_files::may_spill(&settings), "A decrypted input file must not be spilled to disk.");
+}
+
+/// A row duplicating an earlier one (by the dedupe key's fields) should be imported with `DedupeMode::Warn` or left
+/// out with `DedupeMode::Drop`, and listed either way, while rows differing in a field of the key aren't duplicates.
+#[test]
+fn _test_duplicate_rows_warned_or_dropped() {
+
+    let dir = _TestDir::new("duplicate_rows_warned_or_dropped");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,1000,Buy,-1000,0.1
+1-1-20,1000,Buy,-1000,0.10
+1-1-20,1000,Buy again,-1000,0.1
+1-1-20,1000,Buy,\"-1,000\",0.1
+1-2-20,1000,Buy,-1000,0.1
+");
+    let mut settings = _test_settings();
+    let path = dir.input_file("dedupe.csv", &contents);
+    let import = |settings: &ImportProcessParameters| core_functions::import_and_process_final(
+        std::slice::from_ref(&path), settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process").3;
+
+    assert_eq!(import(&settings).len(), 5);
+
+    settings.dedupe = DedupeMode::Warn;
+    assert_eq!(import(&settings).len(), 5);
+    let duplicates = dedupe::find_duplicate_rows(std::slice::from_ref(&path), &settings).expect("Duplicates failed to be found");
+    assert_eq!(duplicates.iter().map(|d| (d.row, d.duplicate_of)).collect::<Vec<_>>(), vec![(6, 5), (8, 5)]);
+    let issues = validation::validate_input_file(&path, &settings).expect("Test input file failed to validate");
+    assert_eq!(issues.iter().map(|issue| issue.row).collect::<Vec<usize>>(), vec![6, 8]);
+
+    settings.dedupe = DedupeMode::Drop;
+    let txns_map = import(&settings);
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(txns_map.get(&2).unwrap().source_row, Some(7));
+
+    //  Without the memo, the third row is a duplicate too
+    settings.dedupe_key = dedupe::parse_dedupe_key("date,accounts,amounts").expect("Dedupe key failed to parse");
+    assert_eq!(import(&settings).len(), 2);
+    assert!(dedupe::parse_dedupe_key("date,ticker").is_err());
+}
+
+/// Tags should be parsed from the memo (and the tags column) into each transaction's labels, by which the reports may
+/// be filtered, and each tag's transactions listed with their income and gains.
+#[test]
+fn _test_tags_label_transactions_for_filtering() {
+
+    let dir = _TestDir::new("tags_label_transactions_for_filtering");
+
+    let contents = "\
+txDate,proceeds,memo,tags,1,2
+,,,,Bank,Wallet
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Paid for #payment:invoice42.,,,0.1
+2-1-20,500,Staking reward #Staking,#payment:invoice43,,0.05
+3-1-20,2000,Sold,staking,2000,-0.15
+";
+    let mut settings = _test_settings();
+    let path = dir.input_file("tags.csv", contents);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let tag = |s: &str| s.parse::<Tag>().unwrap();
+    let tags_of = |num: u32| txns_map.get(&num).unwrap().tags.iter().map(|tag| tag.to_string()).collect::<Vec<String>>();
+
+    assert_eq!(tags_of(1), vec!["#payment:invoice42"]);
+    assert_eq!(tags_of(2), vec!["#staking", "#payment:invoice43"]);
+    assert_eq!(tags_of(3), vec!["#staking"]);
+    assert!("pay ment".parse::<Tag>().is_err());
+
+    settings.report_filter.tag = Some(tag("payment"));
+    assert!(settings.is_reported_txn(txns_map.get(&1).unwrap()) && !settings.is_reported_txn(txns_map.get(&3).unwrap()));
+    settings.report_filter.tag = Some(tag("#payment:invoice43"));
+    assert!(!settings.is_reported_txn(txns_map.get(&1).unwrap()) && settings.is_reported_txn(txns_map.get(&2).unwrap()));
+
+    let tagged = tags::tagged_transactions(&settings.home_currency, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let listed: Vec<(String, u32, Decimal, Decimal)> = tagged.iter()
+        .map(|t| (t.tag.to_string(), t.tx_num, t.income, t.realized))
+        .collect();
+
+    assert_eq!(listed, vec![
+        ("#payment:invoice42".to_string(), 1, dec!(1000), dec!(0)),
+        ("#payment:invoice43".to_string(), 2, dec!(500), dec!(0)),
+        ("#staking".to_string(), 2, dec!(500), dec!(0)),
+        ("#staking".to_string(), 3, dec!(0), dec!(500)),
+    ]);
+}
+
+/// A spend's expense category should come from the expenseCategory column or an `#expense:` tag, and expenses should
+/// be totaled by category and year, while a category on anything but a spend is an issue.
+#[test]
+fn _test_expenses_by_category() {
+
+    let dir = _TestDir::new("expenses_by_category");
+
+    let contents = "\
+txDate,proceeds,memo,expenseCategory,1,2
+,,,,Bank,Wallet
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Bought,,-1000,0.1
+2-1-20,300,Lunch,meals,,-0.02
+3-1-20,500,Laptop #expense:equipment,,,-0.03
+4-1-20,100,Coffee,,,-0.01
+5-1-21,150,Dinner,meals,,-0.01
+";
+    let settings = _test_settings();
+    let path = dir.input_file("expense_categories.csv", contents);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    assert_eq!(txns_map.get(&2).unwrap().expense_category.as_deref(), Some("meals"));
+    assert_eq!(txns_map.get(&3).unwrap().expense_category.as_deref(), Some("equipment"));
+    assert_eq!(txns_map.get(&4).unwrap().expense_category, None);
+
+    let totals = summary::expenses_by_category_and_year(&settings.home_currency, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let totals: Vec<(i32, Option<String>, Decimal)> = totals.into_iter().map(|t| (t.year, t.category, t.expense)).collect();
+
+    assert_eq!(totals, vec![
+        (2020, Some("equipment".to_string()), dec!(500)),
+        (2020, Some("meals".to_string()), dec!(300)),
+        (2020, None, dec!(100)),
+        (2021, Some("meals".to_string()), dec!(150)),
+    ]);
+
+    let contents = "\
+txDate,proceeds,memo,expenseCategory,1,2
+,,,,Bank,Wallet
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Bought,meals,-1000,0.1
+2-1-20,300,Mined,,,0.02
+3-1-20,300,Mined #expense:meals,,,0.02
+";
+    let path = dir.input_file("expense_category_issues.csv", contents);
+    let issues = validation::validate_input_file(&path, &settings).expect("Test input file failed to validate");
+
+    assert_eq!(issues.iter().map(|issue| issue.row).collect::<Vec<usize>>(), vec![5, 7]);
+    assert!(issues.iter().all(|issue| issue.message.contains("expenseCategory")));
+}
+
+/// Income should be attributed to its payer by the payer column or a `#payer:` tag, and totaled by payer and year.
+#[test]
+fn _test_income_by_payer() {
+
+    let dir = _TestDir::new("income_by_payer");
+
+    let contents = "\
+txDate,proceeds,memo,payer,1
+,,,,Wallet
+,,,,BTC
+,,,,no
+1-1-20,1000,Invoice 1,Acme,0.1
+2-1-20,500,Invoice 2 #payer:Globex,,0.05
+3-1-20,250,Invoice 3,Acme,0.02
+4-1-20,100,Mined,,0.01
+5-1-21,300,Invoice 4,Acme,0.03
+";
+    let settings = _test_settings();
+    let path = dir.input_file("payers.csv", contents);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    assert_eq!(txns_map.get(&2).unwrap().payer.as_deref(), Some("Globex"));
+
+    let totals = summary::income_by_payer_and_year(&settings.home_currency, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let totals: Vec<(i32, Option<String>, u32, Decimal)> = totals.into_iter().map(|t| (t.year, t.payer, t.payments, t.income)).collect();
+
+    assert_eq!(totals, vec![
+        (2020, Some("Acme".to_string()), 2, dec!(1250)),
+        (2020, Some("Globex".to_string()), 1, dec!(500)),
+        (2020, None, 1, dec!(100)),
+        (2021, Some("Acme".to_string()), 1, dec!(300)),
+    ]);
+}
+
+/// Corrections should replace a row's txDate, proceeds or memo before they are parsed, so that a typo that wouldn't
+/// parse can be fixed, and a later correction of the same field should replace an earlier one.
+#[test]
+fn _test_corrections_replace_fields_as_imported() {
+
+    let dir = _TestDir::new("corrections_replace_fields_as_imported");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,1000,Buy,-1000,0.1
+13-1-20,9OO,Sell,900,-0.05
+");
+    let corrections_path = dir.input_file("corrections_file.csv", "row,field,value\n");
+    for correction in [
+        Correction { target: CorrectionTarget::Row(6), field: CorrectedField::TxDate, value: "2020-03-01".to_string() },
+        Correction { target: CorrectionTarget::Row(6), field: CorrectedField::Proceeds, value: "800".to_string() },
+        Correction { target: CorrectionTarget::Row(6), field: CorrectedField::Proceeds, value: "900".to_string() },
+        Correction { target: CorrectionTarget::Row(5), field: CorrectedField::Memo, value: "Buy on Jan 1".to_string() },
+    ].iter() {
+        corrections::append_correction(&corrections_path, correction).unwrap();
+    }
+    assert!(corrections::append_correction(&corrections_path,
+        &Correction { target: CorrectionTarget::Row(6), field: CorrectedField::TxDate, value: "3-1-20".to_string() }).is_err());
+
+    let mut settings = _test_settings();
+    settings.corrections = Some(corrections_path);
+    let path = dir.input_file("corrections.csv", &contents);
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let (buy, sell) = (txns_map.get(&1).unwrap(), txns_map.get(&2).unwrap());
+
+    assert_eq!(buy.user_memo, "Buy on Jan 1");
+    assert_eq!((buy.source_row, sell.source_row), (Some(5), Some(6)));
+    assert_eq!(sell.date, NaiveDate::from_ymd_opt(2020, 3, 1).unwrap());
+    assert_eq!(&*sell.date_as_string, "03-01-2020");
+    assert_eq!(sell.proceeds, dec!(900));
+}
+
+/// A row failing to import should name the field a correction would replace, and correcting that field (an optional
+/// column or a quantity) or skipping the row should let the file import, as the wizard's recovery prompt does.
+#[test]
+fn _test_corrections_fix_or_skip_rows_failing_import() {
+
+    let dir = _TestDir::new("corrections_fix_or_skip_rows_failing_import");
+
+    let contents = "\
+txDate,proceeds,memo,fee,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+1-1-20,1000,Buy,1..5,-1000,0.1
+1-2-20,500,Sell,,500,-0.0.5
+1-3-20,oops,Typo,,,
+1-4-20,600,Sell,,600,-0.05
+";
+    let path = dir.input_file("recovery.csv", contents);
+    let corrections_path = dir.path("recovery_corrections.csv");
+
+    let mut settings = _test_settings();
+    settings.corrections = Some(corrections_path.clone());
+
+    //  Each error is fixed with a correction of the field it names, but the row that's only a typo is skipped
+    let fixes = [
+        (5, Some(CorrectedField::Optional("fee")), "1.5"),
+        (6, Some(CorrectedField::Quantity(2)), "-0.05"),
+        (7, Some(CorrectedField::Proceeds), ""),
+    ];
+    for (row, field, value) in fixes.iter() {
+        let err = core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default())
+            .expect_err("A malformed row imported");
+        let err = err.downcast_ref::<InputError>().expect("The error isn't an InputError");
+        assert_eq!((err.row, err.field), (*row, *field), "{}", err);
+
+        let correction = match err.field {
+            Some(CorrectedField::Proceeds) => Correction { target: CorrectionTarget::Row(err.row), field: CorrectedField::Skip, value: String::new() },
+            _ => Correction { target: CorrectionTarget::Row(err.row), field: err.field.unwrap(), value: value.to_string() },
+        };
+        corrections::append_correction(&corrections_path, &correction).unwrap();
+    }
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process with corrections");
+
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(txns_map.get(&1).unwrap().fee, dec!(1.5));
+    assert_eq!(txns_map.get(&3).unwrap().source_row, Some(8));
+}
+
+/// A correction of a transaction should apply to the transaction so numbered without the corrections, even after an
+/// earlier one is voided, and a correction of its row should take precedence over it.
+#[test]
+fn _test_corrections_of_txns_override_or_void() {
+
+    let dir = _TestDir::new("corrections_of_txns_override_or_void");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,1000,Buy,-1000,0.1
+1-2-20,0,Mistaken buy,-500,0.05
+1-3-20,500,Sell,500,-0.05
+");
+    let corrections_path = dir.input_file("txn_corrections_file.csv", "row,field,value\n");
+    for correction in [
+        Correction { target: CorrectionTarget::Txn(2), field: CorrectedField::Skip, value: String::new() },
+        Correction { target: CorrectionTarget::Txn(3), field: CorrectedField::Proceeds, value: "700".to_string() },
+        Correction { target: CorrectionTarget::Txn(1), field: CorrectedField::Memo, value: "Buy (amended)".to_string() },
+        Correction { target: CorrectionTarget::Row(5), field: CorrectedField::Memo, value: "Buy on Jan 1".to_string() },
+    ].iter() {
+        corrections::append_correction(&corrections_path, correction).unwrap();
+    }
+
+    let written = fs::read_to_string(&corrections_path).unwrap();
+    assert!(written.contains("#2,skip,\n") && written.contains("#3,proceeds,700\n"));
+    assert!(corrections::import_corrections(&dir.input_file("txn_corrections_bad.csv",
+        "row,field,value\n#0,void,\n")).is_err());
+
+    let mut settings = _test_settings();
+    settings.corrections = Some(corrections_path);
+    let path = dir.input_file("txn_corrections.csv", &contents);
+
+    let (_raw_accts, _acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let (buy, sell) = (txns_map.get(&1).unwrap(), txns_map.get(&2).unwrap());
+
+    assert_eq!(txns_map.len(), 2);
+    assert_eq!(buy.user_memo, "Buy on Jan 1");
+    assert_eq!(sell.source_row, Some(7));
+    assert_eq!(sell.proceeds, dec!(700));
+}
+
+/// FIFO should skip the `Lot`s it has already spent and LIFO should start from the newest, with a disposal's `Movement`s
+/// returned in `Lot` order either way, and `Transaction`s on the same date should share their date string.
+#[test]
+fn _test_unresolved_rows_until_corrected() {
+
+    let dir = _TestDir::new("unresolved_rows_until_corrected");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Exchange,Wallet,Exchange
+,,,USD,BTC,BTC,ETH
+,,,no,no,no,no
+1-1-20,1000,Buy,-1000,1,,
+2-1-20,0,Swap,,-0.2,,5
+3-1-20,0,Sent,,-0.3,,
+3-1-20,0,Received,,,0.3,
+4-1-20,0,Sent,,-0.1,,
+";
+    let mut settings = _test_settings();
+    settings.transfer_match_tolerance = Some(dec!(0.01));
+    let path = dir.input_file("unresolved.csv", contents);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    //  The matched transfer (rows 7 and 8) needs no proceeds
+    let unresolved = unresolved::find_unresolved_rows(&settings, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    let rows: Vec<(usize, Unresolved)> = unresolved.iter().map(|row| (row.row, row.reason)).collect();
+
+    assert_eq!(rows, vec![(6, Unresolved::MissingProceeds), (9, Unresolved::UnmatchedWithdrawal)]);
+
+    let corrections_path = dir.path("unresolved_corrections.csv");
+    for (row, value) in [(6, "1500"), (9, "300")] {
+        let correction = Correction { target: CorrectionTarget::Row(row), field: CorrectedField::Proceeds, value: value.to_string() };
+        corrections::append_correction(&corrections_path, &correction).unwrap();
+    }
+    settings.corrections = Some(corrections_path);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process with corrections");
+
+    assert!(unresolved::find_unresolved_rows(&settings, &raw_accts, &acct_map, &ars, &txns_map).unwrap().is_empty());
+}
+
+#[test]
+fn _test_rows_needing_review_are_parked() {
+
+    let dir = _TestDir::new("rows_needing_review_are_parked");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-1-20,1000,Buy,-1000,1,
+2-1-20,0,Which way?,,0.5,0.5
+3-1-20,0,Sent,,-0.3,
+3-1-20,0,Received,,,0.3
+4-1-20,0,Sent nowhere,,-0.1,
+5-1-20,abc,Sold,600,-0.2,
+6-1-20,900,Sold,900,-0.3,
+";
+    let mut settings = _test_settings();
+    settings.transfer_match_tolerance = Some(dec!(0.01));
+    let path = dir.input_file("needs_review.csv", contents);
+
+    //  Without parking, the first problem stops the run
+    assert!(core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()).is_err());
+
+    let review_rows = needs_review::find_rows_needing_review(std::slice::from_ref(&path), &settings).unwrap();
+    let rows: Vec<usize> = review_rows.iter().map(|review_row| review_row.row).collect();
+
+    assert_eq!(rows, vec![6, 9, 10]);
+    assert_eq!(review_rows[0].quantities, "2: 0.5, 3: 0.5");
+    assert!(review_rows[0].reason.contains("same sign"));
+    assert!(review_rows[1].reason.contains("no deposit was matched"));
+    assert!(review_rows[2].reason.contains("proceeds (abc)"));
+
+    settings.parked_rows = rows;
+    let (_raw_accts, acct_map, _ars, txns_map) = core_functions::import_and_process_final(
+        std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process with its rows parked");
+
+    //  The buy, the matched transfer and the last sale
+    assert_eq!(txns_map.len(), 3);
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_amts_in_lots(), dec!(0.4));
+    assert_eq!(acct_map.get(&3).unwrap().get_sum_of_amts_in_lots(), dec!(0.3));
+}
+
+#[test]
+fn _test_fifo_and_lifo_spend_lots_in_creation_order() {
+
+    let dir = _TestDir::new("fifo_and_lifo_spend_lots_in_creation_order");
+
+    let contents = _bank_and_exchange_input("\
+1-1-20,100,Buy,-100,0.1
+2-1-20,200,Buy,-200,0.1
+3-1-20,300,Buy,-300,0.1
+4-1-20,600,Sell,600,-0.15
+4-1-20,400,Sell,400,-0.1
+");
+    let path = dir.input_file("lot_order.csv", &contents);
+
+    for (method, expected) in [
+        (InventoryCostingMethod::FIFObyLotCreationDate, [vec![(1, dec!(-0.1)), (2, dec!(-0.05))], vec![(2, dec!(-0.05)), (3, dec!(-0.05))]]),
+        (InventoryCostingMethod::LIFObyLotCreationDate, [vec![(2, dec!(-0.05)), (3, dec!(-0.1))], vec![(1, dec!(-0.05)), (2, dec!(-0.05))]]),
+    ] {
+        let mut settings = _test_settings();
+        settings.costing_method = method.clone();
+
+        let (_raw_accts, _acct_map, ars, txns_map) = core_functions::import_and_process_final(
+            std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default()
+        ).expect("Test input file failed to process");
+
+        for (txn_num, expected_mvmts) in [4u32, 5].iter().zip(expected.iter()) {
+
+            let txn = txns_map.get(txn_num).unwrap();
+            let sell_ar = txn.action_record_idx_vec.iter()
+                .map(|ar_num| ars.get(ar_num).unwrap())
+                .find(|ar| ar.amount < dec!(0))
+                .unwrap();
+            let mvmts: Vec<(u32, Decimal)> = sell_ar.get_mvmts_in_ar_in_lot_date_order().iter()
+                .map(|mvmt| (mvmt.lot_num, mvmt.amount))
+                .collect();
+
+            assert_eq!(&mvmts, expected_mvmts, "{:?}, txn {}", method, txn_num);
+        }
+
+        assert!(Rc::ptr_eq(&txns_map.get(&4).unwrap().date_as_string, &txns_map.get(&5).unwrap().date_as_string));
+    }
+}
+
+/// Proceeds too precise for a float (over 2^24, with cents) should carry through to the `Movement`s exactly.
+#[test]
+fn _test_proceeds_keep_their_precision() {
+
+    let dir = _TestDir::new("proceeds_keep_their_precision");
+
+    let contents = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Exchange
+,,,USD,BTC,ETH
+,,,no,no,no
+1-1-20,16777216.01,Buy,-16777216.01,1000,
+2-1-20,16777217.03,Trade,,-1000,10000
+";
+    let path = dir.input_file("precise_proceeds.csv", contents);
+    let settings = _test_settings();
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(
+        &[path], &settings, &mut core_functions::Timings::default()
+    ).expect("Test input file failed to process");
+
+    let trade = txns_map.get(&2).unwrap();
+    assert_eq!(trade.proceeds, dec!(16777217.03));
+
+    let mvmts = trade.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+    let proceeds: Decimal = mvmts.iter().map(|mvmt| mvmt.proceeds.get()).sum();
+    let gain: Decimal = mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum();
+
+    assert_eq!(proceeds, dec!(16777217.03));
+    assert_eq!(gain, dec!(1.02));
+    assert!(trade.get_auto_memo(&ars, &raw_accts, &acct_map, &settings.home_currency).unwrap().contains("16777217.03 USD"));
+}
+
+/// Home currency values should be written with exactly the configured places, rounding halves as configured, and
+/// quantities should only be rounded when a number of places is set.
+#[test]
+fn _test_output_precision_rounds_as_configured() {
+
+    let bankers = OutputPrecision::default();
+
+    assert_eq!(bankers.fiat(dec!(0.125)), "0.12");
+    assert_eq!(bankers.fiat(dec!(0.135)), "0.14");
+    assert_eq!(bankers.fiat(dec!(1.999)), "2.00");
+    assert_eq!(bankers.fiat(dec!(-250)), "-250.00");
+    assert_eq!(bankers.crypto(dec!(0.123456789)), "0.123456789");
+
+    let half_up = OutputPrecision { fiat_places: 0, crypto_places: Some(8), rounding: OutputRounding::HalfUp, ..bankers };
+
+    assert_eq!(half_up.fiat(dec!(0.5)), "1");
+    assert_eq!(half_up.fiat(dec!(-2.5)), "-3");
+    assert_eq!(half_up.round_fiat(dec!(182.86)), dec!(183));
+    assert_eq!(half_up.crypto(dec!(0.123456785)), "0.12345679");
+    assert_eq!(half_up.crypto(dec!(0.25)), "0.25");
+}
+
+/// Report numbers should be grouped and separated as the locale has them, and report dates formatted as configured.
+#[test]
+fn _test_report_locale_separators() {
+
+    let comma = OutputPrecision { locale: ReportLocale::CommaDecimal, ..OutputPrecision::default() };
+    let dot = OutputPrecision { locale: ReportLocale::DotDecimal, ..OutputPrecision::default() };
+
+    assert_eq!(comma.fiat(dec!(1234567.891)), "1.234.567,89");
+    assert_eq!(comma.number(dec!(0.5)), "0,5");
+    assert_eq!(dot.fiat(dec!(-1234.5)), "-1,234.50");
+    assert_eq!(dot.crypto(dec!(123)), "123");
+    assert_eq!(OutputPrecision::default().fiat(dec!(1234567.891)), "1234567.89");
+
+    assert_eq!(ReportLocale::from_code("de-DE"), Some(ReportLocale::CommaDecimal));
+    assert_eq!(ReportLocale::from_code("en_US"), Some(ReportLocale::DotDecimal));
+    assert_eq!(ReportLocale::from_code("xx"), None);
+
+    let mut settings = _test_settings();
+    let date = NaiveDate::from_ymd_opt(2023, 3, 9).unwrap();
+
+    assert_eq!(settings.report_date(date), "2023-03-09");
+    settings.output_date_format = Some("%d.%m.%Y".to_string());
+    assert_eq!(settings.report_date(date), "09.03.2023");
+}
+
+/// Same-date transactions should be processed in the order of their txTimes, and a `timezone` should move each
+/// timed transaction to its date in that timezone (here, from New Year's Day in UTC back to New Year's Eve).
+#[test]
+fn _test_tx_times_order_and_convert_to_timezone() {
+
+    let dir = _TestDir::new("tx_times_order_and_convert_to_timezone");
+
+    let input = "\
+txDate,proceeds,memo,txTime,1,2
+,,,,Bank,Exchange
+,,,,USD,BTC
+,,,,no,no
+12-31-20,100,Income,20:00,,0.1
+12-31-20,1000,Buy,14:00,-1000,1
+1-1-21,750,Sell,03:00:00Z,750,-0.5
+1-2-21,50,Untimed income,,,0.1
+";
+    assert_eq!(timestamps::parse_utc_offset("-05:00"), FixedOffset::west_opt(5 * 3600));
+    assert_eq!(timestamps::parse_utc_offset("UTC+0930"), FixedOffset::east_opt(9 * 3600 + 1800));
+    assert_eq!(timestamps::parse_utc_offset("EST"), None);
+
+    for (timezone, sell_date) in [(None, "2021-01-01"), (FixedOffset::west_opt(5 * 3600), "2020-12-31")] {
+
+        let mut settings = _test_settings();
+        settings.timezone = timezone;
+        let path = dir.input_file("tx_times.csv", input);
+        let (_raw_accts, _acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let memos: Vec<&str> = (1..=txns_map.len()).map(|num| txns_map.get(&(num as u32)).unwrap().user_memo.as_str()).collect();
+        assert_eq!(memos, vec!["Buy", "Income", "Sell", "Untimed income"], "{:?}", timezone);
+
+        let sell = txns_map.get(&3).unwrap();
+        assert_eq!(sell.date, NaiveDate::parse_from_str(sell_date, "%Y-%m-%d").unwrap(), "{:?}", timezone);
+        assert_eq!(ars.get(&sell.action_record_idx_vec[0]).unwrap().tx_key, 3);
+        assert_eq!(txns_map.get(&4).unwrap().date, NaiveDate::from_ymd_opt(2021, 1, 2).unwrap());
+
+        if timezone.is_some() {
+            assert_eq!(&*sell.date_as_string, "12-31-2020");
+            assert_eq!(sell.time, NaiveTime::from_hms_opt(22, 0, 0));
+        }
+    }
+}
+
+/// A txTime or txSeq should decide the order of same-date transactions, with `SameDateOrder` only breaking ties.
+#[test]
+fn _test_tx_time_and_seq_take_precedence_over_same_date_order() {
+
+    let dir = _TestDir::new("tx_time_and_seq_take_precedence_over_same_date_order");
+
+    // June's sale is timed before its purchase, and September's purchase is sequenced before its sale
+    let input = "\
+txDate,proceeds,memo,txTime,txSeq,1,2
+,,,,,Bank,Exchange
+,,,,,USD,BTC
+,,,,,no,no
+1-1-19,0,Buy Jan,,,-2000,2
+6-1-19,0,Buy Jun,11:00,,-4800,1
+6-1-19,5000,Sell Jun,10:00,,5000,-1
+9-1-19,6100,Sell Sep,,20,6100,-1
+9-1-19,0,Buy Sep,,10,-6000,1
+";
+    for order in [SameDateOrder::FileOrder, SameDateOrder::AcquisitionsFirst, SameDateOrder::DisposalsFirst] {
+
+        let mut settings = _test_settings();
+        settings.same_date_order = order;
+        let path = dir.input_file("tx_seq.csv", input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let memos: Vec<&str> = (1..=txns_map.len()).map(|num| txns_map.get(&(num as u32)).unwrap().user_memo.as_str()).collect();
+        assert_eq!(memos, vec!["Buy Jan", "Sell Jun", "Buy Jun", "Buy Sep", "Sell Sep"], "{:?}", order);
+
+        let gain_on = |memo: &str| -> Decimal {
+            let txn = txns_map.values().find(|txn| txn.user_memo == memo).unwrap();
+            txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap()
+                .iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum()
+        };
+
+        // Under LIFO, June's sale is drawn from January's lot, and September's from September's
+        assert_eq!(gain_on("Sell Jun"), dec!(4000), "{:?}", order);
+        assert_eq!(gain_on("Sell Sep"), dec!(100), "{:?}", order);
+    }
+}
+
+/// With a `translation`, each home currency value should be translated at the rate of its date (or the latest rate
+/// before it), so a basis is at the rate of its acquisition and proceeds at the rate of their disposal.
+#[test]
+fn _test_secondary_currency_translates_at_dated_rates() {
+
+    let dir = _TestDir::new("secondary_currency_translates_at_dated_rates");
+
+    let input = _bank_and_exchange_input("\
+1-2-21,1000,Buy,-1000,1
+6-5-21,1500,Sell,1500,-1
+");
+    let date = |y: i32, m: u32, d: u32| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let translation = FxTranslation {
+        currency: "EUR".to_string(),
+        rates: [(date(2021, 1, 1), dec!(0.8)), (date(2021, 6, 1), dec!(0.9))].into_iter().collect(),
+    };
+
+    assert_eq!(translation.rate_on(date(2021, 6, 5)), Some(dec!(0.9)));
+    assert_eq!(translation.rate_on(date(2020, 12, 31)), None);
+
+    let mut settings = _test_settings();
+    settings.translation = Some(translation);
+    let path = dir.input_file("fx_translation.csv", &input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let settings = settings.translated_settings();
+    assert_eq!(raw_accts.get(&1).unwrap().ticker, "EUR");
+    assert_eq!(acct_map.get(&1).unwrap().get_sum_of_amts_in_lots(), dec!(550));
+
+    let sell = txns_map.get(&2).unwrap();
+    let mvmts = sell.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+
+    assert_eq!(mvmts.iter().map(|mvmt| mvmt.proceeds_lk.get()).sum::<Decimal>(), dec!(1350));
+    assert_eq!(mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum::<Decimal>(), dec!(550));
+}
+
+#[test]
+fn _test_stablecoins_as_cash_equivalents() {
+
+    let dir = _TestDir::new("stablecoins_as_cash_equivalents");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Exchange,Exchange,Exchange
+,,,USD,USDC,BTC,EUR
+,,,no,no,no,no
+1-2-21,1000,Buy USDC,-1000,1000,,
+2-3-21,1010,Buy BTC,,-1000,0.1,
+3-4-21,500,Buy EUR,-500,,,400
+4-5-21,520,Sell EUR,520,,,-400
+";
+    let gain = |stablecoins: Vec<String>, txn_num: u32| {
+
+        let mut settings = _test_settings();
+        settings.stablecoins = stablecoins;
+        let path = dir.input_file("stablecoins.csv", input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let txn = txns_map.get(&txn_num).unwrap();
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, &ars, &raw_accts, &acct_map, &txns_map).unwrap();
+        mvmts.iter().map(|mvmt| mvmt.get_lk_gain_or_loss()).sum::<Decimal>()
+    };
+
+    assert_eq!(gain(vec!["USDC".to_string()], 2), dec!(0));
+    assert_eq!(gain(Vec::new(), 2), dec!(10));
+    assert_eq!(gain(vec!["USDC".to_string()], 4), dec!(20));
+}
+
+#[test]
+fn _test_withdrawals_and_deposits_matched_as_transfers() {
+
+    let dir = _TestDir::new("withdrawals_and_deposits_matched_as_transfers");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Exchange,Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-2-21,1000,Buy,-1000,1,
+1-5-21,1200,Withdraw,,-0.5,
+1-6-21,0,Deposit,,,0.495
+1-7-21,0,Unmatched deposit,,,0.2
+";
+    let mut settings = _test_settings();
+    settings.transfer_match_tolerance = Some(dec!(0.02));
+    let path = dir.input_file("transfer_matching.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    //  The transfer, its fee (split off right after it) and the unmatched deposit, which is income
+    assert_eq!(txns_map.len(), 4);
+
+    let transfer = txns_map.get(&2).unwrap();
+    assert_eq!(transfer.transaction_type(&ars, &raw_accts, &acct_map).unwrap(), TxType::ToSelf);
+    assert_eq!(transfer.user_memo, "Withdraw / Deposit");
+    assert_eq!(txns_map.get(&3).unwrap().kind, TxKind::TransferFee);
+    assert_eq!(txns_map.get(&3).unwrap().proceeds, dec!(12));
+
+    //  The basis of the units received carries over
+    let wallet = acct_map.get(&3).unwrap();
+    assert_eq!(wallet.list_of_lots.borrow()[0].get_sum_of_lk_basis_in_lot(), dec!(495));
+    assert_eq!(wallet.get_sum_of_amts_in_lots(), dec!(0.695));
+}
+
+#[test]
+fn _test_derivative_pnl_is_neither_income_nor_expense() {
+
+    let dir = _TestDir::new("derivative_pnl_is_neither_income_nor_expense");
+
+    // A profit received and a loss paid in USDT, and a loss paid in the home currency
+    let input = "\
+txDate,proceeds,memo,txType,1,2
+,,,,Bank,Exchange
+,,,,USD,USDT
+,,,,no,no
+1-2-21,1000,Buy USDT,,-1000,1000
+2-1-21,300,Futures profit,derivative-pnl,,300
+3-1-21,,Futures loss,derivative-pnl,-200,
+4-1-21,150,Futures loss,derivative-pnl,,-150
+";
+    let settings = _test_settings();
+    let path = dir.input_file("derivative_pnl.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let pnls = derivatives::derivative_pnls(&settings.home_currency, &raw_accts, &acct_map, &ars, &txns_map);
+    assert_eq!(pnls.iter().map(|pnl| pnl.pnl).collect::<Vec<_>>(), vec![dec!(300), dec!(-200), dec!(-150)]);
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.income, dec!(0), "A derivative profit must not be income.");
+    assert_eq!(summary.expense, dec!(0), "A derivative loss must not be an expense.");
+    assert_eq!(summary.derivative_pnl, dec!(-50));
+    assert_eq!(summary.realized_total(), dec!(0), "The USDT paid was disposed of at its basis.");
+
+    // The profit received is a lot at its value
+    assert_eq!(acct_map.get(&2).unwrap().get_sum_of_lk_basis_in_lots(), dec!(1150));
+}
+
+#[test]
+fn _test_liquidity_pool_deposit_swap_or_carryover() {
+
+    let dir = _TestDir::new("liquidity_pool_deposit_swap_or_carryover");
+
+    // ETH (basis 1000) and USDC (basis 2000) deposited, worth 4000 in all, for 10 LP tokens, which are later withdrawn
+    let input = "\
+txDate,proceeds,memo,txType,1,2,3,4
+,,,,Bank,Wallet,Wallet,Wallet
+,,,,USD,ETH,USDC,UNI-V2
+,,,,no,no,no,no
+1-2-21,1000,Buy ETH,,-1000,1,,
+1-2-21,2000,Buy USDC,,-2000,,2000,
+3-1-21,4000,Deposit,lp-deposit,,-1,-2000,10
+6-1-21,5000,Withdraw,lp-withdrawal,,1.1,1900,-10
+";
+    let process = |treatment: PoolTreatment| {
+
+        let mut settings = _test_settings();
+        settings.pool_treatment = treatment;
+        let path = dir.input_file("liquidity_pool.csv", input);
+        let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+            .expect("Test input file failed to process");
+
+        let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+        let lp_lot_count = acct_map.get(&4).unwrap().list_of_lots.borrow().len();
+        (txns_map.len(), lp_lot_count, acct_map.get(&2).unwrap().get_sum_of_lk_basis_in_lots(), summary.realized_total())
+    };
+
+    // Each deposit and withdrawal is two exchanges, and each leg gets half of the LP tokens
+    let (txn_count, _, eth_basis, realized) = process(PoolTreatment::Swap);
+    assert_eq!(txn_count, 6);
+    assert_eq!(eth_basis, dec!(2500), "The ETH withdrawn is acquired at half of the withdrawal's value.");
+    assert_eq!(realized, dec!(2000), "A gain of 1000 on the deposit (on ETH) and of 1000 on the withdrawal.");
+
+    let (_, lp_lot_count, eth_basis, realized) = process(PoolTreatment::Carryover);
+    assert_eq!(lp_lot_count, 2);
+    assert_eq!(eth_basis, dec!(1000), "The first half of the LP tokens withdrawn, from the first lot, carries its basis over to the ETH.");
+    assert_eq!(realized, dec!(0));
+}
+
+#[test]
+fn _test_entities_divide_accounts_and_treat_transfers() {
+
+    let dir = _TestDir::new("entities_divide_accounts_and_treat_transfers");
+
+    // BTC bought for 10000 is moved to the LLC's wallet when worth 15000, and the LLC sells it for 20000
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,LLC Wallet
+,,,USD,BTC,BTC
+,,,no,no,no
+1-2-20,10000,Buy BTC,-10000,1,
+6-1-20,15000,To LLC,,-1,1
+9-1-20,20000,LLC sells,,,-1
+";
+    let accounts = "account,name,ticker,entity\n1,Bank,USD,\n2,Wallet,BTC,\n3,LLC Wallet,BTC,LLC\n";
+    let accounts_file = AccountsFile::from_reader(accounts.as_bytes()).expect("Test accounts file failed to parse");
+    let entities = Entities::from_accounts_file(&accounts_file).unwrap().unwrap();
+    assert_eq!(entities, Entities::parse("LLC=3").unwrap());
+    assert_eq!(entities.entity_of(2), "personal");
+    assert!(Entities::parse("LLC=3,Trust=3").is_err(), "An account belongs to one entity.");
+    assert!(Entities::parse("LLC/1=3").is_err(), "An entity names a directory.");
+
+    let mut settings = _test_settings();
+    settings.entities = Some(entities.clone());
+    let path = dir.input_file("entities.csv", input);
+
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+    assert_eq!(entities.names(&raw_accts), vec!["LLC".to_string(), "personal".to_string()]);
+    assert_eq!(entities.accounts_of("personal", &raw_accts), vec![1, 2]);
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.realized_st, dec!(10000), "With carryover, the LLC takes the basis of 10000.");
+
+    settings.entity_transfers = EntityTransferTreatment::Sale;
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+    assert_eq!(txns_map.get(&2).unwrap().kind, TxKind::EntitySale);
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.realized_st, dec!(10000), "5000 on the sale to the LLC, and 5000 on the LLC's sale.");
+    let llc_lot = &acct_map.get(&3).unwrap().list_of_lots.borrow()[0];
+    assert_eq!(llc_lot.movements.borrow()[0].cost_basis.get(), dec!(15000), "The LLC's basis is the sale's proceeds.");
+    assert_eq!(llc_lot.date_for_basis_purposes, txns_map.get(&2).unwrap().date);
+
+    settings.report_filter.accounts = Some(entities.accounts_of("LLC", &raw_accts));
+    assert!(settings.is_reported_account(raw_accts.get(&3).unwrap()));
+    assert!(!settings.is_reported_account(raw_accts.get(&2).unwrap()));
+}
+
+#[test]
+fn _test_balance_history_and_highest_balances() {
+
+    let dir = _TestDir::new("balance_history_and_highest_balances");
+
+    // Two BTC are held by mid-March, 1.5 are sold in June, and 0.1 is bought the next February
+    let input = "\
+txDate,proceeds,memo,1,2
+,,,Bank,Wallet
+,,,USD,BTC
+,,,no,no
+1-2-20,10000,Buy BTC,-10000,1
+3-15-20,10000,Buy BTC,-10000,1
+6-1-20,15000,Sell BTC,15000,-1.5
+2-1-21,4000,Buy BTC,-4000,0.1
+";
+    let settings = _test_settings();
+    let path = dir.input_file("balance_history.csv", input);
+    let (raw_accts, acct_map, _ars, _txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    assert_eq!("Monthly".parse::<BalanceInterval>(), Ok(BalanceInterval::Monthly));
+
+    let monthly = BalanceHistory::from_maps(&settings, BalanceInterval::Monthly, &raw_accts, &acct_map);
+    assert_eq!(monthly.tickers, vec!["BTC".to_string(), "USD".to_string()]);
+    assert_eq!(monthly.balances.len(), 14, "January 2020 through February 2021.");
+    assert_eq!(monthly.balances[2], (date("2020-03-31"), vec![dec!(2), dec!(-20000)]));
+    assert_eq!(monthly.balances[13].0, date("2021-02-28"));
+
+    let daily = BalanceHistory::from_maps(&settings, BalanceInterval::Daily, &raw_accts, &acct_map);
+    assert_eq!(daily.balances.len(), 397);
+    assert_eq!(daily.balances[73], (date("2020-03-15"), vec![dec!(2), dec!(-20000)]));
+
+    let highest = balance_history::highest_balances(&settings, &raw_accts, &acct_map);
+    let btc: Vec<&HighestBalance> = highest.iter().filter(|balance| balance.ticker == "BTC").collect();
+    assert_eq!(btc.len(), 2);
+    assert_eq!((btc[0].year, btc[0].highest, btc[0].on, btc[0].year_end), (2020, dec!(2), date("2020-03-15"), dec!(0.5)));
+    assert_eq!((btc[1].year, btc[1].highest, btc[1].on), (2021, dec!(0.6), date("2021-02-01")));
+}
+
+#[test]
+fn _test_asset_statistics_average_costs() {
+
+    let dir = _TestDir::new("asset_statistics_average_costs");
+
+    // Two BTC are bought at 10000 and 20000, one is moved to another wallet, and half of one is sold for 9000
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,Ledger
+,,,USD,BTC,BTC
+,,,no,no,no
+1-2-20,10000,Buy BTC,-10000,1,
+3-1-20,20000,Buy BTC,-20000,1,
+4-1-20,,To Ledger,,-1,1
+6-1-20,9000,Sell BTC,9000,-0.5,
+";
+    let settings = _test_settings();
+    let path = dir.input_file("asset_statistics.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let assets = summary::asset_statistics(&settings.home_currency, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(assets.len(), 1, "The home currency has no statistics.");
+
+    let btc = &assets[0];
+    assert_eq!((btc.acquired, btc.acquisition_cost), (dec!(2), dec!(30000)), "The transfer isn't an acquisition.");
+    assert_eq!(btc.average_acquisition_cost(), Some(dec!(15000)));
+    assert_eq!((btc.disposed, btc.average_sale_price(), btc.realized), (dec!(0.5), Some(dec!(18000)), dec!(4000)));
+    assert_eq!((btc.holding, btc.holding_basis), (dec!(1.5), dec!(25000)));
+    assert_eq!(btc.average_holding_basis(), Some(dec!(16666.66666667)));
+}
+
+#[test]
+fn _test_token_migration_carries_basis_and_basis_date() {
+
+    let dir = _TestDir::new("token_migration_carries_basis_and_basis_date");
+
+    // WBTC bought for 30000 is migrated to BTC when worth 50000, and the BTC sold for 40000 a year after the purchase
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,Wallet
+,,,USD,WBTC,BTC
+,,,no,no,no
+1-2-20,30000,Buy WBTC,-30000,1,
+12-1-20,50000,Unwrap,,-1,1
+3-1-21,40000,Sell BTC,40000,,-1
+";
+    let aliases_path = dir.input_file("token_aliases.csv", "alias,ticker\nwbtc,BTC\nVEN,VET\n");
+    let aliases = TokenAliases::from_file(&aliases_path).expect("Test token aliases file failed to import");
+    assert!(aliases.are_equivalent("WBTC", "btc"));
+    assert!(!aliases.are_equivalent("WBTC", "VET"));
+    assert!(!aliases.are_equivalent("BTC", "BTC"));
+
+    let mut settings = _test_settings();
+    settings.token_aliases = Some(aliases);
+    let path = dir.input_file("token_migration.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    assert_eq!(txns_map.get(&2).unwrap().kind, TxKind::TokenMigration);
+
+    let summary = RunSummary::from_maps(&settings.home_currency, settings.long_term_days, settings.fiscal_year_start_month, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(summary.realized_st, dec!(0), "The migration isn't a disposal, and the BTC's basis date is the WBTC's.");
+    assert_eq!(summary.realized_lt, dec!(10000));
+
+    let circular = dir.input_file("token_aliases_circular.csv", "alias,ticker\nA,B\nB,A\n");
+    assert!(TokenAliases::from_file(&circular).is_err());
+}
+
+#[test]
+fn _test_nfts_listed_by_item() {
+
+    let dir = _TestDir::new("nfts_listed_by_item");
+
+    // BAYC#1 is bought, moved to a vault and sold, and BAYC#2 is bought and held
+    let input = "\
+txDate,proceeds,memo,1,2,3,4,5
+,,,Bank,Wallet,Wallet,Vault,Wallet
+,,,USD,ETH,BAYC#1,BAYC#1,BAYC#2
+,,,no,no,no,no,no
+1-2-21,20000,Buy ETH,-20000,10,,,
+2-1-21,10000,Buy BAYC#1,,-5,1,,
+3-1-21,,To vault,,,-1,1,
+4-1-21,16000,Sell BAYC#1,,8,,-1,
+5-1-21,2000,Buy BAYC#2,,-1,,,1
+";
+    assert_eq!(nfts::collection_and_token_id("BAYC#1234"), Some(("BAYC", "1234")));
+    assert_eq!(nfts::collection_and_token_id("BTC"), None);
+
+    let settings = _test_settings();
+    let path = dir.input_file("nfts.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let items = nfts::nft_items(settings.long_term_days, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(items.len(), 2, "The item moved to the vault is listed once.");
+
+    let sold = &items[0];
+    assert_eq!((sold.account_key, sold.token_id.as_str(), sold.cost_basis, sold.acquired_tx_num), (4, "1", dec!(10000), 2));
+    assert_eq!(sold.acquired, NaiveDate::from_ymd_opt(2021, 2, 1).unwrap(), "The transfer carries the basis date over.");
+    assert_eq!(sold.sale.as_ref().map(|sale| (sale.tx_num, sale.gain_or_loss)), Some((4, dec!(6000))));
+
+    assert_eq!(items[1].token_id, "2");
+    assert!(items[1].sale.is_none());
+
+    // Two of the same NFT in one account can't be
+    let doubled = input.replace("5-1-21,2000,Buy BAYC#2,,-1,,,1", "5-1-21,2000,Buy BAYC#2,,-1,,,2");
+    let path = dir.input_file("nfts_doubled.csv", &doubled);
+    assert!(core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default()).is_err());
+}
+
+#[test]
+fn _test_like_kind_exchanges_report_deferred_gain() {
+
+    let dir = _TestDir::new("like_kind_exchanges_report_deferred_gain");
+
+    // BTC bought for 1000 is exchanged for ETH worth 3000 before the cut-off, and the ETH sold after it
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,Wallet
+,,,USD,BTC,ETH
+,,,no,no,no
+1-2-16,1000,Buy BTC,-1000,1,
+6-1-16,3000,Trade,,-1,100
+2-1-19,4000,Sell ETH,4000,,-100
+";
+    let mut settings = _test_settings();
+    settings.lk_treatment_enabled = true;
+    settings.lk_cutoff_date = NaiveDate::from_ymd_opt(2017, 12, 31).unwrap();
+    let path = dir.input_file("like_kind.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let exchanges = like_kind::like_kind_exchanges(&settings.home_currency, settings.lk_cutoff_date, &settings.lk_scope, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(exchanges.len(), 1, "Only the exchange of one non-home currency for another is like-kind.");
+
+    let exchange = &exchanges[0];
+    assert_eq!((exchange.tx_num, exchange.given_acquired), (2, NaiveDate::from_ymd_opt(2016, 1, 2).unwrap()));
+    assert_eq!((exchange.fair_market_value, exchange.given_basis), (dec!(3000), dec!(1000)));
+    assert_eq!(exchange.deferred_gain, dec!(2000));
+    assert_eq!(exchange.carryover_basis, dec!(1000), "The ETH takes the basis of the BTC given up.");
+}
+
+#[test]
+fn _test_like_kind_basis_in_action_records() {
+
+    let dir = _TestDir::new("like_kind_basis_in_action_records");
+
+    // The ETH received in a like-kind exchange is booked at the BTC's basis, not at its value
+    let input = "\
+txDate,proceeds,memo,1,2,3
+,,,Bank,Wallet,Wallet
+,,,USD,BTC,ETH
+,,,no,no,no
+1-2-16,1000,Buy BTC,-1000,1,
+6-1-16,3000,Trade,,-1,100
+";
+    let mut settings = _test_settings();
+    settings.lk_treatment_enabled = true;
+    settings.lk_cutoff_date = NaiveDate::from_ymd_opt(2017, 12, 31).unwrap();
+    let path = dir.input_file("like_kind_basis.csv", input);
+    let (_, _, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let trade = txns_map.get(&2).unwrap();
+    let (given, received) = (ars.get(&trade.action_record_idx_vec[0]).unwrap(), ars.get(&trade.action_record_idx_vec[1]).unwrap());
+    assert_eq!((given.lk_cost_basis_in_ar(), received.lk_cost_basis_in_ar()), (dec!(1000), dec!(1000)));
+    assert_eq!(received.cost_basis_in_ar(), dec!(3000));
+}
+
+/// With `LK_PAIRS` of `crypto/crypto`, an exchange of crypto for a non-home fiat currency (by `LK_ASSET_CLASSES`) should
+/// be taxable, while one of crypto for crypto is like-kind, and with `LK_WINDOWS`, only those within a window are.
+#[test]
+fn _test_like_kind_scope_limits_pairs_and_windows() {
+
+    let dir = _TestDir::new("like_kind_scope_limits_pairs_and_windows");
+
+    let input = "\
+txDate,proceeds,memo,1,2,3,4
+,,,Bank,Wallet,Wallet,Bank
+,,,USD,BTC,ETH,EUR
+,,,no,no,no,no
+1-2-16,2000,Buy BTC,-2000,2,,
+3-1-16,1500,Trade for EUR,,-1,,1400
+6-1-16,3000,Trade for ETH,,-1,100,
+";
+    let mut settings = _test_settings();
+    settings.lk_treatment_enabled = true;
+    settings.lk_cutoff_date = NaiveDate::from_ymd_opt(2017, 12, 31).unwrap();
+    settings.lk_scope = like_kind::LikeKindScope {
+        windows: vec![],
+        classes: like_kind::parse_asset_classes("fiat=EUR|gbp").unwrap(),
+        pairs: like_kind::parse_pairs("crypto/crypto").unwrap(),
+    };
+    let path = dir.input_file("like_kind_scope.csv", input);
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(std::slice::from_ref(&path), &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let exchanges = like_kind::like_kind_exchanges(&settings.home_currency, settings.lk_cutoff_date, &settings.lk_scope, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(exchanges.iter().map(|exchange| exchange.tx_num).collect::<Vec<u32>>(), vec![3]);
+
+    let received = |num: u32| ars.get(&txns_map.get(&num).unwrap().action_record_idx_vec[1]).unwrap().lk_cost_basis_in_ar();
+    assert_eq!(received(2), dec!(1500), "The EUR is booked at its value, the trade being taxable.");
+    assert_eq!(received(3), dec!(1000), "The ETH takes the basis of the BTC given up.");
+
+    settings.lk_scope = like_kind::LikeKindScope {
+        windows: like_kind::parse_windows("2016-01-01..2016-03-31, 2017-01-01..2017-12-31").unwrap(),
+        ..Default::default()
+    };
+    let (raw_accts, acct_map, ars, txns_map) = core_functions::import_and_process_final(&[path], &settings, &mut core_functions::Timings::default())
+        .expect("Test input file failed to process");
+
+    let exchanges = like_kind::like_kind_exchanges(&settings.home_currency, settings.lk_cutoff_date, &settings.lk_scope, &raw_accts, &acct_map, &ars, &txns_map).unwrap();
+    assert_eq!(exchanges.iter().map(|exchange| exchange.tx_num).collect::<Vec<u32>>(), vec![2], "June 2016 is between the windows.");
+    assert_eq!(settings.lk_scope.last_date(), NaiveDate::from_ymd_opt(2017, 12, 31));
+
+    assert!(like_kind::parse_windows("2017-12-31..2016-01-01").is_err());
+    assert!(like_kind::parse_windows("2016-01-01").is_err());
+    assert!(like_kind::parse_asset_classes("fiat=EUR,stable=EUR").is_err());
+    assert!(like_kind::parse_pairs("crypto").is_err());
+}
+
+/// The checksums in a run's manifest should be those `sha256sum` prints, including for input spanning several blocks
+/// (and for input whose padding needs a block of its own).
+#[test]
+fn _test_sha256_matches_known_digests() {
+
+    assert_eq!(checksums::sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(checksums::sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(
+        checksums::sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    assert_eq!(
+        checksums::sha256_hex(&[b'a'; 1000]),
+        "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+    );
+}
+
+/// As do SHA-512 and the HMACs, which sign the requests to exchanges' APIs (RFC 4231's test case 2, and a key longer
+/// than a block).
+#[test]
+fn _test_sha512_and_hmac_match_known_digests() {
+
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+    assert_eq!(hex(&checksums::sha512(b"abc")), "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+        2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f");
+    assert_eq!(hex(&checksums::sha512(&[b'a'; 1000])), "67ba5535a46e3f86dbfbed8cbbaf0125c76ed549ff8b0b9e03e0c88cf90fa634\
+        fa7b12b47d77b694de488ace8d9a65967dc96df599727d3292a8d9d447709c97");
+    assert_eq!(
+        hex(&checksums::hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(hex(&checksums::hmac_sha512(b"Jefe", b"what do ya want for nothing?")), "164b7a7bfcf819e2e395fbe73b56e0a3\
+        87bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737");
+    assert_eq!(hex(&checksums::hmac_sha512(&[b'k'; 200], b"x")), "3bea04514dec7eac85d846ec90cead0c22ff461ba7250c0363587676\
+        6055b829aac0109c567fa359e5f177a9e8c30233a682a33467845ac6f966813635668f7a");
+}
+
+/// The report language follows a locale, fills its placeholders as `format!()` would (or in another order), and its
+/// catalogs translate the same text, keeping each placeholder.
+#[test]
+fn _test_report_language_catalogs_translate_and_fill() {
+
+    assert_eq!(ReportLanguage::from_code("de_AT.UTF-8"), Some(ReportLanguage::German));
+    assert_eq!(ReportLanguage::from_code("es"), Some(ReportLanguage::Spanish));
+    assert_eq!(ReportLanguage::from_code("C"), Some(ReportLanguage::English));
+    assert_eq!(ReportLanguage::from_code("fr_FR"), None);
+
+    let english = ReportLanguage::English;
+    assert_eq!(english.fill("Txn #{:>4} of {:<5}|{}", &[&7, &"BTC", &"x"]), "Txn #   7 of BTC  |x");
+    assert_eq!(english.fill("{1} before {0}, {{literally}}", &[&"a", &"b"]), "b before a, {literally}");
+    assert_eq!(english.fill("Missing: {}{}", &[&1]), "Missing: 1");
+    assert_eq!(english.text("Holdings"), "Holdings");
+
+    assert_eq!(ReportLanguage::Spanish.text("Holdings"), "Tenencias");
+    assert_eq!(ReportLanguage::German.fill("Txn {} on {}: {} ({}) - {}", &[&3, &"2023-01-02", &"a", &"b", &"c"]),
+        "Txn 3 am 2023-01-02: a (b) - c");
+
+    let spanish = ReportLanguage::Spanish.catalog_entries();
+    let german = ReportLanguage::German.catalog_entries();
+
+    assert!(!spanish.is_empty());
+    assert_eq!(
+        spanish.iter().map(|(msgid, _)| msgid).collect::<Vec<_>>(),
+        german.iter().map(|(msgid, _)| msgid).collect::<Vec<_>>(),
+    );
+    for (msgid, msgstr) in spanish.iter().chain(german.iter()) {
+        assert_eq!(report_language::placeholders(msgid), report_language::placeholders(msgstr), "{}", msgid);
+    }
+}
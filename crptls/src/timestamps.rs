@@ -0,0 +1,72 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use chrono::{FixedOffset, NaiveDate, NaiveTime, TimeZone};
+
+
+/// Parses a UTC offset: `UTC` (or `Z`), or `+HH:MM`, `+HHMM` or `+HH` (or the same with a `-`), which may follow
+/// `UTC` (i.e., `UTC-05:00`).
+pub fn parse_utc_offset(field: &str) -> Option<FixedOffset> {
+
+    let field = field.trim();
+    let offset = field.strip_prefix("UTC").unwrap_or(field);
+
+    if offset.is_empty() || offset == "Z" { return FixedOffset::east_opt(0) }
+
+    let (sign, digits) = match offset.split_at(1) {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+
+    let digits = digits.replace(':', "");
+    if !digits.chars().all(|c| c.is_ascii_digit()) { return None }
+
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+
+    if minutes >= 60 { return None }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parses a `txTime`: `HH:MM` or `HH:MM:SS` (with any fraction of a second), which may be followed by its UTC offset
+/// (i.e., `23:30:00Z` or `18:30-05:00`).
+pub(crate) fn parse_tx_time(field: &str) -> Option<(NaiveTime, Option<FixedOffset>)> {
+
+    let field = field.trim();
+
+    let (time, offset) = match field.find(['Z', '+', '-', 'U']) {
+        Some(idx) => (field[..idx].trim(), Some(parse_utc_offset(&field[idx..])?)),
+        None => (field, None),
+    };
+
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok()?;
+
+    Some((time, offset))
+}
+
+/// The date and time of a `txDate` and `txTime` in the `timezone`, if one is set (a time without an offset being
+/// UTC, as exchanges export them), or as they are, if not.
+pub(crate) fn local_date_time(
+    date: NaiveDate,
+    time: NaiveTime,
+    offset: Option<FixedOffset>,
+    timezone: Option<FixedOffset>,
+) -> (NaiveDate, NaiveTime) {
+
+    let timezone = match timezone {
+        Some(timezone) => timezone,
+        None => return (date, time),
+    };
+
+    let offset = offset.unwrap_or(FixedOffset::east_opt(0).unwrap());
+    let local = offset.from_local_datetime(&date.and_time(time)).unwrap().with_timezone(&timezone).naive_local();
+
+    (local.date(), local.time())
+}
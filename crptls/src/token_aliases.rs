@@ -0,0 +1,135 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind};
+
+
+/// Tickers that are the same asset under another name: a wrapped token (i.e., WBTC for BTC) or a token replaced in a
+/// migration (i.e., VEN for VET).  An exchange of one for another of the same asset is a `TxKind::TokenMigration`.
+#[derive(Clone, Debug, Default)]
+pub struct TokenAliases {
+    /// Each alias, and the ticker it is an alias of.
+    aliases: HashMap<String, String>,
+}
+
+impl TokenAliases {
+
+    /// Reads a token aliases file: a CSV with a header row, then one `alias,ticker` row per alias (i.e., `WBTC,BTC`).
+    /// Tickers are case-insensitive, and several aliases may share a ticker (which may itself be an alias).
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+
+        let file = File::open(path)?;
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_reader(file);
+
+        let mut aliases: HashMap<String, String> = HashMap::new();
+
+        for (idx, result) in rdr.records().enumerate() {
+
+            let record = result?;
+            let row = idx + 2;  //  Account for the header and one-based row numbers
+
+            let (alias, ticker) = match (record.get(0), record.get(1)) {
+                (Some(alias), Some(ticker)) if !alias.is_empty() && !ticker.is_empty() => {
+                    (alias.to_uppercase(), ticker.to_uppercase())
+                }
+                _ => return Err(format!("Token aliases file row {} must have an alias and a ticker.", row).into()),
+            };
+
+            if alias == ticker {
+                return Err(format!("Token aliases file row {} makes {} an alias of itself.", row, alias).into())
+            }
+
+            if aliases.insert(alias.clone(), ticker).is_some() {
+                return Err(format!("Token aliases file row {} repeats the alias {}.", row, alias).into())
+            }
+        }
+
+        let token_aliases = TokenAliases { aliases };
+
+        //  Following each alias must lead to a ticker that isn't one, not around in a circle
+        for alias in token_aliases.aliases.keys() {
+            if token_aliases.resolve_chain(alias).is_none() {
+                return Err(format!("The token aliases file's aliases of {} lead back to it.", alias).into())
+            }
+        }
+
+        Ok(token_aliases)
+    }
+
+    /// The ticker that `ticker` is, ultimately, an alias of (i.e., BTC for WBTC, and for BTC itself).
+    pub fn canonical(&self, ticker: &str) -> String {
+        self.resolve_chain(&ticker.to_uppercase()).unwrap_or_else(|| ticker.to_uppercase())
+    }
+
+    /// Whether the two (different) tickers are the same asset.
+    pub fn are_equivalent(&self, ticker: &str, other: &str) -> bool {
+        !ticker.eq_ignore_ascii_case(other) && self.canonical(ticker) == self.canonical(other)
+    }
+
+    /// Whether `ticker` is an alias, or the ticker of one.
+    pub fn contains(&self, ticker: &str) -> bool {
+        let ticker = ticker.to_uppercase();
+        self.aliases.contains_key(&ticker) || self.aliases.values().any(|aliased| *aliased == ticker)
+    }
+
+    fn resolve_chain(&self, ticker: &str) -> Option<String> {
+
+        let mut current = ticker.to_string();
+
+        for _ in 0..=self.aliases.len() {
+            match self.aliases.get(&current) {
+                Some(aliased) => current = aliased.clone(),
+                None => return Some(current),
+            }
+        }
+
+        None
+    }
+}
+
+/// Marks every exchange of one ticker for another of the same asset (see `TokenAliases`) as a
+/// `TxKind::TokenMigration`, which carries the basis and basis dates of what was given over to what was received,
+/// realizing no gain or loss.  Only `Standard` exchanges between non-margin `Account`s are marked.  Returns the number
+/// of `Transaction`s marked.
+pub(crate) fn mark_token_migrations(
+    token_aliases: &TokenAliases,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> usize {
+
+    let mut marked = 0;
+
+    for txn in txns_map.values_mut() {
+
+        if txn.kind != TxKind::Standard || txn.action_record_idx_vec.len() != 2 { continue }
+
+        let og_ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let ic_ar = ars.get(&txn.action_record_idx_vec[1]).unwrap();
+
+        if og_ar.direction() != Polarity::Outgoing || ic_ar.direction() != Polarity::Incoming { continue }
+
+        let og_raw_acct = raw_acct_map.get(&acct_map.get(&og_ar.account_key).unwrap().raw_key).unwrap();
+        let ic_raw_acct = raw_acct_map.get(&acct_map.get(&ic_ar.account_key).unwrap().raw_key).unwrap();
+
+        if og_raw_acct.is_margin || ic_raw_acct.is_margin { continue }
+
+        if token_aliases.are_equivalent(&og_raw_acct.ticker, &ic_raw_acct.ticker) {
+            txn.kind = TxKind::TokenMigration;
+            marked += 1;
+        }
+    }
+
+    marked
+}
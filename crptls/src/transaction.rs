@@ -3,30 +3,78 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::process;
 use std::fmt;
 use std::collections::HashMap;
 use std::error::Error;
 
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveTime};
 use serde_derive::{Serialize, Deserialize};
 
 use crate::account::{Account, Movement, RawAccount};
+use crate::decimal_utils::round_d128_1e2;
+use crate::tags::Tag;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transaction {
 	pub tx_number: u32,	//	Does NOT start at zero.  First txn is 1.
-	pub date_as_string: String,
+	/// The row of the CSV Input File (as imported, so after any merging or converting) this `Transaction` came from,
+	/// counting the four header rows (so the first transaction row is row 5), or `None` if the program generated it.
+	#[serde(default)]
+	pub source_row: Option<usize>,
+	/// Shared with the `Transaction`s before and after it on the same date, and with its `Lot`s and `Movement`s.
+	pub date_as_string: Rc<str>,
 	pub date: NaiveDate,
+	/// The basis date of any `Lot` this `Transaction` creates.  This is the trade `date` unless settle dates are
+	/// enabled and the CSV Input File has a `settleDate` for the `Transaction`.
+	pub date_for_basis_purposes: NaiveDate,
+	/// The time of day, from the optional `txTime` column of the CSV Input File (in the `timezone`, if one is set), by
+	/// which same-date `Transaction`s are ordered.  `None` if not given, or if the program generated the `Transaction`.
+	#[serde(default)]
+	pub time: Option<NaiveTime>,
+	/// The position among the `Transaction`s of its date (and `time`), from the optional `txSeq` column of the CSV Input
+	/// File, by which they're ordered after the `time`.  `None` if not given, or if the program generated the
+	/// `Transaction`.
+	#[serde(default)]
+	pub sequence: Option<u32>,
 	pub user_memo: String,
-	pub proceeds: f32,
+	pub proceeds: Decimal,
+	/// The home currency value of any fee, from the optional `fee` column of the CSV Input File (zero if none), in
+	/// which case `proceeds` are before the fee.  It is added to the basis of what a purchase (with home currency)
+	/// acquires and subtracted from the proceeds of a disposal (and from the basis of what a trade for another
+	/// non-home currency acquires).  On a transfer, it is the value of the units sent but not received (see
+	/// `transfer_fees`).
+	#[serde(default)]
+	pub fee: Decimal,
+	/// What kind of income an income `Transaction` is, from the optional `incomeType` column of the CSV Input File
+	/// (`None` if not given, or if the `Transaction` isn't income).
+	#[serde(default)]
+	pub income_type: Option<IncomeType>,
+	/// The labels of the `Transaction`, from its memo and the optional `tags` column of the CSV Input File (see
+	/// `tags`).
+	#[serde(default)]
+	pub tags: Vec<Tag>,
+	/// The category of a spend's expense (i.e., `meals`), from the optional `expenseCategory` column of the CSV Input
+	/// File or an `#expense:` tag (see `tags::column_or_tag()`).  `None` if not given, in which case it's
+	/// uncategorized.  A transfer's fee takes the category of its transfer.
+	#[serde(default)]
+	pub expense_category: Option<String>,
+	/// Who paid an income `Transaction` (i.e., a client or an exchange), from the optional `payer` column of the CSV
+	/// Input File or a `#payer:` tag.  `None` if not given.
+	#[serde(default)]
+	pub payer: Option<String>,
 	pub action_record_idx_vec: Vec<u32>,
+	pub kind: TxKind,
 }
 
 impl Transaction {
 
+	/// The `proceeds` less the `fee`, which is what a disposal of a non-home currency is deemed to have realized.
+	pub fn proceeds_net_of_fee(&self) -> Decimal {
+		self.proceeds - self.fee
+	}
+
 	pub fn transaction_type(
 		&self,
 		ars: &HashMap<u32, ActionRecord>,
@@ -53,13 +101,16 @@ impl Transaction {
 			let ar2_ticker = ar2_ticker_comp[0];
 
 			if first_ar.direction() == second_ar.direction() {
-                println!("FATAL: TxType: Found transaction with two actionRecords with the same polarity: \n{:#?}", self);
-                process::exit(1);
+				return Err(format!("Txn {} on {} has two quantities in the same direction (both increases or both \
+					decreases), so it's neither a transfer nor an exchange.", self.tx_number, self.date_as_string).into())
 			}
 			if ar1_ticker == ar2_ticker {
 				if ar1_raw_acct.is_margin != ar2_raw_acct.is_margin {
 					Ok(TxType::Flow)
 				}
+				else if self.kind == TxKind::EntitySale {
+					Ok(TxType::Exchange)
+				}
 				else {
 					Ok(TxType::ToSelf)
 				}
@@ -69,12 +120,11 @@ impl Transaction {
 			}
 		}
 		else if self.action_record_idx_vec.len() > 2 {
-            println!("FATAL: TxType: Found transaction with too many actionRecords: \n{:#?}", self);
-            process::exit(1);
+			Err(format!("Txn {} on {} has {} quantities, but a transaction may have at most two.", self.tx_number,
+				self.date_as_string, self.action_record_idx_vec.len()).into())
 		}
 		else {
-            println!("FATAL: TxType: Found transaction with no actionRecords: \n{:#?}", self);
-            process::exit(1);
+			Err(format!("Txn {} on {} has no quantities.", self.tx_number, self.date_as_string).into())
 		}
 	}
 
@@ -83,36 +133,28 @@ impl Transaction {
 		ars: &HashMap<u32, ActionRecord>,
 		raw_acct_map: &HashMap<u16, RawAccount>,
 		acct_map: &HashMap<u16, Account>
-	) -> TxHasMargin {
+	) -> Result<TxHasMargin, Box<dyn Error>> {
 
 		if self.action_record_idx_vec.len() == 1 {
 			let ar = ars.get(&self.action_record_idx_vec[0]).unwrap();
 			let acct = acct_map.get(&ar.account_key).unwrap();
 			let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
 			if raw_acct.is_margin {
-				TxHasMargin::OneAR
+				Ok(TxHasMargin::OneAR)
 			} else {
-				TxHasMargin::NoARs
+				Ok(TxHasMargin::NoARs)
 			}
 		} else {
 
             if self.action_record_idx_vec.len() != 2 {
-                println!("FATAL: Each transaction may have up to two actionrecords, and there are {} actionrecords in transaction:\n{:#?}",
-                self.action_record_idx_vec.len(), self);
+                return Err(format!("Txn {} on {} has {} quantities, but a transaction may have at most two.",
+                    self.tx_number, self.date_as_string, self.action_record_idx_vec.len()).into())
             }
 
-            let first_ar = match ars.get(&self.action_record_idx_vec[0]) {
-                Some(x) => x,
-                None => {
-                    println!("FATAL: ActionRecord not found for: \n{:#?}", self);
-                    process::exit(1)}
-            };
-			let second_ar = match ars.get(&self.action_record_idx_vec[1]) {
-                Some(x) => x,
-                None => {
-                    println!("FATAL: ActionRecord not found for: \n{:#?}", self);
-                    process::exit(1)}
-            };
+            let first_ar = ars.get(&self.action_record_idx_vec[0])
+                .ok_or_else(|| format!("Txn {}: its first quantity wasn't found.", self.tx_number))?;
+			let second_ar = ars.get(&self.action_record_idx_vec[1])
+                .ok_or_else(|| format!("Txn {}: its second quantity wasn't found.", self.tx_number))?;
 
 			let first_acct = acct_map.get(&first_ar.account_key).unwrap();
 			let second_acct = acct_map.get(&second_ar.account_key).unwrap();
@@ -121,8 +163,8 @@ impl Transaction {
 			let second_raw_acct = &raw_acct_map.get(&second_acct.raw_key).unwrap();
 
 			if first_raw_acct.is_margin {
-				if second_raw_acct.is_margin {TxHasMargin::TwoARs} else {TxHasMargin::OneAR}
-			} else if second_raw_acct.is_margin {TxHasMargin::OneAR} else {TxHasMargin::NoARs}
+				if second_raw_acct.is_margin {Ok(TxHasMargin::TwoARs)} else {Ok(TxHasMargin::OneAR)}
+			} else if second_raw_acct.is_margin {Ok(TxHasMargin::OneAR)} else {Ok(TxHasMargin::NoARs)}
 		}
 	}
 
@@ -160,8 +202,7 @@ impl Transaction {
 			quote = second_acct_raw_key;
 			Ok((base, quote))
 		} else {
-            println!("FATAL: {}", VariousErrors::MarginNoUnderbar);
-            std::process::exit(1);
+			Err(format!("Txn {}: {}", self.tx_number, VariousErrors::MarginNoUnderbar).into())
 		}
 	}
 
@@ -184,7 +225,7 @@ impl Transaction {
 
             if !raw_acct.is_home_currency(user_home_currency) & !raw_acct.is_margin {
 
-                let movements = ar.get_mvmts_in_ar_in_lot_date_order(acct_map, txns_map);
+                let movements = ar.get_mvmts_in_ar_in_lot_date_order();
 
                 match self.transaction_type(ars, raw_acct_map, acct_map)? {
                     TxType::Exchange => {
@@ -242,7 +283,7 @@ impl Transaction {
 
             let tx_type = self.transaction_type(ars, raw_accts, acct_map)?;
 
-            let marginness = self.marginness(ars, raw_accts, acct_map);
+            let marginness = self.marginness(ars, raw_accts, acct_map)?;
 
             if (marginness == TxHasMargin::NoARs) | (marginness == TxHasMargin::TwoARs)  {
 
@@ -261,7 +302,7 @@ impl Transaction {
                 let og_amt_and_ticker;
                 if og_raw_acct.is_home_currency(home_currency) {
                     og_amt_and_ticker = format!("{:.2} {}",
-                        og_amt.to_string().as_str().parse::<f32>()?, og_ticker
+                        round_d128_1e2(&og_amt), og_ticker
                     );
                 } else {
                     og_amt_and_ticker = format!("{} {}", og_amt, og_ticker);
@@ -270,7 +311,7 @@ impl Transaction {
                 let ic_amt_and_ticker;
                 if ic_raw_acct.is_home_currency(home_currency) {
                     ic_amt_and_ticker = format!("{:.2} {}",
-                        ic_amt.to_string().as_str().parse::<f32>()?, ic_ticker
+                        round_d128_1e2(&ic_amt), ic_ticker
                     );
                 } else {
                     ic_amt_and_ticker = format!("{} {}", ic_amt, ic_ticker);
@@ -279,7 +320,7 @@ impl Transaction {
                 if tx_type == TxType::Exchange {
                     format!("Paid {} for {}, valued at {:.2} {}.",
                         og_amt_and_ticker, ic_amt_and_ticker,
-                        self.proceeds.to_string().as_str().parse::<f32>()?, home_currency)
+                        round_d128_1e2(&self.proceeds), home_currency)
                 } else {
                     format!("Transferred {} to another account. Received {}, likely after a transaction fee.",
                         og_amt_and_ticker, ic_amt_and_ticker)
@@ -287,7 +328,7 @@ impl Transaction {
             } else {
 
                 format!("Margin profit or loss valued at {:.2} {}.",
-                self.proceeds.to_string().as_str().parse::<f32>()?, home_currency)
+                round_d128_1e2(&self.proceeds), home_currency)
             }
 
         } else {
@@ -301,12 +342,12 @@ impl Transaction {
             if amt > dec!(0.0) {
 
                 format!("Received {} {} valued at {:.2} {}.", amt, ticker,
-                self.proceeds.to_string().as_str().parse::<f32>()?, home_currency)
+                round_d128_1e2(&self.proceeds), home_currency)
 
             } else {
 
                 format!("Spent {} {} valued at {:.2} {}.", amt, ticker,
-                self.proceeds.to_string().as_str().parse::<f32>()?, home_currency)
+                round_d128_1e2(&self.proceeds), home_currency)
 
             }
         };
@@ -342,6 +383,18 @@ impl ActionRecord {
         cb.abs()
     }
 
+    /// As `cost_basis_in_ar()`, but of the like-kind basis (the same, unless like-kind treatment was applied).
+    pub fn lk_cost_basis_in_ar(&self) -> Decimal {
+
+        let mut cb = dec!(0);
+
+        for mvmt in self.movements.borrow().iter() {
+            cb += mvmt.cost_basis_lk.get()
+        }
+
+        cb.abs()
+    }
+
 	// pub fn is_quote_acct_for_margin_exch(
 	// 	&self,
 	// 	raw_accts: &HashMap<u16, RawAccount>,
@@ -353,9 +406,11 @@ impl ActionRecord {
 	// 	raw_acct.ticker.contains('_')
 	// }
 
-    /// Iterates through every `Lot` in the `list_of_lots` of the `ActionRecord`'s `Account`
-    /// until it finds all the `Movements` - cloning each along the way - and then returns
-    /// a `Vec` of `Rc<Movements>`.
+    /// Returns the `ActionRecord`'s `Movements` (cloning each `Rc`) in the order of the `Lot`s they were posted to,
+    /// which is the order they would be found by iterating through every `Lot` in the `list_of_lots` of the
+    /// `ActionRecord`'s `Account`.  Rather than iterating through every `Lot`, though, which is slow for an
+    /// `Account` with many of them, the `Movements` are sorted by their `lot_num` (keeping the order they were
+    /// posted to a given `Lot`).
     ///
     /// Note that a `Lot`'s `date`, and generally its `basis_date` too, will increase
     /// chronologically along with the `Lot`'s `lot_num` which is just it's `index` in the
@@ -363,34 +418,24 @@ impl ActionRecord {
     /// ordered by their creation date (`date`), so later `Lot`s may have earlier `basis_date`s
     /// by virtue of them being the result of a `ToSelf` type `Transaction` that transferred
     /// old "coins."
-    pub fn get_mvmts_in_ar_in_lot_date_order(
-        &self,
-        acct_map: &HashMap<u16, Account>,
-        txns_map: &HashMap<u32, Transaction>,
-    ) -> Vec<Rc<Movement>> {
+    pub fn get_mvmts_in_ar_in_lot_date_order(&self) -> Vec<Rc<Movement>> {
 
-        let txn = txns_map.get(&self.tx_key).unwrap();
-        let mut movements_in_ar = [].to_vec();
-        let acct = acct_map.get(&self.account_key).unwrap();
+        let mut movements_in_ar = self.movements.borrow().clone();
+        movements_in_ar.sort_by_key(|mvmt| mvmt.lot_num);
 
         let target = self.amount;
         let mut measure = dec!(0);
 
-        for lot in acct.list_of_lots.borrow().iter() {
-
-            for mvmt in lot.movements.borrow().iter() {
-
-                if (mvmt.date) <= txn.date && mvmt.action_record_key == self.self_ar_key {
-
-                    measure += mvmt.amount;
+        for (idx, mvmt) in movements_in_ar.iter().enumerate() {
 
-                    movements_in_ar.push(mvmt.clone());
+            measure += mvmt.amount;
 
-                    if measure == target { return movements_in_ar }
-                }
+            if measure == target {
+                movements_in_ar.truncate(idx + 1);
+                return movements_in_ar
             }
         }
-        println!("ERROR: This should never print.");
+        log::error!("This should never print.");
         movements_in_ar
 	}
 }
@@ -431,6 +476,137 @@ impl fmt::Display for TxHasMargin {
     }
 }
 
+/// Numbers `ordered_txns` consecutively in their `Vec` order (starting at 1), updates the `tx_key` of each of their
+/// `ActionRecord`s to match, and inserts them into the (empty) `txns_map`.
+pub(crate) fn renumber_transactions(
+	ordered_txns: Vec<Transaction>,
+	ar_map: &mut HashMap<u32, ActionRecord>,
+	txns_map: &mut HashMap<u32, Transaction>,
+) {
+
+	for (idx, mut txn) in ordered_txns.into_iter().enumerate() {
+
+		txn.tx_number = idx as u32 + 1;
+
+		for ar_num in txn.action_record_idx_vec.iter() {
+			ar_map.get_mut(ar_num).unwrap().tx_key = txn.tx_number;
+		}
+
+		txns_map.insert(txn.tx_number, txn);
+	}
+}
+
+/// Where a `Transaction` came from.  Every row of the CSV Input File is a `Standard` `Transaction` unless its
+/// optional `txType` column says otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TxKind {
+	Standard,
+	/// Generated by the program to cover an otherwise negative balance (see `synthetic_acquisitions`).
+	Synthetic,
+	/// An inheritance, received on the decedent's date of death.  Its `proceeds` are the fair market value on that
+	/// date, which becomes the (stepped-up) basis of the acquired `Lot` rather than income.  The `Lot` is `inherited`,
+	/// so any disposal from it is long-term.
+	Inherited,
+	/// An open `Lot` carried forward from a prior run (see `run_state`).  Its `proceeds` are the `Lot`'s remaining
+	/// cost basis rather than income, and its `date_for_basis_purposes` is the `Lot`'s original basis date.
+	CarriedForward,
+	/// An open `Lot` carried forward from a prior run, as for `CarriedForward`, that was `inherited` (and so stays
+	/// long-term).
+	CarriedForwardInherited,
+	/// The fee split off of a transfer between the user's own `Account`s (see `transfer_fees`).  Its `proceeds` are
+	/// the `fee`.  It is a disposal only if the user has chosen to treat transfer fees as such; otherwise, it is an
+	/// expense, with no gain or loss.
+	TransferFee,
+	/// A gift given, which realizes no gain or loss.  The recipient's basis (and basis date) carry over from the
+	/// `Lot`s given (see `non_sale_disposals`).  Its `proceeds` are the fair market value.
+	Gift,
+	/// A charitable donation, which realizes no gain or loss.  Its `proceeds` are the fair market value, which
+	/// determines the deduction.
+	Donation,
+	/// A lost or stolen asset, which is written off without proceeds (its `proceeds` are ignored), so its cost basis
+	/// is the loss.
+	Lost,
+	/// The realized profit (if received) or loss (if paid) of a cash-settled margin or futures position, which has no
+	/// `Lot` of its own.  Its `proceeds` are the home currency value of the quantity received or paid, which is the
+	/// gain or loss (see `derivatives`), rather than income or an expense.
+	DerivativePnl,
+	/// One of the two exchanges a liquidity pool deposit or withdrawal is split into (see `liquidity_pools`), when
+	/// the basis carries over: what is received takes the basis of what was given, so no gain or loss is realized.
+	PoolCarryover,
+	/// An exchange of one ticker for another of the same asset (see `token_aliases`), such as a wrapped token or a
+	/// token migration: what is received takes the basis and basis dates of what was given, so no gain or loss is
+	/// realized.
+	TokenMigration,
+	/// A change in the quantity of a rebasing or interest-bearing token held (i.e., an aToken or stETH), without a
+	/// trade, treated as set by the `RebaseTreatment` (see `rebases`).  An increase treated as income is `Standard`
+	/// (of `IncomeType::Rebase`) instead.
+	Rebase,
+	/// A transfer from an account of one entity to an account of another, treated as a sale between them (see
+	/// `entities`).  It is processed as an exchange, at its `proceeds`, rather than as a `ToSelf` transfer.
+	EntitySale,
+	/// Generated by the program to sweep the dust a disposal left in an `Account` (see `dust_sweeps`): a spend of it,
+	/// without proceeds, whose (negligible) cost basis is written off, realizing no gain or loss.
+	DustSweep,
+}
+
+impl TxKind {
+
+	/// Whether the `Transaction` carries forward a `Lot` from a prior run (see `run_state`).
+	pub fn is_carried_forward(&self) -> bool {
+		matches!(self, TxKind::CarriedForward | TxKind::CarriedForwardInherited)
+	}
+
+	/// Whether the `Lot` the `Transaction` creates is `inherited`.
+	pub fn creates_inherited_lot(&self) -> bool {
+		matches!(self, TxKind::Inherited | TxKind::CarriedForwardInherited)
+	}
+}
+
+/// The kinds of income separated in the income reports and journal entries (i.e., because mining income may be
+/// self-employment income, while staking rewards, interest, airdrops and hard forks are generally other income).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IncomeType {
+	Mining,
+	Staking,
+	Interest,
+	Airdrop,
+	Fork,
+	/// An increase in the quantity of a rebasing or interest-bearing token held (see `rebases`).
+	Rebase,
+}
+
+impl IncomeType {
+
+	/// Parses an `incomeType` field, ignoring case (`Ok(None)` if blank).
+	pub fn from_field(field: &str) -> Result<Option<IncomeType>, Box<dyn Error>> {
+		match field.trim().to_ascii_lowercase().as_str() {
+			"" => Ok(None),
+			"mining" => Ok(Some(IncomeType::Mining)),
+			"staking" => Ok(Some(IncomeType::Staking)),
+			"interest" => Ok(Some(IncomeType::Interest)),
+			"airdrop" => Ok(Some(IncomeType::Airdrop)),
+			"fork" => Ok(Some(IncomeType::Fork)),
+			"rebase" => Ok(Some(IncomeType::Rebase)),
+			other => Err(format!("Unrecognized incomeType: {}. It must be blank, mining, staking, interest, airdrop, \
+				fork or rebase.", other).into()),
+		}
+	}
+}
+
+impl fmt::Display for IncomeType {
+
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			IncomeType::Mining => write!(f, "Mining"),
+			IncomeType::Staking => write!(f, "Staking"),
+			IncomeType::Interest => write!(f, "Interest"),
+			IncomeType::Airdrop => write!(f, "Airdrop"),
+			IncomeType::Fork => write!(f, "Hard fork"),
+			IncomeType::Rebase => write!(f, "Rebase"),
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Polarity {
 	Outgoing,
@@ -0,0 +1,116 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::transaction::{self, Transaction, ActionRecord, TxKind, TxType};
+
+
+/// Splits the fee off of every transfer (between the user's own `Account`s) with a `fee`.  A transfer's fee is the
+/// units sent but not received, so the outgoing `ActionRecord` is reduced to the amount received, and the difference
+/// becomes a `TxKind::TransferFee` spend from the same `Account`, with the `fee` as its `proceeds`.  That
+/// `Transaction` immediately follows the transfer and is renumbered into place, so every later `Transaction` ends up
+/// one higher.  Returns the number of fees split off.
+pub(crate) fn split_transfer_fees(
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Result<usize, Box<dyn Error>> {
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+    let mut added = 0;
+
+    for num in 1..=txns_map.len() {
+
+        let mut txn = txns_map.remove(&(num as u32)).unwrap();
+
+        if txn.fee.is_zero() || txn.transaction_type(ar_map, raw_acct_map, acct_map)? != TxType::ToSelf {
+            ordered_txns.push(txn);
+            continue
+        }
+
+        let outgoing_ar_num = txn.action_record_idx_vec[0];
+        let sent = -ar_map.get(&outgoing_ar_num).unwrap().amount;
+        let received = ar_map.get(&txn.action_record_idx_vec[1]).unwrap().amount;
+        let fee_units = sent - received;
+
+        if fee_units <= dec!(0) {
+            return Err(format!("Transaction {} is a transfer with a fee, but {} was sent and {} was received. \
+                The fee on a transfer must be paid with the units sent but not received.", num, sent, received).into())
+        }
+
+        let outgoing_ar = ar_map.get_mut(&outgoing_ar_num).unwrap();
+        outgoing_ar.amount = -received;
+        let account_key = outgoing_ar.account_key;
+
+        let ticker = &raw_acct_map.get(&acct_map.get(&account_key).unwrap().raw_key).unwrap().ticker;
+        let fee_ar_num = ar_map.len() as u32 + 1;
+
+        ar_map.insert(fee_ar_num, ActionRecord {
+            account_key,
+            amount: -fee_units,
+            tx_key: 0,  //  Set when renumbering, below
+            self_ar_key: fee_ar_num,
+            movements: RefCell::new([].to_vec()),
+        });
+
+        let fee_txn = Transaction {
+            tx_number: 0,   //  Set when renumbering, below
+            source_row: None,
+            date_as_string: txn.date_as_string.clone(),
+            date: txn.date,
+            date_for_basis_purposes: txn.date,
+            time: None,
+            sequence: None,
+            user_memo: format!("Fee of {} {} on transfer: {}", fee_units, ticker, txn.user_memo),
+            proceeds: txn.fee,
+            fee: dec!(0),
+            income_type: None,
+            tags: txn.tags.clone(),
+            expense_category: txn.expense_category.clone(),
+            payer: None,
+            action_record_idx_vec: vec![fee_ar_num],
+            kind: TxKind::TransferFee,
+        };
+
+        txn.fee = dec!(0);
+
+        ordered_txns.push(txn);
+        ordered_txns.push(fee_txn);
+        added += 1;
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    Ok(added)
+}
+
+/// Treats every transfer fee as an expense rather than a disposal, by netting the proceeds of its `Movement`s against
+/// their cost basis, so the fee realizes no gain or loss and the basis of the units spent is the expense.  This must
+/// follow any like-kind treatment, which may change the `cost_basis_lk`.
+pub(crate) fn expense_transfer_fees(
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) {
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if txn.kind != TxKind::TransferFee { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+            for mvmt in ars.get(ar_num).unwrap().get_mvmts_in_ar_in_lot_date_order().iter() {
+                mvmt.proceeds.set(-mvmt.cost_basis.get());
+                mvmt.proceeds_lk.set(-mvmt.cost_basis_lk.get());
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::decimal_utils::round_d128_1e2;
+use crate::transaction::{self, Transaction, ActionRecord, TxKind};
+
+
+/// The number of days after a withdrawal within which its deposit may arrive (i.e., just past midnight).
+const MATCH_WINDOW_DAYS: i64 = 1;
+
+/// Pairs each withdrawal (a row with only an outgoing quantity) with the first unmatched deposit (a row with only an
+/// incoming quantity) into another `Account` of the same currency, received on the withdrawal's date (and not before
+/// its `time`, if both have one) or the day after, of at most the quantity sent and at least all but the `tolerance`
+/// of it (a fraction, i.e., 0.01 for 1%).  Each pair becomes a single transfer between the user's own `Account`s,
+/// in the withdrawal's place: the deposit's `ActionRecord` joins the withdrawal's `Transaction`, and the deposit's
+/// `Transaction` is removed, so every later `Transaction` is renumbered.  The units sent but not received are the
+/// transfer's fee, valued at its share of the withdrawal's `proceeds`, so a withdrawal without `proceeds` only
+/// matches a deposit of the same quantity.  Only `Standard` rows without a `fee` or `incomeType` are matched, and
+/// neither margin nor home currency `Account`s.  Returns the numbers of the `Transaction`s paired, as imported.
+pub(crate) fn match_transfers(
+    tolerance: Decimal,
+    home_currency: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ar_map: &mut HashMap<u32, ActionRecord>,
+    txns_map: &mut HashMap<u32, Transaction>,
+) -> Vec<(u32, u32)> {
+
+    //  The `Account` and amount of each `Transaction` that could be half of a transfer
+    let mut candidates: HashMap<u32, (u16, Decimal)> = HashMap::new();
+
+    for (num, txn) in txns_map.iter() {
+
+        if txn.kind != TxKind::Standard || txn.income_type.is_some() || !txn.fee.is_zero() { continue }
+        if txn.action_record_idx_vec.len() != 1 { continue }
+
+        let ar = ar_map.get(&txn.action_record_idx_vec[0]).unwrap();
+        let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+        if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+
+        candidates.insert(*num, (ar.account_key, ar.amount));
+    }
+
+    let ticker = |account_key: u16| &raw_acct_map.get(&acct_map.get(&account_key).unwrap().raw_key).unwrap().ticker;
+
+    let mut deposit_nums: Vec<u32> = candidates.iter().filter(|(_, (_, amount))| *amount > dec!(0)).map(|(num, _)| *num).collect();
+    deposit_nums.sort();
+
+    let mut pairs: Vec<(u32, u32)> = Vec::new();
+    let mut matched: HashSet<u32> = HashSet::new();
+
+    for out_num in 1..=txns_map.len() as u32 {
+
+        let (out_acct, out_amount) = match candidates.get(&out_num) {
+            Some(&(acct, amount)) if amount < dec!(0) => (acct, amount),
+            _ => continue,
+        };
+
+        let out_txn = txns_map.get(&out_num).unwrap();
+        let sent = -out_amount;
+
+        let deposit = deposit_nums.iter().copied().find(|in_num| {
+
+            let (in_acct, received) = *candidates.get(in_num).unwrap();
+
+            if matched.contains(in_num) || in_acct == out_acct || ticker(in_acct) != ticker(out_acct) { return false }
+
+            let in_txn = txns_map.get(in_num).unwrap();
+            let days_later = (in_txn.date - out_txn.date).num_days();
+
+            if !(0..=MATCH_WINDOW_DAYS).contains(&days_later) { return false }
+            if days_later == 0 && in_txn.time.zip(out_txn.time).is_some_and(|(in_time, out_time)| in_time < out_time) {
+                return false
+            }
+
+            received <= sent
+                && received >= sent * (dec!(1) - tolerance)
+                && (received == sent || !fee_value(out_txn.proceeds, sent, received).is_zero())
+        });
+
+        if let Some(in_num) = deposit {
+            matched.insert(in_num);
+            pairs.push((out_num, in_num));
+        }
+    }
+
+    if pairs.is_empty() { return pairs }
+
+    let mut deposits: HashMap<u32, Transaction> = matched.iter()
+        .map(|in_num| (*in_num, txns_map.remove(in_num).unwrap()))
+        .collect();
+
+    let mut ordered_txns: Vec<Transaction> = Vec::with_capacity(txns_map.len());
+
+    for num in 1..=(txns_map.len() + deposits.len()) as u32 {
+
+        let mut txn = match txns_map.remove(&num) {
+            Some(txn) => txn,
+            None => continue,   //  A deposit, now part of its withdrawal
+        };
+
+        if let Some((_, in_num)) = pairs.iter().find(|(out_num, _)| *out_num == num) {
+
+            let deposit = deposits.remove(in_num).unwrap();
+            let sent = -ar_map.get(&txn.action_record_idx_vec[0]).unwrap().amount;
+            let received = ar_map.get(&deposit.action_record_idx_vec[0]).unwrap().amount;
+
+            txn.fee = fee_value(txn.proceeds, sent, received);
+            txn.action_record_idx_vec.push(deposit.action_record_idx_vec[0]);
+
+            if !deposit.user_memo.is_empty() {
+                txn.user_memo = if txn.user_memo.is_empty() { deposit.user_memo } else {
+                    format!("{} / {}", txn.user_memo, deposit.user_memo)
+                };
+            }
+        }
+
+        ordered_txns.push(txn);
+    }
+
+    transaction::renumber_transactions(ordered_txns, ar_map, txns_map);
+
+    pairs
+}
+
+/// The share of a withdrawal's `proceeds` that its units sent but not received are worth.
+fn fee_value(proceeds: Decimal, sent: Decimal, received: Decimal) -> Decimal {
+    round_d128_1e2(&(proceeds * (sent - received) / sent))
+}
@@ -0,0 +1,122 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::account::{Account, RawAccount};
+use crate::airdrops::{self, AirdropBasis};
+use crate::core_functions::ImportProcessParameters;
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind, TxType};
+
+
+/// Why a row of the CSV Input File couldn't be fully classified, so that its results can't be relied on until it is
+/// resolved (i.e., with a correction).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unresolved {
+    /// It has no `proceeds`, though its gain, loss or income depends on them.
+    MissingProceeds,
+    /// It has no `proceeds`, and no price for its ticker was found (with `--price-lookup`).
+    UnpricedTicker,
+    /// A withdrawal without `proceeds` that no deposit was matched with (with transfer matching), so it is a spend
+    /// of nothing unless it's valued.
+    UnmatchedWithdrawal,
+    /// A deposit without `proceeds` that no withdrawal was matched with (with transfer matching), so it is income of
+    /// nothing unless it's valued.
+    UnmatchedDeposit,
+}
+
+impl fmt::Display for Unresolved {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Unresolved::MissingProceeds => write!(f, "it has no proceeds"),
+            Unresolved::UnpricedTicker => write!(f, "it has no proceeds, and no price for its ticker was found"),
+            Unresolved::UnmatchedWithdrawal => write!(f, "it's a withdrawal without proceeds, and no deposit matched it"),
+            Unresolved::UnmatchedDeposit => write!(f, "it's a deposit without proceeds, and no withdrawal matched it"),
+        }
+    }
+}
+
+/// A row of the CSV Input File that needs resolving.
+#[derive(Clone, Debug)]
+pub struct UnresolvedRow {
+    /// See `Transaction::source_row`.
+    pub row: usize,
+    pub tx_number: u32,
+    pub date: NaiveDate,
+    pub account: String,
+    pub ticker: String,
+    /// The quantity of its first `ActionRecord` (negative if outgoing).
+    pub amount: Decimal,
+    pub memo: String,
+    pub reason: Unresolved,
+}
+
+/// The rows of the CSV Input File (in `Transaction` order) whose gain, loss or income depends on `proceeds` they don't
+/// have: exchanges, spends, income, inheritances, gifts and donations of a non-home currency (but not transfers
+/// between the user's own `Account`s, margin trades, or airdrops and hard forks given a basis of zero).  Generated
+/// `Transaction`s aren't rows, so they're left out.
+pub fn find_unresolved_rows(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<UnresolvedRow>, Box<dyn Error>> {
+
+    let mut unresolved = Vec::new();
+
+    for num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(num as u32)).unwrap();
+
+        let Some(row) = txn.source_row else { continue };
+
+        if !txn.proceeds.is_zero() { continue }
+        if !matches!(txn.kind, TxKind::Standard | TxKind::Inherited | TxKind::Gift | TxKind::Donation | TxKind::DerivativePnl) {
+            continue
+        }
+        if settings.airdrop_basis == AirdropBasis::Zero && airdrops::is_airdrop_or_fork(txn) { continue }
+        if txn.transaction_type(ars, raw_acct_map, acct_map)? == TxType::ToSelf { continue }
+
+        let legs: Vec<(&ActionRecord, &RawAccount)> = txn.action_record_idx_vec.iter().map(|ar_num| {
+            let ar = ars.get(ar_num).unwrap();
+            (ar, raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap())
+        }).collect();
+
+        if legs.iter().any(|(_ar, raw_acct)| raw_acct.is_margin || raw_acct.is_home_currency(&settings.home_currency)) {
+            continue
+        }
+
+        let (ar, raw_acct) = legs[0];
+
+        //  Transfer matching only pairs rows like these, so one left over may be half of a transfer
+        let could_be_transfer = settings.transfer_match_tolerance.is_some() && legs.len() == 1
+            && txn.kind == TxKind::Standard && txn.income_type.is_none() && txn.fee.is_zero();
+
+        let reason = match ar.direction() {
+            Polarity::Outgoing if could_be_transfer => Unresolved::UnmatchedWithdrawal,
+            Polarity::Incoming if could_be_transfer => Unresolved::UnmatchedDeposit,
+            _ if settings.price_lookup.is_some() => Unresolved::UnpricedTicker,
+            _ => Unresolved::MissingProceeds,
+        };
+
+        unresolved.push(UnresolvedRow {
+            row,
+            tx_number: txn.tx_number,
+            date: txn.date,
+            account: raw_acct.name.clone(),
+            ticker: raw_acct.ticker.clone(),
+            amount: ar.amount,
+            memo: txn.user_memo.clone(),
+            reason,
+        });
+    }
+
+    Ok(unresolved)
+}
@@ -0,0 +1,269 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount};
+use crate::core_functions::ImportProcessParameters;
+use crate::csv_import_accts_txns;
+use crate::dedupe::{DedupeMode, DuplicateFinder};
+use crate::nfts;
+use crate::timestamps;
+use crate::tags;
+use crate::transaction::IncomeType;
+
+
+/// A problem with a row of a CSV Input File, found without processing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+    /// The row of the file, counting the four header rows (so the first transaction row is row 5).
+    pub row: usize,
+    pub message: String,
+}
+
+/// Checks a CSV Input File (converted or remapped, if so set) the way it would be imported, but reports every
+/// problem found, by row, rather than stopping at the first: txDates and settleDates that don't match the date format
+/// or are out of chronological order (in the timezone, if one is set), txTimes that aren't times, txSeqs that aren't
+/// whole numbers, values that aren't numbers, NFT quantities other than 1 (or -1), accounts without a ticker, txTypes,
+/// fees and incomeTypes that don't fit their transaction, transactions without one or two opposing quantities (or
+/// three, for a liquidity pool), transfers (between the user's own accounts) receiving more than was sent, or less
+/// without a fee, and (with `DedupeMode::Warn`) rows duplicating an earlier one.  Problems with the header rows are still fatal, as they are when importing.
+pub fn validate_input_file(
+    import_file_path: &Path,
+    settings: &ImportProcessParameters,
+) -> Result<Vec<ValidationIssue>, Box<dyn Error>> {
+
+    validate_input(csv_import_accts_txns::open_input_file(import_file_path, settings)?, settings)
+}
+
+/// Checks the CSV Input File read from `input` (already converted or remapped), as `validate_input_file()` does.
+pub(crate) fn validate_input(input: Box<dyn Read>, settings: &ImportProcessParameters) -> Result<Vec<ValidationIssue>, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(input);
+
+    let mut raw_acct_map: HashMap<u16, RawAccount> = HashMap::new();
+    let mut acct_map: HashMap<u16, Account> = HashMap::new();
+
+    let columns = csv_import_accts_txns::import_accounts(&mut rdr, settings, &mut raw_acct_map, &mut acct_map)?;
+
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for num in 1..=raw_acct_map.len() {
+        let raw_acct = raw_acct_map.get(&(num as u16)).unwrap();
+        if raw_acct.ticker.is_empty() {
+            issues.push(ValidationIssue { row: 3, message: format!("Account {} ({}) has no ticker.", num, raw_acct.name) });
+        }
+    }
+
+    let mut prior_date: Option<NaiveDate> = None;
+    let mut is_first_txn = true;
+    let mut duplicates = DuplicateFinder::new(&settings.dedupe_key);
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 5;  //  Account for the four header rows and one-based row numbers
+        let field = |idx: Option<usize>| idx.and_then(|idx| record.get(idx)).unwrap_or("").trim();
+        let mut issue = |message: String| issues.push(ValidationIssue { row, message });
+
+        //  A duplicate dropped isn't imported, so isn't checked
+        if settings.dedupe != DedupeMode::Off {
+            if let Some(duplicate) = duplicates.check(row, &record, &columns) {
+                if settings.dedupe == DedupeMode::Drop { continue }
+                issue(format!("It duplicates row {}.", duplicate.duplicate_of));
+            }
+        }
+
+        let tx_time = field(columns.tx_time);
+        let tx_time = if tx_time.is_empty() { None } else {
+            let parsed = timestamps::parse_tx_time(tx_time);
+            if parsed.is_none() {
+                issue(format!("txTime ({}) isn't HH:MM or HH:MM:SS (optionally followed by a UTC offset).", tx_time));
+            }
+            parsed
+        };
+
+        //  As imported, in the timezone, if one is set
+        let tx_date = csv_import_accts_txns::parse_input_file_date(field(Some(0)), settings).map(|date| match tx_time {
+            Some((time, offset)) => timestamps::local_date_time(date, time, offset, settings.timezone).0,
+            None => date,
+        });
+
+        match tx_date {
+            Some(date) => {
+                if let Some(prior) = prior_date.filter(|prior| date < *prior) {
+                    issue(format!("txDate ({}) is before the txDate of the row before it ({}).", date, prior));
+                }
+                prior_date = Some(date);
+            }
+            None => issue(format!("txDate ({}) doesn't match the date format.", field(Some(0)))),
+        }
+
+        let tx_seq = field(columns.tx_seq);
+        if !tx_seq.is_empty() && tx_seq.parse::<u32>().is_err() {
+            issue(format!("txSeq ({}) isn't a whole number.", tx_seq));
+        }
+
+        let settle_date = field(columns.settle_date);
+        if !settle_date.is_empty() && csv_import_accts_txns::parse_input_file_date(settle_date, settings).is_none() {
+            issue(format!("settleDate ({}) doesn't match the date format.", settle_date));
+        }
+
+        let proceeds = field(Some(1)).replace(',', "");
+        match proceeds.parse::<Decimal>() {
+            _ if proceeds.is_empty() => {}
+            Ok(p) if p >= dec!(0) => {}
+            Ok(_) => issue(format!("proceeds ({}) are negative.", proceeds)),
+            Err(_) => issue(format!("proceeds ({}) aren't a number.", proceeds)),
+        }
+
+        let tx_type = field(columns.tx_type).to_ascii_lowercase();
+        let tx_types = ["", "standard", "inherited", "gift", "donation", "lost", "stolen", "derivative-pnl", "lp-deposit", "lp-withdrawal",
+            "airdrop", "fork", "rebase"];
+        if !tx_types.contains(&tx_type.as_str()) {
+            issue(format!("txType ({}) must be blank, standard, inherited, gift, donation, lost, stolen, derivative-pnl, lp-deposit, \
+                lp-withdrawal, airdrop, fork or rebase.", tx_type));
+        }
+
+        let fee = field(columns.fee).replace(',', "");
+        let has_fee = match fee.parse::<Decimal>() {
+            _ if fee.is_empty() => false,
+            Ok(f) if f >= dec!(0) => !f.is_zero(),
+            _ => { issue(format!("fee ({}) isn't a number of zero or more.", fee)); false }
+        };
+
+        let income_type = IncomeType::from_field(field(columns.income_type)).unwrap_or_else(|e| {
+            issue(e.to_string());
+            None
+        });
+
+        let mut quantities: Vec<(u16, Decimal)> = Vec::with_capacity(2);
+
+        for (acct_idx, value) in record.iter().enumerate().skip(columns.first_acct_col) {
+
+            let value = value.trim().replace(',', "");
+            if value.is_empty() { continue }
+
+            let account_key = (acct_idx - columns.first_acct_col + 1) as u16;
+
+            match value.parse::<Decimal>() {
+                Ok(amount) if amount.is_zero() && settings.zero_quantity_rows_are_annotations => {}
+                Ok(amount) => {
+                    let is_nft = raw_acct_map.get(&account_key)
+                        .is_some_and(|raw_acct| !raw_acct.is_margin && nfts::collection_and_token_id(&raw_acct.ticker).is_some());
+                    if is_nft && amount.abs() != dec!(1) {
+                        issue(format!("The quantity for account {} ({}) is an NFT's, so it must be 1 (or -1).", account_key, amount));
+                    }
+                    quantities.push((account_key, amount))
+                }
+                Err(_) => issue(format!("The quantity for account {} ({}) isn't a number.", account_key, value)),
+            }
+        }
+
+        let is_single = |positive: bool| quantities.len() == 1 && (quantities[0].1 > dec!(0)) == positive;
+
+        match quantities.len() {
+            0 if settings.zero_quantity_rows_are_annotations => {
+                if is_first_txn { issue("The first transaction row has no quantities, so it annotates nothing.".to_string()) }
+                continue
+            }
+            0 => issue("There are no quantities.".to_string()),
+            1 | 2 => {}
+            3 if tx_type == "lp-deposit" || tx_type == "lp-withdrawal" => {}
+            n => issue(format!("There are {} quantities, but a transaction has no more than two (unless it's an lp-deposit or \
+                lp-withdrawal).", n)),
+        }
+
+        let outgoing = quantities.iter().filter(|(_, amount)| *amount < dec!(0)).count();
+
+        match tx_type.as_str() {
+            "lp-deposit" if quantities.len() != 3 || outgoing != 2 => issue("The txType is lp-deposit, so there must be \
+                exactly three quantities: two assets deposited (negative) and the LP tokens received (positive).".to_string()),
+            "lp-withdrawal" if quantities.len() != 3 || outgoing != 1 => issue("The txType is lp-withdrawal, so there must \
+                be exactly three quantities: the LP tokens withdrawn (negative) and two assets received (positive).".to_string()),
+            _ => {}
+        }
+
+        is_first_txn = false;
+
+        if quantities.len() == 2 {
+
+            let (out_key, out_amount, in_key, in_amount) = if quantities[0].1 < quantities[1].1 {
+                (quantities[0].0, quantities[0].1, quantities[1].0, quantities[1].1)
+            } else {
+                (quantities[1].0, quantities[1].1, quantities[0].0, quantities[0].1)
+            };
+
+            if (out_amount > dec!(0)) == (in_amount > dec!(0)) {
+                issue("Both quantities are the same sign, but one must be negative and the other positive.".to_string());
+            } else {
+
+                let out_acct = raw_acct_map.get(&out_key).unwrap();
+                let in_acct = raw_acct_map.get(&in_key).unwrap();
+                let base_ticker = |raw_acct: &RawAccount| raw_acct.ticker.split('_').next().unwrap_or("").to_string();
+
+                let is_transfer = base_ticker(out_acct) == base_ticker(in_acct) && out_acct.is_margin == in_acct.is_margin;
+
+                if is_transfer && in_amount > -out_amount {
+                    issue(format!("The transfer receives {} {}, which is more than the {} sent.", in_amount, in_acct.ticker, -out_amount));
+                }
+                if is_transfer && in_amount < -out_amount && !has_fee {
+                    issue(format!("The transfer receives {} {}, which is less than the {} sent, but there is no fee.",
+                        in_amount, in_acct.ticker, -out_amount));
+                }
+            }
+        }
+
+        match tx_type.as_str() {
+            "gift" | "donation" | "lost" | "stolen" if !is_single(false) =>
+                issue(format!("The txType is {}, so there must be exactly one (negative) quantity.", tx_type)),
+            "inherited" if !is_single(true) =>
+                issue("The txType is inherited, so there must be exactly one (positive) quantity.".to_string()),
+            "derivative-pnl" if quantities.len() != 1 =>
+                issue("The txType is derivative-pnl, so there must be exactly one quantity.".to_string()),
+            "rebase" if quantities.len() != 1 =>
+                issue("The txType is rebase, so there must be exactly one quantity.".to_string()),
+            "airdrop" | "fork" if !is_single(true) =>
+                issue(format!("The txType is {}, so there must be exactly one (positive) quantity.", tx_type)),
+            "airdrop" if income_type.is_some_and(|income_type| income_type != IncomeType::Airdrop) =>
+                issue("The txType is airdrop, but the incomeType is another kind of income.".to_string()),
+            "fork" if income_type.is_some_and(|income_type| income_type != IncomeType::Fork) =>
+                issue("The txType is fork, but the incomeType is another kind of income.".to_string()),
+            _ => {}
+        }
+
+        if has_fee && is_single(true) {
+            issue("There is a fee, but this is income. A fee must be on an exchange, a transfer or a spend.".to_string());
+        }
+
+        if income_type.is_some() && (!is_single(true) || !matches!(tx_type.as_str(), "" | "standard" | "airdrop" | "fork")) {
+            issue("There is an incomeType, but this isn't income. There must be exactly one (positive) quantity.".to_string());
+        }
+
+        let has_expense_category = !field(columns.expense_category).is_empty()
+            || tags::tags_in_memo(field(Some(2))).iter().any(|tag| tag.name == "expense" && tag.value.is_some());
+
+        if has_expense_category && !(is_single(false) && matches!(tx_type.as_str(), "" | "standard" | "lost" | "stolen")) && !has_fee {
+            issue("There is an expenseCategory, but this isn't a spend. There must be exactly one (negative) quantity, \
+                or a fee.".to_string());
+        }
+
+        let has_payer = !field(columns.payer).is_empty()
+            || tags::tags_in_memo(field(Some(2))).iter().any(|tag| tag.name == "payer" && tag.value.is_some());
+
+        if has_payer && !is_single(true) {
+            issue("There is a payer, but this isn't income. There must be exactly one (positive) quantity.".to_string());
+        }
+    }
+
+    Ok(issues)
+}
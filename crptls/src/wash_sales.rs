@@ -0,0 +1,240 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::account::{Account, RawAccount, Movement, Lot};
+use crate::transaction::{Transaction, ActionRecord, Polarity, TxKind, TxType};
+use crate::import_cost_proceeds_etc;
+use crate::non_sale_disposals;
+use crate::stablecoins;
+use crate::decimal_utils::round_d128_1e2;
+
+
+/// A disposal at a loss (or the part of one) that was matched to units of a replacement `Lot` of the same currency,
+/// purchased within the wash sale window before or after it.
+#[derive(Clone, Debug)]
+pub struct WashSale {
+    pub tx_num: u32,
+    /// The `Account` and `Lot` disposed of.
+    pub account_key: u16,
+    pub lot_num: u32,
+    /// The replacement `Lot`, and the `Transaction` that acquired it.
+    pub replacement_account_key: u16,
+    pub replacement_lot_num: u32,
+    pub replacement_tx_num: u32,
+    /// The units disposed of that were matched to replacement units (positive).
+    pub amount: Decimal,
+    /// The part of the loss disallowed (positive), which is added to the basis of the replacement `Lot`.
+    pub disallowed_loss: Decimal,
+}
+
+/// Adds cost basis to every `Movement`, as `add_cost_basis_to_movements()` does, while applying the wash sale rule.
+/// `Transaction`s are taken in order, and each outgoing exchange or flow `Movement` at a loss (from a non-margin,
+/// non-home currency `Account`) is matched, unit for unit, to `Lot`s of the same currency purchased (by exchange)
+/// within `window_days` before or after it, oldest first.  A replacement `Lot` acquired before the loss must still
+/// be wholly held, and each of its units replaces only one unit sold.  The matched share of the loss is disallowed
+/// (see `Movement::wash_sale_adjustment`) and added to the basis of the replacement `Lot`, so it is deferred until
+/// that `Lot` is disposed of.  The replacement `Lot` keeps its own basis date.  A transfer fee is a loss only if
+/// `transfer_fee_is_disposal`, and a gift, donation or loss (or a disposal of one of the cash-equivalent
+/// `stablecoins`) is never one.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn add_cost_basis_with_wash_sales(
+    window_days: i64,
+    transfer_fee_is_disposal: bool,
+    stablecoins: &[String],
+    home_currency: &String,
+    home_curr_leg_is_basis: bool,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<WashSale>, Box<dyn Error>> {
+
+    let mut wash_sales: Vec<WashSale> = Vec::new();
+
+    //  Disallowed losses awaiting a replacement `Lot` acquired after the loss, which hasn't been given its basis yet
+    let mut pending: HashMap<(u16, u32), Decimal> = HashMap::new();
+    //  Units of each replacement `Lot` already matched to a loss
+    let mut matched: HashMap<(u16, u32), Decimal> = HashMap::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn_num = txn_num as u32;
+
+        import_cost_proceeds_etc::add_cost_basis_to_txn_movements(
+            txn_num,
+            home_currency,
+            home_curr_leg_is_basis,
+            raw_acct_map,
+            acct_map,
+            ars,
+            txns_map,
+        )?;
+
+        let txn = txns_map.get(&txn_num).unwrap();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+
+            if ar.direction() != Polarity::Incoming { continue }
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+                if let Some(disallowed_loss) = pending.remove(&(ar.account_key, mvmt.lot_num)) {
+                    add_disallowed_loss_to_basis(mvmt, disallowed_loss);
+                }
+            }
+        }
+
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+
+        if tx_type == TxType::ToSelf { continue }
+        if tx_type == TxType::Flow && txn.action_record_idx_vec.len() == 2 { continue }    //  Margin profit or loss
+        if txn.kind == TxKind::TransferFee && !transfer_fee_is_disposal { continue }        //  An expense, not a sale
+        if non_sale_disposals::is_non_sale(txn.kind) { continue }     //  A gift, donation or loss, not a sale
+        //  The basis carries over, so no loss is realized
+        if matches!(txn.kind, TxKind::PoolCarryover | TxKind::TokenMigration) { continue }
+
+        let txn_proceeds = txn.proceeds_net_of_fee();
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            if ar.direction() != Polarity::Outgoing { continue }
+            if raw_acct.is_margin || raw_acct.is_home_currency(home_currency) { continue }
+            if stablecoins::is_cash_equivalent(stablecoins, &raw_acct.ticker) { continue }
+
+            let movements = ar.get_mvmts_in_ar_in_lot_date_order();
+            let lots_disposed: Vec<u32> = movements.iter().map(|mvmt| mvmt.lot_num).collect();
+
+            for mvmt in movements.iter() {
+
+                //  The same proceeds add_proceeds_to_movements() will set
+                let proceeds = round_d128_1e2(&(txn_proceeds * mvmt.amount / ar.amount));
+                let loss = proceeds + mvmt.cost_basis.get();
+
+                if loss >= dec!(0) { continue }
+
+                let sold = -mvmt.amount;
+                let mut unmatched = sold;
+
+                let candidates = replacement_lots(
+                    window_days,
+                    txn,
+                    &raw_acct.ticker,
+                    ar.account_key,
+                    &lots_disposed,
+                    raw_acct_map,
+                    acct_map,
+                    ars,
+                    txns_map,
+                );
+
+                for (acct_key, lot) in candidates.iter() {
+
+                    if unmatched <= dec!(0) { break }
+
+                    let key = (*acct_key, lot.lot_number);
+                    let first_mvmt = lot.movements.borrow().first().unwrap().clone();
+                    let already_matched = matched.get(&key).copied().unwrap_or(dec!(0));
+                    let units = (first_mvmt.amount - already_matched).min(unmatched);
+
+                    if units <= dec!(0) { continue }
+
+                    let disallowed_loss = round_d128_1e2(&(-loss * units / sold));
+
+                    mvmt.wash_sale_adjustment.set(mvmt.wash_sale_adjustment.get() + disallowed_loss);
+
+                    if first_mvmt.transaction_key > txn_num {
+                        *pending.entry(key).or_insert(dec!(0)) += disallowed_loss;
+                    } else {
+                        add_disallowed_loss_to_basis(&first_mvmt, disallowed_loss);
+                    }
+
+                    *matched.entry(key).or_insert(dec!(0)) += units;
+                    unmatched -= units;
+
+                    wash_sales.push(WashSale {
+                        tx_num: txn_num,
+                        account_key: ar.account_key,
+                        lot_num: mvmt.lot_num,
+                        replacement_account_key: *acct_key,
+                        replacement_lot_num: lot.lot_number,
+                        replacement_tx_num: first_mvmt.transaction_key,
+                        amount: units,
+                        disallowed_loss,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(wash_sales)
+}
+
+/// The `Lot`s of `ticker` (in any non-margin `Account`) that could replace what `txn` disposed of, oldest first:
+/// purchased by a `Standard` exchange (other than `txn`) within `window_days` of it, other than the `Lot`s it
+/// disposed of, and, if purchased before it, without any disposal since.
+#[allow(clippy::too_many_arguments)]
+fn replacement_lots(
+    window_days: i64,
+    txn: &Transaction,
+    ticker: &str,
+    disposing_acct_key: u16,
+    lots_disposed: &[u32],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Vec<(u16, Rc<Lot>)> {
+
+    let mut candidates: Vec<(u16, Rc<Lot>)> = Vec::new();
+
+    for num in 1..=acct_map.len() {
+
+        let acct_key = num as u16;
+        let acct = acct_map.get(&acct_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        if raw_acct.is_margin || raw_acct.ticker != ticker { continue }
+
+        for lot in acct.list_of_lots.borrow().iter() {
+
+            if acct_key == disposing_acct_key && lots_disposed.contains(&lot.lot_number) { continue }
+            if (lot.date_of_first_mvmt_in_lot - txn.date).num_days().abs() > window_days { continue }
+
+            let mvmts = lot.movements.borrow();
+            let first_mvmt = mvmts.first().unwrap();
+            let first_txn = txns_map.get(&first_mvmt.transaction_key).unwrap();
+
+            if first_txn.tx_number == txn.tx_number || first_txn.kind != TxKind::Standard { continue }
+            if first_mvmt.amount <= dec!(0) { continue }
+
+            if !matches!(first_txn.transaction_type(ars, raw_acct_map, acct_map), Ok(TxType::Exchange)) { continue }
+
+            if first_txn.tx_number < txn.tx_number
+                && mvmts.iter().skip(1).any(|mvmt| mvmt.transaction_key <= txn.tx_number) { continue }
+
+            candidates.push((acct_key, lot.clone()));
+        }
+    }
+
+    candidates.sort_by_key(|(_acct_key, lot)| (lot.date_of_first_mvmt_in_lot, lot.movements.borrow()[0].transaction_key));
+
+    candidates
+}
+
+/// Adds a disallowed loss to the cost basis of a replacement `Lot`'s first (incoming) `Movement`.
+fn add_disallowed_loss_to_basis(mvmt: &Movement, disallowed_loss: Decimal) {
+    mvmt.cost_basis.set(mvmt.cost_basis.get() + disallowed_loss);
+    mvmt.cost_basis_lk.set(mvmt.cost_basis_lk.get() + disallowed_loss);
+    mvmt.wash_sale_adjustment.set(mvmt.wash_sale_adjustment.get() - disallowed_loss);
+}
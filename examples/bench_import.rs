@@ -0,0 +1,87 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Times the import and processing of a generated history, and reports the peak memory used (on Linux).
+//
+//      cargo run --release --example bench_import -- [TRANSACTIONS] [FILES] [--low-memory]
+//
+//  The history (100,000 transactions, by default) is split among FILES files (2, by default), so that it's merged as
+//  a multi-file import would be. Compare a run with --low-memory to one without to see what spilling saves.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crptls::core_functions::{self, ImportProcessParameters, Timings};
+use crptls::test_data::TestData;
+
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let low_memory = args.iter().any(|arg| arg == "--low-memory");
+    let mut numbers = args.iter().filter(|arg| !arg.starts_with("--")).map(|arg| arg.parse::<usize>());
+    let transactions = numbers.next().transpose()?.unwrap_or(100_000);
+    let files = numbers.next().transpose()?.unwrap_or(2).max(1);
+
+    let dir = std::env::temp_dir().join(format!("cryptools_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let start = Instant::now();
+    let paths = write_history(&dir, transactions, files)?;
+    let input_bytes: u64 = paths.iter().map(|path| fs::metadata(path).map(|meta| meta.len())).sum::<Result<u64, _>>()?;
+    println!("Generated {} transactions in {} file(s) ({:.1} MB) in {:.2?}.",
+        transactions, files, input_bytes as f64 / 1e6, start.elapsed());
+
+    let settings = ImportProcessParameters { low_memory, ..Default::default() };
+    let mut timings = Timings::default();
+
+    let result = core_functions::import_and_process_final(&paths, &settings, &mut timings);
+    fs::remove_dir_all(&dir)?;
+    let (_raw_acct_map, acct_map, ars, txns_map) = result?;
+
+    println!("Processed {} transactions ({} action records) across {} accounts, low_memory: {}.",
+        txns_map.len(), ars.len(), acct_map.len(), low_memory);
+
+    for (phase, duration) in timings.phases.iter() {
+        println!("  {:<40} {:>10.3?}", phase, duration);
+    }
+    println!("  {:<40} {:>10.3?}", "Total", timings.total());
+
+    match peak_memory_kb() {
+        Some(kb) => println!("Peak memory: {:.1} MB", kb as f64 / 1e3),
+        None => println!("Peak memory: unavailable on this platform"),
+    }
+
+    Ok(())
+}
+
+/// Writes `transactions` rows of test data (see `crptls::test_data`) into `files` files, each generated with its own
+/// seed.  Their accounts are the same, so the merge treats them as one.
+fn write_history(dir: &Path, transactions: usize, files: usize) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+
+    let mut paths = Vec::with_capacity(files);
+
+    for file_idx in 0..files {
+
+        let path = dir.join(format!("history_{}.csv", file_idx + 1));
+        let rows = transactions / files + usize::from(file_idx < transactions % files);
+        let test_data = TestData { rows, accounts: 6, seed: file_idx as u64 + 1, ..Default::default() };
+
+        test_data.write(BufWriter::new(File::create(&path)?))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// The peak resident memory of this process, in kB, from `/proc/self/status`.
+fn peak_memory_kb() -> Option<u64> {
+
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+
+    line.split_whitespace().nth(1)?.parse().ok()
+}
@@ -0,0 +1,16 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! With `--batch`, what would otherwise be asked of the user is an error instead, for running in automated pipelines.
+
+use crate::run_result::{self, ExitCode};
+
+/// Writes a JSON object to stderr describing the input that would have been asked for (`missing`, i.e.,
+/// `file_to_import`) and why it's needed (i.e., `{"error":"No file_to_import was given.","missing":"file_to_import"}`),
+/// then exits with `ExitCode::ParseError`.
+pub(crate) fn exit_for_missing_input(missing: &str, message: &str) -> ! {
+
+    eprintln!("{}", serde_json::json!({ "error": message, "missing": missing }));
+
+    run_result::exit(ExitCode::ParseError, Some(message))
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crptls::importers::{self, ChainApi};
+
+use crate::run_result::{self, ExitCode};
+use crate::sync::{self, curl_quoted};
+use crate::Cli;
+
+
+const BLOCKCHAIN_API: &str = "https://blockchain.info/multiaddr";
+pub(crate) const ETHERSCAN_API: &str = "https://api.etherscan.io/v2/api?chainid=1";
+
+/// The transactions asked for on each page of a response.
+const PAGE_SIZE: usize = 1000;
+
+/// Pulls the on-chain history of the `btc` addresses and xpubs (from blockchain.info, or the wallet of the node at
+/// `btc_rpc`) and of the `eth` addresses (from the Etherscan-compatible API at `eth_api`, with the key of the
+/// ETHERSCAN_API_KEY environment variable, if set), and writes it as a CSV Input File to `file` (or stdout), to be
+/// reviewed before it's processed.  Requests are made with `curl`.
+pub(crate) fn chain_sync(
+    btc: &[String],
+    btc_rpc: Option<&str>,
+    eth: &[String],
+    eth_api: &str,
+    file: Option<&Path>,
+    args: &Cli,
+) -> Result<(), Box<dyn Error>> {
+
+    if btc.is_empty() && btc_rpc.is_none() && eth.is_empty() {
+        run_result::fatal(ExitCode::ParseError, "FATAL: chain-sync needs an address to pull the history of, via --btc, --btc-rpc or --eth.")
+    }
+
+    let settings = sync::input_file_settings(args)?;
+    let mut pages = Vec::new();
+
+    match btc_rpc {
+        Some(url) => pages.extend(node_pages(url)?.into_iter().map(|page| (ChainApi::BitcoinRpc, page))),
+        None if !btc.is_empty() => pages.extend(blockchain_pages(btc)?.into_iter().map(|page| (ChainApi::Blockchain, page))),
+        None => {}
+    }
+
+    for address in eth.iter() {
+        pages.extend(etherscan_pages(eth_api, address)?.into_iter().map(|page| (ChainApi::Etherscan, page)));
+    }
+
+    let output = importers::chain_history_to_input_file(&pages, eth, &settings)?;
+
+    sync::write_output(&output, file, "on-chain")
+}
+
+/// The response pages of blockchain.info's `multiaddr` for all the `addresses` (and xpubs) together, so a transaction
+/// between two of them is netted.
+fn blockchain_pages(addresses: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let mut pages = Vec::new();
+    let mut offset = 0;
+
+    log::info!("  Pulling the BTC history of {} from blockchain.info...", addresses.join(", "));
+
+    loop {
+
+        let page = sync::curl(&[format!("url = {}", curl_quoted(&format!(
+            "{}?active={}&n=100&offset={}", BLOCKCHAIN_API, addresses.join("%7C"), offset
+        )))])?;
+
+        let response: Value = serde_json::from_str(&page)
+            .map_err(|_| format!("blockchain.info responded with an error: {}", page.trim()))?;
+
+        let txs = response["txs"].as_array().map_or(0, |txs| txs.len());
+        let n_tx = response["wallet"]["n_tx"].as_u64().unwrap_or(0) as usize;
+        pages.push(page);
+        offset += txs;
+
+        if txs == 0 || offset >= n_tx { break }
+
+        //  blockchain.info limits the rate of requests
+        thread::sleep(Duration::from_secs(10));
+    }
+
+    Ok(pages)
+}
+
+/// The response pages of the `listtransactions` of the Bitcoin Core node at `url` (including its wallet's
+/// watch-only addresses), as the user and password of the BITCOIN_RPC_USER and BITCOIN_RPC_PASSWORD environment
+/// variables, if set.
+fn node_pages(url: &str) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let mut pages = Vec::new();
+    let mut skip = 0;
+
+    log::info!("  Pulling the BTC history of the node's wallet...");
+
+    let user = match (env::var("BITCOIN_RPC_USER"), env::var("BITCOIN_RPC_PASSWORD")) {
+        (Ok(user), Ok(password)) => Some(format!("user = {}", curl_quoted(&format!("{}:{}", user, password)))),
+        _ => None,
+    };
+
+    loop {
+
+        let body = format!(
+            r#"{{"jsonrpc":"1.0","id":"cryptools","method":"listtransactions","params":["*",{},{},true]}}"#,
+            PAGE_SIZE, skip
+        );
+
+        let mut config = vec![
+            format!("url = {}", curl_quoted(url)),
+            "header = \"Content-Type: text/plain\"".to_string(),
+            format!("data = {}", curl_quoted(&body)),
+        ];
+        config.extend(user.clone());
+
+        let page = sync::curl(&config)?;
+
+        //  A node responds to a bad user or password with an empty body
+        let response: Value = serde_json::from_str(&page)
+            .map_err(|_| "The node's response wasn't JSON. Are BITCOIN_RPC_USER and BITCOIN_RPC_PASSWORD set?")?;
+
+        let txs = response["result"].as_array().map_or(0, |txs| txs.len());
+        pages.push(page);
+        skip += txs;
+
+        if txs < PAGE_SIZE { break }
+    }
+
+    Ok(pages)
+}
+
+/// The response pages of the `txlist` of the ETH `address` from the Etherscan-compatible API at `api`.
+fn etherscan_pages(api: &str, address: &str) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let mut pages = Vec::new();
+    let key = env::var("ETHERSCAN_API_KEY").unwrap_or_default();
+    let separator = if api.contains('?') { "&" } else { "?" };
+
+    log::info!("  Pulling the ETH history of {}...", address);
+
+    for page_num in 1.. {
+
+        let page = sync::curl(&[format!("url = {}", curl_quoted(&format!(
+            "{}{}module=account&action=txlist&address={}&startblock=0&endblock=99999999&page={}&offset={}&sort=asc&apikey={}",
+            api, separator, address, page_num, PAGE_SIZE, key
+        )))])?;
+
+        let response: Value = serde_json::from_str(&page)
+            .map_err(|_| format!("The API responded with an error: {}", page.trim()))?;
+
+        //  An error (i.e., of the key) is a `result` that isn't a list, left for the importer to report
+        let txs = response["result"].as_array().map_or(0, |txs| txs.len());
+        pages.push(page);
+
+        if txs < PAGE_SIZE { break }
+
+        //  Etherscan limits the rate of requests without a key
+        thread::sleep(Duration::from_millis(250));
+    }
+
+    Ok(pages)
+}
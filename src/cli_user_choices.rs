@@ -25,6 +25,7 @@ pub fn choose_file_for_import(flag_to_accept_cli_args: bool) -> Result<PathBuf,
     }
 
     println!("Please input a file (absolute or relative path) to import: ");
+    println!("(Without one yet? `cryptools init` asks about your exchanges and wallets and writes one to start from.)");
 
     let (file_string, has_tilde) = _get_path()?;
 
@@ -131,6 +132,13 @@ pub fn choose_inventory_costing_method(cmd_line_arg: String) -> Result<Inventory
     println!("2. LIFO according to the basis date of the lot.");
     println!("3. FIFO according to the order the lot was created.");
     println!("4. FIFO according to the basis date of the lot.");
+    println!("5. Long-term lots first, then short-term lots (FIFO according to the basis date of the lot within each).");
+    println!("6. Long-term lots first, then short-term lots (LIFO according to the basis date of the lot within each).");
+    println!("7. HIFO (lots with the highest cost basis per unit first).");
+    println!("8. Weighted average cost (each disposal is drawn from every lot in proportion to its balance).");
+    println!("9. Specific identification (choose the lots for each disposal as it is processed).");
+    println!("10. UK share pooling (same-day, then 30-day \"bed and breakfast\" matching, then the Section 104 pool).");
+    println!("11. Canadian adjusted cost base (ACB), with the superficial loss rule.");
 
     let method = _costing_method(cmd_line_arg)?;
 
@@ -142,11 +150,10 @@ pub fn choose_inventory_costing_method(cmd_line_arg: String) -> Result<Inventory
 
         match input.trim() { // Without .trim(), there's a hidden \n or something preventing the match
             "" => Ok(inv_costing_from_cmd_arg(env_var_arg)?),
-            "1" => Ok(InventoryCostingMethod::LIFObyLotCreationDate),
-            "2" => Ok(InventoryCostingMethod::LIFObyLotBasisDate),
-            "3" => Ok(InventoryCostingMethod::FIFObyLotCreationDate),
-            "4" => Ok(InventoryCostingMethod::FIFObyLotBasisDate),
-            _   => { println!("Invalid choice.  Please enter a valid choice."); _costing_method(env_var_arg) }
+            choice => match InventoryCostingMethod::from_choice(choice) {
+                Some(method) => Ok(method),
+                None => { println!("Invalid choice.  Please enter a valid choice."); _costing_method(env_var_arg) }
+            }
         }
     }
 
@@ -154,12 +161,9 @@ pub fn choose_inventory_costing_method(cmd_line_arg: String) -> Result<Inventory
 }
 pub fn inv_costing_from_cmd_arg(arg: String) -> Result<InventoryCostingMethod, &'static str> {
 
-    match arg.trim() {
-        "1" => Ok(InventoryCostingMethod::LIFObyLotCreationDate),
-        "2" => Ok(InventoryCostingMethod::LIFObyLotBasisDate),
-        "3" => Ok(InventoryCostingMethod::FIFObyLotCreationDate),
-        "4" => Ok(InventoryCostingMethod::FIFObyLotBasisDate),
-        _ => { 
+    match InventoryCostingMethod::from_choice(&arg) {
+        Some(method) => Ok(method),
+        None => { 
                 println!("WARN: Invalid environment variable for 'INV_COSTING_METHOD'. Using default."); 
                 Ok(InventoryCostingMethod::LIFObyLotCreationDate)
         }
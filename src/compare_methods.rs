@@ -0,0 +1,100 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::path::PathBuf;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+
+use crptls::core_functions::{self, ImportProcessParameters, Timings};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::summary::{self, YearGains};
+
+use cryptools::export::export_csv;
+
+
+/// Processes `input_file_paths` under each costing method that chooses its own lots (every one but specific
+/// identification), each from freshly imported maps, and compares the realized gains and losses of each, by year.
+/// The comparison is printed, and exported as C30_Costing_method_comparison.csv.  Otherwise, nothing is exported.
+pub(crate) fn compare_costing_methods(
+    settings: &ImportProcessParameters,
+    input_file_paths: &[PathBuf],
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    let methods = (1..=11)
+        .filter_map(|choice| InventoryCostingMethod::from_choice(&choice.to_string()))
+        .filter(|method| *method != InventoryCostingMethod::SpecificIdentification);
+
+    let mut comparison: Vec<(InventoryCostingMethod, Vec<YearGains>)> = Vec::new();
+
+    for method in methods {
+
+        log::info!("\nProcessing with costing method: {}...", method);
+
+        let mut method_settings = settings.clone();
+        method_settings.costing_method = method.clone();
+
+        let (
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        ) = core_functions::import_and_process_final(input_file_paths, &method_settings, timings)
+            .map_err(|e| format!("Costing method {}: {}", method, e))?;
+
+        let gains = summary::gains_by_year(
+            &settings.home_currency,
+            settings.long_term_days,
+            settings.fiscal_year_start_month,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        comparison.push((method, gains));
+    }
+
+    print_comparison(settings, &comparison);
+
+    if settings.should_export {
+        export_csv::_30_costing_method_comparison_to_csv(settings, &comparison)?;
+    }
+
+    Ok(())
+}
+
+/// Prints each costing method's realized gain or loss in each year, and in total.
+fn print_comparison(settings: &ImportProcessParameters, comparison: &[(InventoryCostingMethod, Vec<YearGains>)]) {
+
+    let mut years: Vec<i32> = comparison.iter()
+        .flat_map(|(_method, gains)| gains.iter().map(|g| g.year))
+        .filter(|year| settings.tax_year.is_none_or(|tax_year| *year == tax_year))
+        .collect();
+    years.sort();
+    years.dedup();
+
+    println!("\nRealized gain/loss by costing method ({}):", settings.home_currency);
+
+    let mut heading = format!("  {:<56}", "Costing method");
+    for year in years.iter() {
+        heading += &format!(" {:>14}", year);
+    }
+    println!("{} {:>14}", heading, "Total");
+
+    for (method, gains) in comparison.iter() {
+
+        let mut line = format!("  {:<56}", method.to_string());
+        let mut total = Decimal::ZERO;
+
+        for year in years.iter() {
+            let gain: Decimal = gains.iter().filter(|g| g.year == *year).map(|g| g.realized_total()).sum();
+            total += gain;
+            line += &format!(" {:>14.2}", gain);
+        }
+
+        let marker = if *method == settings.costing_method { "  (configured)" } else { "" };
+        println!("{} {:>14.2}{}", line, total, marker);
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use clap::{Arg, ArgAction, ValueEnum};
+
+
+/// A shell that `cryptools completions` writes a completion script for.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Shell {
+    /// Source it (i.e., from ~/.bashrc), or save it to the bash-completion directory.
+    Bash,
+    /// Source it from ~/.zshrc.  It uses zsh's bash completion support.
+    Zsh,
+    /// Save it as ~/.config/fish/completions/cryptools.fish.
+    Fish,
+}
+
+/// The completion script of `shell` for the `cmd`'s flags (with their values, where they're chosen from a list, or
+/// files otherwise), its subcommands and the files to import.
+pub(crate) fn completion_script(shell: Shell, cmd: &mut clap::Command) -> String {
+
+    cmd.build();
+
+    let name = cmd.get_name().to_string();
+    let flags: Vec<&Arg> = cmd.get_arguments().filter(|arg| arg.get_long().is_some() && !arg.is_hide_set()).collect();
+    let subcommands: Vec<(String, String)> = cmd.get_subcommands()
+        .map(|sub| (sub.get_name().to_string(), sub.get_about().map(|about| about.to_string()).unwrap_or_default()))
+        .collect();
+
+    match shell {
+        Shell::Bash => bash_script(&name, &flags, &subcommands),
+        Shell::Zsh => format!("autoload -U +X bashcompinit && bashcompinit\n\n{}", bash_script(&name, &flags, &subcommands)),
+        Shell::Fish => fish_script(&name, &flags, &subcommands),
+    }
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Set | ArgAction::Append)
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values().iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_string()).collect()
+}
+
+/// The first sentence of the `arg`'s help, for a description.
+fn summary(help: &str) -> String {
+
+    let help = help.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    match help.find(". ") {
+        Some(end) => help[..end].to_string(),
+        None => help.trim_end_matches('.').to_string(),
+    }
+}
+
+fn bash_script(name: &str, flags: &[&Arg], subcommands: &[(String, String)]) -> String {
+
+    let function = format!("_{}", name.replace('-', "_"));
+    let words = |words: Vec<String>| words.join(" ");
+
+    let mut value_cases = String::new();
+
+    for arg in flags.iter().filter(|arg| takes_value(arg)) {
+
+        let mut options = vec![format!("--{}", arg.get_long().unwrap())];
+        if let Some(short) = arg.get_short() { options.push(format!("-{}", short)) }
+
+        let values = possible_values(arg);
+        let reply = match values.is_empty() {
+            true => "compgen -f -- \"$cur\"".to_string(),
+            false => format!("compgen -W \"{}\" -- \"$cur\"", words(values)),
+        };
+
+        value_cases += &format!("        {})\n            COMPREPLY=($({}))\n            return ;;\n", options.join("|"), reply);
+    }
+
+    let all_flags = words(flags.iter().map(|arg| format!("--{}", arg.get_long().unwrap())).collect());
+    let subcommand_names = words(subcommands.iter().map(|(sub, _about)| sub.clone()).collect());
+
+    format!("\
+{function}() {{
+    local cur prev
+    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"
+    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+
+    case \"$prev\" in
+{value_cases}    esac
+
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"{all_flags}\" -- \"$cur\"))
+    elif [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W \"{subcommand_names}\" -- \"$cur\") $(compgen -f -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+}}
+
+complete -o filenames -F {function} {name}
+")
+}
+
+fn fish_script(name: &str, flags: &[&Arg], subcommands: &[(String, String)]) -> String {
+
+    let quoted = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+
+    let mut script = String::new();
+
+    for (sub, about) in subcommands.iter() {
+        script += &format!("complete -c {} -n __fish_use_subcommand -f -a {} -d {}\n", name, sub, quoted(&summary(about)));
+    }
+
+    for arg in flags.iter() {
+
+        let mut line = format!("complete -c {} -l {}", name, arg.get_long().unwrap());
+
+        if let Some(short) = arg.get_short() { line += &format!(" -s {}", short) }
+
+        if takes_value(arg) {
+            let values = possible_values(arg);
+            line += " -r";
+            if !values.is_empty() { line += &format!(" -f -a {}", quoted(&values.join(" "))) }
+        }
+
+        if let Some(help) = arg.get_help() {
+            line += &format!(" -d {}", quoted(&summary(&help.to_string())));
+        }
+
+        script += &line;
+        script += "\n";
+    }
+
+    script
+}
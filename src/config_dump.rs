@@ -0,0 +1,66 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+
+use clap::{ArgAction, ArgMatches};
+use clap::parser::ValueSource;
+
+use crate::Cfg;
+
+
+/// The flags not written to the `[flags]` table, as they name the config file (or profile) itself.
+const CONFIG_FLAGS: [&str; 2] = ["config", "profile"];
+
+/// The settings whose environment variables (and so config file keys) aren't named after their `Cfg` fields.
+const RENAMED_SETTINGS: [(&str, &str); 2] = [
+    ("home_curr_leg_is_basis", "home_currency_leg_is_basis"),
+    ("zero_quantity_rows_are_annotations", "zero_quantity_annotations"),
+];
+
+/// The effective settings, as a config file (see `Cli::config`): each setting of the `cfg`, as resolved from the
+/// environment variables, the .env file, the config file and the flags standing for them (those not set are left
+/// out), then a `[flags]` table of every other flag given in the `matches` (on the command line or by the config
+/// file's own `[flags]`), by long name.
+pub(crate) fn dump_settings(cfg: &Cfg, cmd: &clap::Command, matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+
+    let mut settings = match toml::Value::try_from(cfg)? {
+        toml::Value::Table(table) => table,
+        _ => return Err("The settings aren't a table.".into()),
+    };
+
+    for (field, key) in RENAMED_SETTINGS.iter() {
+        if let Some(value) = settings.remove(*field) { settings.insert(key.to_string(), value); }
+    }
+
+    let mut flags = toml::value::Table::new();
+
+    for arg in cmd.get_arguments() {
+
+        let id = arg.get_id().as_str();
+
+        let Some(long) = arg.get_long() else { continue };
+        if CONFIG_FLAGS.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine) { continue }
+
+        let values: Vec<toml::Value> = matches.get_raw(id).into_iter().flatten()
+            .map(|value| toml::Value::String(value.to_string_lossy().to_string()))
+            .collect();
+
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => toml::Value::Boolean(matches.get_flag(id)),
+            //  Given once per time it's repeated (i.e., `-vv`), as the config file's `[flags]` are read
+            ArgAction::Count => toml::Value::Array(vec![toml::Value::Boolean(true); matches.get_count(id) as usize]),
+            ArgAction::Append => toml::Value::Array(values),
+            _ => match values.into_iter().next() {
+                Some(value) => value,
+                None => continue,
+            },
+        };
+
+        flags.insert(long.to_string(), value);
+    }
+
+    settings.insert("flags".to_string(), toml::Value::Table(flags));
+
+    Ok(toml::to_string(&toml::Value::Table(settings))?)
+}
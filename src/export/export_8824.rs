@@ -0,0 +1,190 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io::Write;
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crptls::transaction::{ActionRecord, Transaction};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::like_kind::{self, LikeKindExchange};
+
+use crate::export::export_csv::{report_writer, write_rows_to_csv};
+
+
+/// The like-kind exchanges dated in the tax year (if one is set).
+fn exchanges_in_tax_year(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<LikeKindExchange>, Box<dyn Error>> {
+
+    Ok(like_kind::like_kind_exchanges(&settings.home_currency, settings.lk_cutoff_date, &settings.lk_scope, raw_acct_map, acct_map, ars, txns_map)?
+        .into_iter()
+        .filter(|exchange| settings.is_reported_date(exchange.date))
+        .collect())
+}
+
+/// The quantity and ticker of an `Account`, i.e., `0.5 BTC`.
+fn description(
+    settings: &ImportProcessParameters,
+    amount: Decimal,
+    acct_key: u16,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> String {
+    let raw_acct = raw_acct_map.get(&acct_map.get(&acct_key).unwrap().raw_key).unwrap();
+    format!("{} {}", settings.output_precision.crypto(amount), raw_acct.ticker)
+}
+
+/// Lists each like-kind exchange, with what was given up and received, the gain realized but deferred, and the
+/// basis carried over to what was received, with totals (C26_Like_kind_exchanges.csv).
+pub fn like_kind_exchanges_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Date".to_string(),
+        "Memo".to_string(),
+        "Given up".to_string(),
+        "Acquired".to_string(),
+        "Received".to_string(),
+        "Fair market value".to_string(),
+        "Basis given up".to_string(),
+        "Deferred gain/loss".to_string(),
+        "Carryover basis".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let precision = &settings.output_precision;
+    let mut total_fmv = dec!(0);
+    let mut total_basis = dec!(0);
+    let mut total_deferred = dec!(0);
+    let mut total_carryover = dec!(0);
+
+    for exchange in exchanges_in_tax_year(settings, raw_acct_map, acct_map, ars, txns_map)? {
+
+        let txn = txns_map.get(&exchange.tx_num).unwrap();
+
+        rows.push(vec![
+            exchange.tx_num.to_string(),
+            settings.report_date(exchange.date),
+            txn.user_memo.to_string(),
+            description(settings, exchange.given_amount, exchange.given_acct_key, raw_acct_map, acct_map),
+            settings.report_date(exchange.given_acquired),
+            description(settings, exchange.received_amount, exchange.received_acct_key, raw_acct_map, acct_map),
+            precision.fiat(exchange.fair_market_value),
+            precision.fiat(exchange.given_basis),
+            precision.fiat(exchange.deferred_gain),
+            precision.fiat(exchange.carryover_basis),
+        ]);
+
+        total_fmv += exchange.fair_market_value;
+        total_basis += exchange.given_basis;
+        total_deferred += exchange.deferred_gain;
+        total_carryover += exchange.carryover_basis;
+    }
+
+    rows.push(vec![
+        String::new(),
+        String::new(),
+        "Total".to_string(),
+        String::new(),
+        String::new(),
+        String::new(),
+        precision.fiat(total_fmv),
+        precision.fiat(total_basis),
+        precision.fiat(total_deferred),
+        precision.fiat(total_carryover),
+    ]);
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C26_Like_kind_exchanges.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Writes the lines of Form 8824 (Like-Kind Exchanges) for each like-kind exchange (T7_Form_8824.txt): the property
+/// given up and received, their dates, and Part III's realized, recognized and deferred gain, and the basis of the
+/// property received.  No cash or other (unlike) property is given or received in an exchange of one currency for
+/// another, so the whole of the gain or loss realized is deferred.
+pub fn form_8824_to_txt(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+// Form 8824 - Like-Kind Exchanges
+//
+// Home currency: USD
+// Like-kind cut-off date: 2017-12-31.
+//
+// Exchange 1 (transaction 12, Trade BTC for ETH)
+//     Line 1  Property given up:                          0.50000000 BTC
+//     Line 2  Property received:                          10.00000000 ETH
+//     ...
+//     Line 25 Basis of like-kind property received:       1500.00
+
+    let exchanges = exchanges_in_tax_year(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let mut file = report_writer(settings, &settings.tax_year_file_name("T7_Form_8824.txt"))?;
+
+    writeln!(file, "Form 8824 - Like-Kind Exchanges
+\nHome currency: {}
+Like-kind cut-off date: {}.",
+        settings.home_currency,
+        settings.report_date(settings.lk_cutoff_date)
+    )?;
+
+    let precision = &settings.output_precision;
+
+    for (idx, exchange) in exchanges.iter().enumerate() {
+
+        let txn = txns_map.get(&exchange.tx_num).unwrap();
+        let recognized = dec!(0);
+
+        writeln!(file, "\nExchange {} (transaction {}, {})", idx + 1, exchange.tx_num, txn.user_memo)?;
+
+        let lines = [
+            ("Line 1 ", "Property given up:", description(settings, exchange.given_amount, exchange.given_acct_key, raw_acct_map, acct_map)),
+            ("Line 2 ", "Property received:", description(settings, exchange.received_amount, exchange.received_acct_key, raw_acct_map, acct_map)),
+            ("Line 3 ", "Date given up was acquired:", settings.report_date(exchange.given_acquired)),
+            ("Line 4 ", "Date given up was transferred:", settings.report_date(exchange.date)),
+            ("Line 5 ", "Date received was identified:", settings.report_date(exchange.date)),
+            ("Line 6 ", "Date received was received:", settings.report_date(exchange.date)),
+            ("Line 17", "Fair market value received:", precision.fiat(exchange.fair_market_value)),
+            ("Line 18", "Adjusted basis given up:", precision.fiat(exchange.given_basis)),
+            ("Line 19", "Realized gain or (loss):", precision.fiat(exchange.deferred_gain)),
+            ("Line 23", "Recognized gain:", precision.fiat(recognized)),
+            ("Line 24", "Deferred gain or (loss):", precision.fiat(exchange.deferred_gain - recognized)),
+            ("Line 25", "Basis of like-kind property received:", precision.fiat(exchange.carryover_basis)),
+        ];
+
+        for (line, label, value) in lines {
+            writeln!(file, "    {} {:<40} {:>20}", line, label, value)?;
+        }
+    }
+
+    if exchanges.is_empty() {
+        writeln!(file, "\nNo like-kind exchanges.")?;
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,450 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io::Write;
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use chrono::NaiveDate;
+
+use crptls::transaction::{ActionRecord, Polarity, Transaction, TxKind, TxType};
+use crptls::account::{Account, RawAccount, Term};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::decimal_utils::OutputPrecision;
+use crptls::dust_conversions::{self, DustConversion};
+use crptls::stablecoins;
+
+use crate::export::export_csv::{is_reported_txn, report_writer, write_rows_to_csv};
+
+
+/// The disposals of a single `Transaction` (or of an aggregated dust conversion) for a single holding period,
+/// i.e., a line of Form 8949.  Values are like-kind values, with cost basis negative as on `Movement`s.
+pub(crate) struct Form8949Line {
+    pub term: Term,
+    /// Several, comma-separated, for a dust conversion.
+    pub txn_nums: String,
+    pub memo: String,
+    pub ticker: String,
+    /// Negative. `None` for a dust conversion, which may span several currencies.
+    pub amount: Option<Decimal>,
+    /// A date, "Various" or "INHERITED".
+    pub date_acquired: String,
+    pub date_sold: NaiveDate,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    /// The wash sale loss disallowed (positive), for code W.
+    pub adjustment: Decimal,
+    /// The account the disposal came out of (or, for a dust conversion, the account the dust was converted into).
+    pub acct_key: u16,
+}
+
+impl Form8949Line {
+    pub fn gain_or_loss(&self) -> Decimal {
+        self.proceeds + self.cost_basis + self.adjustment
+    }
+}
+
+/// Collects every line of Form 8949, in `Transaction` order, short-term before long-term within a `Transaction`.
+/// Plain income isn't reported, but margin profit (a dual-`action record` incoming `flow`) is, with no basis.
+pub(crate) fn form_8949_lines(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<Form8949Line>, Box<dyn Error>> {
+
+    let mut lines: Vec<Form8949Line> = Vec::new();
+
+    let length = txns_map.len();
+
+    let dust_conversions = match settings.dust_threshold {
+        Some(threshold) => dust_conversions::find_dust_conversions(
+            threshold,
+            &settings.home_currency,
+            raw_acct_map,
+            acct_map,
+            ars,
+            txns_map
+        )?,
+        None => Vec::new(),
+    };
+
+    for txn_num in 1..=length {
+
+        let txn_num = txn_num as u32;
+
+        if !settings.is_reported_txn(txns_map.get(&txn_num).unwrap()) { continue }
+
+        // Dust conversions are reported as a single aggregated disposal (per term), in place of the first txn
+        if let Some(dc) = dust_conversions.iter().find(|dc| dc.txn_nums.contains(&txn_num)) {
+            let reported = dc.txn_nums.iter()
+                .any(|num| is_reported_txn(settings, txns_map.get(num).unwrap(), raw_acct_map, acct_map, ars));
+            if dc.txn_nums[0] == txn_num && reported {
+                push_dust_conversion_lines(&mut lines, dc, settings, raw_acct_map, acct_map, ars, txns_map)?;
+            }
+            continue
+        }
+
+        let txn = txns_map.get(&(txn_num)).unwrap();
+
+        // Gifts and donations aren't sales (see C20_Gifts_donations_and_losses.csv), and neither is dust swept (see
+        // C43_Dust_sweeps.csv)
+        if matches!(txn.kind, TxKind::Gift | TxKind::Donation | TxKind::DustSweep) { continue }
+
+        let tx_num_string = txn.tx_number.to_string();
+        let tx_memo_string = txn.get_auto_memo(ars,raw_acct_map,acct_map, &settings.home_currency)?;
+
+        let mut term_st: Option<Term> = None;
+        let mut term_lt: Option<Term> = None;
+        let mut ticker: Option<String> = None;
+        let mut origin_acct: Option<u16> = None;
+        let mut polarity: Option<Polarity> = None;
+
+        let mut amount_st = dec!(0);
+        let mut proceeds_st = dec!(0);
+        let mut cost_basis_st = dec!(0);
+        let mut adjustment_st = dec!(0);
+
+        let mut amount_lt = dec!(0);
+        let mut proceeds_lt = dec!(0);
+        let mut cost_basis_lt = dec!(0);
+        let mut adjustment_lt = dec!(0);
+
+        let flow_or_outgoing_exchange_movements = txn.get_outgoing_exchange_and_flow_mvmts(
+            &settings.home_currency,
+            ars,
+            raw_acct_map,
+            acct_map,
+            txns_map
+        )?;
+
+        let mut purchase_date_lt: NaiveDate = NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap();
+        let mut purchase_date_st: NaiveDate = NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap();
+        let mut various_dates_lt: bool = false;
+        let mut various_dates_st: bool = false;
+        let mut lt_set = false;
+        let mut st_set = false;
+        let mut inherited_lt = true;    //  Form 8949 asks for "INHERITED" in place of the date acquired
+        for mvmt in flow_or_outgoing_exchange_movements.iter() {
+            let lot = mvmt.get_lot(acct_map, ars);
+            let acct = acct_map.get(&lot.account_key).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+            if !settings.is_reported_account(raw_acct) { continue }
+
+            if ticker.is_none() { ticker = Some(raw_acct.ticker.clone()) };
+            if origin_acct.is_none() { origin_acct = Some(lot.account_key) };
+
+            if polarity.is_none() {
+                polarity = if mvmt.amount > dec!(0) {
+                    Some(Polarity::Incoming)
+                    } else { Some(Polarity::Outgoing)
+                };
+            }
+
+            fn dates_are_different(existing: &NaiveDate, current: &NaiveDate) -> bool {
+                existing != current
+            }
+
+            let term = mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days);
+
+            if term == Term::LT {
+                if !lt_set { purchase_date_lt = lot.date_for_basis_purposes; lt_set = true }
+                various_dates_lt = dates_are_different(&purchase_date_lt, &lot.date_for_basis_purposes);
+                if !lot.inherited { inherited_lt = false }
+
+                amount_lt += mvmt.amount;
+                proceeds_lt += mvmt.proceeds_lk.get();
+                cost_basis_lt += mvmt.cost_basis_lk.get();
+                adjustment_lt += mvmt.wash_sale_adjustment.get();
+
+                if term_lt.is_none() { term_lt = Some(term) }
+
+            } else {
+                if !st_set { purchase_date_st = lot.date_for_basis_purposes; st_set = true}
+                various_dates_st = dates_are_different(&purchase_date_st, &lot.date_for_basis_purposes);
+
+                assert_eq!(term, Term::ST);
+                amount_st += mvmt.amount;
+                proceeds_st += mvmt.proceeds_lk.get();
+                cost_basis_st += mvmt.cost_basis_lk.get();
+                adjustment_st += mvmt.wash_sale_adjustment.get();
+
+                if term_st.is_none() {
+                    term_st = Some(term);
+                }
+            }
+        }
+        // A cash-equivalent stablecoin's disposals realize no gain or loss, so they aren't reported
+        if ticker.as_ref().is_some_and(|ticker| stablecoins::is_cash_equivalent(&settings.stablecoins, ticker)) { continue }
+
+        let lt_purchase_date = if inherited_lt { "INHERITED".to_string() }
+            else if various_dates_lt { "Various".to_string() } else { purchase_date_lt.to_string() };
+        let st_purchase_date = if various_dates_st { "Various".to_string() } else { purchase_date_st.to_string() };
+
+        if (txn.transaction_type(
+            ars,
+            raw_acct_map,
+            acct_map)? == TxType::Flow
+        ) & (polarity == Some(Polarity::Incoming)) {
+            // The only incoming flow transaction to report would be margin profit, which is a dual-`action record` `transaction`
+            if txn.action_record_idx_vec.len() == 2 {
+                proceeds_st = -proceeds_st;   //  Proceeds are negative for incoming txns
+                cost_basis_st = dec!(0);
+                proceeds_lt = -proceeds_lt;   //  Proceeds are negative for incoming txns
+                cost_basis_lt = dec!(0);
+            } else {
+                continue    // Plain, old income isn't reported on form 8949
+            }
+        }
+
+        for (term, amount, date_acquired, proceeds, cost_basis, adjustment) in [
+            (term_st, amount_st, st_purchase_date, proceeds_st, cost_basis_st, adjustment_st),
+            (term_lt, amount_lt, lt_purchase_date, proceeds_lt, cost_basis_lt, adjustment_lt),
+        ] {
+            if let Some(term) = term {
+                lines.push(Form8949Line {
+                    term,
+                    txn_nums: tx_num_string.clone(),
+                    memo: tx_memo_string.clone(),
+                    ticker: ticker.clone().unwrap(),
+                    amount: Some(amount),
+                    date_acquired,
+                    date_sold: txn.date,
+                    proceeds,
+                    cost_basis,
+                    adjustment,
+                    acct_key: origin_acct.unwrap(),
+                });
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+fn push_dust_conversion_lines(
+    lines: &mut Vec<Form8949Line>,
+    dc: &DustConversion,
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let ic_acct = acct_map.get(&dc.incoming_acct_key).unwrap();
+    let ic_raw_acct = raw_acct_map.get(&ic_acct.raw_key).unwrap();
+
+    let mut amount_received = dec!(0);
+    for txn_num in dc.txn_nums.iter() {
+        let txn = txns_map.get(txn_num).unwrap();
+        amount_received += ars.get(&txn.action_record_idx_vec[1]).unwrap().amount;
+    }
+
+    let txn_nums_string = dc.txn_nums.iter().map(|num| num.to_string()).collect::<Vec<String>>().join(",");
+    let memo = format!("Dust conversion of {} txns into {} {}", dc.txn_nums.len(),
+        settings.output_precision.crypto(amount_received), ic_raw_acct.ticker);
+
+    let (st, lt) = dc.totals_by_term(&settings.home_currency, settings.long_term_days, raw_acct_map, acct_map, ars, txns_map)?;
+
+    for (term, totals) in [(Term::ST, st), (Term::LT, lt)] {
+
+        if let Some(totals) = totals {
+
+            lines.push(Form8949Line {
+                term,
+                txn_nums: txn_nums_string.clone(),
+                memo: memo.clone(),
+                ticker: String::new(),
+                amount: None,
+                date_acquired: totals.date_acquired.map_or("Various".to_string(), |date| date.to_string()),
+                date_sold: dc.date,
+                proceeds: totals.proceeds,
+                cost_basis: totals.cost_basis,
+                adjustment: totals.adjustment,
+                acct_key: dc.incoming_acct_key,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The `date_acquired` of a line as written in the reports (see `ImportProcessParameters::report_date()`), unless
+/// it's "Various" or "INHERITED".
+pub(crate) fn report_date_acquired(settings: &ImportProcessParameters, line: &Form8949Line) -> String {
+    match NaiveDate::parse_from_str(&line.date_acquired, "%Y-%m-%d") {
+        Ok(date) => settings.report_date(date),
+        Err(_) => line.date_acquired.clone(),
+    }
+}
+
+/// Form 8949's description of the property, e.g. "0.25 BTC".
+fn description(line: &Form8949Line, precision: &OutputPrecision) -> String {
+    match line.amount {
+        Some(amount) => format!("{} {}", precision.crypto((-amount).normalize()), line.ticker),
+        None => line.memo.clone(),
+    }
+}
+
+/// Writes Form 8949 with its own columns, (a) through (h), as Part I (short-term) followed by Part II (long-term),
+/// each with its totals.  Cost basis is positive, as on the form.  Columns (f) and (g) are left for the user, except
+/// for a wash sale, whose disallowed loss is reported with code W.
+pub fn form_8949_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let lines = form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Part".to_string(),
+        "(a) Description of property".to_string(),
+        "(b) Date acquired".to_string(),
+        "(c) Date sold or disposed of".to_string(),
+        "(d) Proceeds".to_string(),
+        "(e) Cost or other basis".to_string(),
+        "(f) Code(s)".to_string(),
+        "(g) Amount of adjustment".to_string(),
+        "(h) Gain or (loss)".to_string(),
+        "Txn#".to_string(),             // not in 8949; just useful
+    ];
+    rows.push(columns.to_vec());
+
+    for (part, term) in [("Part I", Term::ST), ("Part II", Term::LT)] {
+
+        let mut total_proceeds = dec!(0);
+        let mut total_cost_basis = dec!(0);
+        let mut total_adjustment = dec!(0);
+
+        for line in lines.iter().filter(|line| line.term == term) {
+
+            let precision = &settings.output_precision;
+            let proceeds = precision.round_fiat(line.proceeds);
+            let cost_basis = -precision.round_fiat(line.cost_basis);
+            let adjustment = precision.round_fiat(line.adjustment);
+
+            total_proceeds += proceeds;
+            total_cost_basis += cost_basis;
+            total_adjustment += adjustment;
+
+            rows.push(vec![
+                part.to_string(),
+                description(line, precision),
+                report_date_acquired(settings, line),
+                settings.report_date(line.date_sold),
+                precision.fiat(proceeds),
+                precision.fiat(cost_basis),
+                if adjustment == dec!(0) { String::new() } else { "W".to_string() },
+                if adjustment == dec!(0) { String::new() } else { precision.fiat(adjustment) },
+                precision.fiat(proceeds - cost_basis + adjustment),
+                line.txn_nums.clone(),
+            ]);
+        }
+
+        rows.push(vec![
+            part.to_string(),
+            "Totals".to_string(),
+            String::new(),
+            String::new(),
+            settings.output_precision.fiat(total_proceeds),
+            settings.output_precision.fiat(total_cost_basis),
+            String::new(),
+            if total_adjustment == dec!(0) { String::new() } else { settings.output_precision.fiat(total_adjustment) },
+            settings.output_precision.fiat(total_proceeds - total_cost_basis + total_adjustment),
+            String::new(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C13_Form_8949_Parts_I_and_II.csv"), &rows)?;
+
+    Ok(())
+}
+
+pub fn form_8949_to_txt(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+// Form 8949 - Sales and Other Dispositions of Capital Assets
+//
+// Costing method used: LIFO by lot creation date.
+// Home currency: USD
+// Enable like-kind treatment: false
+//
+// Part I - Short-Term
+//     (a) Description     (b) Acquired  (c) Sold      (d) Proceeds  (e) Cost basis  (h) Gain or (loss)
+//     0.25 BTC            2016-02-01    2016-03-01          250.00          220.00               30.00
+//     ...
+//     Totals                                                810.00          687.14              122.86
+//
+// Part II - Long-Term
+//     ...
+
+    let lines = form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let mut file = report_writer(settings, &settings.tax_year_file_name("T6_Form_8949.txt"))?;
+
+    writeln!(file, "Form 8949 - Sales and Other Dispositions of Capital Assets
+\nCosting method used: {}.
+Home currency: {}
+Enable like-kind treatment: {}",
+        settings.costing_method,
+        settings.home_currency,
+        settings.lk_treatment_enabled
+    )?;
+
+    if settings.lk_treatment_enabled {
+        writeln!(file, "Like-kind cut-off date: {}.", settings.report_date(settings.lk_cutoff_date))?;
+    }
+
+    for (part, term) in [("Part I - Short-Term", Term::ST), ("Part II - Long-Term", Term::LT)] {
+
+        writeln!(file, "\n{}", part)?;
+        writeln!(file, "    {:<30} {:<12} {:<12} {:>14} {:>16} {:>20}",
+            "(a) Description", "(b) Acquired", "(c) Sold", "(d) Proceeds", "(e) Cost basis", "(h) Gain or (loss)")?;
+
+        let mut total_proceeds = dec!(0);
+        let mut total_cost_basis = dec!(0);
+        let mut total_adjustment = dec!(0);
+
+        for line in lines.iter().filter(|line| line.term == term) {
+
+            let precision = &settings.output_precision;
+            let proceeds = precision.round_fiat(line.proceeds);
+            let cost_basis = -precision.round_fiat(line.cost_basis);
+            let adjustment = precision.round_fiat(line.adjustment);
+
+            total_proceeds += proceeds;
+            total_cost_basis += cost_basis;
+            total_adjustment += adjustment;
+
+            writeln!(file, "    {:<30} {:<12} {:<12} {:>14} {:>16} {:>20}",
+                description(line, precision), report_date_acquired(settings, line), settings.report_date(line.date_sold),
+                precision.fiat(proceeds), precision.fiat(cost_basis), precision.fiat(proceeds - cost_basis + adjustment))?;
+
+            if adjustment != dec!(0) {
+                writeln!(file, "        (f) Code W (wash sale); (g) loss disallowed: {}", precision.fiat(adjustment))?;
+            }
+        }
+
+        writeln!(file, "    {:<56} {:>14} {:>16} {:>20}", "Totals", settings.output_precision.fiat(total_proceeds),
+            settings.output_precision.fiat(total_cost_basis),
+            settings.output_precision.fiat(total_proceeds - total_cost_basis + total_adjustment))?;
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
@@ -3,104 +3,671 @@
 
 use std::error::Error;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crptls::transaction::{Transaction, ActionRecord};
+
+use crptls::transaction::{Transaction, ActionRecord, TxKind};
 use crptls::account::{Account, RawAccount};
-use crptls::core_functions::ImportProcessParameters;
-use crate::export::{export_csv, export_txt, export_je};
+use crptls::core_functions::{ImportProcessParameters, Timings};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::nfts;
+use crptls::non_sale_disposals;
+use crate::export::{export_csv, export_txt, export_je, export_8949, export_8824};
+
+
+/// A group of the reports exported by default, for `--reports`.  C13/T6, C18, C19, C27, C28, C33, C41 and C42 are
+/// only exported when their group is chosen, so that a run without `--reports` exports the reports it always has.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Report {
+    /// C1, C2 and C3: each account's balance and cost basis.
+    Accounts,
+    /// T1, T2 and T3 (and C42, and C43, with dust quantities): each account's lots (and the dust swept out of them).
+    Lots,
+    /// C4, C5, C6 (and C32): each transaction's movements (and each tag's transactions).
+    Transactions,
+    /// C7, C13/T6, C16, C17, C18, C24, C25, C26/T7, C27, C28 and C41: the gains and losses, and the reports of them for
+    /// tax forms.
+    Gains,
+    /// C19 (and C28 and C34): income by type (and by quarter, and by payer).
+    Income,
+    /// C33 (and C40): expenses by category (and the mining P&L, with --costs).
+    Expenses,
+    /// C20: gifts, donations and losses.
+    NonSale,
+    /// C10: synthetic acquisitions.
+    Synthetic,
+    /// J1, and any ledger, Beancount or general journal.
+    #[value(name = "je")]
+    JournalEntries,
+}
+
+/// A report that `--stdout` writes to standard output.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum StdoutReport {
+    /// C1_Acct_Sum_with_cost_basis.csv
+    Accounts,
+    /// T1_Acct_lot_detail.txt
+    Lots,
+    /// C4_Txns_mvmts_detail.csv
+    Transactions,
+    /// C7_Form_8949.csv
+    Gains,
+    /// C18_Gains_by_asset_and_year.csv
+    GainsByAsset,
+    /// C19_Income_by_type.csv
+    Income,
+    /// J1_Journal_Entries.txt
+    #[value(name = "je")]
+    JournalEntries,
+    /// J2_Ledger_Journal.ledger
+    Ledger,
+    /// J3_Beancount_Journal.beancount
+    Beancount,
+    /// J4_General_Journal.csv
+    JournalCsv,
+}
+
+impl StdoutReport {
+
+    /// The beginning of the report's file name, which is followed by the tax year, if there is one.
+    pub fn file_name_prefix(self) -> &'static str {
+        match self {
+            StdoutReport::Accounts => "C1_",
+            StdoutReport::Lots => "T1_",
+            StdoutReport::Transactions => "C4_",
+            StdoutReport::Gains => "C7_",
+            StdoutReport::GainsByAsset => "C18_",
+            StdoutReport::Income => "C19_",
+            StdoutReport::JournalEntries => "J1_",
+            StdoutReport::Ledger => "J2_",
+            StdoutReport::Beancount => "J3_",
+            StdoutReport::JournalCsv => "J4_",
+        }
+    }
+
+    /// The group of reports it's exported with.
+    pub fn group(self) -> Report {
+        match self {
+            StdoutReport::Accounts => Report::Accounts,
+            StdoutReport::Lots => Report::Lots,
+            StdoutReport::Transactions => Report::Transactions,
+            StdoutReport::Gains | StdoutReport::GainsByAsset => Report::Gains,
+            StdoutReport::Income => Report::Income,
+            StdoutReport::JournalEntries
+            | StdoutReport::Ledger
+            | StdoutReport::Beancount
+            | StdoutReport::JournalCsv => Report::JournalEntries,
+        }
+    }
+}
+
+/// An output format of the reports beyond the default ones, made from the fully processed maps, and registered with
+/// `Exporters` (i.e., the PDF summary, or an external exporter, for `--exporter`).  It writes to the
+/// `settings.export_path`, as the report files are.
+pub trait Exporter: Send + Sync {
+
+    /// What it writes (i.e., `Summary.pdf`), for the timings and in any error.
+    fn name(&self) -> String;
+
+    fn export(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        account_map: &HashMap<u16, Account>,
+        action_records_map: &HashMap<u32, ActionRecord>,
+        transactions_map: &HashMap<u32, Transaction>,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+/// The registered `Exporter`s, each run after the default reports are exported.
+#[derive(Clone, Default)]
+pub struct Exporters {
+    exporters: Vec<Arc<dyn Exporter>>,
+}
+
+impl Exporters {
+
+    pub fn register(&mut self, exporter: impl Exporter + 'static) {
+        self.exporters.push(Arc::new(exporter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exporters.is_empty()
+    }
+
+    /// Runs every `Exporter`, in the order registered, stopping at the first to fail.
+    pub fn export(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        account_map: &HashMap<u16, Account>,
+        action_records_map: &HashMap<u32, ActionRecord>,
+        transactions_map: &HashMap<u32, Transaction>,
+        timings: &mut Timings,
+    ) -> Result<(), Box<dyn Error>> {
 
+        for exporter in self.exporters.iter() {
 
+            let start = Instant::now();
+
+            exporter.export(settings, raw_acct_map, account_map, action_records_map, transactions_map)
+                .map_err(|e| format!("{} failed: {}", exporter.name(), e))?;
+
+            timings.record(&format!("Export: {}", exporter.name()), start);
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `report` is to be exported: every one is, unless some were chosen.
+fn includes(reports: &[Report], report: Report) -> bool {
+    reports.is_empty() || reports.contains(&report)
+}
+
+/// Whether the reports of `report` that are only exported when it's chosen (see `Report`) are to be exported.
+fn chosen(reports: &[Report], report: Report) -> bool {
+    reports.contains(&report)
+}
+
+/// Exports the reports (or just the chosen `reports`, if any), each only if it applies to the run.
 pub fn export(
     settings: &ImportProcessParameters,
+    reports: &[Report],
     raw_acct_map: &HashMap<u16, RawAccount>,
     account_map: &HashMap<u16, Account>,
     action_records_map: &HashMap<u32, ActionRecord>,
     transactions_map: &HashMap<u32, Transaction>,
+    timings: &mut Timings,
 ) -> Result<(), Box<dyn Error>> {
 
-    println!("Creating all reports now.");
+    if reports.is_empty() {
+        log::info!("Creating all reports now.");
+    } else {
+        log::info!("Creating the chosen reports now.");
+    }
 
-    export_csv::_1_account_sums_to_csv(
-        &settings,
-        &raw_acct_map,
-        &account_map
-    );
+    if includes(reports, Report::Accounts) {
 
-    export_csv::_2_account_sums_nonzero_to_csv(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-    );
+        let start = Instant::now();
 
-    if settings.lk_treatment_enabled {
-        export_csv::_3_account_sums_to_csv_with_orig_basis(
+        export_csv::_1_account_sums_to_csv(
             &settings,
             &raw_acct_map,
             &account_map
-        );
-    }
-
-    export_csv::_4_transaction_mvmt_detail_to_csv(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map
-    )?;
-
-    export_csv::_5_transaction_mvmt_summaries_to_csv(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map
-    )?;
-
-    export_csv::_6_transaction_mvmt_detail_to_csv_w_orig(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map
-    )?;
-
-    export_csv::_7_gain_loss_8949_to_csv(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map
-    )?;
-
-    export_txt::_1_account_lot_detail_to_txt(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-        &action_records_map,
-        &transactions_map,
-    )?;
-
-    export_txt::_2_account_lot_summary_to_txt(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-    )?;
-
-    export_txt::_3_account_lot_summary_non_zero_to_txt(
-        &settings,
-        &raw_acct_map,
-        &account_map,
-    )?;
-
-    if !settings.lk_treatment_enabled {
-        export_je::prepare_non_lk_journal_entries(
+        )?;
+
+        timings.record("Export: C1_Acct_Sum_with_cost_basis", start);
+
+        let start = Instant::now();
+
+        export_csv::_2_account_sums_nonzero_to_csv(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+        )?;
+
+        timings.record("Export: C2_Acct_Sum_with_nonzero_cost_basis", start);
+
+        if settings.lk_treatment_enabled {
+
+            let start = Instant::now();
+
+            export_csv::_3_account_sums_to_csv_with_orig_basis(
+                &settings,
+                &raw_acct_map,
+                &account_map
+            )?;
+
+            timings.record("Export: C3_Acct_Sum_with_orig_and_lk_cost_basis", start);
+        }
+    }
+
+    for year_settings in tax_year_settings(settings, transactions_map) {
+
+        export_tax_year_reports(
+            &year_settings,
+            reports,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            timings,
+        )?;
+    }
+
+    if includes(reports, Report::Synthetic) && (settings.synthetic_acquisitions.is_some() || settings.lenient) {
+
+        let start = Instant::now();
+
+        export_csv::_10_synthetic_acquisitions_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C10_Synthetic_acquisitions", start);
+    }
+
+    if includes(reports, Report::Lots) {
+
+        let start = Instant::now();
+
+        export_txt::_1_account_lot_detail_to_txt(
             &settings,
             &raw_acct_map,
             &account_map,
             &action_records_map,
             &transactions_map,
         )?;
+
+        timings.record("Export: T1_Acct_lot_detail", start);
+
+        let start = Instant::now();
+
+        export_txt::_2_account_lot_summary_to_txt(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+        )?;
+
+        timings.record("Export: T2_Acct_lot_summary", start);
+
+        let start = Instant::now();
+
+        export_txt::_3_account_lot_summary_non_zero_to_txt(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+        )?;
+
+        timings.record("Export: T3_Acct_lot_summary_non_zero", start);
+
+        if !settings.dust_quantities.is_empty() {
+
+            let start = Instant::now();
+
+            export_csv::_43_dust_sweeps_to_csv(
+                &settings,
+                &raw_acct_map,
+                &account_map,
+                &action_records_map,
+                &transactions_map,
+            )?;
+
+            timings.record("Export: C43_Dust_sweeps", start);
+        }
+    }
+
+    if chosen(reports, Report::Lots) {
+
+        let start = Instant::now();
+
+        export_csv::_42_lots_to_csv(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+        )?;
+
+        timings.record("Export: C42_Lots", start);
     }
 
 Ok(())
-}
\ No newline at end of file
+}
+
+/// The settings to export the reports limited to a tax year with: just `settings`, unless they are to be split by
+/// tax year, in which case a copy for each calendar year with a `Transaction` (oldest first).
+pub fn tax_year_settings(
+    settings: &ImportProcessParameters,
+    transactions_map: &HashMap<u32, Transaction>,
+) -> Vec<ImportProcessParameters> {
+
+    if !settings.split_by_tax_year { return vec![settings.clone()] }
+
+    let mut years: Vec<i32> = transactions_map.values().map(|txn| settings.fiscal_year(txn.date)).collect();
+    years.sort();
+    years.dedup();
+
+    years.into_iter().map(|year| ImportProcessParameters { tax_year: Some(year), ..settings.clone() }).collect()
+}
+
+/// Exports the gain/loss, income, expense and journal entry reports, which are limited to `settings.tax_year` (if set).
+fn export_tax_year_reports(
+    settings: &ImportProcessParameters,
+    reports: &[Report],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    account_map: &HashMap<u16, Account>,
+    action_records_map: &HashMap<u32, ActionRecord>,
+    transactions_map: &HashMap<u32, Transaction>,
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    if includes(reports, Report::Transactions) {
+
+        let start = Instant::now();
+
+        export_csv::_4_transaction_mvmt_detail_to_csv(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        timings.record("Export: C4_Txns_mvmts_detail", start);
+
+        let start = Instant::now();
+
+        export_csv::_5_transaction_mvmt_summaries_to_csv(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        timings.record("Export: C5_Txns_mvmts_summary", start);
+
+        let start = Instant::now();
+
+        export_csv::_6_transaction_mvmt_detail_to_csv_w_orig(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        timings.record("Export: C6_Txns_mvmts_more_detail", start);
+
+        if transactions_map.values().any(|txn| !txn.tags.is_empty()) {
+
+            let start = Instant::now();
+
+            export_csv::_32_tagged_transactions_to_csv(
+                settings,
+                raw_acct_map,
+                account_map,
+                action_records_map,
+                transactions_map,
+            )?;
+
+            timings.record("Export: C32_Tagged_transactions", start);
+        }
+    }
+
+    if includes(reports, Report::Gains) {
+
+        let start = Instant::now();
+
+        export_csv::_7_gain_loss_8949_to_csv(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map
+        )?;
+
+        timings.record("Export: C7_Form_8949", start);
+    }
+
+    if chosen(reports, Report::Gains) {
+
+        let start = Instant::now();
+
+        export_8949::form_8949_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        export_8949::form_8949_to_txt(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C13/T6_Form_8949 (Parts I and II)", start);
+    }
+
+    if includes(reports, Report::Gains) && settings.lk_treatment_enabled {
+
+        let start = Instant::now();
+
+        export_8824::like_kind_exchanges_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        export_8824::form_8824_to_txt(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C26/T7_Form_8824 (Like-kind exchanges)", start);
+    }
+
+    if chosen(reports, Report::Gains) {
+
+        let start = Instant::now();
+
+        export_csv::_27_lot_selection_audit_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C27_Lot_selection_audit", start);
+    }
+
+    if includes(reports, Report::Gains) && settings.wash_sale_window.is_some() {
+
+        let start = Instant::now();
+
+        export_csv::_16_wash_sales_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C16_Wash_sales", start);
+    }
+
+    if includes(reports, Report::Gains) && settings.costing_method == InventoryCostingMethod::AdjustedCostBase {
+
+        let start = Instant::now();
+
+        export_csv::_17_schedule_3_gains_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C17_Schedule_3_capital_gains", start);
+    }
+
+    if chosen(reports, Report::Gains) {
+
+        let start = Instant::now();
+
+        export_csv::_18_gains_by_asset_and_year_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C18_Gains_by_asset_and_year", start);
+
+        let start = Instant::now();
+
+        export_csv::_41_schedule_d_totals_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C41_Schedule_D_totals", start);
+    }
+
+    if chosen(reports, Report::Income) {
+
+        let start = Instant::now();
+
+        export_csv::_19_income_by_type_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C19_Income_by_type", start);
+    }
+
+    if includes(reports, Report::Income) && transactions_map.values().any(|txn| txn.payer.is_some()) {
+
+        let start = Instant::now();
+
+        export_csv::_34_income_by_payer_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C34_Income_by_payer", start);
+    }
+
+    if chosen(reports, Report::Expenses) {
+
+        let start = Instant::now();
+
+        export_csv::_33_expenses_by_category_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C33_Expenses_by_category", start);
+    }
+
+    if includes(reports, Report::Expenses) && !settings.fiat_costs.is_empty() {
+
+        let start = Instant::now();
+
+        export_csv::_40_mining_pnl_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C40_Mining_PnL", start);
+    }
+
+    if chosen(reports, Report::Gains) || chosen(reports, Report::Income) {
+
+        let start = Instant::now();
+
+        export_csv::_28_quarterly_gains_and_income_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C28_Quarterly_gains_and_income", start);
+    }
+
+    if includes(reports, Report::NonSale) && transactions_map.values().any(|txn| non_sale_disposals::is_non_sale(txn.kind)) {
+
+        let start = Instant::now();
+
+        export_csv::_20_gifts_donations_and_losses_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C20_Gifts_donations_and_losses", start);
+    }
+
+    if includes(reports, Report::Gains) && transactions_map.values().any(|txn| txn.kind == TxKind::DerivativePnl) {
+
+        let start = Instant::now();
+
+        export_csv::_24_derivative_pnl_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C24_Derivative_PnL", start);
+    }
+
+    if includes(reports, Report::Gains)
+        && raw_acct_map.values().any(|raw_acct| !raw_acct.is_margin && nfts::collection_and_token_id(&raw_acct.ticker).is_some())
+    {
+
+        let start = Instant::now();
+
+        export_csv::_25_nft_gains_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map
+        )?;
+
+        timings.record("Export: C25_NFT_gains", start);
+    }
+
+    if includes(reports, Report::JournalEntries) {
+
+        let start = Instant::now();
+
+        export_je::prepare_journal_entries(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        timings.record("Export: J1_Journal_Entries", start);
+    }
+
+    Ok(())
+}
@@ -1,24 +1,311 @@
 // Copyright (c) 2017-2023, scoobybejesus;
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::sync::{Mutex, OnceLock};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use chrono::NaiveDate;
 
-use crptls::transaction::{ActionRecord, Polarity, Transaction, TxType};
-use crptls::account::{Account, RawAccount, Term};
-use crptls::core_functions::ImportProcessParameters;
+use crptls::transaction::{ActionRecord, Polarity, Transaction, TxKind, TxType};
+use crptls::account::{lot_id, Account, RawAccount, Term};
+use crptls::core_functions::{ImportProcessParameters, TableFormat};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::decimal_utils::{OutputPrecision, ReportLocale};
+use crptls::run_state::RunState;
+use crptls::balance_history::{self, BalanceHistory, BalanceInterval};
+use crptls::spot_prices;
+use crptls::summary;
+use crptls::derivatives;
+use crptls::fiat_costs;
+use crptls::nfts;
+use crptls::non_sale_disposals;
+use crptls::reconciliation;
+use crptls::dedupe;
+use crptls::needs_review;
+use crptls::tags;
+
+use crate::export::export_8949;
+
+
+/// The process's original standard output, once `reserve_stdout_for_report()` has pointed standard output at standard
+/// error instead.
+static REPORT_STDOUT: OnceLock<File> = OnceLock::new();
+
+/// The path of every report file written in the run, in the order first written, for the manifest.
+static WRITTEN_REPORTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Each report file being written in the run, not yet moved into place: its partial file (see `partial_path()`),
+/// and its path.
+static PENDING_REPORTS: Mutex<Vec<(PathBuf, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Notes that a report was written to `path` (see `written_reports()`).
+pub fn record_written_report(path: &Path) {
+    let mut written = WRITTEN_REPORTS.lock().unwrap();
+    if !written.iter().any(|written_path| written_path == path) {
+        written.push(path.to_path_buf());
+    }
+}
+
+/// The path of every report file written so far in the run, in the order first written.
+pub fn written_reports() -> Vec<PathBuf> {
+    WRITTEN_REPORTS.lock().unwrap().clone()
+}
+
+/// An error if `path` is a file left by an earlier run, which a report may only replace with `--overwrite`.  A file
+/// written earlier in this run (i.e., a report printed again from the print menu) may be replaced.
+pub fn check_overwrite(settings: &ImportProcessParameters, path: &Path) -> io::Result<()> {
+
+    let written_this_run = WRITTEN_REPORTS.lock().unwrap().iter().any(|written_path| written_path == path);
+
+    if !settings.overwrite && !written_this_run && path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists. Pass --overwrite to replace it, or --run-subdir to export to a new \
+                subdirectory of the output directory.", path.display()),
+        ))
+    }
+
+    Ok(())
+}
+
+/// Creates the report file at `path` (see `check_overwrite()`), noting it for the manifest.  It's written as its
+/// partial file, which is only moved to `path` by `commit_written_reports()`, once every report of the run has been
+/// written, so that a run that fails midway leaves the output directory as it was.
+pub fn create_report_file(settings: &ImportProcessParameters, path: &Path) -> io::Result<File> {
+
+    check_overwrite(settings, path)?;
+
+    let partial = partial_path(path);
+    let file = File::create(&partial)?;
+
+    let mut pending = PENDING_REPORTS.lock().unwrap();
+    if !pending.iter().any(|(_partial, pending_path)| pending_path == path) {
+        pending.push((partial, path.to_path_buf()));
+    }
+    drop(pending);
+
+    record_written_report(path);
+
+    Ok(file)
+}
+
+/// Where the report at `path` is written until it's complete (i.e., C1_Acct_Sum_with_cost_basis.csv.partial), so
+/// that a file left by a run that was killed is plainly not a report.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".partial");
+    path.with_file_name(file_name)
+}
+
+/// Moves each report written since the last commit from its partial file to its path (each by a rename, so that no
+/// report is ever seen half-written), replacing any earlier file there.  An earlier file is first moved aside (see
+/// `replaced_path()`), and only removed once every report is in place.  If one can't be moved, the reports already
+/// moved are taken back out, the earlier files are put back and the partial files of the rest are removed, so the
+/// output directory is left as it was.
+pub fn commit_written_reports() -> io::Result<()> {
+
+    let pending = std::mem::take(&mut *PENDING_REPORTS.lock().unwrap());
+
+    //  Each report moved into place, and whether an earlier file at its path was moved aside for it
+    let mut committed: Vec<(&Path, bool)> = Vec::with_capacity(pending.len());
+
+    for (idx, (partial, path)) in pending.iter().enumerate() {
+
+        let replaced = path.exists();
+
+        let result = if replaced { fs::rename(path, replaced_path(path)) } else { Ok(()) }
+            .and_then(|_| fs::rename(partial, path).inspect_err(|_| {
+                if replaced { let _ = fs::rename(replaced_path(path), path); }
+            }));
+
+        if let Err(e) = result {
+            for (path, replaced) in committed.iter().rev() {
+                let _ = fs::remove_file(path);
+                if *replaced { let _ = fs::rename(replaced_path(path), path); }
+            }
+            for (partial, _path) in pending[idx..].iter() { let _ = fs::remove_file(partial); }
+            return Err(io::Error::new(e.kind(), format!("Couldn't move {} into place ({}), so none of the reports \
+                were: the output directory is as it was.", path.display(), e)))
+        }
+
+        committed.push((path, replaced));
+    }
+
+    for (path, replaced) in committed {
+        if replaced { let _ = fs::remove_file(replaced_path(path)); }
+    }
+
+    Ok(())
+}
+
+/// Where an earlier file at the report's `path` is moved while the reports are committed (i.e.,
+/// C1_Acct_Sum_with_cost_basis.csv.replaced), to be put back if they can't all be.
+fn replaced_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".replaced");
+    path.with_file_name(file_name)
+}
+
+/// Removes the partial file of each report written since the last commit, as when the run has failed, so that
+/// neither they nor a mix of stale and fresh reports are left in the output directory.
+pub fn discard_pending_reports() {
+    for (partial, _path) in std::mem::take(&mut *PENDING_REPORTS.lock().unwrap()).iter() {
+        let _ = fs::remove_file(partial);
+    }
+}
+
+/// Whether any of the movements of `txn` belong in the reports (see `ImportProcessParameters::is_reported_account()`),
+/// for a report of whole transactions, such as the journal entries.
+pub fn is_reported_txn(
+    settings: &ImportProcessParameters,
+    txn: &Transaction,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+) -> bool {
+    txn.action_record_idx_vec.iter().any(|ar_num| {
+        let acct = acct_map.get(&ars.get(ar_num).unwrap().account_key).unwrap();
+        settings.is_reported_account(raw_acct_map.get(&acct.raw_key).unwrap())
+    })
+}
+
+/// Keeps standard output for the `--stdout` report alone, by pointing it at standard error, so that everything else
+/// the program prints goes there instead.
+#[cfg(unix)]
+pub fn reserve_stdout_for_report() -> io::Result<()> {
+
+    use std::os::fd::AsFd;
+
+    io::stdout().flush()?;
+    let original = io::stdout().as_fd().try_clone_to_owned()?;
+
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } == -1 {
+        return Err(io::Error::last_os_error())
+    }
+
+    let _ = REPORT_STDOUT.set(File::from(original));
+
+    Ok(())
+}
+
+/// Standard output can't be pointed at standard error but on Unix, so `--stdout` is an error elsewhere, rather than
+/// a report mixed in with everything else the program prints.
+#[cfg(not(unix))]
+pub fn reserve_stdout_for_report() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--stdout is only available on Unix."))
+}
+
+/// Where to write the report `file_name`: a new file of that name (see `report_file_name()`) in the export directory, or, with `--stdout`,
+/// standard output for the chosen report (whose file name begins with `settings.stdout_report`), and nowhere for any
+/// other.
+pub fn report_writer(settings: &ImportProcessParameters, file_name: &str) -> io::Result<Box<dyn Write>> {
+
+    match &settings.stdout_report {
+        None => {
+            let full_path: PathBuf = [settings.export_path.clone(), PathBuf::from(settings.report_file_name(file_name))]
+                .iter().collect();
+            let file = create_report_file(settings, &full_path)?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        Some(prefix) if file_name.starts_with(prefix.as_str()) => match REPORT_STDOUT.get() {
+            Some(stdout) => Ok(Box::new(BufWriter::new(stdout.try_clone()?))),
+            None => Ok(Box::new(io::stdout())),
+        },
+        Some(_) => Ok(Box::new(io::sink())),
+    }
+}
+
+/// Writes the `rows` to `file_name` in the export directory (see `report_writer()`). If `--excel-bom` was passed, a
+/// file begins with a UTF-8 byte order mark so that Excel detects the encoding (otherwise, non-ASCII characters may
+/// appear garbled).  With another `TableFormat`, the rows are written in that format instead, under its extension.
+pub fn write_rows_to_csv(settings: &ImportProcessParameters, file_name: &str, rows: &[Vec<String>]) -> Result<(), Box<dyn Error>> {
 
+    let file_name = &settings.table_format.file_name(file_name);
+
+    let couldnt_write = |e: &dyn Error| format!("Couldn't write {}: {}", file_name, e);
+
+    let mut buffer = report_writer(settings, file_name).map_err(|e| couldnt_write(&e))?;
+
+    match settings.table_format {
+        TableFormat::Csv => {}
+        TableFormat::Txt => {
+            buffer.write_all(rows_to_text_table(rows).as_bytes()).map_err(|e| couldnt_write(&e))?;
+            buffer.flush().map_err(|e| couldnt_write(&e))?;
+            return Ok(())
+        }
+        TableFormat::Json => {
+            serde_json::to_writer_pretty(&mut buffer, &rows_to_json(rows)).map_err(|e| couldnt_write(&e))?;
+            buffer.flush().map_err(|e| couldnt_write(&e))?;
+            return Ok(())
+        }
+    }
+
+    if settings.excel_bom && settings.stdout_report.is_none() {
+        buffer.write_all(b"\xEF\xBB\xBF").map_err(|e| couldnt_write(&e))?;
+    }
+
+    let mut wtr = csv::Writer::from_writer(buffer);
+
+    for row in rows.iter() {
+        wtr.write_record(row).map_err(|e| couldnt_write(&e))?;
+    }
+    wtr.flush().map_err(|e| couldnt_write(&e))?;
+
+    Ok(())
+}
+
+/// The `rows` as a text table, each column padded to its widest cell, with a rule beneath the header row.
+fn rows_to_text_table(rows: &[Vec<String>]) -> String {
+
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| rows.iter().filter_map(|row| row.get(col)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let line = |row: &Vec<String>| -> String {
+        let cells: Vec<String> = widths.iter().enumerate()
+            .map(|(col, width)| format!("{:<width$}", row.get(col).map(|cell| cell.as_str()).unwrap_or(""), width = width))
+            .collect();
+        cells.join("  ").trim_end().to_string() + "\n"
+    };
+
+    let mut table = String::new();
+
+    for (idx, row) in rows.iter().enumerate() {
+        table.push_str(&line(row));
+        if idx == 0 {
+            table.push_str(&(widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<String>>().join("  ") + "\n"));
+        }
+    }
+
+    table
+}
+
+/// The `rows` after the header row as JSON objects keyed by the header (or by the column's number, where a row has
+/// more cells than the header).
+fn rows_to_json(rows: &[Vec<String>]) -> serde_json::Value {
+
+    let Some((header, rows)) = rows.split_first() else { return serde_json::Value::Array(Vec::new()) };
+
+    serde_json::Value::Array(rows.iter().map(|row| {
+        serde_json::Value::Object(row.iter().enumerate().map(|(col, cell)| {
+            let key = header.get(col).filter(|key| !key.is_empty()).cloned().unwrap_or_else(|| (col + 1).to_string());
+            (key, serde_json::Value::String(cell.clone()))
+        }).collect())
+    }).collect())
+}
 
 pub fn _1_account_sums_to_csv(
     settings: &ImportProcessParameters,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>
-) {
+) -> Result<(), Box<dyn Error>> {
 
     let mut rows: Vec<Vec<String>> = Vec::with_capacity(acct_map.len());
 
@@ -48,21 +335,14 @@ pub fn _1_account_sums_to_csv(
         let tentative_balance = acct.get_sum_of_amts_in_lots();
 
         if tentative_balance == dec!(0) {
-            balance = "0.00".to_string()
-        } else { balance = tentative_balance.to_string() }
+            balance = settings.output_precision.fiat(dec!(0))
+        } else { balance = settings.output_precision.crypto(tentative_balance) }
 
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-        let lk_cost_basis: String;
-
-        if raw_acct.is_margin { lk_cost_basis = "0.00".to_string() } else {
-            let tentative_lk_cost_basis = acct.get_sum_of_lk_basis_in_lots();
-            if tentative_lk_cost_basis == dec!(0) {
-                lk_cost_basis = "0.00".to_string()
-            } else { lk_cost_basis = tentative_lk_cost_basis.to_string() }
-        }
+        if !settings.is_reported_account(raw_acct) { continue }
+        let lk_cost_basis = if raw_acct.is_margin { dec!(0) } else { acct.get_sum_of_lk_basis_in_lots() };
 
-        let cb_f32 = lk_cost_basis.as_str().parse::<f32>().unwrap();
-        let cb = format!("{:.2}", cb_f32);
+        let cb = settings.output_precision.fiat(lk_cost_basis);
 
         let nonzero_lots = acct.get_num_of_nonzero_lots();
 
@@ -74,24 +354,16 @@ pub fn _1_account_sums_to_csv(
         row.push(nonzero_lots.to_string());
         rows.push(row);
     }
-    let file_name = PathBuf::from("C1_Acct_Sum_with_cost_basis.csv");
-    let path = PathBuf::from(&settings.export_path.clone());
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
+    write_rows_to_csv(settings, "C1_Acct_Sum_with_cost_basis.csv", &rows)?;
 
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    Ok(())
 }
 
 pub fn _2_account_sums_nonzero_to_csv(
     settings: &ImportProcessParameters,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>,
-) {
+) -> Result<(), Box<dyn Error>> {
 
     let mut rows: Vec<Vec<String>> = Vec::with_capacity(acct_map.len());    //  more than needed...
 
@@ -118,6 +390,7 @@ pub fn _2_account_sums_nonzero_to_csv(
         let mut row: Vec<String> = Vec::with_capacity(total_columns);
 
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        if !settings.is_reported_account(raw_acct) { continue }
         let name = raw_acct.name.to_string();
 
         let balance: String;
@@ -125,20 +398,12 @@ pub fn _2_account_sums_nonzero_to_csv(
         let tentative_balance = acct.get_sum_of_amts_in_lots();
 
         if tentative_balance == dec!(0) {
-            balance = "0.00".to_string()
-        } else { balance_d128 += tentative_balance; balance = tentative_balance.to_string() }
-
-        let lk_cost_basis: String;
+            balance = settings.output_precision.fiat(dec!(0))
+        } else { balance_d128 += tentative_balance; balance = settings.output_precision.crypto(tentative_balance) }
 
-        if raw_acct.is_margin { lk_cost_basis = "0.00".to_string() } else {
-            let tentative_lk_cost_basis = acct.get_sum_of_lk_basis_in_lots();
-            if tentative_lk_cost_basis == dec!(0) {
-                lk_cost_basis = "0.00".to_string()
-            } else { lk_cost_basis = tentative_lk_cost_basis.to_string() }
-        }
+        let lk_cost_basis = if raw_acct.is_margin { dec!(0) } else { acct.get_sum_of_lk_basis_in_lots() };
 
-        let cb_f32 = lk_cost_basis.as_str().parse::<f32>().unwrap();
-        let cb = format!("{:.2}", cb_f32);
+        let cb = settings.output_precision.fiat(lk_cost_basis);
 
         let nonzero_lots = acct.get_num_of_nonzero_lots();
 
@@ -153,24 +418,16 @@ pub fn _2_account_sums_nonzero_to_csv(
         }
     }
 
-    let file_name = PathBuf::from("C2_Acct_Sum_with_nonzero_cost_basis.csv");
-    let path = PathBuf::from(&settings.export_path.clone());
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
+    write_rows_to_csv(settings, "C2_Acct_Sum_with_nonzero_cost_basis.csv", &rows)?;
 
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    Ok(())
 }
 
 pub fn _3_account_sums_to_csv_with_orig_basis(
     settings: &ImportProcessParameters,
     raw_acct_map: &HashMap<u16, RawAccount>,
     acct_map: &HashMap<u16, Account>
-) {
+) -> Result<(), Box<dyn Error>> {
 
     let mut rows: Vec<Vec<String>> = Vec::with_capacity(acct_map.len());
 
@@ -201,36 +458,17 @@ pub fn _3_account_sums_to_csv_with_orig_basis(
         let tentative_balance = acct.get_sum_of_amts_in_lots();
 
         if tentative_balance == dec!(0) {
-            balance = "0.00".to_string()
-        } else { balance = tentative_balance.to_string() }
+            balance = settings.output_precision.fiat(dec!(0))
+        } else { balance = settings.output_precision.crypto(tentative_balance) }
 
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-        let lk_cost_basis: String;
-        let orig_cost_basis: String;
-
-        if raw_acct.is_margin {
+        if !settings.is_reported_account(raw_acct) { continue }
+        let (lk_cost_basis, orig_cost_basis) = if raw_acct.is_margin { (dec!(0), dec!(0)) } else {
+            (acct.get_sum_of_lk_basis_in_lots(), acct.get_sum_of_orig_basis_in_lots())
+        };
 
-            lk_cost_basis = "0.00".to_string();
-            orig_cost_basis = "0.00".to_string();
-        } else {
-
-            let tentative_lk_cost_basis = acct.get_sum_of_lk_basis_in_lots();
-            let tentative_orig_cost_basis = acct.get_sum_of_orig_basis_in_lots();
-
-            if tentative_lk_cost_basis == dec!(0) {
-                lk_cost_basis = "0.00".to_string()
-            } else { lk_cost_basis = tentative_lk_cost_basis.to_string() }
-
-            if tentative_orig_cost_basis == dec!(0) {
-                orig_cost_basis = "0.00".to_string()
-            } else { orig_cost_basis = tentative_orig_cost_basis.to_string() }
-        }
-
-        let cb_f32 = lk_cost_basis.to_string().as_str().parse::<f32>().unwrap();
-        let cb = format!("{:.2}", cb_f32);
-
-        let ocb_f32 = orig_cost_basis.as_str().parse::<f32>().unwrap();
-        let ocb = format!("{:.2}", ocb_f32);
+        let cb = settings.output_precision.fiat(lk_cost_basis);
+        let ocb = settings.output_precision.fiat(orig_cost_basis);
 
         let nonzero_lots = acct.get_num_of_nonzero_lots();
 
@@ -243,17 +481,9 @@ pub fn _3_account_sums_to_csv_with_orig_basis(
         row.push(nonzero_lots.to_string());
         rows.push(row);
     }
-    let file_name = PathBuf::from("C3_Acct_Sum_with_orig_and_lk_cost_basis.csv");
-    let path = PathBuf::from(&settings.export_path.clone());
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
+    write_rows_to_csv(settings, "C3_Acct_Sum_with_orig_and_lk_cost_basis.csv", &rows)?;
 
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    Ok(())
 }
 
 pub fn _4_transaction_mvmt_detail_to_csv(
@@ -279,6 +509,7 @@ pub fn _4_transaction_mvmt_detail_to_csv(
         "Gain/loss".to_string(),
         "Income".to_string(),
         "Expense".to_string(),
+        "Lot ID".to_string(),
     ];
 
     let total_columns = columns.len();
@@ -294,6 +525,8 @@ pub fn _4_transaction_mvmt_detail_to_csv(
         let txn_num = txn_num as u32;
         let txn = txns_map.get(&(txn_num)).unwrap();
 
+        if !settings.is_reported_txn(txn) { continue }
+
         let flow_or_outgoing_exchange_movements = txn.get_outgoing_exchange_and_flow_mvmts(
             &settings.home_currency,
             ars,
@@ -306,8 +539,9 @@ pub fn _4_transaction_mvmt_detail_to_csv(
             let lot = mvmt.get_lot(acct_map, ars);
             let acct = acct_map.get(&lot.account_key).unwrap();
             let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+            if !settings.is_reported_account(raw_acct) { continue }
 
-            let date = txn.date.to_string();
+            let date = settings.report_date(txn.date);
             let tx_number = txn.tx_number.to_string();
             let tx_type = txn.transaction_type(&ars, &raw_acct_map, &acct_map)?;
             let tx_type_string = mvmt.friendly_tx_type(&tx_type);
@@ -315,7 +549,7 @@ pub fn _4_transaction_mvmt_detail_to_csv(
             let mut amount = dec!(0);
             amount += mvmt.amount;   //  To prevent printing -5E+1 instead of 50, for example
             let ticker = raw_acct.ticker.to_string();
-            let term = mvmt.get_term(acct_map, ars, txns_map).to_string();
+            let term = mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).to_string();
             let mut proceeds_lk = mvmt.proceeds_lk.get();
             let mut cost_basis_lk = mvmt.cost_basis_lk.get();
             let mut gain_loss = mvmt.get_lk_gain_or_loss();
@@ -335,29 +569,20 @@ pub fn _4_transaction_mvmt_detail_to_csv(
             row.push(tx_number.to_string());
             row.push(tx_type_string);
             row.push(memo);
-            row.push(amount.to_string());
+            row.push(settings.output_precision.crypto(amount));
             row.push(ticker);
             row.push(term);
-            row.push(proceeds_lk.to_string());
-            row.push(cost_basis_lk.to_string());
-            row.push(gain_loss.to_string());
-            row.push(income.to_string());
-            row.push(expense.to_string());
+            row.push(settings.output_precision.fiat(proceeds_lk));
+            row.push(settings.output_precision.fiat(cost_basis_lk));
+            row.push(settings.output_precision.fiat(gain_loss));
+            row.push(settings.output_precision.fiat(income));
+            row.push(settings.output_precision.fiat(expense));
+            row.push(lot.id());
             rows.push(row);
         }
     }
 
-    let file_name = PathBuf::from("C4_Txns_mvmts_detail.csv");
-    let path = PathBuf::from(&settings.export_path);
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
-
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C4_Txns_mvmts_detail.csv"), &rows)?;
 
     Ok(())
 }
@@ -401,7 +626,9 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
 
         let txn_num = txn_num as u32;
         let txn = txns_map.get(&(txn_num)).unwrap();
-        let txn_date_string = txn.date.to_string();
+
+        if !settings.is_reported_txn(txn) { continue }
+        let txn_date_string = settings.report_date(txn.date);
         let tx_num_string = txn.tx_number.to_string();
         let tx_type = txn.transaction_type(ars, &raw_acct_map, &acct_map)?;
         let tx_memo_string = txn.user_memo.to_string();
@@ -413,6 +640,7 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
         let mut amount_st = dec!(0);
         let mut proceeds_st = dec!(0);
         let mut cost_basis_st = dec!(0);
+        let mut adjustment_st = dec!(0);   //  Wash sale loss disallowed
 
         let mut income_st = dec!(0);
         let mut expense_st = dec!(0);
@@ -420,6 +648,7 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
         let mut amount_lt = dec!(0);
         let mut proceeds_lt = dec!(0);
         let mut cost_basis_lt = dec!(0);
+        let mut adjustment_lt = dec!(0);
 
         let mut income_lt = dec!(0);
         let mut expense_lt = dec!(0);
@@ -437,6 +666,7 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
             let lot = mvmt.get_lot(acct_map, ars);
             let acct = acct_map.get(&lot.account_key).unwrap();
             let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+            if !settings.is_reported_account(raw_acct) { continue }
 
             if count == 0 { tx_type_string = mvmt.friendly_tx_type(&tx_type) };
             count += 1;
@@ -450,18 +680,20 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
                 };
             }
 
-            let term = mvmt.get_term(acct_map, ars, txns_map);
+            let term = mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days);
 
             if term == Term::LT {
                 amount_lt += mvmt.amount;
                 proceeds_lt += mvmt.proceeds_lk.get();
                 cost_basis_lt += mvmt.cost_basis_lk.get();
+                adjustment_lt += mvmt.wash_sale_adjustment.get();
                 if term_lt.is_none() { term_lt = Some(term) }
             } else {
                 assert_eq!(term, Term::ST);
                 amount_st += mvmt.amount;
                 proceeds_st += mvmt.proceeds_lk.get();
                 cost_basis_st += mvmt.cost_basis_lk.get();
+                adjustment_st += mvmt.wash_sale_adjustment.get();
                 if term_st.is_none() {
                     term_st = Some(term);
                 }
@@ -476,9 +708,11 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
             income_st = -proceeds_st;   //  Proceeds are negative for incoming txns
             proceeds_st = dec!(0);
             cost_basis_st = dec!(0);
+            adjustment_st = dec!(0);
             income_lt = -proceeds_lt;   //  Proceeds are negative for incoming txns
             proceeds_lt = dec!(0);
             cost_basis_lt = dec!(0);
+            adjustment_lt = dec!(0);
         }
 
         if (txn.transaction_type(
@@ -498,14 +732,14 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
             row.push(tx_num_string.clone());
             row.push(tx_type_string.clone());
             row.push(tx_memo_string.clone());
-            row.push(amount_st.to_string());
+            row.push(settings.output_precision.crypto(amount_st));
             row.push(ticker.clone().unwrap());
             row.push(term.abbr_string());
-            row.push(proceeds_st.to_string());
-            row.push(cost_basis_st.to_string());
-            row.push((proceeds_st + cost_basis_st).to_string());
-            row.push(income_st.to_string());
-            row.push(expense_st.to_string());
+            row.push(settings.output_precision.fiat(proceeds_st));
+            row.push(settings.output_precision.fiat(cost_basis_st));
+            row.push(settings.output_precision.fiat(proceeds_st + cost_basis_st + adjustment_st));
+            row.push(settings.output_precision.fiat(income_st));
+            row.push(settings.output_precision.fiat(expense_st));
 
             rows.push(row);
         }
@@ -517,30 +751,20 @@ pub fn _5_transaction_mvmt_summaries_to_csv(
             row.push(tx_num_string);
             row.push(tx_type_string.clone());
             row.push(tx_memo_string);
-            row.push(amount_lt.to_string());
+            row.push(settings.output_precision.crypto(amount_lt));
             row.push(ticker.unwrap());
             row.push(term.abbr_string());
-            row.push(proceeds_lt.to_string());
-            row.push(cost_basis_lt.to_string());
-            row.push((proceeds_lt + cost_basis_lt).to_string());
-            row.push(income_lt.to_string());
-            row.push(expense_lt.to_string());
+            row.push(settings.output_precision.fiat(proceeds_lt));
+            row.push(settings.output_precision.fiat(cost_basis_lt));
+            row.push(settings.output_precision.fiat(proceeds_lt + cost_basis_lt + adjustment_lt));
+            row.push(settings.output_precision.fiat(income_lt));
+            row.push(settings.output_precision.fiat(expense_lt));
 
             rows.push(row);
         }
     }
 
-    let file_name = PathBuf::from("C5_Txns_mvmts_summary.csv");
-    let path = PathBuf::from(&settings.export_path);
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
-
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C5_Txns_mvmts_summary.csv"), &rows)?;
 
     Ok(())
 }
@@ -579,10 +803,11 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
         "Orig. Gain/loss".to_string(),
     ];
 
+    //  The lot ID comes last, so that the columns before it are where they always were
     let total_columns = if lk {
-        columns.len() + lk_columns.len()
+        columns.len() + lk_columns.len() + 1
     } else {
-        columns.len()
+        columns.len() + 1
     };
 
     let mut header: Vec<String> = Vec::with_capacity(total_columns);
@@ -592,6 +817,7 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
     if lk {
         header.extend_from_slice(&lk_columns)
     }
+    header.push("Lot ID".to_string());
     rows.push(header);
 
     let length = txns_map.len();
@@ -601,6 +827,8 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
         let txn_num = txn_num as u32;
         let txn = txns_map.get(&(txn_num)).unwrap();
 
+        if !settings.is_reported_txn(txn) { continue }
+
         let flow_or_outgoing_exchange_movements = txn.get_outgoing_exchange_and_flow_mvmts(
             &settings.home_currency,
             ars,
@@ -613,8 +841,9 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
             let lot = mvmt.get_lot(acct_map, ars);
             let acct = acct_map.get(&lot.account_key).unwrap();
             let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+            if !settings.is_reported_account(raw_acct) { continue }
 
-            let date = txn.date.to_string();
+            let date = settings.report_date(txn.date);
             let tx_number = txn.tx_number.to_string();
             let tx_type = txn.transaction_type(&ars, &raw_acct_map, &acct_map)?;
             let tx_type_string = mvmt.friendly_tx_type(&tx_type);
@@ -623,7 +852,7 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
             let mut amount = dec!(0);
             amount += mvmt.amount;   //  To prevent printing -5E+1 instead of 50, for example
             let ticker = raw_acct.ticker.to_string();
-            let term = mvmt.get_term(acct_map, ars, txns_map).to_string();
+            let term = mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).to_string();
             let mut proceeds_lk = mvmt.proceeds_lk.get();
             let mut cost_basis_lk = mvmt.cost_basis_lk.get();
             let mut gain_loss = mvmt.get_lk_gain_or_loss();
@@ -649,34 +878,25 @@ pub fn _6_transaction_mvmt_detail_to_csv_w_orig(
             row.push(tx_type_string);
             row.push(user_memo);
             row.push(auto_memo);
-            row.push(amount.to_string());
+            row.push(settings.output_precision.crypto(amount));
             row.push(ticker);
             row.push(term);
-            row.push(proceeds_lk.to_string());
-            row.push(cost_basis_lk.to_string());
-            row.push(gain_loss.to_string());
-            row.push(income.to_string());
-            row.push(expense.to_string());
+            row.push(settings.output_precision.fiat(proceeds_lk));
+            row.push(settings.output_precision.fiat(cost_basis_lk));
+            row.push(settings.output_precision.fiat(gain_loss));
+            row.push(settings.output_precision.fiat(income));
+            row.push(settings.output_precision.fiat(expense));
             if lk {
-                row.push(orig_proc.to_string());
-                row.push(orig_cost.to_string());
-                row.push(orig_gain_loss.to_string());
+                row.push(settings.output_precision.fiat(orig_proc));
+                row.push(settings.output_precision.fiat(orig_cost));
+                row.push(settings.output_precision.fiat(orig_gain_loss));
             }
+            row.push(lot.id());
             rows.push(row);
         }
     }
 
-    let file_name = PathBuf::from("C6_Txns_mvmts_more_detail.csv");
-    let path = PathBuf::from(&settings.export_path);
-
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
-
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
-    }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C6_Txns_mvmts_more_detail.csv"), &rows)?;
 
     Ok(())
 }
@@ -708,164 +928,2329 @@ pub fn _7_gain_loss_8949_to_csv(
     header.extend_from_slice(&columns);
     rows.push(header);
 
-    let length = txns_map.len();
+    // The account each row's disposal came out of, for splitting the report by account
+    let mut row_accts: Vec<u16> = Vec::new();
 
-    for txn_num in 1..=length {
+    for line in export_8949::form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)? {
 
-        let txn_num = txn_num as u32;
-        let txn = txns_map.get(&(txn_num)).unwrap();
-        let txn_date_string = txn.date.to_string();
-        let tx_num_string = txn.tx_number.to_string();
-        let tx_memo_string = txn.get_auto_memo(ars,raw_acct_map,acct_map, &settings.home_currency)?;
+        let mut row: Vec<String> = Vec::with_capacity(total_columns);
 
-        let mut term_st: Option<Term> = None;
-        let mut term_lt: Option<Term> = None;
-        let mut ticker: Option<String> = None;
-        let mut polarity: Option<Polarity> = None;
+        row.push(line.term.abbr_string());
+        row.push(line.txn_nums.clone());
+        row.push(line.memo.clone());
+        row.push(line.amount.map_or("Various".to_string(), |amount| settings.output_precision.crypto(amount)));
+        row.push(export_8949::report_date_acquired(settings, &line));
+        row.push(settings.report_date(line.date_sold));
+        row.push(settings.output_precision.fiat(line.proceeds));
+        row.push(settings.output_precision.fiat(line.cost_basis));
+        row.push(settings.output_precision.fiat(line.gain_or_loss()));
 
-        let mut amount_st = dec!(0);
-        let mut proceeds_st = dec!(0);
-        let mut cost_basis_st = dec!(0);
+        rows.push(row);
+        row_accts.push(line.acct_key);
+    }
 
-        let mut expense_st = dec!(0);
+    if settings.form_8949_per_account {
+        write_8949_per_account(settings, raw_acct_map, acct_map, &rows, &row_accts)?;
+    } else {
+        write_rows_to_csv(settings, &settings.tax_year_file_name("C7_Form_8949.csv"), &rows)?;
+    }
 
-        let mut amount_lt = dec!(0);
-        let mut proceeds_lt = dec!(0);
-        let mut cost_basis_lt = dec!(0);
+    Ok(())
+}
 
-        let mut expense_lt = dec!(0);
+/// Writes the Form 8949 `rows` (whose first row is the header) as separate short-term and long-term files for
+/// each account, or for each exchange if accounts are grouped by exchange label.  Accounts without disposals
+/// (in a given term) get no file.
+fn write_8949_per_account(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    rows: &[Vec<String>],
+    row_accts: &[u16],
+) -> Result<(), Box<dyn Error>> {
 
-        let flow_or_outgoing_exchange_movements = txn.get_outgoing_exchange_and_flow_mvmts(
-            &settings.home_currency,
-            ars,
-            raw_acct_map,
-            acct_map,
-            txns_map
-        )?;
+    let mut labels: Vec<String> = Vec::new();
 
-        let mut purchase_date_lt: NaiveDate = NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap();
-        let mut purchase_date_st: NaiveDate = NaiveDate::parse_from_str("1-1-1", "%y-%m-%d").unwrap();
-        let mut various_dates_lt: bool = false;
-        let mut various_dates_st: bool = false;
-        let mut lt_set = false;
-        let mut st_set = false;
-        for mvmt in flow_or_outgoing_exchange_movements.iter() {
-            let lot = mvmt.get_lot(acct_map, ars);
-            let acct = acct_map.get(&lot.account_key).unwrap();
-            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+    for acct_key in row_accts.iter() {
+        let raw_acct = raw_acct_map.get(&acct_map.get(acct_key).unwrap().raw_key).unwrap();
+        let label = raw_acct.exchange_label(settings.exchange_label_separator.as_deref());
+        if !labels.contains(&label) { labels.push(label) }
+    }
 
-            if ticker.is_none() { ticker = Some(raw_acct.ticker.clone()) };
+    for label in labels.iter() {
 
-            if polarity.is_none() {
-                polarity = if mvmt.amount > dec!(0) {
-                    Some(Polarity::Incoming)
-                    } else { Some(Polarity::Outgoing)
-                };
-            }
+        for term in [Term::ST, Term::LT] {
 
-            fn dates_are_different(existing: &NaiveDate, current: &NaiveDate) -> bool {
-                if existing != current {true} else {false}
-            }
+            let mut term_rows: Vec<Vec<String>> = vec![rows[0].clone()];
 
-            let term = mvmt.get_term(acct_map, ars, txns_map);
+            for (row, acct_key) in rows[1..].iter().zip(row_accts.iter()) {
+                let raw_acct = raw_acct_map.get(&acct_map.get(acct_key).unwrap().raw_key).unwrap();
+                if row[0] == term.abbr_string() && &raw_acct.exchange_label(settings.exchange_label_separator.as_deref()) == label {
+                    term_rows.push(row.clone());
+                }
+            }
 
-            if term == Term::LT {
-                if lt_set {} else { purchase_date_lt = lot.date_for_basis_purposes; lt_set = true }
-                various_dates_lt = dates_are_different(&purchase_date_lt, &lot.date_for_basis_purposes);
+            if term_rows.len() == 1 { continue }
 
-                amount_lt += mvmt.amount;
-                proceeds_lt += mvmt.proceeds_lk.get();
-                cost_basis_lt += mvmt.cost_basis_lk.get();
+            let file_label: String = label.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            let file_name = format!("C7_Form_8949_{}_{}.csv", file_label, term.abbr_string());
 
-                if term_lt.is_none() { term_lt = Some(term) }
+            write_rows_to_csv(settings, &settings.tax_year_file_name(&file_name), &term_rows)?;
+        }
+    }
 
-            } else {
-                if st_set {} else { purchase_date_st = lot.date_for_basis_purposes; st_set = true}
-                various_dates_st = dates_are_different(&purchase_date_st, &lot.date_for_basis_purposes);
+    Ok(())
+}
 
-                assert_eq!(term, Term::ST);
-                amount_st += mvmt.amount;
-                proceeds_st += mvmt.proceeds_lk.get();
-                cost_basis_st += mvmt.cost_basis_lk.get();
+pub fn _8_open_lot_tax_impact_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    spot_prices: &HashMap<String, Decimal>,
+    as_of_date: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
 
-                if term_st.is_none() {
-                    term_st = Some(term);
-                }
-            }
-        }
-        let lt_purchase_date = if various_dates_lt { "Various".to_string() } else { purchase_date_lt.to_string() };
-        let st_purchase_date = if various_dates_st { "Various".to_string() } else { purchase_date_st.to_string() };
+    let mut rows: Vec<Vec<String>> = [].to_vec();
 
-        if (txn.transaction_type(
-            ars,
-            &raw_acct_map,
-            &acct_map)? == TxType::Flow
-        ) & (polarity == Some(Polarity::Incoming)) {
-            // The only incoming flow transaction to report would be margin profit, which is a dual-`action record` `transaction`
-            if txn.action_record_idx_vec.len() == 2 {
-                proceeds_st = -proceeds_st;   //  Proceeds are negative for incoming txns
-                cost_basis_st = dec!(0);
-                proceeds_lt = -proceeds_lt;   //  Proceeds are negative for incoming txns
-                cost_basis_lt = dec!(0);
-            } else {
-                continue    // Plain, old income isn't reported on form 8949
-            }
-        }
+    let columns = [
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Lot ID".to_string(),
+        "Basis date".to_string(),
+        "Amount".to_string(),
+        "Cost basis".to_string(),
+        "Spot price".to_string(),
+        "Market value".to_string(),
+        "Unrealized gain/loss".to_string(),
+        "Term if sold today".to_string(),
+        "Days until LT".to_string(),
+    ];
 
-        if (txn.transaction_type(
-            ars,
-            &raw_acct_map,
-            &acct_map)? == TxType::Flow
-        ) & (polarity == Some(Polarity::Outgoing)) {
-            expense_st -= proceeds_st;
-            expense_lt -= proceeds_lt;
-        }
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
 
-        if let Some(term) = term_st {
+    let (impacts, unpriced) = spot_prices::open_lot_impacts(
+        spot_prices,
+        as_of_date,
+        &settings.home_currency,
+        settings.long_term_days,
+        raw_acct_map,
+        acct_map,
+    );
+
+    for ticker in unpriced.iter() {
+        log::warn!("WARNING: No spot price for {}. Its open lots are omitted from the spot-price reports (C8 and C12).", ticker);
+    }
 
-            let mut row: Vec<String> = Vec::with_capacity(total_columns);
+    for impact in impacts.iter() {
 
-            row.push(term.abbr_string());
-            row.push(tx_num_string.clone());
-            row.push(tx_memo_string.clone());
-            row.push(amount_st.to_string());
-            row.push(st_purchase_date.clone());
-            row.push(txn_date_string.clone());
-            row.push(proceeds_st.to_string());
-            row.push(cost_basis_st.to_string());
-            row.push((proceeds_st + cost_basis_st).to_string());
+        let acct = acct_map.get(&impact.acct_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
 
-            rows.push(row);
-        }
-        if let Some(term) = term_lt {
+        let mut row: Vec<String> = Vec::with_capacity(total_columns);
 
-            let mut row: Vec<String> = Vec::with_capacity(total_columns);
+        row.push(raw_acct.name.to_string());
+        row.push(raw_acct.ticker.to_string());
+        row.push(lot_id(impact.acct_key, impact.lot_number));
+        row.push(settings.report_date(impact.date_for_basis_purposes));
+        row.push(settings.output_precision.crypto(impact.amount));
+        row.push(settings.output_precision.fiat(impact.cost_basis));
+        row.push(settings.output_precision.number(impact.spot_price));
+        row.push(settings.output_precision.fiat(impact.market_value));
+        row.push(settings.output_precision.fiat(impact.unrealized_gain_or_loss()));
+        row.push(impact.term.abbr_string());
+        row.push(impact.days_until_lt.to_string());
 
-            row.push(term.abbr_string());
-            row.push(tx_num_string);
-            row.push(tx_memo_string);
-            row.push(amount_lt.to_string());
-            row.push(lt_purchase_date.clone());
-            row.push(txn_date_string);
-            row.push(proceeds_lt.to_string());
-            row.push(cost_basis_lt.to_string());
-            row.push((proceeds_lt + cost_basis_lt).to_string());
+        rows.push(row);
+    }
+
+    write_rows_to_csv(settings, &format!("C8_Open_lot_tax_impact_{}.csv", as_of_date), &rows)?;
+
+    Ok(())
+}
+
+/// Lists every open lot at an unrealized loss at its spot price, largest loss first, with its holding period and the
+/// part of the net realized gains of `as_of_date`'s year that selling it would offset (see
+/// `spot_prices::harvest_candidates()`), for choosing the lots to harvest (C29_Tax_loss_harvesting_<DATE>.csv).
+pub fn _29_tax_loss_harvesting_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    spot_prices: &HashMap<String, Decimal>,
+    as_of_date: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Lot ID".to_string(),
+        "Basis date".to_string(),
+        "Days held".to_string(),
+        "Term if sold today".to_string(),
+        "Amount".to_string(),
+        "Cost basis".to_string(),
+        "Spot price".to_string(),
+        "Market value".to_string(),
+        "Harvestable loss".to_string(),
+        "Offsets ST gain".to_string(),
+        "Offsets LT gain".to_string(),
+        "Loss left over".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    rows.push(columns.to_vec());
+
+    //  Unpriced tickers were already warned of, for C8
+    let (impacts, _unpriced) = spot_prices::open_lot_impacts(
+        spot_prices,
+        as_of_date,
+        &settings.home_currency,
+        settings.long_term_days,
+        raw_acct_map,
+        acct_map,
+    );
+
+    let (realized_st, realized_lt) = summary::gains_by_asset_and_year(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?
+        .iter()
+        .filter(|asset| asset.year == settings.fiscal_year(as_of_date))
+        .fold((dec!(0), dec!(0)), |(st, lt), asset| (st + asset.realized_st, lt + asset.realized_lt));
+
+    let candidates = spot_prices::harvest_candidates(&impacts, realized_st, realized_lt);
+
+    let precision = &settings.output_precision;
+    let mut total_row = vec![String::new(); total_columns];
+
+    for candidate in candidates.iter() {
+
+        let impact = &candidate.impact;
+        let raw_acct = raw_acct_map.get(&acct_map.get(&impact.acct_key).unwrap().raw_key).unwrap();
+
+        rows.push(vec![
+            raw_acct.name.to_string(),
+            raw_acct.ticker.to_string(),
+            lot_id(impact.acct_key, impact.lot_number),
+            settings.report_date(impact.date_for_basis_purposes),
+            as_of_date.signed_duration_since(impact.date_for_basis_purposes).num_days().to_string(),
+            impact.term.abbr_string(),
+            precision.crypto(impact.amount),
+            precision.fiat(impact.cost_basis),
+            precision.number(impact.spot_price),
+            precision.fiat(impact.market_value),
+            precision.fiat(candidate.harvestable_loss),
+            precision.fiat(candidate.offsets_st),
+            precision.fiat(candidate.offsets_lt),
+            precision.fiat(candidate.loss_left_over()),
+        ]);
+    }
+
+    let sum = |value: fn(&spot_prices::HarvestCandidate) -> Decimal| candidates.iter().map(value).sum::<Decimal>();
+
+    total_row[0] = "Total".to_string();
+    total_row[10] = precision.fiat(sum(|c| c.harvestable_loss));
+    total_row[11] = precision.fiat(sum(|c| c.offsets_st));
+    total_row[12] = precision.fiat(sum(|c| c.offsets_lt));
+    total_row[13] = precision.fiat(sum(|c| c.loss_left_over()));
+    rows.push(total_row);
+
+    //  What there was to offset
+    let mut realized_row = vec![String::new(); total_columns];
+    realized_row[0] = format!("Net realized gain/loss in {}", settings.fiscal_year(as_of_date));
+    realized_row[11] = precision.fiat(realized_st);
+    realized_row[12] = precision.fiat(realized_lt);
+    rows.push(realized_row);
+
+    write_rows_to_csv(settings, &format!("C29_Tax_loss_harvesting_{}.csv", as_of_date), &rows)?;
+
+    Ok(())
+}
+
+pub fn _9_gains_by_exchange_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Exchange".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Total gain/loss".to_string(),
+        "Accounts".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
+
+    let exchanges = summary::gains_by_exchange(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.exchange_label_separator.as_deref(),
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    for exchange in exchanges.iter() {
+
+        let mut row: Vec<String> = Vec::with_capacity(total_columns);
+
+        row.push(exchange.label.clone());
+        row.push(settings.output_precision.fiat(exchange.realized_st));
+        row.push(settings.output_precision.fiat(exchange.realized_lt));
+        row.push(settings.output_precision.fiat(exchange.realized_total()));
+        row.push(exchange.account_names.join("; "));
+
+        rows.push(row);
+    }
+
+    write_rows_to_csv(settings, "C9_Gains_by_exchange.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Totals the realized gain or loss, income and fees of each exchange (as grouped for C9_Gains_by_exchange.csv) in each
+/// (fiscal) year, with a subtotal for each year and a grand total (C39_Exchange_totals_by_year.csv), for reconciling
+/// against each exchange's Form 1099.
+pub fn _39_exchange_totals_by_year_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Exchange".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Total gain/loss".to_string(),
+        "Income".to_string(),
+        "Fees".to_string(),
+        "Accounts".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let exchanges = summary::totals_by_exchange_and_year(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        settings.exchange_label_separator.as_deref(),
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let exchanges: Vec<_> = exchanges.into_iter().filter(|e| settings.tax_year.is_none_or(|year| e.year == year)).collect();
+
+    let total_row = |year: String, label: &str, totals: [Decimal; 4], accounts: String| vec![
+        year,
+        label.to_string(),
+        settings.output_precision.fiat(totals[0]),
+        settings.output_precision.fiat(totals[1]),
+        settings.output_precision.fiat(totals[0] + totals[1]),
+        settings.output_precision.fiat(totals[2]),
+        settings.output_precision.fiat(totals[3]),
+        accounts,
+    ];
+
+    let add = |sum: &mut [Decimal; 4], totals: [Decimal; 4]| {
+        for (sum, total) in sum.iter_mut().zip(totals) { *sum += total }
+    };
+
+    let mut year_totals = [dec!(0); 4];
+    let mut grand_totals = [dec!(0); 4];
+
+    for (idx, exchange) in exchanges.iter().enumerate() {
+
+        let totals = [exchange.realized_st, exchange.realized_lt, exchange.income, exchange.fees];
+
+        rows.push(total_row(exchange.year.to_string(), &exchange.label, totals, exchange.account_names.join("; ")));
+        add(&mut year_totals, totals);
+
+        //  The year's subtotal follows its last exchange
+        if exchanges.get(idx + 1).is_none_or(|next| next.year != exchange.year) {
+            rows.push(total_row(exchange.year.to_string(), "Year total", year_totals, String::new()));
+            add(&mut grand_totals, year_totals);
+            year_totals = [dec!(0); 4];
+        }
+    }
+
+    rows.push(total_row(String::new(), "Total", grand_totals, String::new()));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C39_Exchange_totals_by_year.csv"), &rows)?;
+
+    Ok(())
+}
+
+pub fn _10_synthetic_acquisitions_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Date".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Amount".to_string(),
+        "Cost basis".to_string(),
+        "Covers txn#".to_string(),
+        "Covered txn memo".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+        if txn.kind != TxKind::Synthetic { continue }
+
+        let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+        // A synthetic acquisition is always inserted immediately before the transaction it covers
+        let covered_txn = txns_map.get(&(txn.tx_number + 1)).unwrap();
+
+        let mut row: Vec<String> = Vec::with_capacity(total_columns);
+
+        row.push(txn.tx_number.to_string());
+        row.push(settings.report_date(txn.date));
+        row.push(raw_acct.name.to_string());
+        row.push(raw_acct.ticker.to_string());
+        row.push(settings.output_precision.crypto(ar.amount));
+        row.push(settings.output_precision.fiat(ar.cost_basis_in_ar()));
+        row.push(covered_txn.tx_number.to_string());
+        row.push(covered_txn.user_memo.to_string());
+
+        rows.push(row);
+    }
+
+    write_rows_to_csv(settings, "C10_Synthetic_acquisitions.csv", &rows)?;
+
+    Ok(())
+}
+
+pub fn _11_portfolio_summaries_to_csv(
+    settings: &ImportProcessParameters,
+    portfolios: &[(String, summary::RunSummary)],
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Portfolio".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Total gain/loss".to_string(),
+        "Income".to_string(),
+        "Expense".to_string(),
+        "Open lots".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
+
+    let summary_row = |name: &str, st: Decimal, lt: Decimal, income: Decimal, expense: Decimal, open_lots: u32| {
+        vec![
+            name.to_string(),
+            settings.output_precision.fiat(st),
+            settings.output_precision.fiat(lt),
+            settings.output_precision.fiat(st + lt),
+            settings.output_precision.fiat(income),
+            settings.output_precision.fiat(expense),
+            open_lots.to_string(),
+        ]
+    };
+
+    for (name, run_summary) in portfolios.iter() {
+        rows.push(summary_row(
+            name,
+            run_summary.realized_st,
+            run_summary.realized_lt,
+            run_summary.income,
+            run_summary.expense,
+            run_summary.open_lots,
+        ));
+    }
+
+    rows.push(summary_row(
+        "Combined",
+        portfolios.iter().map(|(_, s)| s.realized_st).sum(),
+        portfolios.iter().map(|(_, s)| s.realized_lt).sum(),
+        portfolios.iter().map(|(_, s)| s.income).sum(),
+        portfolios.iter().map(|(_, s)| s.expense).sum(),
+        portfolios.iter().map(|(_, s)| s.open_lots).sum(),
+    ));
+
+    write_rows_to_csv(settings, "C11_Portfolio_summaries.csv", &rows)?;
+
+    Ok(())
+}
+
+pub fn _12_unrealized_gains_by_term_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    spot_prices: &HashMap<String, Decimal>,
+    as_of_date: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Ticker".to_string(),
+        "ST lots".to_string(),
+        "ST amount".to_string(),
+        "ST cost basis".to_string(),
+        "ST market value".to_string(),
+        "ST unrealized gain/loss".to_string(),
+        "LT lots".to_string(),
+        "LT amount".to_string(),
+        "LT cost basis".to_string(),
+        "LT market value".to_string(),
+        "LT unrealized gain/loss".to_string(),
+        "Total unrealized gain/loss".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
+
+    // Unpriced tickers are already warned about by the open lot tax impact report
+    let (impacts, _unpriced) = spot_prices::open_lot_impacts(
+        spot_prices,
+        as_of_date,
+        &settings.home_currency,
+        settings.long_term_days,
+        raw_acct_map,
+        acct_map,
+    );
+
+    let currencies = spot_prices::unrealized_by_term(&impacts, raw_acct_map, acct_map);
+
+    let push_term = |row: &mut Vec<String>, bucket: &spot_prices::TermBucket, with_amount: bool| {
+        row.push(bucket.lots.to_string());
+        row.push(if with_amount { settings.output_precision.crypto(bucket.amount) } else { "".to_string() });
+        row.push(settings.output_precision.fiat(bucket.cost_basis));
+        row.push(settings.output_precision.fiat(bucket.market_value));
+        row.push(settings.output_precision.fiat(bucket.unrealized_gain_or_loss()));
+    };
+
+    let mut total_st = spot_prices::TermBucket::default();
+    let mut total_lt = spot_prices::TermBucket::default();
+
+    for currency in currencies.iter() {
+
+        let mut row: Vec<String> = Vec::with_capacity(total_columns);
+
+        row.push(currency.ticker.clone());
+        push_term(&mut row, &currency.st, true);
+        push_term(&mut row, &currency.lt, true);
+        row.push(settings.output_precision.fiat(currency.unrealized_gain_or_loss()));
+
+        rows.push(row);
+
+        for (total, bucket) in [(&mut total_st, &currency.st), (&mut total_lt, &currency.lt)] {
+            total.lots += bucket.lots;
+            total.cost_basis += bucket.cost_basis;
+            total.market_value += bucket.market_value;
+        }
+    }
+
+    // Amounts of different currencies can't be added together, so the total row leaves them blank
+    let mut row: Vec<String> = Vec::with_capacity(total_columns);
+    row.push("Total".to_string());
+    push_term(&mut row, &total_st, false);
+    push_term(&mut row, &total_lt, false);
+    row.push(settings.output_precision.fiat(total_st.unrealized_gain_or_loss() + total_lt.unrealized_gain_or_loss()));
+    rows.push(row);
+
+    write_rows_to_csv(settings, &format!("C12_Unrealized_gains_by_term_{}.csv", as_of_date), &rows)?;
+
+    Ok(())
+}
+
+/// The capital gains CSV import layouts of tax software, for `--tax-software`.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum TaxSoftware {
+    /// TurboTax Online's crypto CSV template: Currency Name, Purchase Date, Cost Basis, Date Sold, Proceeds.
+    #[value(name = "turbotax")]
+    TurboTax,
+    /// TaxAct's capital gains CSV import: Description, Date Acquired, Date Sold, Sales Proceeds, Cost Basis, Term.
+    #[value(name = "taxact")]
+    TaxAct,
+}
+
+impl TaxSoftware {
+    fn name(&self) -> &'static str {
+        match self {
+            TaxSoftware::TurboTax => "TurboTax",
+            TaxSoftware::TaxAct => "TaxAct",
+        }
+    }
+}
+
+/// Writes the Form 8949 lines (as in C7_Form_8949.csv) in the column layout `software` imports, with dates as
+/// MM/DD/YYYY and cost basis positive.  A line drawn from lots with different basis dates is dated "VARIOUS".
+pub fn _14_tax_software_gains_to_csv(
+    settings: &ImportProcessParameters,
+    software: TaxSoftware,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns: Vec<&str> = match software {
+        TaxSoftware::TurboTax => vec!["Currency Name", "Purchase Date", "Cost Basis", "Date Sold", "Proceeds"],
+        TaxSoftware::TaxAct => vec!["Description", "Date Acquired", "Date Sold", "Sales Proceeds", "Cost Basis", "Term"],
+    };
+    rows.push(columns.iter().map(|column| column.to_string()).collect());
+
+    //  The tax software expects its own (US) formats, whatever the REPORT_LOCALE
+    let precision = OutputPrecision { locale: ReportLocale::Plain, ..settings.output_precision };
+
+    let mut wash_sale_txns: Vec<String> = Vec::new();
+
+    for line in export_8949::form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)? {
+
+        if line.adjustment != dec!(0) { wash_sale_txns.push(line.txn_nums.clone()) }
+
+        let date_acquired = match NaiveDate::parse_from_str(&line.date_acquired, "%Y-%m-%d") {
+            Ok(date) => date.format("%m/%d/%Y").to_string(),
+            Err(_) => line.date_acquired.to_uppercase(),    //  "Various" or "INHERITED"
+        };
+        let date_sold = line.date_sold.format("%m/%d/%Y").to_string();
+        let proceeds = precision.fiat(line.proceeds);
+        let cost_basis = precision.fiat(-line.cost_basis);
+
+        let row = match software {
+            TaxSoftware::TurboTax => vec![
+                if line.ticker.is_empty() { "VARIOUS".to_string() } else { line.ticker.clone() },
+                date_acquired,
+                cost_basis,
+                date_sold,
+                proceeds,
+            ],
+            TaxSoftware::TaxAct => vec![
+                match line.amount {
+                    Some(amount) => format!("{} {}", precision.crypto((-amount).normalize()), line.ticker),
+                    None => line.memo.clone(),
+                },
+                date_acquired,
+                date_sold,
+                proceeds,
+                cost_basis,
+                match line.term { Term::ST => "Short".to_string(), Term::LT => "Long".to_string() },
+            ],
+        };
+
+        rows.push(row);
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name(&format!("C14_{}_gains.csv", software.name())), &rows)?;
+
+    // Neither import format has a column for an adjustment, so a wash sale must be finished by hand
+    if !wash_sale_txns.is_empty() {
+        log::info!("  Note: C14_{}_gains.csv has no adjustment column. After importing it, enter code W and the disallowed \
+            loss (see C16_Wash_sales.csv) for the wash sale(s) in txn(s) {}.", software.name(), wash_sale_txns.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Exports the lots open at the end of the fiscal `year` (C15_Holdings_as_of_YYYY-12-31.csv, for a calendar year), each with its remaining amount,
+/// acquisition and basis dates, and remaining cost basis.  Next year's run can start from the file (via
+/// `--opening-balances`) instead of the full history.
+pub fn _15_year_end_holdings_to_csv(
+    settings: &ImportProcessParameters,
+    year: i32,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    if NaiveDate::from_ymd_opt(year + 1, 12, 31).is_none() { return Err("The year is out of range.".into()) }
+
+    let (_start, year_end) = settings.fiscal_year_dates(year);
+    let state = RunState::as_of(settings, raw_acct_map, acct_map, year_end)?;
+
+    write_rows_to_csv(settings, &format!("C15_Holdings_as_of_{}.csv", year_end), &state.opening_balance_rows())?;
+
+    Ok(())
+}
+
+/// Lists the lots open at the end of `as_of_date` (C22_Open_lots_as_of_<DATE>.csv), with each one's remaining amount
+/// and cost basis, its acquisition and basis dates, and how long it has been held (and what term that is) on that date.
+pub fn _22_open_lots_to_csv(
+    settings: &ImportProcessParameters,
+    as_of_date: NaiveDate,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    let state = RunState::as_of(settings, raw_acct_map, acct_map, as_of_date)?;
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Amount".to_string(),
+        "Cost basis".to_string(),
+        "Acquired".to_string(),
+        "Basis date".to_string(),
+        "Days held".to_string(),
+        "Term".to_string(),
+        "Days until LT".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    for lot in state.lots.iter() {
+
+        let days_held = as_of_date.signed_duration_since(lot.basis_date).num_days();
+
+        rows.push(vec![
+            lot.account.clone(),
+            lot.ticker.clone(),
+            settings.output_precision.crypto(lot.amount),
+            settings.output_precision.fiat(lot.cost_basis),
+            lot.acquired.to_string(),
+            settings.report_date(lot.basis_date),
+            days_held.to_string(),
+            lot.term(as_of_date, settings.long_term_days).abbr_string(),
+            if lot.inherited { 0 } else { (settings.long_term_days + 1 - days_held).max(0) }.to_string(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, &format!("C22_Open_lots_as_of_{}.csv", as_of_date), &rows)?;
+
+    Ok(())
+}
+
+/// Lists the amount of each asset held (across the non-margin accounts) at the end of each day or month of the whole
+/// history (C35_Balances_daily.csv or C35_Balances_monthly.csv), a column per ticker, for charting.
+pub fn _35_balance_history_to_csv(
+    settings: &ImportProcessParameters,
+    interval: BalanceInterval,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    let history = BalanceHistory::from_maps(settings, interval, raw_acct_map, acct_map);
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let mut columns = vec!["Date".to_string()];
+    columns.extend(history.tickers.iter().cloned());
+
+    rows.push(columns);
+
+    for (date, balances) in history.balances.iter() {
+        let mut row = vec![settings.report_date(*date)];
+        row.extend(balances.iter().map(|balance| settings.output_precision.crypto(*balance)));
+        rows.push(row);
+    }
+
+    let file_name = match interval {
+        BalanceInterval::Daily => "C35_Balances_daily.csv",
+        BalanceInterval::Monthly => "C35_Balances_monthly.csv",
+    };
+
+    write_rows_to_csv(settings, file_name, &rows)?;
+
+    Ok(())
+}
+
+/// Lists the highest balance of each non-margin account in each (fiscal) year, the first day it was held, and the
+/// balance at the end of the year (C36_Highest_balances.csv), as for reporting the maximum value of a foreign account.
+pub fn _36_highest_balances_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Acct#".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Highest balance".to_string(),
+        "On".to_string(),
+        "Year-end balance".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    for balance in balance_history::highest_balances(settings, raw_acct_map, acct_map).iter() {
+        rows.push(vec![
+            balance.year.to_string(),
+            balance.account_num.to_string(),
+            balance.account.clone(),
+            balance.ticker.clone(),
+            settings.output_precision.crypto(balance.highest),
+            settings.report_date(balance.on),
+            settings.output_precision.crypto(balance.year_end),
+        ]);
+    }
+
+    write_rows_to_csv(settings, "C36_Highest_balances.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists, for each currency other than the home currency, the quantity acquired and disposed of over the whole history,
+/// the average acquisition cost and sale price, the realized gain, and the current holding and its average basis
+/// (C37_Asset_statistics.csv).
+pub fn _37_asset_statistics_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let assets = summary::asset_statistics(&settings.home_currency, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Ticker".to_string(),
+        "Acquired".to_string(),
+        "Acquisition cost".to_string(),
+        "Avg acquisition cost".to_string(),
+        "Disposed".to_string(),
+        "Proceeds".to_string(),
+        "Avg sale price".to_string(),
+        "Realized gain/loss".to_string(),
+        "Holding".to_string(),
+        "Holding basis".to_string(),
+        "Avg holding basis".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let average = |value: Option<Decimal>| value.map(|value| settings.output_precision.number(value)).unwrap_or_default();
+
+    for asset in assets.iter() {
+        rows.push(vec![
+            asset.ticker.clone(),
+            settings.output_precision.crypto(asset.acquired),
+            settings.output_precision.fiat(asset.acquisition_cost),
+            average(asset.average_acquisition_cost()),
+            settings.output_precision.crypto(asset.disposed),
+            settings.output_precision.fiat(asset.proceeds),
+            average(asset.average_sale_price()),
+            settings.output_precision.fiat(asset.realized),
+            settings.output_precision.crypto(asset.holding),
+            settings.output_precision.fiat(asset.holding_basis),
+            average(asset.average_holding_basis()),
+        ]);
+    }
+
+    write_rows_to_csv(settings, "C37_Asset_statistics.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists each disposal with a loss disallowed under the wash sale rule, and each replacement lot whose basis the
+/// disallowed loss was added to, in `Transaction` order (C16_Wash_sales.csv).
+pub fn _16_wash_sales_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Date".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Lot ID".to_string(),
+        "Amount".to_string(),
+        "Proceeds".to_string(),
+        "Cost basis".to_string(),
+        "Loss disallowed".to_string(),
+        "Loss allowed".to_string(),
+        "Basis added".to_string(),
+    ];
+
+    let total_columns = columns.len();
+    let mut header: Vec<String> = Vec::with_capacity(total_columns);
+    header.extend_from_slice(&columns);
+    rows.push(header);
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+
+        if !settings.is_reported_txn(txn) { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+            let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+                let adjustment = mvmt.wash_sale_adjustment.get();
+                if adjustment == dec!(0) { continue }
+
+                let mut row: Vec<String> = Vec::with_capacity(total_columns);
+
+                row.push(txn.tx_number.to_string());
+                row.push(settings.report_date(txn.date));
+                row.push(raw_acct.name.to_string());
+                row.push(raw_acct.ticker.to_string());
+                row.push(lot_id(ar.account_key, mvmt.lot_num));
+                row.push(settings.output_precision.crypto(mvmt.amount));
+
+                if adjustment > dec!(0) {
+                    row.push(settings.output_precision.fiat(mvmt.proceeds_lk.get()));
+                    row.push(settings.output_precision.fiat(mvmt.cost_basis_lk.get()));
+                    row.push(settings.output_precision.fiat(adjustment));
+                    row.push(settings.output_precision.fiat(mvmt.proceeds_lk.get() + mvmt.cost_basis_lk.get() + adjustment));
+                    row.push(String::new());
+                } else {
+                    row.push(String::new());
+                    row.push(settings.output_precision.fiat(mvmt.cost_basis_lk.get()));
+                    row.push(String::new());
+                    row.push(String::new());
+                    row.push(settings.output_precision.fiat(-adjustment));
+                }
+
+                rows.push(row);
+            }
+        }
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C16_Wash_sales.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Lists each disposal in the layout of Schedule 3 (capital gains or losses), for the adjusted cost base: one line per
+/// `Transaction` (Canada doesn't split gains by holding period), with the proceeds of disposition, the adjusted cost
+/// base and the gain or loss, which excludes any superficial loss denied.  The totals are followed by the taxable
+/// capital gain, half of the net gain (C17_Schedule_3_capital_gains.csv).
+pub fn _17_schedule_3_gains_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Description".to_string(),
+        "Date of disposition".to_string(),
+        format!("Proceeds of disposition ({})", settings.home_currency),
+        format!("Adjusted cost base ({})", settings.home_currency),
+        format!("Superficial loss denied ({})", settings.home_currency),
+        format!("Gain (or loss) ({})", settings.home_currency),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let mut lines = export_8949::form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)?.into_iter().peekable();
+
+    let mut total_proceeds = dec!(0);
+    let mut total_acb = dec!(0);
+    let mut total_denied = dec!(0);
+
+    while let Some(first) = lines.next() {
+
+        let mut amount = first.amount;
+        let mut proceeds = first.proceeds;
+        let mut acb = -first.cost_basis;
+        let mut denied = first.adjustment;
+
+        //  The short-term and long-term lines of a `Transaction` are one disposition here
+        while let Some(line) = lines.next_if(|line| line.txn_nums == first.txn_nums) {
+            amount = amount.zip(line.amount).map(|(a, b)| a + b);
+            proceeds += line.proceeds;
+            acb -= line.cost_basis;
+            denied += line.adjustment;
+        }
+
+        total_proceeds += proceeds;
+        total_acb += acb;
+        total_denied += denied;
+
+        rows.push(vec![
+            first.txn_nums.clone(),
+            match amount {
+                Some(amount) => format!("{} {}", settings.output_precision.crypto((-amount).normalize()), first.ticker),
+                None => first.memo.clone(),
+            },
+            settings.report_date(first.date_sold),
+            settings.output_precision.fiat(proceeds),
+            settings.output_precision.fiat(acb),
+            settings.output_precision.fiat(denied),
+            settings.output_precision.fiat(proceeds - acb + denied),
+        ]);
+    }
+
+    let net_gain = total_proceeds - total_acb + total_denied;
+
+    rows.push(vec![
+        String::new(),
+        "Totals".to_string(),
+        String::new(),
+        settings.output_precision.fiat(total_proceeds),
+        settings.output_precision.fiat(total_acb),
+        settings.output_precision.fiat(total_denied),
+        settings.output_precision.fiat(net_gain),
+    ]);
+    rows.push(vec![
+        String::new(),
+        "Taxable capital gain (or allowable capital loss), 50%".to_string(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        settings.output_precision.fiat(net_gain / dec!(2)),
+    ]);
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C17_Schedule_3_capital_gains.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Totals realized gains and losses, short-term and long-term (by `long_term_days`), for each currency disposed of in
+/// each year, with a subtotal for each year and a grand total (C18_Gains_by_asset_and_year.csv).
+pub fn _18_gains_by_asset_and_year_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Ticker".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Total gain/loss".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let assets = summary::gains_by_asset_and_year(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let assets: Vec<_> = assets.into_iter().filter(|asset| settings.tax_year.is_none_or(|year| asset.year == year)).collect();
+
+    let total_row = |year: String, label: &str, st: Decimal, lt: Decimal| vec![
+        year,
+        label.to_string(),
+        settings.output_precision.fiat(st),
+        settings.output_precision.fiat(lt),
+        settings.output_precision.fiat(st + lt),
+    ];
+
+    let mut total_st = dec!(0);
+    let mut total_lt = dec!(0);
+    let mut year_st = dec!(0);
+    let mut year_lt = dec!(0);
+
+    for (idx, asset) in assets.iter().enumerate() {
+
+        rows.push(total_row(asset.year.to_string(), &asset.ticker, asset.realized_st, asset.realized_lt));
+
+        year_st += asset.realized_st;
+        year_lt += asset.realized_lt;
+
+        //  The year's subtotal follows its last currency
+        if assets.get(idx + 1).is_none_or(|next| next.year != asset.year) {
+            rows.push(total_row(asset.year.to_string(), "Year total", year_st, year_lt));
+            total_st += year_st;
+            total_lt += year_lt;
+            year_st = dec!(0);
+            year_lt = dec!(0);
+        }
+    }
+
+    rows.push(total_row(String::new(), "Total", total_st, total_lt));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C18_Gains_by_asset_and_year.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Lists every lot, by its lot ID (see `Lot::id()`), with its account, ticker, the dates it was created and that its
+/// basis dates from, the quantity and cost basis it was created with, and what remains of them (C42_Lots.csv).
+pub fn _42_lots_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Lot ID".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Created".to_string(),
+        "Basis date".to_string(),
+        "Original quantity".to_string(),
+        "Remaining quantity".to_string(),
+        "Original cost basis".to_string(),
+        "Remaining cost basis".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let length = acct_map.len();
+
+    for j in 1..=length {
+
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+        if !settings.is_reported_account(raw_acct) { continue }
+
+        for lot in acct.list_of_lots.borrow().iter() {
+
+            //  The incoming movements are the ones the lot was created with
+            let (orig_amount, orig_basis) = lot.movements.borrow().iter()
+                .filter(|mvmt| mvmt.amount > dec!(0))
+                .fold((dec!(0), dec!(0)), |(amount, basis), mvmt| (amount + mvmt.amount, basis + mvmt.cost_basis_lk.get()));
+
+            let (orig_basis, remaining_basis) = if raw_acct.is_margin {
+                (dec!(0), dec!(0))
+            } else {
+                (orig_basis, lot.get_sum_of_lk_basis_in_lot())
+            };
+
+            rows.push(vec![
+                lot.id(),
+                raw_acct.name.to_string(),
+                raw_acct.ticker.to_string(),
+                lot.date_of_first_mvmt_in_lot.to_string(),
+                lot.date_for_basis_purposes.to_string(),
+                settings.output_precision.crypto(orig_amount),
+                settings.output_precision.crypto(lot.get_sum_of_amts_in_lot()),
+                settings.output_precision.fiat(orig_basis),
+                settings.output_precision.fiat(remaining_basis),
+            ]);
+        }
+    }
+
+    write_rows_to_csv(settings, "C42_Lots.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists the dust swept out of accounts (see `dust_sweeps::sweep_dust()`): each sweep's account, ticker, the quantity
+/// swept and the cost basis written off with it, and the disposal that left it (C43_Dust_sweeps.csv).
+pub fn _43_dust_sweeps_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Date".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Amount swept".to_string(),
+        "Cost basis written off".to_string(),
+        "Left by txn#".to_string(),
+        "Left by txn memo".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let mut total_basis = dec!(0);
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+        if txn.kind != TxKind::DustSweep { continue }
+
+        let ar = ars.get(&txn.action_record_idx_vec[0]).unwrap();
+        let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+
+        if !settings.is_reported_account(raw_acct) { continue }
+
+        // A sweep is inserted right after the disposal that left the dust (and any other sweep it left)
+        let disposal = (1..txn.tx_number).rev()
+            .map(|num| txns_map.get(&num).unwrap())
+            .find(|prior| prior.kind != TxKind::DustSweep)
+            .unwrap();
+
+        total_basis += ar.cost_basis_in_ar();
+
+        rows.push(vec![
+            txn.tx_number.to_string(),
+            settings.report_date(txn.date),
+            raw_acct.name.to_string(),
+            raw_acct.ticker.to_string(),
+            settings.output_precision.crypto(-ar.amount),
+            settings.output_precision.fiat(ar.cost_basis_in_ar()),
+            disposal.tx_number.to_string(),
+            disposal.user_memo.to_string(),
+        ]);
+    }
+
+    let mut total_row = vec![String::new(); columns.len()];
+    total_row[0] = "Total".to_string();
+    total_row[5] = settings.output_precision.fiat(total_basis);
+    rows.push(total_row);
+
+    write_rows_to_csv(settings, "C43_Dust_sweeps.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Totals the lines of Form 8949 (see `export_8949::form_8949_lines()`) for each year of sale, as carried to Schedule D:
+/// the short-term and the long-term proceeds, cost basis, adjustments and gain or loss, and the net gain or loss, with
+/// a grand total (C41_Schedule_D_totals.csv).
+pub fn _41_schedule_d_totals_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "ST proceeds".to_string(),
+        "ST cost basis".to_string(),
+        "ST adjustments".to_string(),
+        "ST gain/loss".to_string(),
+        "LT proceeds".to_string(),
+        "LT cost basis".to_string(),
+        "LT adjustments".to_string(),
+        "LT gain/loss".to_string(),
+        "Net gain/loss".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    //  Proceeds, cost basis (positive) and adjustments, short-term then long-term, by year
+    let mut years: Vec<(i32, [Decimal; 6])> = Vec::new();
+
+    for line in export_8949::form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)? {
+
+        let year = summary::fiscal_year(line.date_sold, settings.fiscal_year_start_month);
+
+        let idx = match years.iter().position(|(y, _totals)| *y == year) {
+            Some(idx) => idx,
+            None => { years.push((year, [dec!(0); 6])); years.len() - 1 }
+        };
+        let offset = match line.term { Term::ST => 0, Term::LT => 3 };
+        let totals = &mut years[idx].1;
+
+        totals[offset] += line.proceeds;
+        totals[offset + 1] -= line.cost_basis;
+        totals[offset + 2] += line.adjustment;
+    }
+
+    years.sort_by_key(|(year, _totals)| *year);
+
+    let total_row = |year: String, totals: &[Decimal; 6]| {
+        let st_gain = totals[0] - totals[1] + totals[2];
+        let lt_gain = totals[3] - totals[4] + totals[5];
+        let mut row = vec![year];
+        row.extend(totals[..3].iter().map(|total| settings.output_precision.fiat(*total)));
+        row.push(settings.output_precision.fiat(st_gain));
+        row.extend(totals[3..].iter().map(|total| settings.output_precision.fiat(*total)));
+        row.push(settings.output_precision.fiat(lt_gain));
+        row.push(settings.output_precision.fiat(st_gain + lt_gain));
+        row
+    };
+
+    let mut grand_totals = [dec!(0); 6];
+
+    for (year, totals) in years.iter() {
+        rows.push(total_row(year.to_string(), totals));
+        grand_totals.iter_mut().zip(totals.iter()).for_each(|(grand, total)| *grand += *total);
+    }
+
+    rows.push(total_row("Total".to_string(), &grand_totals));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C41_Schedule_D_totals.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Totals income by kind (mining, staking, interest, airdrop, hard fork, or other, if no `incomeType` was given) for
+/// each year, with a subtotal for each year and a grand total (C19_Income_by_type.csv).
+pub fn _19_income_by_type_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Income type".to_string(),
+        "Income".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let totals = summary::income_by_type_and_year(
+        &settings.home_currency,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let totals: Vec<_> = totals.into_iter().filter(|total| settings.tax_year.is_none_or(|year| total.year == year)).collect();
+
+    let total_row = |year: String, label: &str, income: Decimal| vec![
+        year,
+        label.to_string(),
+        settings.output_precision.fiat(income),
+    ];
+
+    let mut grand_total = dec!(0);
+    let mut year_total = dec!(0);
+
+    for (idx, total) in totals.iter().enumerate() {
+
+        let label = total.income_type.map_or("Other".to_string(), |income_type| income_type.to_string());
+        rows.push(total_row(total.year.to_string(), &label, total.income));
+
+        year_total += total.income;
+
+        //  The year's subtotal follows its last kind of income
+        if totals.get(idx + 1).is_none_or(|next| next.year != total.year) {
+            rows.push(total_row(total.year.to_string(), "Year total", year_total));
+            grand_total += year_total;
+            year_total = dec!(0);
+        }
+    }
+
+    rows.push(total_row(String::new(), "Total", grand_total));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C19_Income_by_type.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Totals income by payer (the `payer` of each income transaction) for each year, with the number of payments, a
+/// subtotal for each year and a grand total, for reconciling against each payer's Form 1099 (C34_Income_by_payer.csv).
+pub fn _34_income_by_payer_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Payer".to_string(),
+        "Payments".to_string(),
+        "Income".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let totals = summary::income_by_payer_and_year(
+        &settings.home_currency,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let totals: Vec<_> = totals.into_iter().filter(|total| settings.tax_year.is_none_or(|year| total.year == year)).collect();
+
+    let total_row = |year: String, label: &str, payments: u32, income: Decimal| vec![
+        year,
+        label.to_string(),
+        payments.to_string(),
+        settings.output_precision.fiat(income),
+    ];
+
+    let (mut grand_payments, mut grand_total) = (0, dec!(0));
+    let (mut year_payments, mut year_total) = (0, dec!(0));
+
+    for (idx, total) in totals.iter().enumerate() {
+
+        rows.push(total_row(total.year.to_string(), total.payer.as_deref().unwrap_or("No payer"), total.payments, total.income));
+
+        year_payments += total.payments;
+        year_total += total.income;
+
+        //  The year's subtotal follows its last payer
+        if totals.get(idx + 1).is_none_or(|next| next.year != total.year) {
+            rows.push(total_row(total.year.to_string(), "Year total", year_payments, year_total));
+            grand_payments += year_payments;
+            grand_total += year_total;
+            (year_payments, year_total) = (0, dec!(0));
+        }
+    }
+
+    rows.push(total_row(String::new(), "Total", grand_payments, grand_total));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C34_Income_by_payer.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Totals expenses by category (the `expenseCategory` of each spend, or uncategorized, and the category of each cost of
+/// the `--costs` file) for each year, with a subtotal for each year and a grand total (C33_Expenses_by_category.csv).
+pub fn _33_expenses_by_category_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Expense category".to_string(),
+        "Expense".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let mut totals = summary::expenses_by_category_and_year(
+        &settings.home_currency,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    for cost_total in fiat_costs::expenses_by_category_and_year(&settings.fiat_costs, settings.fiscal_year_start_month) {
+        match totals.iter_mut().find(|t| t.year == cost_total.year && t.category == cost_total.category) {
+            Some(total) => total.expense += cost_total.expense,
+            None => totals.push(cost_total),
+        }
+    }
+
+    totals.sort_by(|a, b| (a.year, a.category.is_none(), &a.category).cmp(&(b.year, b.category.is_none(), &b.category)));
+
+    let totals: Vec<_> = totals.into_iter().filter(|total| settings.tax_year.is_none_or(|year| total.year == year)).collect();
+
+    let total_row = |year: String, label: &str, expense: Decimal| vec![
+        year,
+        label.to_string(),
+        settings.output_precision.fiat(expense),
+    ];
+
+    let mut grand_total = dec!(0);
+    let mut year_total = dec!(0);
+
+    for (idx, total) in totals.iter().enumerate() {
+
+        rows.push(total_row(total.year.to_string(), total.category.as_deref().unwrap_or("Uncategorized"), total.expense));
+
+        year_total += total.expense;
+
+        //  The year's subtotal follows its last category
+        if totals.get(idx + 1).is_none_or(|next| next.year != total.year) {
+            rows.push(total_row(total.year.to_string(), "Year total", year_total));
+            grand_total += year_total;
+            year_total = dec!(0);
+        }
+    }
+
+    rows.push(total_row(String::new(), "Total", grand_total));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C33_Expenses_by_category.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Nets the costs of the `--costs` file expensed each year (by category, with equipment depreciated) against the
+/// year's mining income, with the net of each year and a grand total (C40_Mining_PnL.csv).
+pub fn _40_mining_pnl_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Line".to_string(),
+        "Amount".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let incomes = summary::income_by_type_and_year(
+        &settings.home_currency,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let years = fiat_costs::mining_pnl_by_year(&settings.fiat_costs, &incomes, settings.fiscal_year_start_month);
+
+    let row = |year: String, label: &str, amount: Decimal| vec![
+        year,
+        label.to_string(),
+        settings.output_precision.fiat(amount),
+    ];
+
+    let mut grand_total = dec!(0);
+
+    for year in years.iter().filter(|year| settings.tax_year.is_none_or(|tax_year| year.year == tax_year)) {
+
+        rows.push(row(year.year.to_string(), "Mining income", year.income));
+
+        //  Costs are negative, as they're netted against the income
+        for expense in year.expenses.iter() {
+            rows.push(row(year.year.to_string(), expense.category.as_deref().unwrap_or("Uncategorized"), -expense.expense));
+        }
+
+        rows.push(row(year.year.to_string(), "Net", year.net()));
+        grand_total += year.net();
+    }
+
+    rows.push(row(String::new(), "Total", grand_total));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C40_Mining_PnL.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Totals the realized gain or loss (short- and long-term) and the income of each quarter of the fiscal year (see
+/// `summary::gains_and_income_by_quarter()`), with the year to date total after each quarter and a total for each
+/// year, for estimating quarterly tax payments (C28_Quarterly_gains_and_income.csv).
+pub fn _28_quarterly_gains_and_income_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Fiscal year".to_string(),
+        "Quarter".to_string(),
+        "Start".to_string(),
+        "End".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Income".to_string(),
+        "Total".to_string(),
+        "Year to date".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let quarters = summary::gains_and_income_by_quarter(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let quarters: Vec<_> = quarters.into_iter().filter(|q| settings.tax_year.is_none_or(|year| q.fiscal_year == year)).collect();
+
+    let precision = &settings.output_precision;
+    let mut year_to_date = dec!(0);
+    let (mut year_st, mut year_lt, mut year_income) = (dec!(0), dec!(0), dec!(0));
+
+    for (idx, q) in quarters.iter().enumerate() {
+
+        year_to_date += q.total();
+        year_st += q.realized_st;
+        year_lt += q.realized_lt;
+        year_income += q.income;
+
+        rows.push(vec![
+            q.fiscal_year.to_string(),
+            format!("Q{}", q.quarter),
+            settings.report_date(q.start),
+            settings.report_date(q.end),
+            precision.fiat(q.realized_st),
+            precision.fiat(q.realized_lt),
+            precision.fiat(q.income),
+            precision.fiat(q.total()),
+            precision.fiat(year_to_date),
+        ]);
+
+        //  The year's total follows its last quarter
+        if quarters.get(idx + 1).is_none_or(|next| next.fiscal_year != q.fiscal_year) {
+            rows.push(vec![
+                q.fiscal_year.to_string(),
+                "Year total".to_string(),
+                String::new(),
+                String::new(),
+                precision.fiat(year_st),
+                precision.fiat(year_lt),
+                precision.fiat(year_income),
+                precision.fiat(year_to_date),
+                String::new(),
+            ]);
+            year_to_date = dec!(0);
+            (year_st, year_lt, year_income) = (dec!(0), dec!(0), dec!(0));
+        }
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C28_Quarterly_gains_and_income.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Compares the realized gains and losses of the same CSV Input File processed under each costing method in
+/// `comparison` (as from `summary::gains_by_year()`), by year and then in total, along with each figure's difference
+/// from that of the configured costing method (C30_Costing_method_comparison.csv).
+pub fn _30_costing_method_comparison_to_csv(
+    settings: &ImportProcessParameters,
+    comparison: &[(InventoryCostingMethod, Vec<summary::YearGains>)],
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Year".to_string(),
+        "Costing method".to_string(),
+        "ST gain/loss".to_string(),
+        "LT gain/loss".to_string(),
+        "Total".to_string(),
+        format!("Difference from {}", settings.costing_method),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let mut years: Vec<i32> = comparison.iter()
+        .flat_map(|(_method, gains)| gains.iter().map(|g| g.year))
+        .filter(|year| settings.tax_year.is_none_or(|tax_year| *year == tax_year))
+        .collect();
+    years.sort();
+    years.dedup();
+
+    //  A method that realized nothing in a year is listed for it anyway, with zeros
+    let year_gains = |gains: &[summary::YearGains], year: Option<i32>| -> (Decimal, Decimal) {
+        gains.iter()
+            .filter(|g| year.map_or(years.contains(&g.year), |year| g.year == year))
+            .fold((dec!(0), dec!(0)), |(st, lt), g| (st + g.realized_st, lt + g.realized_lt))
+    };
 
-            rows.push(row);
+    let configured = comparison.iter().find(|(method, _gains)| *method == settings.costing_method);
+    let precision = &settings.output_precision;
+
+    for year in years.iter().map(|year| Some(*year)).chain([None]) {
+
+        let (configured_st, configured_lt) = configured.map_or((dec!(0), dec!(0)), |(_method, gains)| year_gains(gains, year));
+
+        for (method, gains) in comparison.iter() {
+
+            let (st, lt) = year_gains(gains, year);
+
+            rows.push(vec![
+                year.map_or("All years".to_string(), |year| year.to_string()),
+                method.to_string(),
+                precision.fiat(st),
+                precision.fiat(lt),
+                precision.fiat(st + lt),
+                match configured {
+                    Some(_) => precision.fiat(st + lt - configured_st - configured_lt),
+                    None => String::new(),
+                },
+            ]);
         }
     }
 
-    let file_name = PathBuf::from("C7_Form_8949.csv");
-    let path = PathBuf::from(&settings.export_path);
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C30_Costing_method_comparison.csv"), &rows)?;
 
-    let full_path: PathBuf = [path, file_name].iter().collect();
-    let buffer = File::create(full_path).unwrap();
-    let mut wtr = csv::Writer::from_writer(buffer);
+    Ok(())
+}
 
-    for row in rows.iter() {
-        wtr.write_record(row).expect("Could not write row to CSV file");
+/// Lists every transaction row duplicating an earlier one (as from `dedupe::find_duplicate_rows()`), with the row it
+/// duplicates and whether it was dropped or imported anyway (C31_Duplicate_rows.csv).
+pub fn _31_duplicate_rows_to_csv(
+    settings: &ImportProcessParameters,
+    duplicates: &[dedupe::DuplicateRow],
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Row".to_string(),
+        "Duplicate of row".to_string(),
+        "Date".to_string(),
+        "Memo".to_string(),
+        "Quantities (by account number)".to_string(),
+        "Action".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let action = match settings.dedupe {
+        dedupe::DedupeMode::Drop => "Dropped",
+        _ => "Imported",
+    };
+
+    for duplicate in duplicates.iter() {
+        rows.push(vec![
+            duplicate.row.to_string(),
+            duplicate.duplicate_of.to_string(),
+            duplicate.date.clone(),
+            duplicate.memo.clone(),
+            duplicate.quantities.clone(),
+            action.to_string(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, "C31_Duplicate_rows.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists every transaction row parked for review (as from `needs_review::find_rows_needing_review()`), which was left
+/// out of processing, with why it couldn't be fully classified (C38_Rows_needing_review.csv).
+pub fn _38_rows_needing_review_to_csv(
+    settings: &ImportProcessParameters,
+    review_rows: &[needs_review::ReviewRow],
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Row".to_string(),
+        "Date".to_string(),
+        "Memo".to_string(),
+        "Quantities (by account number)".to_string(),
+        "Needs review because".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    for review_row in review_rows.iter() {
+        rows.push(vec![
+            review_row.row.to_string(),
+            review_row.date.clone(),
+            review_row.memo.clone(),
+            review_row.quantities.clone(),
+            review_row.reason.clone(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, "C38_Rows_needing_review.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists every gift, donation and lost or stolen asset, by lot, with its cost basis and fair market value
+/// (C20_Gifts_donations_and_losses.csv).  A gift's basis and date acquired carry over to the recipient, a donation's
+/// deduction is its fair market value if long-term (or up to its cost basis if short-term), and a loss is written off
+/// without proceeds.
+pub fn _20_gifts_donations_and_losses_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Date".to_string(),
+        "Txn#".to_string(),
+        "Treatment".to_string(),
+        "Memo".to_string(),
+        "Amount".to_string(),
+        "Ticker".to_string(),
+        "Date acquired".to_string(),
+        "Term".to_string(),
+        "Cost basis".to_string(),
+        "Fair market value".to_string(),
+        "Deduction".to_string(),
+        "Gain/loss".to_string(),
+        "Note".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let disposals = non_sale_disposals::non_sale_disposals(settings.long_term_days, acct_map, ars, txns_map);
+
+    for d in disposals.iter().filter(|d| settings.is_reported_date(d.date)) {
+
+        let txn = txns_map.get(&d.tx_num).unwrap();
+        let raw_acct = raw_acct_map.get(&acct_map.get(&d.account_key).unwrap().raw_key).unwrap();
+
+        let (treatment, note) = match (d.kind, &d.term) {
+            (TxKind::Gift, _) => ("Gift", "The recipient's basis and date acquired carry over"),
+            (TxKind::Donation, Term::LT) => ("Donation", "Deductible at fair market value"),
+            (TxKind::Donation, Term::ST) => ("Donation", "Deduction limited to cost basis"),
+            _ => ("Lost or stolen", "Written off without proceeds"),
+        };
+
+        rows.push(vec![
+            settings.report_date(d.date),
+            d.tx_num.to_string(),
+            treatment.to_string(),
+            txn.user_memo.to_string(),
+            settings.output_precision.crypto(d.amount),
+            raw_acct.ticker.to_string(),
+            settings.report_date(d.basis_date),
+            d.term.abbr_string(),
+            settings.output_precision.fiat(d.cost_basis),
+            settings.output_precision.fiat(d.fair_market_value),
+            settings.output_precision.fiat(d.deduction),
+            settings.output_precision.fiat(d.gain_or_loss),
+            note.to_string(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C20_Gifts_donations_and_losses.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Values every open lot at the latest price of its currency in `dated_prices` (see `spot_prices::latest_prices()`),
+/// as of the latest date of any price, with its unrealized gain or loss and holding period, and a total row.
+pub fn _21_portfolio_valuation_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    dated_prices: &HashMap<(NaiveDate, String), Decimal>,
+) -> Result<(), Box<dyn Error>> {
+
+    let (as_of_date, latest) = match spot_prices::latest_prices(dated_prices) {
+        Some(latest) => latest,
+        None => {
+            log::warn!("WARNING: The prices file has no prices, so no portfolio valuation was exported.");
+            return Ok(())
+        }
+    };
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Lot ID".to_string(),
+        "Basis date".to_string(),
+        "Amount".to_string(),
+        "Cost basis".to_string(),
+        "Price date".to_string(),
+        "Price".to_string(),
+        "Market value".to_string(),
+        "Unrealized gain/loss".to_string(),
+        "Days held".to_string(),
+        "Term".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let prices: HashMap<String, Decimal> = latest.iter().map(|(ticker, (_date, price))| (ticker.clone(), *price)).collect();
+
+    let (mut impacts, unpriced) = spot_prices::open_lot_impacts(
+        &prices,
+        as_of_date,
+        &settings.home_currency,
+        settings.long_term_days,
+        raw_acct_map,
+        acct_map,
+    );
+
+    for ticker in unpriced.iter() {
+        log::warn!("WARNING: No price for {} in the prices file. Its open lots are omitted from the portfolio valuation (C21).", ticker);
+    }
+
+    impacts.sort_by_key(|impact| (impact.acct_key, impact.lot_number));
+
+    let mut total_cost_basis = dec!(0);
+    let mut total_market_value = dec!(0);
+
+    for impact in impacts.iter() {
+
+        let raw_acct = raw_acct_map.get(&acct_map.get(&impact.acct_key).unwrap().raw_key).unwrap();
+        let (price_date, price) = latest.get(&raw_acct.ticker).unwrap();
+
+        rows.push(vec![
+            raw_acct.name.to_string(),
+            raw_acct.ticker.to_string(),
+            lot_id(impact.acct_key, impact.lot_number),
+            settings.report_date(impact.date_for_basis_purposes),
+            settings.output_precision.crypto(impact.amount),
+            settings.output_precision.fiat(impact.cost_basis),
+            settings.report_date(*price_date),
+            settings.output_precision.number(*price),
+            settings.output_precision.fiat(impact.market_value),
+            settings.output_precision.fiat(impact.unrealized_gain_or_loss()),
+            as_of_date.signed_duration_since(impact.date_for_basis_purposes).num_days().to_string(),
+            impact.term.abbr_string(),
+        ]);
+
+        total_cost_basis += impact.cost_basis;
+        total_market_value += impact.market_value;
+    }
+
+    // Amounts of different currencies can't be added together, so the total row leaves them blank
+    let mut total_row = vec!["".to_string(); columns.len()];
+    total_row[0] = "Total".to_string();
+    total_row[5] = settings.output_precision.fiat(total_cost_basis);
+    total_row[8] = settings.output_precision.fiat(total_market_value);
+    total_row[9] = settings.output_precision.fiat(total_market_value - total_cost_basis);
+    rows.push(total_row);
+
+    write_rows_to_csv(settings, &format!("C21_Portfolio_valuation_{}.csv", as_of_date), &rows)?;
+
+    Ok(())
+}
+
+/// Compares every account's end-of-history balance with the `expected` balances (see
+/// `reconciliation::import_expected_balances()`), with the difference and each one's status
+/// (C23_Balance_reconciliation.csv).  Each mismatch, and each expected balance of an unknown account, is printed.
+pub fn _23_balance_reconciliation_to_csv(
+    settings: &ImportProcessParameters,
+    expected: &[reconciliation::ExpectedBalance],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Computed balance".to_string(),
+        "Expected balance".to_string(),
+        "Difference".to_string(),
+        "Status".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let checks = reconciliation::reconcile_balances(expected, raw_acct_map, acct_map);
+
+    for check in checks.iter() {
+
+        let optional = |value: Option<Decimal>| value.map(|v| settings.output_precision.number(v)).unwrap_or_default();
+
+        match check.status() {
+            "MISMATCH" => log::warn!("WARNING: {} ({}) has a computed balance of {}, but {} was expected (a difference of {}).",
+                check.account, check.ticker, optional(check.computed), optional(check.expected), optional(check.difference())),
+            "Unknown account" => log::warn!("WARNING: A balance was expected for {} ({}), but there is no such account.",
+                check.account, check.ticker),
+            _ => {}
+        }
+
+        rows.push(vec![
+            check.account.clone(),
+            check.ticker.clone(),
+            optional(check.computed),
+            optional(check.expected),
+            optional(check.difference()),
+            check.status().to_string(),
+        ]);
+    }
+
+    write_rows_to_csv(settings, "C23_Balance_reconciliation.csv", &rows)?;
+
+    Ok(())
+}
+
+/// Lists the realized profit or loss of every cash-settled margin or futures position (a `derivative-pnl` txType),
+/// with a total for each year and a grand total (C24_Derivative_PnL.csv).
+pub fn _24_derivative_pnl_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Date".to_string(),
+        "Txn#".to_string(),
+        "Memo".to_string(),
+        "Account".to_string(),
+        "Amount".to_string(),
+        "Ticker".to_string(),
+        "Profit/loss".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let pnls: Vec<_> = derivatives::derivative_pnls(&settings.home_currency, raw_acct_map, acct_map, ars, txns_map)
+        .into_iter()
+        .filter(|pnl| settings.is_reported_date(pnl.date))
+        .collect();
+
+    let total_row = |label: String, total: Decimal| vec![
+        String::new(),
+        String::new(),
+        label,
+        String::new(),
+        String::new(),
+        String::new(),
+        settings.output_precision.fiat(total),
+    ];
+
+    let mut grand_total = dec!(0);
+    let mut year_total = dec!(0);
+
+    for (idx, pnl) in pnls.iter().enumerate() {
+
+        let txn = txns_map.get(&pnl.tx_num).unwrap();
+        let raw_acct = raw_acct_map.get(&acct_map.get(&pnl.account_key).unwrap().raw_key).unwrap();
+
+        rows.push(vec![
+            settings.report_date(pnl.date),
+            pnl.tx_num.to_string(),
+            txn.user_memo.to_string(),
+            raw_acct.name.to_string(),
+            settings.output_precision.crypto(pnl.amount),
+            raw_acct.ticker.to_string(),
+            settings.output_precision.fiat(pnl.pnl),
+        ]);
+
+        year_total += pnl.pnl;
+
+        //  The year's total follows its last profit or loss
+        if pnls.get(idx + 1).is_none_or(|next| settings.fiscal_year(next.date) != settings.fiscal_year(pnl.date)) {
+            rows.push(total_row(format!("{} total", settings.fiscal_year(pnl.date)), year_total));
+            grand_total += year_total;
+            year_total = dec!(0);
+        }
+    }
+
+    rows.push(total_row("Total".to_string(), grand_total));
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C24_Derivative_PnL.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Lists the transactions of each tag (from a `#tag` in the memo or the tags column), with each one's proceeds, income
+/// and realized gain or loss, and their totals for the tag, so that (i.e.) receipts may be tied to invoices
+/// (C32_Tagged_transactions.csv).  A transaction with several tags is listed under each.
+pub fn _32_tagged_transactions_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Tag".to_string(),
+        "Date".to_string(),
+        "Txn#".to_string(),
+        "Memo".to_string(),
+        "Proceeds".to_string(),
+        "Income".to_string(),
+        "Realized gain/loss".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let tagged: Vec<_> = tags::tagged_transactions(&settings.home_currency, raw_acct_map, acct_map, ars, txns_map)?
+        .into_iter()
+        .filter(|tagged| settings.is_reported_date(tagged.date))
+        .filter(|tagged| settings.report_filter.tag.as_ref().is_none_or(|filter| tagged.tag.matches(filter)))
+        .collect();
+
+    let precision = &settings.output_precision;
+    let (mut proceeds, mut income, mut realized) = (dec!(0), dec!(0), dec!(0));
+
+    for (idx, tagged_txn) in tagged.iter().enumerate() {
+
+        rows.push(vec![
+            tagged_txn.tag.to_string(),
+            settings.report_date(tagged_txn.date),
+            tagged_txn.tx_num.to_string(),
+            tagged_txn.memo.clone(),
+            precision.fiat(tagged_txn.proceeds),
+            precision.fiat(tagged_txn.income),
+            precision.fiat(tagged_txn.realized),
+        ]);
+
+        proceeds += tagged_txn.proceeds;
+        income += tagged_txn.income;
+        realized += tagged_txn.realized;
+
+        //  The tag's total follows its last transaction
+        if tagged.get(idx + 1).is_none_or(|next| next.tag != tagged_txn.tag) {
+            rows.push(vec![
+                format!("{} total", tagged_txn.tag),
+                String::new(),
+                String::new(),
+                String::new(),
+                precision.fiat(proceeds),
+                precision.fiat(income),
+                precision.fiat(realized),
+            ]);
+            (proceeds, income, realized) = (dec!(0), dec!(0), dec!(0));
+        }
+    }
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C32_Tagged_transactions.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Lists every NFT (an account whose ticker is a collection's ticker, `#`, and a token ID) acquired, with its cost
+/// basis, and its sale, if it's been sold, with a total of the gains and losses realized (C25_NFT_gains.csv).
+pub fn _25_nft_gains_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Collection".to_string(),
+        "Token ID".to_string(),
+        "Account".to_string(),
+        "Acquired".to_string(),
+        "Acquired Txn#".to_string(),
+        "Cost basis".to_string(),
+        "Sold".to_string(),
+        "Sold Txn#".to_string(),
+        "Proceeds".to_string(),
+        "Gain/loss".to_string(),
+        "Term".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let mut total = dec!(0);
+
+    for item in nfts::nft_items(settings.long_term_days, raw_acct_map, acct_map, ars, txns_map)? {
+
+        //  Items still held are listed, and those sold only if sold in the tax year
+        if item.sale.as_ref().is_some_and(|sale| !settings.is_reported_date(sale.date)) { continue }
+
+        let raw_acct = raw_acct_map.get(&acct_map.get(&item.account_key).unwrap().raw_key).unwrap();
+
+        let mut row = vec![
+            item.collection.to_string(),
+            item.token_id.to_string(),
+            raw_acct.name.to_string(),
+            settings.report_date(item.acquired),
+            item.acquired_tx_num.to_string(),
+            settings.output_precision.fiat(item.cost_basis),
+        ];
+
+        match &item.sale {
+            Some(sale) => {
+                row.extend([
+                    settings.report_date(sale.date),
+                    sale.tx_num.to_string(),
+                    settings.output_precision.fiat(sale.proceeds),
+                    settings.output_precision.fiat(sale.gain_or_loss),
+                    sale.term.abbr_string(),
+                ]);
+                total += sale.gain_or_loss;
+            }
+            None => row.extend(["Held".to_string(), String::new(), String::new(), String::new(), String::new()]),
+        }
+
+        rows.push(row);
+    }
+
+    let mut total_row = vec![String::new(); columns.len()];
+    total_row[0] = "Total".to_string();
+    total_row[9] = settings.output_precision.fiat(total);
+    rows.push(total_row);
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C25_NFT_gains.csv"), &rows)?;
+
+    Ok(())
+}
+
+/// Lists every slice of a lot drawn for a disposal, one row per outgoing `movement`: the lot it was drawn from and
+/// the transaction that created it, the quantity taken, and that slice's basis date, proceeds, cost basis and
+/// gain/loss, with totals (C27_Lot_selection_audit.csv).  Values are like-kind values, as on Form 8949, but dust
+/// conversions are listed movement by movement.  Gifts and donations are in C20 instead.
+pub fn _27_lot_selection_audit_to_csv(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Txn#".to_string(),
+        "Date sold".to_string(),
+        "Memo".to_string(),
+        "Account".to_string(),
+        "Ticker".to_string(),
+        "Lot ID".to_string(),
+        "Lot created by Txn#".to_string(),
+        "Basis date".to_string(),
+        "Amount taken".to_string(),
+        "Proceeds".to_string(),
+        "Cost basis".to_string(),
+        "Wash sale adj.".to_string(),
+        "Gain/loss".to_string(),
+        "Term".to_string(),
+    ];
+
+    rows.push(columns.to_vec());
+
+    let precision = &settings.output_precision;
+    let mut total_proceeds = dec!(0);
+    let mut total_cost_basis = dec!(0);
+    let mut total_adjustment = dec!(0);
+
+    for txn_num in 1..=txns_map.len() as u32 {
+
+        let txn = txns_map.get(&txn_num).unwrap();
+
+        if !settings.is_reported_txn(txn) { continue }
+        if matches!(txn.kind, TxKind::Gift | TxKind::Donation | TxKind::DustSweep) { continue }
+
+        let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(
+            &settings.home_currency,
+            ars,
+            raw_acct_map,
+            acct_map,
+            txns_map
+        )?;
+
+        //  An incoming `movement` here (margin profit) draws from no lot
+        for mvmt in mvmts.iter().filter(|mvmt| mvmt.amount < dec!(0)) {
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let raw_acct = raw_acct_map.get(&acct_map.get(&lot.account_key).unwrap().raw_key).unwrap();
+            if !settings.is_reported_account(raw_acct) { continue }
+            let lot_txn_num = lot.movements.borrow().first().map_or(txn_num, |first| first.transaction_key);
+
+            let proceeds = mvmt.proceeds_lk.get();
+            let cost_basis = mvmt.cost_basis_lk.get();
+            let adjustment = mvmt.wash_sale_adjustment.get();
+
+            rows.push(vec![
+                txn_num.to_string(),
+                settings.report_date(txn.date),
+                txn.user_memo.to_string(),
+                raw_acct.name.to_string(),
+                raw_acct.ticker.to_string(),
+                lot.id(),
+                lot_txn_num.to_string(),
+                settings.report_date(lot.date_for_basis_purposes),
+                precision.crypto(-mvmt.amount),
+                precision.fiat(proceeds),
+                precision.fiat(cost_basis),
+                precision.fiat(adjustment),
+                precision.fiat(proceeds + cost_basis + adjustment),
+                mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).abbr_string(),
+            ]);
+
+            total_proceeds += proceeds;
+            total_cost_basis += cost_basis;
+            total_adjustment += adjustment;
+        }
     }
-    wtr.flush().expect("Could not flush Writer, though file should exist and be complete");
+
+    let mut total_row = vec![String::new(); columns.len()];
+    total_row[0] = "Total".to_string();
+    total_row[9] = precision.fiat(total_proceeds);
+    total_row[10] = precision.fiat(total_cost_basis);
+    total_row[11] = precision.fiat(total_adjustment);
+    total_row[12] = precision.fiat(total_proceeds + total_cost_basis + total_adjustment);
+    rows.push(total_row);
+
+    write_rows_to_csv(settings, &settings.tax_year_file_name("C27_Lot_selection_audit.csv"), &rows)?;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,127 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+
+use crate::export::export_all::Exporter;
+use crate::export::export_json::Reports;
+
+
+/// The prefix of the name of an external exporter program (i.e., `cryptools-export-quickbooks`, for
+/// `--exporter quickbooks`).
+pub const EXTERNAL_EXPORTER_PREFIX: &str = "cryptools-export-";
+
+/// An output format written by another program, for `--exporter`: the program is given the reports as JSON (as in
+/// Reports.json) on its standard input, and is run in the `settings.export_path`, with `CRYPTOOLS_EXPORT_DIR`,
+/// `CRYPTOOLS_HOME_CURRENCY` and `CRYPTOOLS_TAX_YEAR` (if set) in its environment.  It fails if the program exits
+/// with an error.
+pub struct ExternalExporter {
+    name: String,
+    program: PathBuf,
+}
+
+impl ExternalExporter {
+
+    /// The exporter `name`: the `cryptools-export-NAME` program found on the PATH, or, if `name` is a path (i.e.,
+    /// `./my-exporter`), that program.
+    pub fn find(name: &str) -> Result<Self, Box<dyn Error>> {
+
+        let path = Path::new(name);
+
+        if path.components().count() > 1 {
+            if !path.is_file() { return Err(format!("The exporter program {} doesn't exist.", name).into()) }
+            //  It's run in the export directory, so a relative path wouldn't do
+            return Ok(ExternalExporter { name: name.to_string(), program: fs::canonicalize(path)? })
+        }
+
+        let file_name = format!("{}{}", EXTERNAL_EXPORTER_PREFIX, name);
+
+        match path_dirs().into_iter().map(|dir| dir.join(&file_name)).find(|program| program.is_file()) {
+            Some(program) => Ok(ExternalExporter { name: name.to_string(), program }),
+            None => {
+                let available = available_exporters();
+                let available = match available.is_empty() {
+                    true => "none were found".to_string(),
+                    false => format!("those found are {}", available.join(", ")),
+                };
+                Err(format!("The exporter {} wasn't found: no {} program is on the PATH ({}).", name, file_name, available).into())
+            }
+        }
+    }
+}
+
+impl Exporter for ExternalExporter {
+
+    fn name(&self) -> String {
+        format!("exporter {}", self.name)
+    }
+
+    fn export(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<(), Box<dyn Error>> {
+
+        let reports = Reports::from_maps(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .current_dir(&settings.export_path)
+            .env("CRYPTOOLS_EXPORT_DIR", &settings.export_path)
+            .env("CRYPTOOLS_HOME_CURRENCY", &settings.home_currency)
+            .stdin(Stdio::piped());
+
+        if let Some(tax_year) = settings.tax_year { command.env("CRYPTOOLS_TAX_YEAR", tax_year.to_string()); }
+
+        let mut child = command.spawn()
+            .map_err(|e| format!("{} couldn't be run: {}", self.program.display(), e))?;
+
+        //  Dropping stdin closes it, so the program sees the end of the JSON
+        let written = {
+            let mut stdin = BufWriter::new(child.stdin.take().unwrap());
+            serde_json::to_writer(&mut stdin, &reports).map_err(io::Error::from).and_then(|()| stdin.flush())
+        };
+
+        let status = child.wait()?;
+
+        if !status.success() { return Err(format!("{} exited with {}", self.program.display(), status).into()) }
+        written?;
+
+        Ok(())
+    }
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH").map(|paths| env::split_paths(&paths).collect()).unwrap_or_default()
+}
+
+/// The names of the external exporters on the PATH (i.e., `quickbooks`, for `cryptools-export-quickbooks`), sorted,
+/// each once.
+pub fn available_exporters() -> Vec<String> {
+
+    let mut names: Vec<String> = path_dirs().iter()
+        .filter_map(|dir| dir.read_dir().ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_prefix(EXTERNAL_EXPORTER_PREFIX).map(str::to_string))
+        .collect();
+
+    names.sort();
+    names.dedup();
+
+    names
+}
@@ -0,0 +1,382 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::error::Error;
+use std::io::{BufWriter, Write};
+
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::summary::{self, MonthlySeries, RunSummary, YearGains};
+
+use crate::export::export_all::Exporter;
+use crate::export::export_csv::create_report_file;
+use crate::export::export_json::Reports;
+
+
+const SHORT_TERM_COLOR: &str = "#4e79a7";
+const LONG_TERM_COLOR: &str = "#f28e2b";
+
+/// Clicking a column's header sorts the table by it (and clicking again reverses it), by each cell's `data-sort`
+/// value if it has one (a number, unlocalized), or else by its text.
+const SORT_SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable th").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var tbody = th.closest("table").tBodies[0];
+    var ascending = th.dataset.order !== "asc";
+    th.closest("tr").querySelectorAll("th").forEach(function (other) { delete other.dataset.order; });
+    th.dataset.order = ascending ? "asc" : "desc";
+    var key = function (row) {
+      var cell = row.cells[th.cellIndex];
+      return cell.dataset.sort !== undefined ? parseFloat(cell.dataset.sort) : cell.textContent.toLowerCase();
+    };
+    Array.from(tbody.rows)
+      .sort(function (a, b) { var x = key(a), y = key(b); return (x < y ? -1 : x > y ? 1 : 0) * (ascending ? 1 : -1); })
+      .forEach(function (row) { tbody.appendChild(row); });
+  });
+});
+"#;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em auto; max-width: 72em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+h2 { border-bottom: 1px solid #ccc; margin-top: 2em; }
+.settings { color: #666; }
+.cards { display: flex; flex-wrap: wrap; gap: 1em; }
+.card { border: 1px solid #ddd; border-radius: 6px; padding: 0.8em 1.2em; min-width: 10em; }
+.card .label { color: #666; font-size: 0.85em; }
+.card .value { font-size: 1.4em; font-weight: bold; }
+.charts { display: flex; flex-wrap: wrap; gap: 1em; }
+svg text { font-size: 11px; fill: #444; }
+table { border-collapse: collapse; margin-top: 0.5em; }
+th, td { padding: 0.25em 0.8em; border-bottom: 1px solid #eee; }
+th { background: #f4f4f4; cursor: pointer; text-align: left; white-space: nowrap; }
+th[data-order="asc"]::after { content: " \25B2"; }
+th[data-order="desc"]::after { content: " \25BC"; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+tr.total td { font-weight: bold; }
+"#;
+
+/// A cell of a table: its text, and the number it sorts by, if it's a number.
+struct Cell {
+    text: String,
+    sort: Option<Decimal>,
+}
+
+impl Cell {
+
+    fn text(text: impl ToString) -> Self {
+        Cell { text: text.to_string(), sort: None }
+    }
+
+    fn number(text: String, value: Decimal) -> Self {
+        Cell { text, sort: Some(value) }
+    }
+}
+
+/// A table that sorts by any column, with its totals (if any) in a footer that stays put.
+fn table(header: &[&str], rows: &[Vec<Cell>], totals: Option<Vec<Cell>>) -> String {
+
+    let row_html = |cells: &[Cell]| -> String {
+        cells.iter().map(|cell| match cell.sort {
+            Some(value) => format!(r#"<td class="number" data-sort="{}">{}</td>"#, value, escape(&cell.text)),
+            None => format!("<td>{}</td>", escape(&cell.text)),
+        }).collect()
+    };
+
+    let mut html = String::from(r#"<table class="sortable"><thead><tr>"#);
+
+    for column in header.iter() {
+        html += &format!("<th>{}</th>", escape(column));
+    }
+
+    html += "</tr></thead><tbody>\n";
+
+    for row in rows.iter() {
+        html += &format!("<tr>{}</tr>\n", row_html(row));
+    }
+
+    html += "</tbody>";
+
+    if let Some(totals) = totals {
+        html += &format!(r#"<tfoot><tr class="total">{}</tr></tfoot>"#, row_html(&totals));
+    }
+
+    html += "</table>\n";
+
+    html
+}
+
+/// A bar chart of the short- and long-term gain or loss of each year, side by side, with losses below the axis.
+fn gains_by_year_chart(years: &[YearGains], home_currency: &str) -> String {
+
+    let (width, height, left, top, bottom) = (640.0, 260.0, 70.0, 30.0, 30.0);
+    let plot_height = height - top - bottom;
+
+    let values: Vec<f64> = years.iter().flat_map(|year| [year.realized_st, year.realized_lt]).map(|v| v.to_f64().unwrap_or(0.0)).collect();
+    let high = values.iter().cloned().fold(0.0, f64::max);
+    let low = values.iter().cloned().fold(0.0, f64::min);
+    let range = if high - low > 0.0 { high - low } else { 1.0 };
+
+    let y = |value: f64| top + (high - value) / range * plot_height;
+    let group_width = (width - left) / years.len().max(1) as f64;
+    let bar_width = (group_width * 0.35).min(40.0);
+
+    let mut svg = format!(r#"<svg width="{}" height="{}" role="img" aria-label="Realized gain or loss by year">"#, width, height);
+
+    svg += &format!(r#"<text x="{}" y="14">Realized gain/loss by year ({})</text>"#, left, escape(home_currency));
+    svg += &format!(r#"<text x="{}" y="{:.1}" text-anchor="end">{:.0}</text>"#, left - 6.0, y(high) + 4.0, high);
+    svg += &format!(r#"<text x="{}" y="{:.1}" text-anchor="end">{:.0}</text>"#, left - 6.0, y(low) + 4.0, low);
+
+    for (idx, year) in years.iter().enumerate() {
+
+        let center = left + group_width * (idx as f64 + 0.5);
+
+        for (offset, value, color, term) in [
+            (-bar_width, year.realized_st, SHORT_TERM_COLOR, "Short-term"),
+            (0.0, year.realized_lt, LONG_TERM_COLOR, "Long-term"),
+        ] {
+            let value = value.to_f64().unwrap_or(0.0);
+            svg += &format!(
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"><title>{} {}: {:.2}</title></rect>"#,
+                center + offset, y(value.max(0.0)), bar_width, (y(value.min(0.0)) - y(value.max(0.0))).max(0.5), color,
+                year.year, term, value,
+            );
+        }
+
+        svg += &format!(r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#, center, height - 10.0, year.year);
+    }
+
+    svg += &format!(r##"<line x1="{}" x2="{}" y1="{:.1}" y2="{:.1}" stroke="#999"/>"##, left, width, y(0.0), y(0.0));
+    svg += &format!(
+        r#"<rect x="{}" y="4" width="10" height="10" fill="{}"/><text x="{}" y="13">Short-term</text><rect x="{}" y="4" width="10" height="10" fill="{}"/><text x="{}" y="13">Long-term</text>"#,
+        width - 190.0, SHORT_TERM_COLOR, width - 176.0, width - 100.0, LONG_TERM_COLOR, width - 86.0,
+    );
+    svg += "</svg>";
+
+    svg
+}
+
+/// A small line chart of the amount of `ticker` held at the end of each month.
+fn holdings_chart(ticker: &str, months: &[(i32, u32)], held: &[Decimal]) -> String {
+
+    let (width, height, left, top, bottom) = (300.0, 130.0, 8.0, 24.0, 20.0);
+    let plot_width = width - left * 2.0;
+    let plot_height = height - top - bottom;
+
+    let values: Vec<f64> = held.iter().map(|v| v.to_f64().unwrap_or(0.0)).collect();
+    let high = values.iter().cloned().fold(0.0, f64::max);
+    let range = if high > 0.0 { high } else { 1.0 };
+    let step = if values.len() > 1 { plot_width / (values.len() - 1) as f64 } else { 0.0 };
+
+    let points: Vec<String> = values.iter().enumerate()
+        .map(|(idx, value)| format!("{:.1},{:.1}", left + step * idx as f64, top + (1.0 - value / range) * plot_height))
+        .collect();
+
+    let month_label = |(year, month): (i32, u32)| format!("{}-{:02}", year, month);
+
+    let mut svg = format!(r#"<svg width="{}" height="{}" role="img" aria-label="{} held by month">"#, width, height, escape(ticker));
+
+    svg += &format!(r#"<text x="{}" y="14"><tspan font-weight="bold">{}</tspan> held (max {})</text>"#, left, escape(ticker),
+        held.iter().max().copied().unwrap_or_default().normalize());
+    svg += &format!(r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#, SHORT_TERM_COLOR, points.join(" "));
+    svg += &format!(r##"<line x1="{}" x2="{}" y1="{}" y2="{}" stroke="#ccc"/>"##, left, width - left, top + plot_height, top + plot_height);
+
+    if let (Some(first), Some(last)) = (months.first(), months.last()) {
+        svg += &format!(r#"<text x="{}" y="{}">{}</text>"#, left, height - 6.0, month_label(*first));
+        svg += &format!(r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, width - left, height - 6.0, month_label(*last));
+    }
+
+    svg += "</svg>";
+
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes a self-contained HTML dashboard of the run (Dashboard.html), for browsing the results without opening the
+/// CSV reports: the headline figures, charts of the gains and losses by year and of each currency held over time,
+/// and tables (sortable by any column) of the gains and losses by year, the income by type, the accounts, the
+/// transactions and the lines of Form 8949.  With a tax year set, only that year's gains, income and transactions
+/// are included.  It needs nothing but a browser: the styles, charts (SVG) and sorting script are all inline.
+pub fn _1_dashboard_to_html(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let fiat = |value: Decimal| Cell::number(settings.output_precision.fiat(value), value);
+    let crypto = |value: Decimal| Cell::number(settings.output_precision.crypto(value), value);
+    let in_tax_year = |year: i32| settings.tax_year.is_none_or(|tax_year| year == tax_year);
+    let home_currency = &settings.home_currency;
+
+    let run_summary = RunSummary::from_maps(home_currency, settings.long_term_days, settings.fiscal_year_start_month, raw_acct_map, acct_map, ars, txns_map)?;
+    let series = MonthlySeries::from_maps(home_currency, raw_acct_map, acct_map, ars, txns_map)?;
+    let reports = Reports::from_maps(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let years: Vec<YearGains> = summary::gains_by_year(home_currency, settings.long_term_days, settings.fiscal_year_start_month, raw_acct_map, acct_map, ars, txns_map)?
+        .into_iter().filter(|year| in_tax_year(year.year)).collect();
+    let incomes: Vec<summary::IncomeTypeTotal> = summary::income_by_type_and_year(home_currency, settings.fiscal_year_start_month, raw_acct_map, acct_map, ars, txns_map)?
+        .into_iter().filter(|total| in_tax_year(total.year)).collect();
+
+    let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html += "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n";
+    html += "<title>Cryptools dashboard</title>\n";
+    html += &format!("<style>{}</style>\n</head>\n<body>\n", STYLE);
+
+    html += "<h1>Cryptools dashboard</h1>\n";
+    html += &format!(r#"<p class="settings">{} &middot; Home currency: {} &middot; Like-kind treatment: {}{}</p>"#,
+        escape(&settings.costing_method.to_string()),
+        escape(home_currency),
+        if settings.lk_treatment_enabled { format!("through {}", settings.report_date(settings.lk_cutoff_date)) } else { "none".to_string() },
+        settings.tax_year.map_or(String::new(), |year| format!(" &middot; Tax year {}", year)),
+    );
+    html += "\n";
+
+    //  The headline figures
+
+    let (total_st, total_lt) = years.iter().fold((dec!(0), dec!(0)), |(st, lt), year| (st + year.realized_st, lt + year.realized_lt));
+    let total_income: Decimal = incomes.iter().map(|total| total.income).sum();
+
+    let mut cards: Vec<(&str, String)> = vec![
+        ("Transactions", run_summary.txn_count.to_string()),
+        ("Short-term gain/loss", settings.output_precision.fiat(total_st)),
+        ("Long-term gain/loss", settings.output_precision.fiat(total_lt)),
+        ("Income", settings.output_precision.fiat(total_income)),
+        ("Open lots", run_summary.open_lots.to_string()),
+    ];
+    if let Some((first, last)) = run_summary.date_range {
+        cards.insert(1, ("Dates", format!("{} to {}", settings.report_date(first), settings.report_date(last))));
+    }
+
+    html += r#"<div class="cards">"#;
+    for (label, value) in cards.iter() {
+        html += &format!(r#"<div class="card"><div class="label">{}</div><div class="value">{}</div></div>"#, escape(label), escape(value));
+    }
+    html += "</div>\n";
+
+    //  The charts
+
+    html += "<h2>Charts</h2>\n";
+    if !years.is_empty() {
+        html += &format!("<div class=\"charts\">{}</div>\n", gains_by_year_chart(&years, home_currency));
+    }
+    html += r#"<div class="charts">"#;
+    for (ticker, held) in series.holdings.iter().filter(|(ticker, _)| ticker != home_currency) {
+        html += &holdings_chart(ticker, &series.months, held);
+    }
+    html += "</div>\n";
+
+    //  The tables
+
+    html += &format!("<h2>Realized gains and losses by year ({})</h2>\n", escape(home_currency));
+    let rows: Vec<Vec<Cell>> = years.iter()
+        .map(|year| vec![Cell::text(year.year), fiat(year.realized_st), fiat(year.realized_lt), fiat(year.realized_total())])
+        .collect();
+    html += &table(&["Year", "Short-term", "Long-term", "Total"], &rows,
+        Some(vec![Cell::text("Total"), fiat(total_st), fiat(total_lt), fiat(total_st + total_lt)]));
+
+    html += &format!("<h2>Income by type ({})</h2>\n", escape(home_currency));
+    let rows: Vec<Vec<Cell>> = incomes.iter()
+        .map(|total| vec![
+            Cell::text(total.year),
+            Cell::text(total.income_type.map_or("Other".to_string(), |income_type| income_type.to_string())),
+            fiat(total.income),
+        ])
+        .collect();
+    html += &table(&["Year", "Income type", "Income"], &rows, Some(vec![Cell::text("Total"), Cell::text(""), fiat(total_income)]));
+
+    html += "<h2>Accounts</h2>\n";
+    let rows: Vec<Vec<Cell>> = reports.accounts.iter()
+        .map(|acct| vec![
+            Cell::number(acct.account_number.to_string(), Decimal::from(acct.account_number)),
+            Cell::text(&acct.name),
+            Cell::text(&acct.ticker),
+            crypto(acct.balance),
+            fiat(acct.cost_basis),
+        ])
+        .collect();
+    html += &table(&["#", "Account", "Ticker", "Balance", &format!("Cost basis ({})", home_currency)], &rows, None);
+
+    html += "<h2>Transactions</h2>\n";
+    let rows: Vec<Vec<Cell>> = reports.transactions.iter()
+        .filter(|txn| settings.is_in_tax_year(txn.date))
+        .map(|txn| {
+            let gain_or_loss: Decimal = txn.movements.iter().map(|mvmt| mvmt.gain_or_loss).sum();
+            let income: Decimal = txn.movements.iter().map(|mvmt| mvmt.income).sum();
+            let expense: Decimal = txn.movements.iter().map(|mvmt| mvmt.expense).sum();
+            vec![
+                Cell::number(txn.txn_number.to_string(), Decimal::from(txn.txn_number)),
+                Cell::text(settings.report_date(txn.date)),
+                Cell::text(&txn.memo),
+                fiat(txn.proceeds),
+                fiat(gain_or_loss),
+                fiat(income),
+                fiat(expense),
+            ]
+        })
+        .collect();
+    html += &table(&["Txn", "Date", "Memo", "Proceeds", "Gain/loss", "Income", "Expense"], &rows, None);
+
+    html += "<h2>Form 8949</h2>\n";
+    let rows: Vec<Vec<Cell>> = reports.gains_and_losses.iter()
+        .filter(|line| settings.is_in_tax_year(line.date_sold))
+        .map(|line| vec![
+            Cell::text(&line.term),
+            Cell::text(&line.description),
+            Cell::text(&line.date_acquired),
+            Cell::text(settings.report_date(line.date_sold)),
+            fiat(line.proceeds),
+            fiat(line.cost_basis),
+            fiat(line.gain_or_loss),
+        ])
+        .collect();
+    html += &table(&["Term", "Description", "Acquired", "Sold", "Proceeds", "Cost basis", "Gain/loss"], &rows, None);
+
+    html += &format!("<script>{}</script>\n</body>\n</html>\n", SORT_SCRIPT);
+
+    let full_path: PathBuf = [
+        settings.export_path.clone(),
+        PathBuf::from(settings.report_file_name(&settings.tax_year_file_name("Dashboard.html"))),
+    ].iter().collect();
+
+    let mut file = BufWriter::new(create_report_file(settings, &full_path)?);
+    file.write_all(html.as_bytes())?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The HTML dashboard (see `_1_dashboard_to_html()`), as an `Exporter`, for `--html`.
+pub struct HtmlDashboard;
+
+impl Exporter for HtmlDashboard {
+
+    fn name(&self) -> String {
+        "Dashboard.html".to_string()
+    }
+
+    fn export(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<(), Box<dyn Error>> {
+        _1_dashboard_to_html(settings, raw_acct_map, acct_map, ars, txns_map)
+    }
+}
@@ -1,73 +1,144 @@
 // Copyright (c) 2017-2023, scoobybejesus
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
-use std::fs::OpenOptions;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::Path;
 use std::error::Error;
-use std::io::prelude::Write;
+use std::io::Write;
 
+use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crptls::transaction::{Transaction, ActionRecord, Polarity, TxType};
+use crptls::transaction::{Transaction, ActionRecord, IncomeType, Polarity, TxKind, TxType};
 use crptls::account::{Account, RawAccount, Term};
 use crptls::core_functions::ImportProcessParameters;
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::derivatives;
+use crptls::like_kind;
 
+use crate::export::export_csv::{is_reported_txn, report_writer, write_rows_to_csv};
+use crate::export::export_txt;
 
-pub fn prepare_non_lk_journal_entries(
-    settings: &ImportProcessParameters,
-    raw_acct_map: &HashMap<u16, RawAccount>,
-    acct_map: &HashMap<u16, Account>,
-    ars: &HashMap<u32, ActionRecord>,
-    txns_map: &HashMap<u32, Transaction>,
-)  -> Result<(), Box<dyn Error>> {
 
-    let file_name = PathBuf::from("J1_Journal_Entries.txt");
-    let path = PathBuf::from(&settings.export_path.clone());
-    let full_path: PathBuf = [path, file_name].iter().collect();
+/// A line of a journal entry, debiting or crediting an account.
+struct JournalLine {
+    /// The account's description in J1_Journal_Entries.txt.
+    label: String,
+    account: LedgerAccount,
+    is_debit: bool,
+    amount: Decimal,
+}
 
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(full_path)?;
+/// The account of a `JournalLine` in a ledger journal.
+enum LedgerAccount {
+    /// One of the user's `Account`s, by the `ActionRecord` of the quantity received or given up.
+    Holding { ar_num: u32 },
+    /// Any other account, by its default name (i.e., `Income:CapitalGains:LongTerm`).
+    Other(String),
+}
 
-    writeln!(file, "Journal Entries
-\nCosting method used: {}.
-Home currency: {}
-Enable like-kind treatment: {}",
-        settings.costing_method,
-        settings.home_currency,
-        settings.lk_treatment_enabled
-    )?;
+/// What a `JournalEntry` is the entry of.
+enum EntrySource {
+    Txn(u32),
+    /// A cost of the `--costs` file, by its line (see `fiat_costs`), or with the (fiscal) `year`, its depreciation in
+    /// that year.
+    Cost { line: usize, date: NaiveDate, description: String, year: Option<i32> },
+}
 
-    if settings.lk_treatment_enabled {
-        writeln!(file, "Like-kind cut-off date: {}.",
-            settings.lk_cutoff_date
-        )?;
+/// The journal entry of a single `Transaction`, or of a cost.
+struct JournalEntry {
+    source: EntrySource,
+    lines: Vec<JournalLine>,
+    /// The income received, if any, by its `IncomeType`.
+    income: Option<(Option<IncomeType>, Decimal)>,
+    /// The gain (or loss) deferred, for a like-kind exchange.
+    deferred_gain: Option<Decimal>,
+}
+
+impl JournalEntry {
+
+    fn push(&mut self, label: &str, account: LedgerAccount, is_debit: bool, amount: Decimal) {
+        self.lines.push(JournalLine { label: label.to_string(), account, is_debit, amount });
     }
 
-    let note = "
-Note: Home currency account activity may be better represented as equity-type accounts,
-depending on the bookkeeping practices you employ.";
+    fn other(&mut self, label: &str, ledger_name: &str, is_debit: bool, amount: Decimal) {
+        self.push(label, LedgerAccount::Other(ledger_name.to_string()), is_debit, amount);
+    }
+
+    /// The entry's date, its description (a `Transaction`'s memo, or else its auto memo), its note (the auto memo) and
+    /// its number in the journals (the `Transaction`'s, or `C` and the cost's line, and the year of a depreciation,
+    /// i.e., `C5-2024`).
+    fn text(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<(NaiveDate, String, String, String), Box<dyn Error>> {
+        match self.source {
+            EntrySource::Txn(txn_num) => {
+                let txn = txns_map.get(&txn_num).unwrap();
+                let auto_memo = txn.get_auto_memo(ars, raw_acct_map, acct_map, &settings.home_currency)?;
+                let description = if txn.user_memo.trim().is_empty() { auto_memo.clone() } else { txn.user_memo.clone() };
+                Ok((txn.date, description, auto_memo, txn_num.to_string()))
+            }
+            EntrySource::Cost { line, date, ref description, year } => {
+                let note = settings.report_language.fill("Cost on line {} of the costs file", &[&line]);
+                let number = year.map_or_else(|| format!("C{}", line), |year| format!("C{}-{}", line, year));
+                Ok((date, description.clone(), note, number))
+            }
+        }
+    }
+}
 
-    writeln!(file, "{}", note)?;
+/// The journal entry of every `Transaction` dated in the tax year (if one is set), using like-kind values throughout:
+/// with like-kind treatment, a like-kind exchange credits the basis given up and debits the same basis received, so
+/// no gain is recognized.
+fn journal_entries(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
 
     let length = txns_map.len();
 
+    //  The gain deferred in each like-kind exchange, by `Transaction`
+    let deferred_gains: HashMap<u32, Decimal> = if settings.lk_treatment_enabled {
+        like_kind::like_kind_exchanges(&settings.home_currency, settings.lk_cutoff_date, &settings.lk_scope, raw_acct_map, acct_map, ars, txns_map)?
+            .into_iter()
+            .map(|exchange| (exchange.tx_num, exchange.deferred_gain))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let accounts = &settings.journal_accounts;
+    let long_term_days = accounts.long_term_days.unwrap_or(settings.long_term_days);
+    let lang = settings.report_language;
+
+    let mut entries: Vec<JournalEntry> = Vec::new();
+
     for txn_num in 1..=length {
 
         let txn_num = txn_num as u32;
         let txn = txns_map.get(&(txn_num)).unwrap();
-        let date = txn.date;
-        let user_memo = txn.user_memo.to_string();
-        let auto_memo = txn.get_auto_memo(ars, raw_acct_map,acct_map, &settings.home_currency)?;
 
-        writeln!(file, "\n====================================================================================================\n")?;
+        if !settings.is_reported_txn(txn) { continue }
+        if !is_reported_txn(settings, txn, raw_acct_map, acct_map, ars) { continue }
+
+        let mut entry = JournalEntry {
+            source: EntrySource::Txn(txn_num),
+            lines: Vec::new(),
+            income: None,
+            deferred_gain: deferred_gains.get(&txn_num).copied(),
+        };
 
-        let mut cost_basis_ic: Option<Decimal> = None;
-        let mut cost_basis_og: Option<Decimal> = None;
+        let mut cost_basis_ic: Option<(u32, Decimal)> = None;
+        let mut cost_basis_og: Option<(u32, Decimal)> = None;
 
         let mut acct_string_ic = "".to_string();
         let mut acct_string_og = "".to_string();
@@ -79,19 +150,19 @@ depending on the bookkeeping practices you employ.";
             let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
 
             if ar.direction() == Polarity::Incoming {
-                cost_basis_ic = Some(ar.cost_basis_in_ar());
+                cost_basis_ic = Some((*ar_num, ar.lk_cost_basis_in_ar()));
                 acct_string_ic = format!("{} - {} ({}) (#{})",
                     raw_acct.name,
                     raw_acct.ticker,
-                    raw_acct.margin_string(),
+                    lang.text(&raw_acct.margin_string()),
                     raw_acct.account_num,
                 );
             } else {
-                cost_basis_og = Some(ar.cost_basis_in_ar());
+                cost_basis_og = Some((*ar_num, ar.lk_cost_basis_in_ar()));
                 acct_string_og = format!("{} - {} ({}) (#{})",
                     raw_acct.name,
                     raw_acct.ticker,
-                    raw_acct.margin_string(),
+                    lang.text(&raw_acct.margin_string()),
                     raw_acct.account_num,
                 );
             }
@@ -105,10 +176,12 @@ depending on the bookkeeping practices you employ.";
         let mut amount_st = dec!(0);
         let mut proceeds_st = dec!(0);
         let mut cost_basis_st = dec!(0);
+        let mut adjustment_st = dec!(0);   //  Wash sale loss disallowed
 
         let mut amount_lt = dec!(0);
         let mut proceeds_lt = dec!(0);
         let mut cost_basis_lt = dec!(0);
+        let mut adjustment_lt = dec!(0);
 
         let mut income = dec!(0);
         let mut expense = dec!(0);
@@ -130,18 +203,20 @@ depending on the bookkeeping practices you employ.";
                 };
             }
 
-            let term = mvmt.get_term(acct_map, ars, txns_map);
+            let term = mvmt.get_term(acct_map, ars, txns_map, long_term_days);
 
             if term == Term::LT {
                 amount_lt += mvmt.amount;
                 proceeds_lt += mvmt.proceeds_lk.get();
                 cost_basis_lt += mvmt.cost_basis_lk.get();
+                adjustment_lt += mvmt.wash_sale_adjustment.get();
                 if term_lt.is_none() { term_lt = Some(term) }
             } else {
                 assert_eq!(term, Term::ST);
                 amount_st += mvmt.amount;
                 proceeds_st += mvmt.proceeds_lk.get();
                 cost_basis_st += mvmt.cost_basis_lk.get();
+                adjustment_st += mvmt.wash_sale_adjustment.get();
                 if term_st.is_none() {
                     term_st = Some(term);
                 }
@@ -163,134 +238,955 @@ depending on the bookkeeping practices you employ.";
             cost_basis_lt = dec!(0);
         }
 
-        let lt_gain_loss = proceeds_lt + cost_basis_lt;
-        let st_gain_loss = proceeds_st + cost_basis_st;
-
-        let mut debits = dec!(0);
-        let mut credits = dec!(0);
+        let lt_gain_loss = proceeds_lt + cost_basis_lt + adjustment_lt;
+        let st_gain_loss = proceeds_st + cost_basis_st + adjustment_st;
 
-        if let Some(cb) = cost_basis_ic {
-            debits += cb;
-            writeln!(file, "{:50}{:5}{:>20.2}{:5}{:>20}",
-            acct_string_ic,
-            "",
-            cb.to_string().as_str().parse::<f32>()?,
-            "",
-            "",
-            )?;
+        if let Some((ar_num, cb)) = cost_basis_ic {
+            entry.push(&acct_string_ic, LedgerAccount::Holding { ar_num }, true, cb);
         }
 
-        if let Some(cb) = cost_basis_og {
-            credits += cb;
-            writeln!(file, "{:50}{:5}{:>20}{:5}{:>20.2}",
-            acct_string_og,
-            "",
-            "",
-            "",
-            cb.to_string().as_str().parse::<f32>()?,
-            )?;
+        if let Some((ar_num, cb)) = cost_basis_og {
+            entry.push(&acct_string_og, LedgerAccount::Holding { ar_num }, false, cb);
         }
 
         if lt_gain_loss != dec!(0) {
 
             if lt_gain_loss > dec!(0) {
-                credits += lt_gain_loss.abs();
-                let ltg_string = format!("Long-term gain disposing {}", amount_lt.abs());
-                writeln!(file, "{:50}{:5}{:>20}{:5}{:>20.2}",
-                ltg_string,
-                "",
-                "",
-                "",
-                lt_gain_loss.to_string().as_str().parse::<f32>()?,
-                )?;
+                let ltg_string = lang.fill("Long-term gain disposing {}", &[&settings.output_precision.crypto(amount_lt.abs())]);
+                entry.other(&ltg_string, accounts.gain_loss_account(Term::LT, true), false, lt_gain_loss);
             } else {
-                debits += lt_gain_loss.abs();
-                let ltl_string = format!("Long-term loss disposing {}", amount_lt.abs());
-                writeln!(file, "{:50}{:5}{:>20.2}{:5}{:>20}",
-                ltl_string,
-                "",
-                lt_gain_loss.abs().to_string().as_str().parse::<f32>()?,
-                "",
-                "",
-                )?;
+                let ltl_string = lang.fill("Long-term loss disposing {}", &[&settings.output_precision.crypto(amount_lt.abs())]);
+                entry.other(&ltl_string, accounts.gain_loss_account(Term::LT, false), true, lt_gain_loss.abs());
             }
         }
 
         if st_gain_loss != dec!(0) {
 
             if st_gain_loss > dec!(0) {
-                credits += st_gain_loss.abs();
-                let stg_string = format!("Short-term gain disposing {}", amount_st.abs());
-                writeln!(file, "{:50}{:5}{:>20}{:5}{:>20.2}",
-                stg_string,
-                "",
-                "",
-                "",
-                st_gain_loss.to_string().as_str().parse::<f32>()?,
-                )?;
+                let stg_string = lang.fill("Short-term gain disposing {}", &[&settings.output_precision.crypto(amount_st.abs())]);
+                entry.other(&stg_string, accounts.gain_loss_account(Term::ST, true), false, st_gain_loss);
             } else {
-                debits += st_gain_loss.abs();
-                let stl_string = format!("Short-term loss disposing {}", amount_st.abs());
-                writeln!(file, "{:50}{:5}{:>20.2}{:5}{:>20}",
-                stl_string,
-                "",
-                st_gain_loss.abs().to_string().as_str().parse::<f32>()?,
-                "",
-                "",
-                )?;
+                let stl_string = lang.fill("Short-term loss disposing {}", &[&settings.output_precision.crypto(amount_st.abs())]);
+                entry.other(&stl_string, accounts.gain_loss_account(Term::ST, false), true, st_gain_loss.abs());
             }
         }
 
+        let cost_basis_ic = cost_basis_ic.map_or(dec!(0), |(_, cb)| cb);
+
+        // A synthetic acquisition stands in for missing history, so its basis is offset against equity
+        if txn.kind == TxKind::Synthetic {
+            entry.other(lang.text("Synthetic acquisition (opening balance equity)"), "Equity:OpeningBalances", false, cost_basis_ic);
+        }
+
+        // An inheritance isn't income, so its stepped-up basis is offset against equity as well
+        if txn.kind == TxKind::Inherited {
+            entry.other(lang.text("Inheritance (stepped-up basis, equity)"), "Equity:Inheritances", false, cost_basis_ic);
+        }
+
+        // A lot carried forward from a prior run was already on the books, so its basis is offset against equity too
+        if txn.kind.is_carried_forward() {
+            entry.other(lang.text("Carried-forward lot (opening balance equity)"), "Equity:OpeningBalances", false, cost_basis_ic);
+        }
+
+        // A wash sale's disallowed loss is deferred into the replacement lot's basis, and released when it is acquired.
+        // A superficial loss is instead added to the ACB, so it's released by the later disposals costed from it.
+        let wash_sale_adjustment: Decimal = txn.action_record_idx_vec.iter()
+            .flat_map(|ar_num| ars.get(ar_num).unwrap().get_mvmts_in_ar_in_lot_date_order())
+            .map(|mvmt| mvmt.wash_sale_adjustment.get())
+            .sum();
+
+        if wash_sale_adjustment > dec!(0) {
+            entry.other(
+                lang.text(if settings.costing_method == InventoryCostingMethod::AdjustedCostBase {
+                    "Superficial loss denied (added to the ACB)"
+                } else {
+                    "Wash sale loss disallowed (deferred)"
+                }),
+                "Assets:DeferredLosses",
+                true,
+                wash_sale_adjustment,
+            );
+        } else if wash_sale_adjustment < dec!(0) {
+            entry.other(lang.text("Wash sale loss added to replacement basis"), "Assets:DeferredLosses", false, wash_sale_adjustment.abs());
+        }
+
+        // A derivative's profit or loss is neither income nor an expense
+        match derivatives::derivative_pnl(txn, &settings.home_currency, raw_acct_map, acct_map, ars) {
+            Some(pnl) if pnl.pnl > dec!(0) => entry.other(lang.text("Derivative gain"), "Income:Derivatives", false, pnl.pnl),
+            Some(pnl) if pnl.pnl < dec!(0) => entry.other(lang.text("Derivative loss"), "Income:Derivatives", true, pnl.pnl.abs()),
+            _ => {}
+        }
+
         if income != dec!(0) {
-            credits += income;
-            writeln!(file, "{:50}{:5}{:>20}{:5}{:>20.2}",
-            "Income",
-            "",
-            "",
-            "",
-            income.to_string().as_str().parse::<f32>()?,
-            )?;
+            let ledger_name = accounts.income_account(txn.income_type);
+            entry.other(&income_label(settings, txn.income_type), &ledger_name, false, income);
+            entry.income = Some((txn.income_type, income));
         }
 
         if expense != dec!(0) {
-            debits += expense.abs();
-            writeln!(file, "{:50}{:5}{:>20.2}{:5}{:>20}",
-            "Expense",
-            "",
-            expense.abs().to_string().as_str().parse::<f32>()?,
-            "",
-            "",
+            let (label, ledger_name) = match (txn.kind, &txn.expense_category) {
+                (TxKind::Gift, _) => (lang.text("Gift (basis carries over to the recipient)").to_string(), "Expenses:Gifts".to_string()),
+                (TxKind::Donation, _) => (lang.text("Charitable donation").to_string(), "Expenses:Donations".to_string()),
+                (_, Some(category)) => (lang.fill("Expense ({})", &[category]), format!("Expenses:Crypto:{}", account_segment(category))),
+                (_, None) => (lang.text("Expense").to_string(), "Expenses:Crypto".to_string()),
+            };
+            entry.other(&label, &ledger_name, true, expense.abs());
+        }
+
+        entries.push(entry);
+    }
+
+    entries.extend(cost_entries(settings));
+
+    Ok(entries)
+}
+
+/// The journal entries of the costs of the `--costs` file dated in the tax year (if one is set): a cost expensed when
+/// paid debits its category's expense account, and equipment is debited to an asset account of its category and
+/// depreciated at the end of each (fiscal) year (see `FiatCost::expensed_in_year()`).  Every cost is paid from cash.
+fn cost_entries(settings: &ImportProcessParameters) -> Vec<JournalEntry> {
+
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    let lang = settings.report_language;
+
+    let entry = |line: usize, date: NaiveDate, description: String, year: Option<i32>| JournalEntry {
+        source: EntrySource::Cost { line, date, description, year },
+        lines: Vec::new(),
+        income: None,
+        deferred_gain: None,
+    };
+
+    for cost in settings.fiat_costs.iter() {
+
+        let category = account_segment(&cost.category);
+        let description = if cost.memo.trim().is_empty() { cost.category.clone() } else { cost.memo.clone() };
+
+        if settings.is_in_tax_year(cost.date) {
+
+            let mut paid = entry(cost.line, cost.date, description.clone(), None);
+
+            match cost.depreciation_years {
+                Some(_) => paid.other(&lang.fill("Equipment ({})", &[&cost.category]), &format!("Assets:Equipment:{}", category), true, cost.amount),
+                None => paid.other(&lang.fill("Expense ({})", &[&cost.category]), &format!("Expenses:{}", category), true, cost.amount),
+            }
+            paid.other(lang.text("Cash"), "Assets:Cash", false, cost.amount);
+
+            entries.push(paid);
+        }
+
+        if cost.depreciation_years.is_none() { continue }
+
+        for year in cost.expense_years(settings.fiscal_year_start_month) {
+
+            let year_end = settings.fiscal_year_dates(year).1;
+            let depreciation = cost.expensed_in_year(year, settings.fiscal_year_start_month);
+
+            if !settings.is_in_tax_year(year_end) || depreciation.is_zero() { continue }
+
+            let mut depreciated = entry(cost.line, year_end, lang.fill("Depreciation of {}", &[&description]), Some(year));
+            depreciated.other(&lang.fill("Depreciation ({})", &[&cost.category]), &format!("Expenses:Depreciation:{}", category), true, depreciation);
+            depreciated.other(&lang.fill("Accumulated depreciation ({})", &[&cost.category]),
+                &format!("Assets:AccumulatedDepreciation:{}", category), false, depreciation);
+
+            entries.push(depreciated);
+        }
+    }
+
+    entries
+}
+
+/// Writes a journal entry for every `Transaction` (J1_Journal_Entries.txt), using like-kind values throughout (see
+/// `journal_entries()`), with the gain deferred in a like-kind exchange noted beneath it.
+pub fn prepare_journal_entries(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+)  -> Result<(), Box<dyn Error>> {
+
+    let mut file = report_writer(settings, &settings.tax_year_file_name("J1_Journal_Entries.txt"))?;
+    let lang = settings.report_language;
+
+    export_txt::write_report_header(&mut file, settings, lang.text("Journal Entries"))?;
+
+    if let Some(year) = settings.tax_year {
+        writeln!(file, "{}", lang.fill("Tax year: {} (transactions dated {} only).", &[&year, &settings.fiscal_year_span(year)]))?;
+    }
+
+    let note = lang.text("Note: Home currency account activity may be better represented as equity-type accounts,
+depending on the bookkeeping practices you employ.");
+
+    writeln!(file, "\n{}", note)?;
+
+    //  Income by `IncomeType`, in the order first received
+    let mut income_subtotals: Vec<(Option<IncomeType>, Decimal)> = Vec::new();
+
+    let entries = journal_entries(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    for entry in entries.iter() {
+
+        writeln!(file, "\n====================================================================================================\n")?;
+
+        for line in entry_lines(settings, entry, raw_acct_map, acct_map, ars, txns_map)? {
+            writeln!(file, "{}", line)?;
+        }
+
+        if let Some((income_type, income)) = entry.income {
+            match income_subtotals.iter_mut().find(|(subtotal_type, _)| *subtotal_type == income_type) {
+                Some((_, subtotal)) => *subtotal += income,
+                None => income_subtotals.push((income_type, income)),
+            }
+        }
+    }
+
+    //  Only worth a summary when some income was given an incomeType
+    if income_subtotals.iter().any(|(income_type, _)| income_type.is_some()) {
+
+        income_subtotals.sort_by_key(|(income_type, _)| (income_type.is_none(), *income_type));
+
+        writeln!(file, "\n====================================================================================================\n")?;
+        writeln!(file, "{}\n", lang.text("Income by type"))?;
+
+        for (income_type, subtotal) in income_subtotals.iter() {
+            writeln!(file, "{:50}{:5}{:>20}{:5}{:>20}",
+                if income_type.is_some() { income_label(settings, *income_type) } else { lang.text("Other income").to_string() },
+                "",
+                "",
+                "",
+                settings.output_precision.fiat(*subtotal),
             )?;
         }
+    }
 
-        writeln!(file, "{:50}{:5}{:>20}{:5}{:>20}",
-            "",
-            "",
-            "--------------------",
-            "",
-            "--------------------",
-        )?;
+    file.flush()?;
 
-        writeln!(file, "{:50}{:5}{:>20.2}{:5}{:>20.2}",
-            "    Totals",
-            "",
-            debits.to_string().as_str().parse::<f32>()?,
-            "",
-            credits.to_string().as_str().parse::<f32>()?,
-        )?;
+    if settings.ledger_export {
+        ledger_journal_to_file(settings, &entries, raw_acct_map, acct_map, ars, txns_map)?;
+    }
 
-        writeln!(file, "\n    (Txn {} on {}. {}. {})",
-            txn_num,
-            date,
-            user_memo,
-            auto_memo,
-        )?;
+    if settings.beancount_export {
+        beancount_journal_to_file(settings, &entries, raw_acct_map, acct_map, ars, txns_map)?;
+    }
+
+    if settings.journal_csv_export {
+        general_journal_to_csv(settings, &entries, raw_acct_map, acct_map, ars, txns_map)?;
+    }
+
+    Ok(())
+}
+
+/// The lines of the `entry` as written to J1_Journal_Entries.txt: each debit and credit, the totals, and the
+/// transaction it's the entry of.
+fn entry_lines(
+    settings: &ImportProcessParameters,
+    entry: &JournalEntry,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let mut lines: Vec<String> = Vec::new();
+    let lang = settings.report_language;
+
+    let mut debits = dec!(0);
+    let mut credits = dec!(0);
+
+    for line in entry.lines.iter() {
+
+        let amount = settings.output_precision.fiat(line.amount);
+
+        //  With a chart of accounts, its accounts (which are all mapped) replace the descriptions
+        let label = if settings.chart_of_accounts.is_empty() {
+            line.label.clone()
+        } else {
+            mapped_account_name(&default_account_name(settings, line, raw_acct_map, acct_map, ars), &settings.chart_of_accounts)
+        };
+
+        if line.is_debit {
+            debits += line.amount;
+            lines.push(format!("{:50}{:5}{:>20}{:5}{:>20}", label, "", amount, "", ""));
+        } else {
+            credits += line.amount;
+            lines.push(format!("{:50}{:5}{:>20}{:5}{:>20}", label, "", "", "", amount));
+        }
+    }
+
+    lines.push(format!("{:50}{:5}{:>20}{:5}{:>20}",
+        "",
+        "",
+        "--------------------",
+        "",
+        "--------------------",
+    ));
+
+    lines.push(format!("{:50}{:5}{:>20}{:5}{:>20}",
+        lang.text("    Totals"),
+        "",
+        settings.output_precision.fiat(debits),
+        "",
+        settings.output_precision.fiat(credits),
+    ));
+
+    lines.push(String::new());
+
+    match entry.source {
+        EntrySource::Txn(txn_num) => {
+            let txn = txns_map.get(&txn_num).unwrap();
+            lines.push(lang.fill("    (Txn {} on {}. {}. {})", &[
+                &txn_num,
+                &settings.report_date(txn.date),
+                &txn.user_memo,
+                &txn.get_auto_memo(ars, raw_acct_map, acct_map, &settings.home_currency)?,
+            ]));
+        }
+        EntrySource::Cost { line, date, ref description, .. } => {
+            lines.push(lang.fill("    (Cost on line {} of the costs file, on {}. {}.)", &[&line, &settings.report_date(date), description]));
+        }
+    }
+
+    if let Some(deferred) = entry.deferred_gain {
+        lines.push(lang.fill("    (Like-kind exchange: a gain or (loss) of {} is deferred into the basis received.)", &[
+            &settings.output_precision.fiat(deferred),
+        ]));
+    }
+
+    // if (debits - credits) != dec!(0) {
+    //     println!("Rounding issue on transaction #{}", txn_num);
+    // }
+
+    Ok(lines)
+}
+
+/// The lines of the journal entry of every `Transaction` dated in the tax year (if one is set), by `Transaction`
+/// number, as in J1_Journal_Entries.txt.
+pub fn journal_entry_lines_by_txn(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<HashMap<u32, Vec<String>>, Box<dyn Error>> {
+
+    let mut lines_by_txn = HashMap::new();
+
+    for entry in journal_entries(settings, raw_acct_map, acct_map, ars, txns_map)?.iter() {
+        if let EntrySource::Txn(txn_num) = entry.source {
+            lines_by_txn.insert(txn_num, entry_lines(settings, entry, raw_acct_map, acct_map, ars, txns_map)?);
+        }
+    }
+
+    Ok(lines_by_txn)
+}
+
+/// A posting to a ledger account, in the home currency (debits positive, credits negative).
+struct Posting<'a> {
+    account: String,
+    /// The quantity and ticker of a holding, if not of the home currency.
+    quantity: Option<(Decimal, &'a str)>,
+    value: Decimal,
+}
+
+/// The postings of a `JournalEntry` to its ledger accounts (before any renaming), rounded as in J1, with any rounding
+/// difference put on the last posting that isn't a holding, so that they balance.
+fn postings<'a>(
+    settings: &ImportProcessParameters,
+    entry: &JournalEntry,
+    raw_acct_map: &'a HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+) -> Vec<Posting<'a>> {
+
+    let home = &settings.home_currency;
+    let precision = &settings.output_precision;
+
+    let mut postings: Vec<Posting> = entry.lines.iter()
+        .map(|line| {
+            let value = if line.is_debit { precision.round_fiat(line.amount) } else { -precision.round_fiat(line.amount) };
+            match line.account {
+                LedgerAccount::Holding { ar_num } => {
+                    let ar = ars.get(&ar_num).unwrap();
+                    let raw_acct = raw_acct_map.get(&acct_map.get(&ar.account_key).unwrap().raw_key).unwrap();
+                    let quantity = if raw_acct.ticker == *home { None } else { Some((ar.amount, raw_acct.ticker.as_str())) };
+                    Posting { account: holding_account_name(raw_acct, home), quantity, value }
+                }
+                LedgerAccount::Other(ref name) => Posting { account: name.clone(), quantity: None, value },
+            }
+        })
+        .collect();
+
+    let residual: Decimal = postings.iter().map(|posting| posting.value).sum();
+
+    if residual != dec!(0) {
+        if let Some(posting) = postings.iter_mut().rev().find(|posting| posting.quantity.is_none()) {
+            posting.value -= residual;
+        }
+    }
+
+    postings
+}
+
+/// Writes the same journal entries as J1_Journal_Entries.txt in the plain-text format of ledger-cli and hledger
+/// (J2_Ledger_Journal.ledger).  Each holding is an `Assets:` account of its account name and ticker, posted in its
+/// own commodity at the total cost (`@@`) of its basis, and every other account is posted in the home currency.  Each
+/// account is renamed by the longest matching prefix in the `chart_of_accounts`, if any.  Values are rounded as in J1, with
+/// any rounding difference put on the entry's last posting that isn't a holding, so that every entry balances.
+fn ledger_journal_to_file(
+    settings: &ImportProcessParameters,
+    entries: &[JournalEntry],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+// 2018-01-05 * Bought BTC
+//     ; Txn 3. Paid 1000.00 USD for 0.5 BTC
+//     Assets:Crypto:Coinbase:BTC                    0.5 BTC @@ 1000.00 USD
+//     Assets:Coinbase:USD                           -1000.00 USD
+
+    let mut file = report_writer(settings, &settings.tax_year_file_name("J2_Ledger_Journal.ledger"))?;
+
+    writeln!(file, "; Journal entries exported by cryptools (costing method: {}, home currency: {}, like-kind treatment: {}).",
+        settings.costing_method,
+        settings.home_currency,
+        settings.lk_treatment_enabled,
+    )?;
+
+    let home = &settings.home_currency;
+    let precision = &settings.output_precision;
+
+    for entry in entries.iter() {
+
+        let (date, description, note, number) = entry.text(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+        writeln!(file, "\n{} * {}", date.format("%Y-%m-%d"), collapse_whitespace(&description))?;
+        match entry.source {
+            EntrySource::Txn(_) => writeln!(file, "    ; Txn {}. {}", number, collapse_whitespace(&note))?,
+            EntrySource::Cost { .. } => writeln!(file, "    ; {}. {}", number, collapse_whitespace(&note))?,
+        }
+
+        if let Some(deferred) = entry.deferred_gain {
+            writeln!(file, "    ; Like-kind exchange: a gain or (loss) of {:.*} {} is deferred into the basis received.",
+                precision.fiat_places as usize, precision.round_fiat(deferred), home)?;
+        }
+
+        for posting in postings(settings, entry, raw_acct_map, acct_map, ars) {
+
+            let account = mapped_account_name(&posting.account, &settings.chart_of_accounts);
+            let places = precision.fiat_places as usize;
+
+            let amount = match posting.quantity {
+                Some((quantity, ticker)) => {
+                    format!("{} {} @@ {:.*} {}", quantity.normalize(), commodity(ticker), places, posting.value.abs(), home)
+                }
+                None => format!("{:.*} {}", places, posting.value, home),
+            };
+
+            writeln!(file, "    {:44}  {}", account, amount)?;
+        }
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The account credited with income of `income_type`, i.e., "Staking income" (or just "Income" if untyped).
+fn income_label(settings: &ImportProcessParameters, income_type: Option<IncomeType>) -> String {
+    let lang = settings.report_language;
+    match income_type {
+        Some(income_type) => lang.fill("{} income", &[&lang.text(&income_type.to_string())]),
+        None => lang.text("Income").to_string(),
+    }
+}
+
+/// Writes the same journal entries as J1_Journal_Entries.txt as a general journal CSV (J4_General_Journal.csv), in the
+/// layout QuickBooks Online and Xero import journals from: each line's date, journal number (the `Transaction`'s),
+/// account, debit or credit, and memo.  Accounts are named, renamed and rounded as for the ledger journal (see
+/// `ledger_journal_to_file()`), so that a chart of accounts can map them onto the books' own, and lines of zero are
+/// left out.
+fn general_journal_to_csv(
+    settings: &ImportProcessParameters,
+    entries: &[JournalEntry],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut rows: Vec<Vec<String>> = [].to_vec();
+
+    let columns = [
+        "Date".to_string(),
+        "Journal No".to_string(),
+        "Account".to_string(),
+        "Debit".to_string(),
+        "Credit".to_string(),
+        "Memo".to_string(),
+    ];
 
-        // if (debits - credits) != dec!(0) {
-        //     println!("Rounding issue on transaction #{}", txn_num);
-        // }
+    rows.push(columns.to_vec());
 
+    let places = settings.output_precision.fiat_places as usize;
+
+    for entry in entries.iter() {
+
+        let (date, memo, _note, number) = entry.text(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+        for posting in postings(settings, entry, raw_acct_map, acct_map, ars) {
+
+            if posting.value == dec!(0) { continue }
+
+            let (debit, credit) = if posting.value > dec!(0) {
+                (format!("{:.*}", places, posting.value), String::new())
+            } else {
+                (String::new(), format!("{:.*}", places, -posting.value))
+            };
+
+            rows.push(vec![
+                settings.report_date(date),
+                number.clone(),
+                mapped_account_name(&posting.account, &settings.chart_of_accounts),
+                debit,
+                credit,
+                collapse_whitespace(&memo),
+            ]);
+        }
     }
 
+    write_rows_to_csv(settings, &settings.tax_year_file_name("J4_General_Journal.csv"), &rows)?;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Writes the same journal entries as J1_Journal_Entries.txt in the plain-text format of Beancount
+/// (J3_Beancount_Journal.beancount), with an `open` for each account and a `balance` assertion of each holding at
+/// each year-end.  Each `Lot` received is posted at its total cost, basis date and a label of its own (i.e.,
+/// `{{220.00 USD, 2016-02-01, "A2-L1"}}`), and each disposal reduces the `Lot`s it came out of by their labels.  The
+/// pooled costing methods (and wash sales) don't cost a disposal at the basis of the `Lot`s it came out of, so the
+/// holdings are instead opened with the `NONE` booking method and each disposal posted at its own cost.  With a tax
+/// year, the holdings at the start of it are posted first, as opening balances.  Accounts are named and renamed, and
+/// values rounded, as for the ledger journal (see `ledger_journal_to_file()`).
+fn beancount_journal_to_file(
+    settings: &ImportProcessParameters,
+    entries: &[JournalEntry],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+// 2016-03-01 * "SECOND"
+//   txn: 2
+//   memo: "Paid -0.25 BTC for 180 XMR, valued at 250.00 USD."
+//   Assets:Crypto:Exchange:XMR                    180 XMR {{250.00 USD, 2016-03-01, "A3-L1"}}
+//   Assets:Crypto:Exchange:BTC                    -0.25 BTC {"A2-L1"}
+//   Income:CapitalGains:ShortTerm                 -30.00 USD
+
+    let home = beancount_commodity(&settings.home_currency);
+    let precision = &settings.output_precision;
+    let places = precision.fiat_places as usize;
+
+    let reduces_by_label = settings.wash_sale_window.is_none() && !matches!(settings.costing_method,
+        InventoryCostingMethod::SharePoolingSection104 | InventoryCostingMethod::AdjustedCostBase);
+
+    let account_name = |name: &str| beancount_account(&mapped_account_name(name, &settings.chart_of_accounts));
+    let holding = |acct_key: u16| {
+        let raw_acct = raw_acct_map.get(&acct_map.get(&acct_key).unwrap().raw_key).unwrap();
+        (raw_acct, account_name(&holding_account_name(raw_acct, &settings.home_currency)))
+    };
+
+    //  Each account posted to, with the date of its first posting
+    let mut opened: HashMap<String, NaiveDate> = HashMap::new();
+    let mut transactions: Vec<String> = Vec::new();
+
+    //  Each posting's account, amount and value (debits positive, credits negative), and whether it's of a holding
+    let write_entry = |date: NaiveDate, narration: &str, metadata: &[(&str, String)], postings: &mut Vec<(String, String, Decimal, bool)>| {
+
+        let residual: Decimal = postings.iter().map(|(_, _, value, _)| *value).sum();
+
+        if residual != dec!(0) {
+            if let Some(posting) = postings.iter_mut().rev().find(|(_, _, _, is_holding)| !is_holding) {
+                posting.2 -= residual;
+                posting.1 = format!("{:.*} {}", places, posting.2, home);
+            }
+        }
+
+        let mut text = format!("{} * \"{}\"\n", date.format("%Y-%m-%d"), beancount_string(narration));
+        for (key, value) in metadata {
+            text.push_str(&format!("  {}: {}\n", key, value));
+        }
+        for (account, amount, _, _) in postings.iter() {
+            text.push_str(&format!("  {:44}  {}\n", account, amount));
+        }
+        text
+    };
+
+    if let Some(year) = settings.tax_year {
+
+        let (start, _end) = settings.fiscal_year_dates(year);
+        let mut postings: Vec<(String, String, Decimal, bool)> = Vec::new();
+
+        for acct_key in 1..=acct_map.len() as u16 {
+
+            let (raw_acct, name) = holding(acct_key);
+
+            for lot in acct_map.get(&acct_key).unwrap().list_of_lots.borrow().iter() {
+
+                let (quantity, basis) = lot.movements.borrow().iter()
+                    .filter(|mvmt| mvmt.date < start)
+                    .fold((dec!(0), dec!(0)), |(quantity, basis), mvmt| (quantity + mvmt.amount, basis + mvmt.cost_basis_lk.get()));
+
+                if quantity == dec!(0) { continue }
+
+                let value = if raw_acct.ticker == settings.home_currency { quantity } else { precision.round_fiat(basis) };
+                let label = lot.id();
+                let amount = holding_amount(raw_acct, &home, quantity, value, places, Some((lot.date_for_basis_purposes, &label)));
+                postings.push((name.clone(), amount, value, true));
+            }
+        }
+
+        if !postings.is_empty() {
+            postings.push(("Equity:OpeningBalances".to_string(), String::new(), dec!(0), false));
+            for (account, _, _, _) in postings.iter() {
+                opened.entry(account.clone()).or_insert(start);
+            }
+            transactions.push(write_entry(start, "Opening balances", &[], &mut postings));
+        }
+    }
+
+    for entry in entries.iter() {
+
+        let (date, narration, note, number) = entry.text(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+        let mut metadata = vec![
+            match entry.source {
+                EntrySource::Txn(_) => ("txn", number),
+                EntrySource::Cost { .. } => ("cost", format!("\"{}\"", number)),
+            },
+            ("memo", format!("\"{}\"", beancount_string(&note))),
+        ];
+        if let Some(deferred) = entry.deferred_gain {
+            metadata.push(("deferred-gain", format!("{:.*} {}", places, precision.round_fiat(deferred), home)));
+        }
+
+        let mut postings: Vec<(String, String, Decimal, bool)> = Vec::new();
+
+        for line in entry.lines.iter() {
+
+            let sign = if line.is_debit { dec!(1) } else { dec!(-1) };
+
+            match line.account {
+                LedgerAccount::Holding { ar_num } => {
+
+                    let ar = ars.get(&ar_num).unwrap();
+                    let (raw_acct, name) = holding(ar.account_key);
+
+                    if raw_acct.ticker == settings.home_currency || raw_acct.is_margin {
+                        let value = sign * precision.round_fiat(line.amount);
+                        postings.push((name, holding_amount(raw_acct, &home, ar.amount, value, places, None), value, true));
+                        continue
+                    }
+
+                    //  One posting per `Lot` received or given up
+                    for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+                        if mvmt.amount == dec!(0) { continue }
+
+                        let lot = mvmt.get_lot(acct_map, ars);
+                        let label = lot.id();
+                        let basis = precision.round_fiat(mvmt.cost_basis_lk.get());
+
+                        let amount = if mvmt.amount < dec!(0) && reduces_by_label {
+                            format!("{} {} {{\"{}\"}}", mvmt.amount.normalize(), beancount_commodity(&raw_acct.ticker), label)
+                        } else {
+                            holding_amount(raw_acct, &home, mvmt.amount, basis, places, Some((lot.date_for_basis_purposes, &label)))
+                        };
+                        postings.push((name.clone(), amount, basis, true));
+                    }
+                }
+                LedgerAccount::Other(ref name) => {
+                    let value = sign * precision.round_fiat(line.amount);
+                    postings.push((account_name(name), format!("{:.*} {}", places, value, home), value, false));
+                }
+            }
+        }
+
+        for (account, _, _, _) in postings.iter() {
+            opened.entry(account.clone()).or_insert(date);
+        }
+
+        transactions.push(write_entry(date, &narration, &metadata, &mut postings));
+    }
+
+    let mut file = report_writer(settings, &settings.tax_year_file_name("J3_Beancount_Journal.beancount"))?;
+
+    writeln!(file, "; Journal entries exported by cryptools (costing method: {}, home currency: {}, like-kind treatment: {}).",
+        settings.costing_method,
+        settings.home_currency,
+        settings.lk_treatment_enabled,
+    )?;
+    writeln!(file, "\noption \"operating_currency\" \"{}\"", home)?;
+    writeln!(file, "option \"infer_tolerance_from_cost\" \"TRUE\"\n")?;
+
+    let holding_names: Vec<String> = (1..=acct_map.len() as u16).map(|acct_key| holding(acct_key).1).collect();
+
+    let mut opens: Vec<(&NaiveDate, &String)> = opened.iter().map(|(account, date)| (date, account)).collect();
+    opens.sort();
+
+    for (date, account) in opens {
+        let booking = if holding_names.contains(account) && !reduces_by_label { " \"NONE\"" } else { "" };
+        writeln!(file, "{} open {}{}", date.format("%Y-%m-%d"), account, booking)?;
+    }
+
+    for transaction in transactions {
+        writeln!(file, "\n{}", transaction.trim_end())?;
+    }
+
+    //  Each holding at each (fiscal) year-end (the start of the next year), by account and commodity
+    let years: Vec<i32> = match settings.tax_year {
+        Some(year) => vec![year],
+        None => {
+            let dates = entries.iter().filter_map(|entry| match entry.source {
+                EntrySource::Txn(txn_num) => Some(txns_map.get(&txn_num).unwrap().date),
+                EntrySource::Cost { .. } => None,
+            });
+            match (dates.clone().min(), dates.max()) {
+                (Some(first), Some(last)) => (settings.fiscal_year(first)..=settings.fiscal_year(last)).collect(),
+                _ => Vec::new(),
+            }
+        }
+    };
+
+    for year in years {
+
+        let start_of_next = settings.fiscal_year_dates(year).1.succ_opt().unwrap();
+        let mut balances: Vec<((String, String), Decimal)> = Vec::new();
+
+        for acct_key in 1..=acct_map.len() as u16 {
+
+            let (raw_acct, name) = holding(acct_key);
+
+            match opened.get(&name) {
+                Some(date) if *date < start_of_next => {}
+                _ => continue,
+            }
+
+            let balance: Decimal = acct_map.get(&acct_key).unwrap().list_of_lots.borrow().iter()
+                .flat_map(|lot| lot.movements.borrow().iter().map(|mvmt| (mvmt.date, mvmt.amount)).collect::<Vec<_>>())
+                .filter(|(date, _)| *date < start_of_next)
+                .map(|(_, amount)| amount)
+                .sum();
+
+            let key = (name, beancount_commodity(&raw_acct.ticker));
+            match balances.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, total)) => *total += balance,
+                None => balances.push((key, balance)),
+            }
+        }
+
+        writeln!(file)?;
+        for ((account, commodity), balance) in balances {
+            writeln!(file, "{} balance {:44}  {} {}", start_of_next.format("%Y-%m-%d"), account, balance.normalize(), commodity)?;
+        }
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The amount of a holding's posting in a Beancount journal: a quantity of the home currency, a margin position at the
+/// total price of its `value`, or a `Lot` at its total cost (`basis`), basis date and label.
+fn holding_amount(
+    raw_acct: &RawAccount,
+    home: &str,
+    quantity: Decimal,
+    value: Decimal,
+    places: usize,
+    lot: Option<(NaiveDate, &str)>,
+) -> String {
+
+    let commodity = beancount_commodity(&raw_acct.ticker);
+
+    match lot {
+        _ if commodity == home => format!("{:.*} {}", places, value, home),
+        Some((date, label)) if !raw_acct.is_margin => {
+            format!("{} {} {{{{{:.*} {}, {}, \"{}\"}}}}", quantity.normalize(), commodity, places, value.abs(), home,
+                date.format("%Y-%m-%d"), label)
+        }
+        _ => format!("{} {} @@ {:.*} {}", quantity.normalize(), commodity, places, value.abs(), home),
+    }
+}
+
+/// A ticker as a Beancount commodity: in capitals, and with any character a commodity can't have as a `-` (i.e.,
+/// `BAYC-1234` for `BAYC#1234`).
+fn beancount_commodity(ticker: &str) -> String {
+
+    let mut commodity: String = ticker.to_uppercase().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "'._-".contains(c) { c } else { '-' })
+        .take(23)
+        .collect();
+
+    if !commodity.starts_with(|c: char| c.is_ascii_uppercase()) { commodity.insert(0, 'X') }
+    if !commodity.ends_with(|c: char| c.is_ascii_alphanumeric()) { commodity.push('X') }
+
+    commodity
+}
+
+/// A ledger account name as a Beancount account: each part beginning with a capital (or a digit), with any character
+/// a part can't have as a `-` (i.e., `Assets:Crypto:Cold-wallet:BTC` for `Assets:Crypto:Cold wallet:BTC`).
+fn beancount_account(account: &str) -> String {
+
+    account.split(':')
+        .map(|part| {
+            let part: String = part.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '-' }).collect();
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) if first.is_alphanumeric() => first.to_uppercase().chain(chars).collect(),
+                _ => format!("X{}", part),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// Text as the inside of a Beancount string.
+fn beancount_string(text: &str) -> String {
+    collapse_whitespace(text).replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The account of a `JournalLine`, before any renaming by the chart of accounts (i.e., `Assets:Crypto:Coinbase:BTC`).
+fn default_account_name(
+    settings: &ImportProcessParameters,
+    line: &JournalLine,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+) -> String {
+    match line.account {
+        LedgerAccount::Holding { ar_num } => {
+            let acct_key = ars.get(&ar_num).unwrap().account_key;
+            holding_account_name(raw_acct_map.get(&acct_map.get(&acct_key).unwrap().raw_key).unwrap(), &settings.home_currency)
+        }
+        LedgerAccount::Other(ref name) => name.clone(),
+    }
+}
+
+/// The ledger account of a holding, i.e., `Assets:Crypto:Coinbase:BTC` (or `Assets:Coinbase:USD` for the home
+/// currency, and `Assets:Margin:Kraken:BTC` for a margin account).
+fn holding_account_name(raw_acct: &RawAccount, home_currency: &str) -> String {
+
+    let kind = if raw_acct.is_margin {
+        "Margin:"
+    } else if raw_acct.ticker == home_currency {
+        ""
+    } else {
+        "Crypto:"
+    };
+
+    format!("Assets:{}{}:{}", kind, account_segment(&raw_acct.name), account_segment(&raw_acct.ticker))
+}
+
+/// A part of a ledger account name, without the colons that separate the parts, nor runs of whitespace (two spaces
+/// end an account name).
+fn account_segment(name: &str) -> String {
+    collapse_whitespace(&name.replace(':', "-"))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// A ticker as a ledger commodity, quoted unless it's only letters (i.e., `"BAYC#1234"`).
+fn commodity(ticker: &str) -> String {
+    if !ticker.is_empty() && ticker.chars().all(|c| c.is_alphabetic()) {
+        ticker.to_string()
+    } else {
+        format!("\"{}\"", ticker)
+    }
+}
+
+/// The `account` renamed by the longest prefix of it (a whole number of its parts) in the `chart_of_accounts`, if any
+/// (i.e., `Assets:Crypto:Coinbase` mapped to `Assets:Exchanges:Coinbase` renames `Assets:Crypto:Coinbase:BTC` to
+/// `Assets:Exchanges:Coinbase:BTC`).
+fn mapped_account_name(account: &str, chart_of_accounts: &HashMap<String, String>) -> String {
+    mapped_account(account, chart_of_accounts).unwrap_or_else(|| account.to_string())
+}
+
+fn mapped_account(account: &str, chart_of_accounts: &HashMap<String, String>) -> Option<String> {
+
+    let mut prefix = account;
+
+    loop {
+        if let Some(mapped) = chart_of_accounts.get(prefix) {
+            return Some(format!("{}{}", mapped, &account[prefix.len()..]))
+        }
+        match prefix.rfind(':') {
+            Some(idx) => prefix = &prefix[..idx],
+            None => return None,
+        }
+    }
+}
+
+/// Reads a chart of accounts file: a CSV with a header row, then one `account,mapped` row per account (or prefix of
+/// accounts) of the journal entries to rename (i.e., `Income:CapitalGains,Income:Investments:Gains`).
+pub fn chart_of_accounts_from_file(path: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut chart_of_accounts: HashMap<String, String> = HashMap::new();
+
+    for (idx, result) in rdr.records().enumerate() {
+
+        let record = result?;
+        let row = idx + 2;  //  Account for the header and one-based row numbers
+
+        match (record.get(0), record.get(1)) {
+            (Some(account), Some(mapped)) if !account.is_empty() && !mapped.is_empty() => {
+                if chart_of_accounts.insert(account.to_string(), mapped.to_string()).is_some() {
+                    return Err(format!("Chart of accounts file row {} repeats the account {}.", row, account).into())
+                }
+            }
+            _ => return Err(format!("Chart of accounts file row {} must have an account and its mapped name.", row).into()),
+        }
+    }
+
+    if chart_of_accounts.is_empty() {
+        return Err("The chart of accounts file must map at least one account.".into())
+    }
+
+    Ok(chart_of_accounts)
+}
+
+/// Every account the journal entries of every `Transaction` (regardless of the tax year) post to that the
+/// `chart_of_accounts` doesn't map, in order.
+pub fn unmapped_accounts(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let all_years = ImportProcessParameters { tax_year: None, ..settings.clone() };
+    let mut unmapped: Vec<String> = Vec::new();
+
+    for entry in journal_entries(&all_years, raw_acct_map, acct_map, ars, txns_map)? {
+        for line in entry.lines.iter() {
+            let account = default_account_name(settings, line, raw_acct_map, acct_map, ars);
+            if mapped_account(&account, &settings.chart_of_accounts).is_none() && !unmapped.contains(&account) {
+                unmapped.push(account);
+            }
+        }
+    }
+
+    unmapped.sort();
+
+    Ok(unmapped)
+}
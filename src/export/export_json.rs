@@ -0,0 +1,289 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io::BufWriter;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crptls::transaction::{ActionRecord, Transaction, TxType};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+
+use crate::export::export_8949;
+use crate::export::export_csv::create_report_file;
+
+
+/// The layout of the reports exported by default (i.e., unless `-s` or `-j` is passed), for `--format`.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// The C*.csv, T*.txt and J*.txt reports.
+    #[value(name = "csv")]
+    CsvAndTxt,
+    /// A single Reports.json with the accounts, lots, movements, gains and losses, and income and expense.
+    #[value(name = "json")]
+    Json,
+}
+
+// Amounts are serialized as strings (e.g., "0.25"), as `Decimal` does by default, so that no precision is lost
+// to floating point.  Like-kind values are used throughout, as in the CSV reports.
+
+/// The contents of Reports.json, which are also the structured results of `cryptools::run()`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Reports {
+    pub home_currency: String,
+    pub accounts: Vec<ReportAccount>,
+    pub transactions: Vec<ReportTransaction>,
+    pub gains_and_losses: Vec<ReportGainOrLoss>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportAccount {
+    pub account_number: u16,
+    pub name: String,
+    pub ticker: String,
+    pub is_margin: bool,
+    /// Its wallet address, from the accounts file, if any.
+    pub address: Option<String>,
+    pub balance: Decimal,
+    pub cost_basis: Decimal,
+    pub lots: Vec<ReportLot>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportLot {
+    /// As in the other reports (see `Lot::id()`).
+    pub lot_id: String,
+    pub lot_number: u32,
+    pub date_acquired: NaiveDate,
+    pub date_for_basis_purposes: NaiveDate,
+    pub balance: Decimal,
+    pub cost_basis: Decimal,
+    pub movements: Vec<ReportLotMovement>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportLotMovement {
+    pub txn_number: u32,
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    pub cost_basis: Decimal,
+    pub proceeds: Decimal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportTransaction {
+    pub txn_number: u32,
+    pub date: NaiveDate,
+    pub memo: String,
+    /// Each tag, as written in a memo (i.e., `#payment:invoice42`).
+    pub tags: Vec<String>,
+    /// A spend's expense category, if it has one.
+    pub expense_category: Option<String>,
+    /// An income transaction's payer, if it has one.
+    pub payer: Option<String>,
+    pub proceeds: Decimal,
+    /// The flow and outgoing exchange movements, as in C4_Txns_mvmts_detail.csv.
+    pub movements: Vec<ReportTxnMovement>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportTxnMovement {
+    pub account_number: u16,
+    pub lot_id: String,
+    pub lot_number: u32,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub amount: Decimal,
+    pub ticker: String,
+    pub term: String,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub gain_or_loss: Decimal,
+    pub income: Decimal,
+    pub expense: Decimal,
+}
+
+/// A line of Form 8949, as in C7_Form_8949.csv.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportGainOrLoss {
+    pub term: String,
+    pub txn_numbers: Vec<u32>,
+    pub description: String,
+    pub memo: String,
+    /// A date, "Various" or "INHERITED".
+    pub date_acquired: String,
+    pub date_sold: NaiveDate,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub gain_or_loss: Decimal,
+}
+
+/// Writes Reports.json (see `Reports::from_maps()`).
+pub fn _1_reports_to_json(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let reports = Reports::from_maps(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let full_path: PathBuf = [settings.export_path.clone(), PathBuf::from(settings.report_file_name("Reports.json"))]
+        .iter().collect();
+    let file = create_report_file(settings, &full_path)?;
+
+    serde_json::to_writer_pretty(BufWriter::new(file), &reports)?;
+
+    Ok(())
+}
+
+impl Reports {
+
+    /// Everything the CSV/TXT reports are made from: every account with its lots and their movements, every
+    /// transaction with the movements that realize gain, loss, income or expense, and every line of Form 8949.
+    pub fn from_maps(
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<Reports, Box<dyn Error>> {
+
+        let mut accounts: Vec<ReportAccount> = Vec::with_capacity(acct_map.len());
+
+        for j in 1..=acct_map.len() {
+
+            let acct = acct_map.get(&(j as u16)).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            let lots = acct.list_of_lots.borrow().iter().map(|lot| {
+                ReportLot {
+                    lot_id: lot.id(),
+                    lot_number: lot.lot_number,
+                    date_acquired: lot.date_of_first_mvmt_in_lot,
+                    date_for_basis_purposes: lot.date_for_basis_purposes,
+                    balance: lot.get_sum_of_amts_in_lot(),
+                    cost_basis: lot.get_sum_of_lk_basis_in_lot(),
+                    movements: lot.movements.borrow().iter().map(|mvmt| {
+                        ReportLotMovement {
+                            txn_number: mvmt.transaction_key,
+                            date: mvmt.date,
+                            amount: mvmt.amount,
+                            cost_basis: mvmt.cost_basis_lk.get(),
+                            proceeds: mvmt.proceeds_lk.get(),
+                        }
+                    }).collect(),
+                }
+            }).collect();
+
+            accounts.push(ReportAccount {
+                account_number: raw_acct.account_num,
+                name: raw_acct.name.clone(),
+                ticker: raw_acct.ticker.clone(),
+                is_margin: raw_acct.is_margin,
+                address: settings.accounts_file.as_ref()
+                    .and_then(|accounts_file| accounts_file.address(raw_acct.account_num))
+                    .map(str::to_string),
+                balance: acct.get_sum_of_amts_in_lots(),
+                cost_basis: acct.get_sum_of_lk_basis_in_lots(),
+                lots,
+            });
+        }
+
+        let mut transactions: Vec<ReportTransaction> = Vec::with_capacity(txns_map.len());
+
+        for txn_num in 1..=txns_map.len() {
+
+            let txn = txns_map.get(&(txn_num as u32)).unwrap();
+            let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+
+            let flow_or_outgoing_exchange_movements = txn.get_outgoing_exchange_and_flow_mvmts(
+                &settings.home_currency,
+                ars,
+                raw_acct_map,
+                acct_map,
+                txns_map
+            )?;
+
+            let mut movements: Vec<ReportTxnMovement> = Vec::with_capacity(flow_or_outgoing_exchange_movements.len());
+
+            for mvmt in flow_or_outgoing_exchange_movements.iter() {
+
+                let lot = mvmt.get_lot(acct_map, ars);
+                let acct = acct_map.get(&lot.account_key).unwrap();
+                let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+                let mut proceeds = mvmt.proceeds_lk.get();
+                let mut cost_basis = mvmt.cost_basis_lk.get();
+                let mut gain_or_loss = mvmt.get_lk_gain_or_loss();
+
+                //  As in C4, an incoming flow is income, not a gain
+                if tx_type == TxType::Flow && mvmt.amount > dec!(0) {
+                    proceeds = dec!(0);
+                    cost_basis = dec!(0);
+                    gain_or_loss = dec!(0);
+                }
+
+                movements.push(ReportTxnMovement {
+                    account_number: raw_acct.account_num,
+                    lot_id: lot.id(),
+                    lot_number: mvmt.lot_num,
+                    tx_type: mvmt.friendly_tx_type(&tx_type),
+                    amount: mvmt.amount,
+                    ticker: raw_acct.ticker.clone(),
+                    term: mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).to_string(),
+                    proceeds,
+                    cost_basis,
+                    gain_or_loss,
+                    income: mvmt.get_income(ars, raw_acct_map, acct_map, txns_map)?,
+                    expense: mvmt.get_expense(ars, raw_acct_map, acct_map, txns_map)?,
+                });
+            }
+
+            transactions.push(ReportTransaction {
+                txn_number: txn.tx_number,
+                date: txn.date,
+                memo: txn.user_memo.clone(),
+                tags: txn.tags.iter().map(|tag| tag.to_string()).collect(),
+                expense_category: txn.expense_category.clone(),
+                payer: txn.payer.clone(),
+                proceeds: txn.proceeds,
+                movements,
+            });
+        }
+
+        let gains_and_losses = export_8949::form_8949_lines(settings, raw_acct_map, acct_map, ars, txns_map)?
+            .into_iter()
+            .map(|line| {
+                ReportGainOrLoss {
+                    term: line.term.to_string(),
+                    txn_numbers: line.txn_nums.split(',').filter_map(|num| num.trim().parse().ok()).collect(),
+                    description: match line.amount {
+                        Some(amount) => format!("{} {}", (-amount).normalize(), line.ticker),
+                        None => line.memo.clone(),
+                    },
+                    gain_or_loss: line.gain_or_loss(),
+                    memo: line.memo,
+                    date_acquired: line.date_acquired,
+                    date_sold: line.date_sold,
+                    proceeds: line.proceeds,
+                    cost_basis: line.cost_basis,
+                }
+            })
+            .collect();
+
+        Ok(Reports {
+            home_currency: settings.home_currency.clone(),
+            accounts,
+            transactions,
+            gains_and_losses,
+        })
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::error::Error;
+use std::io::{BufWriter, Write};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::summary::{self, RunSummary};
+
+use crate::export::export_all::Exporter;
+use crate::export::export_csv::create_report_file;
+
+
+/// US Letter, in points.
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 54.0;
+
+/// The size of a table's text.  Tables are set in Courier, every character of which is 0.6 em wide, so that the
+/// numbers line up on the right.
+const TABLE_SIZE: f32 = 9.0;
+
+/// One of the standard fonts every PDF reader has, so none is embedded.
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+    MonoBold,
+}
+
+impl Font {
+
+    const ALL: [Font; 4] = [Font::Regular, Font::Bold, Font::Mono, Font::MonoBold];
+
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+            Font::MonoBold => "F4",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Mono => "Courier",
+            Font::MonoBold => "Courier-Bold",
+        }
+    }
+}
+
+/// A row of a table, in bold if it's a total.
+struct TableRow {
+    cells: Vec<String>,
+    is_total: bool,
+}
+
+impl TableRow {
+
+    fn new(cells: Vec<String>) -> Self {
+        TableRow { cells, is_total: false }
+    }
+
+    fn total(cells: Vec<String>) -> Self {
+        TableRow { cells, is_total: true }
+    }
+}
+
+/// Lays out lines of text and tables from the top of each page down, starting a new page when one is full.
+struct PdfDocument {
+    /// The content stream of each finished page.
+    pages: Vec<String>,
+    content: String,
+    y: f32,
+}
+
+impl PdfDocument {
+
+    fn new() -> Self {
+        PdfDocument { pages: Vec::new(), content: String::new(), y: PAGE_HEIGHT - MARGIN }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.content));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page unless there's `height` left on this one.
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN { self.new_page() }
+    }
+
+    fn text_at(&mut self, font: Font, size: f32, x: f32, y: f32, text: &str) {
+        self.content += &format!("BT /{} {} Tf {:.2} {:.2} Td ({}) Tj ET\n", font.resource_name(), size, x, y, pdf_string(text));
+    }
+
+    fn line(&mut self, font: Font, size: f32, text: &str) {
+        self.ensure(size * 1.4);
+        self.y -= size * 1.4;
+        self.text_at(font, size, MARGIN, self.y, text);
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn rule(&mut self) {
+        self.space(4.0);
+        self.content += &format!("0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n", MARGIN, self.y, PAGE_WIDTH - MARGIN, self.y);
+    }
+
+    fn heading(&mut self, text: &str) {
+        //  Kept with at least a few rows of what follows it
+        self.ensure(16.0 * 1.4 + 12.0 + TABLE_SIZE * 1.4 * 4.0);
+        self.space(12.0);
+        self.line(Font::Bold, 16.0, text);
+        self.rule();
+        self.space(4.0);
+    }
+
+    /// Label and value pairs, with the values lined up.
+    fn pairs(&mut self, pairs: &[(&str, String)]) {
+
+        let label_width = pairs.iter().map(|(label, _)| text_width(label, 10.0)).fold(0.0, f32::max) + 18.0;
+
+        for (label, value) in pairs.iter() {
+            self.ensure(10.0 * 1.6);
+            self.y -= 10.0 * 1.6;
+            self.text_at(Font::Bold, 10.0, MARGIN, self.y, label);
+            self.text_at(Font::Regular, 10.0, MARGIN + label_width, self.y, value);
+        }
+    }
+
+    /// A table with a header, its first column left-aligned and the rest right-aligned, and the header repeated atop
+    /// each page it runs onto.
+    fn table(&mut self, header: &[&str], rows: &[TableRow]) {
+
+        let char_width = TABLE_SIZE * 0.6;
+        let gap = 3;
+
+        let widths: Vec<usize> = (0..header.len())
+            .map(|col| rows.iter()
+                .filter_map(|row| row.cells.get(col))
+                .map(|cell| cell.chars().count())
+                .chain(std::iter::once(header[col].chars().count()))
+                .max()
+                .unwrap_or(0))
+            .collect();
+
+        let cell_x = |col: usize, text: &str| {
+            let start: usize = widths[..col].iter().map(|width| width + gap).sum();
+            let offset = if col == 0 { 0 } else { widths[col] - text.chars().count() };
+            MARGIN + (start + offset) as f32 * char_width
+        };
+
+        let write_row = |doc: &mut PdfDocument, font: Font, cells: &[&str]| {
+            doc.y -= TABLE_SIZE * 1.4;
+            for (col, cell) in cells.iter().enumerate().filter(|(_col, cell)| !cell.is_empty()) {
+                doc.text_at(font, TABLE_SIZE, cell_x(col, cell), doc.y, cell);
+            }
+        };
+
+        self.ensure(TABLE_SIZE * 1.4 * 2.0);
+        write_row(self, Font::MonoBold, header);
+
+        for row in rows.iter() {
+
+            if self.y - TABLE_SIZE * 1.4 < MARGIN {
+                self.new_page();
+                write_row(self, Font::MonoBold, header);
+            }
+
+            let cells: Vec<&str> = row.cells.iter().map(|cell| cell.as_str()).collect();
+            write_row(self, if row.is_total { Font::MonoBold } else { Font::Mono }, &cells);
+        }
+    }
+
+    /// The finished document, with each page numbered at its foot.
+    fn into_bytes(mut self) -> Vec<u8> {
+
+        if !self.content.is_empty() || self.pages.is_empty() { self.new_page() }
+
+        let page_count = self.pages.len();
+        let font_objects = Font::ALL.len();
+        //  The catalog, the page tree and the fonts, then each page and its content stream
+        let first_page_object = 3 + font_objects;
+
+        let mut objects: Vec<String> = Vec::with_capacity(first_page_object - 1 + page_count * 2);
+
+        let kids: Vec<String> = (0..page_count).map(|idx| format!("{} 0 R", first_page_object + idx * 2)).collect();
+        let fonts: Vec<String> = Font::ALL.iter().enumerate()
+            .map(|(idx, font)| format!("/{} {} 0 R", font.resource_name(), 3 + idx))
+            .collect();
+
+        objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), page_count));
+
+        for font in Font::ALL.iter() {
+            objects.push(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", font.base_font()));
+        }
+
+        for (idx, page) in self.pages.iter().enumerate() {
+
+            let footer = format!("Page {} of {}", idx + 1, page_count);
+            let content = format!("{}BT /F1 8 Tf {:.2} {:.2} Td ({}) Tj ET\n",
+                page,
+                PAGE_WIDTH - MARGIN - text_width(&footer, 8.0),
+                MARGIN / 2.0,
+                footer,
+            );
+
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH, PAGE_HEIGHT, fonts.join(" "), first_page_object + idx * 2 + 1,
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+        }
+
+        let mut pdf: Vec<u8> = b"%PDF-1.4\n".to_vec();
+        let mut offsets: Vec<usize> = Vec::with_capacity(objects.len());
+
+        for (idx, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", idx + 1, object).as_bytes());
+        }
+
+        let xref_offset = pdf.len();
+
+        pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets.iter() {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref_offset).as_bytes());
+
+        pdf
+    }
+}
+
+/// The `text` as the inside of a PDF string in the WinAnsiEncoding: the parentheses and backslash escaped, Latin-1
+/// characters as octal escapes, and any other character as `?`.
+fn pdf_string(text: &str) -> String {
+
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => { escaped.push('\\'); escaped.push(c) }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => escaped += &format!("\\{:03o}", c as u32),
+            _ => escaped.push('?'),
+        }
+    }
+
+    escaped
+}
+
+/// The approximate width of `text` in Helvetica (about half an em per character), for placing it.
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * 0.5
+}
+
+/// Writes a summary of the run as a PDF (Summary.pdf), for handing to a client or attaching to a return: a cover page
+/// with the settings and an overview of the transactions, then the realized gains and losses by year (as in C18), the
+/// income by type and year (as in C19), and the holdings at the end of the run (as in the summary printed to the
+/// console).  With a tax year set, only that year's gains and income are included.
+pub fn _1_summary_to_pdf(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let fiat = |value: Decimal| settings.output_precision.fiat(value);
+    let in_tax_year = |year: i32| settings.tax_year.is_none_or(|tax_year| year == tax_year);
+
+    let run_summary = RunSummary::from_maps(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?;
+
+    let mut doc = PdfDocument::new();
+
+    //  The cover page
+
+    doc.space(120.0);
+    doc.line(Font::Bold, 26.0, "Cryptocurrency Tax Summary");
+    doc.space(6.0);
+    doc.line(Font::Regular, 14.0, &match settings.tax_year {
+        Some(year) => format!("Tax year {}", year),
+        None => "All years".to_string(),
+    });
+    doc.line(Font::Regular, 10.0, &format!("Prepared {}", settings.report_date(chrono::Local::now().date_naive())));
+    doc.space(36.0);
+
+    doc.heading("Settings");
+
+    let mut settings_pairs: Vec<(&str, String)> = vec![
+        ("Home currency", settings.home_currency.clone()),
+        ("Costing method", settings.costing_method.to_string()),
+        ("Long-term holding period", format!("More than {} days", settings.long_term_days)),
+        ("Like-kind treatment", match settings.lk_treatment_enabled {
+            true => format!("Through {}", settings.report_date(settings.lk_cutoff_date)),
+            false => "None".to_string(),
+        }),
+    ];
+    if let Some(year) = settings.tax_year {
+        settings_pairs.push(("Tax year", format!("{} (gains and income dated {} only)", year, settings.fiscal_year_span(year))));
+    }
+    doc.pairs(&settings_pairs);
+
+    doc.heading("Overview");
+
+    let mut overview_pairs: Vec<(&str, String)> = vec![
+        ("Transactions", run_summary.txn_count.to_string()),
+        ("Accounts", run_summary.account_count.to_string()),
+    ];
+    if let Some((first, last)) = run_summary.date_range {
+        overview_pairs.push(("Dates", format!("{} to {}", settings.report_date(first), settings.report_date(last))));
+    }
+    overview_pairs.push(("Open lots", run_summary.open_lots.to_string()));
+    doc.pairs(&overview_pairs);
+
+    doc.new_page();
+
+    //  Gains and losses by year
+
+    doc.heading(&format!("Realized gains and losses by year ({})", settings.home_currency));
+
+    let years: Vec<summary::YearGains> = summary::gains_by_year(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?.into_iter().filter(|year| in_tax_year(year.year)).collect();
+
+    let mut rows: Vec<TableRow> = years.iter()
+        .map(|year| TableRow::new(vec![
+            year.year.to_string(),
+            fiat(year.realized_st),
+            fiat(year.realized_lt),
+            fiat(year.realized_total()),
+        ]))
+        .collect();
+
+    let (total_st, total_lt) = years.iter().fold((dec!(0), dec!(0)), |(st, lt), year| (st + year.realized_st, lt + year.realized_lt));
+    rows.push(TableRow::total(vec!["Total".to_string(), fiat(total_st), fiat(total_lt), fiat(total_st + total_lt)]));
+
+    doc.table(&["Year", "Short-term", "Long-term", "Total"], &rows);
+
+    //  Income by type
+
+    doc.heading(&format!("Income by type ({})", settings.home_currency));
+
+    let incomes: Vec<summary::IncomeTypeTotal> = summary::income_by_type_and_year(
+        &settings.home_currency,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    )?.into_iter().filter(|total| in_tax_year(total.year)).collect();
+
+    let mut rows: Vec<TableRow> = Vec::new();
+    let (mut year_total, mut grand_total) = (dec!(0), dec!(0));
+
+    for (idx, total) in incomes.iter().enumerate() {
+
+        let label = total.income_type.map_or("Other".to_string(), |income_type| income_type.to_string());
+        rows.push(TableRow::new(vec![total.year.to_string(), label, fiat(total.income)]));
+
+        year_total += total.income;
+
+        //  The year's subtotal follows its last kind of income
+        if incomes.get(idx + 1).is_none_or(|next| next.year != total.year) {
+            rows.push(TableRow::total(vec![total.year.to_string(), "Year total".to_string(), fiat(year_total)]));
+            grand_total += year_total;
+            year_total = dec!(0);
+        }
+    }
+
+    rows.push(TableRow::total(vec!["Total".to_string(), String::new(), fiat(grand_total)]));
+
+    doc.table(&["Year", "Income type", "Income"], &rows);
+
+    //  Holdings
+
+    let as_of = run_summary.date_range.map(|(_, last)| last);
+    doc.heading(&match as_of {
+        Some(date) => format!("Holdings as of {}", settings.report_date(date)),
+        None => "Holdings".to_string(),
+    });
+
+    let mut rows: Vec<TableRow> = run_summary.holdings.iter()
+        .map(|holding| TableRow::new(vec![
+            holding.ticker.clone(),
+            settings.output_precision.crypto(holding.amount),
+            fiat(holding.cost_basis),
+            holding.open_lots.to_string(),
+        ]))
+        .collect();
+
+    let total_basis: Decimal = run_summary.holdings.iter().map(|holding| holding.cost_basis).sum();
+    rows.push(TableRow::total(vec!["Total".to_string(), String::new(), fiat(total_basis), run_summary.open_lots.to_string()]));
+
+    doc.table(&["Ticker", "Amount", &format!("Cost basis ({})", settings.home_currency), "Open lots"], &rows);
+
+    doc.space(18.0);
+    doc.line(Font::Regular, 8.0, "Figures use like-kind values throughout. See the CSV and TXT reports for the detail of each \
+        transaction, lot and movement.");
+
+    let full_path: PathBuf = [
+        settings.export_path.clone(),
+        PathBuf::from(settings.report_file_name(&settings.tax_year_file_name("Summary.pdf"))),
+    ].iter().collect();
+
+    let mut file = BufWriter::new(create_report_file(settings, &full_path)?);
+    file.write_all(&doc.into_bytes())?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The PDF summary (see `_1_summary_to_pdf()`), as an `Exporter`, for `--pdf`.
+pub struct PdfSummary;
+
+impl Exporter for PdfSummary {
+
+    fn name(&self) -> String {
+        "Summary.pdf".to_string()
+    }
+
+    fn export(
+        &self,
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<(), Box<dyn Error>> {
+        _1_summary_to_pdf(settings, raw_acct_map, acct_map, ars, txns_map)
+    }
+}
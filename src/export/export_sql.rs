@@ -0,0 +1,254 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io::{BufWriter, Write};
+use std::collections::HashMap;
+use std::path::Path;
+use std::error::Error;
+use std::process::{Command, Stdio};
+
+use crptls::transaction::{ActionRecord, Transaction, TxKind};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::run_state::RunState;
+
+use crate::export::export_csv::create_report_file;
+
+
+const SCHEMA: &str = "\
+CREATE TABLE run (home_currency TEXT, costing_method TEXT, lk_treatment_enabled INTEGER, lk_cutoff_date TEXT);
+CREATE TABLE raw_accounts (account_num INTEGER PRIMARY KEY, name TEXT, ticker TEXT, is_margin INTEGER);
+CREATE TABLE transactions (tx_number INTEGER PRIMARY KEY, date TEXT, basis_date TEXT, memo TEXT, proceeds TEXT, fee TEXT, kind TEXT, \
+income_type TEXT);
+CREATE TABLE action_records (ar_number INTEGER PRIMARY KEY, tx_number INTEGER REFERENCES transactions, \
+account_num INTEGER REFERENCES raw_accounts, amount TEXT);
+CREATE TABLE lots (account_num INTEGER REFERENCES raw_accounts, lot_number INTEGER, lot_id TEXT, date_acquired TEXT, \
+basis_date TEXT, PRIMARY KEY (account_num, lot_number));
+CREATE TABLE movements (account_num INTEGER, lot_number INTEGER, tx_number INTEGER REFERENCES transactions, \
+ar_number INTEGER REFERENCES action_records, date TEXT, amount TEXT, cost_basis TEXT, proceeds TEXT, \
+cost_basis_lk TEXT, proceeds_lk TEXT, FOREIGN KEY (account_num, lot_number) REFERENCES lots);
+";
+
+/// The tables of a `--db` database, which keep every run's rows (by its `run_id`), rather than just the last run's.
+const DB_SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS runs (run_id INTEGER PRIMARY KEY, processed_at TEXT, through TEXT, home_currency TEXT, \
+costing_method TEXT, lk_treatment_enabled INTEGER, lk_cutoff_date TEXT, state TEXT);
+CREATE TABLE IF NOT EXISTS raw_accounts (run_id INTEGER REFERENCES runs, account_num INTEGER, name TEXT, ticker TEXT, \
+is_margin INTEGER, PRIMARY KEY (run_id, account_num));
+CREATE TABLE IF NOT EXISTS transactions (run_id INTEGER REFERENCES runs, tx_number INTEGER, date TEXT, basis_date TEXT, \
+memo TEXT, proceeds TEXT, fee TEXT, kind TEXT, income_type TEXT, PRIMARY KEY (run_id, tx_number));
+CREATE TABLE IF NOT EXISTS action_records (run_id INTEGER REFERENCES runs, ar_number INTEGER, tx_number INTEGER, \
+account_num INTEGER, amount TEXT, PRIMARY KEY (run_id, ar_number));
+CREATE TABLE IF NOT EXISTS lots (run_id INTEGER REFERENCES runs, account_num INTEGER, lot_number INTEGER, lot_id TEXT, \
+date_acquired TEXT, basis_date TEXT, PRIMARY KEY (run_id, account_num, lot_number));
+CREATE TABLE IF NOT EXISTS movements (run_id INTEGER REFERENCES runs, account_num INTEGER, lot_number INTEGER, \
+tx_number INTEGER, ar_number INTEGER, date TEXT, amount TEXT, cost_basis TEXT, proceeds TEXT, cost_basis_lk TEXT, \
+proceeds_lk TEXT);
+";
+
+/// The program the `--db` database is read and written with.
+const SQLITE3: &str = "sqlite3";
+
+/// Writes the processed maps to `path` as an SQL script that creates (or replaces) one table each for the raw
+/// accounts, transactions, action records, lots and movements, i.e., for `sqlite3 cryptools.db < path`.  Amounts
+/// are exact decimal strings, so `CAST(amount AS REAL)` for arithmetic.  Dates are YYYY-MM-DD.
+pub fn _1_processed_maps_to_sql(
+    settings: &ImportProcessParameters,
+    path: &Path,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut file = BufWriter::new(create_report_file(settings, path)?);
+
+    writeln!(file, "-- Processed by cryptools. Load with: sqlite3 cryptools.db < {}", path.display())?;
+    writeln!(file, "BEGIN TRANSACTION;")?;
+
+    for table in ["movements", "lots", "action_records", "transactions", "raw_accounts", "run"] {
+        writeln!(file, "DROP TABLE IF EXISTS {};", table)?;
+    }
+    write!(file, "{}", SCHEMA)?;
+
+    writeln!(file, "INSERT INTO run VALUES ({}, {}, {}, {});",
+        text(&settings.home_currency),
+        text(&settings.costing_method.to_string()),
+        settings.lk_treatment_enabled as u8,
+        if settings.lk_treatment_enabled { text(&settings.lk_cutoff_date.to_string()) } else { "NULL".to_string() },
+    )?;
+
+    write_inserts(&mut file, "", raw_acct_map, acct_map, ars, txns_map)?;
+
+    writeln!(file, "COMMIT;")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Adds the run of the processed maps to the SQLite database at `path` (created if need be), as a new row of `runs`,
+/// with its open lots (the `state`, from which the next `--db` run resumes, see `latest_db_state()`), and its rows
+/// of the raw accounts, transactions, action records, lots and movements, each under its `run_id`.  The rows of
+/// earlier runs are kept, for querying them all with SQL.  It's written with the `sqlite3` program, in a single
+/// transaction, so a failed run adds nothing.
+pub fn _2_processed_maps_to_db(
+    settings: &ImportProcessParameters,
+    path: &Path,
+    state: &RunState,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut sql: Vec<u8> = Vec::new();
+
+    writeln!(sql, "BEGIN TRANSACTION;")?;
+    write!(sql, "{}", DB_SCHEMA)?;
+
+    writeln!(sql, "INSERT INTO runs (processed_at, through, home_currency, costing_method, lk_treatment_enabled, \
+        lk_cutoff_date, state) VALUES ({}, '{}', {}, {}, {}, {}, {});",
+        text(&chrono::Local::now().to_rfc3339()),
+        state.through,
+        text(&settings.home_currency),
+        text(&settings.costing_method.to_string()),
+        settings.lk_treatment_enabled as u8,
+        if settings.lk_treatment_enabled { text(&settings.lk_cutoff_date.to_string()) } else { "NULL".to_string() },
+        text(&toml::to_string(state)?),
+    )?;
+
+    write_inserts(&mut sql, "(SELECT MAX(run_id) FROM runs), ", raw_acct_map, acct_map, ars, txns_map)?;
+
+    writeln!(sql, "COMMIT;")?;
+
+    sqlite3(path, &sql).map_err(|e| format!("Couldn't add the run to the --db database {}: {}", path.display(), e))?;
+
+    Ok(())
+}
+
+/// The open lots saved by the latest run added to the SQLite database at `path` (see `_2_processed_maps_to_db()`),
+/// for the next run to resume from, or `None` if there's no database there yet.
+pub fn latest_db_state(path: &Path) -> Result<Option<RunState>, Box<dyn Error>> {
+
+    if !path.exists() { return Ok(None) }
+
+    let state = sqlite3(path, b"SELECT state FROM runs ORDER BY run_id DESC LIMIT 1;")
+        .map_err(|e| format!("Couldn't read the --db database {}: {}", path.display(), e))?;
+
+    match state.trim().is_empty() {
+        true => Ok(None),
+        false => Ok(Some(toml::from_str(&state)?)),
+    }
+}
+
+/// Runs `sqlite3` on the database at `path`, with the `sql` as its input, stopping at any error.  Returns what it
+/// printed.
+fn sqlite3(path: &Path, sql: &[u8]) -> Result<String, Box<dyn Error>> {
+
+    let mut child = Command::new(SQLITE3)
+        .arg("-batch")
+        .arg("-bail")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't run {} (is it installed?): {}", SQLITE3, e))?;
+
+    //  Written from its own thread, so that a large run can't deadlock against the output being read
+    let mut stdin = child.stdin.take().unwrap();
+    let sql = sql.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&sql));
+
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into())
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Writes an `INSERT` of each raw account, transaction, action record, lot and movement, with `run_id` (i.e., `1, `)
+/// leading each row's values.
+fn write_inserts(
+    out: &mut impl Write,
+    run_id: &str,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<(), Box<dyn Error>> {
+
+    for num in 1..=raw_acct_map.len() {
+        let raw_acct = raw_acct_map.get(&(num as u16)).unwrap();
+        writeln!(out, "INSERT INTO raw_accounts VALUES ({}{}, {}, {}, {});",
+            run_id, raw_acct.account_num, text(&raw_acct.name), text(&raw_acct.ticker), raw_acct.is_margin as u8)?;
+    }
+
+    for num in 1..=txns_map.len() {
+        let txn = txns_map.get(&(num as u32)).unwrap();
+        let kind = match txn.kind {
+            TxKind::Standard => "standard",
+            TxKind::Synthetic => "synthetic",
+            TxKind::Inherited => "inherited",
+            TxKind::CarriedForward => "carried-forward",
+            TxKind::CarriedForwardInherited => "carried-forward-inherited",
+            TxKind::TransferFee => "transfer-fee",
+            TxKind::Gift => "gift",
+            TxKind::Donation => "donation",
+            TxKind::Lost => "lost",
+            TxKind::DerivativePnl => "derivative-pnl",
+            TxKind::PoolCarryover => "pool-carryover",
+            TxKind::TokenMigration => "token-migration",
+            TxKind::Rebase => "rebase",
+            TxKind::EntitySale => "entity-sale",
+            TxKind::DustSweep => "dust-sweep",
+        };
+        let income_type = txn.income_type.map_or("NULL".to_string(), |income_type| text(&income_type.to_string()));
+        writeln!(out, "INSERT INTO transactions VALUES ({}{}, '{}', '{}', {}, '{}', '{}', '{}', {});",
+            run_id, txn.tx_number, txn.date, txn.date_for_basis_purposes, text(&txn.user_memo), txn.proceeds, txn.fee, kind,
+            income_type)?;
+    }
+
+    for num in 1..=ars.len() {
+        let ar = ars.get(&(num as u32)).unwrap();
+        writeln!(out, "INSERT INTO action_records VALUES ({}{}, {}, {}, '{}');",
+            run_id, ar.self_ar_key, ar.tx_key, ar.account_key, ar.amount)?;
+    }
+
+    for num in 1..=acct_map.len() {
+
+        let acct = acct_map.get(&(num as u16)).unwrap();
+
+        for lot in acct.list_of_lots.borrow().iter() {
+
+            writeln!(out, "INSERT INTO lots VALUES ({}{}, {}, '{}', '{}', '{}');",
+                run_id, lot.account_key, lot.lot_number, lot.id(), lot.date_of_first_mvmt_in_lot, lot.date_for_basis_purposes)?;
+
+            for mvmt in lot.movements.borrow().iter() {
+                writeln!(out, "INSERT INTO movements VALUES ({}{}, {}, {}, {}, '{}', '{}', '{}', '{}', '{}', '{}');",
+                    run_id,
+                    lot.account_key,
+                    lot.lot_number,
+                    mvmt.transaction_key,
+                    mvmt.action_record_key,
+                    mvmt.date,
+                    mvmt.amount,
+                    mvmt.cost_basis.get(),
+                    mvmt.proceeds.get(),
+                    mvmt.cost_basis_lk.get(),
+                    mvmt.proceeds_lk.get(),
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An SQL string literal.
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -1,18 +1,21 @@
 // Copyright (c) 2017-2023, scoobybejesus
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
-use std::fs::OpenOptions;
 use std::collections::HashMap;
-use std::path::PathBuf;
 use std::error::Error;
-use std::io::prelude::Write;
+use std::io::Write;
 
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use crptls::transaction::{Transaction, ActionRecord};
+use crptls::transaction::{Transaction, ActionRecord, Polarity, TxType};
 use crptls::account::{Account, RawAccount};
 use crptls::core_functions::ImportProcessParameters;
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::summary::RunSummary;
+
+use crate::export::export_csv::report_writer;
+use crate::export::export_je;
 
 
 pub fn _1_account_lot_detail_to_txt(
@@ -61,174 +64,203 @@ pub fn _1_account_lot_detail_to_txt(
 
 
 
-    let file_name = PathBuf::from("T1_Acct_lot_detail.txt");
-    let path = PathBuf::from(&settings.export_path.clone());
-    let full_path: PathBuf = [path, file_name].iter().collect();
-
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(full_path)?;
+    let mut file = report_writer(settings, "T1_Acct_lot_detail.txt")?;
 
     let length = acct_map.len();
 
-    let home_currency = &settings.home_currency;
+    write_report_header(&mut file, settings,
+        settings.report_language.text("Account Listing - All Lots - All Movements - with high level of detail."))?;
 
-    writeln!(file, "Account Listing - All Lots - All Movements - with high level of detail.
-\nCosting method used: {}.
-Home currency: {}
-Enable like-kind treatment: {}",
-        settings.costing_method,
-        home_currency,
-        settings.lk_treatment_enabled
-    )?;
+    for j in 1..=length {
 
-    if settings.lk_treatment_enabled {
-        writeln!(file, "Like-kind cut-off date: {}.",
-            settings.lk_cutoff_date
-        )?;
+        let acct = acct_map.get(&(j as u16)).unwrap();
+
+        if !settings.is_reported_account(raw_acct_map.get(&acct.raw_key).unwrap()) { continue }
+
+        write_account_lot_detail(&mut file, settings, raw_acct_map, acct_map, ars, txns_map, acct)?;
     }
 
-    for j in 1..=length {
+    file.flush()?;
 
-        let acct = acct_map.get(&(j as u16)).unwrap();
-        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
-        let ticker = &raw_acct.ticker;
+    Ok(())
+}
 
-        if acct.list_of_lots.borrow().len() > 0 {
+/// Writes the first lines of a text report: its `title`, then the settings of the run, in the `report_language`.
+pub(crate) fn write_report_header(
+    file: &mut impl Write,
+    settings: &ImportProcessParameters,
+    title: &str,
+) -> Result<(), Box<dyn Error>> {
 
-            writeln!(file, "\n\n=====================================")?;
-            writeln!(file, "{} {}", raw_acct.name, ticker)?;
+    let lang = settings.report_language;
 
-            let acct_bal_line;
+    writeln!(file, "{}\n", title)?;
+    writeln!(file, "{}", lang.fill("Costing method used: {}.", &[&lang.text(&settings.costing_method.to_string())]))?;
+    writeln!(file, "{}", lang.fill("Home currency: {}", &[&settings.home_currency]))?;
+    writeln!(file, "{}", lang.fill("Enable like-kind treatment: {}", &[&lang.text(&settings.lk_treatment_enabled.to_string())]))?;
 
-            if raw_acct.is_home_currency(home_currency) {
-                acct_bal_line = format!("Account balance: {:.2} {}; Total cost basis: {:.2}",
-                    acct.get_sum_of_amts_in_lots().to_string().as_str().parse::<f32>()?,
-                    ticker,
-                    acct.get_sum_of_lk_basis_in_lots().to_string().as_str().parse::<f32>()?
-                );
-            } else {
-                acct_bal_line = format!("Account balance: {} {}; Total cost basis: {:.2}",
-                    acct.get_sum_of_amts_in_lots(),
-                    ticker,
-                    acct.get_sum_of_lk_basis_in_lots().to_string().as_str().parse::<f32>()?
-                );
-            }
+    if settings.lk_treatment_enabled {
+        writeln!(file, "{}", lang.fill("Like-kind cut-off date: {}.", &[&settings.report_date(settings.lk_cutoff_date)]))?;
+    }
 
-            writeln!(file, "{}", acct_bal_line)?;
+    Ok(())
+}
+
+/// Writes one account's section of the lot detail report (balance, then every lot and its movements).
+fn write_account_lot_detail(
+    file: &mut impl Write,
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    acct: &Account,
+) -> Result<(), Box<dyn Error>> {
+
+    let home_currency = &settings.home_currency;
+    let lang = settings.report_language;
+    let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+    let ticker = &raw_acct.ticker;
+
+    if acct.list_of_lots.borrow().len() > 0 {
+
+        writeln!(file, "\n\n=====================================")?;
+        writeln!(file, "{} {}", raw_acct.name, ticker)?;
+
+        let acct_bal_line;
 
+        if raw_acct.is_home_currency(home_currency) {
+            acct_bal_line = lang.fill("Account balance: {} {}; Total cost basis: {}", &[
+                &settings.output_precision.fiat(acct.get_sum_of_amts_in_lots()),
+                ticker,
+                &settings.output_precision.fiat(acct.get_sum_of_lk_basis_in_lots()),
+            ]);
         } else {
-            continue
+            acct_bal_line = lang.fill("Account balance: {} {}; Total cost basis: {}", &[
+                &settings.output_precision.crypto(acct.get_sum_of_amts_in_lots()),
+                ticker,
+                &settings.output_precision.fiat(acct.get_sum_of_lk_basis_in_lots()),
+            ]);
         }
 
-        if raw_acct.is_margin { writeln!(file, "Margin Account")?; }
+        writeln!(file, "{}", acct_bal_line)?;
 
-        for (lot_idx, lot) in acct.list_of_lots.borrow().iter().enumerate() {
+    } else {
+        return Ok(())
+    }
 
-            let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
+    if raw_acct.is_margin { writeln!(file, "{}", lang.text("Margin Account"))?; }
 
-            let formatted_basis: String;
-            if lk_lot_basis == dec!(0) {
-                formatted_basis = "0.00".to_string()
-            } else { formatted_basis = lk_lot_basis.to_string() }
+    for lot in acct.list_of_lots.borrow().iter() {
 
-            let movements_sum = lot.get_sum_of_amts_in_lot();
+        let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
 
-            let formatted_sum: String;
-            if movements_sum == dec!(0) {
-                formatted_sum = "0.00".to_string()
-            } else { formatted_sum = movements_sum.to_string() }
+        let movements_sum = lot.get_sum_of_amts_in_lot();
 
-            if acct.list_of_lots.borrow().len() > 0 {
+        let formatted_sum: String;
+        if movements_sum == dec!(0) {
+            formatted_sum = settings.output_precision.fiat(dec!(0))
+        } else { formatted_sum = settings.output_precision.crypto(movements_sum) }
+
+        if acct.list_of_lots.borrow().len() > 0 {
+
+            writeln!(file, "-------------------------")?;
+            writeln!(file, "{}", lang.fill("  Lot {}", &[&lot.id()]))?;
+
+            let lot_sum_row;
+
+            if raw_acct.is_home_currency(home_currency) {
+                lot_sum_row = lang.fill("    • Σ: {} {}, with remaining cost basis of {} {} and basis date of {}", &[
+                    &settings.output_precision.fiat(movements_sum),
+                    ticker,
+                    &settings.output_precision.fiat(lk_lot_basis),
+                    home_currency,
+                    &settings.report_date(lot.date_for_basis_purposes),
+                ])
+            } else {
+                lot_sum_row = lang.fill("    • Σ: {} {}, with remaining cost basis of {} {} and basis date of {}", &[
+                    &formatted_sum,
+                    ticker,
+                    &settings.output_precision.fiat(lk_lot_basis),
+                    home_currency,
+                    &settings.report_date(lot.date_for_basis_purposes),
+                ])
+            }
+            writeln!(file, "{}", lot_sum_row)?;
+            writeln!(file, "{}", lang.text("     Movements:"))?;
 
-                writeln!(file, "-------------------------")?;
-                writeln!(file, "  Lot {}", (lot_idx+1))?;
+            for (m_idx, mvmt) in lot.movements.borrow().iter().enumerate() {
 
-                let lot_sum_row;
+                let txn = txns_map.get(&mvmt.transaction_key).unwrap();
+                let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+
+                let description_string: String;
 
                 if raw_acct.is_home_currency(home_currency) {
-                    lot_sum_row = format!("    • Σ: {:.2} {}, with remaining cost basis of {:.2} {} and basis date of {}",
-                        formatted_sum.to_string().as_str().parse::<f32>()?,
+                    description_string = lang.fill("\t{}.  {:<8} {} (Txn #{:>4}) {} txn on {:10}. - {}", &[
+                        &(m_idx+1),
+                        &settings.output_precision.fiat(mvmt.amount),
                         ticker,
-                        formatted_basis.to_string().as_str().parse::<f32>()?,
-                        home_currency,
-                        lot.date_for_basis_purposes
-                    )
+                        &mvmt.transaction_key,
+                        &lang.text(&tx_type.to_string()),
+                        &settings.report_date(mvmt.date),
+                        &txn.user_memo,
+                    ]);
                 } else {
-                    lot_sum_row = format!("    • Σ: {} {}, with remaining cost basis of {:.2} {} and basis date of {}",
-                        formatted_sum,
+                    description_string = lang.fill("\t{}.  {:<8} {} (Txn #{:>4}) {} txn on {:10}. - {}", &[
+                        &(m_idx+1),
+                        &settings.output_precision.crypto(mvmt.amount),
                         ticker,
-                        formatted_basis.to_string().as_str().parse::<f32>()?,
-                        home_currency,
-                        lot.date_for_basis_purposes
-                    )
+                        &mvmt.transaction_key,
+                        &lang.text(&tx_type.to_string()),
+                        &settings.report_date(mvmt.date),
+                        &txn.user_memo,
+                    ]);
+                };
+
+                writeln!(file, "{}", description_string)?;
+
+                let lk_proceeds = mvmt.proceeds_lk.get();
+                let lk_cost_basis = mvmt.cost_basis_lk.get();
+                let gain_loss: Decimal;
+
+                // if mvmt.amount > dec!(0) { // Can't have a gain on an incoming txn
+                //     gain_loss = dec!(0)
+                // } else
+                if raw_acct.is_home_currency(home_currency) {  //  Can't have a gain disposing home currency
+                    gain_loss = dec!(0)
+                // } else if tx_type == TxType::ToSelf {   //  Can't have a gain sending to yourself
+                //     gain_loss = dec!(0)
+                } else {
+                    gain_loss = lk_proceeds + lk_cost_basis + mvmt.wash_sale_adjustment.get();
                 }
-                writeln!(file, "{}", lot_sum_row)?;
-                writeln!(file, "     Movements:")?;
-
-                for (m_idx, mvmt) in lot.movements.borrow().iter().enumerate() {
-
-                    let txn = txns_map.get(&mvmt.transaction_key).unwrap();
-                    let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
-
-                    let description_string: String;
-
-                    if raw_acct.is_home_currency(home_currency) {
-                        description_string = format!("\t{}.  {:<8.2} {} (Txn #{:>4}) {:>9} txn on {:10}. - {}",
-                            (m_idx+1),
-                            mvmt.amount.to_string().as_str().parse::<f32>()?,
-                            ticker,
-                            mvmt.transaction_key,
-                            tx_type,
-                            mvmt.date,
-                            txn.user_memo
-                        );
-                    } else {
-                        description_string = format!("\t{}.  {:<8} {} (Txn #{:>4}) {:>9} txn on {:10}. - {}",
-                            (m_idx+1),
-                            mvmt.amount,
-                            ticker,
-                            mvmt.transaction_key,
-                            tx_type,
-                            mvmt.date,
-                            txn.user_memo
-                        );
-                    };
-
-                    writeln!(file, "{}", description_string)?;
-
-                    let lk_proceeds = mvmt.proceeds_lk.get();
-                    let lk_cost_basis = mvmt.cost_basis_lk.get();
-                    let gain_loss: Decimal;
-
-                    // if mvmt.amount > dec!(0) { // Can't have a gain on an incoming txn
-                    //     gain_loss = dec!(0)
-                    // } else
-                    if raw_acct.is_home_currency(home_currency) {  //  Can't have a gain disposing home currency
-                        gain_loss = dec!(0)
-                    // } else if tx_type == TxType::ToSelf {   //  Can't have a gain sending to yourself
-                    //     gain_loss = dec!(0)
-                    } else {
-                        gain_loss = lk_proceeds + lk_cost_basis;
-                    }
-
-                    let income = mvmt.get_income(ars, raw_acct_map,	acct_map, txns_map)?;
-                    let expense = mvmt.get_expense(ars, raw_acct_map, acct_map, txns_map)?;
-
-                    let activity_str = format!("\t    Proceeds: {:>10.2}; Cost basis: {:>10.2}; for Gain/loss: {} {:>10.2}; Inc.: {:>10.2}; Exp.: {:>10.2}.",
-                        lk_proceeds.to_string().as_str().parse::<f32>()?,
-                        lk_cost_basis.to_string().as_str().parse::<f32>()?,
-                        mvmt.get_term(acct_map, ars, txns_map),
-                        gain_loss.to_string().as_str().parse::<f32>()?,
-                        income.to_string().as_str().parse::<f32>()?,
-                        expense.to_string().as_str().parse::<f32>()?,
-                    );
-
-                    writeln!(file, "{}", activity_str)?;
 
+                let income = mvmt.get_income(ars, raw_acct_map,	acct_map, txns_map)?;
+                let expense = mvmt.get_expense(ars, raw_acct_map, acct_map, txns_map)?;
+
+                let activity_str = lang.fill("\t    Proceeds: {:>10}; Cost basis: {:>10}; for Gain/loss: {} {:>10}; Inc.: {:>10}; Exp.: {:>10}.", &[
+                    &settings.output_precision.fiat(lk_proceeds),
+                    &settings.output_precision.fiat(lk_cost_basis),
+                    &lang.text(&mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).abbr_string()),
+                    &settings.output_precision.fiat(gain_loss),
+                    &settings.output_precision.fiat(income),
+                    &settings.output_precision.fiat(expense),
+                ]);
+
+                writeln!(file, "{}", activity_str)?;
+
+                if mvmt.wash_sale_adjustment.get() > dec!(0)
+                    && settings.costing_method == InventoryCostingMethod::AdjustedCostBase {
+                    writeln!(file, "{}", lang.fill("\t    Superficial loss: loss denied (and added to the adjusted cost base): {:>10}.",
+                        &[&settings.output_precision.fiat(mvmt.wash_sale_adjustment.get())]))?;
+                } else if mvmt.wash_sale_adjustment.get() > dec!(0) {
+                    writeln!(file, "{}", lang.fill("\t    Wash sale: loss disallowed (and added to the replacement lot's basis): {:>10}.",
+                        &[&settings.output_precision.fiat(mvmt.wash_sale_adjustment.get())]))?;
+                } else if mvmt.wash_sale_adjustment.get() < dec!(0) {
+                    writeln!(file, "{}", lang.fill("\t    Wash sale replacement: disallowed loss included in cost basis: {:>10}.",
+                        &[&settings.output_precision.fiat(-mvmt.wash_sale_adjustment.get())]))?;
                 }
+
             }
         }
     }
@@ -236,6 +268,11 @@ Enable like-kind treatment: {}",
     Ok(())
 }
 
+/// The width of the widest lot ID of `acct`, to which the lot summaries pad each, so that the columns after it line up.
+fn lot_id_width(acct: &Account) -> usize {
+    acct.list_of_lots.borrow().iter().map(|lot| lot.id().len()).max().unwrap_or(0)
+}
+
 pub fn _2_account_lot_summary_to_txt(
     settings: &ImportProcessParameters,
     raw_acct_map: &HashMap<u16, RawAccount>,
@@ -257,79 +294,60 @@ pub fn _2_account_lot_summary_to_txt(
 
 
 
-    let file_name = PathBuf::from("T2_Acct_lot_summary.txt");
-    let path = PathBuf::from(&settings.export_path.clone());
-    let full_path: PathBuf = [path, file_name].iter().collect();
-
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(full_path)?;
+    let mut file = report_writer(settings, "T2_Acct_lot_summary.txt")?;
+    let lang = settings.report_language;
 
     let length = acct_map.len();
 
-    writeln!(file, "Account Listing - All Lots - No Movements - Summary detail.
-\nCosting method used: {}.
-Home currency: {}
-Enable like-kind treatment: {}",
-        settings.costing_method,
-        settings.home_currency,
-        settings.lk_treatment_enabled
-    )?;
-
-    if settings.lk_treatment_enabled {
-        writeln!(file, "Like-kind cut-off date: {}.",
-            settings.lk_cutoff_date
-        )?;
-    }
+    write_report_header(&mut file, settings,
+        lang.text("Account Listing - All Lots - No Movements - Summary detail."))?;
 
     for j in 1..=length {
 
         let acct = acct_map.get(&(j as u16)).unwrap();
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        if !settings.is_reported_account(raw_acct) { continue }
 
         if acct.list_of_lots.borrow().len() > 0 {
 
             writeln!(file, "\n=====================================")?;
             writeln!(file, "{} {}", raw_acct.name, raw_acct.ticker)?;
-            writeln!(file, "Account balance: {} {}; Total cost basis: {:.2}",
-                acct.get_sum_of_amts_in_lots(),
-                raw_acct.ticker,
-                acct.get_sum_of_lk_basis_in_lots().to_string().as_str().parse::<f32>()?
-            )?;
+            writeln!(file, "{}", lang.fill("Account balance: {} {}; Total cost basis: {}", &[
+                &settings.output_precision.crypto(acct.get_sum_of_amts_in_lots()),
+                &raw_acct.ticker,
+                &settings.output_precision.fiat(acct.get_sum_of_lk_basis_in_lots()),
+            ]))?;
         }
-        if raw_acct.is_margin { writeln!(file, "Margin Account")?; }
+        if raw_acct.is_margin { writeln!(file, "{}", lang.text("Margin Account"))?; }
 
-        for (lot_idx, lot) in acct.list_of_lots.borrow().iter().enumerate() {
+        let id_width = lot_id_width(acct);
 
-            let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
+        for lot in acct.list_of_lots.borrow().iter() {
 
-            let formatted_basis: String;
-            if lk_lot_basis == dec!(0) {
-                formatted_basis = "0.00".to_string()
-            } else { formatted_basis = lk_lot_basis.to_string() }
+            let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
 
             let movements_sum = lot.get_sum_of_amts_in_lot();
 
             let formatted_sum: String;
             if movements_sum == dec!(0) {
-                formatted_sum = "0.00".to_string()
-            } else { formatted_sum = movements_sum.to_string() }
+                formatted_sum = settings.output_precision.fiat(dec!(0))
+            } else { formatted_sum = settings.output_precision.crypto(movements_sum) }
 
             if acct.list_of_lots.borrow().len() > 0 {
 
-                writeln!(file, "  Lot {:>3} created {} w/ basis date {} • Σ: {:>12}, and cost basis of {:>10.2}",
-                    (lot_idx+1),
-                    lot.date_of_first_mvmt_in_lot,
-                    lot.date_for_basis_purposes,
-                    formatted_sum,
-                    formatted_basis.to_string().as_str().parse::<f32>()?,
-                )?;
+                writeln!(file, "{}", lang.fill("  Lot {} created {} w/ basis date {} • Σ: {:>12}, and cost basis of {:>10}", &[
+                    &format!("{:<1$}", lot.id(), id_width),
+                    &settings.report_date(lot.date_of_first_mvmt_in_lot),
+                    &lot.date_for_basis_purposes,
+                    &formatted_sum,
+                    &settings.output_precision.fiat(lk_lot_basis),
+                ]))?;
             }
         }
     }
 
+    file.flush()?;
+
     Ok(())
 }
 
@@ -351,37 +369,19 @@ pub fn _3_account_lot_summary_non_zero_to_txt(
 
 
 
-    let file_name = PathBuf::from("T3_Acct_lot_summary_non_zero.txt");
-    let path = PathBuf::from(&settings.export_path.clone());
-    let full_path: PathBuf = [path, file_name].iter().collect();
-
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(full_path)?;
+    let mut file = report_writer(settings, "T3_Acct_lot_summary_non_zero.txt")?;
+    let lang = settings.report_language;
 
     let length = acct_map.len();
 
-    writeln!(file, "Account Listing - Non-zero Lots - No Movements - Summary detail.
-\nCosting method used: {}.
-Home currency: {}
-Enable like-kind treatment: {}",
-        settings.costing_method,
-        settings.home_currency,
-        settings.lk_treatment_enabled
-    )?;
-
-    if settings.lk_treatment_enabled {
-        writeln!(file, "Like-kind cut-off date: {}.",
-            settings.lk_cutoff_date
-        )?;
-    }
+    write_report_header(&mut file, settings,
+        lang.text("Account Listing - Non-zero Lots - No Movements - Summary detail."))?;
 
     for j in 1..=length {
 
         let acct = acct_map.get(&(j as u16)).unwrap();
         let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        if !settings.is_reported_account(raw_acct) { continue }
         let amt_in_acct = acct.get_sum_of_amts_in_lots();
 
         if acct.list_of_lots.borrow().len() > 0 {
@@ -389,42 +389,447 @@ Enable like-kind treatment: {}",
 
                 writeln!(file, "\n=====================================")?;
                 writeln!(file, "{} {}", raw_acct.name, raw_acct.ticker)?;
-                writeln!(file, "Account balance: {} {}; Total cost basis: {:.2}",
-                    amt_in_acct,
-                    raw_acct.ticker,
-                    acct.get_sum_of_lk_basis_in_lots().to_string().as_str().parse::<f32>()?
-                )?;
+                writeln!(file, "{}", lang.fill("Account balance: {} {}; Total cost basis: {}", &[
+                    &settings.output_precision.crypto(amt_in_acct),
+                    &raw_acct.ticker,
+                    &settings.output_precision.fiat(acct.get_sum_of_lk_basis_in_lots()),
+                ]))?;
             } else {
                 continue
             }
         }
-        if raw_acct.is_margin { writeln!(file, "Margin Account")?; }
+        if raw_acct.is_margin { writeln!(file, "{}", lang.text("Margin Account"))?; }
 
-        for (lot_idx, lot) in acct.list_of_lots.borrow().iter().enumerate() {
+        let id_width = lot_id_width(acct);
 
-            let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
+        for lot in acct.list_of_lots.borrow().iter() {
 
-            let formatted_basis: String;
-            if lk_lot_basis == dec!(0) {
-                formatted_basis = "0.00".to_string()
-            } else { formatted_basis = lk_lot_basis.to_string() }
+            let lk_lot_basis = lot.get_sum_of_lk_basis_in_lot();
 
             let movements_sum = lot.get_sum_of_amts_in_lot();
 
             if acct.list_of_lots.borrow().len() > 0 && movements_sum > dec!(0) {
 
-                writeln!(file, "  Lot {:>3} created {} w/ basis date {} • Σ: {:>12}, and cost basis of {:>10.2}",
-                    (lot_idx+1),
-                    lot.date_of_first_mvmt_in_lot,
-                    lot.date_for_basis_purposes,
-                    movements_sum,
-                    formatted_basis.to_string().as_str().parse::<f32>()?,
-                )?;
+                writeln!(file, "{}", lang.fill("  Lot {} created {} w/ basis date {} • Σ: {:>12}, and cost basis of {:>10}", &[
+                    &format!("{:<1$}", lot.id(), id_width),
+                    &settings.report_date(lot.date_of_first_mvmt_in_lot),
+                    &lot.date_for_basis_purposes,
+                    &settings.output_precision.crypto(movements_sum),
+                    &settings.output_precision.fiat(lk_lot_basis),
+                ]))?;
+            }
+        }
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+pub fn _4_currency_report_to_txt(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    ticker: &str,
+) -> Result<(), Box<dyn Error>> {
+
+// Currency Report - ETH - All Accounts
+//
+// Costing method used: LIFO by lot creation date.
+// Home currency: USD
+// Enable like-kind treatment: false
+//
+// Holdings
+//     Exchange ETH: 1.7 ETH; cost basis of 510.00
+//     Total: 1.7 ETH; cost basis of 510.00
+//
+// Acquisitions
+//     Txn #   3 on 2018-02-01:        2 ETH into Exchange (Exchange); basis of 600.00 - Bought ETH
+//
+// Disposals
+//     Txn #   4 on 2018-06-01:     -0.3 ETH from Exchange (Exchange); proceeds of 150.00; basis of -90.00; ST gain/loss of 60.00 - Sold ETH
+//
+// (followed by the lot detail of every ETH account, as in T1_Acct_lot_detail.txt)
+
+    let matches_ticker = |acct: &Account| -> bool {
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        raw_acct.ticker.eq_ignore_ascii_case(ticker)
+    };
+
+    let mut acct_keys = Vec::new();
+
+    for j in 1..=acct_map.len() {
+        let acct = acct_map.get(&(j as u16)).unwrap();
+        if matches_ticker(acct) && !acct.list_of_lots.borrow().is_empty() {
+            acct_keys.push(j as u16);
+        }
+    }
+
+    if acct_keys.is_empty() {
+        return Err(format!("No activity was found for currency {}. No currency report was created.", ticker).into())
+    }
+
+    let ticker = ticker.to_uppercase();
+    let mut file = report_writer(settings, &format!("T4_Currency_report_{}.txt", ticker))?;
+
+    let lang = settings.report_language;
+
+    write_report_header(&mut file, settings, &lang.fill("Currency Report - {} - All Accounts", &[&ticker]))?;
+
+    writeln!(file, "\n{}", lang.text("Holdings"))?;
+
+    let mut total_amt = dec!(0);
+    let mut total_basis = dec!(0);
+
+    for acct_key in acct_keys.iter() {
+
+        let acct = acct_map.get(acct_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        let amt = acct.get_sum_of_amts_in_lots();
+        let basis = acct.get_sum_of_lk_basis_in_lots();
+
+        total_amt += amt;
+        total_basis += basis;
+
+        writeln!(file, "{}", lang.fill("    {} {}: {} {}; cost basis of {}{}", &[
+            &raw_acct.name,
+            &ticker,
+            &settings.output_precision.crypto(amt),
+            &ticker,
+            &settings.output_precision.fiat(basis),
+            &if raw_acct.is_margin { lang.text(" (margin)") } else { "" },
+        ]))?;
+    }
+
+    writeln!(file, "{}", lang.fill("    Total: {} {}; cost basis of {}", &[
+        &settings.output_precision.crypto(total_amt),
+        &ticker,
+        &settings.output_precision.fiat(total_basis),
+    ]))?;
+
+    let mut acquisitions = Vec::new();
+    let mut disposals = Vec::new();
+
+    for txn_num in 1..=txns_map.len() {
+
+        let txn = txns_map.get(&(txn_num as u32)).unwrap();
+        let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+
+        if tx_type == TxType::ToSelf { continue }
+
+        for ar_num in txn.action_record_idx_vec.iter() {
+
+            let ar = ars.get(ar_num).unwrap();
+
+            if !acct_keys.contains(&ar.account_key) { continue }
+
+            let acct = acct_map.get(&ar.account_key).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+
+            if raw_acct.is_margin { continue }
+
+            for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+                match ar.direction() {
+
+                    Polarity::Incoming => {
+
+                        acquisitions.push(lang.fill("    Txn #{:>4} on {}: {:>12} {} into {} ({}); basis of {} - {}", &[
+                            &txn.tx_number,
+                            &settings.report_date(txn.date),
+                            &settings.output_precision.crypto(mvmt.amount),
+                            &ticker,
+                            &raw_acct.name,
+                            &lang.text(&mvmt.friendly_tx_type(&tx_type)),
+                            &settings.output_precision.fiat(mvmt.cost_basis_lk.get()),
+                            &txn.user_memo,
+                        ]));
+                    }
+
+                    Polarity::Outgoing => {
+
+                        disposals.push(lang.fill("    Txn #{:>4} on {}: {:>12} {} from {} ({}); proceeds of {}; basis of {}; {} gain/loss of {} - {}", &[
+                            &txn.tx_number,
+                            &settings.report_date(txn.date),
+                            &settings.output_precision.crypto(mvmt.amount),
+                            &ticker,
+                            &raw_acct.name,
+                            &lang.text(&mvmt.friendly_tx_type(&tx_type)),
+                            &settings.output_precision.fiat(mvmt.proceeds_lk.get()),
+                            &settings.output_precision.fiat(mvmt.cost_basis_lk.get()),
+                            &lang.text(&mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).abbr_string()),
+                            &settings.output_precision.fiat(mvmt.get_lk_gain_or_loss()),
+                            &txn.user_memo,
+                        ]));
+                    }
+                }
+            }
+        }
+    }
+
+    writeln!(file, "\n{}", lang.text("Acquisitions"))?;
+    for line in acquisitions.iter() { writeln!(file, "{}", line)?; }
+
+    writeln!(file, "\n{}", lang.text("Disposals"))?;
+    for line in disposals.iter() { writeln!(file, "{}", line)?; }
+
+    writeln!(file, "\n\n{}", lang.text("Lot detail"))?;
+
+    for acct_key in acct_keys.iter() {
+
+        let acct = acct_map.get(acct_key).unwrap();
+
+        write_account_lot_detail(&mut file, settings, raw_acct_map, acct_map, ars, txns_map, acct)?;
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The lines of the drill-down report of `txn_num` (as in T8_Txn_report_<TXN>.txt): the transaction, each of its
+/// action records with every movement and its effect on the lot (the balance before and after), and its journal
+/// entry (`journal_entry`, from `export_je::journal_entry_lines_by_txn()`, if it has one in the tax year).
+pub fn transaction_report_lines(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    txn_num: u32,
+    journal_entry: Option<&Vec<String>>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let txn = txns_map.get(&txn_num).ok_or(format!("There is no transaction {}.", txn_num))?;
+    let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+    let precision = &settings.output_precision;
+    let lang = settings.report_language;
+
+    let mut lines = vec![
+        lang.fill("Txn {} on {}: {} ({}) - {}", &[
+            &txn_num, &settings.report_date(txn.date), &lang.text(&tx_type.to_string()), &format!("{:?}", txn.kind), &txn.user_memo,
+        ]),
+        format!("    {}", txn.get_auto_memo(ars, raw_acct_map, acct_map, &settings.home_currency)?),
+        lang.fill("    Proceeds: {}", &[&precision.fiat(txn.proceeds)]),
+    ];
+
+    if let Some(row) = txn.source_row {
+        lines.push(lang.fill("    From row {} of the CSV Input File", &[&row]));
+    }
+
+    lines.push(String::new());
+    lines.push(lang.text("Movements").to_string());
+
+    for ar_num in txn.action_record_idx_vec.iter() {
+
+        let ar = ars.get(ar_num).unwrap();
+        let acct = acct_map.get(&ar.account_key).unwrap();
+        let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+        let is_home_currency = raw_acct.is_home_currency(&settings.home_currency);
+        let amount = |amt: Decimal| if is_home_currency { precision.fiat(amt) } else { precision.crypto(amt) };
+
+        lines.push(format!("    {} {} (#{}; {}): {} {} ({})",
+            raw_acct.name,
+            raw_acct.ticker,
+            raw_acct.account_num,
+            lang.text(&raw_acct.margin_string()),
+            amount(ar.amount),
+            raw_acct.ticker,
+            lang.text(&ar.direction().to_string()),
+        ));
+
+        for mvmt in ar.get_mvmts_in_ar_in_lot_date_order().iter() {
+
+            let lot = mvmt.get_lot(acct_map, ars);
+            let lot_mvmts = lot.movements.borrow();
+
+            //  The lot's balance once this movement was posted to it
+            let balance_after: Decimal = lot_mvmts.iter()
+                .take_while(|lot_mvmt| lot_mvmt.action_record_key != mvmt.action_record_key)
+                .map(|lot_mvmt| lot_mvmt.amount)
+                .sum::<Decimal>() + mvmt.amount;
+
+            let mut line = lang.fill("        Lot {:>7} (basis date {}): {:>12}; basis of {}", &[
+                &lot.id(),
+                &settings.report_date(lot.date_for_basis_purposes),
+                &amount(mvmt.amount),
+                &precision.fiat(mvmt.cost_basis_lk.get()),
+            ]);
+
+            if ar.direction() == Polarity::Outgoing && !is_home_currency && tx_type != TxType::ToSelf {
+                line.push_str(&lang.fill("; proceeds of {}; {} gain/loss of {}", &[
+                    &precision.fiat(mvmt.proceeds_lk.get()),
+                    &lang.text(&mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).abbr_string()),
+                    &precision.fiat(mvmt.get_lk_gain_or_loss()),
+                ]));
             }
+
+            if lot_mvmts.first().is_some_and(|first| first.action_record_key == mvmt.action_record_key) {
+                line.push_str(&lang.fill("; new lot of {}", &[&amount(balance_after)]));
+            } else {
+                line.push_str(&lang.fill("; lot balance {} -> {}", &[&amount(balance_after - mvmt.amount), &amount(balance_after)]));
+            }
+
+            lines.push(line);
         }
     }
 
+    lines.push(String::new());
+    lines.push(lang.text("Journal entry").to_string());
+
+    match journal_entry {
+        Some(entry) => lines.extend(entry.iter().map(|line| if line.is_empty() { String::new() } else { format!("    {}", line) })),
+        None => lines.push(lang.text("    None (the transaction isn't in the tax year).").to_string()),
+    }
+
+    Ok(lines)
+}
+
+/// The drill-down report lines (see `transaction_report_lines()`) of every transaction, by transaction number.
+pub fn transaction_reports(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+) -> Result<HashMap<u32, Vec<String>>, Box<dyn Error>> {
+
+    let journal_entries = export_je::journal_entry_lines_by_txn(settings, raw_acct_map, acct_map, ars, txns_map)?;
+
+    let mut reports = HashMap::new();
+
+    for txn_num in 1..=txns_map.len() as u32 {
+        let lines = transaction_report_lines(
+            settings, raw_acct_map, acct_map, ars, txns_map, txn_num, journal_entries.get(&txn_num)
+        )?;
+        reports.insert(txn_num, lines);
+    }
+
+    Ok(reports)
+}
+
+/// Exports the drill-down report of a single transaction (T8_Txn_report_<TXN>.txt): every movement arising from it,
+/// the effect of each on its lot, and its journal entry.
+pub fn _6_transaction_report_to_txt(
+    settings: &ImportProcessParameters,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    txn_num: u32,
+) -> Result<(), Box<dyn Error>> {
+
+// Transaction Report - Txn 12
+//
+// Costing method used: LIFO by lot creation date.
+// Home currency: USD
+// Enable like-kind treatment: false
+//
+// Txn 12 on 2016-12-01: Exchange (Standard) - TWELFTH
+//     Paid -1.5 BTC for 400 XMR, valued at 2000.00 USD.
+//     Proceeds: 2000.00
+//
+// Movements
+//     Wallet BTC (#3; Non-margin): -1.5 BTC (Outgoing)
+//         Lot    2 (basis date 2016-07-01):  -0.50000000; basis of -142.86; proceeds of 666.67; ST gain/loss of 523.81; lot balance 0.50000000 -> 0.00000000
+//
+// Journal entry
+//     (as in J1_Journal_Entries.txt)
+
+    if !txns_map.contains_key(&txn_num) {
+        return Err(format!("There is no transaction {}. No transaction report was created.", txn_num).into())
+    }
+
+    let journal_entries = export_je::journal_entry_lines_by_txn(settings, raw_acct_map, acct_map, ars, txns_map)?;
+    let lines = transaction_report_lines(
+        settings, raw_acct_map, acct_map, ars, txns_map, txn_num, journal_entries.get(&txn_num)
+    )?;
+
+    let mut file = report_writer(settings, &format!("T8_Txn_report_{}.txt", txn_num))?;
+
+    write_report_header(&mut file, settings,
+        &settings.report_language.fill("Transaction Report - Txn {}", &[&txn_num]))?;
+
+    writeln!(file)?;
+
+    for line in lines.iter() {
+        writeln!(file, "{}", line)?;
+    }
+
+    file.flush()?;
+
     Ok(())
 }
 
+pub fn _5_run_summary_to_txt(
+    settings: &ImportProcessParameters,
+    title: &str,
+    summary: &RunSummary,
+) -> Result<(), Box<dyn Error>> {
+
+// Summary - alice
+//
+// Costing method used: LIFO by lot creation date.
+// Home currency: USD
+// Enable like-kind treatment: false
+//
+// Transactions processed: 12, 2016-02-01 through 2016-12-01
+// Realized gain/loss: 1780.00 (ST 1780.00; LT 0.00)
+//     2016: 1780.00 (ST 1780.00; LT 0.00)
+// Income: 0.00
+// Expense: 0.00
+// Open lots: 5
+//
+// Holdings (by cost basis)
+//     XMR: 360 XMR; cost basis of 1220.00 in 2 lots
+
+    let mut file = report_writer(settings, "T5_Summary.txt")?;
+    let lang = settings.report_language;
+
+    write_report_header(&mut file, settings, &lang.fill("Summary - {}", &[&title]))?;
+
+    let date_range = match summary.date_range {
+        Some((first, last)) => lang.fill(", {} through {}", &[&settings.report_date(first), &settings.report_date(last)]),
+        None => "".to_string(),
+    };
+
+    writeln!(file, "\n{}{}", lang.fill("Transactions processed: {}", &[&summary.txn_count]), date_range)?;
+
+    writeln!(file, "{}", lang.fill("Realized gain/loss: {} (ST {}; LT {})", &[
+        &settings.output_precision.fiat(summary.realized_total()),
+        &settings.output_precision.fiat(summary.realized_st),
+        &settings.output_precision.fiat(summary.realized_lt),
+    ]))?;
+
+    for year in summary.realized_by_year.iter() {
+        writeln!(file, "{}", lang.fill("    {}: {} (ST {}; LT {})", &[
+            &year.year,
+            &settings.output_precision.fiat(year.realized_total()),
+            &settings.output_precision.fiat(year.realized_st),
+            &settings.output_precision.fiat(year.realized_lt),
+        ]))?;
+    }
+
+    writeln!(file, "{}", lang.fill("Income: {}", &[&settings.output_precision.fiat(summary.income)]))?;
+    writeln!(file, "{}", lang.fill("Expense: {}", &[&settings.output_precision.fiat(summary.expense)]))?;
+    writeln!(file, "{}", lang.fill("Open lots: {}", &[&summary.open_lots]))?;
+
+    writeln!(file, "\n{}", lang.text("Holdings (by cost basis)"))?;
+
+    for holding in summary.holdings.iter() {
+        let lots = if holding.open_lots == 1 { "    {}: {} {}; cost basis of {} in {} lot" } else { "    {}: {} {}; cost basis of {} in {} lots" };
+        writeln!(file, "{}", lang.fill(lots, &[
+            &holding.ticker,
+            &settings.output_precision.crypto(holding.amount),
+            &holding.ticker,
+            &settings.output_precision.fiat(holding.cost_basis),
+            &holding.open_lots,
+        ]))?;
+    }
 
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,309 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::error::Error;
+
+use rust_decimal::Decimal;
+
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::{ImportProcessParameters, Timings};
+use crptls::decimal_utils::ReportLocale;
+
+use crate::export::export_all;
+use crate::export::export_csv::{check_overwrite, commit_written_reports, create_report_file};
+
+
+/// Exports the default reports (or the chosen `reports`), as `export_all::export()` would, into a single Reports.xlsx
+/// workbook instead of separate files, one sheet per report, in the order C*, T*, J*.  A CSV report's cells are numbers where they
+/// parse as one, and a TXT report is one line per row in the first column.
+pub fn _1_reports_to_xlsx(
+    settings: &ImportProcessParameters,
+    reports: &[export_all::Report],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    account_map: &HashMap<u16, Account>,
+    action_records_map: &HashMap<u32, ActionRecord>,
+    transactions_map: &HashMap<u32, Transaction>,
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    let full_path: PathBuf = [settings.export_path.clone(), PathBuf::from(settings.report_file_name("Reports.xlsx"))]
+        .iter().collect();
+
+    check_overwrite(settings, &full_path)?;
+
+    //  The reports are written as usual, to a scratch directory, then read back in as sheets
+    let scratch_dir = std::env::temp_dir().join(format!("cryptools_workbook_{}", std::process::id()));
+    fs::create_dir_all(&scratch_dir)?;
+
+    let mut scratch_settings = settings.clone();
+    scratch_settings.export_path = scratch_dir.clone();
+    scratch_settings.excel_bom = false;
+    //  The sheets are named (and ordered) after the reports' own file names
+    scratch_settings.file_name_prefix = None;
+    scratch_settings.file_name_date = None;
+    //  So that numbers are cells Excel shows in its own locale, rather than text in the REPORT_LOCALE
+    scratch_settings.output_precision.locale = ReportLocale::Plain;
+
+    let result = export_all::export(
+        &scratch_settings,
+        reports,
+        raw_acct_map,
+        account_map,
+        action_records_map,
+        transactions_map,
+        timings,
+    )
+    //  So that the scratch reports are read complete, from their own names
+    .and_then(|_| commit_written_reports().map_err(|e| e.into()))
+    .and_then(|_| read_sheets(&scratch_dir));
+
+    fs::remove_dir_all(&scratch_dir)?;
+
+    let sheets = result?;
+
+    write_workbook(settings, &full_path, &sheets)?;
+
+    Ok(())
+}
+
+/// A sheet's name (at most 31 characters, as Excel requires) and its rows of cells.
+type Sheet = (String, Vec<Vec<String>>);
+
+fn read_sheets(dir: &Path) -> Result<Vec<Sheet>, Box<dyn Error>> {
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+
+    //  C1, C2, ..., C13, then T1, ..., then J1
+    let sort_key = |path: &PathBuf| {
+        let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+        let kind = match stem.chars().next() { Some('C') => 0, Some('T') => 1, _ => 2 };
+        let num: u32 = stem[1..].split('_').next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        (kind, num, stem)
+    };
+    paths.sort_by_key(sort_key);
+
+    let mut sheets: Vec<Sheet> = Vec::with_capacity(paths.len());
+
+    for path in paths.iter() {
+
+        let rows: Vec<Vec<String>> = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => {
+                let mut rdr = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_path(path)?;
+                rdr.records()
+                    .map(|record| record.map(|r| r.iter().map(|cell| cell.to_string()).collect()))
+                    .collect::<Result<_, _>>()?
+            }
+            _ => fs::read_to_string(path)?.lines().map(|line| vec![line.to_string()]).collect(),
+        };
+
+        let mut name: String = path.file_stem().unwrap().to_string_lossy().chars().take(31).collect();
+        while sheets.iter().any(|(existing, _)| *existing == name) { name.pop(); }
+
+        sheets.push((name, rows));
+    }
+
+    Ok(sheets)
+}
+
+/// Writes a minimal SpreadsheetML workbook (an uncompressed zip archive of XML parts).
+fn write_workbook(settings: &ImportProcessParameters, path: &Path, sheets: &[Sheet]) -> Result<(), Box<dyn Error>> {
+
+    let mut parts: Vec<(String, Vec<u8>)> = Vec::with_capacity(sheets.len() + 5);
+
+    let mut content_types = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+        r#"<Override PartName="/xl/workbook.xml" "#,
+        r#"ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+    ));
+    let mut workbook = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" "#,
+        r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    ));
+    let mut workbook_rels = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    ));
+
+    for (i, (name, rows)) in sheets.iter().enumerate() {
+
+        let n = i + 1;
+
+        content_types.push_str(&format!(concat!(
+            r#"<Override PartName="/xl/worksheets/sheet{}.xml" "#,
+            r#"ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+        ), n));
+        workbook.push_str(&format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, escape(name), n, n));
+        workbook_rels.push_str(&format!(concat!(
+            r#"<Relationship Id="rId{}" "#,
+            r#"Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" "#,
+            r#"Target="worksheets/sheet{}.xml"/>"#,
+        ), n, n));
+
+        parts.push((format!("xl/worksheets/sheet{}.xml", n), worksheet_xml(rows).into_bytes()));
+    }
+
+    content_types.push_str("</Types>");
+    workbook.push_str("</sheets></workbook>");
+    workbook_rels.push_str("</Relationships>");
+
+    let package_rels = concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Id="rId1" "#,
+        r#"Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" "#,
+        r#"Target="xl/workbook.xml"/></Relationships>"#,
+    );
+
+    parts.insert(0, ("[Content_Types].xml".to_string(), content_types.into_bytes()));
+    parts.insert(1, ("_rels/.rels".to_string(), package_rels.as_bytes().to_vec()));
+    parts.insert(2, ("xl/workbook.xml".to_string(), workbook.into_bytes()));
+    parts.insert(3, ("xl/_rels/workbook.xml.rels".to_string(), workbook_rels.into_bytes()));
+
+    write_zip(settings, path, &parts)
+}
+
+fn worksheet_xml(rows: &[Vec<String>]) -> String {
+
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    ));
+
+    for (r, row) in rows.iter().enumerate() {
+
+        xml.push_str(&format!(r#"<row r="{}">"#, r + 1));
+
+        for (c, cell) in row.iter().enumerate() {
+
+            let reference = format!("{}{}", column_letters(c), r + 1);
+
+            if cell.parse::<Decimal>().is_ok() {
+                xml.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, reference, cell));
+            } else if !cell.is_empty() {
+                xml.push_str(&format!(
+                    r#"<c r="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                    reference,
+                    escape(cell)
+                ));
+            }
+        }
+
+        xml.push_str("</row>");
+    }
+
+    xml.push_str("</sheetData></worksheet>");
+
+    xml
+}
+
+/// A, B, ..., Z, AA, AB, ... for the zero-based column `index`.
+fn column_letters(index: usize) -> String {
+
+    let mut letters = String::new();
+    let mut n = index + 1;
+
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.insert(0, (b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+
+    letters
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || *c == '\t')  //  Control characters aren't allowed in XML 1.0
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes the `parts` as a zip archive, each stored uncompressed.
+fn write_zip(settings: &ImportProcessParameters, path: &Path, parts: &[(String, Vec<u8>)]) -> Result<(), Box<dyn Error>> {
+
+    const DOS_DATE_1980_01_01: u16 = 0x0021;
+
+    let mut wtr = BufWriter::new(create_report_file(settings, path)?);
+    let mut central_directory: Vec<u8> = Vec::new();
+    let mut offset: u32 = 0;
+
+    for (name, data) in parts.iter() {
+
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let name_len = name.len() as u16;
+
+        let mut local_header: Vec<u8> = Vec::with_capacity(30 + name.len());
+        local_header.extend_from_slice(&0x04034b50_u32.to_le_bytes());     //  Local file header signature
+        local_header.extend_from_slice(&20_u16.to_le_bytes());             //  Version needed to extract
+        local_header.extend_from_slice(&0_u16.to_le_bytes());              //  Flags
+        local_header.extend_from_slice(&0_u16.to_le_bytes());              //  Method: stored
+        local_header.extend_from_slice(&0_u16.to_le_bytes());              //  Time
+        local_header.extend_from_slice(&DOS_DATE_1980_01_01.to_le_bytes());
+        local_header.extend_from_slice(&crc.to_le_bytes());
+        local_header.extend_from_slice(&size.to_le_bytes());               //  Compressed size
+        local_header.extend_from_slice(&size.to_le_bytes());               //  Uncompressed size
+        local_header.extend_from_slice(&name_len.to_le_bytes());
+        local_header.extend_from_slice(&0_u16.to_le_bytes());              //  Extra field length
+        local_header.extend_from_slice(name.as_bytes());
+
+        central_directory.extend_from_slice(&0x02014b50_u32.to_le_bytes()); //  Central directory header signature
+        central_directory.extend_from_slice(&20_u16.to_le_bytes());         //  Version made by
+        central_directory.extend_from_slice(&local_header[4..30]);         //  Same as in the local header
+        central_directory.extend_from_slice(&0_u16.to_le_bytes());          //  File comment length
+        central_directory.extend_from_slice(&0_u16.to_le_bytes());          //  Disk number start
+        central_directory.extend_from_slice(&0_u16.to_le_bytes());          //  Internal attributes
+        central_directory.extend_from_slice(&0_u32.to_le_bytes());          //  External attributes
+        central_directory.extend_from_slice(&offset.to_le_bytes());         //  Offset of local header
+        central_directory.extend_from_slice(name.as_bytes());
+
+        wtr.write_all(&local_header)?;
+        wtr.write_all(data)?;
+
+        offset += local_header.len() as u32 + size;
+    }
+
+    let entries = parts.len() as u16;
+
+    wtr.write_all(&central_directory)?;
+    wtr.write_all(&0x06054b50_u32.to_le_bytes())?;                    //  End of central directory signature
+    wtr.write_all(&0_u16.to_le_bytes())?;                             //  Number of this disk
+    wtr.write_all(&0_u16.to_le_bytes())?;                             //  Disk where central directory starts
+    wtr.write_all(&entries.to_le_bytes())?;                           //  Entries on this disk
+    wtr.write_all(&entries.to_le_bytes())?;                           //  Total entries
+    wtr.write_all(&(central_directory.len() as u32).to_le_bytes())?;
+    wtr.write_all(&offset.to_le_bytes())?;                            //  Offset of central directory
+    wtr.write_all(&0_u16.to_le_bytes())?;                             //  Comment length
+
+    wtr.flush()?;
+
+    Ok(())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+
+    let mut crc = 0xFFFFFFFF_u32;
+
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
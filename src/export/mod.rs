@@ -4,4 +4,12 @@
 pub mod export_all;
 pub mod export_je;
 pub mod export_csv;
-pub mod export_txt;
\ No newline at end of file
+pub mod export_txt;
+pub mod export_8949;
+pub mod export_8824;
+pub mod export_json;
+pub mod export_xlsx;
+pub mod export_pdf;
+pub mod export_html;
+pub mod export_external;
+pub mod export_sql;
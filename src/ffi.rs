@@ -0,0 +1,80 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! An extern-C interface to `json_api::run_json()`, for other languages (and, built for a wasm32 target, for web
+//! front ends): the CSV Input File's bytes and the settings (as JSON) go in, and the reports (as in Reports.json) come
+//! out as JSON.  Build it as a shared library with `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! No wasm-bindgen glue is included; a wasm host instead writes the CSV and settings into memory it gets from
+//! `cryptools_alloc()`, and reads the NUL-terminated result back out.  The progress is logged with the `log` crate
+//! (so isn't shown, unless the host program sets up a logger).  A problem with the CSV Input File is returned as an
+//! `error`, and a panic doesn't unwind into the host.  Nothing here writes a file or exits the process, and in a
+//! wasm32 build, `cryptools::run()` refuses to export the reports to files.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use crate::json_api;
+
+
+/// Processes the CSV Input File of `csv_len` bytes at `csv` with the settings in `settings_json` (a NUL-terminated
+/// JSON object of `json_api::JsonSettings`, or null for the defaults), and returns a NUL-terminated JSON object:
+/// `reports`, as in Reports.json, or `error`, a message.  The result must be freed with `cryptools_free_string()`.
+///
+/// # Safety
+///
+/// `csv` must point to `csv_len` readable bytes, and `settings_json` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cryptools_run_json(csv: *const u8, csv_len: usize, settings_json: *const c_char) -> *mut c_char {
+
+    let csv = if csv.is_null() { Vec::new() } else { slice::from_raw_parts(csv, csv_len).to_vec() };
+
+    let settings = match settings_json.is_null() {
+        true => Ok(None),
+        false => CStr::from_ptr(settings_json).to_str().map(Some).map_err(|_e| "The settings aren't UTF-8.".to_string()),
+    };
+
+    //  A panic mustn't unwind across the C boundary
+    let result = settings.and_then(|settings| {
+        panic::catch_unwind(AssertUnwindSafe(|| json_api::run_json(csv, settings).map_err(|e| e.to_string())))
+            .unwrap_or_else(|_panic| Err("The CSV Input File couldn't be processed.".to_string()))
+    });
+
+    let json = result.unwrap_or_else(|e| serde_json::json!({ "error": e }).to_string());
+
+    //  JSON escapes any NUL in a string, so there's none to fail on
+    CString::new(json).unwrap().into_raw()
+}
+
+/// Frees a result of `cryptools_run_json()`.
+///
+/// # Safety
+///
+/// `result` must be null or have been returned by `cryptools_run_json()`, and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn cryptools_free_string(result: *mut c_char) {
+    if !result.is_null() {
+        drop(CString::from_raw(result));
+    }
+}
+
+/// Allocates `len` bytes for a host (i.e., a wasm one) to write the CSV or settings into.  Free them with
+/// `cryptools_dealloc()`.
+#[no_mangle]
+pub extern "C" fn cryptools_alloc(len: usize) -> *mut u8 {
+    let mut buffer: Vec<u8> = Vec::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// Frees the `len` bytes at `ptr`, which were allocated by `cryptools_alloc()`.
+///
+/// # Safety
+///
+/// `ptr` must have been returned by `cryptools_alloc(len)`, and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn cryptools_dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
@@ -0,0 +1,97 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! Runs the engine on a CSV Input File's bytes with settings given as JSON, returning the reports as JSON, for front
+//! ends in other languages (see `ffi`, with the `ffi` feature) and over HTTP (see `cryptools serve`).
+
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::summary;
+
+use crate::Options;
+
+
+/// The settings that may be passed as a JSON object (i.e., `{"costing_method": 3}`).  Any left out are as for
+/// `cryptools -a`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JsonSettings {
+    home_currency: Option<String>,
+    /// As for `INV_COSTING_METHOD` (1 through 11, except 9, specific identification).
+    costing_method: Option<u8>,
+    /// As for `ISO_DATE`.
+    iso_date: Option<bool>,
+    /// As for `DATE_SEPARATOR_IS_SLASH`.
+    date_separator_is_slash: Option<bool>,
+    /// As for `DATE_FORMAT`.
+    date_format: Option<String>,
+    /// When set (as YYYY-MM-DD), like-kind treatment is applied through this date.
+    like_kind_cutoff_date: Option<NaiveDate>,
+    /// As for `LONG_TERM_DAYS`.
+    long_term_days: Option<i64>,
+    /// As for `--tax-year`.
+    tax_year: Option<i32>,
+    /// As for `FISCAL_YEAR_END`.
+    fiscal_year_end: Option<String>,
+}
+
+impl JsonSettings {
+
+    fn into_options(self) -> Result<Options, Box<dyn Error>> {
+
+        let mut options = Options::new(Vec::new());
+        let settings = &mut options.settings;
+
+        if let Some(home_currency) = self.home_currency {
+            settings.home_currency = home_currency.trim().to_uppercase();
+        }
+        if let Some(method) = self.costing_method {
+            settings.costing_method = InventoryCostingMethod::from_choice(&method.to_string())
+                .ok_or_else(|| format!("There's no costing method {}.", method))?;
+        }
+        if let Some(iso_date) = self.iso_date {
+            settings.input_file_uses_iso_date_style = iso_date;
+        }
+        if self.date_separator_is_slash == Some(true) {
+            settings.input_file_date_separator = "/".to_string();
+        }
+        if let Some(cutoff_date) = self.like_kind_cutoff_date {
+            settings.lk_treatment_enabled = true;
+            settings.lk_cutoff_date = cutoff_date;
+        }
+        if let Some(days) = self.long_term_days {
+            settings.long_term_days = days;
+        }
+        settings.input_file_date_format = self.date_format;
+        settings.tax_year = self.tax_year;
+        if let Some(fiscal_year_end) = self.fiscal_year_end {
+            settings.fiscal_year_start_month = summary::parse_fiscal_year_end(&fiscal_year_end)?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Processes `csv` with the `settings`, returning a JSON object with the `reports` (as in Reports.json).
+pub fn run(csv: Vec<u8>, settings: JsonSettings) -> Result<serde_json::Value, Box<dyn Error>> {
+
+    let results = crate::run_csv(csv, &settings.into_options()?)?;
+
+    Ok(serde_json::json!({ "reports": results.reports }))
+}
+
+/// The same as `run()`, except that the settings are `settings_json` (the defaults, if `None`), and the result is
+/// written out as JSON.
+pub fn run_json(csv: Vec<u8>, settings_json: Option<&str>) -> Result<String, Box<dyn Error>> {
+
+    let settings: JsonSettings = match settings_json {
+        Some(json) => serde_json::from_str(json).map_err(|e| format!("Invalid settings: {}", e))?,
+        None => JsonSettings::default(),
+    };
+
+    Ok(run(csv, settings)?.to_string())
+}
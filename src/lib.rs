@@ -0,0 +1,190 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! The cryptools engine, for embedding in other Rust programs: `run()` imports and processes the CSV Input File(s)
+//! with the given `Options`, optionally exports the reports, and returns the processed accounts, lots, movements and
+//! gains and losses as `Results`.  The command-line program is built on the same `export` modules.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! let mut options = cryptools::Options::new(vec![PathBuf::from("transactions.csv")]);
+//! options.settings.home_currency = "EUR".to_string();
+//!
+//! let results = cryptools::run(&options).unwrap();
+//!
+//! for gain in results.reports.gains_and_losses.iter() {
+//!     println!("{} {}: {}", gain.date_sold, gain.description, gain.gain_or_loss);
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+pub use crptls;
+
+use crptls::account::{Account, RawAccount};
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::core_functions::{self, ImportProcessParameters, Timings};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::summary::RunSummary;
+
+pub mod export;
+pub mod json_api;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+use export::export_all::{self, Exporters, Report};
+use export::export_csv;
+use export::export_json::Reports;
+
+
+/// What `run()` imports, how it's processed, and which reports are exported.
+#[derive(Clone)]
+pub struct Options {
+    /// The CSV Input File(s), merged into one if there are several (or `-`, for standard input).
+    pub input_file_paths: Vec<PathBuf>,
+    /// The settings of the run, which default to those of `cryptools -a` (see `ImportProcessParameters::default()`),
+    /// except that nothing is exported unless `should_export` is set.
+    pub settings: ImportProcessParameters,
+    /// The groups of reports exported to the `settings.export_path`, if `settings.should_export` is set.  Every one
+    /// is, if this is empty.
+    pub reports: Vec<Report>,
+    /// Any further output formats (see `export_all::Exporter`), run after the reports, if `settings.should_export`
+    /// is set.
+    pub exporters: Exporters,
+}
+
+impl Options {
+
+    pub fn new(input_file_paths: Vec<PathBuf>) -> Self {
+        Options {
+            input_file_paths,
+            settings: ImportProcessParameters::default(),
+            reports: Vec::new(),
+            exporters: Exporters::default(),
+        }
+    }
+}
+
+/// The results of `run()`.
+pub struct Results {
+    /// Every account with its lots and their movements, every transaction with the movements that realize gain,
+    /// loss, income or expense, and every line of Form 8949 (as in Reports.json).
+    pub reports: Reports,
+    /// The headline figures of the run.
+    pub summary: RunSummary,
+    pub raw_acct_map: HashMap<u16, RawAccount>,
+    pub account_map: HashMap<u16, Account>,
+    pub action_records_map: HashMap<u32, ActionRecord>,
+    pub transactions_map: HashMap<u32, Transaction>,
+}
+
+/// Imports and processes the `options.input_file_paths`, exports the reports (if `options.settings.should_export` is
+/// set), and returns the `Results`.  Specific identification, whose lots are chosen by the user as each disposal is
+/// processed, isn't available here (see `core_functions::import_and_process_final_with_lot_picker()`).
+pub fn run(options: &Options) -> Result<Results, Box<dyn Error>> {
+
+    check_costing_method(&options.settings)?;
+
+    let mut timings = Timings::default();
+    let maps = core_functions::import_and_process_final(&options.input_file_paths, &options.settings, &mut timings)?;
+
+    results(options, maps, &mut timings)
+}
+
+/// The same as `run()`, except that the CSV Input File is `csv`, rather than the `options.input_file_paths`.
+pub fn run_csv(csv: Vec<u8>, options: &Options) -> Result<Results, Box<dyn Error>> {
+
+    check_costing_method(&options.settings)?;
+
+    let mut timings = Timings::default();
+    let maps = core_functions::import_and_process_final_from_csv(csv, &options.settings, &mut timings)?;
+
+    results(options, maps, &mut timings)
+}
+
+fn check_costing_method(settings: &ImportProcessParameters) -> Result<(), Box<dyn Error>> {
+
+    if settings.costing_method == InventoryCostingMethod::SpecificIdentification {
+        return Err("Specific identification requires a LotPicker, so can't be used with cryptools::run().".into())
+    }
+
+    Ok(())
+}
+
+/// Exports the reports of the processed `maps` (if so set), and returns them as `Results`.
+fn results(
+    options: &Options,
+    maps: core_functions::ProcessedMaps,
+    timings: &mut Timings,
+) -> Result<Results, Box<dyn Error>> {
+
+    let settings = &options.settings;
+
+    let (
+        raw_acct_map,
+        account_map,
+        action_records_map,
+        transactions_map,
+    ) = maps;
+
+    //  A wasm32 build (i.e., of the `ffi` feature, for a web front end) has no filesystem to export to
+    if settings.should_export && cfg!(target_arch = "wasm32") {
+        return Err("The reports can't be exported to files in a wasm32 build. Read them from the results instead.".into())
+    }
+
+    if settings.should_export {
+
+        fs::create_dir_all(&settings.export_path)?;
+
+        let exported = export_all::export(
+            settings,
+            &options.reports,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+            timings,
+        ).and_then(|()| options.exporters.export(
+            settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+            timings,
+        ));
+
+        //  The reports are only moved into place if every one was written (see `create_report_file()`)
+        match exported {
+            Ok(()) => export_csv::commit_written_reports()?,
+            Err(e) => {
+                export_csv::discard_pending_reports();
+                return Err(e)
+            }
+        }
+    }
+
+    let reports = Reports::from_maps(settings, &raw_acct_map, &account_map, &action_records_map, &transactions_map)?;
+
+    let summary = RunSummary::from_maps(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        &raw_acct_map,
+        &account_map,
+        &action_records_map,
+        &transactions_map,
+    )?;
+
+    Ok(Results {
+        reports,
+        summary,
+        raw_acct_map,
+        account_map,
+        action_records_map,
+        transactions_map,
+    })
+}
@@ -0,0 +1,53 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+
+/// Prints the messages of cryptools and crptls (not those of its dependencies): errors, warnings and the messages
+/// about each step of the run to stdout, as they always have been, and debug and trace messages to stderr, prefixed
+/// by their level and module.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && (metadata.target().starts_with("cryptools") || metadata.target().starts_with("crptls"))
+    }
+
+    fn log(&self, record: &Record) {
+
+        if !self.enabled(record.metadata()) { return }
+
+        if record.level() == Level::Warn {
+            crate::run_result::record_warning(&record.args().to_string());
+        }
+
+        match record.level() {
+            Level::Error | Level::Warn | Level::Info => println!("{}", record.args()),
+            Level::Debug | Level::Trace => eprintln!("{:<5} {}: {}", record.level(), record.target(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sets up the logger: `quiet` shows only warnings (and errors), and each `verbose` adds a level below the default
+/// (info): debug, then trace.
+pub fn init(verbose: u8, quiet: bool) {
+
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    //  Only fails if a logger was already set
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
@@ -8,81 +8,781 @@
 // #[warn(dead_code)] is the default (same for unused_variables)
 
 
-use std::path::PathBuf;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::error::Error;
+use std::time::Instant;
+use std::collections::HashMap;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+use serde::Serialize;
+use chrono::NaiveDate;
 
 mod setup;
+mod batch;
 mod cli_user_choices;
 mod wizard;
 mod skip_wizard;
-mod export;
+mod portfolio;
+mod progress;
+mod logging;
+mod manifest;
+mod run_result;
+mod compare_methods;
+mod what_if;
+mod watch;
+mod completions;
+mod config_dump;
+mod serve;
+mod state_dirs;
+mod sync;
+mod chain_sync;
+mod onboarding;
 
 #[cfg(feature = "print_menu")]
 mod mytui;
 
-use export::{export_all, export_je};
+use crptls::account::{Account, RawAccount};
+use crptls::transaction::{Transaction, ActionRecord};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::run_state::RunState;
+
+use cryptools::export::export_all::Exporters;
+use cryptools::export::export_external::ExternalExporter;
+use cryptools::export::{export_all, export_csv, export_je, export_html, export_json, export_pdf, export_sql, export_txt, export_xlsx};
+
+use run_result::ExitCode;
 
 
 #[derive(Parser, Debug)]
-#[command(name = "cryptools")]
+#[command(name = "cryptools", args_override_self = true)]
 pub struct Cli {
 
     /// User is instructing the program to skip the data entry wizard.
     /// When set, default settings will be assumed if they are not set by 
     /// environment variables (or .env file) or certain command line flags.
-    #[arg(id = "accept args", short, long = "accept")]
+    #[arg(id = "accept args", short, long = "accept", global = true)]
     accept_args: bool,
 
+    /// Never prompts, for running in automated pipelines.  Anything that would be asked of the user (the wizard, when
+    /// run without -a; a file_to_import that is missing or not found; the print menu; or specific identification's lot
+    /// picker) instead stops the run with exit code 2 and a JSON object on stderr describing the missing input, i.e.,
+    /// `{"error":"No file_to_import was given.","missing":"file_to_import"}`.
+    #[arg(id = "batch", long = "batch", global = true)]
+    batch: bool,
+
     /// Suppresses the printing of "all" reports, except that it *will* trigger the
     /// exporting of a txt file containing an accounting journal entry for every transaction.
     /// Individual account and transaction reports may still be printed via the print_menu
-    /// with the -p flag. Note: the journal entries are not suitable for like-kind transactions.
-    #[arg(id = "journal entries", short, long = "journal-entries")]
+    /// with the -p flag. With like-kind treatment, each like-kind exchange carries its basis over, recognizing no gain.
+    #[arg(id = "journal entries", short, long = "journal-entries", global = true)]
     journal_entries_only: bool,
 
-    /// Once the file_to_import has been fully processed, the user will be presented
-    /// with a menu for manually selecting which reports to print/export. If this flag is not
-    /// set, the program will print/export all available reports.
+    /// Also writes the journal entries, wherever J1_Journal_Entries.txt is written, as a plain-text journal for
+    /// ledger-cli and hledger (J2_Ledger_Journal.ledger), with accounts such as Assets:Crypto:Coinbase:BTC and
+    /// Income:CapitalGains:LongTerm.
+    #[arg(id = "ledger", long = "ledger", global = true)]
+    ledger: bool,
+
+    /// Also writes the journal entries, wherever J1_Journal_Entries.txt is written, as a Beancount journal
+    /// (J3_Beancount_Journal.beancount), with each lot posted at its cost and basis date, and a balance assertion of
+    /// each holding at each year-end. Accounts are named as for --ledger.
+    #[arg(id = "beancount", long = "beancount", global = true)]
+    beancount: bool,
+
+    /// Also writes the journal entries, wherever J1_Journal_Entries.txt is written, as a general journal CSV in the
+    /// layout QuickBooks Online and Xero import (J4_General_Journal.csv): date, journal no, account, debit, credit and
+    /// memo. Accounts are named as for --ledger.
+    #[arg(id = "journal csv", long = "journal-csv", global = true)]
+    journal_csv: bool,
+
+    /// A CSV file mapping the accounts of the journal entries (J1_Journal_Entries.txt, and the --ledger, --beancount
+    /// and --journal-csv journals) onto your chart of accounts: a header row, then one account,mapped row per account.
+    /// Each row also maps the accounts beneath it (i.e., Assets:Crypto:Coinbase,Assets:Coinbase maps
+    /// Assets:Crypto:Coinbase:BTC to Assets:Coinbase:BTC). The accounts are those of --ledger: Assets:Crypto:NAME:TICKER
+    /// (Assets:NAME:TICKER for the home currency, Assets:Margin:NAME:TICKER for margin), Income:CapitalGains:LongTerm
+    /// and :ShortTerm, Income:Crypto (and Income:Crypto:TYPE by incomeType), Income:Derivatives, Expenses:Crypto,
+    /// Expenses:Gifts, Expenses:Donations, Equity:OpeningBalances, Equity:Inheritances and Assets:DeferredLosses, and
+    /// for --costs, Expenses:CATEGORY, Assets:Equipment:CATEGORY, Expenses:Depreciation:CATEGORY,
+    /// Assets:AccumulatedDepreciation:CATEGORY and Assets:Cash.
+    /// It's fatal if any account the journal entries post to isn't mapped.
+    #[arg(id = "chart of accounts", long = "chart-of-accounts", alias = "ledger-accounts", value_name = "FILE", global = true)]
+    chart_of_accounts: Option<PathBuf>,
+
+    /// Once the file_to_import has been fully processed, the user will be presented with a dashboard
+    /// summarizing the results, followed by a menu for manually selecting which reports to print/export.
+    /// From the dashboard, the accounts (down to each lot's movements) and transactions may first be browsed, and
+    /// holdings, realized gains and income charted by month.
+    /// If this flag is not set, the program will print/export all available reports.
     #[cfg(feature = "print_menu")]
-    #[arg(id = "print menu", short, long = "print-menu")]
+    #[arg(id = "print menu", short, long = "print-menu", conflicts_with_all = ["portfolio", "stdout", "compare methods"], global = true)]
     print_menu: bool,
 
     /// Prevents the program from writing reports to files.
     /// This will be ignored if -a is not set (the wizard will always ask to output).
-    #[arg(id = "suppress reports", short, long = "suppress")]
+    #[arg(id = "suppress reports", short, long = "suppress", global = true)]
     suppress_reports: bool,
 
+    /// The format of the reports exported by default: csv (the C*.csv, T*.txt and J*.txt reports) or json (a single
+    /// Reports.json with the accounts, lots, movements, gains and losses, and income and expense, for scripting).
+    #[arg(id = "report format", long = "format", value_name = "FORMAT", value_enum, default_value = "csv", global = true)]
+    report_format: export_json::ReportFormat,
+
+    /// Writes the reports exported by default into a single Reports.xlsx workbook, one sheet per report,
+    /// instead of separate CSV and TXT files.
+    #[arg(id = "workbook", long = "workbook", conflicts_with = "report format", global = true)]
+    workbook: bool,
+
+    /// Also writes a PDF summary of the run (Summary.pdf), for handing to a client or attaching to a return: a cover
+    /// page with the settings, then the realized gains and losses by year, the income by type, and the holdings.
+    #[arg(id = "pdf", long = "pdf", global = true)]
+    pdf: bool,
+
+    /// Also writes a self-contained HTML dashboard of the run (Dashboard.html), for browsing the results in a browser
+    /// rather than opening the CSV reports: the headline figures, charts of the gains and losses by year and of each
+    /// currency held over time, and tables of the gains, income, accounts and transactions, sortable by any column.
+    #[arg(id = "html", long = "html", global = true)]
+    html: bool,
+
+    /// Also runs the external exporter NAME (the program cryptools-export-NAME on the PATH, or the program at NAME, if
+    /// it's a path), for an output format not built in: it's given the reports as JSON (as in Reports.json) on its
+    /// standard input, and is run in the output directory. May be given more than once.
+    #[arg(id = "exporter", long = "exporter", value_name = "NAME", global = true)]
+    exporters: Vec<String>,
+
+    /// Exports only the chosen groups of reports, instead of all of them (i.e., --reports gains,income,je, or
+    /// --report gains --report je). Each report is still only exported if it applies to the run (i.e., C26/T7 only with
+    /// like-kind treatment). C13/T6, C18, C19, C27, C28, C33, C41 and C42 are only exported when their group is chosen,
+    /// so that a run without --reports exports the reports it always has. Also applies to the --workbook.
+    #[arg(id = "reports", long = "reports", alias = "report", value_name = "REPORTS", value_enum, value_delimiter = ',',
+        conflicts_with_all = ["journal entries", "report format"], global = true)]
+    reports: Vec<export_all::Report>,
+
+    /// Writes the chosen report to standard output instead of to a file, and no other report, so that it can be piped
+    /// into another program (i.e., --stdout gains | xsv table). Everything else the program prints goes to standard
+    /// error instead. Only available on Unix (elsewhere, it's an error). The ledger, beancount and journal-csv reports
+    /// needn't also be asked for by their own flags.
+    #[arg(id = "stdout", long = "stdout", value_name = "REPORT", value_enum,
+        conflicts_with_all = ["reports", "journal entries", "report format", "workbook", "portfolio", "suppress reports"],
+        global = true)]
+    stdout: Option<export_all::StdoutReport>,
+
     /// Output directory for exported reports.
-    #[arg(id = "output directory", short, long = "output", default_value = ".")]
+    #[arg(id = "output directory", short, long = "output", default_value = ".", global = true)]
     output_dir_path: PathBuf,
 
+    /// Exports the reports to a new subdirectory of the output directory named for the time of the run (i.e.,
+    /// 2024-04-15_093000), rather than to the output directory itself, so each run's reports are kept.
+    #[arg(id = "run subdir", long = "run-subdir", global = true)]
+    run_subdir: bool,
+
+    /// Begins the file name of each report with PREFIX (i.e., `alice_` for alice_C1_Account_sums.csv).
+    #[arg(id = "file prefix", long = "file-prefix", value_name = "PREFIX", global = true)]
+    file_prefix: Option<String>,
+
+    /// Ends the file name of each report in the date of the run (i.e., C1_Account_sums_2024-04-15.csv), after the
+    /// year of any `--tax-year`.
+    #[arg(id = "date stamp", long = "date-stamp", global = true)]
+    date_stamp: bool,
+
+    /// Replaces any report left in the output directory by an earlier run.  Otherwise, a report that would replace a
+    /// file of the same name is a fatal error, so that an earlier run's reports aren't lost.
+    #[arg(id = "overwrite", long = "overwrite", global = true)]
+    overwrite: bool,
+
     /// Causes the program to expect the `txDate` field in the file_to_import to use the format YYYY-MM-dd
     /// or YY-MM-dd (or YYYY/MM/dd or YY/MM/dd) instead of the default US-style MM-dd-YYYY or MM-dd-YY 
     /// (or MM/dd/YYYY or MM/dd/YY).
     /// NOTE: this flag overrides the ISO_DATE environment variable, including if set in the .env file.
-    #[arg(id = "imported file uses ISO 8601 date format", short, long = "iso")]
+    #[arg(id = "imported file uses ISO 8601 date format", short, long = "iso", global = true)]
     iso_date: bool,
 
     /// Tells the program a non-default date separator (instead of a hyphen "-", a slash "/") was used
     /// in the file_to_import `txDate` column (i.e. 2017-12-31 instead of 2017/12/31).
     /// NOTE: this flag overrides the DATE_SEPARATOR_IS_SLASH environment variable, including if set in the .env file.
-    #[arg(id = "date separator character is slash", short, long = "date-separator-is-slash")]
+    #[arg(id = "date separator character is slash", short, long = "date-separator-is-slash", global = true)]
     date_separator_is_slash: bool,
 
+    /// Begins each exported CSV file with a UTF-8 byte order mark (BOM). Excel needs this to correctly display
+    /// non-ASCII characters (i.e., currency symbols or memos) when opening a CSV file directly. Off by default,
+    /// because programs reading the CSV files may not expect it.
+    #[arg(id = "excel bom", long = "excel-bom", global = true)]
+    excel_bom: bool,
+
+    /// Continues past any transaction that spends more than its account holds (which is otherwise fatal, after every
+    /// one is listed with its date and shortfall), with a warning for each, covering each shortfall with a zero-basis
+    /// synthetic acquisition.  Also see the SYNTHETIC_ACQUISITIONS environment variable.
+    #[arg(id = "lenient", long = "lenient", global = true)]
+    lenient: bool,
+
+    /// Parks each transaction row that can't be fully classified (one that wouldn't import, such as a row with two
+    /// quantities of the same sign, and, with `--match-transfers`, a withdrawal or deposit without proceeds whose
+    /// other side wasn't found) instead of stopping the run.  Parked rows are left out of processing, as if they
+    /// weren't in the file_to_import, and listed with why in C38_Rows_needing_review.csv, for correcting later.
+    #[arg(id = "needs review", long = "needs-review", conflicts_with = "check only", global = true)]
+    needs_review: bool,
+
+    /// What's done with a transaction row duplicating an earlier one (i.e., from exports of overlapping date ranges), by
+    /// its date, accounts, quantities and memo (or the fields of the DEDUPE_KEY environment variable): `warn` imports
+    /// it with a warning, `drop` leaves it out, and `off` imports it as any other.  With `warn` or `drop`, each is
+    /// listed in C31_Duplicate_rows.csv.
+    #[arg(id = "dedupe", long = "dedupe", value_name = "MODE", value_parser = ["warn", "drop", "off"], default_value = "off",
+        global = true)]
+    dedupe: String,
+
+    /// Pairs each withdrawal (a row with only an outgoing quantity) with a deposit of the same currency into another
+    /// account, received that day or the next, of the quantity sent less at most a network fee, as a transfer between
+    /// your own accounts (with basis carryover).  Also see the TRANSFER_MATCH_TOLERANCE environment variable.
+    #[arg(id = "match transfers", long = "match-transfers", global = true)]
+    match_transfers: bool,
+
+    /// Only checks the file_to_import (as it would be imported), listing every problem found by row (i.e., dates that
+    /// don't match the date format or are out of order, values that aren't numbers, and inconsistent transfers),
+    /// without processing it or exporting anything.  Exits with an error if there are any.
+    #[arg(id = "check only", long = "check-only", global = true)]
+    check_only: bool,
+
+    /// Writes the file_to_import (as it would be imported) to FILE with its memos blanked, its payers and tickers
+    /// (other than the home currency) renamed the same way throughout, and each currency's amounts multiplied by a
+    /// random whole factor of its own, so it may be attached to a bug report without giving away your finances. It
+    /// processes the same (its gains and losses scaled by the home currency's factor). Nothing else is done.
+    #[arg(id = "anonymize export", long = "anonymize-export", value_name = "FILE", conflicts_with = "check only",
+        global = true)]
+    anonymize_export: Option<PathBuf>,
+
+    /// Processes the file_to_import as usual, but exports nothing (nor saves any `--save-state`), instead printing a
+    /// summary of the run: the transactions, accounts and lots created, and the income, proceeds and realized gain or
+    /// loss in total and by year.  Any `--price-lookup` file is still updated with the prices fetched, and the run result
+    /// is written only if `--run-result` names its file.
+    #[arg(id = "dry run", long = "dry-run", conflicts_with = "check only", global = true)]
+    dry_run: bool,
+
+    /// Processes the file_to_import, then watches it, processing it again whenever it changes, until interrupted
+    /// (i.e., with Ctrl-C), for checking each edit while cleaning up an export.  Combine it with `--dry-run` to print
+    /// just the summary of each run, or `--stdout REPORT` to print a single report.  Requires -a (--accept).
+    #[arg(id = "watch", long = "watch", conflicts_with_all = ["portfolio", "compare methods"], global = true)]
+    watch: bool,
+
+    /// Processes the file_to_import under each costing method that chooses its own lots (every method but specific
+    /// identification), and compares the realized gain or loss of each, by year and in total, instead of exporting
+    /// the usual reports.  The comparison is printed, and exported as C30_Costing_method_comparison.csv.
+    #[arg(id = "compare methods", long = "compare-methods",
+        conflicts_with_all = ["check only", "dry run", "portfolio", "stdout"], global = true)]
+    compare_methods: bool,
+
+    /// Replaces the Form 8949 report (C7_Form_8949.csv) with separate short-term and long-term files for each account
+    /// the disposals came out of (i.e., C7_Form_8949_Coinbase_ST.csv), for importing one broker at a time. Accounts
+    /// are grouped by exchange instead if the EXCHANGE_LABEL_SEPARATOR environment variable is set.
+    #[arg(id = "8949 per account", long = "8949-per-account", global = true)]
+    form_8949_per_account: bool,
+
+    /// Exports a standalone report (T4_Currency_report_<TICKER>.txt) for a single currency, across all accounts:
+    /// current holdings, every acquisition and disposal (with realized gain/loss), and the detail of every lot.
+    /// This report is created in addition to any others, and the program will error if the ticker has no activity.
+    #[arg(id = "currency report", long = "currency-report", value_name = "TICKER", global = true)]
+    currency_report: Option<String>,
+
+    /// Exports a drill-down report (T8_Txn_report_<TXN>.txt) for a single transaction, by its number (as in the
+    /// Txn# column of the reports): every movement arising from it, with the lot each was posted to and the lot's
+    /// balance before and after, and its journal entry.  In the print menu, press Enter on a transaction instead.
+    /// The program will error if there is no such transaction.
+    #[arg(id = "txn report", long = "txn-report", value_name = "TXN", global = true)]
+    txn_report: Option<u32>,
+
+    /// Leaves the transaction TXN (by its number, as in the Txn# column of the reports of a run without it) out of
+    /// processing, as if its row weren't in the file_to_import, without editing it: i.e., to see what the gains would
+    /// have been without a sale, or to isolate a suspect row.  May be repeated, or given a comma-separated list.  Later
+    /// transactions are renumbered.  The change in the realized gain or loss, by year, is printed.
+    #[arg(id = "exclude txn", long = "exclude-txn", value_name = "TXN", value_delimiter = ',', global = true)]
+    exclude_txns: Vec<u32>,
+
+    /// Also exports the summary printed at the end of the run (T5_Summary.txt): the transactions processed, the
+    /// realized gains and losses (short- and long-term, overall and by year), the income and expenses, and the
+    /// holdings left, for the headline numbers without opening the CSV reports.
+    #[arg(id = "export summary", long = "export-summary", global = true)]
+    export_summary: bool,
+
+    /// Spot-price file (a CSV with a header row, then one `ticker,price` row per currency, priced in the home currency).
+    /// When set, the program exports a report (C8_Open_lot_tax_impact_<DATE>.csv) of the unrealized gain or loss of every
+    /// open lot if it were sold today at the spot price, and whether that gain or loss would be short- or long-term.
+    /// Lots are sorted with the largest unrealized loss first, to surface tax-loss harvesting candidates.
+    /// A second report (C12_Unrealized_gains_by_term_<DATE>.csv) totals those unrealized gains and losses by
+    /// prospective term for each currency, and overall.  A third (C29_Tax_loss_harvesting_<DATE>.csv) lists the lots
+    /// at a loss, largest first, with their holding periods and the part of this year's realized gains each would offset.
+    #[arg(id = "spot prices", long = "spot-prices", value_name = "FILE", global = true)]
+    spot_prices: Option<PathBuf>,
+
+    /// Prices file (a CSV with a header row, then `date,ticker,price` rows, dated YYYY-MM-DD and priced in the home
+    /// currency, as for `--price-lookup`).  When set, the program exports a portfolio valuation
+    /// (C21_Portfolio_valuation_<DATE>.csv) of every open lot at the latest price of its currency, as of the latest
+    /// date in the file, with its unrealized gain or loss and holding period.
+    #[arg(id = "prices", long = "prices", value_name = "FILE", global = true)]
+    prices: Option<PathBuf>,
+
+    /// Exports a report (C9_Gains_by_exchange.csv) of net short- and long-term realized gain or loss for each exchange,
+    /// based on the account each disposal came out of, and another (C39_Exchange_totals_by_year.csv) of each
+    /// exchange's gain or loss, income received and fees paid in each (fiscal) year, for reconciling against the
+    /// exchange's Form 1099. By default, each account is its own exchange; set the EXCHANGE_LABEL_SEPARATOR
+    /// environment variable to group accounts by the beginning of their names.
+    #[arg(id = "gains by exchange", long = "gains-by-exchange", global = true)]
+    gains_by_exchange: bool,
+
+    /// Exports the Form 8949 disposals (C14_TurboTax_gains.csv or C14_TaxAct_gains.csv) in the column layout that
+    /// the chosen tax software accepts for importing capital gains, so the columns needn't be remapped by hand.
+    #[arg(id = "tax software", long = "tax-software", value_name = "SOFTWARE", global = true)]
+    tax_software: Option<export_csv::TaxSoftware>,
+
+    /// Exports the lots open at the end of the (fiscal) YEAR (C15_Holdings_as_of_YEAR-12-31.csv, for a calendar year),
+    /// with each one's remaining amount, acquisition and basis dates, and remaining cost basis, for starting next
+    /// year's run via `--opening-balances`.
+    #[arg(id = "year end holdings", long = "year-end-holdings", value_name = "YEAR", global = true)]
+    year_end_holdings: Option<i32>,
+
+    /// Exports the lots open at the end of the last transaction's date (C22_Open_lots_as_of_<DATE>.csv), with each
+    /// one's remaining amount and cost basis, acquisition and basis dates, days held and term, for planning tax-loss
+    /// harvesting.  No prices are needed.
+    #[arg(id = "open lots", long = "open-lots", global = true)]
+    open_lots: bool,
+
+    /// Expected balances file (a CSV with a header row, then one `account,ticker,balance` row per account, as of the
+    /// last transaction, per the exchange or wallet).  When set, the program exports a reconciliation
+    /// (C23_Balance_reconciliation.csv) of each account's computed balance against the expected one, and warns of each
+    /// mismatch, with the difference.
+    #[arg(id = "verify balances", long = "verify-balances", value_name = "FILE", global = true)]
+    verify_balances: Option<PathBuf>,
+
+    /// Exports the amount of each asset held at the end of each day or month (INTERVAL: daily or monthly) of the whole
+    /// history (C35_Balances_daily.csv or C35_Balances_monthly.csv), for charting, and the highest balance of each
+    /// account in each (fiscal) year, with the day it was first held (C36_Highest_balances.csv), as for an FBAR.  No
+    /// prices are needed.
+    #[arg(id = "balance history", long = "balance-history", value_name = "INTERVAL", global = true)]
+    balance_history: Option<crptls::balance_history::BalanceInterval>,
+
+    /// Exports, for each asset, the quantity acquired and disposed of over the whole history, the average acquisition
+    /// cost and sale price, the realized gain, and the current holding with its average basis (C37_Asset_statistics.csv).
+    #[arg(id = "asset stats", long = "asset-stats", global = true)]
+    asset_stats: bool,
+
+    /// With `--open-lots`, lists the lots open at the end of DATE (YYYY-MM-DD) instead.
+    #[arg(id = "as of", long = "as-of", value_name = "DATE", requires = "open lots", global = true)]
+    as_of: Option<NaiveDate>,
+
+    /// Limits the gain/loss, income, expense and journal entry reports (C4-C7, C13, C14, C16-C20, T6 and J1) to the
+    /// transactions dated in the (fiscal) YEAR, and adds the year to their file names (i.e.,
+    /// C4_Txns_mvmts_detail_2023.csv).  Pass `all` to instead export them once for each fiscal year with a transaction.
+    #[arg(id = "tax year", long = "tax-year", value_name = "YEAR", global = true)]
+    tax_year: Option<String>,
+
+    /// The month (1 through 12) the fiscal year begins in, by which every report grouping by year (and the quarterly
+    /// summary, C28_Quarterly_gains_and_income.csv) groups, and by which `--tax-year` chooses its transactions.  Each
+    /// fiscal year is named for the calendar year it begins in.  FISCAL_YEAR_END sets it by the year's last day.
+    #[arg(id = "fiscal year start", long = "fiscal-year-start", value_name = "MONTH", default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..=12), global = true)]
+    fiscal_year_start: u32,
+
+    /// Limits the reports to the movements of the asset TICKER (i.e., BTC): the account and lot reports (C1-C3 and
+    /// T1-T3) to its accounts, and the transaction, gain/loss and journal entry reports (C4-C7, C13, C14, C17, C18,
+    /// C27, T6 and J1) to its movements and the transactions with them.  The lots are processed as without it.
+    #[arg(id = "filter asset", long = "filter-asset", value_name = "TICKER", global = true)]
+    filter_asset: Option<String>,
+
+    /// Limits the reports to the movements of account NUM (as numbered in the header rows of the file_to_import),
+    /// as `--filter-asset` does to those of an asset.
+    #[arg(id = "filter account", long = "filter-account", value_name = "NUM", global = true)]
+    filter_account: Option<u16>,
+
+    /// Limits the reports dated by transaction (those `--tax-year` limits) to the transactions tagged TAG (i.e.,
+    /// `payment:invoice42`, or `payment` for any payment tag), from a `#TAG` in the memo or the tags column.  The lots
+    /// are processed, and the account and lot reports are, as without it.
+    #[arg(id = "filter tag", long = "filter-tag", value_name = "TAG", global = true)]
+    filter_tag: Option<crptls::tags::Tag>,
+
+    /// Limits the reports dated by transaction (those `--tax-year` limits) to the transactions dated on or after
+    /// DATE (YYYY-MM-DD).  The lots are processed, and the account and lot reports are, as without it.
+    #[arg(id = "from", long = "from", value_name = "DATE", global = true)]
+    from: Option<NaiveDate>,
+
+    /// Limits the reports dated by transaction to the transactions dated on or before DATE (YYYY-MM-DD), as
+    /// `--from` does to those after it.
+    #[arg(id = "to", long = "to", value_name = "DATE", global = true)]
+    to: Option<NaiveDate>,
+
+    /// Writes the processed accounts, transactions, lots and movements to FILE as an SQL script, which loads them
+    /// into SQLite tables (i.e., `sqlite3 cryptools.db < FILE`) for querying with SQL.  Loading it again replaces them.
+    /// To keep every run, and import only the transactions since the last one, use `--db` instead.
+    #[arg(id = "sql dump", long = "sql-dump", value_name = "FILE", global = true)]
+    sql_dump: Option<PathBuf>,
+
+    /// Adds the run to the SQLite database FILE (created if need be, with the `sqlite3` program): its raw accounts,
+    /// transactions, action records, lots and movements, under a new run_id in the runs table, which also keeps its
+    /// open lots.  Once FILE holds a run, the next one resumes from that run's open lots (as with `--resume-from`),
+    /// so the file_to_import need only hold the transactions since.  The rows of every run are kept, for SQL.
+    #[arg(id = "db", long = "db", value_name = "FILE", conflicts_with_all = ["portfolio", "resume from", "resume latest",
+        "opening balances"], global = true)]
+    db: Option<PathBuf>,
+
+    /// Writes Manifest.json to the output directory once the run is complete, recording the program version, the
+    /// time of the run, its command line and settings, and the SHA-256 of each CSV Input File and of each report
+    /// written, so the reports can later be checked against the inputs they came from (i.e., with `sha256sum`).
+    #[arg(id = "manifest", long = "manifest", conflicts_with_all = ["portfolio", "stdout"], global = true)]
+    manifest: bool,
+
+    /// Writes the run result to FILE, rather than to run_result.json in the output directory.  It's written at the end
+    /// of every run (failed or not, though a `--dry-run` writes it only to this FILE), with the exit code and its status (`success`, `processing_error`, `parse_error`,
+    /// `validation_error` or `success_with_warnings`, for exit codes 0 through 4), the error (if any), every warning
+    /// logged, and the path of each report written.
+    #[arg(id = "run result", long = "run-result", value_name = "FILE", global = true)]
+    run_result: Option<PathBuf>,
+
+    /// Encrypts each report written (and the manifest, with `--manifest`) once the run is complete, with `gpg
+    /// --symmetric` (AES-256), replacing it with the same file with `.gpg` added.  The passphrase is that of an
+    /// encrypted file_to_import, if it was, or the CRYPTOOLS_PASSPHRASE environment variable, or is asked for.  An
+    /// encrypted file_to_import (from gpg or age) is decrypted whether or not this is set.
+    #[arg(id = "encrypt output", long = "encrypt-output", conflicts_with_all = ["portfolio", "stdout"], global = true)]
+    encrypt_output: bool,
+
+    /// Prints the duration of each major phase of the run (import, lot processing, each export, etc.) once
+    /// the run is complete. The timings are printed to stderr, so they won't mix with anything written to stdout.
+    #[arg(id = "timings", long = "timings", global = true)]
+    timings: bool,
+
+    /// Doesn't show the progress of the run on stderr.  Otherwise, when stderr is a terminal, a progress bar (or a
+    /// count of rows) is drawn for reading the file_to_import and for lot processing, and a line is printed as each
+    /// phase (including each export) is complete.
+    #[arg(id = "no progress", long = "no-progress", global = true)]
+    no_progress: bool,
+
+    /// Skips the summary printed once the run is complete (transactions processed, their date range, net realized
+    /// gain/loss by term, income, expenses and open lots), and the messages about each step of the run.  Warnings
+    /// are still printed.
+    #[arg(id = "quiet", short, long = "quiet", global = true)]
+    quiet: bool,
+
+    /// Prints debug messages to stderr, including which lot each disposal was drawn from and why (by the inventory
+    /// costing method).  Pass it twice (`-vv`) to trace every movement posted to a lot as well.
+    #[arg(id = "verbose", short, long = "verbose", action = clap::ArgAction::Count, conflicts_with = "quiet", global = true)]
+    verbose: u8,
+
+    /// Processes a named portfolio's file (i.e., `--portfolio alice=alice.csv`) instead of a single file_to_import.
+    /// May be repeated. Each portfolio is processed on its own, with the same settings, and its reports (plus a
+    /// summary, T5_Summary.txt) are exported to a subdirectory of the output directory named after the portfolio.
+    #[arg(id = "portfolio", long = "portfolio", value_name = "NAME=FILE", value_parser = portfolio::parse_portfolio, global = true)]
+    portfolios: Vec<(String, PathBuf)>,
+
+    /// Also exports a report (C11_Portfolio_summaries.csv) to the output directory comparing the headline figures
+    /// of every `--portfolio`, with a combined total.
+    #[arg(id = "combined summary", long = "combined-summary", requires = "portfolio", global = true)]
+    combined_summary: bool,
+
+    /// A TOML file mapping the columns of a CSV with a single header row (such as an exchange's export) to the
+    /// columns of the file_to_import: `date`, `proceeds`, `memo` (and optionally `settle_date` and `tx_type`) name
+    /// the source columns, and each `[[accounts]]` gives a quantity `column` and the `name`, `ticker` and `margin`
+    /// of its account.  See examples/resources/faker1__import_map.toml.
+    #[arg(id = "import map", long = "import-map", value_name = "FILE", global = true)]
+    import_map: Option<PathBuf>,
+
+    /// The exchange whose transaction-history export the file_to_import is, to be converted as it is imported:
+    /// coinbase (the transaction history report), coinbase-pro (fills), kraken (trades), binance (trade history)
+    /// or gemini (transaction history, saved as CSV).  Each currency becomes an account named after the exchange.
+    #[arg(id = "source", long = "source", value_name = "EXCHANGE", conflicts_with = "import map", global = true)]
+    source: Option<crptls::importers::Source>,
+
+    /// The shape of the file_to_import: wide (four header rows describing the accounts, then a column of quantities per
+    /// account), rows (a single header row, then a row per transaction, with an `account`, optional `ticker` and
+    /// `quantity` column for each account it moves), or long (a single header row, then a row per leg, with the
+    /// columns `date`, `account`, `asset`, `amount`, and optionally `proceeds`, `txid` and `memo`, the legs of a
+    /// transaction sharing its txid). With rows or long, an account not in the --accounts file is created when first
+    /// encountered, given its ticker. See InputFile_CSV.md.
+    #[arg(id = "schema", long = "schema", value_name = "SCHEMA", default_value = "wide",
+        conflicts_with_all = ["import map", "source"], global = true)]
+    schema: crptls::csv_import_accts_txns::InputSchema,
+
+    /// An accounts file: a CSV with a header row, then a row per account, with the columns `account` (its number, 1,
+    /// 2, 3...), `name`, `ticker`, and optionally `margin` (yes or no), `address` (its wallet address) and `entity`
+    /// (see ENTITIES in .env.example).  With --schema rows or long, these are the file_to_import's accounts, and any
+    /// other is created when first encountered.  Otherwise, each must match the file_to_import's account of its number.
+    /// See examples/resources/faker1__accounts.csv.
+    #[arg(id = "accounts file", long = "accounts", value_name = "FILE", global = true)]
+    accounts_file: Option<PathBuf>,
+
+    /// Reads the file_to_import in large chunks, and writes any intermediate CSV (the file_to_import converted from
+    /// --source, --import-map or another --schema, or several of them merged) to a temporary file as it's converted,
+    /// rather than keeping it in memory, for very large histories on a machine with little RAM. Nothing is
+    /// memory-mapped: the transactions, lots and movements (and the index of each lot's movements) are all still held
+    /// in memory while the lots are processed, as are the parsed rows of a --source export or a --schema rows or long
+    /// file until they're converted, and an encrypted file_to_import once decrypted, which is never written out as
+    /// plaintext. Memory grows by about 1.4 KB per transaction processed, and 2.4 KB with the default reports
+    /// exported (a peak of 950 MB for 400,000 transactions of gen-test-data), with or without --low-memory.
+    #[arg(id = "low memory", long = "low-memory", global = true)]
+    low_memory: bool,
+
+    /// After processing, saves the open lots (and the date of the last transaction) to FILE, from which a later run
+    /// can resume with `--resume-from`.
+    #[arg(id = "save state", long = "save-state", value_name = "FILE", conflicts_with = "portfolio", global = true)]
+    save_state: Option<PathBuf>,
+
+    /// The same as `--save-state`, but to a snapshot named by the date of the last transaction
+    /// (state_YYYY-MM-DD.toml), kept in the snapshots directory of the `--state-dir`.
+    #[arg(id = "save snapshot", long = "save-snapshot", conflicts_with_all = ["portfolio", "save state"], global = true)]
+    save_snapshot: bool,
+
+    /// Carries forward the open lots saved with `--save-state` by a prior run, so the file_to_import need only hold
+    /// the transactions since, and the reports cover just that period.  Each lot keeps its original basis date and
+    /// remaining cost basis.
+    #[arg(id = "resume from", long = "resume-from", value_name = "FILE", conflicts_with = "portfolio", global = true)]
+    resume_from: Option<PathBuf>,
+
+    /// The same as `--resume-from`, but from the latest snapshot saved with `--save-snapshot`.
+    #[arg(id = "resume latest", long = "resume-latest", conflicts_with_all = ["portfolio", "resume from", "opening balances"],
+        global = true)]
+    resume_latest: bool,
+
+    /// Starts from the lots in FILE (as exported via `--year-end-holdings`, and perhaps since edited, or written by hand
+    /// for what was held before the tracked history) as opening balances, so the file_to_import need only hold the
+    /// transactions after the file's "As of" date.  Each lot keeps its acquisition and basis dates and its cost basis.
+    /// Without "As of" or "Basis date" columns, the lots are as of the latest date acquired, and each one's basis date
+    /// is its date acquired.  An "Inherited" column of yes or no marks the inherited lots, which are always long-term.
+    #[arg(id = "opening balances", long = "opening-balances", value_name = "FILE", conflicts_with_all = ["portfolio", "resume from"], global = true)]
+    opening_balances: Option<PathBuf>,
+
+    /// Fills in the proceeds of any transaction with zero (or blank) proceeds at the day's price of its outgoing (or
+    /// only) currency, from FILE (a CSV of date,ticker,price rows, after a header, dated YYYY-MM-DD and priced in the
+    /// home currency).  Prices not in FILE are fetched from CoinGecko (with curl) and appended to it, which caches them.
+    #[arg(id = "price lookup", long = "price-lookup", value_name = "FILE", global = true)]
+    price_lookup: Option<PathBuf>,
+
+    /// With `--price-lookup` (or `--cached-prices`), reads prices only from its FILE, never fetching any (so FILE must
+    /// exist).
+    #[arg(id = "offline prices", long = "offline-prices", global = true)]
+    offline_prices: bool,
+
+    /// The same as `--price-lookup`, but with the price cache (prices.csv) kept in the cache directory of the
+    /// `--state-dir`, so prices fetched by one run are reused by every later one, wherever it's run from.
+    #[arg(id = "cached prices", long = "cached-prices", conflicts_with = "price lookup", global = true)]
+    cached_prices: bool,
+
+    /// Keeps the profiles, the price cache (of `--cached-prices`) and the state snapshots (of `--save-snapshot`) in
+    /// DIR, rather than in the platform's directories for them: ~/.config/cryptools, ~/.cache/cryptools and
+    /// ~/.local/share/cryptools on Linux (or as set by the XDG variables), %APPDATA%\cryptools (and
+    /// %LOCALAPPDATA%\cryptools) on Windows, and ~/Library/Application Support/cryptools (and ~/Library/Caches) on macOS.
+    #[arg(id = "state dir", long = "state-dir", value_name = "DIR", global = true)]
+    state_dir: Option<PathBuf>,
+
+    /// Applies the corrections in FILE (a CSV of row,field,value rows, after a header, where the row is that of the
+    /// file_to_import, counting its four header rows, or # and the number of a transaction as imported without the
+    /// corrections, and the field is txDate, proceeds, memo, an optional column, an account number, for its quantity,
+    /// or skip (or void), to leave the row out) as the file_to_import is imported, so it needn't be edited itself.  An edit made to a transaction in the print menu, or to a row that failed to import in the
+    /// wizard, is saved to FILE.
+    #[arg(id = "corrections", long = "corrections", value_name = "FILE", global = true)]
+    corrections: Option<PathBuf>,
+
+    /// Reads the lots each disposal is drawn from, under specific identification (inventory costing method 9), from
+    /// FILE (a CSV of txn,lot,amount rows, after a header, where the txn is the number of the disposal's transaction,
+    /// the lot is a lot number or lot ID, such as A2-L3, and the amount is drawn from that lot), rather than asking in
+    /// the lot picker.  This lets specific identification run without an interactive terminal, or with --batch.
+    #[arg(id = "lot selections", long = "lot-selections", value_name = "FILE", global = true)]
+    lot_selections: Option<PathBuf>,
+
+    /// Expenses the costs in FILE, paid in the home currency rather than from an account of the file_to_import (i.e.,
+    /// electricity, or mining equipment): a CSV of date,category,amount,memo,depreciationYears rows, after a header,
+    /// where the date is YYYY-MM-DD and the depreciation years are blank for a cost expensed when paid, or the number
+    /// of years equipment is depreciated over (straight-line, by month).  They're added to C33_Expenses_by_category.csv
+    /// and the journal entries, and netted against mining income in C40_Mining_PnL.csv.
+    #[arg(id = "costs", long = "costs", value_name = "FILE", global = true)]
+    costs: Option<PathBuf>,
+
+    /// Reads settings from a TOML config FILE (or, if not given, from cryptools.toml, if the program is run from a
+    /// directory with one).  Its top-level keys are the environment variables (i.e., `home_currency = "EUR"`), which
+    /// an environment variable or .env file overrides, and its `[flags]` table holds the command line flags by long
+    /// name (i.e., `output = "reports"` or `open-lots = true`), which a flag given on the command line overrides.
+    /// See examples/cryptools.toml.
+    #[arg(id = "config", long = "config", value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// Reads settings from the profile NAME, saved at the end of the wizard, instead of a config file.  It holds the
+    /// wizard's answers (costing method, like-kind treatment, output directory and whether to export) and the date
+    /// format and home currency, and skips the wizard.  Profiles are saved as config files, in profiles in the
+    /// config directory of the `--state-dir` (i.e., ~/.config/cryptools/profiles/NAME.toml on Linux).
+    #[arg(id = "profile", long = "profile", value_name = "NAME", global = true)]
+    profile: Option<String>,
+
     /// File to be imported.  Some notes on the columns: (a) by default, the program expects the `txDate` column to 
     /// be formatted as %m-%d-%y. You may alter this with ISO_DATE and DATE_SEPARATOR_IS_SLASH flags or environment
-    /// variables; (b) the `proceeds` column and any values in transactions must have a period (".") as the decimal
-    /// separator; and (c) there is now experimental support for negative values being wrapped in parentheses. Use
+    /// variables, or set the DATE_FORMAT environment variable to any other format; (b) the `proceeds` column and any values in transactions must have a period (".") as the decimal
+    /// separator, unless the INPUT_LOCALE environment variable is set to one with a decimal comma (i.e., `de`); and (c) there is now experimental support for negative values being wrapped in parentheses. Use
     /// the python script for sanitizing/converting negative values if they are a problem.
     /// See .env.example for further details on environment variables.
-    #[arg(id = "file_to_import")]
-    file_to_import: Option<PathBuf>,
+    /// More than one file (or a directory of CSV files) may be given, i.e., one per exchange or wallet.  The files
+    /// are merged into one, with the rows sorted by date and any account in more than one file (with the same name,
+    /// ticker and margin setting) treated as a single account.  A file of `-` is read from standard input (i.e.,
+    /// `other-program | cryptools -a -`), which requires -a.
+    #[arg(id = "file_to_import", conflicts_with = "portfolio")]
+    file_to_import: Vec<PathBuf>,
+
+    /// What to do with the file_to_import.  Without a command, the flags alone decide (i.e., `-a` to skip the
+    /// wizard, `-s` to export only the reports asked for by flag).  Every flag may follow the command.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// The workflows of the program, each of which stands for a combination of the flat flags (see `Cli::apply_command()`)
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Imports and processes the file(s), exporting every report, without the wizard (as with `-a`).
+    Import {
+        #[arg(id = "import files", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Imports and processes the file(s), exporting only the reports asked for by flag (i.e., `--open-lots` or
+    /// `--tax-software`), without the wizard (as with `-a -s`).
+    Report {
+        #[arg(id = "report files", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Checks the file(s) without processing them, listing every problem by row (as with `--check-only`).
+    Validate {
+        #[arg(id = "validate files", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Walks through the settings with the data entry wizard before processing the file(s), choosing one if none is
+    /// given (as without `-a`).
+    Wizard {
+        #[arg(id = "wizard files")]
+        files: Vec<PathBuf>,
+    },
+    /// Imports and processes the file(s), exporting only the valuations of the open lots from the prices passed
+    /// with `--prices` (C21) and/or `--spot-prices` (C8 and C12), without the wizard.
+    Prices {
+        #[arg(id = "prices files", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Runs an HTTP server answering each POST /reports, whose JSON body is the CSV Input File and any settings
+    /// (i.e., {"csv": "...", "settings": {"costing_method": 3}}), with the reports as JSON (as in Reports.json).
+    /// The settings are those of the request (all optional: home_currency, costing_method, iso_date,
+    /// date_separator_is_slash, date_format, like_kind_cutoff_date, long_term_days and tax_year), not of the command
+    /// line.  Connections are handled on worker threads, and one that stalls is dropped after 30 seconds.
+    Serve {
+        /// The address to listen on.
+        #[arg(id = "listen", long = "listen", value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    /// Writes a completion script for the shell to stdout, completing the flags (and their values), the commands
+    /// and the files to import.
+    Completions {
+        #[arg(id = "shell", value_enum)]
+        shell: completions::Shell,
+    },
+    /// Writes a CSV Input File of made-up transactions (buys, sales, trades and transfers over ten years, none taking
+    /// an account negative) to FILE, or to stdout, for measuring performance or for a repro case that shares nothing
+    /// of your own history. The same options always write the same file.
+    GenTestData {
+        /// The number of transaction rows.
+        #[arg(id = "rows", long = "rows", value_name = "N", default_value_t = 1000)]
+        rows: usize,
+        /// The number of accounts (at least 2): a bank account in the home currency, then an exchange and a wallet
+        /// account of each of BTC, ETH, XMR and so on, in turn. (--accounts is the accounts file.)
+        #[arg(id = "num accounts", long = "num-accounts", value_name = "M", default_value_t = 4)]
+        accounts: usize,
+        /// Picks the transactions, quantities and prices, for a different file of the same size.
+        #[arg(id = "seed", long = "seed", value_name = "SEED", default_value_t = 1)]
+        seed: u64,
+        #[arg(id = "test data file")]
+        file: Option<PathBuf>,
+    },
+    /// Pulls the trade, deposit and withdrawal history of an exchange (coinbase or kraken) from its API, with a
+    /// read-only API key (from the COINBASE_API_KEY and COINBASE_API_SECRET, or KRAKEN_API_KEY and KRAKEN_API_SECRET,
+    /// environment variables), and writes it as a CSV Input File to FILE, or to stdout.  Requires curl.
+    Sync {
+        #[arg(id = "exchange", value_name = "EXCHANGE")]
+        exchange: crptls::importers::Source,
+        /// Pulls only the transactions after the last date of the CSV Input File EXISTING, and writes them merged
+        /// into it (to FILE, which may be EXISTING itself).
+        #[arg(id = "merge", long = "merge", value_name = "EXISTING")]
+        merge: Option<PathBuf>,
+        #[arg(id = "sync file")]
+        file: Option<PathBuf>,
+    },
+    /// Pulls the on-chain history of BTC and ETH addresses from a public API (or a Bitcoin Core node), and writes it
+    /// as a CSV Input File to FILE, or to stdout, with an account of each currency named `On-chain wallet`.  Each
+    /// transaction is received, or sent (fee included), with proceeds of 0, to be reviewed (i.e., a transfer from
+    /// your own exchange account combined with its other side) and priced (i.e., with `--price-lookup`) before
+    /// processing.  Requires curl.
+    ChainSync {
+        /// A BTC address, or an xpub (ypub or zpub), pulled from blockchain.info.  May be repeated, for the addresses of
+        /// one wallet.
+        #[arg(id = "btc", long = "btc", value_name = "ADDRESS")]
+        btc: Vec<String>,
+        /// Pulls the BTC history of the wallet of the Bitcoin Core node at URL (i.e., http://127.0.0.1:8332/wallet/cold,
+        /// with its addresses imported as watch-only) instead, as the user and password of the BITCOIN_RPC_USER and
+        /// BITCOIN_RPC_PASSWORD environment variables.
+        #[arg(id = "btc rpc", long = "btc-rpc", value_name = "URL", conflicts_with = "btc")]
+        btc_rpc: Option<String>,
+        /// An ETH address, pulled from Etherscan (native ETH only, not tokens), with the key of the ETHERSCAN_API_KEY
+        /// environment variable.  May be repeated.
+        #[arg(id = "eth", long = "eth", value_name = "ADDRESS")]
+        eth: Vec<String>,
+        /// An Etherscan-compatible API to pull the ETH history from instead (i.e., a block explorer's, or another
+        /// chain's).
+        #[arg(id = "eth api", long = "eth-api", value_name = "URL", default_value = chain_sync::ETHERSCAN_API)]
+        eth_api: String,
+        #[arg(id = "chain sync file")]
+        file: Option<PathBuf>,
+    },
+    /// Starts a new user off without a CSV Input File: asks which exchanges and wallets hold which currencies (and
+    /// the home currency and costing method), then writes cryptools_input.csv, with an account column of each and a
+    /// few sample rows to replace, and a cryptools.toml config file to match, into DIR (the current directory by
+    /// default).  Neither file may already exist.
+    Init {
+        #[arg(id = "init dir", value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+    /// Works with the settings of a run.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Writes the effective settings to stdout, in TOML (as a config file for `--config`), after the environment
+    /// variables, .env file, config file (or profile) and flags have all been merged.
+    Dump,
+}
+
+impl Cli {
+
+    /// Sets the flat flags the `command` (if any) stands for, and moves its files into `file_to_import`.
+    fn apply_command(&mut self) {
+
+        let files = match self.command.take() {
+            None => return,
+            Some(Command::Import { files }) => { self.accept_args = true; files }
+            Some(Command::Report { files }) => { self.accept_args = true; self.suppress_reports = true; files }
+            Some(Command::Validate { files }) => { self.check_only = true; files }
+            Some(Command::Wizard { files }) => { self.accept_args = false; files }
+            Some(Command::Serve { .. } | Command::Completions { .. } | Command::GenTestData { .. } | Command::Sync { .. }
+                | Command::ChainSync { .. } | Command::Init { .. } | Command::Config { .. }) => return,
+            Some(Command::Prices { files }) => {
+                if self.prices.is_none() && self.spot_prices.is_none() {
+                    run_result::fatal(ExitCode::ParseError, "FATAL: The prices command needs a prices file, via --prices FILE or --spot-prices FILE.")
+                }
+                self.accept_args = true;
+                self.suppress_reports = true;
+                files
+            }
+        };
+
+        if !self.file_to_import.is_empty() {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Give the file(s) to import after the command (i.e., `cryptools import FILE`), not before it.")
+        }
+
+        self.file_to_import = files;
+    }
 }
 
 /// These are the values able to be captured from environment variables.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Cfg {
     /// Setting the corresponding environment variable to `true` (or `1`) will cause the program to expect the `txDate` field in the 
     /// `Cli::file_to_import` to use the format YYYY-MM-dd or YY-MM-dd (or YYYY/MM/dd or YY/MM/dd, depending on the date-separator option).
@@ -92,26 +792,277 @@ pub struct Cfg {
     /// Switches the default date separator from hyphen to slash (i.e., from "-" to "/") to indicate the separator
     /// character used in the file_to_import txDate column (i.e. 2017-12-31 to 2017/12/31).
     date_separator_is_slash: bool,
+    /// A chrono strftime format string used to parse the `txDate` field of the `Cli::file_to_import` directly (i.e.,
+    /// `%b %d, %Y` for `Jan 5, 2021`, or `%d-%b-%Y` for `05-Jan-2021`). When set, it takes precedence over the ISO_DATE
+    /// and DATE_SEPARATOR_IS_SLASH options (including their command line flags).  The default is blank/commented/`None`.
+    date_format: Option<String>,
+    /// The delimiter of the fields of the `Cli::file_to_import`: `,`, `;`, `|`, `tab` or `auto`, to detect whichever
+    /// of them splits its first rows into the same number of fields (and whether its fields are quoted by single or
+    /// double quotes).  [default: auto]
+    csv_delimiter: Option<String>,
+    /// The locale of the numbers of the `Cli::file_to_import`: a language code like `de`, whose numbers have a decimal
+    /// comma (1.234,56), or `en` or `plain`, for a decimal point (with any commas taken to separate thousands).
+    /// [default: plain]
+    input_locale: Option<String>,
     /// Home currency (currency from the `proceeds` column of the `Cli::file_to_import` and in which all resulting reports are denominated).  
-    /// Default is `USD`.
+    /// Secondary home currencies may follow it (i.e., `USD,EUR`), each getting a parallel set of reports, translated at
+    /// the FX_RATES_FILE rates.  Default is `USD`.
     home_currency: String,
+    /// A CSV of `date,currency,rate` rows (after a header row), dated YYYY-MM-DD, with each rate being the units of
+    /// a secondary home currency that one unit of the home currency is worth.  Required by a secondary home currency.
+    /// The default is blank/commented/`None`.
+    fx_rates_file: Option<String>,
     /// Cutoff date through which like-kind exchange treatment should be applied. You must use %y-%m-%d (or %Y-%m-%d)
     /// format for like-kind cutoff date entry.  The default is blank/commented/`None`.
     lk_cutoff_date: Option<String>,
+    /// Date ranges within which like-kind exchange treatment is applied, instead of through LK_CUTOFF_DATE, as
+    /// `YYYY-MM-DD..YYYY-MM-DD` separated by commas (i.e., `2016-01-01..2017-12-31,2019-01-01..2019-06-30`).
+    /// The default is blank/commented/`None`.
+    lk_windows: Option<String>,
+    /// The asset class of each ticker listed, as `class=TICKER|TICKER` separated by commas (i.e., `fiat=EUR|GBP`),
+    /// for LK_PAIRS.  Any ticker not listed is `crypto`.  The default is blank/commented/`None`.
+    lk_asset_classes: Option<String>,
+    /// The pairs of asset classes (or tickers) whose exchanges get like-kind treatment, as `class/class` separated by
+    /// commas (i.e., `crypto/crypto`).  Any other exchange is taxable.  The default is blank/commented/`None`, in which
+    /// case every exchange of two non-home currencies gets like-kind treatment.
+    lk_pairs: Option<String>,
     /// method number for lot selection <method number for lot selection>
     /// 1. LIFO according to the order the lot was created.
     /// 2. LIFO according to the basis date of the lot.
     /// 3. FIFO according to the order the lot was created.
     /// 4. FIFO according to the basis date of the lot.
+    /// 5. Long-term lots first, then short-term lots (FIFO according to the basis date of the lot within each).
+    /// 6. Long-term lots first, then short-term lots (LIFO according to the basis date of the lot within each).
+    /// 7. HIFO (lots with the highest cost basis per unit first).
+    /// 8. Weighted average cost (each disposal is drawn from every lot in proportion to its balance).
+    /// 9. Specific identification (choose the lots for each disposal as it is processed; requires print_menu).
+    /// 10. UK share pooling (same-day, then 30-day "bed and breakfast" matching, then the Section 104 pool).
+    /// 11. Canadian adjusted cost base (ACB), with the superficial loss rule.
      /// [default: 1]
     inv_costing_method: String,
+    /// Whether the home currency leg of a trade (i.e., the USD spent in a USD->BTC buy) is the cost basis of
+    /// the acquired asset. Setting to `false` (or `0`) values the acquired asset using the `proceeds` column instead.
+    /// The home currency leg itself never generates a gain or loss.  [default: true]
+    home_curr_leg_is_basis: bool,
+    /// Setting to `true` (or `1`) causes acquired lots to use the optional `settleDate` column of the `Cli::file_to_import`
+    /// (when present and not blank) as their basis date, rather than the trade date in the `txDate` column.  This can
+    /// change whether a disposal near the one-year mark is short- or long-term.  [default: false]
+    settle_date_is_basis_date: bool,
+    /// Setting to `true` (or `1`) treats a row of the `Cli::file_to_import` without any non-zero quantity (i.e., a memo
+    /// or fee adjustment an exchange exports on its own row) as an annotation of the transaction on the row before it,
+    /// rather than as a transaction. No lot or movement is created for it.  [default: false]
+    zero_quantity_rows_are_annotations: bool,
+    /// Setting to `true` (or `1`) treats the fee on a transfer between the user's own accounts (the units sent but not
+    /// received, per the optional `fee` column) as a disposal of those units, realizing a gain or loss.  Otherwise, the
+    /// fee is an expense of the basis of the units spent.  [default: false]
+    transfer_fee_is_disposal: bool,
+    /// A comma-separated list of stablecoin tickers (i.e., `USDC,USDT,DAI`) to treat as cash equivalents: disposing of
+    /// one realizes no gain or loss (its proceeds are its basis), so its disposals are left off the Form 8949 report.
+    /// Any other stablecoin, and any fiat currency other than the home currency, is an asset like any other, with its
+    /// own lots and gains or losses.  The default is blank/commented/`None`.
+    stablecoins: Option<String>,
+    /// With `--match-transfers`, the largest fraction of a withdrawal's units that may be lost to a network fee for it
+    /// to be paired with a deposit (i.e., `0.01` for 1%).  The units sent but not received are the transfer's fee,
+    /// valued at their share of the withdrawal's proceeds.  [default: 0.01]
+    transfer_match_tolerance: Option<String>,
+    /// How a liquidity pool deposit or withdrawal (an `lp-deposit` or `lp-withdrawal` txType) is treated: `swap`, in
+    /// which each asset deposited is exchanged for half of the LP tokens (and vice versa), realizing a gain or loss,
+    /// or `carryover`, in which the basis of what was given carries over to what was received.  [default: swap]
+    lp_treatment: Option<String>,
+    /// Where a disposal's cost basis is drawn from: `per-wallet`, the lots held in the account it came out of, or
+    /// `universal`, the lots of its currency held in any account, in the order of the costing method.  [default: per-wallet]
+    lot_pooling: Option<String>,
+    /// The basis of what is received in an airdrop or a hard fork (an `airdrop` or `fork` txType or incomeType): `fmv`,
+    /// its fair market value (the `proceeds`), which is also income, or `zero`, with no income.  [default: fmv]
+    airdrop_basis: Option<String>,
+    /// How an increase from a rebase (a `rebase` txType) is treated: `income`, at its fair market value (the
+    /// `proceeds`) as it accrues, or `basis`, adding units but no basis, so the basis held is spread over more units.
+    /// A decrease is always a loss of the basis of the units lost.  [default: income]
+    rebase_treatment: Option<String>,
+    /// Path to a CSV of `alias,ticker` rows naming tickers that are the same asset (i.e., `WBTC,BTC` or `VEN,VET`),
+    /// for which an exchange of one for another carries its basis and basis dates over, rather than being a disposal.
+    token_aliases: Option<String>,
+    /// The accounts of each entity (i.e., an LLC or a trust) reported separately, as `entity=account|account`
+    /// separated by commas (i.e., `LLC=3|4,Trust=5`), rather than in the `entity` column of the accounts file.  Any
+    /// other account is `personal`.  The default is blank/commented/`None`.
+    entities: Option<String>,
+    /// How a transfer between the accounts of two entities is treated: `carryover`, with its basis and basis dates
+    /// carried over, or `sale`, as a sale at its `proceeds` by the entity sending to the entity receiving.  [default: carryover]
+    entity_transfers: Option<String>,
+    /// Other headers the CSV Input File's columns may have, as `column=alias|alias` separated by commas (i.e.,
+    /// `txDate=Date|timestamp,memo=Notes`).  The default is blank/commented/`None`.
+    header_aliases: Option<String>,
+    /// The fields of a transaction row compared in finding duplicates (with `--dedupe`), separated by commas: any of
+    /// `date`, `accounts`, `amounts`, `proceeds` and `memo`.  [default: date,accounts,amounts,memo]
+    dedupe_key: Option<String>,
+    /// Proceeds threshold (in home currency) at or below which same-day exchanges into the same account are
+    /// grouped as a single dust conversion on the Form 8949 report, and/or `TICKER=quantity` entries, separated by
+    /// commas (i.e., `5.00,BTC=0.00000001`), at or below which what a disposal leaves in an account of that ticker is
+    /// swept out of it (see C43_Dust_sweeps.csv).  The default is blank/commented/`None`.
+    dust_threshold: Option<String>,
+    /// Rounds each outgoing amount that is more precise than allowed, before lots are processed, so that disposals
+    /// close out lots cleanly rather than leaving residual micro-lots. Set to `lots` to round to the precision of the
+    /// amounts previously received by the account, or to a number of decimal places.  Each residual is printed.
+    /// The default is blank/commented/`None`.
+    disposal_rounding: Option<String>,
+    /// Groups accounts by exchange in per-exchange reports, using the part of each account's name before this
+    /// separator as its exchange label (i.e., with ":", accounts "Kraken: BTC" and "Kraken: ETH" are both "Kraken").
+    /// The default is blank/commented/`None`, in which case each account is its own exchange.
+    exchange_label_separator: Option<String>,
+    /// For a knowingly incomplete history, set to `zero` or `market` to insert a synthetic acquisition ahead of any
+    /// transaction that would otherwise take an account negative, covering exactly the shortfall. `market` values it
+    /// using the proceeds of that transaction. Every synthetic acquisition is listed in C10_Synthetic_acquisitions.csv.
+    /// The default is blank/commented/`None`, in which case a negative balance is a fatal error.
+    synthetic_acquisitions: Option<String>,
+    /// The order in which transactions on the same date are processed: `file-order`, `acquisitions-first` (then
+    /// exchanges, then disposals) or `disposals-first`. Under LIFO, this decides whether a same-day disposal is drawn
+    /// from that day's acquisitions or from older lots. It breaks ties after the txTime and txSeq columns (if any),
+    /// and any tie after it is broken by the order of the rows. Reordered transactions are renumbered.
+    /// [default: file-order]
+    same_date_order: Option<String>,
+    /// Your (tax) timezone, as a UTC offset like `-05:00` or `+09:00` (or `UTC`). The txDate and txTime of each row
+    /// with a txTime (which is UTC, unless it has an offset) are converted to it, so transactions fall on the dates
+    /// (and in the tax years) of your timezone. The default is blank/commented/`None`, in which case no conversion
+    /// is done, and a txTime only orders the transactions of its date.
+    timezone: Option<String>,
+    /// Setting to `true` (or `1`) applies the wash sale rule: a loss on a disposal is disallowed to the extent the same
+    /// currency was purchased within the window before or after it, and is added to the basis of the replacement lot.
+    /// Every wash sale is listed in C16_Wash_sales.csv. Can't be combined with like-kind treatment.  [default: false]
+    wash_sale: bool,
+    /// The number of days before and after a loss within which a purchase is a wash sale replacement.  [default: 30]
+    wash_sale_window: Option<String>,
+    /// The number of days a lot must be held for more than, for its disposal to be long-term rather than short-term.
+    /// Every report's short-term/long-term classification uses it.  [default: 365]
+    long_term_days: Option<String>,
+    /// The number of days a lot must be held for more than, for its disposal's gain or loss to be posted to a long-term
+    /// account in the journal entries, if other than LONG_TERM_DAYS.  The default is blank/commented/`None`.
+    je_long_term_days: Option<String>,
+    /// The accounts the journal entries post gains, losses and income to, as `key=account` separated by commas (i.e.,
+    /// `st-gain=Income:Gains:ShortTerm,st-loss=Expenses:Losses:ShortTerm`).  The default is blank/commented/`None`.
+    je_accounts: Option<String>,
+    /// The number of decimal places of the home currency values (proceeds, cost basis, gains and the like) in every
+    /// report.  [default: 2]
+    fiat_decimal_places: Option<String>,
+    /// The number of decimal places the quantities in the reports are rounded to.  The default is blank/commented/
+    /// `None`, in which case each quantity is written with all of its decimal places.
+    crypto_decimal_places: Option<String>,
+    /// How a value exactly halfway between two roundings is rounded in the reports: `half-up` (away from zero) or
+    /// `bankers` (to the even digit).  Values are exact while processing, so only the reports are affected.
+    /// [default: bankers]
+    output_rounding: Option<String>,
+    /// The separators of the numbers in the reports: a language code like `en` (1,234.56) or `de` (1.234,56), or
+    /// `plain` (1234.56).  [default: plain]
+    report_locale: Option<String>,
+    /// A chrono strftime format string for the dates in the reports (i.e., `%d.%m.%Y`).  The default is blank/
+    /// commented/`None`, in which case dates are written as YYYY-MM-DD.
+    output_date_format: Option<String>,
+    /// The language of the text reports, the journal entries and the print menu: a language code like `es` or
+    /// `de-DE`.  The default is blank/commented/`None`, in which case it's the language of the locale (LC_ALL,
+    /// LC_MESSAGES or LANG), if Spanish or German, or else English.
+    report_language: Option<String>,
+    /// The month and day (MM-DD, i.e., `06-30`) the fiscal year ends on, which must be the last day of a month.  Each
+    /// report grouping by year groups by fiscal year, named for the calendar year it begins in, as does `--tax-year`.
+    /// The default is blank/commented/`None`, in which case the fiscal year is as `--fiscal-year-start` (by default,
+    /// the calendar year).
+    fiscal_year_end: Option<String>,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+
+    //  Any report not yet moved into place is only moved once the run has succeeded (see `create_report_file()`)
+    let result = match run() {
+        Ok(()) => export_csv::commit_written_reports().map_err(|e| e.into()),
+        Err(e) => {
+            export_csv::discard_pending_reports();
+            Err(e)
+        }
+    };
+
+    //  Printed for the user (i.e., an `InputError`'s row and its snippet), rather than as the error's Debug form
+    match result {
+        Ok(()) => run_result::exit(run_result::success_code(), None),
+        Err(e) => {
+            match e.downcast_ref::<clap::Error>() {
+                Some(usage_error) => { let _ = usage_error.print(); }
+                None => eprintln!("Error: {}", e),
+            }
+            run_result::exit(run_result::exit_code_of(e.as_ref()), Some(&e.to_string()))
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+
+    let raw_args = setup::args_with_config_flags().map_err(run_result::parse_error)?;
+    let mut args = match Cli::try_parse_from(raw_args.clone()) {
+        Ok(args) => args,
+        //  --help and --version aren't runs
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(path) = &args.run_result {
+        run_result::set_path(path);
+    }
+    //  Until the export directory is settled (by the wizard, or --run-subdir)
+    run_result::set_export_dir(&args.output_dir_path);
+
+    if matches!(args.command, Some(Command::Completions { .. } | Command::Serve { .. } | Command::Init { .. } | Command::Config { .. })) {
+        run_result::skip();
+    }
+    //  A dry run writes no files, unless one was named for the run result
+    if args.dry_run && args.run_result.is_none() {
+        run_result::skip();
+    }
+
+    if let Some(Command::Completions { shell }) = args.command {
+        print!("{}", completions::completion_script(shell, &mut Cli::command()));
+        return Ok(())
+    }
+
+    // Only the settings (or the test data) are written to stdout
+    let dump_config = matches!(args.command, Some(Command::Config { action: ConfigAction::Dump }));
+    let test_data_to_stdout = matches!(args.command, Some(Command::GenTestData { file: None, .. }));
+    let sync_to_stdout = matches!(args.command, Some(Command::Sync { file: None, .. } | Command::ChainSync { file: None, .. }));
+
+    logging::init(args.verbose, args.quiet || dump_config || test_data_to_stdout || sync_to_stdout);
+
+    if let Some(Command::Serve { listen }) = &args.command {
+        return serve::serve(listen)
+    }
+    if let Some(Command::GenTestData { rows, accounts, seed, file }) = &args.command {
+        return gen_test_data(*rows, *accounts, *seed, file.as_deref(), &args)
+    }
+    if let Some(Command::Sync { exchange, merge, file }) = &args.command {
+        return sync::sync(*exchange, merge.as_deref(), file.as_deref(), &args)
+    }
+    if let Some(Command::ChainSync { btc, btc_rpc, eth, eth_api, file }) = &args.command {
+        return chain_sync::chain_sync(btc, btc_rpc.as_deref(), eth, eth_api, file.as_deref(), &args)
+    }
+    if let Some(Command::Init { dir }) = &args.command {
+        return onboarding::init(dir.as_deref(), &args)
+    }
+    args.apply_command();
+
+    if dump_config {
+        let cfg = setup::get_env(&args).map_err(run_result::parse_error)?;
+        let mut cmd = Cli::command();
+        let matches = cmd.try_get_matches_from_mut(raw_args)?;
+        print!("{}", config_dump::dump_settings(&cfg, &cmd, &matches)?);
+        return Ok(())
+    }
+
+    #[cfg(feature = "print_menu")]
+    if args.batch && args.print_menu {
+        batch::exit_for_missing_input("print_menu", "The print menu would ask which reports to print or export.")
+    }
 
-    let args = Cli::parse();
+    if let Some(report) = args.stdout {
+        export_csv::reserve_stdout_for_report()?;
+        args.reports = vec![report.group()];
+    }
 
-    println!(
+    log::info!(
         "\
 Hello!
 
@@ -124,16 +1075,218 @@ See examples/.env.example or run with --help to learn how to change default prog
   Note: The software is designed to import a full history. Gains and losses may be incorrect otherwise.
     ");
 
-    let cfg = setup::get_env(&args)?;
+    if args.watch {
+
+        if !args.accept_args {
+            run_result::fatal(ExitCode::ParseError, "FATAL: --watch requires -a (--accept), as the wizard would otherwise ask its questions on every run.")
+        }
+
+        #[cfg(feature = "print_menu")]
+        if args.print_menu {
+            run_result::fatal(ExitCode::ParseError, "FATAL: --watch can't be combined with the print menu.")
+        }
+
+        if args.file_to_import.iter().any(|file| crptls::csv_import_accts_txns::is_stdin(file)) {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Standard input (-) can't be watched for changes.")
+        }
+    }
+
+    let cfg = setup::get_env(&args).map_err(run_result::parse_error)?;
+    let environment = serde_json::to_value(&cfg)?;
+
+    let (input_file_paths, mut settings) = setup::run_setup(&args, cfg)?;
+
+    run_result::set_export_dir(&settings.export_path);
+
+    if args.batch && settings.costing_method == InventoryCostingMethod::SpecificIdentification && !args.check_only
+        && settings.lot_selections.is_none() {
+        batch::exit_for_missing_input("lot_allocations",
+            "Specific identification (inventory costing method 9) would ask which lots each disposal is drawn from \
+            (pass --lot-selections FILE to read them from a file).")
+    }
 
-    let (input_file_path, settings) = setup::run_setup(&args, cfg)?;
+    // The lot picker's TUI (or the debug messages) would be drawn over by the progress bar
+    let mut timings = progress::ProgressBar::timings(
+        !args.no_progress && args.verbose == 0 && settings.costing_method != InventoryCostingMethod::SpecificIdentification
+    );
+
+    let input_file_paths = match input_file_paths {
+        Some(paths) => paths,
+        None => {
+            portfolio::process_portfolios(&args, &settings, &mut timings)?;
+            print_timings(&args, &timings);
+            return Ok(())
+        }
+    };
+
+    if args.watch {
+        return watch::watch(&input_file_paths)
+    }
+
+    if args.check_only {
+
+        let mut problems = 0;
+
+        for path in input_file_paths.iter() {
+
+            let issues = crptls::validation::validate_input_file(path, &settings)?;
+
+            for issue in issues.iter() {
+                println!("  {}: row {}: {}", path.display(), issue.row, issue.message);
+            }
+
+            problems += issues.len();
+        }
+
+        if problems > 0 {
+            run_result::fatal(ExitCode::ValidationError, &format!("\nFound {} problem(s). Nothing was processed.", problems))
+        }
+
+        println!("\nNo problems found. Nothing was processed.");
+        return Ok(())
+    }
+
+    if let Some(path) = &args.anonymize_export {
+        let file = std::io::BufWriter::new(File::create(path)?);
+        crptls::anonymize::anonymize_input_files(&input_file_paths, &settings, file)?;
+        println!("\nWrote an anonymized copy of the file_to_import to {}. Nothing was processed.", path.display());
+        return Ok(())
+    }
 
+    if args.compare_methods {
+        compare_methods::compare_costing_methods(&settings, &input_file_paths, &mut timings)?;
+        print_timings(&args, &timings);
+        return Ok(())
+    }
+
+    let review_rows = match args.needs_review {
+        true => {
+            let review_rows = crptls::needs_review::find_rows_needing_review(&input_file_paths, &settings)?;
+            if !review_rows.is_empty() {
+                log::warn!("WARNING: Parked {} row(s) that couldn't be fully classified, leaving them out of processing. \
+                    See C38_Rows_needing_review.csv.", review_rows.len());
+            }
+            settings.parked_rows = review_rows.iter().map(|review_row| review_row.row).collect();
+            Some(review_rows)
+        }
+        false => None,
+    };
+
+    // In the wizard (at a terminal), a row that fails to import may be skipped or edited, with a correction, and the
+    // file_to_import is imported again, with it
     let (
-        raw_acct_map,
-        account_map,
-        action_records_map,
-        transactions_map,
-    ) = crptls::core_functions::import_and_process_final(input_file_path, &settings)?;
+        mut raw_acct_map,
+        mut account_map,
+        mut action_records_map,
+        mut transactions_map,
+    ) = loop {
+
+        let error = match import_and_process(&input_file_paths, &settings, &mut timings) {
+            Ok(maps) => break maps,
+            Err(e) => e,
+        };
+
+        let input_error = match error.downcast_ref::<crptls::input_error::InputError>() {
+            Some(input_error) if !args.accept_args && std::io::IsTerminal::is_terminal(&std::io::stdin()) => input_error,
+            _ => return Err(error),
+        };
+
+        let corrections_path = settings.corrections.clone()
+            .unwrap_or_else(|| wizard::default_corrections_path(&input_file_paths));
+
+        if !wizard::recover_from_input_error(input_error, &corrections_path)? { return Err(error) }
+
+        if settings.corrections.is_none() {
+            println!("Pass `--corrections {}` to apply the correction on later runs.", corrections_path.display());
+        }
+
+        settings.corrections = Some(corrections_path);
+    };
+
+    // In the wizard, the rows that couldn't be fully classified are resolved with corrections, and the file_to_import
+    // is processed again, with them
+    if !args.accept_args {
+
+        let unresolved = crptls::unresolved::find_unresolved_rows(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        let corrections_path = settings.corrections.clone()
+            .unwrap_or_else(|| wizard::default_corrections_path(&input_file_paths));
+
+        let saved = wizard::resolve_unresolved_rows(&unresolved, &settings.home_currency, &corrections_path)?;
+
+        if saved > 0 {
+
+            if settings.corrections.is_none() {
+                println!("\nSaved {} correction(s). Pass `--corrections {}` to apply them on later runs.",
+                    saved, corrections_path.display());
+            }
+
+            settings.corrections = Some(corrections_path);
+
+            (raw_acct_map, account_map, action_records_map, transactions_map) =
+                import_and_process(&input_file_paths, &settings, &mut timings)?;
+        }
+    }
+
+    if !settings.excluded_txns.is_empty() {
+        what_if::print_exclusion_delta(
+            &settings,
+            &input_file_paths,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+            &mut timings,
+        )?;
+    }
+
+    if args.dry_run {
+
+        let summary = crptls::summary::RunSummary::from_maps(
+            &settings.home_currency,
+            settings.long_term_days,
+            settings.fiscal_year_start_month,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        print_run_summary("Dry run (nothing was exported)", &settings.home_currency, &summary);
+        println!("  Accounts:                {}", summary.account_count);
+        println!("  Lots created:            {}", summary.lots_created);
+        println!("  Proceeds of disposals:   {:.2} {}", summary.proceeds, settings.home_currency);
+        print_holdings(&summary);
+
+        print_timings(&args, &timings);
+        return Ok(())
+    }
+
+    if !settings.chart_of_accounts.is_empty() {
+
+        let unmapped = export_je::unmapped_accounts(
+            &settings,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        if !unmapped.is_empty() {
+            println!("FATAL: The --chart-of-accounts file doesn't map these accounts of the journal entries (map each, or a \
+                prefix of it, i.e., Income:CapitalGains):");
+            for account in unmapped.iter() {
+                println!("  {}", account);
+            }
+            run_result::exit(ExitCode::ValidationError, Some("The --chart-of-accounts file doesn't map every account of the journal entries."))
+        }
+    }
 
     let mut should_export_all = settings.should_export;
 
@@ -143,43 +1296,180 @@ See examples/.env.example or run with --help to learn how to change default prog
     #[cfg(feature = "print_menu")]
     if present_print_menu_tui { should_export_all = false }
 
-    let print_journal_entries_only = settings.journal_entry_export;
-    if print_journal_entries_only { should_export_all = false }
+    export_reports(
+        &args,
+        &settings,
+        should_export_all,
+        &raw_acct_map,
+        &account_map,
+        &action_records_map,
+        &transactions_map,
+        &mut timings,
+    )?;
 
-    if should_export_all {
+    export_entity_reports(
+        &args,
+        &settings,
+        should_export_all,
+        &raw_acct_map,
+        &account_map,
+        &action_records_map,
+        &transactions_map,
+        &mut timings,
+    )?;
 
-        export_all::export(
-            &settings,
+    export_secondary_currency_reports(&args, &settings, &input_file_paths, should_export_all, &mut timings)?;
+
+    if should_export_all && !settings.journal_entry_export && args.export_summary {
+
+        let start = Instant::now();
+
+        let summary = crptls::summary::RunSummary::from_maps(
+            &settings.home_currency,
+            settings.long_term_days,
+            settings.fiscal_year_start_month,
             &raw_acct_map,
             &account_map,
             &action_records_map,
-            &transactions_map
+            &transactions_map,
         )?;
+
+        export_txt::_5_run_summary_to_txt(&settings, &summary_title(&input_file_paths), &summary)?;
+
+        timings.record("Export: T5_Summary", start);
+    }
+
+    if settings.dedupe != crptls::dedupe::DedupeMode::Off {
+
+        let start = Instant::now();
+
+        let duplicates = crptls::dedupe::find_duplicate_rows(&input_file_paths, &settings)?;
+
+        export_csv::_31_duplicate_rows_to_csv(&settings, &duplicates)?;
+
+        timings.record("Export: C31_Duplicate_rows", start);
+    }
+
+    if let Some(review_rows) = &review_rows {
+
+        let start = Instant::now();
+
+        export_csv::_38_rows_needing_review_to_csv(&settings, review_rows)?;
+
+        timings.record("Export: C38_Rows_needing_review", start);
+    }
+
+    if args.save_state.is_some() || args.save_snapshot {
+
+        let state = RunState::from_processed(&settings, &raw_acct_map, &account_map, &transactions_map)?;
+
+        let path = match &args.save_state {
+            Some(path) => path.clone(),
+            None => {
+                let path = state_dirs::StateDirs::new(args.state_dir.as_deref()).snapshot_path(state.through);
+                if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)? }
+                path
+            }
+        };
+
+        state.write_to_file(&path)?;
+
+        log::info!("  Saved {} open lot(s) held through {} to {}.", state.lots.len(), state.through, path.display());
     }
 
-    if print_journal_entries_only && !settings.lk_treatment_enabled {
+    if let Some(db_path) = &args.db {
+
+        let state = RunState::from_processed(&settings, &raw_acct_map, &account_map, &transactions_map)?;
 
-        export_je::prepare_non_lk_journal_entries(
+        export_sql::_2_processed_maps_to_db(
             &settings,
+            db_path,
+            &state,
             &raw_acct_map,
             &account_map,
             &action_records_map,
             &transactions_map,
         )?;
+
+        log::info!("  Added the run, with {} open lot(s) held through {}, to {}.", state.lots.len(), state.through,
+            db_path.display());
     }
 
     #[cfg(feature = "print_menu")]
     if present_print_menu_tui {
 
-        mytui::print_menu_tui::print_menu_tui(
-            &settings,
+        let mut reopen_at = None;
+
+        // After an edit is saved to the corrections file, the file_to_import is processed again, with it
+        loop {
+
+            reopen_at = mytui::print_menu_tui::print_menu_tui(
+                &settings,
+                &raw_acct_map,
+                &account_map,
+                &action_records_map,
+                &transactions_map,
+                reopen_at,
+            )?;
+
+            if reopen_at.is_none() { break }
+
+            (raw_acct_map, account_map, action_records_map, transactions_map) =
+                import_and_process(&input_file_paths, &settings, &mut timings)?;
+        }
+    }
+
+    //  Checked before any report is moved into place, so that reports that can't be encrypted are never left
+    let encryption_passphrase = match args.encrypt_output && settings.should_export {
+        true => {
+            let mut paths = export_csv::written_reports();
+            if args.manifest { paths.push(manifest::manifest_path(&settings)) }
+            Some(prepare_report_encryption(&settings, &paths)?)
+        }
+        false => None,
+    };
+
+    let mut manifest_path = None;
+
+    if args.manifest && settings.should_export {
+
+        //  So that it checksums the reports as they're left
+        export_csv::commit_written_reports()?;
+
+        let path = manifest::write_manifest(&settings, &input_file_paths, environment)?;
+
+        log::info!("  Saved the manifest of the run to {}.", path.display());
+        manifest_path = Some(path);
+    }
+
+    if let Some(passphrase) = encryption_passphrase {
+
+        export_csv::commit_written_reports()?;
+
+        let mut paths = export_csv::written_reports();
+        paths.extend(manifest_path);
+
+        encrypt_reports(&paths, &passphrase)?;
+    }
+
+    if !args.quiet {
+
+        let summary = crptls::summary::RunSummary::from_maps(
+            &settings.home_currency,
+            settings.long_term_days,
+            settings.fiscal_year_start_month,
             &raw_acct_map,
             &account_map,
             &action_records_map,
-            &transactions_map
+            &transactions_map,
         )?;
+
+        print_run_summary("Summary", &settings.home_currency, &summary);
+        print_holdings(&summary);
     }
 
+    print_timings(&args, &timings);
+
     // use tests::test;
     // test::run_tests(
     //     &transactions_map,
@@ -191,3 +1481,618 @@ See examples/.env.example or run with --help to learn how to change default prog
     Ok(())
 
 }
+
+/// Checks, before any of the reports at `paths` (the manifest among them, if it's to be written) is moved into place,
+/// that they can be encrypted: that `gpg` runs, and that no encrypted report left by an earlier run is in the way
+/// (which is only replaced with `--overwrite`, as a report is).  Returns the passphrase to encrypt them with.
+fn prepare_report_encryption(
+    settings: &crptls::core_functions::ImportProcessParameters,
+    paths: &[PathBuf],
+) -> Result<String, Box<dyn Error>> {
+
+    crptls::encryption::check_gpg()?;
+
+    for path in paths.iter() {
+        export_csv::check_overwrite(settings, &crptls::encryption::encrypted_path(path))?;
+    }
+
+    crptls::encryption::passphrase("Passphrase for the encrypted reports: ", true)
+}
+
+/// Encrypts each of the reports at `paths` (see `encryption::encrypt_file()`), with the `passphrase`.  Scratch files
+/// that were removed (i.e., those zipped into the workbook) are passed over.  If one can't be encrypted, it and the
+/// rest not yet encrypted are removed, rather than left unencrypted, and the error names it.
+fn encrypt_reports(paths: &[PathBuf], passphrase: &str) -> Result<(), Box<dyn Error>> {
+
+    let paths: Vec<&PathBuf> = paths.iter().filter(|path| path.is_file()).collect();
+
+    for (idx, path) in paths.iter().enumerate() {
+        if let Err(e) = crptls::encryption::encrypt_file(path, passphrase) {
+            for path in paths[idx..].iter() { let _ = std::fs::remove_file(path); }
+            return Err(format!("Couldn't encrypt {}: {}.  It and the {} other report(s) not yet encrypted were removed \
+                rather than left unencrypted ({} were encrypted).", path.display(), e, paths.len() - idx - 1, idx).into())
+        }
+    }
+
+    if !paths.is_empty() {
+        log::info!("  Encrypted {} report(s), each now ending in .gpg.", paths.len());
+    }
+
+    Ok(())
+}
+
+/// Imports and processes `input_file_paths`.  For specific identification, the lots for each disposal are read from
+/// the lot selections file, if one was given, or else chosen by the user in a TUI, which requires the `print_menu`
+/// feature and an interactive terminal.
+fn import_and_process(
+    input_file_paths: &[PathBuf],
+    settings: &crptls::core_functions::ImportProcessParameters,
+    timings: &mut crptls::core_functions::Timings,
+) -> Result<crptls::core_functions::ProcessedMaps, Box<dyn Error>> {
+
+    if settings.costing_method == InventoryCostingMethod::SpecificIdentification {
+
+        if let Some(path) = &settings.lot_selections {
+            return crptls::core_functions::import_and_process_final_with_lot_picker(
+                input_file_paths,
+                settings,
+                &mut crptls::specific_id::FileLotPicker::from_file(path)?,
+                timings,
+            )
+        }
+
+        if !std::io::IsTerminal::is_terminal(&std::io::stdin()) || !std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+            return Err("Specific identification (inventory costing method 9) asks which lots each disposal is drawn \
+                from, which needs an interactive terminal.  Without one, pass --lot-selections FILE (with --batch, if \
+                it's a script) to read the lots from a file of txn,lot,amount rows.".into())
+        }
+
+        #[cfg(feature = "print_menu")]
+        return crptls::core_functions::import_and_process_final_with_lot_picker(
+            input_file_paths,
+            settings,
+            &mut mytui::lot_picker_tui::TuiLotPicker { language: settings.report_language },
+            timings,
+        );
+
+        #[cfg(not(feature = "print_menu"))]
+        {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Specific identification (inventory costing method 9) requires the print_menu feature.")
+        }
+    }
+
+    crptls::core_functions::import_and_process_final(input_file_paths, settings, timings)
+}
+
+/// Processes `input_file_paths` again for each secondary home currency, with the home currency values translated
+/// into it (see `fx_translation`), and exports the same reports (in its currency) to a subdirectory of the output
+/// directory named after it.
+/// Exports a set of reports for each of the `settings`' entities, limited to its accounts, in a directory of its name.
+/// The lots were processed across every entity, so nothing is processed again.
+#[allow(clippy::too_many_arguments)]
+fn export_entity_reports(
+    args: &Cli,
+    settings: &crptls::core_functions::ImportProcessParameters,
+    should_export_all: bool,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    account_map: &HashMap<u16, Account>,
+    action_records_map: &HashMap<u32, ActionRecord>,
+    transactions_map: &HashMap<u32, Transaction>,
+    timings: &mut crptls::core_functions::Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    let Some(entities) = &settings.entities else { return Ok(()) };
+
+    for entity in entities.names(raw_acct_map).iter() {
+
+        log::info!("\nExporting the reports of entity {}...", entity);
+
+        let mut entity_settings = settings.clone();
+        entity_settings.export_path = settings.export_path.join(entity);
+        entity_settings.report_filter.accounts = Some(entities.accounts_of(entity, raw_acct_map));
+
+        if settings.should_export {
+            std::fs::create_dir_all(&entity_settings.export_path)?;
+        }
+
+        export_reports(
+            args,
+            &entity_settings,
+            should_export_all,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            timings,
+        ).map_err(|e| format!("Entity {}: {}", entity, e))?;
+    }
+
+    Ok(())
+}
+
+fn export_secondary_currency_reports(
+    args: &Cli,
+    settings: &crptls::core_functions::ImportProcessParameters,
+    input_file_paths: &[PathBuf],
+    should_export_all: bool,
+    timings: &mut crptls::core_functions::Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    if settings.secondary_currencies.is_empty() { return Ok(()) }
+
+    if settings.costing_method == InventoryCostingMethod::SpecificIdentification {
+        log::info!("  Note: No reports in secondary home currencies, as the lots would have to be chosen again for each.");
+        return Ok(())
+    }
+
+    for translation in settings.secondary_currencies.iter() {
+
+        log::info!("\nProcessing in secondary home currency {}...", translation.currency);
+
+        let mut fx_settings = settings.clone();
+        fx_settings.export_path = settings.export_path.join(&translation.currency);
+        fx_settings.translation = Some(translation.clone());
+
+        if settings.should_export {
+            std::fs::create_dir_all(&fx_settings.export_path)?;
+        }
+
+        let (
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        ) = import_and_process(input_file_paths, &fx_settings, timings)?;
+
+        export_reports(
+            args,
+            &fx_settings.translated_settings(),
+            should_export_all,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+            timings,
+        ).map_err(|e| format!("Secondary home currency {}: {}", translation.currency, e))?;
+    }
+
+    Ok(())
+}
+
+/// Exports every report called for by `settings` and the command line args, other than via the print menu.
+#[allow(clippy::too_many_arguments)]
+fn export_reports(
+    args: &Cli,
+    settings: &crptls::core_functions::ImportProcessParameters,
+    mut should_export_all: bool,
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    account_map: &HashMap<u16, Account>,
+    action_records_map: &HashMap<u32, ActionRecord>,
+    transactions_map: &HashMap<u32, Transaction>,
+    timings: &mut crptls::core_functions::Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    let print_journal_entries_only = settings.journal_entry_export;
+    if print_journal_entries_only { should_export_all = false }
+
+    //  Found first, so that an exporter that isn't found is reported before any report is written
+    let mut exporters = Exporters::default();
+
+    if args.pdf { exporters.register(export_pdf::PdfSummary) }
+    if args.html { exporters.register(export_html::HtmlDashboard) }
+    for name in args.exporters.iter() { exporters.register(ExternalExporter::find(name)?) }
+
+    if should_export_all && args.report_format == export_json::ReportFormat::Json {
+
+        let start = Instant::now();
+
+        export_json::_1_reports_to_json(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        )?;
+
+        timings.record("Export: Reports.json", start);
+
+    } else if should_export_all && args.workbook {
+
+        let start = Instant::now();
+
+        export_xlsx::_1_reports_to_xlsx(
+            settings,
+            &args.reports,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            timings,
+        )?;
+
+        timings.record("Export: Reports.xlsx", start);
+
+    } else if should_export_all {
+
+        export_all::export(
+            settings,
+            &args.reports,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            timings,
+        )?;
+    }
+
+    if should_export_all {
+
+        exporters.export(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            timings,
+        )?;
+    }
+
+    if print_journal_entries_only {
+
+        let start = Instant::now();
+
+        for year_settings in export_all::tax_year_settings(settings, transactions_map) {
+            export_je::prepare_journal_entries(
+                &year_settings,
+                raw_acct_map,
+                account_map,
+                action_records_map,
+                transactions_map,
+            )?;
+        }
+
+        timings.record("Export: J1_Journal_Entries", start);
+    }
+
+    if let Some(ticker) = &args.currency_report {
+
+        let start = Instant::now();
+
+        export_txt::_4_currency_report_to_txt(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            ticker,
+        )?;
+
+        timings.record("Export: T4_Currency_report", start);
+    }
+
+    if let Some(txn_num) = args.txn_report {
+
+        let start = Instant::now();
+
+        export_txt::_6_transaction_report_to_txt(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            txn_num,
+        )?;
+
+        timings.record("Export: T8_Txn_report", start);
+    }
+
+    if args.gains_by_exchange {
+
+        let start = Instant::now();
+
+        export_csv::_9_gains_by_exchange_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        )?;
+
+        timings.record("Export: C9_Gains_by_exchange", start);
+
+        let start = Instant::now();
+
+        export_csv::_39_exchange_totals_by_year_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        )?;
+
+        timings.record("Export: C39_Exchange_totals_by_year", start);
+    }
+
+    if let Some(software) = args.tax_software {
+
+        let start = Instant::now();
+
+        for year_settings in export_all::tax_year_settings(settings, transactions_map) {
+            export_csv::_14_tax_software_gains_to_csv(
+                &year_settings,
+                software,
+                raw_acct_map,
+                account_map,
+                action_records_map,
+                transactions_map,
+            )?;
+        }
+
+        timings.record("Export: C14_Tax_software_gains", start);
+    }
+
+    if let Some(year) = args.year_end_holdings {
+
+        let start = Instant::now();
+
+        export_csv::_15_year_end_holdings_to_csv(
+            settings,
+            year,
+            raw_acct_map,
+            account_map,
+        )?;
+
+        timings.record("Export: C15_Holdings_as_of", start);
+    }
+
+    if args.open_lots {
+
+        let start = Instant::now();
+
+        let as_of_date = match args.as_of {
+            Some(date) => date,
+            None => transactions_map.values().map(|txn| txn.date).max().ok_or("There are no transactions.")?,
+        };
+
+        export_csv::_22_open_lots_to_csv(
+            settings,
+            as_of_date,
+            raw_acct_map,
+            account_map,
+        )?;
+
+        timings.record("Export: C22_Open_lots_as_of", start);
+    }
+
+    if let Some(interval) = args.balance_history {
+
+        let start = Instant::now();
+
+        export_csv::_35_balance_history_to_csv(
+            settings,
+            interval,
+            raw_acct_map,
+            account_map,
+        )?;
+
+        export_csv::_36_highest_balances_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+        )?;
+
+        timings.record("Export: C35_Balances and C36_Highest_balances", start);
+    }
+
+    if args.asset_stats {
+
+        let start = Instant::now();
+
+        export_csv::_37_asset_statistics_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        )?;
+
+        timings.record("Export: C37_Asset_statistics", start);
+    }
+
+    if let Some(expected_balances_path) = &args.verify_balances {
+
+        let start = Instant::now();
+
+        let expected = crptls::reconciliation::import_expected_balances(expected_balances_path)?;
+
+        export_csv::_23_balance_reconciliation_to_csv(
+            settings,
+            &expected,
+            raw_acct_map,
+            account_map,
+        )?;
+
+        timings.record("Export: C23_Balance_reconciliation", start);
+    }
+
+    if let Some(sql_dump_path) = &args.sql_dump {
+
+        let start = Instant::now();
+
+        export_sql::_1_processed_maps_to_sql(
+            settings,
+            sql_dump_path,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        )?;
+
+        timings.record("Export: SQL dump", start);
+    }
+
+    if let Some(spot_prices_path) = &args.spot_prices {
+
+        let start = Instant::now();
+
+        let mut spot_prices = crptls::spot_prices::import_spot_prices(spot_prices_path)?;
+        let today = chrono::Local::now().naive_local().date();
+
+        //  Spot prices are in the (primary) home currency, so they're translated at the latest rate
+        if let Some(rate) = settings.translation.as_ref().and_then(|translation| translation.rate_on(today)) {
+            spot_prices.values_mut().for_each(|price| *price *= rate);
+        }
+
+        export_csv::_8_open_lot_tax_impact_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            &spot_prices,
+            today,
+        )?;
+
+        timings.record("Export: C8_Open_lot_tax_impact", start);
+
+        let start = Instant::now();
+
+        export_csv::_12_unrealized_gains_by_term_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            &spot_prices,
+            today,
+        )?;
+
+        timings.record("Export: C12_Unrealized_gains_by_term", start);
+
+        let start = Instant::now();
+
+        export_csv::_29_tax_loss_harvesting_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+            &spot_prices,
+            today,
+        )?;
+
+        timings.record("Export: C29_Tax_loss_harvesting", start);
+    }
+
+    if let Some(prices_path) = &args.prices {
+
+        let start = Instant::now();
+
+        let mut dated_prices = crptls::price_lookup::import_prices(prices_path)?;
+
+        //  As are historical prices, which are translated at the rates of their dates (if there are any by then)
+        if let Some(translation) = &settings.translation {
+            dated_prices.retain(|(date, _), price| match translation.rate_on(*date) {
+                Some(rate) => { *price *= rate; true }
+                None => false,
+            });
+        }
+
+        export_csv::_21_portfolio_valuation_to_csv(
+            settings,
+            raw_acct_map,
+            account_map,
+            &dated_prices,
+        )?;
+
+        timings.record("Export: C21_Portfolio_valuation", start);
+    }
+
+    Ok(())
+}
+
+fn print_run_summary(heading: &str, home_currency: &str, summary: &crptls::summary::RunSummary) {
+
+    let date_range = match summary.date_range {
+        Some((first, last)) => format!(", {} through {}", first, last),
+        None => "".to_string(),
+    };
+
+    println!("\n{}:", heading);
+    println!("  Transactions processed:  {}{}", summary.txn_count, date_range);
+    println!("  Net realized gain/loss:  {:.2} {} (short-term {:.2}; long-term {:.2})",
+        summary.realized_total(), home_currency, summary.realized_st, summary.realized_lt);
+    for year in summary.realized_by_year.iter() {
+        println!("    {}:                  {:.2} {} (short-term {:.2}; long-term {:.2})",
+            year.year, year.realized_total(), home_currency, year.realized_st, year.realized_lt);
+    }
+    println!("  Income:                  {:.2} {}", summary.income, home_currency);
+    println!("  Expenses:                {:.2} {}", summary.expense, home_currency);
+    if !summary.derivative_pnl.is_zero() {
+        println!("  Derivative PnL:          {:.2} {}", summary.derivative_pnl, home_currency);
+    }
+    println!("  Open lots:               {}", summary.open_lots);
+}
+
+/// The title of T5_Summary.txt for a run of the `input_file_paths`: their file names.
+fn summary_title(input_file_paths: &[PathBuf]) -> String {
+    input_file_paths.iter()
+        .map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// The closing holdings of the `summary`, largest cost basis first.
+fn print_holdings(summary: &crptls::summary::RunSummary) {
+
+    if summary.holdings.is_empty() { return }
+
+    println!("  Holdings (by cost basis):");
+    for holding in summary.holdings.iter() {
+        println!("    {:<22} {} {} (cost basis {:.2} in {} lot{})",
+            format!("{}:", holding.ticker),
+            holding.amount.normalize(),
+            holding.ticker,
+            holding.cost_basis,
+            holding.open_lots,
+            if holding.open_lots == 1 { "" } else { "s" },
+        );
+    }
+}
+
+/// Writes the test data of the gen-test-data command to `file` (or stdout), in the home currency and date format of
+/// the settings, so it may be imported with them.
+fn gen_test_data(rows: usize, accounts: usize, seed: u64, file: Option<&Path>, args: &Cli) -> Result<(), Box<dyn Error>> {
+
+    let cfg = setup::get_env(args)?;
+    let separator = if cfg.date_separator_is_slash { "/" } else { "-" };
+
+    let test_data = crptls::test_data::TestData {
+        rows,
+        accounts,
+        seed,
+        home_currency: cfg.home_currency.split(',').next().unwrap_or("USD").trim().to_string(),
+        date_format: cfg.date_format.clone().unwrap_or_else(|| match cfg.iso_date {
+            true => format!("%Y{}%m{}%d", separator, separator),
+            false => format!("%m{}%d{}%y", separator, separator),
+        }),
+    };
+
+    match file {
+        None => test_data.write(std::io::stdout().lock())?,
+        Some(path) => {
+            test_data.write(std::io::BufWriter::new(File::create(path)?))?;
+            log::info!("Wrote {} transactions across {} accounts to {}.", rows, accounts, path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn print_timings(args: &Cli, timings: &crptls::core_functions::Timings) {
+
+    if args.timings {
+
+        eprintln!("\nTimings:");
+        for (phase, duration) in timings.phases.iter() {
+            eprintln!("  {:<48} {:>10.3}s", phase, duration.as_secs_f64());
+        }
+        eprintln!("  {:<48} {:>10.3}s", "Total (measured phases)", timings.total().as_secs_f64());
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crptls::checksums;
+use crptls::core_functions::ImportProcessParameters;
+use crptls::csv_import_accts_txns;
+
+use cryptools::export::export_csv;
+
+
+/// The file name of the manifest, in the export directory.
+const MANIFEST_FILE_NAME: &str = "Manifest.json";
+
+/// Where `write_manifest()` writes the manifest.
+pub fn manifest_path(settings: &ImportProcessParameters) -> PathBuf {
+    [settings.export_path.clone(), PathBuf::from(settings.report_file_name(MANIFEST_FILE_NAME))].iter().collect()
+}
+
+
+/// Writes Manifest.json to the export directory, recording the program and its version, when it was run, the command
+/// line, the `environment` (the settings from environment variables, the .env file or a config file) and the main
+/// settings they led to, and the SHA-256 of each of the `input_file_paths` and of each report written in the run
+/// (listed by their path within the export directory, sorted).  Returns the manifest's path.
+pub fn write_manifest(
+    settings: &ImportProcessParameters,
+    input_file_paths: &[PathBuf],
+    environment: Value,
+) -> Result<PathBuf, Box<dyn Error>> {
+
+    let mut inputs = Vec::new();
+
+    for path in input_file_paths.iter() {
+
+        let bytes = match csv_import_accts_txns::is_stdin(path) {
+            true => csv_import_accts_txns::stdin_input().unwrap_or_default().to_vec(),
+            false => fs::read(path)?,
+        };
+
+        inputs.push(json!({
+            "path": path.display().to_string(),
+            "bytes": bytes.len(),
+            "sha256": checksums::sha256_hex(&bytes),
+        }));
+    }
+
+    let mut reports = Vec::new();
+
+    for path in export_csv::written_reports().iter() {
+
+        //  Scratch files (i.e., those zipped into the workbook) are written and then removed
+        if !path.is_file() { continue }
+
+        let bytes = fs::read(path)?;
+
+        reports.push((relative_to(path, &settings.export_path), bytes.len(), checksums::sha256_hex(&bytes)));
+    }
+
+    reports.sort();
+
+    let reports: Vec<Value> = reports.into_iter().map(|(path, bytes, sha256)| json!({
+        "path": path,
+        "bytes": bytes,
+        "sha256": sha256,
+    })).collect();
+
+    let manifest = json!({
+        "program": "cryptools",
+        "version": env!("CARGO_PKG_VERSION"),
+        "created": chrono::Local::now().to_rfc3339(),
+        "command_line": std::env::args().collect::<Vec<String>>(),
+        "settings": {
+            "home_currency": settings.home_currency,
+            "costing_method": settings.costing_method.to_string(),
+            "lk_treatment_enabled": settings.lk_treatment_enabled,
+            "lk_cutoff_date": settings.lk_cutoff_date.to_string(),
+            "long_term_days": settings.long_term_days,
+            "tax_year": settings.tax_year,
+            "export_path": settings.export_path.display().to_string(),
+        },
+        "environment": environment,
+        "inputs": inputs,
+        "reports": reports,
+    });
+
+    let path = manifest_path(settings);
+
+    export_csv::check_overwrite(settings, &path)?;
+
+    //  Moved into place only once complete, as the reports are
+    let partial = export_csv::partial_path(&path);
+    let mut wtr = BufWriter::new(File::create(&partial)?);
+    serde_json::to_writer_pretty(&mut wtr, &manifest)?;
+    wtr.flush()?;
+    drop(wtr);
+
+    fs::rename(&partial, &path)?;
+
+    Ok(path)
+}
+
+/// `path` within `directory` (with `/` separators, so the manifest reads the same on any platform), or all of `path`
+/// if it's elsewhere (i.e., the `--sql-dump` FILE).
+fn relative_to(path: &Path, directory: &Path) -> String {
+    match path.strip_prefix(directory) {
+        Ok(relative) => relative.components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<String>>()
+            .join("/"),
+        Err(_e) => path.display().to_string(),
+    }
+}
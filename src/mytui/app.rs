@@ -3,15 +3,24 @@
 
 use std::error::Error;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use crptls::transaction::{Transaction, ActionRecord};
 use crptls::account::{Account, RawAccount};
-use crptls::core_functions::ImportProcessParameters;
-use ratatui::widgets::ListState;
+use crptls::core_functions::{ImportProcessParameters, TableFormat};
+use crptls::summary::{MonthlySeries, RunSummary};
+use crptls::specific_id::{Disposal, LotAllocations};
+use crptls::corrections::{self, Correction, CorrectedField, CorrectionTarget};
+use crptls::report_language::ReportLanguage;
+use chrono::NaiveDate;
+use ratatui::widgets::{ListState, TableState};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 
-use crate::export::{export_csv, export_je, export_txt};
+use cryptools::export::{export_csv, export_je, export_txt, export_8949};
+use crate::mytui::browser::Browser;
 
-pub (crate) const REPORTS: [&'static str; 11] = [
+pub (crate) const REPORTS: [&'static str; 12] = [
     "1. CSV: Account Sums",
     "2. CSV: Account Sums (Non-zero only)",
     "3. CSV: Account Sums (Orig. basis vs like-kind basis)",
@@ -23,8 +32,26 @@ pub (crate) const REPORTS: [&'static str; 11] = [
     "9. TXT: Accounts by lot (every lot balance)",
     "10. TXT: Accounts by lot (every non-zero lot balance)",
     "11. TXT: Bookkeeping journal entries",
+    "12. CSV/TXT: Form 8949 Parts I and II (short-term and long-term)",
 ];
 
+/// The formats report `idx` (of `REPORTS`) may be exported in, other than as listed: each CSV report may instead be a
+/// text table or JSON, and the Form 8949 may be only its CSV or only its TXT.  The TXT reports have no other format.
+pub (crate) fn report_formats(idx: usize) -> &'static [TableFormat] {
+    match idx + 1 {
+        1..=7 => &[TableFormat::Csv, TableFormat::Txt, TableFormat::Json],
+        12 => &[TableFormat::Csv, TableFormat::Txt],
+        _ => &[],
+    }
+}
+
+/// Where and how a report of the list is exported, if other than as listed and into the output directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportDestination {
+    pub format: Option<TableFormat>,
+    pub dir: Option<PathBuf>,
+}
+
 pub struct StatefulList<I> {
     pub items: Vec<I>,
     pub state: ListState,
@@ -66,39 +93,218 @@ impl<T> StatefulList<T> {
 
 }
 
+/// The dashboard is the landing screen.  From there, the user moves on to selecting reports, or browses the
+/// results first: the accounts, drilling into an account's lots and a lot's movements, or the transactions, or
+/// charts of holdings, realized gains and income over time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Screen {
+    Dashboard,
+    Reports,
+    Accounts,
+    /// The lots of the account with this key.
+    Lots(u16),
+    /// The movements of the lot with this account key and lot number.
+    Movements(u16, u32),
+    Transactions,
+    /// The drill-down report of the transaction with this number.
+    Transaction(u32),
+    Charts,
+}
+
+/// How far PageUp and PageDown move the selection in a browser table.
+const PAGE_ROWS: i64 = 10;
+
+/// The fields of a transaction that may be edited, in the order they are shown.
+pub (crate) const EDITED_FIELDS: [CorrectedField; 3] = [CorrectedField::TxDate, CorrectedField::Proceeds, CorrectedField::Memo];
+
+/// The transaction being edited, with a value for each of `EDITED_FIELDS`.
+pub struct TxnEditor {
+    pub tx_num: u32,
+    pub row: usize,
+    pub original: Vec<String>,
+    pub values: Vec<String>,
+    /// The index of the field being typed into.
+    pub field: usize,
+}
+
 pub struct PrintWindow<'a> {
     pub title: &'a str,
     pub should_quit: bool,
+    pub screen: Screen,
+    pub summary: RunSummary,
     pub tasks: StatefulList<&'a str>,
     pub to_print_by_idx: Vec<usize>,
     pub to_print_by_title: Vec<&'a str>,
+    pub browser: Browser,
+    pub table: TableState,
+    /// Rows of a browser table are shown only if a cell contains this (ignoring case).
+    pub filter: String,
+    /// Whether keys typed are being added to the `filter`.
+    pub filtering: bool,
+    /// The filter and selection of each browser screen drilled out of, to be restored on going back.
+    trail: Vec<(String, Option<usize>)>,
+    pub editor: Option<TxnEditor>,
+    /// Where edits are saved (see `ImportProcessParameters::corrections`).  Without it, nothing can be edited.
+    pub corrections: Option<PathBuf>,
+    /// Whether edits were saved, so the file_to_import must be processed again (and the print menu re-opened).
+    pub should_rerun: bool,
+    /// Shown below a browser table until the next key is pressed.
+    pub message: Option<String>,
+    pub charts: MonthlySeries,
+    /// The index of the currency whose holdings are charted.
+    pub chart_ticker: usize,
+    /// The browser screen a transaction's drill-down report was opened from, to go back to.
+    txn_report_from: Screen,
+    /// The format and directory of each report of `REPORTS`, by its index.
+    pub destinations: Vec<ReportDestination>,
+    /// The directory the reports are exported to unless another is chosen (see `ImportProcessParameters::export_path`).
+    pub export_path: PathBuf,
+    /// The directory being typed for the highlighted report, while one is.
+    pub dir_entry: Option<String>,
+    /// The language of the text on screen (see `ImportProcessParameters::report_language`).
+    pub language: ReportLanguage,
 }
 
 impl<'a> PrintWindow<'a> {
 
-    pub fn new(title: &'a str) -> PrintWindow<'a> {
+    pub fn new(
+        title: &'a str,
+        summary: RunSummary,
+        browser: Browser,
+        charts: MonthlySeries,
+        corrections: Option<PathBuf>,
+        export_path: PathBuf,
+        language: ReportLanguage,
+    ) -> PrintWindow<'a> {
         let mut tasks = StatefulList::new(REPORTS.to_vec());
         tasks.state.select(Some(0));
 
         PrintWindow {
             title,
             should_quit: false,
+            screen: Screen::Dashboard,
+            summary,
             tasks,
             to_print_by_idx: Vec::with_capacity(REPORTS.len()),
             to_print_by_title: Vec::with_capacity(REPORTS.len()),
+            browser,
+            table: TableState::default(),
+            filter: String::new(),
+            filtering: false,
+            trail: Vec::new(),
+            editor: None,
+            corrections,
+            should_rerun: false,
+            message: None,
+            charts,
+            chart_ticker: 0,
+            txn_report_from: Screen::Transactions,
+            destinations: vec![ReportDestination::default(); REPORTS.len()],
+            export_path,
+            dir_entry: None,
+            language,
         }
     }
 
+    /// Opens on the transactions, with the `selected` one highlighted, once they have been processed again.
+    pub fn reopen_on_transactions(&mut self, selected: usize) {
+        self.browse(Screen::Transactions);
+        self.trail = vec![(String::new(), None)];
+        self.table.select(Some(selected.min(self.browser.transactions.len().saturating_sub(1))));
+        self.message = self.corrections.as_ref()
+            .map(|path| self.language.fill("Processed again with the corrections in {}.", &[&path.display()]));
+    }
+
     pub fn on_up(&mut self) {
-        self.tasks.select_previous();
+        if let Some(editor) = &mut self.editor {
+            editor.field = (editor.field + EDITED_FIELDS.len() - 1) % EDITED_FIELDS.len();
+            return
+        }
+        if self.screen == Screen::Reports { self.tasks.select_previous() }
+        if self.is_browsing() { self.move_selection(-1) }
     }
 
     pub fn on_down(&mut self) {
-        self.tasks.select_next();
+        if let Some(editor) = &mut self.editor {
+            editor.field = (editor.field + 1) % EDITED_FIELDS.len();
+            return
+        }
+        if self.screen == Screen::Reports { self.tasks.select_next() }
+        if self.is_browsing() { self.move_selection(1) }
+    }
+
+    pub fn on_page_up(&mut self) {
+        if self.is_browsing() { self.move_selection(-PAGE_ROWS) }
+    }
+
+    pub fn on_page_down(&mut self) {
+        if self.is_browsing() { self.move_selection(PAGE_ROWS) }
+    }
+
+    /// On the charts, Left and Right chart the holdings of another currency.
+    pub fn on_left(&mut self) {
+        let tickers = self.charts.holdings.len();
+        if self.screen == Screen::Charts && tickers > 0 { self.chart_ticker = (self.chart_ticker + tickers - 1) % tickers }
+    }
+
+    pub fn on_right(&mut self) {
+        let tickers = self.charts.holdings.len();
+        if self.screen == Screen::Charts && tickers > 0 { self.chart_ticker = (self.chart_ticker + 1) % tickers }
+    }
+
+    /// Cancels an edit, or else stops filtering, or else clears the filter, or else goes back a browser screen.
+    pub fn on_esc(&mut self) {
+        if self.screen == Screen::Charts { self.screen = Screen::Dashboard }
+        if self.screen == Screen::Reports { self.dir_entry = None }
+        if !self.is_browsing() { return }
+
+        self.message = None;
+
+        if self.editor.is_some() {
+            self.editor = None;
+        } else if self.filtering {
+            self.filtering = false;
+        } else if !self.filter.is_empty() {
+            self.filter.clear();
+            self.reset_selection();
+        } else {
+            self.back();
+        }
+    }
+
+    pub fn on_backspace(&mut self) {
+        if let Some(entry) = &mut self.dir_entry {
+            entry.pop();
+        } else if let Some(editor) = &mut self.editor {
+            editor.values[editor.field].pop();
+        } else if self.filtering {
+            self.filter.pop();
+            self.reset_selection();
+        }
     }
 
     pub fn on_key(&mut self, c: char) -> Result<(), Box<dyn Error>> {
 
+        if self.screen == Screen::Dashboard {
+            self.on_dashboard_key(c);
+            return Ok(())
+        }
+
+        if self.is_browsing() {
+            self.on_browser_key(c);
+            return Ok(())
+        }
+
+        if self.screen == Screen::Charts {
+            self.on_charts_key(c);
+            return Ok(())
+        }
+
+        if self.dir_entry.is_some() {
+            self.on_dir_entry_key(c);
+            return Ok(())
+        }
+
         match c {
 
             'q' => {
@@ -124,11 +330,304 @@ impl<'a> PrintWindow<'a> {
                 self.to_print_by_title.retain(|&x| x != selected_str );
                 self.tasks.select_previous();
             }
+            'f' => {
+                let selected = self.tasks.state.selected().unwrap();
+                let formats = report_formats(selected);
+                let destination = &mut self.destinations[selected];
+                // As listed, then each other format in turn, then as listed again
+                destination.format = match destination.format.and_then(|format| formats.iter().position(|f| *f == format)) {
+                    None => formats.first().copied(),
+                    Some(idx) => formats.get(idx + 1).copied(),
+                };
+            }
+            'o' => {
+                let selected = self.tasks.state.selected().unwrap();
+                let dir = self.destinations[selected].dir.as_ref().unwrap_or(&self.export_path);
+                self.dir_entry = Some(dir.display().to_string());
+            }
+            'b' => {
+                self.screen = Screen::Dashboard;
+            }
             _ => {}
         }
         Ok(())
     }
 
+    /// While typing the highlighted report's directory, Enter sets it (or, if blank, the output directory again).
+    fn on_dir_entry_key(&mut self, c: char) {
+
+        let entry = self.dir_entry.as_mut().unwrap();
+
+        match c {
+            '\n' => {
+                let selected = self.tasks.state.selected().unwrap();
+                let dir = PathBuf::from(entry.trim());
+                self.destinations[selected].dir = if dir.as_os_str().is_empty() || dir == self.export_path { None } else { Some(dir) };
+                self.dir_entry = None;
+            }
+            c => entry.push(c),
+        }
+    }
+
+    /// How report `idx` will be exported: as listed, or in the format chosen, and where, if not the output directory.
+    pub fn destination_label(&self, idx: usize) -> String {
+        let destination = &self.destinations[idx];
+        let format = destination.format.map(|format| self.language.fill(" as {}", &[&format.name()])).unwrap_or_default();
+        let dir = destination.dir.as_ref().map(|dir| self.language.fill(" into {}", &[&dir.display()])).unwrap_or_default();
+        format!("{}{}{}", self.language.text(REPORTS[idx]), format, dir)
+    }
+
+    /// From the dashboard, 'r' opens the report list, and a digit opens the report list with that report
+    /// (i.e., '7' for the Form 8949 report) already highlighted.  'a' and 't' browse the accounts and transactions,
+    /// and 'c' shows the charts.
+    fn on_dashboard_key(&mut self, c: char) {
+
+        match c {
+
+            'q' => {
+                self.to_print_by_idx = Vec::with_capacity(0);
+                self.should_quit = true;
+            }
+            'r' | '\n' => {
+                self.screen = Screen::Reports;
+            }
+            '1'..='9' => {
+                let idx = c.to_digit(10).unwrap() as usize - 1;
+                self.tasks.state.select(Some(idx));
+                self.screen = Screen::Reports;
+            }
+            'a' => self.browse(Screen::Accounts),
+            't' => self.browse(Screen::Transactions),
+            'c' => self.screen = Screen::Charts,
+            _ => {}
+        }
+    }
+
+    fn on_charts_key(&mut self, c: char) {
+
+        match c {
+
+            'q' => {
+                self.to_print_by_idx = Vec::with_capacity(0);
+                self.should_quit = true;
+            }
+            'b' => self.screen = Screen::Dashboard,
+            'r' => self.screen = Screen::Reports,
+            _ => {}
+        }
+    }
+
+    /// In a browser table, Enter drills into the selected account, lot, or transaction (or movement's transaction),
+    /// '/' starts filtering (until Enter or Esc),
+    /// 'b' goes back, and 'r' moves on to selecting reports.
+    fn on_browser_key(&mut self, c: char) {
+
+        self.message = None;
+
+        if self.editor.is_some() {
+            self.on_editor_key(c);
+            return
+        }
+
+        if self.filtering {
+            match c {
+                '\n' => self.filtering = false,
+                c => {
+                    self.filter.push(c);
+                    self.reset_selection();
+                }
+            }
+            return
+        }
+
+        match c {
+
+            'q' => {
+                self.to_print_by_idx = Vec::with_capacity(0);
+                self.should_quit = true;
+            }
+            '\n' => self.drill_in(),
+            '/' => self.filtering = true,
+            'e' if self.screen == Screen::Transactions => self.open_editor(),
+            'b' => self.back(),
+            'r' => {
+                self.trail.clear();
+                self.filter.clear();
+                self.screen = Screen::Reports;
+            }
+            _ => {}
+        }
+    }
+
+    /// While editing, Tab (or Up and Down) moves between fields, and Enter saves the edits.
+    fn on_editor_key(&mut self, c: char) {
+
+        let editor = self.editor.as_mut().unwrap();
+
+        match c {
+            '\t' => editor.field = (editor.field + 1) % EDITED_FIELDS.len(),
+            '\n' => {
+                if let Err(e) = self.save_edits() { self.message = Some(e.to_string()) }
+            }
+            c => editor.values[editor.field].push(c),
+        }
+    }
+
+    fn open_editor(&mut self) {
+
+        let tx_num = match self.table.selected().and_then(|i| self.visible_rows().get(i).map(|(key, _cells)| *key)) {
+            Some(tx_num) => tx_num,
+            None => return,
+        };
+
+        if self.corrections.is_none() {
+            self.message = Some(self.language.text("Transactions can only be edited with --corrections FILE, where edits are saved.").to_string());
+            return
+        }
+
+        match self.browser.editable.get(&tx_num) {
+            Some((row, values)) => {
+                self.editor = Some(TxnEditor {
+                    tx_num,
+                    row: *row,
+                    original: values.clone(),
+                    values: values.clone(),
+                    field: 0,
+                });
+            }
+            None => {
+                self.message = Some(self.language.fill("Transaction {} was generated by the program, so it can't be edited.", &[&tx_num]));
+            }
+        }
+    }
+
+    /// Saves each edited field to the corrections file, as a correction of the transaction's row, so that the
+    /// file_to_import is processed again with it.  A date may not move the transaction out of date order.
+    fn save_edits(&mut self) -> Result<(), Box<dyn Error>> {
+
+        let editor = self.editor.as_ref().unwrap();
+
+        let edits: Vec<Correction> = EDITED_FIELDS.iter().enumerate()
+            .filter(|(idx, _field)| editor.values[*idx] != editor.original[*idx])
+            .map(|(idx, field)| Correction { target: CorrectionTarget::Row(editor.row), field: *field, value: editor.values[idx].trim().to_string() })
+            .collect();
+
+        for edit in edits.iter() { edit.validate()? }
+
+        if let Some(date) = edits.iter().find_map(|edit| edit.tx_date()) {
+
+            let date_of = |tx_num: u32| self.browser.transactions.get(tx_num as usize - 1)
+                .and_then(|(_key, cells)| NaiveDate::parse_from_str(&cells[1], "%Y-%m-%d").ok());
+
+            let prior = if editor.tx_num > 1 { date_of(editor.tx_num - 1) } else { None };
+            let next = date_of(editor.tx_num + 1);
+
+            if prior.is_some_and(|prior| date < prior) || next.is_some_and(|next| date > next) {
+                return Err(format!("A txDate of {} would put transaction {} out of date order.", date, editor.tx_num).into())
+            }
+        }
+
+        if edits.is_empty() {
+            self.editor = None;
+            return Ok(())
+        }
+
+        let path = self.corrections.as_ref().unwrap();
+
+        for edit in edits.iter() { corrections::append_correction(path, edit)? }
+
+        self.editor = None;
+        self.to_print_by_idx = Vec::with_capacity(0);
+        self.should_rerun = true;
+        self.should_quit = true;
+
+        Ok(())
+    }
+
+    pub fn is_browsing(&self) -> bool {
+        !matches!(self.screen, Screen::Dashboard | Screen::Reports | Screen::Charts)
+    }
+
+    /// The rows of the browser table on screen that match the filter.
+    pub fn visible_rows(&self) -> Vec<&(u32, Vec<String>)> {
+
+        let rows = match self.screen {
+            Screen::Accounts => &self.browser.accounts,
+            Screen::Lots(acct_key) => &self.browser.lots[&acct_key],
+            Screen::Movements(acct_key, lot_num) => &self.browser.movements[&(acct_key, lot_num)],
+            Screen::Transactions => &self.browser.transactions,
+            Screen::Transaction(txn_num) => &self.browser.txn_reports[&txn_num],
+            Screen::Dashboard | Screen::Reports | Screen::Charts => return Vec::new(),
+        };
+
+        let filter = self.filter.to_lowercase();
+
+        rows.iter()
+            .filter(|(_key, cells)| filter.is_empty() || cells.iter().any(|cell| cell.to_lowercase().contains(&filter)))
+            .collect()
+    }
+
+    /// Opens a browser screen, remembering the filter and selection of the one it was opened from.
+    fn browse(&mut self, screen: Screen) {
+        self.trail.push((std::mem::take(&mut self.filter), self.table.selected()));
+        self.filtering = false;
+        self.screen = screen;
+        self.reset_selection();
+    }
+
+    fn drill_in(&mut self) {
+
+        let key = match self.table.selected().and_then(|i| self.visible_rows().get(i).map(|(key, _cells)| *key)) {
+            Some(key) => key,
+            None => return,
+        };
+
+        match self.screen {
+            Screen::Accounts => self.browse(Screen::Lots(key as u16)),
+            Screen::Lots(acct_key) => self.browse(Screen::Movements(acct_key, key)),
+            Screen::Movements(..) | Screen::Transactions => {
+                self.txn_report_from = self.screen;
+                self.browse(Screen::Transaction(key));
+            }
+            _ => {}
+        }
+    }
+
+    fn back(&mut self) {
+
+        let (filter, selected) = self.trail.pop().unwrap_or_default();
+
+        self.screen = match self.screen {
+            Screen::Movements(acct_key, _lot_num) => Screen::Lots(acct_key),
+            Screen::Lots(_acct_key) => Screen::Accounts,
+            Screen::Transaction(_txn_num) => self.txn_report_from,
+            _ => Screen::Dashboard,
+        };
+        self.filter = filter;
+        self.filtering = false;
+        self.table = TableState::default();
+        self.table.select(selected);
+    }
+
+    fn reset_selection(&mut self) {
+        let any_rows = !self.visible_rows().is_empty();
+        self.table = TableState::default();
+        self.table.select(if any_rows { Some(0) } else { None });
+    }
+
+    /// Moves the selection by `by` rows, wrapping around by single rows but stopping at either end by pages.
+    fn move_selection(&mut self, by: i64) {
+
+        let len = self.visible_rows().len() as i64;
+
+        if len == 0 { return }
+
+        let i = self.table.selected().unwrap_or(0) as i64 + by;
+        let i = if by.abs() == 1 { i.rem_euclid(len) } else { i.clamp(0, len - 1) };
+
+        self.table.select(Some(i as usize));
+    }
+
     fn change_vecs_to_chrono_order(vec: &mut Vec<usize>, strvec: &mut Vec<&str>) {
 
         let length = vec.len();
@@ -153,18 +652,29 @@ pub fn export(
     transactions_map: &HashMap<u32, Transaction>,
 ) -> Result<(), Box<dyn Error>> {
 
-    let reports = REPORTS.to_vec();
-
-    println!("Attempting to export:");
+    println!("{}", app.language.text("Attempting to export:"));
 
     if app.to_print_by_idx.is_empty() {
-        println!("  None selected.");
+        println!("{}", app.language.text("  None selected."));
         return Ok(())
     }
 
     for report_idx in app.to_print_by_idx.iter() {
 
-        println!("    {}", reports[*report_idx]);
+        println!("    {}", app.destination_label(*report_idx));
+
+        let destination = &app.destinations[*report_idx];
+        let mut report_settings = settings.clone();
+
+        if let Some(dir) = &destination.dir {
+            std::fs::create_dir_all(dir)?;
+            report_settings.export_path = dir.clone();
+        }
+        if let Some(format) = destination.format {
+            report_settings.table_format = format;
+        }
+
+        let settings = &report_settings;
 
         match report_idx + 1 {
 
@@ -173,21 +683,21 @@ pub fn export(
                     &settings,
                     &raw_acct_map,
                     &account_map
-                );
+                )?;
             }
             2 => {
                 export_csv::_2_account_sums_nonzero_to_csv(
                     &settings,
                     &raw_acct_map,
                     &account_map,
-                );
+                )?;
             }
             3 => {
                 export_csv::_3_account_sums_to_csv_with_orig_basis(
                     &settings,
                     &raw_acct_map,
                     &account_map
-                );
+                )?;
             }
             4 => {
                 export_csv::_4_transaction_mvmt_detail_to_csv(
@@ -250,21 +760,101 @@ pub fn export(
                 )?;
             }
             11 => {
-                if !settings.lk_treatment_enabled {
-                    export_je::prepare_non_lk_journal_entries(
-                        &settings,
-                        &raw_acct_map,
-                        &account_map,
-                        &action_records_map,
-                        &transactions_map,
+                export_je::prepare_journal_entries(
+                    &settings,
+                    &raw_acct_map,
+                    &account_map,
+                    &action_records_map,
+                    &transactions_map,
+                )?;
+            }
+            12 => {
+                if destination.format != Some(TableFormat::Txt) {
+                    export_8949::form_8949_to_csv(
+                        settings,
+                        raw_acct_map,
+                        account_map,
+                        action_records_map,
+                        transactions_map,
+                    )?;
+                }
+                if destination.format != Some(TableFormat::Csv) {
+                    export_8949::form_8949_to_txt(
+                        settings,
+                        raw_acct_map,
+                        account_map,
+                        action_records_map,
+                        transactions_map,
                     )?;
-                } else {
-                    println!("       *Skipping non-like-kind report: {}", reports[*report_idx]);
                 }
             }
             _ => {}
         }
     }
-    println!("Successfully exported.");
+    export_csv::commit_written_reports()?;
+
+    println!("{}", app.language.text("Successfully exported."));
     Ok(())
-}
\ No newline at end of file
+}
+
+/// The state of the specific identification lot picker, for a single `Disposal`.  Each open lot is either not
+/// drawn from, or drawn from for as much as it holds (or as much of the `Disposal` as remains unallocated).
+pub struct LotPickerWindow<'a> {
+    pub disposal: &'a Disposal,
+    pub lots: TableState,
+    pub allocations: Vec<Decimal>,
+    pub message: Option<String>,
+    pub language: ReportLanguage,
+}
+
+impl<'a> LotPickerWindow<'a> {
+
+    pub fn new(disposal: &'a Disposal, language: ReportLanguage) -> LotPickerWindow<'a> {
+        let mut lots = TableState::default();
+        lots.select(Some(0));
+
+        LotPickerWindow {
+            disposal,
+            lots,
+            allocations: vec![dec!(0); disposal.open_lots.len()],
+            message: None,
+            language,
+        }
+    }
+
+    pub fn remaining(&self) -> Decimal {
+        self.disposal.amount - self.allocations.iter().sum::<Decimal>()
+    }
+
+    pub fn on_up(&mut self) {
+        let i = self.lots.selected().unwrap_or(0);
+        self.lots.select(Some(if i == 0 { self.allocations.len() - 1 } else { i - 1 }));
+    }
+
+    pub fn on_down(&mut self) {
+        let i = self.lots.selected().unwrap_or(0);
+        self.lots.select(Some(if i + 1 >= self.allocations.len() { 0 } else { i + 1 }));
+    }
+
+    pub fn allocate_selected(&mut self) {
+        let i = self.lots.selected().unwrap_or(0);
+        let available = self.remaining() + self.allocations[i];
+        self.allocations[i] = available.min(self.disposal.open_lots[i].balance);
+        self.message = None;
+    }
+
+    pub fn clear_selected(&mut self) {
+        let i = self.lots.selected().unwrap_or(0);
+        self.allocations[i] = dec!(0);
+        self.message = None;
+    }
+
+    /// The chosen amounts by lot number, for returning from `LotPicker::allocate()`.
+    pub fn allocations_by_lot_number(&self) -> LotAllocations {
+        self.disposal.open_lots.iter()
+            .zip(self.allocations.iter())
+            .filter(|(_lot, amt)| **amt > dec!(0))
+            .map(|(lot, amt)| (lot.lot_number, *amt))
+            .collect()
+    }
+}
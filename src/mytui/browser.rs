@@ -0,0 +1,180 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crptls::transaction::{Transaction, ActionRecord, Polarity, TxKind, TxType};
+use crptls::account::{Account, RawAccount, Term};
+use crptls::core_functions::ImportProcessParameters;
+use crptls::decimal_utils::round_d128_1e2;
+
+use cryptools::export::export_txt;
+
+
+/// The rows of a browser table, each keyed by what it drills into (an account key or a lot number) or by its
+/// transaction number.
+pub type Rows = Vec<(u32, Vec<String>)>;
+
+/// Every table the report browser can show, prepared up front from the processed maps, so browsing needs nothing
+/// else.  Values are like-kind values, as on the dashboard.
+pub struct Browser {
+    pub accounts: Rows,
+    /// By account key.
+    pub lots: HashMap<u16, Rows>,
+    /// By account key and lot number.
+    pub movements: HashMap<(u16, u32), Rows>,
+    /// Each transaction's realized gain/loss (by term), income and expense.
+    pub transactions: Rows,
+    /// By transaction number, the row of the CSV Input File of each transaction that came from one, and the
+    /// values of its `EDITED_FIELDS`, as a correction would give them.
+    pub editable: HashMap<u32, (usize, Vec<String>)>,
+    /// By transaction number, the lines of its drill-down report (as in T8_Txn_report_<TXN>.txt), one per row.
+    pub txn_reports: HashMap<u32, Rows>,
+}
+
+impl Browser {
+
+    pub fn from_maps(
+        settings: &ImportProcessParameters,
+        raw_acct_map: &HashMap<u16, RawAccount>,
+        acct_map: &HashMap<u16, Account>,
+        ars: &HashMap<u32, ActionRecord>,
+        txns_map: &HashMap<u32, Transaction>,
+    ) -> Result<Browser, Box<dyn Error>> {
+
+        let mut browser = Browser {
+            accounts: Vec::with_capacity(acct_map.len()),
+            lots: HashMap::new(),
+            movements: HashMap::new(),
+            transactions: Vec::with_capacity(txns_map.len()),
+            editable: HashMap::new(),
+            txn_reports: HashMap::new(),
+        };
+
+        for j in 1..=acct_map.len() {
+
+            let acct = acct_map.get(&(j as u16)).unwrap();
+            let raw_acct = raw_acct_map.get(&acct.raw_key).unwrap();
+            let lots = acct.list_of_lots.borrow();
+
+            browser.accounts.push((j as u32, vec![
+                j.to_string(),
+                raw_acct.name.clone(),
+                raw_acct.ticker.clone(),
+                raw_acct.margin_string(),
+                acct.get_sum_of_amts_in_lots().to_string(),
+                format!("{:.2}", acct.get_sum_of_lk_basis_in_lots()),
+                format!("{} of {}", acct.get_num_of_nonzero_lots(), lots.len()),
+            ]));
+
+            let mut lot_rows: Rows = Vec::with_capacity(lots.len());
+
+            for lot in lots.iter() {
+
+                let mvmts = lot.movements.borrow();
+
+                lot_rows.push((lot.lot_number, vec![
+                    lot.id(),
+                    lot.date_of_first_mvmt_in_lot.to_string(),
+                    lot.date_for_basis_purposes.to_string(),
+                    lot.get_sum_of_amts_in_lot().to_string(),
+                    format!("{:.2}", lot.get_sum_of_lk_basis_in_lot()),
+                    mvmts.len().to_string(),
+                ]));
+
+                let mvmt_rows: Rows = mvmts.iter().map(|mvmt| {
+
+                    let txn = txns_map.get(&mvmt.transaction_key).unwrap();
+                    let is_outgoing = ars.get(&mvmt.action_record_key).unwrap().direction() == Polarity::Outgoing;
+
+                    (mvmt.transaction_key, vec![
+                        mvmt.transaction_key.to_string(),
+                        mvmt.date.to_string(),
+                        mvmt.amount.to_string(),
+                        format!("{:.2}", mvmt.cost_basis_lk.get()),
+                        format!("{:.2}", mvmt.proceeds_lk.get()),
+                        format!("{:.2}", mvmt.get_lk_gain_or_loss()),
+                        if is_outgoing {
+                            mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days).abbr_string()
+                        } else {
+                            String::new()
+                        },
+                        txn.user_memo.clone(),
+                    ])
+                }).collect();
+
+                browser.movements.insert((j as u16, lot.lot_number), mvmt_rows);
+            }
+
+            browser.lots.insert(j as u16, lot_rows);
+        }
+
+        for num in 1..=txns_map.len() {
+
+            let txn = txns_map.get(&(num as u32)).unwrap();
+            let tx_type = txn.transaction_type(ars, raw_acct_map, acct_map)?;
+            let mvmts = txn.get_outgoing_exchange_and_flow_mvmts(&settings.home_currency, ars, raw_acct_map, acct_map, txns_map)?;
+
+            let (mut st, mut lt, mut income, mut expense) = (dec!(0), dec!(0), dec!(0), dec!(0));
+
+            let is_pnl = txn.kind == TxKind::DerivativePnl;
+
+            // As in `RunSummary::from_maps()`
+            for mvmt in mvmts.iter() {
+
+                if tx_type == TxType::Flow && mvmt.amount > dec!(0) {
+                    if !is_pnl { income -= mvmt.proceeds_lk.get() }
+                    continue
+                }
+
+                if tx_type == TxType::Flow && !is_pnl {
+                    expense -= mvmt.proceeds_lk.get();
+                }
+
+                match mvmt.get_term(acct_map, ars, txns_map, settings.long_term_days) {
+                    Term::ST => st += mvmt.get_lk_gain_or_loss(),
+                    Term::LT => lt += mvmt.get_lk_gain_or_loss(),
+                }
+            }
+
+            let memo = if txn.user_memo.is_empty() {
+                txn.get_auto_memo(ars, raw_acct_map, acct_map, &settings.home_currency)?
+            } else {
+                txn.user_memo.clone()
+            };
+
+            if let Some(row) = txn.source_row {
+                browser.editable.insert(num as u32, (row, vec![
+                    txn.date.format("%Y-%m-%d").to_string(),
+                    txn.proceeds.to_string(),
+                    txn.user_memo.clone(),
+                ]));
+            }
+
+            let amount = |amt: Decimal| if amt.is_zero() { String::new() } else { format!("{:.2}", amt) };
+
+            browser.transactions.push((num as u32, vec![
+                num.to_string(),
+                txn.date.to_string(),
+                tx_type.to_string(),
+                format!("{:.2}", round_d128_1e2(&txn.proceeds)),
+                amount(st),
+                amount(lt),
+                amount(income),
+                amount(expense),
+                memo,
+            ]));
+        }
+
+        browser.txn_reports = export_txt::transaction_reports(settings, raw_acct_map, acct_map, ars, txns_map)?
+            .into_iter()
+            .map(|(num, lines)| (num, lines.into_iter().enumerate().map(|(idx, line)| (idx as u32, vec![line])).collect()))
+            .collect();
+
+        Ok(browser)
+    }
+}
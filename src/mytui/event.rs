@@ -1,94 +0,0 @@
-// Copyright (c) 2017-2023, scoobybejesus
-// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
-
-// TODO: cite source?
-
-use std::io;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
-
-
-use termion::input::TermRead;
-use termion::event::Key;
-
-
-#[derive(Debug, Clone, Copy)]
-pub struct Config {
-    pub exit_key: Key,
-    pub tick_rate: Duration,
-}
-
-impl Default for Config {
-    fn default() -> Config {
-        Config {
-            exit_key: Key::Char('q'),
-            tick_rate: Duration::from_millis(250),
-        }
-    }
-}
-
-pub enum Event<I> {
-    Input(I),
-    Tick,
-}
-
-/// A small event handler that wraps termion input and tick events. Each event
-/// type is handled in its own thread and returned to a common `Receiver`
-pub struct Events {
-    rx: mpsc::Receiver<Event<Key>>,
-    _input_handle: thread::JoinHandle<()>,
-    _tick_handle: thread::JoinHandle<()>,
-}
-
-impl Events {
-
-    pub fn _new() -> Events {
-        Events::with_config(Config::default())
-    }
-
-    pub fn with_config(config: Config) -> Events {
-
-        let (tx, rx) = mpsc::channel();
-        let _input_handle = {
-            let tx = tx.clone();
-            thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    match evt {
-                        Ok(key) => {
-                            if let Err(_) = tx.send(Event::Input(key)) {
-                                return;
-                            }
-                            if key == config.exit_key {
-                                return;
-                            }
-                        }
-                        Err(_) => {}
-                    }
-                }
-            })
-        };
-
-        let _tick_handle = {
-            let tx = tx.clone();
-            thread::spawn(move || {
-                let tx = tx.clone();
-                loop {
-                    tx.send(Event::Tick).unwrap();
-                    thread::sleep(config.tick_rate);
-                }
-            })
-        };
-
-        Events {
-            rx,
-            _input_handle,
-            _tick_handle,
-        }
-    }
-
-    pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
-        self.rx.recv()
-    }
-}
\ No newline at end of file
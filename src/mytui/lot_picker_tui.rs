@@ -0,0 +1,72 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io;
+use std::time::Duration;
+use std::error::Error;
+
+use ratatui::Terminal;
+use ratatui::backend::TermionBackend;
+use rust_decimal_macros::dec;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::input::TermRead;
+use termion::event::Key;
+
+use crptls::report_language::ReportLanguage;
+use crptls::specific_id::{Disposal, LotAllocations, LotPicker};
+
+use crate::mytui::ui as ui;
+use crate::mytui::app as app;
+
+
+/// Presents each disposal's open lots in a TUI so the user can choose which lots it is drawn from.  The terminal
+/// is only taken over while a disposal is on screen, so processing output in between is printed as usual.
+pub(crate) struct TuiLotPicker {
+    pub(crate) language: ReportLanguage,
+}
+
+impl LotPicker for TuiLotPicker {
+
+    fn allocate(&mut self, disposal: &Disposal) -> Result<LotAllocations, Box<dyn Error>> {
+
+        let stdout = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+        let backend = TermionBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+        terminal.hide_cursor()?;
+
+        let mut window = app::LotPickerWindow::new(disposal, self.language);
+
+        // Keys are read here directly, rather than on an input thread, so that none outlives the disposal.
+        let mut keys = io::stdin().keys();
+
+        loop {
+
+            ui::draw_lot_picker(&mut terminal, &mut window)?;
+
+            let key = match keys.next() {
+                Some(key) => key?,
+                None => return Err("Input ended before lots were chosen.".into()),
+            };
+
+            match key {
+                Key::Up => window.on_up(),
+                Key::Down => window.on_down(),
+                Key::Right | Key::Char('a') => window.allocate_selected(),
+                Key::Left | Key::Char('c') => window.clear_selected(),
+                Key::Char('\n') => {
+                    if window.remaining() == dec!(0) { break }
+                    window.message = Some(self.language.fill("{} {} is not yet allocated.", &[&window.remaining(), &disposal.ticker]));
+                }
+                Key::Char('q') => return Err(format!("Lot selection for txn {} was cancelled.", disposal.tx_number).into()),
+                _ => {}
+            }
+        }
+
+        // As with the print menu, this lets the native terminal come back before anything else is printed
+        std::mem::drop(terminal);
+        std::thread::sleep(Duration::from_millis(10));
+
+        Ok(window.allocations_by_lot_number())
+    }
+}
@@ -2,6 +2,7 @@
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
 pub mod print_menu_tui;
+pub mod lot_picker_tui;
 mod app;
-mod ui;
-mod event;
\ No newline at end of file
+mod browser;
+mod ui;
\ No newline at end of file
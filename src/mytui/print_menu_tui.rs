@@ -10,25 +10,43 @@ use ratatui::Terminal;
 use ratatui::backend::TermionBackend;
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
-use termion::input::MouseTerminal;
+use termion::input::{MouseTerminal, TermRead};
 use termion::event::Key;
 
 use crptls::transaction::{Transaction, ActionRecord};
 use crptls::account::{Account, RawAccount};
 use crptls::core_functions::ImportProcessParameters;
+use crptls::summary::{MonthlySeries, RunSummary};
 
-use crate::mytui::event::{Events, Event, Config};
 use crate::mytui::ui as ui;
 use crate::mytui::app as app;
+use crate::mytui::browser::Browser;
 
 
+/// Presents the dashboard, browser and report list, and exports the reports chosen.  If a transaction was edited
+/// instead, this returns the index of the selected transaction, for re-opening on it once the file_to_import has
+/// been processed again (with `reopen_at`).
 pub (crate) fn print_menu_tui(
     settings: &ImportProcessParameters,
     raw_acct_map: &HashMap<u16, RawAccount>,
     account_map: &HashMap<u16, Account>,
     action_records_map: &HashMap<u32, ActionRecord>,
     transactions_map: &HashMap<u32, Transaction>,
-) -> Result<(), Box<dyn Error>> {
+    reopen_at: Option<usize>,
+) -> Result<Option<usize>, Box<dyn Error>> {
+
+    let summary = RunSummary::from_maps(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        account_map,
+        action_records_map,
+        transactions_map,
+    )?;
+
+    let browser = Browser::from_maps(settings, raw_acct_map, account_map, action_records_map, transactions_map)?;
+    let charts = MonthlySeries::from_maps(&settings.home_currency, raw_acct_map, account_map, action_records_map, transactions_map)?;
 
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = MouseTerminal::from(stdout);
@@ -37,20 +55,29 @@ pub (crate) fn print_menu_tui(
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
 
-    let mut app = app::PrintWindow::new("Reports");
+    let mut app = app::PrintWindow::new(
+        "Reports",
+        summary,
+        browser,
+        charts,
+        settings.corrections.clone(),
+        settings.export_path.clone(),
+        settings.report_language,
+    );
+
+    if let Some(selected) = reopen_at { app.reopen_on_transactions(selected) }
 
-    let events = Events::with_config(Config {
-        tick_rate: Duration::from_millis(250u64),
-        ..Config::default()
-    });
+    // Keys are read here directly, rather than on an input thread, so that none outlives the print menu to take the
+    // keys meant for the lot picker or the re-opened print menu (or stops at a 'q' typed into a filter or an edit).
+    let mut keys = io::stdin().keys();
 
     loop {
 
         ui::draw(&mut terminal, &mut app)?;
 
-        if let Event::Input(key) = events.next()? {
+        if let Some(key) = keys.next() {
 
-            match key {
+            match key? {
 
                 Key::Char(c) => {
                     app.on_key(c)?;
@@ -61,6 +88,24 @@ pub (crate) fn print_menu_tui(
                 Key::Down => {
                     app.on_down();
                 }
+                Key::Left => {
+                    app.on_left();
+                }
+                Key::Right => {
+                    app.on_right();
+                }
+                Key::PageUp => {
+                    app.on_page_up();
+                }
+                Key::PageDown => {
+                    app.on_page_down();
+                }
+                Key::Esc => {
+                    app.on_esc();
+                }
+                Key::Backspace => {
+                    app.on_backspace();
+                }
                 _ => {}
             }
         }
@@ -74,6 +119,12 @@ pub (crate) fn print_menu_tui(
     std::mem::drop(terminal);
     std::thread::sleep(Duration::from_millis(10));
 
+    if app.should_rerun {
+        println!("{}", settings.report_language.fill("Saved the edits to {}. Processing again...",
+            &[&settings.corrections.as_ref().unwrap().display()]));
+        return Ok(Some(app.table.selected().unwrap_or(0)))
+    }
+
     app::export(
         &app,
         &settings,
@@ -83,5 +134,5 @@ pub (crate) fn print_menu_tui(
         &transactions_map
     )?;
 
-    Ok(())
+    Ok(None)
 }
\ No newline at end of file
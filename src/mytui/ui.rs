@@ -3,119 +3,650 @@
 
 use std::error::Error;
 
-use ::ratatui::Terminal;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+
+use ::ratatui::{Frame, Terminal};
 use ::ratatui::style::{Color, Modifier, Style};
-use ::ratatui::text::{Text, Span, Line};
-use ratatui::widgets::{Wrap, ListItem};
+use ::ratatui::text::{Span, Line};
+use ratatui::widgets::{Wrap, ListItem, Row, Table, Axis, BarChart, Chart, Dataset, GraphType};
+use ::ratatui::symbols::Marker;
 use ::ratatui::widgets::{Block, Borders, Paragraph, List};
 use ::ratatui::layout::{Layout, Constraint, Direction};
 use ::ratatui::backend::Backend;
 
-use crate::mytui::app::{PrintWindow, REPORTS};
+use crate::mytui::app::{PrintWindow, LotPickerWindow, Screen, REPORTS, EDITED_FIELDS};
 
 
 pub fn draw<B: Backend>(terminal: &mut Terminal<B>, app: &mut PrintWindow) -> Result<(), Box<dyn Error>> {
 
     terminal.draw(|f| {
 
-        let instructions = vec![
-            Line::from(vec![Span::raw("")]),
+        match app.screen {
+            Screen::Dashboard => draw_dashboard(f, app),
+            Screen::Reports => draw_reports(f, app),
+            Screen::Charts => draw_charts(f, app),
+            _ => draw_browser(f, app),
+        }
+    })?;
 
-            Line::from(vec![
-                Span::raw("  Press '"),
-                Span::styled("x", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-                Span::raw("' to add the selected report to the list of reports to print/export."),
-            ]),
+    Ok(())
+}
+
+fn draw_dashboard(f: &mut Frame, app: &mut PrintWindow) {
+
+    let summary = &app.summary;
+    let lang = app.language;
+    let padded = |label: &str| format!("  {:34}", lang.text(label));
+    let label = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+
+    fn gain_style(amt: Decimal) -> Style {
+        if amt < dec!(0) { Style::default().fg(Color::Red) } else { Style::default().fg(Color::Green) }
+    }
+
+    let figures = vec![
+        Line::from(vec![Span::raw("")]),
+        Line::from(vec![
+            Span::styled(padded("Realized gain/loss (short-term):"), label),
+            Span::styled(format!("{:.2}", summary.realized_st), gain_style(summary.realized_st)),
+        ]),
+        Line::from(vec![
+            Span::styled(padded("Realized gain/loss (long-term):"), label),
+            Span::styled(format!("{:.2}", summary.realized_lt), gain_style(summary.realized_lt)),
+        ]),
+        Line::from(vec![
+            Span::styled(padded("Realized gain/loss (total):"), label),
+            Span::styled(format!("{:.2}", summary.realized_total()), gain_style(summary.realized_total())),
+        ]),
+        Line::from(vec![Span::raw("")]),
+        Line::from(vec![
+            Span::styled(padded("Income:"), label),
+            Span::raw(format!("{:.2}", summary.income)),
+        ]),
+        Line::from(vec![
+            Span::styled(padded("Expenses:"), label),
+            Span::raw(format!("{:.2}", summary.expense)),
+        ]),
+        Line::from(vec![
+            Span::styled(padded("Open lots:"), label),
+            Span::raw(summary.open_lots.to_string()),
+        ]),
+    ];
+
+    let instructions = vec![
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("r", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' (or Enter) to choose reports to print/export, or a number ('1'-'9') to jump to that report.")),
+        ]),
+
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("a", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to browse the accounts (and their lots and movements), or '")),
+            Span::styled("t", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to browse the transactions, or '")),
+            Span::styled("c", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to chart holdings, realized gains and income over time.")),
+        ]),
+
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to quit without printing.")),
+        ]),
+    ];
+
+    // Only the largest holdings fit comfortably alongside the headline figures
+    let shown_holdings = summary.holdings.len().min(10);
+
+    let top_level_chunks = Layout::default()
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(figures.len() as u16 + 2),
+            Constraint::Length(shown_holdings as u16 + 3),
+            Constraint::Length(instructions.len() as u16 + 2),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(f.size());
+
+    let level_2_chunks = Layout::default()
+        .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
+        .direction(Direction::Horizontal)
+        .split(top_level_chunks[1]);
+
+    let pg1 = Paragraph::new(figures)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                lang.text("Summary"),
+                Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        );
+    f.render_widget(pg1, level_2_chunks[1]);
+
+    let level_2_chunks = Layout::default()
+        .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
+        .direction(Direction::Horizontal)
+        .split(top_level_chunks[2]);
+
+    let holding_rows: Vec<Row> = summary.holdings.iter().take(shown_holdings).map(|h| {
+        Row::new(vec![
+            h.ticker.clone(),
+            h.amount.to_string(),
+            format!("{:.2}", h.cost_basis),
+            h.open_lots.to_string(),
+        ])
+    }).collect();
+
+    let widths = [
+        Constraint::Percentage(20),
+        Constraint::Percentage(35),
+        Constraint::Percentage(30),
+        Constraint::Percentage(15),
+    ];
+
+    let holdings = Table::new(holding_rows)
+        .header(Row::new(["Ticker", "Amount", "Cost basis", "Open lots"].map(|header| lang.text(header))).style(label))
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                lang.text("Top currencies by cost basis"),
+                Style::default().fg(Color::White).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .widths(&widths);
+    f.render_widget(holdings, level_2_chunks[1]);
+
+    let pg2 = Paragraph::new(instructions)
+        .block(Block::default()
+            .borders(Borders::NONE)
+            .title(Span::styled(
+                lang.text("Instructions"),
+                Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .wrap(Wrap {trim: false});
+    f.render_widget(pg2, top_level_chunks[3]);
+}
+
+fn draw_reports(f: &mut Frame, app: &mut PrintWindow) {
+
+    let lang = app.language;
+
+    let instructions = vec![
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("x", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to add the selected report to the list of reports to print/export.")),
+        ]),
+
+        Line::from(vec![Span::raw("")]),
+        
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("d", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to delete the selected report from the list of reports to print/export.")),
+        ]),
+        
+        Line::from(vec![Span::raw("")]),
+        
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("f", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to change the selected report's format (CSV, TXT or JSON), or '")),
+            Span::styled("o", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to type the directory it's exported to.")),
+        ]),
+
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("p", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to print/export the selected reports.")),
+        ]),
+        
+        Line::from(vec![Span::raw("")]),
+        
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("b", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to go back to the dashboard.")),
+        ]),
+
+        Line::from(vec![Span::raw("")]),
+
+        Line::from(vec![
+            Span::raw(lang.text("  Press '")),
+            Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::raw(lang.text("' to quit without printing.")),
+        ]),
+    ];
+
+    let rpts_to_prnt: Vec<String> = app.to_print_by_idx.iter().map(|idx| app.destination_label(*idx)).collect();
+
+    let top_level_chunks = Layout::default()
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(instructions.len() as u16 + 2),
+            Constraint::Length(REPORTS.len() as u16 + 2),
+            Constraint::Length(rpts_to_prnt.len() as u16 + 2),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(f.size());
+
+    let pg1 = Paragraph::new(instructions)
+        .block(Block::default()
+            .borders(Borders::NONE)
+            .title(Span::styled(
+                lang.text("Instructions"),
+                Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .wrap(Wrap {trim: false});
+    f.render_widget(pg1, top_level_chunks[1]);
+
+    let level_2_chunks = Layout::default()
+        .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
+        .direction(Direction::Horizontal)
+        .split(top_level_chunks[2]);
+
+    let report_list_items: Vec<_> = (0..app.tasks.items.len()).map(|idx| ListItem::new(app.destination_label(idx))).collect();
+
+    let items = List::new(report_list_items)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                lang.text("Reports available for exporting"),
+                Style::default().fg(Color::White).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">");
+    f.render_stateful_widget(items, level_2_chunks[1], &mut app.tasks.state);
+
+    let level_2_chunks = Layout::default()
+        .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
+        .direction(Direction::Horizontal)
+        .split(top_level_chunks[3]);
+
+    let rpts_to_prnt: Vec<_> = rpts_to_prnt.into_iter().map(ListItem::new).collect();
+
+    let to_print = List::new(rpts_to_prnt)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                lang.text("Reports to be exported"),
+                Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        );
+    f.render_widget(to_print, level_2_chunks[1]);
+
+    if let Some(entry) = &app.dir_entry {
+        let prompt = Paragraph::new(Line::from(vec![
+            Span::styled(lang.text("  Directory (Enter to set, Esc to cancel): "), Style::default().fg(Color::Cyan)),
+            Span::raw(entry.as_str()),
+        ]));
+        f.render_widget(prompt, top_level_chunks[5]);
+    }
+}
+
+/// The browser table on screen: its title, and its columns' headers and widths (as percentages).
+fn browser_columns(app: &PrintWindow) -> (String, Vec<(&'static str, u16)>) {
+
+    let account_name = |acct_key: u16| app.browser.accounts[acct_key as usize - 1].1[1].clone();
+    let lang = app.language;
+
+    match app.screen {
+        Screen::Lots(acct_key) => (
+            lang.fill("Lots of account {} ({}) - Enter to view a lot's movements", &[&acct_key, &account_name(acct_key)]),
+            vec![("Lot", 10), ("Acquired", 18), ("Basis date", 18), ("Balance", 22), ("Cost basis", 22), ("Movements", 12)],
+        ),
+        Screen::Movements(acct_key, lot_num) => (
+            lang.fill("Movements of lot {} of account {} ({}) - Enter to view a movement's transaction", &[&lot_num, &acct_key,
+                &account_name(acct_key)]),
+            vec![("Txn", 6), ("Date", 11), ("Amount", 16), ("Cost basis", 12), ("Proceeds", 12), ("Gain/loss", 12),
+                ("Term", 6), ("Memo", 25)],
+        ),
+        Screen::Transaction(txn_num) => (
+            lang.fill("Transaction {} - its movements, their effect on each lot, and its journal entry", &[&txn_num]),
+            vec![("", 100)],
+        ),
+        Screen::Transactions => (
+            lang.text("Transactions (gain/loss by term, income and expense) - Enter to view a transaction").to_string(),
+            vec![("Txn", 6), ("Date", 11), ("Type", 9), ("Proceeds", 11), ("ST gain/loss", 11), ("LT gain/loss", 11),
+                ("Income", 10), ("Expense", 10), ("Memo", 21)],
+        ),
+        _ => (
+            lang.text("Accounts - Enter to view an account's lots").to_string(),
+            vec![("#", 5), ("Account", 25), ("Ticker", 10), ("Margin", 10), ("Balance", 20), ("Cost basis", 18),
+                ("Open lots", 12)],
+        ),
+    }
+}
+
+fn draw_browser(f: &mut Frame, app: &mut PrintWindow) {
+
+    let lang = app.language;
+    let label = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+    let key = |k: &'static str, color: Color| Span::styled(k, Style::default().fg(color).add_modifier(Modifier::BOLD));
+
+    let (title, columns) = browser_columns(app);
+
+    let rows: Vec<Row> = app.visible_rows().iter().map(|(_key, cells)| Row::new(cells.clone())).collect();
+    let shown = rows.len();
+
+    let widths: Vec<Constraint> = columns.iter().map(|(_header, width)| Constraint::Percentage(*width)).collect();
 
+    let table = Table::new(rows)
+        .header(Row::new(columns.iter().map(|(header, _width)| lang.text(header)).collect::<Vec<_>>()).style(label))
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                title,
+                Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">")
+        .widths(&widths);
+
+    let filter_line = if app.filtering || !app.filter.is_empty() {
+        Line::from(vec![
+            Span::styled(lang.text("  Filter: "), label),
+            Span::raw(format!("{}{}", app.filter, if app.filtering { "_" } else { "" })),
+            Span::raw(lang.fill("  ({} rows)", &[&shown])),
+        ])
+    } else {
+        Line::from(vec![Span::raw(lang.fill("  {} rows", &[&shown]))])
+    };
+
+    let mut instructions = vec![
+        filter_line,
+        Line::from(vec![
+            Span::raw(lang.text("  Up/Down and PageUp/PageDown to scroll, Enter to drill in, '")), key("/", Color::Cyan),
+            Span::raw(lang.text("' to filter (Esc to clear), '")), key("b", Color::LightMagenta),
+            Span::raw(lang.text("' (or Esc) to go back, '")), key("r", Color::Green),
+            Span::raw(lang.text("' to choose reports, or '")), key("q", Color::Red),
+            Span::raw(lang.text("' to quit without printing.")),
+        ]),
+    ];
+
+    if app.screen == Screen::Transactions {
+        instructions.push(Line::from(vec![
+            Span::raw(lang.text("  Press '")), key("e", Color::Yellow),
+            Span::raw(lang.text("' to edit the selected transaction's date, proceeds or memo, and process the file again.")),
+        ]));
+    }
+
+    if let Some(editor) = &app.editor {
+
+        instructions = vec![Line::from(vec![
+            Span::styled(lang.fill("  Editing transaction {} (row {} of the file)", &[&editor.tx_num, &editor.row]), label),
+        ])];
+
+        for (idx, field) in EDITED_FIELDS.iter().enumerate() {
+            let is_current = idx == editor.field;
+            instructions.push(Line::from(vec![
+                Span::styled(format!("  {:>10}: ", field.to_string()), label),
+                Span::styled(
+                    format!("{}{}", editor.values[idx], if is_current { "_" } else { "" }),
+                    if is_current { Style::default().fg(Color::Yellow) } else { Style::default() },
+                ),
+            ]));
+        }
+
+        instructions.push(Line::from(vec![
+            Span::raw(lang.text("  Tab (or Up/Down) to move between fields, Enter to save the edits and process the file again, \
+                or Esc to cancel.  The txDate is YYYY-MM-DD.")),
+        ]));
+    }
+
+    if let Some(message) = &app.message {
+        instructions.push(Line::from(vec![Span::styled(format!("  {}", message), Style::default().fg(Color::Yellow))]));
+    }
+
+    let top_level_chunks = Layout::default()
+        .constraints([
+            Constraint::Min(5),
+            Constraint::Length(instructions.len() as u16 + 2),
+        ].as_ref())
+        .split(f.size());
+
+    f.render_stateful_widget(table, top_level_chunks[0], &mut app.table);
+
+    let pg1 = Paragraph::new(instructions)
+        .block(Block::default()
+            .borders(Borders::NONE)
+            .title(Span::styled(
+                lang.text("Instructions"),
+                Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .wrap(Wrap {trim: false});
+    f.render_widget(pg1, top_level_chunks[1]);
+}
+
+/// A line chart of a monthly series, with the first, middle and last months along the x-axis.
+fn monthly_line_chart<'a>(title: String, months: &[(i32, u32)], values: &[Decimal], points: &'a [(f64, f64)], color: Color) -> Chart<'a> {
+
+    let low = values.iter().copied().fold(dec!(0), Decimal::min);
+    let high = values.iter().copied().fold(dec!(0), Decimal::max);
+    let high = if high == low { low + dec!(1) } else { high };
+
+    let month_label = |idx: usize| Span::raw(months.get(idx).map(|(y, m)| format!("{}-{:02}", y, m)).unwrap_or_default());
+
+    Chart::new(vec![
+        Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(color))
+            .data(points)
+    ])
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(title, Style::default().fg(Color::White).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)))
+        )
+        .x_axis(Axis::default()
+            .bounds([0.0, (months.len().max(2) - 1) as f64])
+            .labels(vec![month_label(0), month_label(months.len() / 2), month_label(months.len().saturating_sub(1))])
+        )
+        .y_axis(Axis::default()
+            .bounds([low.to_f64().unwrap_or(0.0), high.to_f64().unwrap_or(1.0)])
+            .labels(vec![Span::raw(format!("{:.2}", low)), Span::raw(format!("{:.2}", high))])
+        )
+}
+
+fn draw_charts(f: &mut Frame, app: &mut PrintWindow) {
+
+    let charts = &app.charts;
+    let months = &charts.months;
+    let lang = app.language;
+    let key = |k: &'static str, color: Color| Span::styled(k, Style::default().fg(color).add_modifier(Modifier::BOLD));
+    let points = |values: &[Decimal]| -> Vec<(f64, f64)> {
+        values.iter().enumerate().map(|(idx, value)| (idx as f64, value.to_f64().unwrap_or(0.0))).collect()
+    };
+
+    let top_level_chunks = Layout::default()
+        .constraints([
+            Constraint::Percentage(35),
+            Constraint::Percentage(30),
+            Constraint::Min(8),
+            Constraint::Length(2),
+        ].as_ref())
+        .split(f.size());
+
+    match charts.holdings.get(app.chart_ticker) {
+        Some((ticker, held)) => {
+            let held_points = points(held);
+            let title = lang.fill("Holdings of {} at each month end ({} of {}, Left/Right for another currency)",
+                &[ticker, &(app.chart_ticker + 1), &charts.holdings.len()]);
+            f.render_widget(monthly_line_chart(title, months, held, &held_points, Color::Cyan), top_level_chunks[0]);
+        }
+        None => {
+            f.render_widget(Paragraph::new(lang.text("  No holdings.")).block(Block::default().borders(Borders::ALL)), top_level_chunks[0]);
+        }
+    }
+
+    let realized_points = points(&charts.cumulative_realized);
+    let realized_color = if charts.cumulative_realized.last().copied().unwrap_or(dec!(0)) < dec!(0) { Color::Red } else { Color::Green };
+    f.render_widget(
+        monthly_line_chart(lang.text("Cumulative realized gain/loss").to_string(), months, &charts.cumulative_realized, &realized_points, realized_color),
+        top_level_chunks[1],
+    );
+
+    // Only the latest months whose bars fit are shown
+    let (bar_width, bar_gap) = (7, 1);
+    let fit = (top_level_chunks[2].width.saturating_sub(2) / (bar_width + bar_gap)) as usize;
+    let first_shown = months.len().saturating_sub(fit);
+
+    let labels: Vec<String> = months[first_shown..].iter().map(|(y, m)| format!("{:02}-{:02}", y % 100, m)).collect();
+    let bars: Vec<(&str, u64)> = labels.iter().zip(charts.income[first_shown..].iter())
+        .map(|(label, income)| (label.as_str(), income.round().to_u64().unwrap_or(0)))
+        .collect();
+
+    let income = BarChart::default()
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(
+                lang.text("Income by month (rounded)"),
+                Style::default().fg(Color::White).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+            ))
+        )
+        .data(bars.as_slice())
+        .bar_width(bar_width)
+        .bar_gap(bar_gap)
+        .bar_style(Style::default().fg(Color::Yellow))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Yellow));
+    f.render_widget(income, top_level_chunks[2]);
+
+    let instructions = Paragraph::new(vec![Line::from(vec![
+        Span::raw(lang.text("  Left/Right to chart another currency's holdings, '")), key("b", Color::LightMagenta),
+        Span::raw(lang.text("' (or Esc) to go back, '")), key("r", Color::Green),
+        Span::raw(lang.text("' to choose reports, or '")), key("q", Color::Red),
+        Span::raw(lang.text("' to quit without printing.")),
+    ])]).wrap(Wrap {trim: false});
+    f.render_widget(instructions, top_level_chunks[3]);
+}
+
+pub fn draw_lot_picker<B: Backend>(terminal: &mut Terminal<B>, window: &mut LotPickerWindow) -> Result<(), Box<dyn Error>> {
+
+    terminal.draw(|f| {
+
+        let disposal = window.disposal;
+        let lang = window.language;
+        let label = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+        let key = |k: &'static str, color: Color| Span::styled(k, Style::default().fg(color).add_modifier(Modifier::BOLD));
+
+        let heading = vec![
             Line::from(vec![Span::raw("")]),
-            
             Line::from(vec![
-                Span::raw("  Press '"),
-                Span::styled("d", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                Span::raw("' to delete the selected report from the list of reports to print/export."),
+                Span::styled(lang.text("  Txn: "), label),
+                Span::raw(lang.fill("{} on {} ({})", &[&disposal.tx_number, &disposal.date, &disposal.memo])),
             ]),
-            
-            Line::from(vec![Span::raw("")]),
-            
             Line::from(vec![
-                Span::raw("  Press '"),
-                Span::styled("p", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                Span::raw("' to print/export the selected reports."),
+                Span::styled(lang.text("  Disposing of: "), label),
+                Span::raw(lang.fill("{} {} from {}, for proceeds of {}", &[&disposal.amount, &disposal.ticker,
+                    &disposal.account_name, &format!("{:.2}", disposal.proceeds)])),
             ]),
-            
-            Line::from(vec![Span::raw("")]),
-            
             Line::from(vec![
-                Span::raw("  Press '"),
-                Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                Span::raw("' to quit without printing."),
+                Span::styled(lang.text("  Remaining to allocate: "), label),
+                Span::styled(window.remaining().to_string(), Style::default().fg(
+                    if window.remaining() == dec!(0) { Color::Green } else { Color::Yellow }
+                )),
             ]),
         ];
 
-        let rpts_to_prnt = app.to_print_by_title.iter().map(|&rpt_to_prnt| {
-            Text::styled(
-                format!("{}", rpt_to_prnt),
-                Style::default().fg(Color::White)
-            )
-        });
+        let instructions = vec![
+            Line::from(vec![Span::raw("")]),
+            Line::from(vec![
+                Span::raw(lang.text("  Press '")), key("a", Color::Cyan),
+                Span::raw(lang.text("' (or Right) to draw as much as possible from the selected lot, or '")), key("c", Color::Yellow),
+                Span::raw(lang.text("' (or Left) to clear it.")),
+            ]),
+            Line::from(vec![
+                Span::raw(lang.text("  Press Enter to confirm once the whole disposal is allocated, or '")), key("q", Color::Red),
+                Span::raw(lang.text("' to stop processing.")),
+            ]),
+            Line::from(vec![Span::raw(format!("  {}", window.message.clone().unwrap_or_default()))]),
+        ];
 
         let top_level_chunks = Layout::default()
             .constraints([
-                Constraint::Length(1),
+                Constraint::Length(heading.len() as u16 + 2),
+                Constraint::Min(5),
                 Constraint::Length(instructions.len() as u16 + 2),
-                Constraint::Length(REPORTS.len() as u16 + 2),
-                Constraint::Length(rpts_to_prnt.len() as u16 + 2),
-                Constraint::Length(1),
             ].as_ref())
             .split(f.size());
 
-        let pg1 = Paragraph::new(instructions)
+        let pg1 = Paragraph::new(heading)
             .block(Block::default()
-                .borders(Borders::NONE)
+                .borders(Borders::ALL)
                 .title(Span::styled(
-                    "Instructions",
+                    lang.text("Specific identification"),
                     Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
                 ))
-            )
-            .wrap(Wrap {trim: false});
-        f.render_widget(pg1, top_level_chunks[1]);
+            );
+        f.render_widget(pg1, top_level_chunks[0]);
 
-        let level_2_chunks = Layout::default()
-            .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
-            .direction(Direction::Horizontal)
-            .split(top_level_chunks[2]);
+        let lot_rows: Vec<Row> = disposal.open_lots.iter().zip(window.allocations.iter()).map(|(lot, amt)| {
+            Row::new(vec![
+                lot.lot_number.to_string(),
+                lot.basis_date.to_string(),
+                lang.text(&lot.term.abbr_string()).to_string(),
+                lot.balance.to_string(),
+                format!("{:.2}", lot.cost_basis),
+                format!("{:.2}", lot.unrealized_gain_or_loss),
+                if *amt > dec!(0) { amt.to_string() } else { String::new() },
+            ])
+        }).collect();
 
-        let report_list_items: Vec<_> = app.tasks.items.iter().map(|i| ListItem::new(*i)).collect();
+        let widths = [
+            Constraint::Percentage(6),
+            Constraint::Percentage(14),
+            Constraint::Percentage(6),
+            Constraint::Percentage(19),
+            Constraint::Percentage(17),
+            Constraint::Percentage(19),
+            Constraint::Percentage(19),
+        ];
 
-        let items = List::new(report_list_items)
+        let lots = Table::new(lot_rows)
+            .header(Row::new(["Lot", "Basis date", "Term", "Balance", "Cost basis", "Gain/loss", "Draw"].map(|header| lang.text(header))).style(label))
             .block(Block::default()
                 .borders(Borders::ALL)
                 .title(Span::styled(
-                    "Reports available for exporting",
+                    lang.text("Open lots (gain/loss if the whole balance were disposed of at this price)"),
                     Style::default().fg(Color::White).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
                 ))
             )
             .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
-            .highlight_symbol(">");
-        f.render_stateful_widget(items, level_2_chunks[1], &mut app.tasks.state);
-
-        let level_2_chunks = Layout::default()
-            .constraints([Constraint::Percentage(10), Constraint::Percentage(80),Constraint::Percentage(10),].as_ref())
-            .direction(Direction::Horizontal)
-            .split(top_level_chunks[3]);
-
-        let rpts_to_prnt: Vec<_> = app.to_print_by_title.iter().map(|i| ListItem::new(*i)).collect();
+            .highlight_symbol(">")
+            .widths(&widths);
+        f.render_stateful_widget(lots, top_level_chunks[1], &mut window.lots);
 
-        let to_print = List::new(rpts_to_prnt)
+        let pg2 = Paragraph::new(instructions)
             .block(Block::default()
-                .borders(Borders::ALL)
+                .borders(Borders::NONE)
                 .title(Span::styled(
-                    "Reports to be exported",
-                    Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
+                    lang.text("Instructions"),
+                    Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED)
                 ))
-            );
-        f.render_widget(to_print, level_2_chunks[1]);
+            )
+            .wrap(Wrap {trim: false});
+        f.render_widget(pg2, top_level_chunks[2]);
     })?;
 
     Ok(())
-}
\ No newline at end of file
+}
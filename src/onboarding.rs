@@ -0,0 +1,132 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! `cryptools init`: for a new user without a CSV Input File, asks which exchanges and wallets hold which currencies,
+//! and writes a template input file with their account columns and a few sample rows, plus a config file to match.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::template::{Template, TemplateAccount};
+
+use crate::{batch, cli_user_choices, setup, Cli};
+
+
+/// The file name of the template, in the directory given to `init`.
+const TEMPLATE_FILE_NAME: &str = "cryptools_input.csv";
+/// The file name of the config file, which is read from the directory `cryptools` is run from (see `setup`).
+const CONFIG_FILE_NAME: &str = "cryptools.toml";
+
+/// The txDate format of the template, matching the `iso_date` of its config file.
+const TEMPLATE_DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub(crate) fn init(dir: Option<&Path>, args: &Cli) -> Result<(), Box<dyn Error>> {
+
+    if args.batch {
+        batch::exit_for_missing_input("init", "init asks which exchanges and wallets hold which currencies.")
+    }
+
+    let dir = dir.unwrap_or(Path::new("."));
+    let template_path = dir.join(TEMPLATE_FILE_NAME);
+    let config_path = dir.join(CONFIG_FILE_NAME);
+
+    for path in [&template_path, &config_path] {
+        if path.exists() {
+            return Err(format!("{} already exists, so init would overwrite it. Choose another directory.", path.display()).into())
+        }
+    }
+
+    let cfg = setup::get_env(args)?;
+    let default_currency = cfg.home_currency.split(',').next().unwrap_or("USD").trim().to_uppercase();
+
+    println!("\nThis will write a CSV Input File to start from (and a config file to match), with a column for each \
+        currency held at each of your exchanges and wallets.\n");
+
+    println!("What is your home currency? [Default: {}]", default_currency);
+    let home_currency = match read_answer()?.to_uppercase() {
+        answer if answer.is_empty() => default_currency,
+        answer => answer,
+    };
+
+    let mut accounts: Vec<TemplateAccount> = Vec::new();
+
+    loop {
+
+        match accounts.is_empty() {
+            true => println!("\nName an exchange or wallet you hold cryptocurrency at (i.e., Coinbase or Ledger): "),
+            false => println!("\nName another exchange or wallet, or leave blank when done: "),
+        }
+
+        let name = read_answer()?;
+
+        if name.is_empty() {
+            if accounts.is_empty() { println!("The template needs at least one exchange or wallet."); continue }
+            break
+        }
+
+        println!("Which currencies do you hold at {}? Enter their tickers, separated by commas (i.e., BTC,ETH): ", name);
+
+        let tickers: Vec<String> = read_answer()?.split(',')
+            .map(|ticker| ticker.trim().to_uppercase())
+            .filter(|ticker| !ticker.is_empty())
+            .collect();
+
+        if tickers.is_empty() { println!("   No currencies, so {} is left out.", name) }
+
+        for ticker in tickers {
+            if ticker == home_currency {
+                println!("   {} is the home currency, which the template's Bank account holds, so it's left out.", ticker);
+                continue
+            }
+            if !accounts.iter().any(|acct| acct.name == name && acct.ticker == ticker) {
+                accounts.push(TemplateAccount { name: name.clone(), ticker, is_margin: false });
+            }
+        }
+    }
+
+    println!();
+    let costing_method = cli_user_choices::choose_inventory_costing_method(cfg.inv_costing_method.clone())?;
+    let choice = (1..=11).find(|choice| InventoryCostingMethod::from_choice(&choice.to_string()).as_ref() == Some(&costing_method))
+        .ok_or("The costing method has no number.")?;
+
+    if !dir.as_os_str().is_empty() { fs::create_dir_all(dir)? }
+
+    let template = Template { home_currency: home_currency.clone(), date_format: TEMPLATE_DATE_FORMAT.to_string(), accounts };
+    let mut csv = Vec::new();
+    template.write(&mut csv)?;
+    fs::write(&template_path, csv)?;
+
+    let mut settings = toml::value::Table::new();
+    settings.insert("home_currency".to_string(), toml::Value::String(home_currency));
+    settings.insert("iso_date".to_string(), toml::Value::Boolean(true));
+    settings.insert("inv_costing_method".to_string(), toml::Value::String(choice.to_string()));
+
+    fs::write(&config_path, format!(
+        "## Written by `cryptools init` for {}.  Any other setting of examples/cryptools.toml may be added.\n\n{}",
+        TEMPLATE_FILE_NAME,
+        toml::to_string(&toml::Value::Table(settings))?,
+    ))?;
+
+    println!("\nWrote {} ({} account columns, with sample rows) and {}.", template_path.display(),
+        template.accounts.len() + 1, config_path.display());
+    println!("Replace the sample rows with your own transactions, then run `cryptools {}` from {}, where {} is read.",
+        TEMPLATE_FILE_NAME, dir.display(), CONFIG_FILE_NAME);
+
+    Ok(())
+}
+
+/// A line from stdin, trimmed.
+fn read_answer() -> Result<String, Box<dyn Error>> {
+
+    let mut input = String::new();
+    let stdin = io::stdin();
+
+    if stdin.lock().read_line(&mut input)? == 0 {
+        return Err("The input ended before init was done, so nothing was written.".into())
+    }
+
+    Ok(input.trim().to_string())
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::path::PathBuf;
+use std::error::Error;
+use std::fs;
+
+use crptls::core_functions::{ImportProcessParameters, Timings};
+use crptls::summary::RunSummary;
+
+use cryptools::export::{export_csv, export_txt};
+
+use crate::run_result::{self, ExitCode};
+
+
+/// Parses a `--portfolio` arg of the form `NAME=FILE`.  The name becomes a subdirectory of the output directory,
+/// so it can't be blank or contain a path separator.
+pub(crate) fn parse_portfolio(arg: &str) -> Result<(String, PathBuf), String> {
+
+    let (name, file) = match arg.split_once('=') {
+        Some((name, file)) if !file.is_empty() => (name.trim(), file),
+        _ => return Err(format!("`{}` must be in the form NAME=FILE", arg)),
+    };
+
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!("`{}` is not a valid portfolio name (it is used as a directory name)", name))
+    }
+
+    Ok((name.to_string(), PathBuf::from(file)))
+}
+
+/// Processes each `--portfolio` on its own, from its own freshly imported maps, so that nothing about one
+/// portfolio (its `Lot`s, basis, etc.) can affect another.  Each portfolio's reports and summary are exported
+/// to a subdirectory of the output directory named after the portfolio.
+pub(crate) fn process_portfolios(
+    args: &super::Cli,
+    settings: &ImportProcessParameters,
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    let mut names: Vec<&String> = Vec::new();
+
+    for (name, _file) in args.portfolios.iter() {
+        if names.contains(&name) {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: More than one --portfolio is named {}.", name))
+        }
+        names.push(name);
+    }
+
+    let mut summaries: Vec<(String, RunSummary)> = Vec::with_capacity(args.portfolios.len());
+
+    for (name, file) in args.portfolios.iter() {
+
+        log::info!("\nProcessing portfolio {} ({})...", name, file.display());
+
+        let mut portfolio_settings = settings.clone();
+        portfolio_settings.export_path = settings.export_path.join(name);
+
+        if settings.should_export {
+            fs::create_dir_all(&portfolio_settings.export_path)?;
+        }
+
+        let (
+            raw_acct_map,
+            account_map,
+            action_records_map,
+            transactions_map,
+        ) = super::import_and_process(std::slice::from_ref(file), &portfolio_settings, timings)
+            .map_err(|e| format!("Portfolio {}: {}", name, e))?;
+
+        super::export_reports(
+            args,
+            &portfolio_settings,
+            portfolio_settings.should_export,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+            timings,
+        ).map_err(|e| format!("Portfolio {}: {}", name, e))?;
+
+        super::export_secondary_currency_reports(
+            args,
+            &portfolio_settings,
+            std::slice::from_ref(file),
+            portfolio_settings.should_export,
+            timings,
+        ).map_err(|e| format!("Portfolio {}: {}", name, e))?;
+
+        let summary = RunSummary::from_maps(
+            &settings.home_currency,
+            settings.long_term_days,
+            settings.fiscal_year_start_month,
+            &raw_acct_map,
+            &account_map,
+            &action_records_map,
+            &transactions_map,
+        )?;
+
+        if !args.quiet {
+            super::print_run_summary(&format!("Summary of portfolio {}", name), &settings.home_currency, &summary);
+            super::print_holdings(&summary);
+        }
+
+        if settings.should_export {
+            export_txt::_5_run_summary_to_txt(&portfolio_settings, name, &summary)?;
+        }
+
+        summaries.push((name.clone(), summary));
+    }
+
+    if args.combined_summary && settings.should_export {
+        export_csv::_11_portfolio_summaries_to_csv(settings, &summaries)?;
+    }
+
+    Ok(())
+}
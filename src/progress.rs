@@ -0,0 +1,67 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crptls::core_functions::{Progress, Timings};
+
+
+const BAR_WIDTH: usize = 30;
+
+/// Redraws the count of rows read for a phase of unknown length every this many rows.
+const COUNT_STEP: usize = 1000;
+
+/// Shows the progress of a run on stderr: a bar for each phase of known length (like lot processing), a running
+/// count for one of unknown length (like reading the CSV Input File), and a line with the duration of each phase
+/// once it's complete (like each export).
+pub struct ProgressBar {
+    /// The phase being drawn, and the percent (or count) last drawn for it.
+    drawn: Option<(String, usize)>,
+    start: Instant,
+}
+
+impl ProgressBar {
+
+    /// A `Timings` that shows its progress, if stderr is a terminal (so that it doesn't fill up a log file).
+    pub fn timings(show: bool) -> Timings {
+        if show && io::IsTerminal::is_terminal(&io::stderr()) {
+            Timings::with_progress(Box::new(ProgressBar { drawn: None, start: Instant::now() }))
+        } else {
+            Timings::default()
+        }
+    }
+
+    fn clear_line(&mut self) {
+        if self.drawn.take().is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+impl Progress for ProgressBar {
+
+    fn update(&mut self, phase: &str, done: usize, total: usize) {
+
+        //  A phase of unknown length has a `total` of zero
+        let step = (done * 100).checked_div(total).unwrap_or(done / COUNT_STEP);
+
+        if let Some((drawn_phase, drawn_step)) = &self.drawn {
+            if drawn_phase == phase && *drawn_step == step { return }
+        }
+
+        match (done * BAR_WIDTH).checked_div(total) {
+            Some(filled) => eprint!("\r\x1b[K  {:<48} [{}{}] {:>3}%",
+                phase, "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), step),
+            None => eprint!("\r\x1b[K  {:<48} {:>10} rows", phase, step * COUNT_STEP),
+        }
+
+        io::stderr().flush().ok();
+        self.drawn = Some((phase.to_string(), step));
+    }
+
+    fn finished(&mut self, phase: &str, duration: Duration) {
+        self.clear_line();
+        eprintln!("  {:<48} {:>10.3}s  (total {:.1}s)", phase, duration.as_secs_f64(), self.start.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//! How a run ended, for automation around the program: its exit code, which tells the ways of failing apart, and
+//! run_result.json, which is written at the end of every run with the warnings logged and the reports written.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::json;
+
+use crptls::input_error::InputError;
+
+use cryptools::export::export_csv;
+
+
+/// The file name of the run result, in the export directory (unless `--run-result` names another file).
+const RUN_RESULT_FILE_NAME: &str = "run_result.json";
+
+/// The exit code of a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ExitCode {
+    /// Every report was written, without a warning.
+    Success = 0,
+    /// Processing (or exporting) failed.
+    ProcessingError = 1,
+    /// The command line, a setting or a settings file couldn't be parsed (or, with `--batch`, an input that would be
+    /// asked for is missing).  This is also the exit code of clap's own usage errors.
+    ParseError = 2,
+    /// A CSV Input File (or a file checked against the run, i.e., the --chart-of-accounts file) isn't valid.
+    ValidationError = 3,
+    /// Every report was written, but warnings were logged along the way.
+    Warnings = 4,
+}
+
+impl ExitCode {
+
+    fn status(&self) -> &'static str {
+        match self {
+            ExitCode::Success => "success",
+            ExitCode::ProcessingError => "processing_error",
+            ExitCode::ParseError => "parse_error",
+            ExitCode::ValidationError => "validation_error",
+            ExitCode::Warnings => "success_with_warnings",
+        }
+    }
+}
+
+/// An error parsing the command line or the settings, which ends the run with `ExitCode::ParseError`.
+#[derive(Debug)]
+pub(crate) struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ParseError {}
+
+/// The `error` (of parsing the command line or settings) as a `ParseError`.
+pub(crate) fn parse_error(error: Box<dyn Error>) -> Box<dyn Error> {
+    Box::new(ParseError(error.to_string()))
+}
+
+/// Where the run result is written: the `--run-result` file, if given, or else in the export directory (or in the
+/// output directory given, until it's settled, or in the current directory, until the command line is parsed).
+struct Destination {
+    path: Option<PathBuf>,
+    is_given: bool,
+    /// For a command that writes no reports, or once it's been written.
+    is_skipped: bool,
+}
+
+static DESTINATION: Mutex<Destination> = Mutex::new(Destination { path: None, is_given: false, is_skipped: false });
+
+/// Every warning logged in the run, in order.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Writes the run result to `path` (from `--run-result`) instead.
+pub(crate) fn set_path(path: &Path) {
+    let mut destination = DESTINATION.lock().unwrap();
+    destination.path = Some(path.to_path_buf());
+    destination.is_given = true;
+}
+
+/// Writes the run result to the `export_dir`, unless `--run-result` named its file.
+pub(crate) fn set_export_dir(export_dir: &Path) {
+    let mut destination = DESTINATION.lock().unwrap();
+    if !destination.is_given {
+        destination.path = Some(export_dir.join(RUN_RESULT_FILE_NAME));
+    }
+}
+
+/// Writes no run result, as for a command that only prints to stdout (i.e., `completions`).
+pub(crate) fn skip() {
+    DESTINATION.lock().unwrap().is_skipped = true;
+}
+
+/// Notes a warning logged (see `logging`), for the run result.
+pub(crate) fn record_warning(message: &str) {
+    WARNINGS.lock().unwrap().push(message.trim().to_string());
+}
+
+/// The exit code of a run that ended with `error`.
+pub(crate) fn exit_code_of(error: &(dyn Error + 'static)) -> ExitCode {
+    if error.is::<ParseError>() || error.is::<clap::Error>() {
+        ExitCode::ParseError
+    } else if error.is::<InputError>() {
+        ExitCode::ValidationError
+    } else {
+        ExitCode::ProcessingError
+    }
+}
+
+/// The exit code of a run that succeeded: `Success`, unless any warning was logged.
+pub(crate) fn success_code() -> ExitCode {
+    if WARNINGS.lock().unwrap().is_empty() { ExitCode::Success } else { ExitCode::Warnings }
+}
+
+/// Prints the `message` (i.e., `FATAL: ...`), then ends the run with `code`.
+pub(crate) fn fatal(code: ExitCode, message: &str) -> ! {
+    println!("{}", message);
+    exit(code, Some(message))
+}
+
+/// Ends the run with `code`, having written the run result with the `error` (if any).  A failed run discards any
+/// report not yet moved into place (see `export_csv::create_report_file()`).
+pub(crate) fn exit(code: ExitCode, error: Option<&str>) -> ! {
+
+    if !matches!(code, ExitCode::Success | ExitCode::Warnings) {
+        export_csv::discard_pending_reports();
+    }
+
+    if let Err(e) = write(code, error) {
+        eprintln!("Couldn't write the run result: {}", e);
+    }
+
+    std::process::exit(code as i32)
+}
+
+/// Writes the run result: the exit code and its status, the `error` (if any), every warning logged, and the path of
+/// each report written (that's been moved into place).
+fn write(code: ExitCode, error: Option<&str>) -> Result<(), Box<dyn Error>> {
+
+    let path = {
+        let mut destination = DESTINATION.lock().unwrap();
+        if destination.is_skipped { return Ok(()) }
+        destination.is_skipped = true;
+        destination.path.clone().unwrap_or_else(|| PathBuf::from(RUN_RESULT_FILE_NAME))
+    };
+
+    let outputs: Vec<String> = export_csv::written_reports().iter()
+        .filter(|path| path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+
+    let result = json!({
+        "exit_code": code as i32,
+        "status": code.status(),
+        "error": error,
+        "warnings": *WARNINGS.lock().unwrap(),
+        "outputs": outputs,
+    });
+
+    fs::write(&path, serde_json::to_string_pretty(&result)? + "\n")?;
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use cryptools::json_api::{self, JsonSettings};
+
+
+/// The largest request body accepted (a CSV Input File, as JSON).
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// The largest request line and headers accepted, together.
+const MAX_HEADER_BYTES: u64 = 64 * 1024;
+
+/// How long a connection may go without sending (or taking) any of the request (or response) before it's dropped.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An HTTP request, as much of it as is needed.
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// The body of a `POST /reports`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReportsRequest {
+    /// The CSV Input File.
+    csv: String,
+    #[serde(default)]
+    settings: JsonSettings,
+}
+
+/// Listens on `address` (i.e., `127.0.0.1:8080`), answering each `POST /reports` (whose JSON body is the `csv` and any
+/// `settings`, i.e., `{"csv": "...", "settings": {"costing_method": 3}}`) with the reports as JSON, as in Reports.json.
+/// Connections are handled by a worker thread per available CPU, so a slow client or a large CSV Input File doesn't
+/// hold up the rest, until the program is stopped.
+pub fn serve(address: &str) -> Result<(), Box<dyn Error>> {
+
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Couldn't listen on {}: {}", address, e))?;
+
+    println!("Listening on http://{} for POST /reports. Stop with Ctrl-C.", listener.local_addr()?);
+
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let (sender, receiver) = mpsc::channel::<TcpStream>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..workers {
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            //  The lock is only held while waiting for the next connection
+            let stream = match receiver.lock().unwrap().recv() {
+                Ok(stream) => stream,
+                Err(_disconnected) => break,
+            };
+            handle_connection(stream);
+        });
+    }
+
+    for stream in listener.incoming() {
+
+        match stream {
+            Ok(stream) => sender.send(stream)?,
+            Err(e) => println!("  Couldn't accept a connection: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the request on `stream` and writes the response.
+fn handle_connection(mut stream: TcpStream) {
+
+    if let Err(e) = stream.set_read_timeout(Some(TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(TIMEOUT))) {
+        println!("  Couldn't set the connection's timeouts: {}", e);
+        return
+    }
+
+    let (status, body) = match read_request(&stream) {
+        Ok(Some(request)) => respond(request),
+        Ok(None) => return,
+        Err(e) if timed_out(e.as_ref()) => {
+            (408, error_json(&format!("The request took over {} seconds to arrive.", TIMEOUT.as_secs())))
+        }
+        Err(e) => (400, error_json(&e.to_string())),
+    };
+
+    println!("  Answered with {}.", status);
+
+    if let Err(e) = write_response(&mut stream, status, &body) {
+        println!("  Couldn't write the response: {}", e);
+    }
+}
+
+/// Whether reading the request failed because `TIMEOUT` passed (which is `WouldBlock` on some platforms).
+fn timed_out(error: &(dyn Error + 'static)) -> bool {
+    error.downcast_ref::<io::Error>()
+        .is_some_and(|e| matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut))
+}
+
+/// The status and JSON body answering the `request`.
+fn respond(request: Request) -> (u16, String) {
+
+    if request.path.split('?').next() != Some("/reports") {
+        return (404, error_json("Not found. POST the CSV Input File to /reports."))
+    }
+
+    if request.method != "POST" {
+        return (405, error_json("Only POST is allowed."))
+    }
+
+    let request: ReportsRequest = match serde_json::from_slice(&request.body) {
+        Ok(request) => request,
+        Err(e) => return (400, error_json(&format!("Invalid request: {}", e))),
+    };
+
+    //  A panic while processing one request shouldn't stop the server
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        json_api::run(request.csv.into_bytes(), request.settings).map_err(|e| e.to_string())
+    }));
+
+    match result {
+        Ok(Ok(reports)) => (200, reports.to_string()),
+        Ok(Err(e)) => (422, error_json(&e)),
+        Err(_panic) => (500, error_json("The CSV Input File couldn't be processed.")),
+    }
+}
+
+/// The HTTP/1.1 request on `stream`, or `None` if it closed without one.  The request line and headers may be at most
+/// `MAX_HEADER_BYTES`, and the body `MAX_BODY_BYTES`.
+fn read_request(stream: &TcpStream) -> Result<Option<Request>, Box<dyn Error>> {
+
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEADER_BYTES);
+    let headers_too_large = || format!("The request line and headers are over {} bytes.", MAX_HEADER_BYTES);
+
+    let mut request_line = String::new();
+    if head.read_line(&mut request_line)? == 0 { return Ok(None) }
+    if !request_line.ends_with('\n') { return Err(headers_too_large().into()) }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err("Malformed request line.".into()),
+    };
+
+    println!("\n{} {}", method, path);
+
+    let mut content_length: usize = 0;
+
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header)? == 0 {
+            if head.limit() == 0 { return Err(headers_too_large().into()) }
+            break
+        }
+        if !header.ends_with('\n') && head.limit() == 0 { return Err(headers_too_large().into()) }
+
+        let header = header.trim_end();
+        if header.is_empty() { break }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_e| "Invalid Content-Length.")?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!("The request body is over {} bytes.", MAX_BODY_BYTES).into())
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Some(Request { method, path, body }))
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &str) -> std::io::Result<()> {
+
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    };
+
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body)?;
+
+    stream.flush()
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
@@ -1,104 +1,630 @@
 // Copyright (c) 2017-2023, scoobybejesus
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 use std::env;
-use std::fs::File;
+use std::ffi::OsString;
+use std::fs::{self, File};
 
 use chrono::NaiveDate;
+use chrono::format::{Item, StrftimeItems};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use dotenv;
 
-use crptls::core_functions::ImportProcessParameters;
+use crptls::core_functions::{ImportProcessParameters, JournalAccounts, TableFormat};
 use crptls::costing_method::InventoryCostingMethod;
+use crptls::csv_dialect::CsvDelimiter;
+use crptls::csv_import_accts_txns::{self, is_stdin};
+use crptls::disposal_rounding::DisposalRounding;
+use crptls::dust_sweeps::{self, DustQuantities};
+use crptls::synthetic_acquisitions::SyntheticBasis;
+use crptls::same_date_ordering::SameDateOrder;
+use crptls::timestamps;
+use crptls::import_map::ImportMap;
+use crptls::accounts_file::AccountsFile;
+use crptls::entities::{Entities, EntityTransferTreatment};
+use crptls::liquidity_pools::PoolTreatment;
+use crptls::lot_pooling::LotPooling;
+use crptls::airdrops::AirdropBasis;
+use crptls::rebases::RebaseTreatment;
+use crptls::token_aliases::TokenAliases;
+use crptls::dedupe::{self, DedupeMode, DEFAULT_DEDUPE_KEY};
+use crptls::run_state::RunState;
+use crptls::price_lookup::PriceLookup;
+use crptls::decimal_utils::{OutputPrecision, OutputRounding, ReportLocale};
+use crptls::fiat_costs;
+use crptls::fx_translation::{self, FxTranslation};
+use crptls::report_filter::ReportFilter;
+use crptls::report_language::ReportLanguage;
+use crptls::like_kind::{self, LikeKindScope};
+use crptls::summary;
 
+use crate::batch;
+use crate::run_result::{self, ExitCode};
 use crate::cli_user_choices;
+use cryptools::export::export_all::StdoutReport;
+use cryptools::export::{export_je, export_sql};
 use crate::skip_wizard;
 use crate::wizard;
+use crate::state_dirs::{self, StateDirs};
 
 
+/// The config file read when no `--config` is passed, if it exists in the directory the program is run from.
+const DEFAULT_CONFIG_FILE: &str = "cryptools.toml";
+
+/// A config file's settings, as environment variable names (in uppercase) and values, and its `[flags]`, as command
+/// line args.
+type ConfigFile = (Vec<(String, String)>, Vec<OsString>);
+
+/// Reads a config file (see `Cli::config`).
+fn read_config_file(path: &Path) -> Result<ConfigFile, Box<dyn Error>> {
+
+    let config: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+    let table = config.as_table().ok_or("The config file must be a table of settings.")?;
+
+    let value_string = |key: &str, value: &toml::Value| -> Result<String, Box<dyn Error>> {
+        match value {
+            toml::Value::String(s) => Ok(s.clone()),
+            toml::Value::Integer(i) => Ok(i.to_string()),
+            toml::Value::Float(f) => Ok(f.to_string()),
+            toml::Value::Boolean(b) => Ok(b.to_string()),
+            _ => Err(format!("The config file's {} must be a string, number or boolean.", key).into()),
+        }
+    };
+
+    let mut vars: Vec<(String, String)> = Vec::new();
+    let mut flags: Vec<OsString> = Vec::new();
+
+    for (key, value) in table.iter() {
+
+        if key != "flags" {
+            vars.push((key.to_uppercase(), value_string(key, value)?));
+            continue
+        }
+
+        let flag_table = value.as_table().ok_or("The config file's [flags] must be a table.")?;
+
+        for (flag, value) in flag_table.iter() {
+
+            let values = match value {
+                toml::Value::Array(values) => values.clone(),
+                _ => vec![value.clone()],
+            };
+
+            for value in values.iter() {
+                match value {
+                    toml::Value::Boolean(true) => flags.push(format!("--{}", flag).into()),
+                    toml::Value::Boolean(false) => {}
+                    _ => {
+                        flags.push(format!("--{}", flag).into());
+                        flags.push(value_string(flag, value)?.into());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((vars, flags))
+}
+
+/// The path of the config file given via `--config` in the command line `args` or, if none, the default config
+/// file, if it exists.
+fn config_file_path(args: &[OsString]) -> Option<PathBuf> {
+
+    let state_dir = state_dirs::state_dir_arg(args);
+
+    for (idx, arg) in args.iter().enumerate() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" { return args.get(idx + 1).map(PathBuf::from) }
+        if let Some(path) = arg.strip_prefix("--config=") { return Some(PathBuf::from(path)) }
+        if arg == "--profile" { return args.get(idx + 1).map(|name| profile_path(state_dir.as_deref(), &name.to_string_lossy())) }
+        if let Some(name) = arg.strip_prefix("--profile=") { return Some(profile_path(state_dir.as_deref(), name)) }
+    }
+
+    Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists())
+}
+
+/// The profile `name` (see `Cli::profile`) of the `StateDirs` of the `state_dir` or, if it's not there (and no
+/// `state_dir` is given), where profiles were saved before: .cryptools/profiles in the home directory.
+fn profile_path(state_dir: Option<&Path>, name: &str) -> PathBuf {
+
+    let path = StateDirs::new(state_dir).profile_path(name);
+
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from);
+    let legacy_path = home.map(|home| home.join(".cryptools").join("profiles").join(format!("{}.toml", name)));
+
+    match legacy_path {
+        Some(legacy_path) if state_dir.is_none() && !path.exists() && legacy_path.exists() => legacy_path,
+        _ => path,
+    }
+}
+
+/// The command line args, with the `[flags]` of the config file or profile (if any) inserted ahead of those given, so
+/// that a flag given on the command line overrides the config file's (the `Cli` lets a later flag override an earlier
+/// one).  The default config file, if used, is also passed as `--config`, so its settings are read by `get_env()` (as
+/// a profile's are, by its name).
+pub fn args_with_config_flags() -> Result<Vec<OsString>, Box<dyn Error>> {
+
+    let mut args: Vec<OsString> = env::args_os().collect();
+
+    let is_arg = |name: &str| args.iter()
+        .any(|arg| arg.to_string_lossy() == name || arg.to_string_lossy().starts_with(&format!("{}=", name)));
+    let is_profile = is_arg("--profile");
+
+    if is_profile && is_arg("--config") {
+        return Err("A --profile can't be combined with a --config file.".into())
+    }
+
+    let path = match config_file_path(&args) {
+        Some(path) => path,
+        None => return Ok(args),
+    };
+
+    let (_vars, flags) = match is_profile {
+        true => read_config_file(&path).map_err(|e| format!("Couldn't read the profile ({}): {}", path.display(), e))?,
+        false => read_config_file(&path).map_err(|e| format!("Couldn't read the config file ({}): {}", path.display(), e))?,
+    };
+
+    let mut user_args = args.split_off(1).into_iter();
+    if !is_profile { args.extend(["--config".into(), path.into_os_string()]) }
+    args.extend(flags);
+
+    while let Some(arg) = user_args.next() {
+        if arg == "--config" { user_args.next(); continue }
+        if arg.to_string_lossy().starts_with("--config=") { continue }
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
 pub fn get_env(cmd_args: &super::Cli) -> Result<super::Cfg, Box<dyn Error>> {
 
     match dotenv::dotenv() {
-        Ok(_path) => { println!("Exporting temporary environment variables from .env file.") },
-        Err(_e) => println!("Did not find .env file.")
+        Ok(_path) => { log::info!("Exporting temporary environment variables from .env file.") },
+        Err(_e) => log::info!("Did not find .env file.")
+    }
+
+    // The config file's settings are the defaults, where neither an environment variable nor the .env file sets them
+    if let Some(path) = cmd_args.config.clone().or_else(|| cmd_args.profile.as_deref().map(|name| profile_path(cmd_args.state_dir.as_deref(), name))) {
+
+        let (vars, _flags) = read_config_file(&path)?;
+
+        log::info!("Reading settings from config file {} (where not set by environment variables).", path.display());
+
+        for (key, value) in vars.iter() {
+            if env::var(key).is_err() { env::set_var(key, value) }
+        }
     }
 
-    println!("  Setting runtime variables according to command line options or environment variables (the former take precedent).");
+    log::info!("  Setting runtime variables according to command line options or environment variables (the former take precedent).");
 
     let iso_date: bool = if cmd_args.iso_date {
-        println!("    Command line flag for ISO_DATE was set. Using YY-mm-dd or YY/mm/dd.");
+        log::info!("    Command line flag for ISO_DATE was set. Using YY-mm-dd or YY/mm/dd.");
         true
     } else {
         match env::var("ISO_DATE") {
             Ok(val) => {
                 if val == "1" || val.to_lowercase() == "true" {
-                    println!("    Found ISO_DATE env var: {}. Using YY-mm-dd or YY/mm/dd.", val);
+                    log::info!("    Found ISO_DATE env var: {}. Using YY-mm-dd or YY/mm/dd.", val);
                     true
                 } else {
-                    println!("    Found ISO_DATE env var: {} (not 1 or true). Using MM-dd-YY or MM/dd/YY.", val);
+                    log::info!("    Found ISO_DATE env var: {} (not 1 or true). Using MM-dd-YY or MM/dd/YY.", val);
                     false
                 }
             },
             Err(_e) => {
-                println!("    Using default dating convention (MM-dd-YY or MM/dd/YY).");
+                log::info!("    Using default dating convention (MM-dd-YY or MM/dd/YY).");
                 false
             },
         }
     };
 
     let date_separator_is_slash: bool = if cmd_args.date_separator_is_slash {
-        println!("    Command line flag for DATE_SEPARATOR_IS_SLASH was set. Date separator set to slash (\"/\").");
+        log::info!("    Command line flag for DATE_SEPARATOR_IS_SLASH was set. Date separator set to slash (\"/\").");
         true
     } else {
         match env::var("DATE_SEPARATOR_IS_SLASH") {
             Ok(val) => {
                 if val == "1" || val.to_ascii_lowercase() == "true" {
-                    println!("    Found DATE_SEPARATOR_IS_SLASH env var: {}. Date separator set to slash (\"/\").", val);
+                    log::info!("    Found DATE_SEPARATOR_IS_SLASH env var: {}. Date separator set to slash (\"/\").", val);
                     true
                 } else {
-                    println!("    Found DATE_SEPARATOR_IS_SLASH env var: {} (not 1 or true). Date separator set to hyphen (\"-\").", val);
+                    log::info!("    Found DATE_SEPARATOR_IS_SLASH env var: {} (not 1 or true). Date separator set to hyphen (\"-\").", val);
                     false
                 }
             }
             Err(_e) => {
-                println!("    Using default date separator, hyphen (\"-\").");
+                log::info!("    Using default date separator, hyphen (\"-\").");
                 false
             },
         }
     };
 
+    let date_format = match env::var("DATE_FORMAT") {
+        Ok(val) => {
+            log::info!("    Found DATE_FORMAT env var: {}. This overrides the ISO_DATE and date separator options.", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let csv_delimiter = match env::var("CSV_DELIMITER") {
+        Ok(val) => {
+            log::info!("    Found CSV_DELIMITER env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let input_locale = match env::var("INPUT_LOCALE") {
+        Ok(val) => {
+            log::info!("    Found INPUT_LOCALE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
     let home_currency = match env::var("HOME_CURRENCY") {
         Ok(val) => {
-            println!("    Found HOME_CURRENCY env var: {}", val);
+            log::info!("    Found HOME_CURRENCY env var: {}", val);
             val.to_uppercase()},
         Err(_e) => {
-            println!("    Using default home currency (USD).");
+            log::info!("    Using default home currency (USD).");
             "USD".to_string()},
     };
 
+    let fx_rates_file = match env::var("FX_RATES_FILE") {
+        Ok(val) => {
+            log::info!("    Found FX_RATES_FILE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
     let lk_cutoff_date = match env::var("LK_CUTOFF_DATE") {
         Ok(val) => {
-            println!("    Found LK_CUTOFF_DATE env var: {}", val);
+            log::info!("    Found LK_CUTOFF_DATE env var: {}", val);
             Some(val)},
         Err(_e) => None,
     };
     
+    let lk_windows = match env::var("LK_WINDOWS") {
+        Ok(val) => {
+            log::info!("    Found LK_WINDOWS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let lk_asset_classes = match env::var("LK_ASSET_CLASSES") {
+        Ok(val) => {
+            log::info!("    Found LK_ASSET_CLASSES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let lk_pairs = match env::var("LK_PAIRS") {
+        Ok(val) => {
+            log::info!("    Found LK_PAIRS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
     let inv_costing_method = match env::var("INV_COSTING_METHOD") {
         Ok(val) => {
-            println!("    Found INV_COSTING_METHOD env var: {}", val);
+            log::info!("    Found INV_COSTING_METHOD env var: {}", val);
             val},
         Err(_e) => {
-            println!("    Using default inventory costing method (LIFO by lot creation date).");
+            log::info!("    Using default inventory costing method (LIFO by lot creation date).");
             "1".to_string()},
     };
 
+    let home_curr_leg_is_basis: bool = match env::var("HOME_CURRENCY_LEG_IS_BASIS") {
+        Ok(val) => {
+            if val == "0" || val.eq_ignore_ascii_case("false") {
+                log::info!("    Found HOME_CURRENCY_LEG_IS_BASIS env var: {}. Acquisitions for home currency will be valued using the proceeds column.", val);
+                false
+            } else {
+                log::info!("    Found HOME_CURRENCY_LEG_IS_BASIS env var: {}. Home currency spent will be the basis of the acquired asset.", val);
+                true
+            }
+        }
+        Err(_e) => true,
+    };
+
+    let settle_date_is_basis_date: bool = match env::var("SETTLE_DATE_IS_BASIS_DATE") {
+        Ok(val) => {
+            if val == "1" || val.eq_ignore_ascii_case("true") {
+                log::info!("    Found SETTLE_DATE_IS_BASIS_DATE env var: {}. Lots will use the settleDate column (if present) as their basis date.", val);
+                true
+            } else {
+                log::info!("    Found SETTLE_DATE_IS_BASIS_DATE env var: {} (not 1 or true). Lots will use the trade date as their basis date.", val);
+                false
+            }
+        }
+        Err(_e) => false,
+    };
+
+    let zero_quantity_rows_are_annotations: bool = match env::var("ZERO_QUANTITY_ANNOTATIONS") {
+        Ok(val) => {
+            if val == "1" || val.eq_ignore_ascii_case("true") {
+                log::info!("    Found ZERO_QUANTITY_ANNOTATIONS env var: {}. Rows without quantities will annotate the transaction before them.", val);
+                true
+            } else {
+                log::info!("    Found ZERO_QUANTITY_ANNOTATIONS env var: {} (not 1 or true). Rows without quantities are processed as transactions.", val);
+                false
+            }
+        }
+        Err(_e) => false,
+    };
+
+    let transfer_fee_is_disposal: bool = match env::var("TRANSFER_FEE_IS_DISPOSAL") {
+        Ok(val) => {
+            if val == "1" || val.eq_ignore_ascii_case("true") {
+                log::info!("    Found TRANSFER_FEE_IS_DISPOSAL env var: {}. Transfer fees will be disposals.", val);
+                true
+            } else {
+                log::info!("    Found TRANSFER_FEE_IS_DISPOSAL env var: {} (not 1 or true). Transfer fees will be expenses.", val);
+                false
+            }
+        }
+        Err(_e) => false,
+    };
+
+    let dust_threshold = match env::var("DUST_THRESHOLD") {
+        Ok(val) => {
+            log::info!("    Found DUST_THRESHOLD env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let disposal_rounding = match env::var("DISPOSAL_ROUNDING") {
+        Ok(val) => {
+            log::info!("    Found DISPOSAL_ROUNDING env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let exchange_label_separator = match env::var("EXCHANGE_LABEL_SEPARATOR") {
+        Ok(val) => {
+            log::info!("    Found EXCHANGE_LABEL_SEPARATOR env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let synthetic_acquisitions = match env::var("SYNTHETIC_ACQUISITIONS") {
+        Ok(val) => {
+            log::info!("    Found SYNTHETIC_ACQUISITIONS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let stablecoins = match env::var("STABLECOINS") {
+        Ok(val) => {
+            log::info!("    Found STABLECOINS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let transfer_match_tolerance = match env::var("TRANSFER_MATCH_TOLERANCE") {
+        Ok(val) => {
+            log::info!("    Found TRANSFER_MATCH_TOLERANCE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let lp_treatment = match env::var("LP_TREATMENT") {
+        Ok(val) => {
+            log::info!("    Found LP_TREATMENT env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let lot_pooling = match env::var("LOT_POOLING") {
+        Ok(val) => {
+            log::info!("    Found LOT_POOLING env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let airdrop_basis = match env::var("AIRDROP_BASIS") {
+        Ok(val) => {
+            log::info!("    Found AIRDROP_BASIS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let rebase_treatment = match env::var("REBASE_TREATMENT") {
+        Ok(val) => {
+            log::info!("    Found REBASE_TREATMENT env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let token_aliases = match env::var("TOKEN_ALIASES") {
+        Ok(val) => {
+            log::info!("    Found TOKEN_ALIASES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let entities = match env::var("ENTITIES") {
+        Ok(val) => {
+            log::info!("    Found ENTITIES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let entity_transfers = match env::var("ENTITY_TRANSFERS") {
+        Ok(val) => {
+            log::info!("    Found ENTITY_TRANSFERS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let header_aliases = match env::var("HEADER_ALIASES") {
+        Ok(val) => {
+            log::info!("    Found HEADER_ALIASES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let dedupe_key = match env::var("DEDUPE_KEY") {
+        Ok(val) => {
+            log::info!("    Found DEDUPE_KEY env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let same_date_order = match env::var("SAME_DATE_ORDER") {
+        Ok(val) => {
+            log::info!("    Found SAME_DATE_ORDER env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let timezone = match env::var("TIMEZONE") {
+        Ok(val) => {
+            log::info!("    Found TIMEZONE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let wash_sale: bool = match env::var("WASH_SALE") {
+        Ok(val) => {
+            if val == "1" || val.eq_ignore_ascii_case("true") {
+                log::info!("    Found WASH_SALE env var: {}. Losses with a replacement purchase nearby will be disallowed.", val);
+                true
+            } else {
+                log::info!("    Found WASH_SALE env var: {} (not 1 or true). The wash sale rule won't be applied.", val);
+                false
+            }
+        }
+        Err(_e) => false,
+    };
+
+    let wash_sale_window = match env::var("WASH_SALE_WINDOW") {
+        Ok(val) => {
+            log::info!("    Found WASH_SALE_WINDOW env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let long_term_days = match env::var("LONG_TERM_DAYS") {
+        Ok(val) => {
+            log::info!("    Found LONG_TERM_DAYS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let je_long_term_days = match env::var("JE_LONG_TERM_DAYS") {
+        Ok(val) => {
+            log::info!("    Found JE_LONG_TERM_DAYS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let je_accounts = match env::var("JE_ACCOUNTS") {
+        Ok(val) => {
+            log::info!("    Found JE_ACCOUNTS env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let fiat_decimal_places = match env::var("FIAT_DECIMAL_PLACES") {
+        Ok(val) => {
+            log::info!("    Found FIAT_DECIMAL_PLACES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let crypto_decimal_places = match env::var("CRYPTO_DECIMAL_PLACES") {
+        Ok(val) => {
+            log::info!("    Found CRYPTO_DECIMAL_PLACES env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let output_rounding = match env::var("OUTPUT_ROUNDING") {
+        Ok(val) => {
+            log::info!("    Found OUTPUT_ROUNDING env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let report_locale = match env::var("REPORT_LOCALE") {
+        Ok(val) => {
+            log::info!("    Found REPORT_LOCALE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let report_language = match env::var("REPORT_LANGUAGE") {
+        Ok(val) => {
+            log::info!("    Found REPORT_LANGUAGE env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let output_date_format = match env::var("OUTPUT_DATE_FORMAT") {
+        Ok(val) => {
+            log::info!("    Found OUTPUT_DATE_FORMAT env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
+    let fiscal_year_end = match env::var("FISCAL_YEAR_END") {
+        Ok(val) => {
+            log::info!("    Found FISCAL_YEAR_END env var: {}", val);
+            Some(val)},
+        Err(_e) => None,
+    };
+
     let cfg = super::Cfg {
         iso_date,
         date_separator_is_slash,
+        date_format,
+        csv_delimiter,
+        input_locale,
         home_currency,
+        fx_rates_file,
         lk_cutoff_date,
+        lk_windows,
+        lk_asset_classes,
+        lk_pairs,
         inv_costing_method,
+        home_curr_leg_is_basis,
+        settle_date_is_basis_date,
+        zero_quantity_rows_are_annotations,
+        transfer_fee_is_disposal,
+        stablecoins,
+        transfer_match_tolerance,
+        lp_treatment,
+        lot_pooling,
+        airdrop_basis,
+        rebase_treatment,
+        token_aliases,
+        entities,
+        entity_transfers,
+        header_aliases,
+        dedupe_key,
+        dust_threshold,
+        disposal_rounding,
+        exchange_label_separator,
+        synthetic_acquisitions,
+        same_date_order,
+        timezone,
+        wash_sale,
+        wash_sale_window,
+        long_term_days,
+        je_long_term_days,
+        je_accounts,
+        fiat_decimal_places,
+        crypto_decimal_places,
+        output_rounding,
+        report_locale,
+        output_date_format,
+        report_language,
+        fiscal_year_end,
     };
 
     Ok(cfg)
@@ -110,46 +636,144 @@ pub struct ArgsForImportVarsTBD {
     pub lk_cutoff_date_arg: Option<String>,
     pub output_dir_path: PathBuf,
     pub suppress_reports: bool,
+    /// The settings saved to a profile along with the wizard's answers (see `wizard::save_profile()`), as config file
+    /// keys and values.
+    pub profile_settings: Vec<(&'static str, toml::Value)>,
+    /// Where a profile is saved.
+    pub state_dirs: StateDirs,
 }
 
-pub (crate) fn run_setup(cmd_args: &super::Cli, cfg: super::Cfg) -> Result<(PathBuf, ImportProcessParameters), Box<dyn Error>> {
+/// The file(s) to import (`None` if processing `--portfolio`s, each of which names its own file) and the settings.
+pub (crate) type Setup = (Option<Vec<PathBuf>>, ImportProcessParameters);
+
+/// Returns the file(s) to import and the settings.  A directory stands for every CSV file in it, in file name order.
+pub (crate) fn run_setup(cmd_args: &super::Cli, cfg: super::Cfg) -> Result<Setup, Box<dyn Error>> {
 
     let date_separator = match cfg.date_separator_is_slash {
         false => { "-" } // Default
         true => { "/" } // Overridden by env var or cmd line flag
     };
 
-    let input_file_path = match cmd_args.file_to_import.to_owned() {
-        Some(file) => { 
-            if File::open(&file).is_ok() {
-                file
-            } else {
-                cli_user_choices::choose_file_for_import(cmd_args.accept_args)?
+    if let Some(format) = &cfg.date_format {
+        validate_date_format("DATE_FORMAT", format);
+    }
+
+    if let Some(format) = &cfg.output_date_format {
+        validate_date_format("OUTPUT_DATE_FORMAT", format);
+    }
+
+    let input_file_paths = if !cmd_args.portfolios.is_empty() { None } else {
+        match cmd_args.file_to_import.as_slice() {
+            [] => {
+                if cmd_args.batch {
+                    batch::exit_for_missing_input("file_to_import", "No file_to_import was given.")
+                }
+                if !cmd_args.accept_args {
+                    wizard::shall_we_proceed()?;
+                    println!("Note: No file was provided as a command line arg, or the provided file wasn't found.\n");
+                }
+                Some(expand_input_paths(&[cli_user_choices::choose_file_for_import(cmd_args.accept_args)?])?)
             }
-        },
-        None => {
-            if !cmd_args.accept_args {
-                wizard::shall_we_proceed()?;
-                println!("Note: No file was provided as a command line arg, or the provided file wasn't found.\n");
+            [file] if !is_stdin(file) && File::open(file).is_err() => {
+                if cmd_args.batch {
+                    batch::exit_for_missing_input("file_to_import",
+                        &format!("The file to import ({}) wasn't found.", file.display()))
+                }
+                Some(expand_input_paths(&[cli_user_choices::choose_file_for_import(cmd_args.accept_args)?])?)
+            }
+            files => {
+                if let Some(missing) = files.iter().find(|file| !is_stdin(file) && File::open(file).is_err()) {
+                    run_result::fatal(ExitCode::ParseError, &format!("FATAL: The file to import ({}) wasn't found.", missing.display()))
+                }
+                Some(expand_input_paths(files)?)
             }
-            cli_user_choices::choose_file_for_import(cmd_args.accept_args)?
         }
     };
 
+    let reads_stdin = cmd_args.file_to_import.iter().any(|file| is_stdin(file));
+
+    if reads_stdin && cmd_args.file_to_import.iter().filter(|file| is_stdin(file)).count() > 1 {
+        run_result::fatal(ExitCode::ParseError, "FATAL: Standard input (-) can only be imported once.")
+    }
+
+    if reads_stdin && !(cmd_args.accept_args || cmd_args.check_only) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: Importing standard input (-) requires -a (--accept), as the wizard's answers would be read from it.")
+    }
+
+    #[cfg(feature = "print_menu")]
+    if reads_stdin && cmd_args.print_menu {
+        run_result::fatal(ExitCode::ParseError, "FATAL: The print menu can't be used when importing standard input (-), as its keys would be read from it.")
+    }
+
+    let mut profile_settings = vec![
+        ("home_currency", toml::Value::String(cfg.home_currency.clone())),
+        ("iso_date", toml::Value::Boolean(cfg.iso_date)),
+        ("date_separator_is_slash", toml::Value::Boolean(cfg.date_separator_is_slash)),
+    ];
+    if let Some(date_format) = &cfg.date_format {
+        profile_settings.push(("date_format", toml::Value::String(date_format.clone())));
+    }
+
+    let lk_scope = LikeKindScope {
+        windows: cfg.lk_windows.as_deref().map_or_else(Vec::new, |val| {
+            like_kind::parse_windows(val).unwrap_or_else(|e| {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for LK_WINDOWS: {} See .env.example.", e))
+            })
+        }),
+        classes: cfg.lk_asset_classes.as_deref().map_or_else(HashMap::new, |val| {
+            like_kind::parse_asset_classes(val).unwrap_or_else(|e| {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for LK_ASSET_CLASSES: {} See .env.example.", e))
+            })
+        }),
+        pairs: cfg.lk_pairs.as_deref().map_or_else(Vec::new, |val| {
+            like_kind::parse_pairs(val).unwrap_or_else(|e| {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for LK_PAIRS: {} See .env.example.", e))
+            })
+        }),
+    };
+
+    //  The windows stand in for the cut-off date, which is then the last date of the last of them
+    let lk_cutoff_date_arg = match lk_scope.last_date() {
+        Some(_) if cfg.lk_cutoff_date.is_some() => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: LK_WINDOWS can't be combined with LK_CUTOFF_DATE. See .env.example.")
+        }
+        Some(last_date) => Some(last_date.format("%Y-%m-%d").to_string()),
+        None => cfg.lk_cutoff_date,
+    };
+
     let wizard_or_not_args = ArgsForImportVarsTBD {
         inv_costing_method_arg: cfg.inv_costing_method,
-        lk_cutoff_date_arg: cfg.lk_cutoff_date,
+        lk_cutoff_date_arg,
         output_dir_path: cmd_args.output_dir_path.to_owned(),
         suppress_reports: cmd_args.suppress_reports,
+        profile_settings,
+        state_dirs: StateDirs::new(cmd_args.state_dir.as_deref()),
     };
 
+    if cmd_args.batch && !(cmd_args.accept_args || cmd_args.check_only) {
+        batch::exit_for_missing_input("accept", "The wizard would ask for the costing method, like-kind treatment and \
+            output directory. Pass -a (--accept) to use the settings given instead.")
+    }
+
     let(
         costing_method_choice,
         like_kind_election,
         like_kind_cutoff_date_string,
         should_export,
         output_dir_path,
-     ) = wizard_or_not(cmd_args.accept_args, wizard_or_not_args)?;
+     ) = wizard_or_not(cmd_args.accept_args || cmd_args.check_only, wizard_or_not_args)?;
+
+    //  Each run's reports go to their own, new subdirectory
+    let output_dir_path = match cmd_args.run_subdir {
+        true => {
+            let run_dir = output_dir_path.join(chrono::Local::now().format("%Y-%m-%d_%H%M%S").to_string());
+            if should_export {
+                fs::create_dir_all(&run_dir)?;
+            }
+            run_dir
+        }
+        false => output_dir_path,
+    };
 
     let like_kind_cutoff_date = if like_kind_election {
         NaiveDate::parse_from_str(&like_kind_cutoff_date_string, "%y-%m-%d")
@@ -157,20 +781,530 @@ pub (crate) fn run_setup(cmd_args: &super::Cli, cfg: super::Cfg) -> Result<(Path
             .expect("Environment variable for LK_CUTOFF_DATE has an incorrect format. Program must abort. See .env.example."))
     } else { NaiveDate::parse_from_str(&"1-1-1", "%y-%m-%d").unwrap() };
 
+    let (dust_threshold, dust_quantities) = cfg.dust_threshold.as_deref().map_or_else(|| (None, DustQuantities::new()), |val| {
+        dust_sweeps::parse_dust_threshold(val).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for DUST_THRESHOLD: {} See .env.example.", e))
+        })
+    });
+
+    let disposal_rounding = cfg.disposal_rounding.map(|val| {
+        if val.eq_ignore_ascii_case("lots") {
+            DisposalRounding::LotPrecision
+        } else {
+            DisposalRounding::DecimalPlaces(val.parse::<u32>()
+                .expect("Environment variable for DISPOSAL_ROUNDING must be `lots` or a number of decimal places. Program must abort. See .env.example."))
+        }
+    });
+
+    let synthetic_acquisitions = cfg.synthetic_acquisitions.map(|val| {
+        match val.to_ascii_lowercase().as_str() {
+            "zero" => SyntheticBasis::Zero,
+            "market" => SyntheticBasis::Market,
+            _ => {
+                run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for SYNTHETIC_ACQUISITIONS must be `zero` or `market`. See .env.example.")
+            }
+        }
+    });
+
+    let same_date_order = match cfg.same_date_order.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("file-order") => SameDateOrder::FileOrder,
+        Some("acquisitions-first") => SameDateOrder::AcquisitionsFirst,
+        Some("disposals-first") => SameDateOrder::DisposalsFirst,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for SAME_DATE_ORDER must be `file-order`, `acquisitions-first` or `disposals-first`. See .env.example.")
+        }
+    };
+
+    let stablecoins: Vec<String> = cfg.stablecoins.as_deref().unwrap_or_default()
+        .split(',')
+        .map(|ticker| ticker.trim().to_ascii_uppercase())
+        .filter(|ticker| !ticker.is_empty())
+        .collect();
+
+    let transfer_match_tolerance = if cmd_args.match_transfers {
+        let tolerance = cfg.transfer_match_tolerance.as_deref().unwrap_or("0.01");
+        match tolerance.parse::<Decimal>() {
+            Ok(tolerance) if tolerance >= dec!(0) && tolerance < dec!(1) => Some(tolerance),
+            _ => {
+                run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for TRANSFER_MATCH_TOLERANCE must be a fraction of at least 0 and less than 1. See .env.example.")
+            }
+        }
+    } else { None };
+
+    let timezone = cfg.timezone.map(|val| timestamps::parse_utc_offset(&val).unwrap_or_else(|| {
+        run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for TIMEZONE must be a UTC offset like `-05:00` or `+09:00`, or `UTC`. See .env.example.")
+    }));
+
+    let wash_sale_window = if cfg.wash_sale {
+        if like_kind_election {
+            run_result::fatal(ExitCode::ParseError, "FATAL: The wash sale rule (WASH_SALE) can't be combined with like-kind treatment. See .env.example.")
+        }
+        Some(cfg.wash_sale_window.map_or(30, |val| val.parse::<i64>().ok().filter(|days| *days >= 0)
+            .unwrap_or_else(|| {
+                run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for WASH_SALE_WINDOW must be a number of days. See .env.example.")
+            })))
+    } else { None };
+
+    let (tax_year, split_by_tax_year) = match cmd_args.tax_year.as_deref() {
+        None => (None, false),
+        Some("all") => (None, true),
+        Some(val) => match val.parse::<i32>() {
+            Ok(year) => (Some(year), false),
+            Err(_e) => {
+                run_result::fatal(ExitCode::ParseError, "FATAL: --tax-year must be a year (i.e., 2023) or `all`.")
+            }
+        },
+    };
+
+    if reads_stdin && costing_method_choice == InventoryCostingMethod::SpecificIdentification {
+        run_result::fatal(ExitCode::ParseError, "FATAL: Specific identification can't be used when importing standard input (-), as the lots would be chosen from it.")
+    }
+
+    let long_term_days = cfg.long_term_days.map_or(365, |val| val.parse::<i64>().ok().filter(|days| *days >= 0)
+        .unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for LONG_TERM_DAYS must be a number of days. See .env.example.")
+        }));
+
+    if costing_method_choice == InventoryCostingMethod::SharePoolingSection104 && (like_kind_election || cfg.wash_sale) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: UK share pooling (inventory costing method 10) can't be combined with like-kind treatment or WASH_SALE.")
+    }
+
+    if costing_method_choice == InventoryCostingMethod::AdjustedCostBase && (like_kind_election || cfg.wash_sale) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: The adjusted cost base (inventory costing method 11) can't be combined with like-kind treatment or WASH_SALE.")
+    }
+
+    let pool_treatment = match cfg.lp_treatment.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("swap") => PoolTreatment::Swap,
+        Some("carryover") => PoolTreatment::Carryover,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for LP_TREATMENT must be `swap` or `carryover`. See .env.example.")
+        }
+    };
+
+    let is_pooled = matches!(costing_method_choice,
+        InventoryCostingMethod::SharePoolingSection104 | InventoryCostingMethod::AdjustedCostBase);
+
+    if pool_treatment == PoolTreatment::Carryover && (like_kind_election || is_pooled) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: LP_TREATMENT=carryover can't be combined with like-kind treatment, UK share pooling or the adjusted cost base.")
+    }
+
+    let lot_pooling = match cfg.lot_pooling.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("per-wallet") => LotPooling::PerWallet,
+        Some("universal") => LotPooling::Universal,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for LOT_POOLING must be `per-wallet` or `universal`. See .env.example.")
+        }
+    };
+
+    if lot_pooling == LotPooling::Universal
+        && (like_kind_election || cfg.wash_sale || costing_method_choice == InventoryCostingMethod::SpecificIdentification) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: LOT_POOLING=universal can't be combined with like-kind treatment, WASH_SALE or specific identification.")
+    }
+
+    let airdrop_basis = match cfg.airdrop_basis.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("fmv") => AirdropBasis::FairMarketValue,
+        Some("zero") => AirdropBasis::Zero,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for AIRDROP_BASIS must be `fmv` or `zero`. See .env.example.")
+        }
+    };
+
+    let rebase_treatment = match cfg.rebase_treatment.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("income") => RebaseTreatment::Income,
+        Some("basis") => RebaseTreatment::SpreadBasis,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for REBASE_TREATMENT must be `income` or `basis`. See .env.example.")
+        }
+    };
+
+    let decimal_places = |var: &str, val: String| val.parse::<u32>().ok().filter(|places| *places <= 28)
+        .unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for {} must be a number of decimal places. See .env.example.", var))
+        });
+
+    let output_precision = OutputPrecision {
+        fiat_places: cfg.fiat_decimal_places.map_or(2, |val| decimal_places("FIAT_DECIMAL_PLACES", val)),
+        crypto_places: cfg.crypto_decimal_places.map(|val| decimal_places("CRYPTO_DECIMAL_PLACES", val)),
+        rounding: match cfg.output_rounding.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+            None | Some("bankers") => OutputRounding::Bankers,
+            Some("half-up") => OutputRounding::HalfUp,
+            Some(_) => {
+                run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for OUTPUT_ROUNDING must be `half-up` or `bankers`. See .env.example.")
+            }
+        },
+        locale: cfg.report_locale.map_or(ReportLocale::Plain, |val| ReportLocale::from_code(&val)
+            .unwrap_or_else(|| {
+                run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for REPORT_LOCALE must be `plain` or a language code like `en` or `de`. \
+                    See .env.example.")
+            })),
+    };
+
+    let report_language = match cfg.report_language {
+        Some(val) => ReportLanguage::from_code(&val).unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for REPORT_LANGUAGE must be a language code \
+                like `en`, `es` or `de`. See .env.example.")
+        }),
+        None => ReportLanguage::from_env(),
+    };
+
+    //  The first is the home currency, and any others are secondary home currencies
+    let mut currencies = cfg.home_currency.split(',').map(|currency| currency.trim().to_uppercase());
+    let home_currency = currencies.next().unwrap_or_default();
+
+    if stablecoins.contains(&home_currency) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: The home currency can't be one of the STABLECOINS. See .env.example.")
+    }
+
+    let secondary_currencies: Vec<FxTranslation> = currencies.map(|currency| {
+        let path = cfg.fx_rates_file.as_ref().unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: A secondary home currency ({}) requires the FX_RATES_FILE environment variable. See .env.example.", currency))
+        });
+        fx_translation::import_fx_rates(Path::new(path), &currency).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the FX_RATES_FILE ({}): {}", path, e))
+        })
+    }).collect();
+
+    let mut journal_accounts = cfg.je_accounts.as_deref().map_or_else(JournalAccounts::default, |val| {
+        JournalAccounts::parse(val).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for JE_ACCOUNTS: {} See .env.example.", e))
+        })
+    });
+
+    journal_accounts.long_term_days = cfg.je_long_term_days.as_deref().map(|val| val.parse::<i64>().ok().filter(|days| *days >= 0)
+        .unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for JE_LONG_TERM_DAYS must be a number of days. See .env.example.")
+        }));
+
+    let header_aliases = cfg.header_aliases.as_deref().map_or_else(HashMap::new, |val| {
+        csv_import_accts_txns::parse_header_aliases(val).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for HEADER_ALIASES: {} See .env.example.", e))
+        })
+    });
+
+    let dedupe_key = cfg.dedupe_key.as_deref().map_or_else(|| DEFAULT_DEDUPE_KEY.to_vec(), |val| {
+        dedupe::parse_dedupe_key(val).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for DEDUPE_KEY: {} See .env.example.", e))
+        })
+    });
+
+    let token_aliases = cfg.token_aliases.as_ref().map(|path| {
+        let aliases = TokenAliases::from_file(Path::new(path)).unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the TOKEN_ALIASES file ({}): {}", path, e))
+        });
+        if aliases.contains(&home_currency) {
+            run_result::fatal(ExitCode::ParseError, "FATAL: The home currency can't be in the TOKEN_ALIASES file. See .env.example.")
+        }
+        if is_pooled {
+            run_result::fatal(ExitCode::ParseError, "FATAL: TOKEN_ALIASES can't be combined with UK share pooling or the adjusted cost base.")
+        }
+        aliases
+    });
+
+    let import_map = match &cmd_args.import_map {
+        Some(path) => match ImportMap::from_file(path) {
+            Ok(import_map) => Some(import_map),
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --import-map file ({}): {}", path.display(), e))
+            }
+        },
+        None => None,
+    };
+
+    let accounts_file = match &cmd_args.accounts_file {
+        Some(path) => match AccountsFile::from_file(path) {
+            Ok(accounts_file) => Some(accounts_file),
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --accounts file ({}): {}", path.display(), e))
+            }
+        },
+        None => None,
+    };
+
+    let entities = match (&cfg.entities, accounts_file.as_ref().map(Entities::from_accounts_file)) {
+        (Some(_val), Some(Ok(Some(_entities)))) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Give the entities in the entity column of the --accounts file or in ENTITIES, not both.")
+        }
+        (_, Some(Err(e))) => {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: The entity column of the --accounts file: {}", e))
+        }
+        (Some(val), _) => match Entities::parse(val) {
+            Ok(entities) => Some(entities),
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for ENTITIES: {} See .env.example.", e))
+            }
+        },
+        (None, Some(Ok(entities))) => entities,
+        (None, None) => None,
+    };
+
+    let entity_transfers = match cfg.entity_transfers.as_deref().map(|val| val.to_ascii_lowercase()).as_deref() {
+        None | Some("carryover") => EntityTransferTreatment::Carryover,
+        Some("sale") => EntityTransferTreatment::Sale,
+        Some(_) => {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for ENTITY_TRANSFERS must be `carryover` or `sale`. See .env.example.")
+        }
+    };
+
+    if entity_transfers == EntityTransferTreatment::Sale && entities.is_none() {
+        run_result::fatal(ExitCode::ParseError, "FATAL: ENTITY_TRANSFERS=sale needs entities, given in ENTITIES or the entity column of the --accounts file.")
+    }
+
+    if entity_transfers == EntityTransferTreatment::Sale && is_pooled {
+        run_result::fatal(ExitCode::ParseError, "FATAL: ENTITY_TRANSFERS=sale can't be combined with UK share pooling or the adjusted cost base.")
+    }
+
+    let resume_from_path = match cmd_args.resume_latest {
+        true => match StateDirs::new(cmd_args.state_dir.as_deref()).latest_snapshot() {
+            Some(path) => Some(path),
+            None => {
+                run_result::fatal(ExitCode::ParseError, "FATAL: --resume-latest was passed, but no snapshot has been saved (with --save-snapshot).")
+            }
+        },
+        false => cmd_args.resume_from.clone(),
+    };
+
+    let resume_from = match &resume_from_path {
+        Some(path) => match RunState::from_file(path) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --resume-from file ({}): {}", path.display(), e))
+            }
+        },
+        None => None,
+    };
+
+    let resume_from = match &cmd_args.opening_balances {
+        Some(path) => match RunState::from_opening_balances(path, &home_currency) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --opening-balances file ({}): {}", path.display(), e))
+            }
+        },
+        None => resume_from,
+    };
+
+    let resume_from = match &cmd_args.db {
+        Some(path) => match export_sql::latest_db_state(path) {
+            Ok(state) => state,
+            Err(e) => run_result::fatal(ExitCode::ParseError, &format!("FATAL: {}", e)),
+        },
+        None => resume_from,
+    };
+
+    if let (Some(from), Some(to)) = (cmd_args.from, cmd_args.to) {
+        if from > to {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: The --from date ({}) is after the --to date ({}).", from, to))
+        }
+    }
+
+    if cmd_args.stdout.is_some() && split_by_tax_year {
+        run_result::fatal(ExitCode::ParseError, "FATAL: --stdout writes a single report, so it can't be combined with --tax-year all.")
+    }
+
+    if cmd_args.stdout.is_some() && !secondary_currencies.is_empty() {
+        run_result::fatal(ExitCode::ParseError, "FATAL: --stdout writes a single report, so it can't be combined with secondary home currencies.")
+    }
+
+    let chart_of_accounts = match &cmd_args.chart_of_accounts {
+        Some(path) => match export_je::chart_of_accounts_from_file(path) {
+            Ok(chart_of_accounts) => chart_of_accounts,
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --chart-of-accounts file ({}): {}", path.display(), e))
+            }
+        },
+        None => HashMap::new(),
+    };
+
+    let fiat_costs = match &cmd_args.costs {
+        Some(path) => match fiat_costs::import_costs(path) {
+            Ok(costs) => costs,
+            Err(e) => {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't read the --costs file ({}): {}", path.display(), e))
+            }
+        },
+        None => Vec::new(),
+    };
+
+    if cmd_args.offline_prices && !(cmd_args.price_lookup.is_some() || cmd_args.cached_prices) {
+        run_result::fatal(ExitCode::ParseError, "FATAL: --offline-prices requires --price-lookup or --cached-prices.")
+    }
+
+    let price_lookup_path = match cmd_args.cached_prices {
+        true => {
+            let path = StateDirs::new(cmd_args.state_dir.as_deref()).price_cache_path();
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).unwrap_or_else(|e| {
+                    run_result::fatal(ExitCode::ParseError, &format!("FATAL: Couldn't create the price cache directory ({}): {}", dir.display(), e))
+                })
+            }
+            Some(path)
+        }
+        false => cmd_args.price_lookup.clone(),
+    };
+
+    let price_lookup = price_lookup_path.as_ref().map(|path| {
+        if cmd_args.offline_prices && !path.exists() {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: The --price-lookup file ({}) doesn't exist, and --offline-prices was passed.", path.display()))
+        }
+        PriceLookup { path: path.clone(), offline: cmd_args.offline_prices }
+    });
+
+    let csv_delimiter = cfg.csv_delimiter.as_deref().map_or(CsvDelimiter::Auto, |val| {
+        val.parse::<CsvDelimiter>().unwrap_or_else(|e| {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for CSV_DELIMITER: {} See .env.example.", e))
+        })
+    });
+
+    let input_locale = cfg.input_locale.as_deref().map_or(ReportLocale::Plain, |val| {
+        ReportLocale::from_code(val).unwrap_or_else(|| {
+            run_result::fatal(ExitCode::ParseError, "FATAL: Environment variable for INPUT_LOCALE must be `plain` or a language code like `en` or `de`. \
+                See .env.example.")
+        })
+    });
+
+    let fiscal_year_start_month = match cfg.fiscal_year_end.as_deref() {
+        None => cmd_args.fiscal_year_start,
+        Some(val) => {
+            let start_month = summary::parse_fiscal_year_end(val).unwrap_or_else(|e| {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for FISCAL_YEAR_END: {} See .env.example.", e))
+            });
+            if cmd_args.fiscal_year_start != 1 && cmd_args.fiscal_year_start != start_month {
+                run_result::fatal(ExitCode::ParseError, &format!("FATAL: FISCAL_YEAR_END ({}) and --fiscal-year-start ({}) disagree.", val, cmd_args.fiscal_year_start))
+            }
+            start_month
+        }
+    };
+
     let settings = ImportProcessParameters {
         input_file_uses_iso_date_style: cfg.iso_date,
         input_file_date_separator: date_separator.to_string(),
-        home_currency: cfg.home_currency.to_uppercase(),
+        input_file_date_format: cfg.date_format,
+        csv_delimiter,
+        input_locale,
+        home_currency,
         costing_method: costing_method_choice,
+        same_date_order,
+        timezone,
+        long_term_days,
         lk_treatment_enabled: like_kind_election,
         lk_cutoff_date: like_kind_cutoff_date,
+        lk_scope,
         lk_basis_date_preserved: true,  //  TODO
+        settle_date_is_basis_date: cfg.settle_date_is_basis_date,
+        zero_quantity_rows_are_annotations: cfg.zero_quantity_rows_are_annotations,
         should_export,
         export_path: output_dir_path,
         journal_entry_export: cmd_args.journal_entries_only,
+        ledger_export: cmd_args.ledger || cmd_args.stdout == Some(StdoutReport::Ledger),
+        beancount_export: cmd_args.beancount || cmd_args.stdout == Some(StdoutReport::Beancount),
+        journal_csv_export: cmd_args.journal_csv || cmd_args.stdout == Some(StdoutReport::JournalCsv),
+        chart_of_accounts,
+        journal_accounts,
+        fiat_costs,
+        stdout_report: cmd_args.stdout.map(|report| report.file_name_prefix().to_string()),
+        excel_bom: cmd_args.excel_bom,
+        table_format: TableFormat::Csv,
+        form_8949_per_account: cmd_args.form_8949_per_account,
+        home_curr_leg_is_basis: cfg.home_curr_leg_is_basis,
+        dust_threshold,
+        dust_quantities,
+        disposal_rounding,
+        exchange_label_separator: cfg.exchange_label_separator,
+        synthetic_acquisitions,
+        import_map,
+        import_source: cmd_args.source,
+        input_schema: cmd_args.schema,
+        accounts_file,
+        low_memory: cmd_args.low_memory,
+        resume_from,
+        wash_sale_window,
+        tax_year,
+        split_by_tax_year,
+        transfer_fee_is_disposal: cfg.transfer_fee_is_disposal,
+        stablecoins,
+        transfer_match_tolerance,
+        pool_treatment,
+        lot_pooling,
+        airdrop_basis,
+        rebase_treatment,
+        dedupe: cmd_args.dedupe.parse::<DedupeMode>()?,
+        dedupe_key,
+        header_aliases,
+        token_aliases,
+        entities,
+        entity_transfers,
+        price_lookup,
+        lenient: cmd_args.lenient,
+        corrections: cmd_args.corrections.clone(),
+        lot_selections: cmd_args.lot_selections.clone(),
+        excluded_txns: cmd_args.exclude_txns.clone(),
+        parked_rows: Vec::new(),
+        output_precision,
+        output_date_format: cfg.output_date_format,
+        report_language,
+        secondary_currencies,
+        translation: None,
+        file_name_prefix: cmd_args.file_prefix.clone(),
+        file_name_date: cmd_args.date_stamp.then(|| chrono::Local::now().date_naive()),
+        overwrite: cmd_args.overwrite,
+        fiscal_year_start_month,
+        report_filter: ReportFilter {
+            asset: cmd_args.filter_asset.clone(),
+            account: cmd_args.filter_account,
+            accounts: None,
+            from: cmd_args.from,
+            to: cmd_args.to,
+            tag: cmd_args.filter_tag.clone(),
+        },
     };
 
-    Ok((input_file_path, settings))
+    Ok((input_file_paths, settings))
+}
+
+/// Replaces each directory in `paths` with the CSV files in it, sorted by file name.
+fn expand_input_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+
+    let mut expanded: Vec<PathBuf> = Vec::with_capacity(paths.len());
+
+    for path in paths.iter() {
+
+        if !path.is_dir() {
+            expanded.push(path.clone());
+            continue
+        }
+
+        let mut csv_files: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?
+            .into_iter()
+            .filter(|file| file.is_file() && file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")))
+            .collect();
+
+        if csv_files.is_empty() {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: The directory to import ({}) has no CSV files.", path.display()))
+        }
+
+        csv_files.sort();
+        expanded.extend(csv_files);
+    }
+
+    Ok(expanded)
+}
+
+/// Exits if `format` isn't a strftime format string that a complete date can be parsed back out of.
+fn validate_date_format(var: &str, format: &str) {
+
+    let has_invalid_specifier = StrftimeItems::new(format).any(|item| item == Item::Error);
+
+    let round_trips = !has_invalid_specifier && {
+        let sample = NaiveDate::from_ymd_opt(2021, 1, 5).unwrap();
+        NaiveDate::parse_from_str(&sample.format(format).to_string(), format) == Ok(sample)
+    };
+
+    if !round_trips {
+        run_result::fatal(ExitCode::ParseError, &format!("FATAL: Environment variable for {} ({}) must be a strftime format string including the year, \
+            month and day (i.e., `%b %d, %Y`). See .env.example.", var, format))
+    }
 }
 
 fn wizard_or_not(accept_args: bool, args: ArgsForImportVarsTBD) -> Result<(
@@ -0,0 +1,85 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use directories::ProjectDirs;
+
+
+/// Where the program keeps what it saves between runs: the profiles (in `config`), the price cache (in `cache`) and
+/// the state snapshots (in `data`).  These are the platform's directories for the program (i.e., ~/.config/cryptools,
+/// ~/.cache/cryptools and ~/.local/share/cryptools on Linux, %APPDATA%\cryptools on Windows, and
+/// ~/Library/Application Support/cryptools and ~/Library/Caches/cryptools on macOS), or all one `--state-dir`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StateDirs {
+    pub config: PathBuf,
+    pub cache: PathBuf,
+    pub data: PathBuf,
+}
+
+impl StateDirs {
+
+    /// The `state_dir`, if given, for everything; otherwise, the platform's directories (or .cryptools, in the directory
+    /// the program is run from, if there's no home directory).
+    pub(crate) fn new(state_dir: Option<&Path>) -> StateDirs {
+
+        if let Some(dir) = state_dir {
+            return StateDirs { config: dir.to_path_buf(), cache: dir.to_path_buf(), data: dir.to_path_buf() }
+        }
+
+        match ProjectDirs::from("", "", "cryptools") {
+            Some(dirs) => StateDirs {
+                config: dirs.config_dir().to_path_buf(),
+                cache: dirs.cache_dir().to_path_buf(),
+                data: dirs.data_dir().to_path_buf(),
+            },
+            None => StateDirs::new(Some(Path::new(".cryptools"))),
+        }
+    }
+
+    /// Where the profile `name` (see `Cli::profile`) is saved: `name`.toml, in profiles.
+    pub(crate) fn profile_path(&self, name: &str) -> PathBuf {
+        self.config.join("profiles").join(format!("{}.toml", name))
+    }
+
+    /// The price file of `--cached-prices`.
+    pub(crate) fn price_cache_path(&self) -> PathBuf {
+        self.cache.join("prices.csv")
+    }
+
+    /// Where `--save-snapshot` saves the state of a run holding lots through `through`: state_YYYY-MM-DD.toml, in
+    /// snapshots.
+    pub(crate) fn snapshot_path(&self, through: NaiveDate) -> PathBuf {
+        self.data.join("snapshots").join(format!("state_{}.toml", through.format("%Y-%m-%d")))
+    }
+
+    /// The snapshot of `--resume-latest`: the one holding lots through the latest date, if any has been saved.
+    pub(crate) fn latest_snapshot(&self) -> Option<PathBuf> {
+
+        let entries = fs::read_dir(self.data.join("snapshots")).ok()?;
+
+        //  The names sort by date
+        entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with("state_") && name.ends_with(".toml")
+            }))
+            .max()
+    }
+}
+
+/// The `--state-dir` given in the command line `args`, if any, for use before they're parsed.
+pub(crate) fn state_dir_arg(args: &[OsString]) -> Option<PathBuf> {
+
+    for (idx, arg) in args.iter().enumerate() {
+        let arg = arg.to_string_lossy();
+        if arg == "--state-dir" { return args.get(idx + 1).map(PathBuf::from) }
+        if let Some(dir) = arg.strip_prefix("--state-dir=") { return Some(PathBuf::from(dir)) }
+    }
+
+    None
+}
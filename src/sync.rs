@@ -0,0 +1,279 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crptls::checksums;
+use crptls::core_functions::ImportProcessParameters;
+use crptls::csv_import_accts_txns;
+use crptls::importers::{self, Source};
+
+use crate::run_result::{self, ExitCode};
+use crate::setup;
+use crate::Cli;
+
+
+const COINBASE_API: &str = "https://api.coinbase.com";
+const KRAKEN_API: &str = "https://api.kraken.com";
+
+/// Pulls the transaction history of the `source` exchange (Coinbase or Kraken) from its API, with the read-only API key
+/// and secret of its environment variables (i.e., COINBASE_API_KEY and COINBASE_API_SECRET), and writes it as a CSV
+/// Input File to `file` (or stdout).  With `merge`, only the transactions after the last date of that CSV Input File
+/// are pulled, and they're written merged into it.  Requests are made with `curl`.
+pub(crate) fn sync(source: Source, merge: Option<&Path>, file: Option<&Path>, args: &Cli) -> Result<(), Box<dyn Error>> {
+
+    let settings = input_file_settings(args)?;
+
+    let pages = match source {
+        Source::Coinbase => {
+            let (key, secret) = api_key("COINBASE");
+            coinbase_pages(&key, &secret)?
+        }
+        Source::Kraken => {
+            let (key, secret) = api_key("KRAKEN");
+            kraken_pages(&key, &decode_base64(&secret).ok_or("KRAKEN_API_SECRET isn't base64.")?)?
+        }
+        _ => {
+            run_result::fatal(ExitCode::ParseError, &format!("FATAL: Only Coinbase's and Kraken's APIs can be synced from, not {}'s.", source))
+        }
+    };
+
+    let after = match merge {
+        Some(path) => csv_import_accts_txns::last_txn_date(path, &settings)?,
+        None => None,
+    };
+
+    let synced = importers::api_history_to_input_file(source, &pages, after, &settings)?;
+
+    let output = match merge {
+        Some(path) => csv_import_accts_txns::merge_into_input_file(path, synced, &settings)?,
+        None => synced,
+    };
+
+    write_output(&output, file, &source.to_string())
+}
+
+/// The settings of the CSV Input File written (its home currency and date format), from the environment and `args`.
+pub(crate) fn input_file_settings(args: &Cli) -> Result<ImportProcessParameters, Box<dyn Error>> {
+
+    let cfg = setup::get_env(args)?;
+    let separator = if cfg.date_separator_is_slash { "/" } else { "-" };
+
+    Ok(ImportProcessParameters {
+        home_currency: cfg.home_currency.split(',').next().unwrap_or("USD").trim().to_uppercase(),
+        input_file_date_separator: separator.to_string(),
+        input_file_uses_iso_date_style: cfg.iso_date,
+        input_file_date_format: cfg.date_format.clone(),
+        ..Default::default()
+    })
+}
+
+/// Writes the `output` of the `history` pulled to `file`, or to stdout.
+pub(crate) fn write_output(output: &[u8], file: Option<&Path>, history: &str) -> Result<(), Box<dyn Error>> {
+
+    match file {
+        None => std::io::stdout().lock().write_all(output)?,
+        Some(path) => {
+            fs::write(path, output)?;
+            log::info!("Wrote the {} history to {}.", history, path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// The API key and secret of the `exchange`'s `<exchange>_API_KEY` and `<exchange>_API_SECRET` environment variables.
+fn api_key(exchange: &str) -> (String, String) {
+
+    let var = |name: String| env::var(&name).unwrap_or_else(|_| {
+        run_result::fatal(ExitCode::ParseError, &format!("FATAL: Syncing requires a read-only API key, in the {} environment variable. See .env.example.", name))
+    });
+
+    (var(format!("{}_API_KEY", exchange)), var(format!("{}_API_SECRET", exchange)))
+}
+
+/// The response pages of the `transactions` of every one of the Coinbase accounts of the API `key`, signed with the
+/// `secret` (as a legacy API key's requests are: the HMAC-SHA256 of the timestamp, method and path, in hex).
+fn coinbase_pages(key: &str, secret: &str) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let get_all = |first_path: String| -> Result<Vec<String>, Box<dyn Error>> {
+
+        let mut pages = Vec::new();
+        let mut path = Some(first_path);
+
+        while let Some(this_path) = path {
+
+            let timestamp = unix_time().as_secs().to_string();
+            let signature = hex(&checksums::hmac_sha256(secret.as_bytes(), format!("{}GET{}", timestamp, this_path).as_bytes()));
+
+            let page = curl(&[
+                format!("url = {}", curl_quoted(&format!("{}{}", COINBASE_API, this_path))),
+                format!("header = {}", curl_quoted(&format!("CB-ACCESS-KEY: {}", key))),
+                format!("header = {}", curl_quoted(&format!("CB-ACCESS-SIGN: {}", signature))),
+                format!("header = {}", curl_quoted(&format!("CB-ACCESS-TIMESTAMP: {}", timestamp))),
+                format!("header = {}", curl_quoted("CB-VERSION: 2023-01-01")),
+            ])?;
+
+            let response: Value = serde_json::from_str(&page)?;
+            if let Some(error) = response["errors"].as_array().and_then(|errors| errors.first()) {
+                return Err(format!("Coinbase's API responded with an error: {}", error["message"]).into())
+            }
+
+            path = response["pagination"]["next_uri"].as_str().filter(|uri| !uri.is_empty()).map(|uri| uri.to_string());
+            pages.push(page);
+        }
+
+        Ok(pages)
+    };
+
+    let mut pages = Vec::new();
+
+    for accounts_page in get_all("/v2/accounts?limit=100".to_string())?.iter() {
+
+        let accounts: Value = serde_json::from_str(accounts_page)?;
+
+        for account in accounts["data"].as_array().into_iter().flatten() {
+            let id = account["id"].as_str().ok_or("A Coinbase account has no id.")?;
+            log::info!("  Pulling the transactions of Coinbase account {}...", account["name"].as_str().unwrap_or(id));
+            pages.extend(get_all(format!("/v2/accounts/{}/transactions?limit=100", id))?);
+        }
+    }
+
+    Ok(pages)
+}
+
+/// The response pages of the `TradesHistory` and of the deposits and withdrawals of the `Ledgers` of the Kraken API
+/// `key`, signed with the (decoded) `secret`: the HMAC-SHA512 of the path and the SHA-256 of the nonce and request
+/// body, in base64.
+fn kraken_pages(key: &str, secret: &[u8]) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let mut pages = Vec::new();
+    let mut nonce = unix_time().as_millis();
+
+    for (endpoint, list, params) in [
+        ("TradesHistory", "trades", ""),
+        ("Ledgers", "ledger", "&type=deposit"),
+        ("Ledgers", "ledger", "&type=withdrawal"),
+    ] {
+
+        let path = format!("/0/private/{}", endpoint);
+        let mut offset = 0;
+
+        loop {
+
+            nonce = nonce.max(unix_time().as_millis()) + 1;
+            let body = format!("nonce={}{}&ofs={}", nonce, params, offset);
+
+            let mut message = path.as_bytes().to_vec();
+            message.extend_from_slice(&checksums::sha256(format!("{}{}", nonce, body).as_bytes()));
+            let signature = encode_base64(&checksums::hmac_sha512(secret, &message));
+
+            let page = curl(&[
+                format!("url = {}", curl_quoted(&format!("{}{}", KRAKEN_API, path))),
+                format!("header = {}", curl_quoted(&format!("API-Key: {}", key))),
+                format!("header = {}", curl_quoted(&format!("API-Sign: {}", signature))),
+                format!("data = {}", curl_quoted(&body)),
+            ])?;
+
+            let response: Value = serde_json::from_str(&page)?;
+            if let Some(error) = response["error"].as_array().and_then(|errors| errors.first()) {
+                return Err(format!("Kraken's API responded with an error: {}", error).into())
+            }
+
+            let entries = response["result"][list].as_object().map_or(0, |entries| entries.len());
+            let count = response["result"]["count"].as_u64().unwrap_or(0) as usize;
+            pages.push(page);
+            offset += entries;
+
+            if entries == 0 || offset >= count { break }
+
+            //  Kraken limits the rate of these calls
+            thread::sleep(Duration::from_secs(2));
+        }
+    }
+
+    Ok(pages)
+}
+
+/// The body of the response to the request of the curl `config` lines (each value quoted with `curl_quoted()`),
+/// which are passed on stdin (rather than as arguments), keeping the API key and signature out of the process list.
+pub(crate) fn curl(config: &[String]) -> Result<String, Box<dyn Error>> {
+
+    let mut child = Command::new("curl")
+        .args(["-sS", "--max-time", "30", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run curl: {}", e))?;
+
+    child.stdin.take().unwrap().write_all(config.join("\n").as_bytes())?;
+
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        return Err(format!("The request failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into())
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The `text` as a quoted value of a curl config line.
+pub(crate) fn curl_quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unix_time() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+
+    let mut encoded = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| group | (*byte as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            match idx <= chunk.len() {
+                true => encoded.push(BASE64[(group >> (18 - 6 * idx) & 0x3f) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+
+    let mut bytes = Vec::new();
+    let mut group = 0u32;
+    let mut bits = 0;
+
+    for c in encoded.trim().trim_end_matches('=').bytes() {
+        group = group << 6 | BASE64.iter().position(|b| *b == c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
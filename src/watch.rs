@@ -0,0 +1,58 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+
+/// How often the files being watched are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long after a change is noticed before processing again, so that an editor's save has finished.
+const SETTLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Processes `input_file_paths` (with the same command line, less `--watch`), and again whenever any of them changes,
+/// until interrupted (i.e., with Ctrl-C).  Each run is its own process, so a fatal problem with the file (i.e., an
+/// invalid date) ends only that run, and the next change is processed as usual.
+pub(crate) fn watch(input_file_paths: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+
+    let exe = env::current_exe()?;
+    let args: Vec<OsString> = env::args_os().skip(1).filter(|arg| arg != "--watch").collect();
+
+    let modified = || -> Vec<Option<SystemTime>> {
+        input_file_paths.iter().map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok()).collect()
+    };
+
+    let mut last_modified = modified();
+
+    loop {
+
+        let status = Command::new(&exe).args(&args).status()?;
+
+        if !status.success() {
+            println!("\nThe run failed ({}).", status);
+        }
+
+        println!("\nWatching {} for changes (Ctrl-C to stop)...", input_file_paths.iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", "));
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let now_modified = modified();
+            if now_modified != last_modified { break }
+        }
+
+        thread::sleep(SETTLE_INTERVAL);
+        last_modified = modified();
+
+        println!("\nA file changed. Processing again...\n");
+    }
+}
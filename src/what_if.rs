@@ -0,0 +1,95 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::error::Error;
+
+use rust_decimal::Decimal;
+
+use crptls::account::{Account, RawAccount};
+use crptls::core_functions::{self, ImportProcessParameters, Timings};
+use crptls::costing_method::InventoryCostingMethod;
+use crptls::summary::{self, YearGains};
+use crptls::transaction::{Transaction, ActionRecord};
+
+
+/// Processes `input_file_paths` again with every transaction (none of the `excluded_txns` left out), from freshly
+/// imported maps, and prints the realized gain or loss of each year with and without the excluded transactions, and the
+/// difference.  The maps are those of the run without them.
+pub(crate) fn print_exclusion_delta(
+    settings: &ImportProcessParameters,
+    input_file_paths: &[PathBuf],
+    raw_acct_map: &HashMap<u16, RawAccount>,
+    acct_map: &HashMap<u16, Account>,
+    ars: &HashMap<u32, ActionRecord>,
+    txns_map: &HashMap<u32, Transaction>,
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+
+    //  The lots of each disposal would have to be picked all over again
+    if settings.costing_method == InventoryCostingMethod::SpecificIdentification {
+        println!("\nThe change in the realized gain/loss from excluding transactions isn't shown under specific identification.");
+        return Ok(())
+    }
+
+    log::info!("\nProcessing again with every transaction, for the change from excluding {:?}...", settings.excluded_txns);
+
+    let mut all_settings = settings.clone();
+    all_settings.excluded_txns.clear();
+
+    let (
+        all_raw_acct_map,
+        all_account_map,
+        all_action_records_map,
+        all_transactions_map,
+    ) = core_functions::import_and_process_final(input_file_paths, &all_settings, timings)?;
+
+    let gains_of = |raw_acct_map, acct_map, ars, txns_map| summary::gains_by_year(
+        &settings.home_currency,
+        settings.long_term_days,
+        settings.fiscal_year_start_month,
+        raw_acct_map,
+        acct_map,
+        ars,
+        txns_map,
+    );
+
+    let with_all = gains_of(&all_raw_acct_map, &all_account_map, &all_action_records_map, &all_transactions_map)?;
+    let without = gains_of(raw_acct_map, acct_map, ars, txns_map)?;
+
+    print_delta(settings, &with_all, &without);
+
+    Ok(())
+}
+
+/// Prints the realized gain or loss of each year, and in total, with every transaction and without the excluded ones.
+fn print_delta(settings: &ImportProcessParameters, with_all: &[YearGains], without: &[YearGains]) {
+
+    let mut years: Vec<i32> = with_all.iter().chain(without.iter())
+        .map(|g| g.year)
+        .filter(|year| settings.tax_year.is_none_or(|tax_year| *year == tax_year))
+        .collect();
+    years.sort();
+    years.dedup();
+
+    let year_gain = |gains: &[YearGains], year: i32| -> Decimal {
+        gains.iter().filter(|g| g.year == year).map(|g| g.realized_total()).sum()
+    };
+
+    let excluded: Vec<String> = settings.excluded_txns.iter().map(|txn_num| txn_num.to_string()).collect();
+
+    println!("\nRealized gain/loss without transaction(s) {} ({}):", excluded.join(", "), settings.home_currency);
+    println!("  {:<10} {:>16} {:>16} {:>16}", "Year", "All txns", "Without", "Change");
+
+    let (mut total_all, mut total_without) = (Decimal::ZERO, Decimal::ZERO);
+
+    for year in years.iter() {
+        let (all, less) = (year_gain(with_all, *year), year_gain(without, *year));
+        total_all += all;
+        total_without += less;
+        println!("  {:<10} {:>16.2} {:>16.2} {:>16.2}", year, all, less, less - all);
+    }
+
+    println!("  {:<10} {:>16.2} {:>16.2} {:>16.2}", "Total", total_all, total_without, total_without - total_all);
+}
@@ -2,14 +2,21 @@
 // Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
 
 use std::error::Error;
+use std::fs;
 use std::process;
 use std::io::{self, BufRead};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use rust_decimal::Decimal;
+
+use crptls::corrections::{self, Correction, CorrectedField, CorrectionTarget};
 use crptls::costing_method::InventoryCostingMethod;
+use crptls::input_error::InputError;
+use crptls::unresolved::UnresolvedRow;
 
 use crate::cli_user_choices;
 use crate::setup::ArgsForImportVarsTBD;
+use crate::state_dirs::StateDirs;
 
 
 pub(crate) fn wizard(args: ArgsForImportVarsTBD) -> Result<(
@@ -37,9 +44,72 @@ pub(crate) fn wizard(args: ArgsForImportVarsTBD) -> Result<(
 
     let (should_export, output_dir_path) = export_reports_to_output_dir(args.output_dir_path)?;
 
+    save_profile(
+        &args.state_dirs,
+        args.profile_settings,
+        &costing_method_choice,
+        like_kind_election.then_some(like_kind_cutoff_date_string.as_str()),
+        should_export,
+        &output_dir_path,
+    )?;
+
     Ok((costing_method_choice, like_kind_election, like_kind_cutoff_date_string, should_export, output_dir_path.to_path_buf()))
 }
 
+/// Offers to save the wizard's answers, with the `settings` (as config file keys and values), to a profile, which a
+/// later run can reuse (skipping the wizard) with `--profile`.
+fn save_profile(
+    state_dirs: &StateDirs,
+    mut settings: Vec<(&'static str, toml::Value)>,
+    costing_method: &InventoryCostingMethod,
+    like_kind_cutoff_date: Option<&str>,
+    should_export: bool,
+    output_dir_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+
+    println!("\nSave these answers to a profile, to reuse with `--profile NAME`? Enter a NAME, or leave blank to skip: ");
+
+    let mut input = String::new();
+    let stdin = io::stdin();
+    stdin.lock().read_line(&mut input)?;
+
+    let name = input.trim();
+
+    if name.is_empty() { return Ok(()) }
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        println!("A profile's name may only have letters, numbers, '-' and '_'. The profile wasn't saved.");
+        return Ok(())
+    }
+
+    let choice = (1..=11).find(|choice| InventoryCostingMethod::from_choice(&choice.to_string()).as_ref() == Some(costing_method))
+        .ok_or("The costing method has no number.")?;
+
+    settings.push(("inv_costing_method", toml::Value::String(choice.to_string())));
+    if let Some(date) = like_kind_cutoff_date {
+        settings.push(("lk_cutoff_date", toml::Value::String(date.to_string())));
+    }
+
+    let mut flags = toml::value::Table::new();
+    flags.insert("accept".to_string(), toml::Value::Boolean(true));
+    flags.insert("output".to_string(), toml::Value::String(output_dir_path.to_string_lossy().to_string()));
+    if !should_export {
+        flags.insert("suppress".to_string(), toml::Value::Boolean(true));
+    }
+
+    let mut profile: toml::value::Table = settings.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+    profile.insert("flags".to_string(), toml::Value::Table(flags));
+
+    let path = state_dirs.profile_path(name);
+
+    if let Some(dir) = path.parent() { fs::create_dir_all(dir)? }
+    fs::write(&path, toml::to_string(&toml::Value::Table(profile))?)?;
+
+    println!("Saved the profile to {}.", path.display());
+
+    Ok(())
+}
+
 pub fn shall_we_proceed() -> Result<(), Box<dyn Error>> {
 
     println!("\n  Shall we proceed? [Y/n] ");
@@ -96,4 +166,150 @@ fn export_reports_to_output_dir(output_dir_path: PathBuf) -> Result<(bool, PathB
     }
 
     Ok((choice, path))
-}
\ No newline at end of file
+}
+/// Walks the user through resolving each of the `unresolved` rows not already corrected in the `corrections_path`
+/// file, saving each decision to it as a correction of the row's proceeds, so a later run (with `--corrections`)
+/// needn't ask again.  A row may be valued, kept as is (saved as proceeds of zero), or skipped for now.  Returns the
+/// number of corrections saved.
+pub(crate) fn resolve_unresolved_rows(
+    unresolved: &[UnresolvedRow],
+    home_currency: &str,
+    corrections_path: &Path,
+) -> Result<usize, Box<dyn Error>> {
+
+    let corrected: Vec<usize> = match corrections_path.exists() {
+        true => corrections::import_corrections(corrections_path)?.iter().filter_map(|c| c.row()).collect(),
+        false => Vec::new(),
+    };
+
+    let unresolved: Vec<&UnresolvedRow> = unresolved.iter().filter(|row| !corrected.contains(&row.row)).collect();
+
+    if unresolved.is_empty() { return Ok(0) }
+
+    println!("\n{} row(s) of the input file couldn't be fully classified. Each decision is saved to {}.",
+        unresolved.len(), corrections_path.display());
+
+    let mut saved = 0;
+
+    for row in unresolved {
+
+        println!("\n  Row {} (txn {}, {}): {} {} in {} ({:?}): {}.",
+            row.row, row.tx_number, row.date, row.amount, row.ticker, row.account, row.memo, row.reason);
+        println!("  Enter its proceeds in {}, or 'k' to keep it as is, 's' to skip it for now or 'q' to stop: ", home_currency);
+
+        let value = match _resolve()? {
+            Resolution::Proceeds(proceeds) => proceeds,
+            Resolution::Keep => "0".to_string(),
+            Resolution::Skip => continue,
+            Resolution::Stop => break,
+        };
+
+        let correction = Correction { target: CorrectionTarget::Row(row.row), field: CorrectedField::Proceeds, value };
+        corrections::append_correction(corrections_path, &correction)?;
+        saved += 1;
+    }
+
+    enum Resolution { Proceeds(String), Keep, Skip, Stop }
+
+    fn _resolve() -> Result<Resolution, Box<dyn Error>> {
+
+        let mut input = String::new();
+        let stdin = io::stdin();
+        stdin.lock().read_line(&mut input)?;
+
+        match input.trim().to_ascii_lowercase().as_str() {
+
+            "k" | "keep" => { Ok(Resolution::Keep) },
+            "s" | "skip" => { Ok(Resolution::Skip) },
+            "q" | "quit" | "stop" => { Ok(Resolution::Stop) },
+            other if other.replace(',', "").parse::<Decimal>().is_ok_and(|p| p >= Decimal::ZERO) => {
+                Ok(Resolution::Proceeds(other.replace(',', "")))
+            },
+            _   => { println!("Please respond with a number of zero or more, or 'k', 's' or 'q'."); _resolve() }
+        }
+    }
+
+    Ok(saved)
+}
+
+/// Shows the `error` a row of the input file failed to import with, and asks whether to skip the row, edit the field
+/// at fault (if the error names one), or abort, saving the decision to the `corrections_path` file as a correction, so
+/// the file_to_import may be imported again with it (and a later run, with `--corrections`, needn't ask again).
+/// Returns whether a correction was saved (or `false`, to abort).
+pub(crate) fn recover_from_input_error(error: &InputError, corrections_path: &Path) -> Result<bool, Box<dyn Error>> {
+
+    println!("\n{}\n", error);
+
+    let field = error.field;
+    match field {
+        Some(field) => println!("  Enter 's' to skip row {}, 'e' to edit its {} or 'a' to abort: ", error.row, field_name(field)),
+        None => println!("  Enter 's' to skip row {} or 'a' to abort: ", error.row),
+    }
+
+    let correction = match (_recover()?, field) {
+        (Recovery::Skip, _) => Correction { target: CorrectionTarget::Row(error.row), field: CorrectedField::Skip, value: String::new() },
+        (Recovery::Edit, Some(field)) => Correction { target: CorrectionTarget::Row(error.row), field, value: _edit(error, field)? },
+        (Recovery::Edit, None) => {
+            println!("  There's no one field of row {} to edit.", error.row);
+            return recover_from_input_error(error, corrections_path)
+        }
+        (Recovery::Abort, _) => return Ok(false),
+    };
+
+    corrections::append_correction(corrections_path, &correction)?;
+    println!("  Saved to {}.", corrections_path.display());
+
+    fn field_name(field: CorrectedField) -> String {
+        match field {
+            CorrectedField::Quantity(account_num) => format!("quantity for account {}", account_num),
+            _ => field.to_string(),
+        }
+    }
+
+    enum Recovery { Skip, Edit, Abort }
+
+    fn _recover() -> Result<Recovery, Box<dyn Error>> {
+
+        let mut input = String::new();
+        let stdin = io::stdin();
+        stdin.lock().read_line(&mut input)?;
+
+        match input.trim().to_ascii_lowercase().as_str() {
+
+            "s" | "skip" => { Ok(Recovery::Skip) },
+            "e" | "edit" => { Ok(Recovery::Edit) },
+            "a" | "abort" | "q" | "quit" => { Ok(Recovery::Abort) },
+            _   => { println!("Please respond with 's', 'e' or 'a'."); _recover() }
+        }
+    }
+
+    //  The field is edited in place, starting from its value in the file (but for a txDate, which is entered anew,
+    //  as YYYY-MM-DD), until it's a value the field may take
+    fn _edit(error: &InputError, field: CorrectedField) -> Result<String, Box<dyn Error>> {
+
+        let mut rl = rustyline::DefaultEditor::new()?;
+        let initial = match field {
+            CorrectedField::TxDate => { println!("  Enter the txDate as YYYY-MM-DD."); String::new() },
+            _ => error.value.clone().unwrap_or_default(),
+        };
+
+        loop {
+            let value = rl.readline_with_initial(&format!("  {}: ", field_name(field)), (&initial, ""))?;
+            let correction = Correction { target: CorrectionTarget::Row(error.row), field, value: value.trim().to_string() };
+            match correction.validate() {
+                Ok(()) => return Ok(correction.value),
+                Err(e) => println!("  {}", e),
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Where the decisions resolving rows are saved without `--corrections`: corrections.csv, beside the (first) input file.
+pub(crate) fn default_corrections_path(input_file_paths: &[PathBuf]) -> PathBuf {
+    input_file_paths.first()
+        .and_then(|path| path.parent())
+        .map(|dir| dir.join("corrections.csv"))
+        .unwrap_or_else(|| PathBuf::from("corrections.csv"))
+}
@@ -0,0 +1,64 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  What the tests that run the program share.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+
+/// A new, empty directory for one test's runs (named for the test and the process, so that concurrent runs of the
+/// tests don't share it), removed once dropped.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("cryptools_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The sample input examples/resources/`name`.
+pub fn sample_input(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/resources").join(name)
+}
+
+/// Writes the header rows of the faker1 sample input to `path`, with its transactions dated in the year 2016 months
+/// of `months`.
+pub fn write_faker1_part(path: &Path, months: std::ops::RangeInclusive<u32>) {
+    let input = fs::read_to_string(sample_input("faker1__sample_input.csv")).unwrap();
+    let part: Vec<&str> = input.lines().enumerate()
+        .filter(|(idx, line)| *idx < 4 || months.contains(&line.split('-').next().unwrap().parse().unwrap()))
+        .map(|(_idx, line)| line)
+        .collect();
+    fs::write(path, part.join("\n")).unwrap();
+}
+
+/// Runs the program with `-a` and `args`, then the `input`, exporting to `dir` (in which it's run, so that no .env
+/// file of the checkout is read).
+pub fn run(dir: &Path, args: &[&str], input: &Path) -> Output {
+    run_inputs(dir, args, &[input])
+}
+
+/// Runs the program as `run()` does, but with several `inputs`, to be merged.
+pub fn run_inputs(dir: &Path, args: &[&str], inputs: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cryptools"))
+        .current_dir(dir)
+        .arg("-a")
+        .arg("-o").arg(dir)
+        .args(args)
+        .args(inputs)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,75 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Imports a history in two parts into a `--db` database, as an incremental import would.  These need the `sqlite3`
+//  program, as `--db` does, so are skipped without it.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+mod common;
+use common::TempDir;
+
+
+fn has_sqlite3() -> bool {
+    let found = Command::new("sqlite3").arg("-version").output().is_ok_and(|output| output.status.success());
+    if !found { eprintln!("Skipped: sqlite3 isn't installed.") }
+    found
+}
+
+/// What `sqlite3` prints for the `query` of the database at `db`.
+fn query(db: &Path, query: &str) -> String {
+    let output = Command::new("sqlite3").arg("-batch").arg(db).arg(query).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn second_import_resumes_from_the_first_and_keeps_both_runs() {
+
+    if !has_sqlite3() { return }
+
+    let dir = TempDir::new("db");
+    let db = dir.0.join("cryptools.db");
+    let db_arg = db.to_str().unwrap();
+
+    let (first_part, second_part) = (dir.0.join("first.csv"), dir.0.join("second.csv"));
+    common::write_faker1_part(&first_part, 1..=7);
+    common::write_faker1_part(&second_part, 8..=12);
+
+    for (part, out) in [(&first_part, "first"), (&second_part, "second")] {
+        let out = dir.0.join(out);
+        fs::create_dir(&out).unwrap();
+        let output = common::run(&out, &["--db", db_arg], part);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stdout));
+    }
+
+    assert_eq!(query(&db, "SELECT run_id, through FROM runs;"), "1|2016-07-01\n2|2016-12-01\n");
+    assert_eq!(query(&db, "SELECT COUNT(DISTINCT run_id) FROM movements;"), "2\n");
+
+    //  The open lots after the second part are those of the whole history processed at once
+    let whole = dir.0.join("whole");
+    fs::create_dir(&whole).unwrap();
+    let state = whole.join("state.toml");
+    let input = common::sample_input("faker1__sample_input.csv");
+    let output = common::run(&whole, &["--save-state", state.to_str().unwrap()], &input);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stdout));
+
+    assert_eq!(query(&db, "SELECT state FROM runs WHERE run_id = 2;").trim(), fs::read_to_string(&state).unwrap().trim());
+}
+
+#[test]
+fn db_that_isnt_a_cryptools_database_is_an_error() {
+
+    if !has_sqlite3() { return }
+
+    let dir = TempDir::new("db_other");
+    let db = dir.0.join("other.db");
+    query(&db, "CREATE TABLE other (x INTEGER);");
+
+    let output = common::run(&dir.0, &["--db", db.to_str().unwrap()], &common::sample_input("faker1__sample_input.csv"));
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no such table: runs"));
+}
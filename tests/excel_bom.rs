@@ -0,0 +1,63 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Checks which exported files begin with the UTF-8 byte order mark: every CSV file with `--excel-bom`, and no
+//  other file, nor a report written to stdout, which is to be piped into another program.
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::TempDir;
+
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The exported files in `dir`, by name, and whether each begins with the BOM.
+fn files_with_bom(dir: &Path) -> Vec<(String, bool)> {
+    let mut files: Vec<(String, bool)> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.file_name().unwrap() != "run_result.json")
+        .map(|path| (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read(&path).unwrap().starts_with(BOM)))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn no_file_begins_with_the_bom_by_default() {
+
+    let dir = TempDir::new("bom_default");
+    let input = common::sample_input("faker1__sample_input.csv");
+    assert_eq!(common::run(&dir.0, &[], &input).status.code(), Some(0));
+
+    let files = files_with_bom(&dir.0);
+    assert!(files.iter().any(|(name, _)| name.ends_with(".csv")));
+    for (name, bom) in files {
+        assert!(!bom, "{} begins with the BOM", name);
+    }
+}
+
+#[test]
+fn every_csv_file_begins_with_the_bom_with_excel_bom() {
+
+    let dir = TempDir::new("bom_excel");
+    let input = common::sample_input("faker1__sample_input.csv");
+    assert_eq!(common::run(&dir.0, &["--excel-bom"], &input).status.code(), Some(0));
+
+    for (name, bom) in files_with_bom(&dir.0) {
+        assert_eq!(bom, name.ends_with(".csv"), "{}", name);
+    }
+}
+
+#[test]
+fn report_written_to_stdout_never_begins_with_the_bom() {
+
+    let dir = TempDir::new("bom_stdout");
+    let input = common::sample_input("faker1__sample_input.csv");
+    let output = common::run(&dir.0, &["--excel-bom", "--stdout", "accounts"], &input);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    assert!(output.stdout.starts_with(b"Account,"), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(files_with_bom(&dir.0).is_empty());
+}
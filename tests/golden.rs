@@ -0,0 +1,205 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Exports every report format for each sample input in examples/resources, and compares each file with the one
+//  saved in tests/golden/FIXTURE/, so that any change to what's exported shows up as a failing test.  A change that's
+//  meant to change the reports saves them again with:
+//
+//      UPDATE_GOLDEN=1 cargo test --test golden
+//
+//  and the difference is then reviewed in the diff of tests/golden/.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use cryptools::crptls::core_functions::{ImportProcessParameters, Timings};
+use cryptools::export::export_all::Report;
+use cryptools::export::{export_csv, export_html, export_json, export_pdf, export_sql, export_xlsx};
+use cryptools::Options;
+
+
+/// Each fixture's name, its CSV Input File, and the settings it's processed with.
+fn fixtures() -> Vec<(&'static str, &'static str, ImportProcessParameters)> {
+    vec![
+        ("faker1", "examples/resources/faker1__sample_input.csv", ImportProcessParameters::default()),
+        ("faker2", "examples/resources/faker2__sample_input.csv", ImportProcessParameters {
+            input_file_date_separator: "/".to_string(),
+            ..Default::default()
+        }),
+    ]
+}
+
+/// Stand in for the output directory, which the SQL dump names, and the date of the run, which the PDF summary is
+/// `Prepared` on, so that the reports don't differ from one run to the next.
+const EXPORT_DIR: &str = "EXPORT_DIR";
+const PREPARED_DATE: &str = "Prepared TODAY";
+
+#[test]
+fn every_export_format_matches_its_golden_file() {
+
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches: Vec<String> = Vec::new();
+
+    //  One fixture at a time, as the written reports are kept track of for the whole process (see `export_csv`)
+    for (name, input, settings) in fixtures() {
+
+        let dir = std::env::temp_dir().join(format!("cryptools_golden_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let exported = export_every_format(&root.join(input), settings, &dir);
+        let compared = exported.and_then(|()| compare_dir(&dir, &root.join("tests/golden").join(name), update));
+        let _ = fs::remove_dir_all(&dir);
+
+        match compared {
+            Ok(fixture_mismatches) => mismatches.extend(fixture_mismatches.into_iter().map(|m| format!("{}: {}", name, m))),
+            Err(e) => panic!("{}: failed to export or compare the reports: {}", name, e),
+        }
+    }
+
+    assert!(mismatches.is_empty(),
+        "The reports differ from their golden files (UPDATE_GOLDEN=1 saves them, if that's intended):\n{}",
+        mismatches.join("\n"));
+}
+
+/// Writes, for the `input`: every CSV and TXT report (of every group, as with `--reports` naming them all) and journal
+/// (J1-J4), with the PDF summary, the HTML dashboard and the SQL dump, to `dir`; the JSON reports to `dir/json`; and
+/// the workbook to `dir/xlsx`.
+fn export_every_format(input: &Path, settings: ImportProcessParameters, dir: &Path) -> Result<(), Box<dyn Error>> {
+
+    let settings = ImportProcessParameters {
+        should_export: true,
+        export_path: dir.to_path_buf(),
+        ledger_export: true,
+        beancount_export: true,
+        journal_csv_export: true,
+        ..settings
+    };
+
+    let mut options = Options::new(vec![input.to_path_buf()]);
+    options.settings = settings.clone();
+    options.reports = Report::value_variants().to_vec();
+    options.exporters.register(export_pdf::PdfSummary);
+    options.exporters.register(export_html::HtmlDashboard);
+
+    let results = cryptools::run(&options)?;
+    let maps = (&results.raw_acct_map, &results.account_map, &results.action_records_map, &results.transactions_map);
+
+    export_sql::_1_processed_maps_to_sql(&settings, &dir.join("cryptools.sql"), maps.0, maps.1, maps.2, maps.3)?;
+
+    let json_settings = ImportProcessParameters { export_path: dir.join("json"), ..settings.clone() };
+    fs::create_dir_all(&json_settings.export_path)?;
+    export_json::_1_reports_to_json(&json_settings, maps.0, maps.1, maps.2, maps.3)?;
+
+    let xlsx_settings = ImportProcessParameters { export_path: dir.join("xlsx"), ..settings };
+    fs::create_dir_all(&xlsx_settings.export_path)?;
+    export_xlsx::_1_reports_to_xlsx(&xlsx_settings, &options.reports, maps.0, maps.1, maps.2, maps.3, &mut Timings::default())?;
+
+    export_csv::commit_written_reports()?;
+
+    Ok(())
+}
+
+/// Compares each file under `exported` with the one of the same path under `golden`, returning a line for each that
+/// differs, is missing or is extra.  With `update`, the `golden` directory is replaced by the `exported` files instead.
+fn compare_dir(exported: &Path, golden: &Path, update: bool) -> Result<Vec<String>, Box<dyn Error>> {
+
+    let exported_files = files_under(exported)?;
+    let mut mismatches = Vec::new();
+
+    if update {
+        let _ = fs::remove_dir_all(golden);
+        for file in exported_files.iter() {
+            fs::create_dir_all(golden.join(file).parent().unwrap())?;
+            fs::write(golden.join(file), normalized(exported, &exported.join(file))?)?;
+        }
+        return Ok(mismatches)
+    }
+
+    let golden_files = if golden.exists() { files_under(golden)? } else { Vec::new() };
+
+    for file in exported_files.iter() {
+
+        if !golden_files.contains(file) {
+            mismatches.push(format!("{} has no golden file", file.display()));
+            continue
+        }
+
+        let expected = fs::read(golden.join(file))?;
+        let actual = normalized(exported, &exported.join(file))?;
+
+        if actual != expected {
+            mismatches.push(format!("{} differs{}", file.display(), first_difference(&expected, &actual)));
+        }
+    }
+
+    for file in golden_files.iter().filter(|file| !exported_files.contains(file)) {
+        mismatches.push(format!("{} wasn't exported", file.display()));
+    }
+
+    Ok(mismatches)
+}
+
+/// The relative paths of the files under `dir`, sorted.
+fn files_under(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(next) = dirs.pop() {
+        for entry in fs::read_dir(&next)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path.strip_prefix(dir)?.to_path_buf());
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// The contents of the exported `file`, with the `export_dir` and today's `Prepared` date replaced (in a text file).
+fn normalized(export_dir: &Path, file: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+
+    let contents = fs::read(file)?;
+    let prepared = format!("Prepared {}", chrono::Local::now().date_naive());
+
+    Ok(match String::from_utf8(contents) {
+        Ok(text) => text
+            .replace(&export_dir.display().to_string(), EXPORT_DIR)
+            .replace(&prepared, PREPARED_DATE)
+            .into_bytes(),
+        Err(e) => e.into_bytes(),
+    })
+}
+
+/// Where a text file first differs (i.e., `: line 12 was "..." and is now "..."`), or nothing, for a binary file.
+fn first_difference(expected: &[u8], actual: &[u8]) -> String {
+
+    let (Ok(expected), Ok(actual)) = (std::str::from_utf8(expected), std::str::from_utf8(actual)) else {
+        return String::new()
+    };
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+
+    for line_num in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => break,
+            (expected_line, actual_line) if expected_line != actual_line => {
+                return format!(": line {} was {:?} and is now {:?}",
+                    line_num, expected_line.unwrap_or_default(), actual_line.unwrap_or_default())
+            }
+            _ => {}
+        }
+    }
+
+    String::new()
+}
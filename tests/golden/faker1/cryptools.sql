@@ -0,0 +1,99 @@
+-- Processed by cryptools. Load with: sqlite3 cryptools.db < EXPORT_DIR/cryptools.sql
+BEGIN TRANSACTION;
+DROP TABLE IF EXISTS movements;
+DROP TABLE IF EXISTS lots;
+DROP TABLE IF EXISTS action_records;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS raw_accounts;
+DROP TABLE IF EXISTS run;
+CREATE TABLE run (home_currency TEXT, costing_method TEXT, lk_treatment_enabled INTEGER, lk_cutoff_date TEXT);
+CREATE TABLE raw_accounts (account_num INTEGER PRIMARY KEY, name TEXT, ticker TEXT, is_margin INTEGER);
+CREATE TABLE transactions (tx_number INTEGER PRIMARY KEY, date TEXT, basis_date TEXT, memo TEXT, proceeds TEXT, fee TEXT, kind TEXT, income_type TEXT);
+CREATE TABLE action_records (ar_number INTEGER PRIMARY KEY, tx_number INTEGER REFERENCES transactions, account_num INTEGER REFERENCES raw_accounts, amount TEXT);
+CREATE TABLE lots (account_num INTEGER REFERENCES raw_accounts, lot_number INTEGER, lot_id TEXT, date_acquired TEXT, basis_date TEXT, PRIMARY KEY (account_num, lot_number));
+CREATE TABLE movements (account_num INTEGER, lot_number INTEGER, tx_number INTEGER REFERENCES transactions, ar_number INTEGER REFERENCES action_records, date TEXT, amount TEXT, cost_basis TEXT, proceeds TEXT, cost_basis_lk TEXT, proceeds_lk TEXT, FOREIGN KEY (account_num, lot_number) REFERENCES lots);
+INSERT INTO run VALUES ('USD', 'LIFO by lot creation date', 0, NULL);
+INSERT INTO raw_accounts VALUES (1, 'Bank', 'USD', 0);
+INSERT INTO raw_accounts VALUES (2, 'Exchange', 'BTC', 0);
+INSERT INTO raw_accounts VALUES (3, 'Wallet', 'BTC', 0);
+INSERT INTO raw_accounts VALUES (4, 'Exchange', 'XMR', 0);
+INSERT INTO raw_accounts VALUES (5, 'Simplewallet', 'XMR', 0);
+INSERT INTO transactions VALUES (1, '2016-02-01', '2016-02-01', 'FIRST', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (2, '2016-03-01', '2016-03-01', 'SECOND', '250', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (3, '2016-04-01', '2016-04-01', 'THIRD', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (4, '2016-05-01', '2016-05-01', 'FOURTH', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (5, '2016-05-02', '2016-05-02', 'FIFTH', '160', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (6, '2016-06-01', '2016-06-01', 'SIXTH', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (7, '2016-07-01', '2016-07-01', 'SEVENTH', '200', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (8, '2016-08-01', '2016-08-01', 'EIGHTH', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (9, '2016-09-01', '2016-09-01', 'NINTH', '400', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (10, '2016-10-01', '2016-10-01', 'TENTH', '900', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (11, '2016-11-01', '2016-11-01', 'ELEVENTH', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (12, '2016-12-01', '2016-12-01', 'TWELFTH', '2000', '0', 'standard', NULL);
+INSERT INTO action_records VALUES (1, 1, 1, '-220');
+INSERT INTO action_records VALUES (2, 1, 2, '0.25');
+INSERT INTO action_records VALUES (3, 2, 2, '-0.25');
+INSERT INTO action_records VALUES (4, 2, 4, '180');
+INSERT INTO action_records VALUES (5, 3, 4, '-90');
+INSERT INTO action_records VALUES (6, 3, 5, '90');
+INSERT INTO action_records VALUES (7, 4, 4, '90');
+INSERT INTO action_records VALUES (8, 4, 5, '-90');
+INSERT INTO action_records VALUES (9, 5, 2, '0.3');
+INSERT INTO action_records VALUES (10, 5, 4, '-90');
+INSERT INTO action_records VALUES (11, 6, 2, '-0.3');
+INSERT INTO action_records VALUES (12, 6, 3, '0.3');
+INSERT INTO action_records VALUES (13, 7, 2, '0.7');
+INSERT INTO action_records VALUES (14, 7, 4, '-90');
+INSERT INTO action_records VALUES (15, 8, 2, '0.3');
+INSERT INTO action_records VALUES (16, 8, 3, '-0.3');
+INSERT INTO action_records VALUES (17, 9, 2, '-0.5');
+INSERT INTO action_records VALUES (18, 9, 4, '200');
+INSERT INTO action_records VALUES (19, 10, 2, '1');
+INSERT INTO action_records VALUES (20, 10, 4, '-200');
+INSERT INTO action_records VALUES (21, 11, 2, '-1.5');
+INSERT INTO action_records VALUES (22, 11, 3, '1.5');
+INSERT INTO action_records VALUES (23, 12, 3, '-1.5');
+INSERT INTO action_records VALUES (24, 12, 5, '400');
+INSERT INTO lots VALUES (1, 1, 'A1-L1', '2016-02-01', '2016-02-01');
+INSERT INTO movements VALUES (1, 1, 1, 1, '2016-02-01', '-220', '-220', '220', '-220', '220');
+INSERT INTO lots VALUES (2, 1, 'A2-L1', '2016-02-01', '2016-02-01');
+INSERT INTO movements VALUES (2, 1, 1, 2, '2016-02-01', '0.25', '220', '-220', '220', '-220');
+INSERT INTO movements VALUES (2, 1, 2, 3, '2016-03-01', '-0.25', '-220', '250', '-220', '250');
+INSERT INTO lots VALUES (2, 2, 'A2-L2', '2016-05-02', '2016-05-02');
+INSERT INTO movements VALUES (2, 2, 5, 9, '2016-05-02', '0.3', '160.0', '-160.0', '160.0', '-160.0');
+INSERT INTO movements VALUES (2, 2, 6, 11, '2016-06-01', '-0.3', '-160.0', '160.0', '-160.0', '160.0');
+INSERT INTO lots VALUES (2, 3, 'A2-L3', '2016-07-01', '2016-07-01');
+INSERT INTO movements VALUES (2, 3, 7, 13, '2016-07-01', '0.7', '200.0', '-200.0', '200.0', '-200.0');
+INSERT INTO movements VALUES (2, 3, 9, 17, '2016-09-01', '-0.2', '-57.14', '160.00', '-57.14', '160.00');
+INSERT INTO movements VALUES (2, 3, 11, 21, '2016-11-01', '-0.5', '-142.86', '142.86', '-142.86', '142.86');
+INSERT INTO lots VALUES (2, 4, 'A2-L4', '2016-08-01', '2016-06-01');
+INSERT INTO movements VALUES (2, 4, 8, 15, '2016-08-01', '0.3', '160.0', '-160.0', '160.0', '-160.0');
+INSERT INTO movements VALUES (2, 4, 9, 17, '2016-09-01', '-0.3', '-160.0', '240.00', '-160.0', '240.00');
+INSERT INTO lots VALUES (2, 5, 'A2-L5', '2016-10-01', '2016-10-01');
+INSERT INTO movements VALUES (2, 5, 10, 19, '2016-10-01', '1', '900.0', '-900.0', '900.0', '-900.0');
+INSERT INTO movements VALUES (2, 5, 11, 21, '2016-11-01', '-1', '-900.0', '900.0', '-900.0', '900.0');
+INSERT INTO lots VALUES (3, 1, 'A3-L1', '2016-06-01', '2016-05-02');
+INSERT INTO movements VALUES (3, 1, 6, 12, '2016-06-01', '0.3', '160.0', '-160.0', '160.0', '-160.0');
+INSERT INTO movements VALUES (3, 1, 8, 16, '2016-08-01', '-0.3', '-160.0', '160.0', '-160.0', '160.0');
+INSERT INTO lots VALUES (3, 2, 'A3-L2', '2016-11-01', '2016-07-01');
+INSERT INTO movements VALUES (3, 2, 11, 22, '2016-11-01', '0.50000000', '142.86', '-142.86', '142.86', '-142.86');
+INSERT INTO movements VALUES (3, 2, 12, 23, '2016-12-01', '-0.50000000', '-142.86', '666.67', '-142.86', '666.67');
+INSERT INTO lots VALUES (3, 3, 'A3-L3', '2016-11-01', '2016-10-01');
+INSERT INTO movements VALUES (3, 3, 11, 22, '2016-11-01', '1.00000000', '900.0', '-900.0', '900.0', '-900.0');
+INSERT INTO movements VALUES (3, 3, 12, 23, '2016-12-01', '-1.00000000', '-900.0', '1333.33', '-900.0', '1333.33');
+INSERT INTO lots VALUES (4, 1, 'A4-L1', '2016-03-01', '2016-03-01');
+INSERT INTO movements VALUES (4, 1, 2, 4, '2016-03-01', '180', '250.0', '-250.0', '250.0', '-250.0');
+INSERT INTO movements VALUES (4, 1, 3, 5, '2016-04-01', '-90', '-125.00', '125.00', '-125.00', '125.00');
+INSERT INTO movements VALUES (4, 1, 7, 14, '2016-07-01', '-90', '-125.00', '200', '-125.00', '200');
+INSERT INTO lots VALUES (4, 2, 'A4-L2', '2016-05-01', '2016-04-01');
+INSERT INTO movements VALUES (4, 2, 4, 7, '2016-05-01', '90', '125.00', '-125.00', '125.00', '-125.00');
+INSERT INTO movements VALUES (4, 2, 5, 10, '2016-05-02', '-90', '-125.00', '160', '-125.00', '160');
+INSERT INTO lots VALUES (4, 3, 'A4-L3', '2016-09-01', '2016-09-01');
+INSERT INTO movements VALUES (4, 3, 9, 18, '2016-09-01', '200', '400.0', '-400.0', '400.0', '-400.0');
+INSERT INTO movements VALUES (4, 3, 10, 20, '2016-10-01', '-200', '-400.0', '900', '-400.0', '900');
+INSERT INTO lots VALUES (5, 1, 'A5-L1', '2016-04-01', '2016-03-01');
+INSERT INTO movements VALUES (5, 1, 3, 6, '2016-04-01', '90', '125.00', '-125.00', '125.00', '-125.00');
+INSERT INTO movements VALUES (5, 1, 4, 8, '2016-05-01', '-90', '-125.00', '125.00', '-125.00', '125.00');
+INSERT INTO lots VALUES (5, 2, 'A5-L2', '2016-12-01', '2016-12-01');
+INSERT INTO movements VALUES (5, 2, 12, 24, '2016-12-01', '400', '2000.0', '-2000.0', '2000.0', '-2000.0');
+COMMIT;
@@ -0,0 +1,80 @@
+-- Processed by cryptools. Load with: sqlite3 cryptools.db < EXPORT_DIR/cryptools.sql
+BEGIN TRANSACTION;
+DROP TABLE IF EXISTS movements;
+DROP TABLE IF EXISTS lots;
+DROP TABLE IF EXISTS action_records;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS raw_accounts;
+DROP TABLE IF EXISTS run;
+CREATE TABLE run (home_currency TEXT, costing_method TEXT, lk_treatment_enabled INTEGER, lk_cutoff_date TEXT);
+CREATE TABLE raw_accounts (account_num INTEGER PRIMARY KEY, name TEXT, ticker TEXT, is_margin INTEGER);
+CREATE TABLE transactions (tx_number INTEGER PRIMARY KEY, date TEXT, basis_date TEXT, memo TEXT, proceeds TEXT, fee TEXT, kind TEXT, income_type TEXT);
+CREATE TABLE action_records (ar_number INTEGER PRIMARY KEY, tx_number INTEGER REFERENCES transactions, account_num INTEGER REFERENCES raw_accounts, amount TEXT);
+CREATE TABLE lots (account_num INTEGER REFERENCES raw_accounts, lot_number INTEGER, lot_id TEXT, date_acquired TEXT, basis_date TEXT, PRIMARY KEY (account_num, lot_number));
+CREATE TABLE movements (account_num INTEGER, lot_number INTEGER, tx_number INTEGER REFERENCES transactions, ar_number INTEGER REFERENCES action_records, date TEXT, amount TEXT, cost_basis TEXT, proceeds TEXT, cost_basis_lk TEXT, proceeds_lk TEXT, FOREIGN KEY (account_num, lot_number) REFERENCES lots);
+INSERT INTO run VALUES ('USD', 'LIFO by lot creation date', 0, NULL);
+INSERT INTO raw_accounts VALUES (1, 'Bank', 'USD', 0);
+INSERT INTO raw_accounts VALUES (2, 'Exchange', 'BTC', 0);
+INSERT INTO raw_accounts VALUES (3, 'Wallet', 'BTC', 0);
+INSERT INTO raw_accounts VALUES (4, 'Exchange', 'XMR', 0);
+INSERT INTO raw_accounts VALUES (5, 'Simplewallet', 'XMR', 0);
+INSERT INTO transactions VALUES (1, '2016-02-01', '2016-02-01', 'Mining income', '220', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (2, '2016-03-01', '2016-03-01', 'Bought $25 Satoshi coffee mug', '25', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (3, '2016-04-01', '2016-04-01', 'Bought back for less', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (4, '2016-05-01', '2016-05-01', 'Traded for Monero', '125', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (5, '2016-05-02', '2016-05-02', 'Withdrew to my wallet', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (6, '2016-06-01', '2016-06-01', 'Bought more Monero', '150', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (7, '2016-07-01', '2016-07-01', 'Withdrew to my wallet', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (8, '2016-08-01', '2016-08-01', 'Remote tech support income', '320', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (9, '2016-09-01', '2016-09-01', 'Took Monero profit', '700', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (10, '2016-10-01', '2016-10-01', 'Withdrew to my wallet', '0', '0', 'standard', NULL);
+INSERT INTO transactions VALUES (11, '2016-11-01', '2016-11-01', 'Contribute $100 worth to OSS project', '100', '0', 'standard', NULL);
+INSERT INTO action_records VALUES (1, 1, 2, '0.25');
+INSERT INTO action_records VALUES (2, 2, 2, '-0.03');
+INSERT INTO action_records VALUES (3, 3, 1, '-20');
+INSERT INTO action_records VALUES (4, 3, 2, '0.03');
+INSERT INTO action_records VALUES (5, 4, 2, '-0.1');
+INSERT INTO action_records VALUES (6, 4, 4, '600');
+INSERT INTO action_records VALUES (7, 5, 4, '-500');
+INSERT INTO action_records VALUES (8, 5, 5, '499.98');
+INSERT INTO action_records VALUES (9, 6, 2, '-0.1');
+INSERT INTO action_records VALUES (10, 6, 4, '450');
+INSERT INTO action_records VALUES (11, 7, 4, '-400');
+INSERT INTO action_records VALUES (12, 7, 5, '399.98');
+INSERT INTO action_records VALUES (13, 8, 2, '0.2');
+INSERT INTO action_records VALUES (14, 9, 2, '1.2');
+INSERT INTO action_records VALUES (15, 9, 4, '-150');
+INSERT INTO action_records VALUES (16, 10, 2, '-1');
+INSERT INTO action_records VALUES (17, 10, 3, '1');
+INSERT INTO action_records VALUES (18, 11, 5, '-25');
+INSERT INTO lots VALUES (1, 1, 'A1-L1', '2016-04-01', '2016-04-01');
+INSERT INTO movements VALUES (1, 1, 3, 3, '2016-04-01', '-20', '-20', '20', '-20', '20');
+INSERT INTO lots VALUES (2, 1, 'A2-L1', '2016-02-01', '2016-02-01');
+INSERT INTO movements VALUES (2, 1, 1, 1, '2016-02-01', '0.25', '220.0', '-220.0', '220.0', '-220.0');
+INSERT INTO movements VALUES (2, 1, 2, 2, '2016-03-01', '-0.03', '-26.40', '25', '-26.40', '25');
+INSERT INTO movements VALUES (2, 1, 4, 5, '2016-05-01', '-0.07', '-61.60', '87.5', '-61.60', '87.5');
+INSERT INTO movements VALUES (2, 1, 6, 9, '2016-06-01', '-0.1', '-88.00', '150', '-88.00', '150');
+INSERT INTO lots VALUES (2, 2, 'A2-L2', '2016-04-01', '2016-04-01');
+INSERT INTO movements VALUES (2, 2, 3, 4, '2016-04-01', '0.03', '20', '-20', '20', '-20');
+INSERT INTO movements VALUES (2, 2, 4, 5, '2016-05-01', '-0.03', '-20', '37.5', '-20', '37.5');
+INSERT INTO lots VALUES (2, 3, 'A2-L3', '2016-08-01', '2016-08-01');
+INSERT INTO movements VALUES (2, 3, 8, 13, '2016-08-01', '0.2', '320.0', '-320.0', '320.0', '-320.0');
+INSERT INTO lots VALUES (2, 4, 'A2-L4', '2016-09-01', '2016-09-01');
+INSERT INTO movements VALUES (2, 4, 9, 14, '2016-09-01', '1.2', '700.0', '-700.0', '700.0', '-700.0');
+INSERT INTO movements VALUES (2, 4, 10, 16, '2016-10-01', '-1', '-583.33', '583.33', '-583.33', '583.33');
+INSERT INTO lots VALUES (3, 1, 'A3-L1', '2016-10-01', '2016-09-01');
+INSERT INTO movements VALUES (3, 1, 10, 17, '2016-10-01', '1', '583.33', '-583.33', '583.33', '-583.33');
+INSERT INTO lots VALUES (4, 1, 'A4-L1', '2016-05-01', '2016-05-01');
+INSERT INTO movements VALUES (4, 1, 4, 6, '2016-05-01', '600', '125.0', '-125.0', '125.0', '-125.0');
+INSERT INTO movements VALUES (4, 1, 5, 7, '2016-05-02', '-500', '-104.17', '104.17', '-104.17', '104.17');
+INSERT INTO movements VALUES (4, 1, 9, 15, '2016-09-01', '-100', '-20.83', '466.67', '-20.83', '466.67');
+INSERT INTO lots VALUES (4, 2, 'A4-L2', '2016-06-01', '2016-06-01');
+INSERT INTO movements VALUES (4, 2, 6, 10, '2016-06-01', '450', '150.0', '-150.0', '150.0', '-150.0');
+INSERT INTO movements VALUES (4, 2, 7, 11, '2016-07-01', '-400', '-133.33', '133.33', '-133.33', '133.33');
+INSERT INTO movements VALUES (4, 2, 9, 15, '2016-09-01', '-50', '-16.67', '233.33', '-16.67', '233.33');
+INSERT INTO lots VALUES (5, 1, 'A5-L1', '2016-05-02', '2016-05-01');
+INSERT INTO movements VALUES (5, 1, 5, 8, '2016-05-02', '499.98', '104.17', '-104.17', '104.17', '-104.17');
+INSERT INTO lots VALUES (5, 2, 'A5-L2', '2016-07-01', '2016-06-01');
+INSERT INTO movements VALUES (5, 2, 7, 12, '2016-07-01', '399.98', '133.33', '-133.33', '133.33', '-133.33');
+INSERT INTO movements VALUES (5, 2, 11, 18, '2016-11-01', '-25', '-8.33', '100', '-8.33', '100');
+COMMIT;
@@ -0,0 +1,39 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Uses the program as a library, as another Rust program would.
+
+use std::fs;
+use std::path::Path;
+
+use cryptools::crptls::core_functions::ImportProcessParameters;
+use cryptools::Options;
+
+
+#[test]
+fn run_returns_an_error_rather_than_exiting_when_a_report_cant_be_written() {
+
+    let dir = std::env::temp_dir().join(format!("cryptools_library_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    //  As left by an earlier run, and `overwrite` isn't set
+    let earlier_report = dir.join("C1_Acct_Sum_with_cost_basis.csv");
+    fs::write(&earlier_report, "earlier run").unwrap();
+
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/resources/faker1__sample_input.csv");
+    let mut options = Options::new(vec![input]);
+    options.settings = ImportProcessParameters { should_export: true, export_path: dir.clone(), ..Default::default() };
+
+    let result = cryptools::run(&options);
+    let earlier_report = fs::read_to_string(&earlier_report).unwrap();
+    let left_in_dir = fs::read_dir(&dir).unwrap().count();
+    let _ = fs::remove_dir_all(&dir);
+
+    match result {
+        Ok(_) => panic!("The run should have refused to overwrite the earlier report."),
+        Err(e) => assert!(e.to_string().contains("already exists"), "{}", e),
+    }
+    assert_eq!(earlier_report, "earlier run");
+    assert_eq!(left_in_dir, 1, "A failed run should leave the output directory as it was.");
+}
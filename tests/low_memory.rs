@@ -0,0 +1,74 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Runs the program on each shape of input with and without `--low-memory`, which must only change where the
+//  intermediate CSVs are kept, never what's exported.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod common;
+use common::TempDir;
+
+
+/// The name and contents of each file in `dir`, in name order.
+fn exported(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files: Vec<(PathBuf, Vec<u8>)> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .map(|path| (PathBuf::from(path.file_name().unwrap()), fs::read(&path).unwrap()))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn low_memory_exports_the_same_files_as_a_normal_run() {
+
+    let dir = TempDir::new("low_memory");
+    let accounts = common::sample_input("faker1__accounts.csv");
+    let import_map = common::sample_input("faker1__import_map.toml");
+    let (first_part, second_part) = (dir.0.join("first.csv"), dir.0.join("second.csv"));
+    common::write_faker1_part(&first_part, 1..=7);
+    common::write_faker1_part(&second_part, 8..=12);
+
+    let wide = common::sample_input("faker1__sample_input.csv");
+    let exchange_export = common::sample_input("faker1__exchange_export.csv");
+    let long = common::sample_input("faker1__long.csv");
+    let cases: Vec<(&str, Vec<&str>, Vec<&Path>)> = vec![
+        ("wide", vec![], vec![&wide]),
+        ("import map", vec!["--import-map", import_map.to_str().unwrap()], vec![&exchange_export]),
+        ("long", vec!["--schema", "long", "--accounts", accounts.to_str().unwrap()], vec![&long]),
+        ("merged", vec![], vec![&first_part, &second_part]),
+    ];
+
+    //  Both runs export to the same directory (emptied in between), as the reports name it, and export every report
+    let out = dir.0.join("out");
+    for (case, args, inputs) in cases {
+
+        let args: Vec<&str> = args.iter().copied()
+            .chain(["--reports", "accounts,lots,transactions,gains,income,expenses,non-sale,synthetic,je"])
+            .collect();
+
+        fs::create_dir(&out).unwrap();
+        let output = common::run_inputs(&out, &args, &inputs);
+        assert_eq!(output.status.code(), Some(0), "{}: {}", case, String::from_utf8_lossy(&output.stdout));
+        let normal = exported(&out);
+        fs::remove_dir_all(&out).unwrap();
+
+        fs::create_dir(&out).unwrap();
+        let low_memory_args: Vec<&str> = args.iter().copied().chain(["--low-memory"]).collect();
+        let output = common::run_inputs(&out, &low_memory_args, &inputs);
+        assert_eq!(output.status.code(), Some(0), "{}: {}", case, String::from_utf8_lossy(&output.stdout));
+        let low_memory = exported(&out);
+        fs::remove_dir_all(&out).unwrap();
+
+        assert_eq!(
+            normal.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            low_memory.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            "{}", case,
+        );
+        for ((name, normal), (_, low_memory)) in normal.iter().zip(&low_memory) {
+            assert!(normal == low_memory, "{}: {} differs with --low-memory", case, name.display());
+        }
+    }
+}
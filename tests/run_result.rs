@@ -0,0 +1,50 @@
+// Copyright (c) 2017-2023, scoobybejesus
+// Redistributions must include the license: https://github.com/scoobybejesus/cryptools/blob/master/LEGAL.txt
+
+//  Runs the program as automation would, checking the exit code and the run_result.json each run leaves behind.
+
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+mod common;
+use common::TempDir;
+
+
+/// Runs the program on the faker1 sample input with `args`, exporting to `dir`.
+fn run(dir: &Path, args: &[&str]) -> Output {
+    common::run(dir, args, &common::sample_input("faker1__sample_input.csv"))
+}
+
+fn status(run_result: &Path) -> String {
+    let run_result: serde_json::Value = serde_json::from_str(&fs::read_to_string(run_result).unwrap()).unwrap();
+    run_result["status"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn failed_export_replaces_the_run_result_of_an_earlier_success() {
+
+    let dir = TempDir::new("failed_export");
+    let run_result = dir.0.join("run_result.json");
+
+    assert_eq!(run(&dir.0, &[]).status.code(), Some(0));
+    assert_eq!(status(&run_result), "success");
+
+    //  The reports are already there, and --overwrite wasn't passed
+    let rerun = run(&dir.0, &[]);
+    assert_eq!(rerun.status.code(), Some(1), "{}", String::from_utf8_lossy(&rerun.stdout));
+    assert_eq!(status(&run_result), "processing_error");
+}
+
+#[test]
+fn dry_run_writes_no_run_result_unless_one_is_named() {
+
+    let dir = TempDir::new("dry_run");
+
+    assert_eq!(run(&dir.0, &["--dry-run"]).status.code(), Some(0));
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0, "A dry run mustn't write any file.");
+
+    let named = dir.0.join("named.json");
+    assert_eq!(run(&dir.0, &["--dry-run", "--run-result", named.to_str().unwrap()]).status.code(), Some(0));
+    assert_eq!(status(&named), "success");
+}